@@ -0,0 +1,81 @@
+//! Per-tick dispatch cost of the scheduler
+//!
+//! Times `Scheduler::tick` for 1, 4, 8 and 16 registered tasks in three
+//! loads: nothing due (every tick idles), one task due each tick, and a
+//! full ready queue (every task released each tick, one dispatched).
+//! Each case runs with the default [`Rms`] policy, which scans the task
+//! table every tick (the O(n) baseline), and with a [`RunQueue`], which
+//! only looks at the table when a release or deadline is due.
+//!
+//! ```text
+//! cargo bench --bench dispatch
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use alice_rtos::task::MAX_TASKS;
+use alice_rtos::{Rms, RunQueue, Scheduler, SchedulingPolicy, Task, TaskPriority};
+
+/// Ticks timed per run
+const TICKS: u32 = 200_000;
+/// Runs per case (the fastest is reported)
+const RUNS: usize = 7;
+/// Tick length (µs)
+const TICK_US: u64 = 10;
+
+fn noop(_: &mut [u8]) {}
+
+/// Scheduler with `n` tasks, `due` of them released every tick
+fn scheduler<P: SchedulingPolicy>(policy: P, n: usize, due: usize) -> Scheduler<P> {
+    let mut sched = Scheduler::with_policy(policy);
+    for i in 0..n {
+        let period = if i < due {
+            TICK_US as u32
+        } else {
+            1_000_000_000
+        };
+        // 優先度はスロット順と逆にして、最後に見つかるタスクを選ばせる
+        let priority = TaskPriority((n - i) as u8);
+        sched.register(Task::new(b"bench", noop, priority, period, 1));
+    }
+    // 初回リリース分を消化してから計測
+    while sched.tick(0).is_some() {}
+    sched
+}
+
+/// Mean cost of one tick (ns), best of `RUNS`
+fn time<P: SchedulingPolicy + Clone>(policy: &P, n: usize, due: usize) -> f64 {
+    (0..RUNS)
+        .map(|_| {
+            let mut sched = scheduler(policy.clone(), n, due);
+            let start = Instant::now();
+            for _ in 0..TICKS {
+                black_box(sched.tick(black_box(TICK_US)));
+            }
+            start.elapsed().as_nanos() as f64 / f64::from(TICKS)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// One table per policy
+fn report<P: SchedulingPolicy + Clone>(name: &str, policy: &P) {
+    println!("{name}");
+    println!(
+        "{:>6} {:>12} {:>12} {:>12}",
+        "tasks", "idle ns", "one ns", "full ns"
+    );
+    for n in [1, 4, 8, MAX_TASKS] {
+        let idle = time(policy, n, 0);
+        let one = time(policy, n, 1);
+        let full = time(policy, n, n);
+        println!("{n:>6} {idle:>12.1} {one:>12.1} {full:>12.1}");
+    }
+}
+
+fn main() {
+    report("Rms (table scan)", &Rms);
+    report("RunQueue", &RunQueue::new());
+}
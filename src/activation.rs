@@ -0,0 +1,308 @@
+//! Per-task activation queues: explicit pending-job counting and catch-up
+//!
+//! When a periodic job is still pending at its next period boundary, the
+//! scheduler merges the new activation into it: the job takes over the
+//! later release and one deadline miss is counted, but only one job runs
+//! for both activations. [`ActivationQueue`] observes the kernel's ticks
+//! (a [`TickObserver`], alone or next to other managers) and makes that
+//! explicit per task:
+//!
+//! - with depth 1 (the default) every merged activation is a lost job,
+//!   counted in [`lost`](ActivationQueue::lost) and reported as
+//!   [`KernelEvent::JobsLost`];
+//! - with depth N > 1 up to N − 1 merged activations are queued
+//!   ([`queued`](ActivationQueue::queued)) and executed back-to-back: after
+//!   each job of the task, the next queued one is released at once, with
+//!   the upcoming period boundary as its deadline. Activations arriving
+//!   while the queue is full are lost.
+//!
+//! Queued jobs still compete at their priority, so catching up never
+//! delays higher-priority work; each of them is late by construction and
+//! already counted in the task's `deadline_misses`. Only tasks released
+//! by their period are queued — interrupt and chain releases that arrive
+//! while a job is pending are merged without a trace. A suspended or
+//! removed task drops its queue. Merges are only seen on observed ticks
+//! ([`ActivationQueue::tick`], [`tick_observed`](SizedKernel::tick_observed)
+//! and friends).
+//!
+//! ```rust
+//! use alice_rtos::activation::ActivationQueue;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! let ctl = kernel.add_task(b"ctl", noop, TaskPriority::LOW, 100, 10).unwrap();
+//! let mut queue = ActivationQueue::new();
+//! assert!(queue.set_depth(&kernel, ctl, 4));
+//!
+//! // A 350 µs stall: the pending job absorbs three more activations
+//! assert_eq!(queue.tick(&mut kernel, 350), Some(ctl));
+//! assert_eq!(queue.lost(ctl), 0);
+//! // The late job runs and frees a slot; all three then run back-to-back
+//! assert_eq!(queue.queued(ctl), 2);
+//! for _ in 0..3 {
+//!     assert_eq!(queue.tick(&mut kernel, 10), Some(ctl));
+//! }
+//! assert_eq!(queue.queued(ctl), 0);
+//! assert_eq!(queue.caught_up(ctl), 3);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::events::{emit, KernelEvent};
+use crate::kernel::{KernelStats, SizedKernel, TickObserver};
+use crate::policy::SchedulingPolicy;
+use crate::task::{TaskMask, TaskState, MAX_TASKS};
+use crate::timer::Tick;
+
+/// Deepest activation queue per task
+pub const MAX_ACTIVATION_DEPTH: u8 = 16;
+
+/// Bounded activation queue of each task
+pub struct ActivationQueue {
+    /// Activations a task may hold, the pending job included (1 = merge)
+    depth: [u8; MAX_TASKS],
+    /// Activations queued behind the pending job
+    queued: [u8; MAX_TASKS],
+    /// Activations lost to a full queue
+    lost: [u32; MAX_TASKS],
+    /// Queued jobs executed
+    caught_up: [u32; MAX_TASKS],
+    /// Tasks whose pending job is a released queued activation
+    catching_up: TaskMask,
+    /// Deadline misses per task before the observed tick
+    misses_before: [u32; MAX_TASKS],
+}
+
+impl Default for ActivationQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActivationQueue {
+    /// Depth 1 for every task: merged activations are counted as lost
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            depth: [1; MAX_TASKS],
+            queued: [0; MAX_TASKS],
+            lost: [0; MAX_TASKS],
+            caught_up: [0; MAX_TASKS],
+            catching_up: 0,
+            misses_before: [0; MAX_TASKS],
+        }
+    }
+
+    /// Let task `idx` hold up to `depth` activations (pending job included)
+    ///
+    /// Returns false for a task that is not released by its period, or a
+    /// depth outside `1..=MAX_ACTIVATION_DEPTH`. Queued activations beyond
+    /// a lowered depth are lost.
+    pub fn set_depth<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &SizedKernel<SCRATCH, P>,
+        idx: usize,
+        depth: u8,
+    ) -> bool {
+        let periodic = kernel
+            .scheduler
+            .get_task(idx)
+            .is_some_and(|t| t.is_active() && t.period_us > 0 && t.time_released());
+        if !periodic || !(1..=MAX_ACTIVATION_DEPTH).contains(&depth) {
+            return false;
+        }
+        self.depth[idx] = depth;
+        let excess = self.queued[idx].saturating_sub(depth - 1);
+        self.queued[idx] -= excess;
+        self.lose(idx, u32::from(excess));
+        true
+    }
+
+    /// Activations task `idx` may hold
+    #[must_use]
+    pub fn depth(&self, idx: usize) -> u8 {
+        self.depth.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Activations of task `idx` queued behind its pending job
+    #[must_use]
+    pub fn queued(&self, idx: usize) -> u8 {
+        self.queued.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Activations of task `idx` lost to a full queue
+    #[must_use]
+    pub fn lost(&self, idx: usize) -> u32 {
+        self.lost.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Queued jobs of task `idx` executed back-to-back
+    #[must_use]
+    pub fn caught_up(&self, idx: usize) -> u32 {
+        self.caught_up.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Tick the kernel, queue merged activations and release queued jobs
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        delta_us: u64,
+    ) -> Option<usize> {
+        kernel.tick_observed(delta_us, self)
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`](crate::kernel::Kernel::run_for))
+    pub fn run_for<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        total_us: u64,
+        tick_us: u64,
+    ) -> KernelStats {
+        kernel.run_for_observed(total_us, tick_us, self)
+    }
+
+    /// Count and report `count` lost activations of `idx`
+    fn lose(&mut self, idx: usize, count: u32) {
+        if count == 0 {
+            return;
+        }
+        self.lost[idx] = self.lost[idx].saturating_add(count);
+        emit(KernelEvent::JobsLost {
+            idx: idx as u8,
+            count: count.min(u32::from(u16::MAX)) as u16,
+        });
+    }
+}
+
+/// Snapshots the deadline misses before each tick and queues the
+/// activations merged by it
+impl TickObserver for ActivationQueue {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        self.misses_before = [0; MAX_TASKS];
+        for (idx, task) in kernel.scheduler.tasks() {
+            self.misses_before[idx] = task.deadline_misses;
+        }
+    }
+
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        executed: Option<usize>,
+    ) {
+        let misses = self.misses_before;
+        for (idx, &before) in misses.iter().enumerate() {
+            let bit: TaskMask = 1 << idx;
+            let Some(task) = kernel.scheduler.get_task_mut(idx) else {
+                continue;
+            };
+            if matches!(task.state, TaskState::Suspended | TaskState::Inactive)
+                || !task.time_released()
+                || task.period_us == 0
+            {
+                self.queued[idx] = 0;
+                self.catching_up &= !bit;
+                continue;
+            }
+            if executed == Some(idx) && self.catching_up & bit != 0 {
+                self.catching_up &= !bit;
+                self.caught_up[idx] = self.caught_up[idx].saturating_add(1);
+            }
+            // 締め切りを 1 回逃す = 次の活性化が保留中のジョブに併合された
+            let merged = task.deadline_misses.wrapping_sub(before);
+            let room = u32::from(self.depth[idx] - 1 - self.queued[idx]);
+            let kept = merged.min(room);
+            self.queued[idx] += kept as u8;
+
+            if executed == Some(idx) && self.queued[idx] > 0 && !task.mode_change_pending() {
+                // 直後の周期境界を締め切りとして即時リリース
+                task.next_activation = task.next_activation.wrapping_sub(task.period_us as Tick);
+                task.state = TaskState::Ready;
+                self.queued[idx] -= 1;
+                self.catching_up |= bit;
+            }
+            self.lose(idx, merged - kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::task::{Task, TaskPriority};
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_depth_one_reports_merged_activations_as_lost() {
+        let mut k = Kernel::testing();
+        let ctl = k
+            .add_task(b"ctl", noop, TaskPriority::LOW, 100, 10)
+            .unwrap();
+        let mut q = ActivationQueue::new();
+        assert_eq!(q.depth(ctl), 1);
+        // 350 µs の停止で 3 回分が併合される
+        assert_eq!(q.tick(&mut k, 350), Some(ctl));
+        assert_eq!((q.queued(ctl), q.lost(ctl)), (0, 3));
+        assert_eq!(k.scheduler.get_task(ctl).unwrap().deadline_misses, 3);
+        // 追いつき実行はしない
+        assert_eq!(q.tick(&mut k, 10), None);
+        assert_eq!(q.caught_up(ctl), 0);
+    }
+
+    #[test]
+    fn test_bounded_queue_catches_up_in_priority_order() {
+        let mut k = Kernel::testing();
+        let hi = k
+            .add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 10)
+            .unwrap();
+        let lo = k.add_task(b"lo", noop, TaskPriority::LOW, 100, 10).unwrap();
+        let mut q = ActivationQueue::new();
+        assert!(q.set_depth(&k, lo, 3));
+        // 最初の tick は hi が取り、lo は 550 µs 待たされる
+        assert_eq!(q.tick(&mut k, 0), Some(hi));
+        assert_eq!(q.tick(&mut k, 550), Some(lo));
+        // 併合 5 回: 2 回分を保持、3 回分を失う (1 回分は即時リリース)
+        assert_eq!((q.queued(lo), q.lost(lo), q.caught_up(lo)), (1, 3, 0));
+        assert_eq!(q.tick(&mut k, 10), Some(lo));
+        assert_eq!(q.tick(&mut k, 10), Some(lo));
+        assert_eq!(q.tick(&mut k, 10), None);
+        assert_eq!(q.caught_up(lo), 2);
+        // 周期は元のまま: 次は t = 600
+        assert_eq!(k.scheduler.get_task(lo).unwrap().next_activation, 600);
+        q.run_for(&mut k, 2_000, 10);
+        assert_eq!(q.lost(lo), 3);
+        assert_eq!(k.scheduler.get_task(lo).unwrap().deadline_misses, 5);
+    }
+
+    #[test]
+    fn test_rejects_aperiodic_and_bad_depth() {
+        let mut k = Kernel::testing();
+        let ctl = k
+            .add_task(b"ctl", noop, TaskPriority::LOW, 100, 10)
+            .unwrap();
+        let isr = Task::new(b"dma", noop, TaskPriority::HIGH, 0, 10).released_by_isr();
+        let dma = k.scheduler.register(isr).unwrap();
+        let mut q = ActivationQueue::new();
+        assert!(!q.set_depth(&k, dma, 2));
+        assert!(!q.set_depth(&k, ctl, 0));
+        assert!(!q.set_depth(&k, ctl, MAX_ACTIVATION_DEPTH + 1));
+        assert!(!q.set_depth(&k, 9, 2));
+
+        // 深さを下げると溢れた分は失われる
+        assert!(q.set_depth(&k, ctl, 5));
+        q.tick(&mut k, 450);
+        assert_eq!(q.queued(ctl), 3);
+        assert!(q.set_depth(&k, ctl, 2));
+        assert_eq!((q.queued(ctl), q.lost(ctl)), (1, 2));
+        // 停止でキューは捨てられる
+        k.scheduler.suspend(ctl);
+        q.tick(&mut k, 10);
+        assert_eq!(q.queued(ctl), 0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Cache-line aligned SPSC ring for DMA producers
+//!
+//! `AlignedSpscRing<T, N, A>` keeps its indices in the same model-checked
+//! protocol as [`SpscRing`](crate::spsc::SpscRing), laid out for DMA and
+//! multicore use:
+//!
+//! - the slot storage starts on an `A` boundary (e.g. a 32-byte cache line)
+//!   and is padded to a whole number of lines, so cache maintenance on the
+//!   buffer never touches neighbouring data;
+//! - the write and read indices each sit on their own line, so a producer
+//!   core and a consumer core do not false-share;
+//! - slots are accessed with volatile reads / writes, so data written by a
+//!   DMA engine behind the compiler's back is always re-read.
+//!
+//! A DMA producer takes the free contiguous region with
+//! [`AlignedProducer::dma_region`], points the transfer at it and publishes
+//! the received items with [`AlignedProducer::commit`]. On cores with a
+//! data cache that is not DMA-coherent (Cortex-M7) the application still
+//! cleans / invalidates the region around the transfer.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+use crate::spsc::{IndexCell, Indices};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// Alignment marker for [`AlignedSpscRing`]
+///
+/// Implemented by zero-sized types whose own alignment is the requested
+/// boundary.
+pub trait Alignment {}
+
+/// 16-byte alignment marker
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(align(16))]
+pub struct Align16;
+
+/// 32-byte alignment marker (Cortex-M7 / A-class cache line)
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(align(32))]
+pub struct Align32;
+
+/// 64-byte alignment marker (x86 / Cortex-A cache line)
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(align(64))]
+pub struct Align64;
+
+/// 128-byte alignment marker (Apple M-series / POWER cache line)
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(align(128))]
+pub struct Align128;
+
+impl Alignment for Align16 {}
+impl Alignment for Align32 {}
+impl Alignment for Align64 {}
+impl Alignment for Align128 {}
+
+/// `value` starting on an `A` boundary, size rounded up to a multiple of `A`
+#[repr(C)]
+struct Padded<A, T> {
+    _align: [A; 0],
+    value: T,
+}
+
+impl<A, T> Padded<A, T> {
+    const fn new(value: T) -> Self {
+        Self { _align: [], value }
+    }
+}
+
+impl<A, I: IndexCell> IndexCell for Padded<A, I> {
+    #[inline]
+    fn load(&self, order: Ordering) -> usize {
+        self.value.load(order)
+    }
+
+    #[inline]
+    fn store(&self, value: usize, order: Ordering) {
+        self.value.store(value, order);
+    }
+}
+
+/// Aligned lock-free SPSC ring
+///
+/// Capacity is `N - 1` (one slot reserved for full/empty distinction),
+/// so `N` must be at least 2. Like [`Channel`](crate::channel::Channel) it is split exactly once into
+/// an [`AlignedProducer`] / [`AlignedConsumer`] pair.
+pub struct AlignedSpscRing<T, const N: usize, A: Alignment = Align32> {
+    /// Slot storage (slots between read and write are initialized)
+    buffer: Padded<A, UnsafeCell<[MaybeUninit<T>; N]>>,
+    /// Write index (producer) and read index (consumer), one line each
+    indices: Indices<N, Padded<A, AtomicUsize>>,
+    /// Halves already handed out?
+    split: AtomicBool,
+}
+
+// SAFETY: the producer only writes slots outside the readable window before
+// publishing them with a Release store, and the consumer only reads slots
+// it observed through an Acquire load. Each half is unique (`split` is
+// one-shot).
+unsafe impl<T: Send, const N: usize, A: Alignment> Sync for AlignedSpscRing<T, N, A> {}
+
+impl<T: Copy, const N: usize, A: Alignment> Default for AlignedSpscRing<T, N, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize, A: Alignment> AlignedSpscRing<T, N, A> {
+    /// One slot stays free, so a usable ring needs two
+    const N_OK: () = assert!(
+        N >= 2,
+        "aligned ring needs at least 2 slots (one stays free)"
+    );
+
+    /// Create an empty ring (usable in `static` initializers)
+    ///
+    /// A ring of fewer than 2 slots fails the build:
+    ///
+    /// ```compile_fail
+    /// use alice_rtos::aligned_ring::AlignedSpscRing;
+    ///
+    /// static NONE: AlignedSpscRing<u8, 1> = AlignedSpscRing::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        let () = Self::N_OK;
+        Self {
+            buffer: Padded::new(UnsafeCell::new([const { MaybeUninit::uninit() }; N])),
+            indices: Indices::with_cells(
+                Padded::new(AtomicUsize::new(0)),
+                Padded::new(AtomicUsize::new(0)),
+            ),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Split into producer and consumer halves
+    ///
+    /// Returns `None` if the ring was already split.
+    pub fn split(&self) -> Option<(AlignedProducer<'_, T, N, A>, AlignedConsumer<'_, T, N, A>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((
+            AlignedProducer { ring: self },
+            AlignedConsumer { ring: self },
+        ))
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Is the ring empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Available capacity
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Start of the slot storage (aligned to `A`)
+    pub const fn as_ptr(&self) -> *const T {
+        self.buffer.value.get().cast()
+    }
+
+    /// Alignment of the slot storage and of each index, in bytes
+    #[must_use]
+    pub const fn alignment() -> usize {
+        core::mem::align_of::<A>()
+    }
+
+    /// Pointer to slot `idx`
+    const fn slot(&self, idx: usize) -> *mut T {
+        // SAFETY: `idx < N` at every call site
+        unsafe { self.buffer.value.get().cast::<T>().add(idx) }
+    }
+}
+
+/// Producer half of an [`AlignedSpscRing`] (CPU or DMA)
+pub struct AlignedProducer<'a, T, const N: usize, A: Alignment> {
+    ring: &'a AlignedSpscRing<T, N, A>,
+}
+
+impl<T: Copy, const N: usize, A: Alignment> AlignedProducer<'_, T, N, A> {
+    /// Push a value
+    ///
+    /// Returns false if the ring is full.
+    pub fn push(&mut self, value: T) -> bool {
+        let (write, free) = self.ring.indices.free();
+        if free == 0 {
+            return false; // Full
+        }
+        // SAFETY: slot `write` is free (the consumer handed it back with
+        // the Release store observed in `free`); only the unique producer
+        // writes it.
+        unsafe { ptr::write_volatile(self.ring.slot(write), value) };
+        self.ring.indices.publish(write, 1);
+        true
+    }
+
+    /// Free contiguous region starting at the write index
+    ///
+    /// Returns the slot pointer and length to hand to a DMA transfer. The
+    /// region stops at the end of the storage; once that part is
+    /// committed the next call returns the wrapped-around remainder.
+    pub fn dma_region(&mut self) -> (*mut T, usize) {
+        let (write, len) = self.region();
+        (self.ring.slot(write), len)
+    }
+
+    /// Write index and length of the free contiguous region
+    fn region(&self) -> (usize, usize) {
+        let (write, free) = self.ring.indices.free();
+        (write, free.min(N - write))
+    }
+
+    /// Publish `count` items written into the [`dma_region`](Self::dma_region)
+    ///
+    /// `count` is clamped to the current region; returns the number of
+    /// items published.
+    ///
+    /// # Safety
+    ///
+    /// The first `count` slots of the region must have been fully written
+    /// (the DMA transfer has completed).
+    pub unsafe fn commit(&mut self, count: usize) -> usize {
+        let (write, len) = self.region();
+        let count = count.min(len);
+        // DMA の書き込みをインデックス公開より前に確定させる
+        fence(Ordering::Release);
+        self.ring.indices.publish(write, count);
+        count
+    }
+
+    /// Is the ring full?
+    pub fn is_full(&self) -> bool {
+        self.ring.len() == N - 1
+    }
+}
+
+/// Consumer half of an [`AlignedSpscRing`]
+pub struct AlignedConsumer<'a, T, const N: usize, A: Alignment> {
+    ring: &'a AlignedSpscRing<T, N, A>,
+}
+
+impl<T: Copy, const N: usize, A: Alignment> AlignedConsumer<'_, T, N, A> {
+    /// Pop the oldest value
+    ///
+    /// Returns `None` if the ring is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let (read, queued) = self.ring.indices.queued();
+        if queued == 0 {
+            return None; // Empty
+        }
+        // SAFETY: slot `read` was written and published by the producer
+        // (Release store observed in `queued`); volatile so DMA data is
+        // re-read.
+        let value = unsafe { ptr::read_volatile(self.ring.slot(read)) };
+        self.ring.indices.consume(read, 1);
+        Some(value)
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Is the ring empty?
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{align_of, offset_of, size_of};
+
+    #[test]
+    fn test_layout_aligned_and_padded() {
+        type Ring = AlignedSpscRing<u16, 8, Align32>;
+        assert_eq!(Ring::alignment(), 32);
+        assert_eq!(align_of::<Ring>(), 32);
+        // 16 B のバッファも 1 ライン分に切り上げ、書き込み・読み出し
+        // インデックスはそれぞれ 1 ライン (フィールド順は問わない)
+        assert_eq!(offset_of!(Ring, buffer) % 32, 0);
+        assert_eq!(offset_of!(Ring, indices) % 32, 0);
+        assert_eq!(size_of::<Indices<8, Padded<Align32, AtomicUsize>>>(), 64);
+        assert_eq!(size_of::<Ring>(), 32 + 64 + 32);
+        assert_eq!(size_of::<Padded<Align64, AtomicUsize>>(), 64);
+
+        let ring = Ring::new();
+        assert_eq!(ring.as_ptr() as usize % 32, 0);
+    }
+
+    #[test]
+    fn test_push_pop_fifo_wraps() {
+        let ring = AlignedSpscRing::<u32, 4>::new();
+        let (mut tx, mut rx) = ring.split().unwrap();
+        assert!(ring.split().is_none());
+        for round in 0..5 {
+            assert!(tx.push(round));
+            assert!(tx.push(round + 100));
+            assert_eq!(rx.pop(), Some(round));
+            assert_eq!(rx.pop(), Some(round + 100));
+        }
+        assert!(rx.pop().is_none());
+        assert!(tx.push(1) && tx.push(2) && tx.push(3));
+        assert!(tx.is_full());
+        assert!(!tx.push(4));
+    }
+
+    #[test]
+    fn test_dma_region_commit() {
+        let ring = AlignedSpscRing::<u8, 8, Align16>::new();
+        let (mut dma, mut rx) = ring.split().unwrap();
+
+        // 空: 末尾手前まで 7 スロット (満杯判定用に 1 つ残す)
+        let (ptr, len) = dma.dma_region();
+        assert_eq!(len, 7);
+        // DMA 転送の代わりに生ポインタで書き込む
+        for i in 0..5 {
+            unsafe { ptr.add(i).write_volatile(10 + i as u8) };
+        }
+        assert_eq!(unsafe { dma.commit(5) }, 5);
+        assert_eq!(rx.len(), 5);
+        for i in 0..5 {
+            assert_eq!(rx.pop(), Some(10 + i));
+        }
+
+        // write=5, read=5: 領域はバッファ末尾で切れる
+        let (ptr, len) = dma.dma_region();
+        assert_eq!(len, 3);
+        for i in 0..3 {
+            unsafe { ptr.add(i).write_volatile(20 + i as u8) };
+        }
+        // 要求が領域を超えたら切り詰め
+        assert_eq!(unsafe { dma.commit(10) }, 3);
+        // 折り返し後の残り (read の手前まで)
+        let (ptr, len) = dma.dma_region();
+        assert_eq!(ptr.cast_const(), ring.as_ptr());
+        assert_eq!(len, 4);
+        assert_eq!(rx.pop(), Some(20));
+        assert_eq!(rx.pop(), Some(21));
+        assert_eq!(rx.pop(), Some(22));
+        assert!(rx.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+//! Atomic types for the lock-free primitives
+//!
+//! Re-exports `core::sync::atomic` by default. With the `critical-section`
+//! feature the same names come from `portable-atomic`, which emulates
+//! read-modify-write operations (`swap`, `fetch_or`, compare-exchange)
+//! inside a critical section on cores without native atomic RMW
+//! instructions (AVR, MSP430, Cortex-M0 / thumbv6m). The application then
+//! links a `critical-section` implementation for its target.
+//!
+//! Under `--cfg loom` the SPSC ring indices ([`IndexAtomic`]) come from
+//! `loom` instead, so the model tests in `spsc` explore every
+//! interleaving and reordering the memory model allows:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+#[cfg(not(feature = "critical-section"))]
+pub(crate) use core::sync::atomic::{
+    fence, AtomicBool, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering,
+};
+
+#[cfg(all(feature = "mpu", not(feature = "critical-section")))]
+pub(crate) use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "critical-section")]
+pub(crate) use portable_atomic::{
+    fence, AtomicBool, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering,
+};
+
+#[cfg(all(feature = "mpu", feature = "critical-section"))]
+pub(crate) use portable_atomic::AtomicPtr;
+
+/// Atomic behind the SPSC ring indices
+#[cfg(not(any(loom, feature = "critical-section")))]
+pub(crate) type IndexAtomic = AtomicUsize;
+
+/// Atomic behind the SPSC ring indices (model-checked by `loom`)
+#[cfg(all(loom, not(feature = "critical-section")))]
+pub(crate) type IndexAtomic = loom::sync::atomic::AtomicUsize;
@@ -0,0 +1,222 @@
+//! Block-based DSP helpers: block sizes between producer / consumer rates
+//!
+//! When a 44.1 kHz producer feeds a 1 kHz consumer, each consumer run
+//! gets 44.1 samples on average: 44 most of the time and 45 every tenth
+//! run. [`BlockSizer`] spreads the fractional part Bresenham-style, so
+//! the block sizes never differ by more than one and the delivered
+//! stream never drifts more than one sample from the exact rate.
+//!
+//! [`BlockSizer::configure`] sets the watermarks of the [`SpscRing`]
+//! between the two tasks so its high mark fires as soon as the largest
+//! block is queued, and [`BlockSizer::take_block`] only ever pops whole
+//! blocks — a consumer released on [`Watermark::High`](crate::spsc::Watermark::High)
+//! always finds a full block.
+//!
+//! ```rust
+//! use alice_rtos::block::BlockSizer;
+//!
+//! let mut blocks = BlockSizer::from_rates(44_100, 1_000);
+//! assert_eq!((blocks.min_block(), blocks.max_block()), (44, 45));
+//! let total: usize = (0..10).map(|_| blocks.next_block()).sum();
+//! assert_eq!(total, 441);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::spsc::SpscRing;
+
+/// Greatest common divisor
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+/// Bresenham block-size sequence for a producer / consumer rate ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSizer {
+    /// Producer items per consumer run = `num / den` (reduced)
+    num: u32,
+    /// Denominator of the ratio
+    den: u32,
+    /// Fractional items carried into the next block (`< den`)
+    acc: u32,
+}
+
+impl BlockSizer {
+    /// Sizer for a producer at `producer_hz` feeding a consumer at `consumer_hz`
+    ///
+    /// # Panics
+    ///
+    /// On a zero rate (a compile error in a `const` item).
+    #[must_use]
+    pub const fn from_rates(producer_hz: u32, consumer_hz: u32) -> Self {
+        assert!(producer_hz > 0 && consumer_hz > 0, "rates must be non-zero");
+        let g = gcd(producer_hz, consumer_hz);
+        Self {
+            num: producer_hz / g,
+            den: consumer_hz / g,
+            acc: 0,
+        }
+    }
+
+    /// Sizer for tasks with periods `producer_us` and `consumer_us`
+    ///
+    /// # Panics
+    ///
+    /// On a zero period (a compile error in a `const` item).
+    #[must_use]
+    pub const fn from_periods(producer_us: u32, consumer_us: u32) -> Self {
+        // 1 回の消費 = consumer_us / producer_us 個
+        Self::from_rates(consumer_us, producer_us)
+    }
+
+    /// Smallest block size
+    #[must_use]
+    pub const fn min_block(&self) -> usize {
+        (self.num / self.den) as usize
+    }
+
+    /// Largest block size (`min_block + 1` unless the ratio is whole)
+    #[must_use]
+    pub const fn max_block(&self) -> usize {
+        self.num.div_ceil(self.den) as usize
+    }
+
+    /// Consumer runs after which the block pattern repeats exactly
+    #[must_use]
+    pub const fn cycle_len(&self) -> u32 {
+        self.den
+    }
+
+    /// Size of the next block, without consuming it
+    #[must_use]
+    pub const fn peek_block(&self) -> usize {
+        ((self.acc as u64 + self.num as u64) / self.den as u64) as usize
+    }
+
+    /// Size of the next block, advancing the sequence
+    pub fn next_block(&mut self) -> usize {
+        let total = self.acc as u64 + self.num as u64;
+        self.acc = (total % self.den as u64) as u32;
+        (total / self.den as u64) as usize
+    }
+
+    /// How far the delivered stream lags the exact rate, in ppm of one item
+    ///
+    /// Always below one item; zero at the end of every cycle.
+    #[must_use]
+    pub const fn drift_ppm(&self) -> u32 {
+        (self.acc as u64 * 1_000_000 / self.den as u64) as u32
+    }
+
+    /// Restart the sequence (e.g. after the stream was resynchronized)
+    pub const fn reset(&mut self) {
+        self.acc = 0;
+    }
+
+    /// Set `ring`'s watermarks so the high mark means "a full block is queued"
+    ///
+    /// High is [`max_block`](Self::max_block) and low one below it, so the
+    /// notification re-arms as soon as a block has been taken. Returns
+    /// false if the ring cannot hold a largest block.
+    pub fn configure<const N: usize>(&self, ring: &mut SpscRing<N>) -> bool {
+        let high = self.max_block();
+        high > 0 && ring.set_watermarks(high - 1, high)
+    }
+
+    /// Pop the next block from `ring` into `out`
+    ///
+    /// Only whole blocks are taken: returns `None` (and leaves the ring and
+    /// the sequence untouched) while fewer items than the next block are
+    /// queued or `out` is too short.
+    pub fn take_block<const N: usize>(
+        &mut self,
+        ring: &mut SpscRing<N>,
+        out: &mut [u32],
+    ) -> Option<usize> {
+        let n = self.peek_block();
+        if ring.len() < n || out.len() < n {
+            return None;
+        }
+        ring.pop_slice(&mut out[..n]);
+        self.next_block();
+        Some(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spsc::Watermark;
+
+    #[test]
+    fn test_audio_to_control_rate() {
+        let mut s = BlockSizer::from_rates(44_100, 1_000);
+        assert_eq!((s.min_block(), s.max_block()), (44, 45));
+        assert_eq!(s.cycle_len(), 10);
+        // 9 回は 44、10 回目に 45 (誤差は 1 サンプル未満)
+        for _ in 0..9 {
+            assert_eq!(s.next_block(), 44);
+            assert!(s.drift_ppm() < 1_000_000);
+        }
+        assert_eq!(s.drift_ppm(), 900_000);
+        assert_eq!(s.peek_block(), 45);
+        assert_eq!(s.next_block(), 45);
+        assert_eq!(s.drift_ppm(), 0);
+    }
+
+    #[test]
+    fn test_from_periods_sums_exactly() {
+        // 23 µs 周期 → 1 ms 周期: 1 周期あたり 43.48 個
+        let mut s = BlockSizer::from_periods(23, 1_000);
+        assert_eq!((s.min_block(), s.max_block()), (43, 44));
+        let total: usize = (0..s.cycle_len()).map(|_| s.next_block()).sum();
+        assert_eq!(total, 1_000);
+        assert_eq!(s.drift_ppm(), 0);
+
+        // 整数比ならブロック長は一定
+        let whole = BlockSizer::from_periods(250, 1_000);
+        assert_eq!((whole.min_block(), whole.max_block()), (4, 4));
+        // 消費側が速い場合は 0 または 1 個
+        let mut up = BlockSizer::from_periods(1_000, 250);
+        let blocks: [usize; 4] = core::array::from_fn(|_| up.next_block());
+        assert_eq!(blocks, [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_take_only_full_blocks() {
+        let mut ring = SpscRing::<64>::new();
+        let mut s = BlockSizer::from_rates(44_100, 1_000);
+        assert!(s.configure(&mut ring));
+        let mut block = [0u32; 45];
+
+        for i in 0..43 {
+            ring.push(i);
+        }
+        assert_eq!(s.take_block(&mut ring, &mut block), None);
+        assert_eq!(ring.len(), 43);
+        ring.push(43);
+        assert_eq!(ring.take_watermark(), None);
+        ring.push(44);
+        // 最大ブロック分たまったら High
+        assert_eq!(ring.take_watermark(), Some(Watermark::High));
+        assert_eq!(s.take_block(&mut ring, &mut block), Some(44));
+        assert_eq!(block[43], 43);
+        assert_eq!(ring.take_watermark(), Some(Watermark::Low));
+        // 出力バッファが短ければ取らない
+        assert_eq!(s.take_block(&mut ring, &mut [0u32; 8]), None);
+        assert_eq!(ring.len(), 1);
+    }
+
+    #[test]
+    fn test_configure_rejects_small_ring() {
+        let mut ring = SpscRing::<32>::new();
+        assert!(!BlockSizer::from_rates(44_100, 1_000).configure(&mut ring));
+        // 0 か 1 個のブロックは 1 個で High
+        assert!(BlockSizer::from_periods(1_000, 250).configure(&mut ring));
+    }
+}
@@ -0,0 +1,374 @@
+//! Broadcast channel: one producer, many consumers
+//!
+//! A static ring in which every consumer keeps its own read cursor, so a
+//! single producer (e.g. an IMU task) can feed several tasks at their own
+//! pace. The producer never blocks: once the ring is full it overwrites
+//! the oldest item. A consumer that falls more than `N` items behind loses
+//! the overwritten items, is told how many through [`RecvError::Lagged`]
+//! and resumes at the oldest item still available.
+//!
+//! Each slot carries a sequence stamp written around the data (seqlock
+//! style), so a consumer racing with an overwrite detects it instead of
+//! returning a torn value. `N` must be a power of two of at least 2: the
+//! slot index then follows the wrapping `u32` sequence without a jump, and
+//! the stamp only has to tell apart the laps of one slot, which fits in a
+//! `u32` for the whole sequence range.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{fence, AtomicBool, AtomicU32, Ordering};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+/// Why [`BroadcastReceiver::recv`] returned no value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// No new item
+    Empty,
+    /// The consumer fell behind and this many items were overwritten
+    Lagged(u32),
+}
+
+/// Single-producer multi-consumer broadcast ring
+pub struct Broadcast<T, const N: usize> {
+    /// Item storage
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Per-slot stamp: `2·lap + 2` once item `seq` (`lap = seq / N`) is
+    /// complete, one less (odd) while it is being written
+    stamps: [AtomicU32; N],
+    /// Items published so far (wrapping)
+    head: AtomicU32,
+    /// Sender handed out?
+    sender_taken: AtomicBool,
+}
+
+// SAFETY: only the unique sender writes slots; receivers copy a slot with
+// a volatile read and discard the copy unless its stamp was complete and
+// unchanged around the read. `T: Copy` means discarded copies need no drop.
+unsafe impl<T: Copy + Send, const N: usize> Sync for Broadcast<T, N> {}
+
+impl<T: Copy, const N: usize> Default for Broadcast<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> Broadcast<T, N> {
+    /// The slot index and stamp follow the wrapping sequence
+    const N_OK: () = assert!(
+        N >= 2 && N.is_power_of_two(),
+        "broadcast ring needs a power-of-two number of slots, at least 2"
+    );
+
+    /// Empty ring (usable in `static` initializers)
+    ///
+    /// A ring whose `N` is not a power of two of at least 2 fails the
+    /// build:
+    ///
+    /// ```compile_fail
+    /// use alice_rtos::broadcast::Broadcast;
+    ///
+    /// static ODD: Broadcast<u32, 3> = Broadcast::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        let () = Self::N_OK;
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            stamps: [const { AtomicU32::new(0) }; N],
+            head: AtomicU32::new(0),
+            sender_taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Producer handle
+    ///
+    /// Returns `None` if the sender was already taken.
+    pub fn sender(&self) -> Option<BroadcastSender<'_, T, N>> {
+        if self.sender_taken.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(BroadcastSender { ring: self })
+    }
+
+    /// New consumer that sees items published from now on
+    pub fn subscribe(&self) -> BroadcastReceiver<'_, T, N> {
+        BroadcastReceiver {
+            ring: self,
+            cursor: self.head.load(Ordering::Acquire),
+            lost: 0,
+        }
+    }
+
+    /// Items published so far (wrapping)
+    pub fn published(&self) -> u32 {
+        self.head.load(Ordering::Acquire)
+    }
+
+    /// Items kept for slow consumers
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Slot of item `seq`
+    const fn slot(seq: u32) -> usize {
+        seq as usize & (N - 1)
+    }
+
+    /// Stamp of slot [`slot`](Self::slot)`(seq)` once item `seq` is complete
+    ///
+    /// Distinct for every lap of the slot over the `u32` sequence range
+    /// (`2^32 / N` laps, `N` ≥ 2), so a stale slot never passes for a
+    /// newer item.
+    const fn stamp(seq: u32) -> u32 {
+        (seq >> N.trailing_zeros()).wrapping_mul(2).wrapping_add(2)
+    }
+
+    fn publish(&self, value: T) {
+        let seq = self.head.load(Ordering::Relaxed);
+        let idx = Self::slot(seq);
+        let stamp = Self::stamp(seq);
+        self.stamps[idx].store(stamp.wrapping_sub(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        // SAFETY: unique sender; readers of this slot see the odd stamp
+        // and discard their copy.
+        unsafe {
+            core::ptr::write_volatile(self.slots[idx].get(), MaybeUninit::new(value));
+        }
+        self.stamps[idx].store(stamp, Ordering::Release);
+        self.head.store(seq.wrapping_add(1), Ordering::Release);
+    }
+}
+
+/// Producer half of a [`Broadcast`]
+pub struct BroadcastSender<'a, T, const N: usize> {
+    ring: &'a Broadcast<T, N>,
+}
+
+impl<T: Copy, const N: usize> BroadcastSender<'_, T, N> {
+    /// Publish to every consumer (never blocks; overwrites the oldest item)
+    pub fn send(&mut self, value: T) {
+        self.ring.publish(value);
+    }
+}
+
+/// Consumer of a [`Broadcast`] with its own read cursor
+pub struct BroadcastReceiver<'a, T, const N: usize> {
+    ring: &'a Broadcast<T, N>,
+    /// Sequence of the next item to read
+    cursor: u32,
+    /// Items lost to overruns so far
+    lost: u32,
+}
+
+impl<T: Copy, const N: usize> BroadcastReceiver<'_, T, N> {
+    /// Next item for this consumer
+    ///
+    /// On [`RecvError::Lagged`] the cursor has already moved to the oldest
+    /// item still available; call again to continue from there.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        let head = self.ring.head.load(Ordering::Acquire);
+        if head == self.cursor {
+            return Err(RecvError::Empty);
+        }
+        if head.wrapping_sub(self.cursor) > N as u32 {
+            return Err(self.skip_to_oldest(head));
+        }
+        let idx = Broadcast::<T, N>::slot(self.cursor);
+        let expected = Broadcast::<T, N>::stamp(self.cursor);
+        if self.ring.stamps[idx].load(Ordering::Acquire) != expected {
+            return Err(self.skip_to_oldest(self.ring.head.load(Ordering::Acquire)));
+        }
+        // SAFETY: the stamp shows the slot holds item `cursor`; a copy torn
+        // by a concurrent overwrite is rejected by the re-check below.
+        let value = unsafe { core::ptr::read_volatile(self.ring.slots[idx].get()) };
+        fence(Ordering::Acquire);
+        if self.ring.stamps[idx].load(Ordering::Relaxed) != expected {
+            return Err(self.skip_to_oldest(self.ring.head.load(Ordering::Acquire)));
+        }
+        self.cursor = self.cursor.wrapping_add(1);
+        // SAFETY: see above.
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Items published but not yet read by this consumer
+    pub fn lag(&self) -> u32 {
+        self.ring
+            .head
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.cursor)
+    }
+
+    /// Items this consumer lost because it fell behind
+    pub const fn overruns(&self) -> u32 {
+        self.lost
+    }
+
+    /// Move past overwritten items; `head` is the producer position seen
+    fn skip_to_oldest(&mut self, head: u32) -> RecvError {
+        // Leave one slot of margin: the producer may be overwriting the
+        // oldest one right now
+        let oldest = head.wrapping_sub(N as u32).wrapping_add(1);
+        let skipped = oldest.wrapping_sub(self.cursor);
+        self.cursor = oldest;
+        self.lost = self.lost.wrapping_add(skipped);
+        RecvError::Lagged(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_consumer_sees_every_item() {
+        let ring = Broadcast::<u32, 4>::new();
+        let mut tx = ring.sender().unwrap();
+        let mut motion = ring.subscribe();
+        let mut logger = ring.subscribe();
+        tx.send(1);
+        tx.send(2);
+        assert_eq!(motion.recv(), Ok(1));
+        assert_eq!(motion.recv(), Ok(2));
+        assert_eq!(motion.recv(), Err(RecvError::Empty));
+        assert_eq!(logger.lag(), 2);
+        assert_eq!(logger.recv(), Ok(1));
+        assert_eq!(logger.recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_sender_taken_once() {
+        let ring = Broadcast::<u8, 2>::new();
+        assert!(ring.sender().is_some());
+        assert!(ring.sender().is_none());
+    }
+
+    #[test]
+    fn test_late_subscriber_starts_at_head() {
+        let ring = Broadcast::<u8, 4>::new();
+        let mut tx = ring.sender().unwrap();
+        tx.send(1);
+        let mut rx = ring.subscribe();
+        assert_eq!(rx.recv(), Err(RecvError::Empty));
+        tx.send(2);
+        assert_eq!(rx.recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_slow_consumer_lagged() {
+        let ring = Broadcast::<u32, 4>::new();
+        let mut tx = ring.sender().unwrap();
+        let mut fast = ring.subscribe();
+        let mut slow = ring.subscribe();
+        for i in 0..10 {
+            tx.send(i);
+            assert_eq!(fast.recv(), Ok(i));
+        }
+        // 10 件中 4 件しか保持されない → 遅い側は 7 件を失い最古の 7 から再開
+        assert_eq!(slow.recv(), Err(RecvError::Lagged(7)));
+        assert_eq!(slow.overruns(), 7);
+        assert_eq!(slow.recv(), Ok(7));
+        assert_eq!(slow.recv(), Ok(8));
+        assert_eq!(slow.recv(), Ok(9));
+        assert_eq!(slow.recv(), Err(RecvError::Empty));
+        assert_eq!(fast.overruns(), 0);
+    }
+
+    #[test]
+    fn test_exactly_full_not_lagged() {
+        let ring = Broadcast::<u32, 4>::new();
+        let mut tx = ring.sender().unwrap();
+        let mut rx = ring.subscribe();
+        for i in 0..4 {
+            tx.send(i);
+        }
+        assert_eq!(rx.lag(), 4);
+        for i in 0..4 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+    }
+
+    #[test]
+    fn test_static_broadcast() {
+        static IMU: Broadcast<[i16; 3], 8> = Broadcast::new();
+        let mut rx = IMU.subscribe();
+        IMU.sender().unwrap().send([1, 2, 3]);
+        assert_eq!(rx.recv(), Ok([1, 2, 3]));
+        assert_eq!(IMU.published(), 1);
+        assert_eq!(IMU.capacity(), 8);
+    }
+
+    #[test]
+    fn test_sequence_wrap_keeps_order() {
+        // u32::MAX 付近から始めて、周回をまたいでも順番どおり・欠落なし
+        let ring = Broadcast::<u32, 4>::new();
+        ring.head.store(u32::MAX - 5, Ordering::Relaxed);
+        let mut tx = ring.sender().unwrap();
+        let mut fast = ring.subscribe();
+        let mut slow = ring.subscribe();
+        for i in 0..12 {
+            tx.send(i);
+            assert_eq!(fast.recv(), Ok(i));
+        }
+        assert_eq!(fast.recv(), Err(RecvError::Empty));
+        assert_eq!(ring.published(), 6);
+        // 遅い側は周回後も最古の 3 件から再開できる
+        assert_eq!(slow.recv(), Err(RecvError::Lagged(9)));
+        for i in 9..12 {
+            assert_eq!(slow.recv(), Ok(i));
+        }
+        assert_eq!(slow.recv(), Err(RecvError::Empty));
+    }
+
+    #[test]
+    fn test_stamps_distinct_across_sequence_range() {
+        // 2^31 件離れた同じスロットの項目も印が異なる
+        for seq in [0, 1, 7, u32::MAX / 2, u32::MAX - 1, u32::MAX] {
+            let far = seq.wrapping_add(1 << 31);
+            assert_eq!(Broadcast::<u8, 2>::slot(seq), Broadcast::<u8, 2>::slot(far));
+            assert_ne!(
+                Broadcast::<u8, 2>::stamp(seq),
+                Broadcast::<u8, 2>::stamp(far)
+            );
+            assert_ne!(
+                Broadcast::<u8, 8>::stamp(seq),
+                Broadcast::<u8, 8>::stamp(far)
+            );
+        }
+        // 書き込み中 (奇数) と完了 (偶数) は区別できる
+        assert_eq!(Broadcast::<u8, 2>::stamp(u32::MAX) % 2, 0);
+    }
+
+    #[test]
+    fn test_concurrent_consumers_ordered_and_untorn() {
+        extern crate std;
+        use std::thread;
+
+        let ring = Broadcast::<[u64; 4], 16>::new();
+        let mut tx = ring.sender().unwrap();
+        let receivers = [ring.subscribe(), ring.subscribe()];
+        thread::scope(|s| {
+            for mut rx in receivers {
+                s.spawn(move || {
+                    let mut last = None;
+                    let mut seen = 0u64;
+                    while seen + u64::from(rx.overruns()) < 50_000 {
+                        match rx.recv() {
+                            Ok(v) => {
+                                assert!(v.iter().all(|&x| x == v[0]));
+                                assert!(last.is_none_or(|l| v[0] > l));
+                                last = Some(v[0]);
+                                seen += 1;
+                            }
+                            Err(RecvError::Empty) => core::hint::spin_loop(),
+                            Err(RecvError::Lagged(_)) => {}
+                        }
+                    }
+                });
+            }
+            for i in 0..50_000u64 {
+                tx.send([i; 4]);
+            }
+        });
+    }
+}
@@ -0,0 +1,239 @@
+//! Per-task execution budgets (CBS-style CPU reservation)
+//!
+//! Each budgeted task may consume at most `budget_us` of CPU time per
+//! replenishment period. A task that exhausts its budget is *throttled*:
+//! it is still released, but the kernel skips it in dispatch until the
+//! next replenishment, so a runaway task cannot starve lower-priority
+//! work in cooperative mode.
+//!
+//! CPU time is measured with an optional µs clock around the task body;
+//! without a clock each job is charged its declared WCET, which turns the
+//! budget into a cap on jobs per period. The table lives outside the
+//! kernel and is attached with
+//! [`Kernel::attach_budgets`](crate::kernel::Kernel::attach_budgets).
+//!
+//! Author: Moroya Sakamoto
+
+use crate::profiler::ProfileClock;
+use crate::task::{TaskMask, MAX_TASKS};
+
+/// Per-task execution budgets and throttling state
+pub struct BudgetTable {
+    /// CPU-time clock (None = charge WCET per job)
+    clock: Option<ProfileClock>,
+    /// Budget per replenishment period (µs, 0 = unlimited)
+    budget_us: [u32; MAX_TASKS],
+    /// Replenishment period (µs)
+    period_us: [u32; MAX_TASKS],
+    /// Budget left in the current period (µs)
+    remaining_us: [u32; MAX_TASKS],
+    /// Next replenishment instant (µs)
+    next_replenish: [u64; MAX_TASKS],
+    /// Times each task ran out of budget
+    throttles: [u32; MAX_TASKS],
+    /// Tasks currently out of budget
+    throttled: TaskMask,
+}
+
+impl Default for BudgetTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BudgetTable {
+    /// Empty table charging each job its WCET (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            clock: None,
+            budget_us: [0; MAX_TASKS],
+            period_us: [0; MAX_TASKS],
+            remaining_us: [0; MAX_TASKS],
+            next_replenish: [0; MAX_TASKS],
+            throttles: [0; MAX_TASKS],
+            throttled: 0,
+        }
+    }
+
+    /// Empty table charging measured CPU time read from `clock`
+    #[must_use]
+    pub const fn with_clock(clock: ProfileClock) -> Self {
+        let mut table = Self::new();
+        table.clock = Some(clock);
+        table
+    }
+
+    /// Read the CPU-time clock (None = WCET charging)
+    #[inline(always)]
+    pub(crate) fn now(&self) -> Option<u64> {
+        self.clock.map(|clock| clock())
+    }
+
+    /// Reserve `budget_us` of CPU per `period_us` for task `idx`
+    ///
+    /// The first period starts at `now_us` with a full budget. A zero
+    /// budget removes the reservation. Returns false for an invalid index
+    /// or a zero period.
+    pub fn set_budget(&mut self, idx: usize, budget_us: u32, period_us: u32, now_us: u64) -> bool {
+        if idx >= MAX_TASKS || (budget_us > 0 && period_us == 0) {
+            return false;
+        }
+        self.budget_us[idx] = budget_us;
+        self.period_us[idx] = period_us;
+        self.remaining_us[idx] = budget_us;
+        self.next_replenish[idx] = now_us + period_us as u64;
+        self.throttled &= !(1 << idx);
+        true
+    }
+
+    /// Budget left for `idx` in the current period (None = unbudgeted)
+    #[must_use]
+    pub fn remaining_us(&self, idx: usize) -> Option<u32> {
+        (self.budget_us.get(idx).copied().unwrap_or(0) > 0).then(|| self.remaining_us[idx])
+    }
+
+    /// Tasks currently throttled
+    #[must_use]
+    pub const fn throttled_mask(&self) -> TaskMask {
+        self.throttled
+    }
+
+    /// Is `idx` out of budget?
+    #[must_use]
+    pub const fn is_throttled(&self, idx: usize) -> bool {
+        idx < MAX_TASKS && self.throttled & (1 << idx) != 0
+    }
+
+    /// Times `idx` exhausted its budget
+    #[must_use]
+    pub fn throttle_count(&self, idx: usize) -> u32 {
+        self.throttles.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Throttling events across all tasks
+    #[must_use]
+    pub fn total_throttles(&self) -> u64 {
+        self.throttles.iter().map(|&n| n as u64).sum()
+    }
+
+    /// Refill every budget whose period ended at or before `now_us`
+    ///
+    /// Returns the tasks still throttled afterwards.
+    pub fn replenish(&mut self, now_us: u64) -> TaskMask {
+        for idx in 0..MAX_TASKS {
+            let period = self.period_us[idx] as u64;
+            if self.budget_us[idx] == 0 || now_us < self.next_replenish[idx] {
+                continue;
+            }
+            // Skip whole periods that passed without a tick
+            let missed = (now_us - self.next_replenish[idx]) / period;
+            self.next_replenish[idx] += (missed + 1) * period;
+            self.remaining_us[idx] = self.budget_us[idx];
+            self.throttled &= !(1 << idx);
+        }
+        self.throttled
+    }
+
+    /// Charge `used_us` of CPU to `idx`, throttling it when exhausted
+    ///
+    /// Returns true if this charge throttled the task.
+    pub fn charge(&mut self, idx: usize, used_us: u32) -> bool {
+        if idx >= MAX_TASKS || self.budget_us[idx] == 0 {
+            return false;
+        }
+        self.remaining_us[idx] = self.remaining_us[idx].saturating_sub(used_us);
+        let bit: TaskMask = 1 << idx;
+        if self.remaining_us[idx] == 0 && self.throttled & bit == 0 {
+            self.throttled |= bit;
+            self.throttles[idx] += 1;
+            return true;
+        }
+        false
+    }
+
+    /// Earliest replenishment of a throttled task
+    #[must_use]
+    pub fn next_replenish_us(&self) -> Option<u64> {
+        (0..MAX_TASKS)
+            .filter(|&i| self.throttled & (1 << i) != 0)
+            .map(|i| self.next_replenish[i])
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charge_until_throttled() {
+        let mut b = BudgetTable::new();
+        assert!(b.set_budget(1, 100, 1_000, 0));
+        assert!(!b.charge(1, 60));
+        assert_eq!(b.remaining_us(1), Some(40));
+        assert!(b.charge(1, 60));
+        assert!(b.is_throttled(1));
+        assert_eq!(b.throttled_mask(), 0b10);
+        // 既にスロットル中 → 再カウントしない
+        assert!(!b.charge(1, 10));
+        assert_eq!(b.throttle_count(1), 1);
+    }
+
+    #[test]
+    fn test_replenish_at_period_boundary() {
+        let mut b = BudgetTable::new();
+        b.set_budget(0, 50, 1_000, 0);
+        b.charge(0, 50);
+        assert_eq!(b.next_replenish_us(), Some(1_000));
+        assert_eq!(b.replenish(999), 0b1);
+        assert_eq!(b.replenish(1_000), 0);
+        assert_eq!(b.remaining_us(0), Some(50));
+        assert_eq!(b.next_replenish_us(), None);
+    }
+
+    #[test]
+    fn test_replenish_skips_missed_periods() {
+        let mut b = BudgetTable::new();
+        b.set_budget(0, 50, 100, 0);
+        b.charge(0, 50);
+        b.replenish(1_050);
+        b.charge(0, 50);
+        // 次の補充は 1_100 (周期境界に揃う)
+        assert_eq!(b.next_replenish_us(), Some(1_100));
+    }
+
+    #[test]
+    fn test_unbudgeted_never_throttled() {
+        let mut b = BudgetTable::new();
+        assert!(!b.charge(3, u32::MAX));
+        assert!(!b.is_throttled(3));
+        assert_eq!(b.remaining_us(3), None);
+        assert!(!b.charge(MAX_TASKS, 1));
+        assert!(!b.is_throttled(MAX_TASKS));
+    }
+
+    #[test]
+    fn test_set_budget_validation_and_clear() {
+        let mut b = BudgetTable::new();
+        assert!(!b.set_budget(0, 10, 0, 0));
+        assert!(!b.set_budget(MAX_TASKS, 10, 100, 0));
+        b.set_budget(0, 10, 100, 0);
+        b.charge(0, 10);
+        assert!(b.set_budget(0, 0, 0, 0));
+        assert!(!b.is_throttled(0));
+        assert_eq!(b.remaining_us(0), None);
+    }
+
+    #[test]
+    fn test_total_throttles() {
+        let mut b = BudgetTable::new();
+        b.set_budget(0, 1, 10, 0);
+        b.set_budget(1, 1, 10, 0);
+        b.charge(0, 1);
+        b.charge(1, 1);
+        b.replenish(10);
+        b.charge(0, 1);
+        assert_eq!(b.total_throttles(), 3);
+    }
+}
@@ -0,0 +1,352 @@
+//! WCET calibration: measure task bodies and back-fill `wcet_us`
+//!
+//! When the worst-case execution times are not known up front, register
+//! the tasks with a rough guess and let [`Kernel::calibrate_wcet`] measure
+//! them before the kernel starts. Each active task body is run
+//! [`runs`](WcetCalibrator::runs) times on its own scratch, the largest
+//! execution time is padded by a safety margin (20 % by default) and
+//! written into the task table.
+//!
+//! Time comes from either a µs [`ProfileClock`] (a host `Instant`, a
+//! hardware timer) or the cycle counter of [`profiler::cycles`] — DWT
+//! `CYCCNT` on Cortex-M with the `cortex-m` feature — scaled by the core
+//! clock. Results are rounded up to whole microseconds.
+//!
+//! Calibration calls the real task bodies, so their side effects (channel
+//! sends, GPIO) happen `runs` times per task. Measured maxima are only as
+//! good as the inputs seen during the runs; keep the margin for the paths
+//! the calibration did not exercise.
+//!
+//! ```rust
+//! use alice_rtos::calibrate::WcetCalibrator;
+//! use alice_rtos::profiler::advance_soft_cycles;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! // 64 MHz core: 6_400 cycles = 100 µs
+//! fn filter(_: &mut [u8]) {
+//!     advance_soft_cycles(6_400);
+//! }
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"filter", filter, TaskPriority::HIGH, 1_000, 1).unwrap();
+//! let report = kernel
+//!     .calibrate_wcet(&WcetCalibrator::cycles(64_000_000).runs(8).margin_percent(25))
+//!     .unwrap();
+//! let wcet = kernel.scheduler.get_task(0).unwrap().wcet_us;
+//! assert!(wcet >= 125);
+//! assert_eq!(report.wcet_after_us(0), wcet);
+//! assert!(report.utilization_after_ppm() > report.utilization_before_ppm());
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt;
+
+#[cfg(doc)]
+use crate::kernel::Kernel;
+use crate::profiler::{self, ProfileClock};
+use crate::task::{TaskMask, MAX_TASKS};
+use crate::text::{Percent, ShortName};
+
+/// Default number of runs per task
+pub const DEFAULT_RUNS: u16 = 16;
+
+/// Default safety margin on the measured maximum (percent)
+pub const DEFAULT_MARGIN_PERCENT: u16 = 20;
+
+/// Time source of a calibration run
+#[derive(Debug, Clone, Copy)]
+enum Clock {
+    /// Free-running µs clock
+    Micros(ProfileClock),
+    /// [`profiler::cycles`] at this core frequency (Hz)
+    Cycles(u32),
+}
+
+/// Calibration settings: clock, runs per task and safety margin
+#[derive(Debug, Clone, Copy)]
+pub struct WcetCalibrator {
+    clock: Clock,
+    runs: u16,
+    margin_percent: u16,
+}
+
+impl WcetCalibrator {
+    /// Time task bodies with a µs clock
+    #[must_use]
+    pub const fn new(clock: ProfileClock) -> Self {
+        Self {
+            clock: Clock::Micros(clock),
+            runs: DEFAULT_RUNS,
+            margin_percent: DEFAULT_MARGIN_PERCENT,
+        }
+    }
+
+    /// Time task bodies with the cycle counter of a `cpu_hz` core
+    #[must_use]
+    pub const fn cycles(cpu_hz: u32) -> Self {
+        Self {
+            clock: Clock::Cycles(cpu_hz),
+            runs: DEFAULT_RUNS,
+            margin_percent: DEFAULT_MARGIN_PERCENT,
+        }
+    }
+
+    /// Run each task `runs` times (at least once)
+    #[must_use]
+    pub const fn runs(mut self, runs: u16) -> Self {
+        self.runs = if runs == 0 { 1 } else { runs };
+        self
+    }
+
+    /// Pad the measured maximum by `percent`
+    #[must_use]
+    pub const fn margin_percent(mut self, percent: u16) -> Self {
+        self.margin_percent = percent;
+        self
+    }
+
+    /// Start of a measurement
+    fn start(&self) -> u64 {
+        match self.clock {
+            Clock::Micros(now) => now(),
+            Clock::Cycles(_) => u64::from(profiler::cycles()),
+        }
+    }
+
+    /// µs since `start`, rounded up
+    fn elapsed_us(&self, start: u64) -> u64 {
+        match self.clock {
+            Clock::Micros(now) => now().saturating_sub(start),
+            Clock::Cycles(cpu_hz) => {
+                // Stopwatch と同じく 32 ビットの周回差分
+                let cycles = u64::from(profiler::cycles().wrapping_sub(start as u32));
+                (cycles * 1_000_000).div_ceil(u64::from(cpu_hz.max(1)))
+            }
+        }
+    }
+
+    /// Time one call of `body` (µs, rounded up)
+    pub(crate) fn measure(&self, body: impl FnOnce()) -> u32 {
+        let start = self.start();
+        body();
+        self.elapsed_us(start).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Measured maximum padded by the margin (at least 1 µs)
+    #[must_use]
+    pub const fn padded(&self, max_us: u32) -> u32 {
+        let padded = (max_us as u64 * (100 + self.margin_percent as u64)).div_ceil(100);
+        if padded == 0 {
+            1
+        } else if padded > u32::MAX as u64 {
+            u32::MAX
+        } else {
+            padded as u32
+        }
+    }
+
+    /// Runs per task
+    #[must_use]
+    pub const fn run_count(&self) -> u16 {
+        self.runs
+    }
+}
+
+/// Outcome of [`Kernel::calibrate_wcet`]
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationReport {
+    /// Calibrated task slots
+    pub(crate) tasks: TaskMask,
+    /// Task names
+    pub(crate) names: [ShortName; MAX_TASKS],
+    /// Periods (µs)
+    pub(crate) periods: [u32; MAX_TASKS],
+    /// WCET before calibration (µs)
+    pub(crate) before: [u32; MAX_TASKS],
+    /// Largest measured execution time (µs)
+    pub(crate) measured: [u32; MAX_TASKS],
+    /// WCET written to the task table (µs)
+    pub(crate) after: [u32; MAX_TASKS],
+    /// Bodies that panicked during a run (std / test builds)
+    pub(crate) faulted: TaskMask,
+}
+
+impl CalibrationReport {
+    /// Empty report
+    pub(crate) const fn new() -> Self {
+        Self {
+            tasks: 0,
+            names: [ShortName([0; 8]); MAX_TASKS],
+            periods: [0; MAX_TASKS],
+            before: [0; MAX_TASKS],
+            measured: [0; MAX_TASKS],
+            after: [0; MAX_TASKS],
+            faulted: 0,
+        }
+    }
+
+    /// Calibrated task slots
+    #[must_use]
+    pub const fn tasks(&self) -> TaskMask {
+        self.tasks
+    }
+
+    /// Tasks whose body panicked during calibration (WCET left unchanged)
+    #[must_use]
+    pub const fn faulted(&self) -> TaskMask {
+        self.faulted
+    }
+
+    /// WCET of `idx` before calibration (µs)
+    #[must_use]
+    pub const fn wcet_before_us(&self, idx: usize) -> u32 {
+        self.before[idx]
+    }
+
+    /// Largest execution time measured for `idx` (µs)
+    #[must_use]
+    pub const fn measured_max_us(&self, idx: usize) -> u32 {
+        self.measured[idx]
+    }
+
+    /// WCET of `idx` written back (µs)
+    #[must_use]
+    pub const fn wcet_after_us(&self, idx: usize) -> u32 {
+        self.after[idx]
+    }
+
+    /// Calibrated tasks whose new WCET exceeds their period
+    #[must_use]
+    pub fn over_period(&self) -> TaskMask {
+        self.slots()
+            .filter(|&i| self.after[i] > self.periods[i])
+            .fold(0, |mask, i| mask | 1 << i)
+    }
+
+    /// Utilization of the calibrated tasks before (ppm)
+    #[must_use]
+    pub fn utilization_before_ppm(&self) -> u32 {
+        self.utilization_ppm(&self.before)
+    }
+
+    /// Utilization of the calibrated tasks after (ppm)
+    #[must_use]
+    pub fn utilization_after_ppm(&self) -> u32 {
+        self.utilization_ppm(&self.after)
+    }
+
+    fn utilization_ppm(&self, wcets: &[u32; MAX_TASKS]) -> u32 {
+        self.slots()
+            .map(|i| crate::scheduler::utilization_ppm(wcets[i], self.periods[i]))
+            .fold(0, u32::saturating_add)
+    }
+
+    fn slots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MAX_TASKS).filter(|&i| self.tasks & (1 << i) != 0)
+    }
+}
+
+/// One row per task (name, period, WCET before, measured max, WCET after)
+/// and the utilization before / after
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name       period   before measured    after")?;
+        for i in self.slots() {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>8} {:>8} {:>8}{}",
+                self.names[i],
+                self.periods[i],
+                self.before[i],
+                self.measured[i],
+                self.after[i],
+                if self.faulted & (1 << i) != 0 {
+                    " faulted"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        writeln!(
+            f,
+            "U {} -> {}",
+            Percent(self.utilization_before_ppm()),
+            Percent(self.utilization_after_ppm())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::KernelError;
+    use crate::kernel::Kernel;
+    use crate::task::TaskPriority;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    fn now() -> u64 {
+        NOW.load(Ordering::Relaxed)
+    }
+
+    /// 呼ばれるたびに 40..=70 µs かかる
+    fn jittery(_: &mut [u8]) {
+        let n = CALLS.fetch_add(1, Ordering::Relaxed);
+        NOW.fetch_add(40 + n % 4 * 10, Ordering::Relaxed);
+    }
+
+    fn quick(scratch: &mut [u8]) {
+        scratch[0] = scratch[0].wrapping_add(1);
+    }
+
+    #[test]
+    fn test_max_padded_and_written_back() {
+        let mut k = Kernel::testing();
+        k.add_task(b"jitter", jittery, TaskPriority::HIGH, 1_000, 10);
+        k.add_task(b"quick", quick, TaskPriority::LOW, 5_000, 50);
+        let cal = WcetCalibrator::new(now).runs(8).margin_percent(50);
+        let report = k.calibrate_wcet(&cal).unwrap();
+
+        assert_eq!(report.tasks(), 0b11);
+        assert_eq!(report.measured_max_us(0), 70);
+        assert_eq!(report.wcet_after_us(0), 105);
+        assert_eq!(k.scheduler.get_task(0).unwrap().wcet_us, 105);
+        // 0 µs は 1 µs に切り上げ
+        assert_eq!(report.measured_max_us(1), 0);
+        assert_eq!(k.scheduler.get_task(1).unwrap().wcet_us, 1);
+        // 本体は runs 回呼ばれる
+        assert_eq!(CALLS.load(Ordering::Relaxed), 8);
+        assert_eq!(report.utilization_before_ppm(), 20_000);
+        assert_eq!(report.utilization_after_ppm(), 105_200);
+        assert_eq!(report.over_period(), 0);
+
+        let text = std::format!("{report}");
+        assert!(text.contains("jitter       1000       10       70      105"));
+        assert!(text.ends_with("U 2.00% -> 10.52%\n"));
+    }
+
+    #[test]
+    fn test_refused_while_running() {
+        let mut k = Kernel::testing();
+        k.add_task(b"quick", quick, TaskPriority::LOW, 5_000, 50);
+        k.start().unwrap();
+        assert_eq!(
+            k.calibrate_wcet(&WcetCalibrator::new(now)).unwrap_err(),
+            KernelError::KernelRunning
+        );
+    }
+
+    #[test]
+    fn test_padding_rounds_up() {
+        let cal = WcetCalibrator::cycles(48_000_000);
+        assert_eq!(cal.run_count(), DEFAULT_RUNS);
+        assert_eq!(cal.padded(0), 1);
+        assert_eq!(cal.padded(10), 12);
+        assert_eq!(cal.padded(11), 14);
+        assert_eq!(cal.runs(0).run_count(), 1);
+        assert_eq!(cal.margin_percent(0).padded(7), 7);
+    }
+}
@@ -0,0 +1,518 @@
+//! Typed inter-task channels
+//!
+//! A `Channel<T, N>` is a typed SPSC ring that keeps its indices in the
+//! same model-checked protocol as [`SpscRing`](crate::spsc::SpscRing), but
+//! with interior mutability so the producer half and the consumer half can
+//! be handed to two different tasks.
+//! Channels are declared statically (directly or through a [`ChannelPool`])
+//! and wired to tasks at registration time with
+//! [`Kernel::connect`](crate::kernel::Kernel::connect).
+//!
+//! Channels of [`Stamped`] values carry the kernel time each item was
+//! produced at, so a consumer can ask for the newest item not older than
+//! a given age ([`Receiver::recv_fresh`]); older items are dropped and
+//! counted ([`Receiver::stale_dropped`]).
+//!
+//! ```rust
+//! use alice_rtos::channel::{Channel, Stamped};
+//! use alice_rtos::WrappingInstant;
+//!
+//! static SAMPLES: Channel<Stamped<i16>, 8> = Channel::new();
+//! let (mut tx, mut rx) = SAMPLES.split().unwrap();
+//! tx.send_at(10, WrappingInstant::new(0));
+//! tx.send_at(11, WrappingInstant::new(400));
+//! tx.send_at(12, WrappingInstant::new(900));
+//! // Controller at t = 1 200 with a 1 ms period: 0 µs sample is too old
+//! let now = WrappingInstant::new(1_200);
+//! assert_eq!(rx.recv_fresh(now, 1_000).map(|s| s.value), Some(12));
+//! assert_eq!(rx.stale_dropped(), 1);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::domain::TimeDomain;
+use crate::error::{KernelError, KernelResult};
+use crate::spsc::Indices;
+use crate::timer::{Tick, WrappingInstant};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+/// Maximum channel links tracked by the kernel
+pub const MAX_LINKS: usize = 8;
+
+/// Typed lock-free SPSC channel
+///
+/// Capacity is `N - 1` (one slot reserved for full/empty distinction),
+/// so `N` must be at least 2.
+/// The channel can be split exactly once into a [`Sender`] / [`Receiver`]
+/// pair; the `&mut self` methods on the halves enforce single-producer,
+/// single-consumer use.
+pub struct Channel<T, const N: usize> {
+    /// Ring storage (slots between read and write are initialized)
+    buffer: UnsafeCell<[MaybeUninit<T>; N]>,
+    /// Write index (sender) and read index (receiver)
+    indices: Indices<N, AtomicUsize>,
+    /// Halves already handed out?
+    split: AtomicBool,
+}
+
+// SAFETY: the sender only writes the free slot at the write index before
+// publishing it with a Release store, and the receiver only reads slots it
+// observed through an Acquire load. Each half is unique (`split` is one-shot).
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+impl<T: Copy, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> Channel<T, N> {
+    /// One slot stays free, so a usable channel needs two
+    const N_OK: () = assert!(N >= 2, "channel needs at least 2 slots (one stays free)");
+
+    /// Create an empty channel (usable in `static` initializers)
+    ///
+    /// A channel of fewer than 2 slots fails the build:
+    ///
+    /// ```compile_fail
+    /// use alice_rtos::channel::Channel;
+    ///
+    /// static NONE: Channel<u32, 1> = Channel::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        let () = Self::N_OK;
+        Self {
+            buffer: UnsafeCell::new([const { MaybeUninit::uninit() }; N]),
+            indices: Indices::with_cells(AtomicUsize::new(0), AtomicUsize::new(0)),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Split into producer and consumer halves
+    ///
+    /// Returns `None` if the channel was already split.
+    pub fn split(&self) -> Option<(Sender<'_, T, N>, Receiver<'_, T, N>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((
+            Sender { channel: self },
+            Receiver {
+                channel: self,
+                stale: 0,
+            },
+        ))
+    }
+
+    /// Has the channel been split into halves?
+    pub fn is_split(&self) -> bool {
+        self.split.load(Ordering::Acquire)
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Is the channel empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Available capacity
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    pub(crate) fn push(&self, value: T) -> bool {
+        let (write, free) = self.indices.free();
+        if free == 0 {
+            return false; // Full
+        }
+        // SAFETY: slot `write` is free (the receiver handed it back with
+        // the Release store observed in `free`); only the unique sender
+        // writes it.
+        unsafe {
+            (*self.buffer.get())[write] = MaybeUninit::new(value);
+        }
+        self.indices.publish(write, 1);
+        true
+    }
+
+    pub(crate) fn pop(&self) -> Option<T> {
+        let (read, queued) = self.indices.queued();
+        if queued == 0 {
+            return None; // Empty
+        }
+        // SAFETY: slot `read` was initialized by the sender and published
+        // by the Release store observed in `queued`.
+        let value = unsafe { (*self.buffer.get())[read].assume_init() };
+        self.indices.consume(read, 1);
+        Some(value)
+    }
+}
+
+/// Producer half of a [`Channel`]
+pub struct Sender<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T: Copy, const N: usize> Sender<'_, T, N> {
+    /// Send a value
+    ///
+    /// Returns false if the channel is full.
+    pub fn send(&mut self, value: T) -> bool {
+        self.channel.push(value)
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Is the channel empty?
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Is the channel full?
+    pub fn is_full(&self) -> bool {
+        self.channel.len() == self.channel.capacity()
+    }
+}
+
+/// Consumer half of a [`Channel`]
+pub struct Receiver<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+    /// Items dropped by [`recv_fresh`](Self::recv_fresh) as too old
+    stale: u32,
+}
+
+impl<T: Copy, const N: usize> Receiver<'_, T, N> {
+    /// Receive the oldest value
+    ///
+    /// Returns None if the channel is empty.
+    pub fn recv(&mut self) -> Option<T> {
+        self.channel.pop()
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Is the channel empty?
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Oldest item, or [`KernelError::TimedOut`] once `now` reaches `deadline`
+    ///
+    /// [`KernelError::WouldBlock`] while the channel is empty before the
+    /// deadline. To block a task instead of polling, see
+    /// [`RecvTimeouts`](crate::timeout::RecvTimeouts).
+    pub fn recv_deadline(
+        &mut self,
+        now: WrappingInstant,
+        deadline: WrappingInstant,
+    ) -> KernelResult<T> {
+        match self.channel.pop() {
+            Some(item) => Ok(item),
+            None if now.has_reached(deadline) => Err(KernelError::TimedOut),
+            None => Err(KernelError::WouldBlock),
+        }
+    }
+
+    /// Items dropped as too old by [`recv_fresh`](Self::recv_fresh)
+    pub const fn stale_dropped(&self) -> u32 {
+        self.stale
+    }
+}
+
+/// Value tagged with the kernel time it was produced at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamped<T> {
+    /// Production time
+    pub at: WrappingInstant,
+    /// Payload
+    pub value: T,
+}
+
+impl<T: Copy, const N: usize> Sender<'_, Stamped<T>, N> {
+    /// Send `value` produced at `at` (e.g. [`Scheduler::now`](crate::scheduler::Scheduler::now))
+    ///
+    /// Returns false if the channel is full.
+    pub fn send_at(&mut self, value: T, at: WrappingInstant) -> bool {
+        self.channel.push(Stamped { at, value })
+    }
+}
+
+impl<T: Copy, const N: usize> Receiver<'_, Stamped<T>, N> {
+    /// Newest item produced at most `max_age_us` before `now`
+    ///
+    /// Drains the channel: items older than `max_age_us` are dropped and
+    /// counted in [`stale_dropped`](Self::stale_dropped), fresh items
+    /// superseded by a newer one are dropped uncounted. Returns None if
+    /// no fresh item was queued.
+    pub fn recv_fresh(&mut self, now: WrappingInstant, max_age_us: Tick) -> Option<Stamped<T>> {
+        self.drain_fresh(now, max_age_us, |at| at)
+    }
+
+    /// Next item sent from domain `from`, stamped on the clock of `to`
+    ///
+    /// For channels crossing kernels (see [`domain`](crate::domain)).
+    pub fn recv_from(&mut self, from: &TimeDomain, to: &TimeDomain) -> Option<Stamped<T>> {
+        self.channel.pop().map(|item| Stamped {
+            at: from.translate(item.at, to),
+            value: item.value,
+        })
+    }
+
+    /// [`recv_fresh`](Self::recv_fresh) for items sent from domain `from`
+    ///
+    /// `now` and the returned stamp are on the clock of `to`.
+    pub fn recv_fresh_from(
+        &mut self,
+        from: &TimeDomain,
+        to: &TimeDomain,
+        now: WrappingInstant,
+        max_age_us: Tick,
+    ) -> Option<Stamped<T>> {
+        self.drain_fresh(now, max_age_us, |at| from.translate(at, to))
+    }
+
+    /// Drain, keeping the newest item whose mapped stamp is fresh
+    fn drain_fresh(
+        &mut self,
+        now: WrappingInstant,
+        max_age_us: Tick,
+        map: impl Fn(WrappingInstant) -> WrappingInstant,
+    ) -> Option<Stamped<T>> {
+        let mut newest = None;
+        while let Some(item) = self.channel.pop() {
+            let at = map(item.at);
+            if now.since(at) > max_age_us {
+                self.stale = self.stale.saturating_add(1);
+            } else {
+                newest = Some(Stamped {
+                    at,
+                    value: item.value,
+                });
+            }
+        }
+        newest
+    }
+}
+
+/// Static pool of same-typed channels
+///
+/// Declared once as a `static`; [`Kernel::connect`](crate::kernel::Kernel::connect)
+/// draws one channel per producer/consumer link.
+pub struct ChannelPool<T, const N: usize, const COUNT: usize> {
+    /// Channel storage
+    channels: [Channel<T, N>; COUNT],
+    /// Next unallocated channel
+    next: AtomicUsize,
+}
+
+impl<T: Copy, const N: usize, const COUNT: usize> Default for ChannelPool<T, N, COUNT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize, const COUNT: usize> ChannelPool<T, N, COUNT> {
+    /// Create a pool of `COUNT` empty channels
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            channels: [const { Channel::new() }; COUNT],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Take the next free channel
+    ///
+    /// Returns `None` when the pool is exhausted.
+    pub fn alloc(&self) -> Option<&Channel<T, N>> {
+        let idx = self.next.fetch_add(1, Ordering::AcqRel);
+        if idx >= COUNT {
+            self.next.store(COUNT, Ordering::Release);
+            return None;
+        }
+        Some(&self.channels[idx])
+    }
+
+    /// Take the next free channel and split it
+    ///
+    /// Each channel is handed out once, so a fresh one always splits; one
+    /// that does not is skipped instead of failing the call while usable
+    /// channels remain. Returns `None` when the pool is exhausted.
+    pub fn alloc_split(&self) -> Option<(Sender<'_, T, N>, Receiver<'_, T, N>)> {
+        loop {
+            if let Some(halves) = self.alloc()?.split() {
+                return Some(halves);
+            }
+        }
+    }
+
+    /// Number of channels handed out
+    pub fn allocated(&self) -> usize {
+        self.next.load(Ordering::Acquire).min(COUNT)
+    }
+
+    /// Pool size
+    pub const fn capacity(&self) -> usize {
+        COUNT
+    }
+}
+
+/// Producer → consumer wiring recorded by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    /// Producing task index
+    pub producer: u8,
+    /// Consuming task index
+    pub consumer: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Sample {
+        t: u32,
+        v: f32,
+    }
+
+    #[test]
+    fn test_send_recv_typed() {
+        let ch = Channel::<Sample, 4>::new();
+        let (mut tx, mut rx) = ch.split().unwrap();
+        assert!(tx.send(Sample { t: 1, v: 0.5 }));
+        assert_eq!(rx.recv(), Some(Sample { t: 1, v: 0.5 }));
+        assert_eq!(rx.recv(), None);
+    }
+
+    #[test]
+    fn test_recv_fresh_drops_stale() {
+        let ch = Channel::<Stamped<u32>, 8>::new();
+        let (mut tx, mut rx) = ch.split().unwrap();
+        let at = WrappingInstant::new;
+        assert_eq!(rx.recv_fresh(at(0), 100), None);
+        for t in [0, 50, 150, 180] {
+            assert!(tx.send_at(t, at(t as Tick)));
+        }
+        // t = 200、100 µs 以内: 150 と 180、最新の 180 を返す
+        assert_eq!(
+            rx.recv_fresh(at(200), 100),
+            Some(Stamped {
+                at: at(180),
+                value: 180
+            })
+        );
+        assert_eq!(rx.stale_dropped(), 2);
+        assert!(rx.is_empty());
+
+        // 全て古ければ None、破棄は累積
+        tx.send_at(300, at(300));
+        assert_eq!(rx.recv_fresh(at(500), 100), None);
+        assert_eq!(rx.stale_dropped(), 3);
+    }
+
+    #[test]
+    fn test_recv_fresh_across_tick_wrap() {
+        let ch = Channel::<Stamped<u8>, 4>::new();
+        let (mut tx, mut rx) = ch.split().unwrap();
+        // ラップ直前の生成、直後の消費
+        tx.send_at(1, WrappingInstant::new(Tick::MAX - 20));
+        tx.send_at(2, WrappingInstant::new(Tick::MAX - 5));
+        let now = WrappingInstant::new(10);
+        assert_eq!(rx.recv_fresh(now, 20).map(|s| s.value), Some(2));
+        assert_eq!(rx.stale_dropped(), 1);
+    }
+
+    #[test]
+    fn test_split_once() {
+        let ch = Channel::<u32, 4>::new();
+        assert!(!ch.is_split());
+        assert!(ch.split().is_some());
+        assert!(ch.is_split());
+        assert!(ch.split().is_none());
+    }
+
+    #[test]
+    fn test_full_channel() {
+        let ch = Channel::<u16, 4>::new();
+        let (mut tx, _rx) = ch.split().unwrap();
+        assert!(tx.send(1));
+        assert!(tx.send(2));
+        assert!(tx.send(3));
+        assert!(tx.is_full());
+        assert!(!tx.send(4));
+    }
+
+    #[test]
+    fn test_fifo_wraparound() {
+        let ch = Channel::<u8, 4>::new();
+        let (mut tx, mut rx) = ch.split().unwrap();
+        for round in 0..5u8 {
+            for i in 0..3 {
+                assert!(tx.send(round * 3 + i));
+            }
+            assert_eq!(rx.len(), 3);
+            for i in 0..3 {
+                assert_eq!(rx.recv(), Some(round * 3 + i));
+            }
+        }
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_pool_alloc_exhaustion() {
+        let pool = ChannelPool::<u32, 8, 2>::new();
+        assert_eq!(pool.capacity(), 2);
+        assert!(pool.alloc().is_some());
+        assert!(pool.alloc().is_some());
+        assert!(pool.alloc().is_none());
+        assert_eq!(pool.allocated(), 2);
+    }
+
+    #[test]
+    fn test_pool_alloc_split() {
+        let pool = ChannelPool::<u32, 4, 3>::new();
+        // alloc で渡した分はそのまま、alloc_split は次の空きを分割
+        let taken = pool.alloc().unwrap();
+        let (mut tx, mut rx) = pool.alloc_split().unwrap();
+        assert!(!taken.is_split());
+        tx.send(5);
+        assert_eq!(rx.recv(), Some(5));
+        assert!(pool.alloc_split().is_some());
+        assert!(pool.alloc_split().is_none());
+        assert_eq!(pool.allocated(), 3);
+    }
+
+    #[test]
+    fn test_pool_channels_independent() {
+        let pool = ChannelPool::<u32, 4, 2>::new();
+        let (mut a_tx, mut a_rx) = pool.alloc().unwrap().split().unwrap();
+        let (mut b_tx, mut b_rx) = pool.alloc().unwrap().split().unwrap();
+        a_tx.send(1);
+        b_tx.send(2);
+        assert_eq!(a_rx.recv(), Some(1));
+        assert_eq!(b_rx.recv(), Some(2));
+    }
+
+    #[test]
+    fn test_static_channel() {
+        static CH: Channel<u32, 8> = Channel::new();
+        let (mut tx, mut rx) = CH.split().unwrap();
+        tx.send(7);
+        assert_eq!(rx.recv(), Some(7));
+        assert_eq!(CH.capacity(), 7);
+    }
+}
@@ -0,0 +1,281 @@
+//! Chrome trace-event (JSON) export of a simulated schedule (`std` feature)
+//!
+//! [`ChromeTrace`] observes kernel ticks on the host — directly or through
+//! the [`Simulator`](crate::simulator::Simulator) — and records every
+//! dispatch and deadline miss. [`ChromeTrace::write_json`] turns the
+//! recording into the [Trace Event Format] understood by
+//! `chrome://tracing` and Perfetto:
+//!
+//! - one track (thread) per task, named after the task;
+//! - a complete (`X`) slice per job, as long as the task's WCET (the
+//!   kernel does not time task bodies);
+//! - an instant (`i`) event on the task's track per deadline miss.
+//!
+//! Timestamps are kernel microseconds, the format's native unit.
+//!
+//! [Trace Event Format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt::{self, Write};
+use std::string::String;
+use std::vec::Vec;
+
+use crate::kernel::Kernel;
+use crate::task::{TaskState, MAX_TASKS};
+
+/// One recorded trace event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// Task `idx` ran a job starting at `start_us` for `dur_us`
+    Run {
+        /// Task slot
+        idx: u8,
+        /// Dispatch time (µs)
+        start_us: u64,
+        /// Slice length (the task's WCET, µs)
+        dur_us: u32,
+    },
+    /// Task `idx` was found to have missed its deadline at `at_us`
+    DeadlineMiss {
+        /// Task slot
+        idx: u8,
+        /// Kernel time of detection (µs)
+        at_us: u64,
+    },
+}
+
+/// Recorder of dispatches and deadline misses for trace export
+#[derive(Debug, Default)]
+pub struct ChromeTrace {
+    /// Events in time order
+    events: Vec<TraceEvent>,
+}
+
+impl ChromeTrace {
+    /// Empty recording
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    /// Run `kernel.tick(delta_us)` and record what happened
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        self.record(kernel, |k| k.tick(delta_us))
+    }
+
+    /// Run `step` (one kernel tick, e.g. `|k| sim.step(k)`) and record
+    /// its dispatch and any deadline misses it detected
+    pub fn record<F>(&mut self, kernel: &mut Kernel, step: F) -> Option<usize>
+    where
+        F: FnOnce(&mut Kernel) -> Option<usize>,
+    {
+        let mut misses = [0u32; MAX_TASKS];
+        for (idx, count) in misses.iter_mut().enumerate() {
+            *count = kernel
+                .scheduler
+                .get_task(idx)
+                .map_or(0, |t| t.deadline_misses);
+        }
+
+        let executed = step(kernel);
+        let now = kernel.timer.now_us();
+
+        for (idx, &before) in misses.iter().enumerate() {
+            let after = kernel
+                .scheduler
+                .get_task(idx)
+                .map_or(0, |t| t.deadline_misses);
+            for _ in before..after {
+                self.events.push(TraceEvent::DeadlineMiss {
+                    idx: idx as u8,
+                    at_us: now,
+                });
+            }
+        }
+        if let Some(idx) = executed {
+            let dur_us = kernel.scheduler.get_task(idx).map_or(0, |t| t.wcet_us);
+            self.events.push(TraceEvent::Run {
+                idx: idx as u8,
+                start_us: now,
+                dur_us,
+            });
+        }
+        executed
+    }
+
+    /// Recorded events
+    #[must_use]
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Forget all events
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Write the recording as Trace Event Format JSON
+    ///
+    /// `kernel` supplies the task names for the track labels.
+    pub fn write_json<W: Write>(&self, kernel: &Kernel, out: &mut W) -> fmt::Result {
+        out.write_str("{\"traceEvents\":[")?;
+        out.write_str(
+            "{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":0,\"args\":{\"name\":\"alice-rtos\"}}",
+        )?;
+        let scheduler = &kernel.scheduler;
+        for idx in 0..scheduler.task_slots() {
+            let Some(task) = scheduler.get_task(idx) else {
+                continue;
+            };
+            if task.state == TaskState::Inactive {
+                continue;
+            }
+            write!(
+                out,
+                ",{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{idx},\"args\":{{\"name\":\""
+            )?;
+            write_name(out, &task.name)?;
+            write!(
+                out,
+                "\"}}}},{{\"name\":\"thread_sort_index\",\"ph\":\"M\",\"pid\":0,\"tid\":{idx},\"args\":{{\"sort_index\":{idx}}}}}"
+            )?;
+        }
+        for event in &self.events {
+            match *event {
+                TraceEvent::Run {
+                    idx,
+                    start_us,
+                    dur_us,
+                } => {
+                    out.write_str(",{\"name\":\"")?;
+                    let name = scheduler.get_task(idx as usize).map_or([0; 8], |t| t.name);
+                    write_name(out, &name)?;
+                    write!(
+                        out,
+                        "\",\"ph\":\"X\",\"pid\":0,\"tid\":{idx},\"ts\":{start_us},\"dur\":{dur_us}}}"
+                    )?;
+                }
+                TraceEvent::DeadlineMiss { idx, at_us } => write!(
+                    out,
+                    ",{{\"name\":\"deadline miss\",\"ph\":\"i\",\"s\":\"t\",\"pid\":0,\"tid\":{idx},\"ts\":{at_us}}}"
+                )?,
+            }
+        }
+        out.write_str("]}")
+    }
+
+    /// The recording as a JSON string
+    #[must_use]
+    pub fn to_json(&self, kernel: &Kernel) -> String {
+        let mut json = String::new();
+        // String への書き込みは失敗しない
+        let _ = self.write_json(kernel, &mut json);
+        json
+    }
+}
+
+/// Write a task name as JSON string content (non-printable → `?`)
+fn write_name<W: Write>(out: &mut W, name: &[u8; 8]) -> fmt::Result {
+    for &b in name.iter().take_while(|&&b| b != 0) {
+        match b {
+            b'"' | b'\\' => {
+                out.write_char('\\')?;
+                out.write_char(b as char)?;
+            }
+            b' '..=b'~' => out.write_char(b as char)?,
+            _ => out.write_char('?')?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_records_dispatches_with_wcet() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"slow", noop, TaskPriority::LOW, 200, 20);
+        let mut trace = ChromeTrace::new();
+        trace.tick(&mut kernel, 0);
+        trace.tick(&mut kernel, 50);
+        assert_eq!(
+            trace.events(),
+            &[
+                TraceEvent::Run {
+                    idx: 0,
+                    start_us: 0,
+                    dur_us: 10
+                },
+                TraceEvent::Run {
+                    idx: 1,
+                    start_us: 50,
+                    dur_us: 20
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deadline_miss_instant() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 100, 10);
+        let mut trace = ChromeTrace::new();
+        trace.tick(&mut kernel, 0);
+        // 次の起動から 2 周期以上遅れてディスパッチ → 期限超過
+        trace.tick(&mut kernel, 350);
+        let misses = trace
+            .events()
+            .iter()
+            .filter(|e| matches!(e, TraceEvent::DeadlineMiss { idx: 0, .. }))
+            .count();
+        assert_eq!(
+            misses as u32,
+            kernel.scheduler.get_task(0).unwrap().deadline_misses
+        );
+        assert!(misses > 0);
+        assert!(trace.to_json(&kernel).contains(
+            "{\"name\":\"deadline miss\",\"ph\":\"i\",\"s\":\"t\",\"pid\":0,\"tid\":0,\"ts\":350}"
+        ));
+    }
+
+    #[test]
+    fn test_json_tracks_and_slices() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"s\"l", noop, TaskPriority::LOW, 200, 20);
+        let mut trace = ChromeTrace::new();
+        trace.tick(&mut kernel, 0);
+        let json = trace.to_json(&kernel);
+        assert!(json.starts_with("{\"traceEvents\":[{\"name\":\"process_name\""));
+        assert!(json.ends_with("]}"));
+        assert!(json.contains("\"tid\":0,\"args\":{\"name\":\"fast\"}"));
+        // 名前の引用符はエスケープ
+        assert!(json.contains("\"tid\":1,\"args\":{\"name\":\"s\\\"l\"}"));
+        assert!(json
+            .contains("{\"name\":\"fast\",\"ph\":\"X\",\"pid\":0,\"tid\":0,\"ts\":0,\"dur\":10}"));
+        trace.clear();
+        assert!(trace.events().is_empty());
+    }
+
+    #[test]
+    fn test_record_through_simulator() {
+        use crate::simulator::Simulator;
+
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop, TaskPriority::HIGH, 100, 10);
+        let mut sim = Simulator::new(50).unpaced();
+        let mut trace = ChromeTrace::new();
+        for _ in 0..4 {
+            trace.record(&mut kernel, |k| sim.step(k));
+        }
+        // 50 µs ステップ: t = 50 (0 で起動済み) / 100 / 200 で実行
+        assert_eq!(trace.events().len(), 3);
+    }
+}
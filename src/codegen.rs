@@ -0,0 +1,822 @@
+//! Build-time code generation from a schedule file (`codegen` feature)
+//!
+//! Describes the task set and its channels in a small TOML file and turns
+//! it into the static tables of [`table`](crate::table), so registration
+//! code is never written by hand. [`generate`] is meant for `build.rs`: it
+//! reads the schedule, validates it with the checks of
+//! [`Kernel::load_table`] (names, periods, free slots, RMS test), writes
+//! the Rust items and returns a utilization report per priority level. A
+//! bad schedule fails the build with the line at fault.
+//!
+//! # Schedule file
+//!
+//! ```toml
+//! # Tasks take slots in file order
+//! [[task]]
+//! name = "imu"            # 1–8 bytes, a Rust identifier
+//! func = "sensors::imu"   # body, path from the crate root (default: the name)
+//! priority = "HIGH"       # CRITICAL / HIGH / NORMAL / LOW / IDLE or 0–255
+//! period_us = 1_000
+//! wcet_us = 150
+//!
+//! [[channel]]
+//! name = "SAMPLES"        # name of the generated static
+//! type = "u16"
+//! capacity = 8            # items in flight
+//! from = "imu"
+//! to = "fusion"
+//! ```
+//!
+//! The parser reads this subset of TOML: `[[task]]` / `[[channel]]`
+//! tables, `key = value` lines with basic strings or integers (`_`
+//! separators allowed) and `#` comments.
+//!
+//! # Generated items
+//!
+//! | Item | Content |
+//! |------|---------|
+//! | `IMU`, … | `usize` slot of each task (upper-cased name) |
+//! | `SPECS` | `[TaskSpec; n]` for [`Kernel::load_table`] |
+//! | `FUNCS` | `[TaskFn; n]`, parallel to `SPECS` |
+//! | `SAMPLES`, … | `static Channel<T, capacity + 1>` per channel |
+//! | `LINKS` | `[Link; k]`, producer → consumer of each channel |
+//!
+//! ```rust
+//! use alice_rtos::codegen::Schedule;
+//!
+//! let schedule = Schedule::parse(
+//!     r#"
+//!     [[task]]
+//!     name = "imu"
+//!     priority = "HIGH"
+//!     period_us = 1_000
+//!     wcet_us = 150
+//!
+//!     [[task]]
+//!     name = "fusion"
+//!     priority = 2
+//!     period_us = 5_000
+//!     wcet_us = 900
+//!
+//!     [[channel]]
+//!     name = "SAMPLES"
+//!     type = "u16"
+//!     capacity = 8
+//!     from = "imu"
+//!     to = "fusion"
+//!     "#,
+//! )
+//! .unwrap();
+//! let rust = schedule.rust("schedule.toml");
+//! assert!(rust.contains("pub const FUSION: usize = 1;"));
+//! assert!(rust.contains("Channel<u16, 9>"));
+//! assert!(schedule.report().contains("total 33.00%"));
+//! ```
+//!
+//! In the firmware crate:
+//!
+//! ```rust,ignore
+//! // build.rs
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("schedule.rs");
+//! alice_rtos::codegen::generate("schedule.toml".as_ref(), &out).unwrap();
+//! println!("cargo:rerun-if-changed=schedule.toml");
+//!
+//! // main.rs
+//! mod schedule {
+//!     include!(concat!(env!("OUT_DIR"), "/schedule.rs"));
+//! }
+//! kernel.load_table(&schedule::SPECS, &schedule::FUNCS)?;
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt::{self, Write};
+use std::boxed::Box;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::kernel::Kernel;
+use crate::scheduler::{harmonic, liu_layland_bound_ppm};
+use crate::table::{LoadError, TaskSpec};
+use crate::task::{TaskFn, TaskPriority};
+use crate::text::Percent;
+
+/// What is wrong with a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodegenErrorKind {
+    /// The schedule could not be read or the output written
+    Io(io::ErrorKind),
+    /// Not a table header, `key = value` line or comment
+    Syntax,
+    /// Key not used by this kind of table
+    UnknownKey(String),
+    /// Key given twice in one table
+    DuplicateKey(String),
+    /// Required key not given
+    MissingKey(&'static str),
+    /// Value of the wrong type or out of range
+    BadValue(&'static str),
+    /// Not a Rust identifier, path or type
+    BadName(String),
+    /// Two generated items with the same name
+    DuplicateItem(String),
+    /// A channel end names no task
+    UnknownTask(String),
+    /// Refused by the task table checks
+    Table(LoadError),
+}
+
+/// Schedule failure: the line at fault (if any) and the reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodegenError {
+    /// 1-based line of the schedule, `None` for whole-file errors
+    pub line: Option<usize>,
+    /// What was wrong
+    pub kind: CodegenErrorKind,
+}
+
+impl CodegenError {
+    /// Error at `line`
+    const fn at(line: usize, kind: CodegenErrorKind) -> Self {
+        Self {
+            line: Some(line),
+            kind,
+        }
+    }
+}
+
+impl From<io::Error> for CodegenError {
+    fn from(e: io::Error) -> Self {
+        Self {
+            line: None,
+            kind: CodegenErrorKind::Io(e.kind()),
+        }
+    }
+}
+
+impl fmt::Display for CodegenErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(kind) => write!(f, "I/O error ({kind})"),
+            Self::Syntax => f.write_str("expected `[[task]]`, `[[channel]]` or `key = value`"),
+            Self::UnknownKey(key) => write!(f, "unknown key `{key}`"),
+            Self::DuplicateKey(key) => write!(f, "key `{key}` given twice"),
+            Self::MissingKey(key) => write!(f, "missing key `{key}`"),
+            Self::BadValue(key) => write!(f, "invalid value for `{key}`"),
+            Self::BadName(name) => write!(f, "`{name}` is not a valid Rust name"),
+            Self::DuplicateItem(name) => write!(f, "item `{name}` generated twice"),
+            Self::UnknownTask(name) => write!(f, "no task named `{name}`"),
+            Self::Table(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "schedule line {line}: {}", self.kind),
+            None => write!(f, "schedule: {}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// Value of a `key = value` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Str(String),
+    Int(u64),
+}
+
+/// One `[[task]]` or `[[channel]]` table
+struct Section {
+    /// Line of the header
+    line: usize,
+    /// Keys not yet consumed, with their value and line
+    fields: Vec<(String, Value, usize)>,
+}
+
+impl Section {
+    /// Remove `key`, if given
+    fn take(&mut self, key: &'static str) -> Option<(Value, usize)> {
+        let pos = self.fields.iter().position(|(k, _, _)| k == key)?;
+        let (_, value, line) = self.fields.remove(pos);
+        Some((value, line))
+    }
+
+    /// String value of `key`, if given
+    fn string(&mut self, key: &'static str) -> Result<Option<(String, usize)>, CodegenError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some((Value::Str(s), line)) => Ok(Some((s, line))),
+            Some((_, line)) => Err(CodegenError::at(line, CodegenErrorKind::BadValue(key))),
+        }
+    }
+
+    /// Required string value of `key`
+    fn required(&mut self, key: &'static str) -> Result<(String, usize), CodegenError> {
+        self.string(key)?.ok_or(CodegenError::at(
+            self.line,
+            CodegenErrorKind::MissingKey(key),
+        ))
+    }
+
+    /// Required integer value of `key`, at most `max`
+    fn int(&mut self, key: &'static str, max: u64) -> Result<u64, CodegenError> {
+        match self.take(key) {
+            None => Err(CodegenError::at(
+                self.line,
+                CodegenErrorKind::MissingKey(key),
+            )),
+            Some((Value::Int(v), _)) if v <= max => Ok(v),
+            Some((_, line)) => Err(CodegenError::at(line, CodegenErrorKind::BadValue(key))),
+        }
+    }
+
+    /// Fail on a key left over
+    fn finish(self) -> Result<(), CodegenError> {
+        match self.fields.into_iter().next() {
+            Some((key, _, line)) => Err(CodegenError::at(line, CodegenErrorKind::UnknownKey(key))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A channel between two tasks
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChannelRow {
+    /// Name of the static
+    name: String,
+    /// Item type
+    ty: String,
+    /// Items in flight
+    capacity: usize,
+    /// Producer slot
+    from: usize,
+    /// Consumer slot
+    to: usize,
+}
+
+/// Task set and channels read from a schedule file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// Task table rows, in slot order
+    specs: Vec<TaskSpec>,
+    /// Body path of each task
+    funcs: Vec<String>,
+    /// Channels
+    channels: Vec<ChannelRow>,
+}
+
+impl Schedule {
+    /// Parse and validate a schedule
+    ///
+    /// # Errors
+    ///
+    /// [`CodegenError`] for a syntax error, a missing or invalid key, or
+    /// a task set [`Kernel::load_table`] would refuse.
+    pub fn parse(text: &str) -> Result<Self, CodegenError> {
+        let mut tasks = Vec::new();
+        let mut channels = Vec::new();
+        for (is_task, section) in sections(text)? {
+            if is_task {
+                tasks.push(section);
+            } else {
+                channels.push(section);
+            }
+        }
+
+        let mut specs = Vec::new();
+        let mut funcs = Vec::new();
+        let mut lines = Vec::new();
+        for mut section in tasks {
+            let (name, name_line) = section.required("name")?;
+            check_ident(&name, name_line)?;
+            let func = match section.string("func")? {
+                Some((func, line)) => {
+                    if !func.split("::").all(is_ident) {
+                        return Err(CodegenError::at(line, CodegenErrorKind::BadName(func)));
+                    }
+                    func
+                }
+                None => name.clone(),
+            };
+            let priority = match section.take("priority") {
+                None => TaskPriority::NORMAL,
+                Some((value, line)) => priority(&value).ok_or(CodegenError::at(
+                    line,
+                    CodegenErrorKind::BadValue("priority"),
+                ))?,
+            };
+            let period_us = section.int("period_us", u64::from(u32::MAX))? as u32;
+            let wcet_us = section.int("wcet_us", u64::from(u32::MAX))? as u32;
+            lines.push(section.line);
+            section.finish()?;
+            // ビルドスクリプトの間だけ使う名前: 解放しない
+            let name: &'static str = Box::leak(name.into_boxed_str());
+            specs.push(TaskSpec::new(name, priority, period_us, wcet_us));
+            funcs.push(func);
+        }
+
+        // Kernel::load_table と同じ検査 (空のカーネルに読み込む)
+        let noops: Vec<TaskFn> = specs.iter().map(|_| noop as TaskFn).collect();
+        if let Err(e) = Kernel::testing().load_table(&specs, &noops) {
+            return Err(CodegenError {
+                line: e.index.map(|i| lines[i]),
+                kind: CodegenErrorKind::Table(e),
+            });
+        }
+
+        let mut rows = Vec::new();
+        let mut items: Vec<String> = specs.iter().map(|s| s.name.to_ascii_uppercase()).collect();
+        for mut section in channels {
+            let (name, name_line) = section.required("name")?;
+            check_ident(&name, name_line)?;
+            let (ty, ty_line) = section.required("type")?;
+            if ty.trim().is_empty()
+                || !ty
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_:<>[]();,& '".contains(c))
+            {
+                return Err(CodegenError::at(ty_line, CodegenErrorKind::BadName(ty)));
+            }
+            let capacity = section.int("capacity", u64::from(u16::MAX))? as usize;
+            if capacity == 0 {
+                return Err(CodegenError::at(
+                    section.line,
+                    CodegenErrorKind::BadValue("capacity"),
+                ));
+            }
+            let mut end = |key: &'static str| -> Result<usize, CodegenError> {
+                let (task, line) = section.required(key)?;
+                specs
+                    .iter()
+                    .position(|s| s.name == task)
+                    .ok_or(CodegenError::at(line, CodegenErrorKind::UnknownTask(task)))
+            };
+            let (from, to) = (end("from")?, end("to")?);
+            if items.contains(&name) {
+                return Err(CodegenError::at(
+                    name_line,
+                    CodegenErrorKind::DuplicateItem(name),
+                ));
+            }
+            items.push(name.clone());
+            section.finish()?;
+            rows.push(ChannelRow {
+                name,
+                ty: ty.trim().to_string(),
+                capacity,
+                from,
+                to,
+            });
+        }
+        if let Some(i) = (1..items.len()).find(|&i| items[..i].contains(&items[i])) {
+            return Err(CodegenError {
+                line: lines.get(i).copied(),
+                kind: CodegenErrorKind::DuplicateItem(items[i].clone()),
+            });
+        }
+
+        Ok(Self {
+            specs,
+            funcs,
+            channels: rows,
+        })
+    }
+
+    /// Task table rows, in slot order
+    #[must_use]
+    pub fn specs(&self) -> &[TaskSpec] {
+        &self.specs
+    }
+
+    /// Rust items of the schedule; `source` names the file in the header
+    #[must_use]
+    pub fn rust(&self, source: &str) -> String {
+        let mut out = String::new();
+        // String への書き込みは失敗しない
+        let _ = self.write_rust(&mut out, source);
+        out
+    }
+
+    /// Write the items of [`rust`](Self::rust)
+    fn write_rust(&self, out: &mut String, source: &str) -> fmt::Result {
+        writeln!(
+            out,
+            "// @generated by alice_rtos::codegen from {source}; do not edit"
+        )?;
+        writeln!(out)?;
+        for (slot, spec) in self.specs.iter().enumerate() {
+            writeln!(out, "/// Slot of task `{}`", spec.name)?;
+            writeln!(
+                out,
+                "pub const {}: usize = {slot};",
+                spec.name.to_ascii_uppercase()
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "/// Task table")?;
+        writeln!(
+            out,
+            "pub const SPECS: [::alice_rtos::table::TaskSpec; {}] = [",
+            self.specs.len()
+        )?;
+        for spec in &self.specs {
+            writeln!(
+                out,
+                "    ::alice_rtos::table::TaskSpec::new({:?}, {}, {}, {}),",
+                spec.name,
+                priority_expr(spec.priority),
+                spec.period_us,
+                spec.wcet_us
+            )?;
+        }
+        writeln!(out, "];")?;
+        writeln!(out)?;
+        writeln!(out, "/// Task bodies, parallel to [`SPECS`]")?;
+        writeln!(
+            out,
+            "pub const FUNCS: [::alice_rtos::TaskFn; {}] = [",
+            self.funcs.len()
+        )?;
+        for func in &self.funcs {
+            writeln!(out, "    crate::{func},")?;
+        }
+        writeln!(out, "];")?;
+        for ch in &self.channels {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "/// `{}` → `{}`",
+                self.specs[ch.from].name, self.specs[ch.to].name
+            )?;
+            writeln!(
+                out,
+                "pub static {}: ::alice_rtos::channel::Channel<{}, {}> = ::alice_rtos::channel::Channel::new();",
+                ch.name,
+                ch.ty,
+                ch.capacity + 1
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "/// Producer → consumer of each channel")?;
+        writeln!(
+            out,
+            "pub const LINKS: [::alice_rtos::channel::Link; {}] = [",
+            self.channels.len()
+        )?;
+        for ch in &self.channels {
+            writeln!(
+                out,
+                "    ::alice_rtos::channel::Link {{ producer: {}, consumer: {} }},",
+                ch.from, ch.to
+            )?;
+        }
+        writeln!(out, "];")
+    }
+
+    /// Utilization per priority level, the RMS bound and the channels
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        // String への書き込みは失敗しない
+        let _ = self.write_report(&mut out);
+        out
+    }
+
+    /// Write the table of [`report`](Self::report)
+    fn write_report(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "prio tasks         U    cumul.")?;
+        let mut levels: Vec<TaskPriority> = self.specs.iter().map(|s| s.priority).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        let mut total = 0u32;
+        for level in levels {
+            let at_level = self.specs.iter().filter(|s| s.priority == level);
+            let u = at_level.clone().map(TaskSpec::utilization_ppm).sum::<u32>();
+            total += u;
+            writeln!(
+                out,
+                "{:>4} {:>5} {:>9} {:>9}",
+                level.0,
+                at_level.count(),
+                Percent(u),
+                Percent(total)
+            )?;
+        }
+        let periods: Vec<u32> = self.specs.iter().map(|s| s.period_us).collect();
+        let (bound, test) = if harmonic(&periods) {
+            (1_000_000, "harmonic")
+        } else {
+            (liu_layland_bound_ppm(periods.len()), "Liu & Layland")
+        };
+        writeln!(
+            out,
+            "total {} of {} ({test}, {} tasks)",
+            Percent(total),
+            Percent(bound),
+            self.specs.len()
+        )?;
+        for ch in &self.channels {
+            writeln!(
+                out,
+                "channel {}: {} -> {}, {} x {}",
+                ch.name, self.specs[ch.from].name, self.specs[ch.to].name, ch.capacity, ch.ty
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Build-script entry: read `schedule`, write its Rust items to `out`
+///
+/// Returns the [`report`](Schedule::report).
+///
+/// # Errors
+///
+/// [`CodegenError`] if the schedule cannot be read or is invalid, or the
+/// output cannot be written.
+pub fn generate(schedule: &Path, out: &Path) -> Result<String, CodegenError> {
+    let parsed = Schedule::parse(&fs::read_to_string(schedule)?)?;
+    let source = schedule.file_name().map_or_else(
+        || schedule.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    fs::write(out, parsed.rust(&source))?;
+    Ok(parsed.report())
+}
+
+/// Body used to validate the table
+fn noop(_: &mut [u8]) {}
+
+/// Split `text` into `[[task]]` (true) and `[[channel]]` (false) tables
+fn sections(text: &str) -> Result<Vec<(bool, Section)>, CodegenError> {
+    let mut out: Vec<(bool, Section)> = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = trimmed.strip_prefix("[[") {
+            let header = strip_comment(header);
+            let is_task = match header.strip_suffix("]]").map(str::trim) {
+                Some("task") => true,
+                Some("channel") => false,
+                _ => return Err(CodegenError::at(line, CodegenErrorKind::Syntax)),
+            };
+            out.push((
+                is_task,
+                Section {
+                    line,
+                    fields: Vec::new(),
+                },
+            ));
+            continue;
+        }
+        let (key, value) = trimmed
+            .split_once('=')
+            .ok_or(CodegenError::at(line, CodegenErrorKind::Syntax))?;
+        let key = key.trim();
+        let bare = !key.is_empty()
+            && key
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        let (Some((_, section)), true) = (out.last_mut(), bare) else {
+            return Err(CodegenError::at(line, CodegenErrorKind::Syntax));
+        };
+        let value =
+            parse_value(value.trim()).ok_or(CodegenError::at(line, CodegenErrorKind::Syntax))?;
+        if section.fields.iter().any(|(k, _, _)| k == key) {
+            return Err(CodegenError::at(
+                line,
+                CodegenErrorKind::DuplicateKey(key.to_string()),
+            ));
+        }
+        section.fields.push((key.to_string(), value, line));
+    }
+    Ok(out)
+}
+
+/// Text before a `#` comment, trimmed
+fn strip_comment(s: &str) -> &str {
+    s.split_once('#').map_or(s, |(before, _)| before).trim()
+}
+
+/// Basic string (`\"` and `\\` escapes) or integer, then an optional comment
+fn parse_value(s: &str) -> Option<Value> {
+    let Some(body) = s.strip_prefix('"') else {
+        let digits = strip_comment(s).replace('_', "");
+        return digits.parse().ok().map(Value::Int);
+    };
+    let mut value = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                return strip_comment(&body[i + 1..])
+                    .is_empty()
+                    .then_some(Value::Str(value))
+            }
+            '\\' => match chars.next()?.1 {
+                c @ ('"' | '\\') => value.push(c),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Priority by name or number
+fn priority(value: &Value) -> Option<TaskPriority> {
+    match value {
+        Value::Int(n) => u8::try_from(*n).ok().map(TaskPriority),
+        Value::Str(name) => match name.as_str() {
+            "CRITICAL" => Some(TaskPriority::CRITICAL),
+            "HIGH" => Some(TaskPriority::HIGH),
+            "NORMAL" => Some(TaskPriority::NORMAL),
+            "LOW" => Some(TaskPriority::LOW),
+            "IDLE" => Some(TaskPriority::IDLE),
+            _ => None,
+        },
+    }
+}
+
+/// Rust expression of a priority, by name where it has one
+fn priority_expr(priority: TaskPriority) -> String {
+    let name = match priority {
+        TaskPriority::CRITICAL => "CRITICAL",
+        TaskPriority::HIGH => "HIGH",
+        TaskPriority::NORMAL => "NORMAL",
+        TaskPriority::LOW => "LOW",
+        TaskPriority::IDLE => "IDLE",
+        TaskPriority(n) => return std::format!("::alice_rtos::TaskPriority({n})"),
+    };
+    std::format!("::alice_rtos::TaskPriority::{name}")
+}
+
+/// ASCII identifier: letter or `_`, then letters, digits or `_`
+fn is_ident(s: &str) -> bool {
+    let mut bytes = s.bytes();
+    bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+        && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// [`is_ident`], reported at `line`
+fn check_ident(name: &str, line: usize) -> Result<(), CodegenError> {
+    if is_ident(name) {
+        Ok(())
+    } else {
+        Err(CodegenError::at(
+            line,
+            CodegenErrorKind::BadName(name.to_string()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEDULE: &str = r#"
+# 2 タスク + 1 チャネル
+[[task]]
+name = "imu"          # 1 kHz
+func = "sensors::imu"
+priority = "HIGH"
+period_us = 1_000
+wcet_us = 150
+
+[[task]]
+name = "fusion"
+priority = 2
+period_us = 5_000
+wcet_us = 900
+
+[[channel]]
+name = "SAMPLES"
+type = "[i16; 3]"
+capacity = 4
+from = "imu"
+to = "fusion"
+"#;
+
+    fn err(text: &str) -> CodegenError {
+        Schedule::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn test_generated_items() {
+        let schedule = Schedule::parse(SCHEDULE).unwrap();
+        assert_eq!(
+            schedule.specs()[0],
+            TaskSpec::new("imu", TaskPriority::HIGH, 1_000, 150)
+        );
+        let rust = schedule.rust("schedule.toml");
+        assert!(rust.starts_with("// @generated by alice_rtos::codegen from schedule.toml"));
+        for item in [
+            "pub const IMU: usize = 0;",
+            "::alice_rtos::table::TaskSpec::new(\"imu\", ::alice_rtos::TaskPriority::HIGH, 1000, 150),",
+            "::alice_rtos::table::TaskSpec::new(\"fusion\", ::alice_rtos::TaskPriority::NORMAL, 5000, 900),",
+            "    crate::sensors::imu,\n    crate::fusion,\n",
+            "/// `imu` → `fusion`\npub static SAMPLES: ::alice_rtos::channel::Channel<[i16; 3], 5> =",
+            "::alice_rtos::channel::Link { producer: 0, consumer: 1 },",
+        ] {
+            assert!(rust.contains(item), "{item}\n{rust}");
+        }
+    }
+
+    #[test]
+    fn test_report_per_priority() {
+        let schedule = Schedule::parse(SCHEDULE).unwrap();
+        assert_eq!(
+            schedule.report(),
+            "prio tasks         U    cumul.\n\
+             \x20  1     1    15.00%    15.00%\n\
+             \x20  2     1    18.00%    33.00%\n\
+             total 33.00% of 100.00% (harmonic, 2 tasks)\n\
+             channel SAMPLES: imu -> fusion, 4 x [i16; 3]\n"
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let e = err("[[task]]\nname = \"a\"\nperiod_us = 100\nwcet_us = 10\nspeed = 3\n");
+        assert_eq!(
+            e,
+            CodegenError::at(5, CodegenErrorKind::UnknownKey("speed".into()))
+        );
+        assert_eq!(e.to_string(), "schedule line 5: unknown key `speed`");
+        assert_eq!(err("name = \"a\"").line, Some(1));
+        assert_eq!(err("[[tasks]]").kind, CodegenErrorKind::Syntax);
+        assert_eq!(
+            err("[[task]]\nname = \"a\"\n").kind,
+            CodegenErrorKind::MissingKey("period_us")
+        );
+        assert_eq!(
+            err("[[task]]\nname = \"a\"\npriority = \"URGENT\"\nperiod_us = 1\nwcet_us = 1").line,
+            Some(3)
+        );
+        assert_eq!(
+            err("[[task]]\nname = \"a-b\"\nperiod_us = 1\nwcet_us = 1").kind,
+            CodegenErrorKind::BadName("a-b".into())
+        );
+        assert_eq!(
+            err("[[task]]\nname = \"a\"\nname = \"b\"").kind,
+            CodegenErrorKind::DuplicateKey("name".into())
+        );
+        assert_eq!(
+            err("[[task]]\nname = \"a\\q\"").kind,
+            CodegenErrorKind::Syntax
+        );
+    }
+
+    #[test]
+    fn test_table_and_channel_checks() {
+        // WCET > 周期: 該当タスクのヘッダ行
+        let e = err("[[task]]\nname = \"a\"\nperiod_us = 100\nwcet_us = 10\n[[task]]\nname = \"b\"\nperiod_us = 100\nwcet_us = 200\n");
+        assert_eq!(e.line, Some(5));
+        assert!(e
+            .to_string()
+            .ends_with("task spec 1 \"b\": WCET exceeds the period"));
+
+        let task = "[[task]]\nname = \"a\"\nperiod_us = 100\nwcet_us = 10\n";
+        let e = err(&std::format!("{task}[[channel]]\nname = \"Q\"\ntype = \"u8\"\ncapacity = 2\nfrom = \"a\"\nto = \"z\"\n"));
+        assert_eq!(
+            e,
+            CodegenError::at(10, CodegenErrorKind::UnknownTask("z".into()))
+        );
+        let e = err(&std::format!("{task}[[channel]]\nname = \"A\"\ntype = \"u8\"\ncapacity = 2\nfrom = \"a\"\nto = \"a\"\n"));
+        assert_eq!(e.kind, CodegenErrorKind::DuplicateItem("A".into()));
+        let e = err(&std::format!("{task}[[channel]]\nname = \"Q\"\ntype = \"u8\"\ncapacity = 0\nfrom = \"a\"\nto = \"a\"\n"));
+        assert_eq!(e.kind, CodegenErrorKind::BadValue("capacity"));
+    }
+
+    #[test]
+    fn test_generate_writes_the_output() {
+        let dir = std::env::temp_dir().join(std::format!("alice-codegen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("schedule.toml"), dir.join("schedule.rs"));
+        fs::write(&input, SCHEDULE).unwrap();
+        let report = generate(&input, &output).unwrap();
+        assert!(report.contains("total 33.00%"));
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .contains("from schedule.toml;"));
+        assert_eq!(
+            generate(&dir.join("missing.toml"), &output)
+                .unwrap_err()
+                .kind,
+            CodegenErrorKind::Io(io::ErrorKind::NotFound)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+//! Const-evaluable kernel configuration
+//!
+//! Builds the task table in a `const` context and validates it with
+//! integer RMS math while the firmware is compiled. An unschedulable
+//! configuration fails the build through a const panic, so it can never
+//! be flashed.
+//!
+//! ```rust
+//! use alice_rtos::config::{KernelBuilder, KernelConfig};
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn synth(_: &mut [u8]) {}
+//! fn motion(_: &mut [u8]) {}
+//!
+//! const CONFIG: KernelConfig<2> = KernelBuilder::new()
+//!     .task(b"synth", synth, TaskPriority::CRITICAL, 23, 8)
+//!     .task(b"motion", motion, TaskPriority::HIGH, 100, 15)
+//!     .build();
+//!
+//! let mut kernel = Kernel::testing();
+//! assert!(CONFIG.install(&mut kernel));
+//! ```
+//!
+//! [`kernel_config!`](crate::kernel_config) declares the task functions
+//! and the configuration in one place; names come from the function
+//! identifiers and are checked at compile time as well:
+//!
+//! ```rust
+//! use alice_rtos::{kernel_config, task, Kernel, Task};
+//!
+//! kernel_config! {
+//!     pub const CONFIG;
+//!
+//!     /// 44.1 kHz audio block
+//!     fn synth(_scratch) [CRITICAL, period_us = 23, wcet_us = 8] {}
+//!
+//!     fn telemetry as "tlm"(scratch) [LOW, period_us = 10_000, wcet_us = 200] {
+//!         scratch[0] = scratch[0].wrapping_add(1);
+//!     }
+//! }
+//!
+//! // A single validated descriptor
+//! const SYNTH: Task = task!(synth, CRITICAL, 23, 8);
+//!
+//! let mut kernel = Kernel::testing();
+//! assert_eq!(CONFIG.len(), 2);
+//! assert!(CONFIG.install(&mut kernel));
+//! assert_eq!(kernel.scheduler.find_by_name(b"tlm"), Some(1));
+//! assert_eq!(SYNTH.name, CONFIG.tasks()[0].name);
+//! ```
+//!
+//! ```compile_fail
+//! use alice_rtos::kernel_config;
+//!
+//! // "telemetry" is 9 bytes → compile error (rename with `as "tlm"`)
+//! kernel_config! {
+//!     const CONFIG;
+//!     fn telemetry(_s) [LOW, period_us = 10_000, wcet_us = 200] {}
+//! }
+//! # let _ = CONFIG;
+//! ```
+//!
+//! ```compile_fail
+//! use alice_rtos::config::{KernelBuilder, KernelConfig};
+//! use alice_rtos::TaskPriority;
+//!
+//! fn hog(_: &mut [u8]) {}
+//!
+//! // U = 0.9 + 0.5 > 0.828 → rejected at compile time
+//! const CONFIG: KernelConfig<2> = KernelBuilder::new()
+//!     .task(b"a", hog, TaskPriority::CRITICAL, 100, 90)
+//!     .task(b"b", hog, TaskPriority::HIGH, 100, 50)
+//!     .build();
+//! # let _ = CONFIG;
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::Kernel;
+use crate::scheduler::liu_layland_bound_ppm;
+use crate::task::{mask_slots, Task, TaskFn, TaskMask, TaskPriority, MAX_TASKS};
+
+/// Const-evaluable task-set builder
+///
+/// `N` is the number of task slots in the resulting configuration.
+pub struct KernelBuilder<const N: usize> {
+    /// Task table under construction
+    tasks: [Task; N],
+    /// Number of tasks added
+    len: usize,
+}
+
+impl<const N: usize> Default for KernelBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> KernelBuilder<N> {
+    /// Empty builder
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tasks: [Task::empty(); N],
+            len: 0,
+        }
+    }
+
+    /// Add a periodic task
+    ///
+    /// # Panics
+    ///
+    /// In a `const` context these panics are compile errors: more than
+    /// `N` tasks, a zero period, or WCET larger than the period.
+    #[must_use]
+    pub const fn task(
+        self,
+        name: &[u8],
+        func: TaskFn,
+        priority: TaskPriority,
+        period_us: u32,
+        wcet_us: u32,
+    ) -> Self {
+        self.add(Task::new(name, func, priority, period_us, wcet_us))
+    }
+
+    /// Add a prepared task (e.g. from [`task!`](macro@crate::task))
+    ///
+    /// # Panics
+    ///
+    /// Same as [`task`](Self::task).
+    #[must_use]
+    pub const fn add(mut self, task: Task) -> Self {
+        assert!(self.len < N, "more tasks than configured slots");
+        assert!(task.period_us > 0, "task period must be non-zero");
+        assert!(
+            task.wcet_us <= task.period_us,
+            "task WCET exceeds its period"
+        );
+        self.tasks[self.len] = task;
+        self.len += 1;
+        self
+    }
+
+    /// Total utilization in parts per million (rounded up)
+    #[must_use]
+    pub const fn utilization_ppm(&self) -> u32 {
+        utilization_ppm(&self.tasks, self.len)
+    }
+
+    /// Finish the configuration
+    ///
+    /// # Panics
+    ///
+    /// If the utilization exceeds the Liu & Layland bound for the task
+    /// count — a compile error when evaluated in a `const` item.
+    #[must_use]
+    pub const fn build(self) -> KernelConfig<N> {
+        assert!(
+            self.utilization_ppm() <= liu_layland_bound_ppm(self.len),
+            "task set exceeds the Liu & Layland bound"
+        );
+        KernelConfig {
+            tasks: self.tasks,
+            len: self.len,
+        }
+    }
+}
+
+/// Validated task table produced by [`KernelBuilder::build`]
+pub struct KernelConfig<const N: usize> {
+    /// Task table
+    tasks: [Task; N],
+    /// Number of valid entries
+    len: usize,
+}
+
+impl<const N: usize> KernelConfig<N> {
+    /// Configured tasks
+    #[must_use]
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks[..self.len]
+    }
+
+    /// Number of configured tasks
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Is the configuration empty?
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Total utilization in parts per million (rounded up)
+    #[must_use]
+    pub const fn utilization_ppm(&self) -> u32 {
+        utilization_ppm(&self.tasks, self.len)
+    }
+
+    /// Register every task with `kernel`, all or nothing
+    ///
+    /// Each task goes through [`Kernel::try_add`]: it is reported as
+    /// `TaskRegistered` and passes the kernel's admission check. Returns
+    /// false, with the kernel left as it was, if the task table lacks the
+    /// free slots or the kernel refuses a task.
+    pub fn install(&self, kernel: &mut Kernel) -> bool {
+        if kernel.scheduler.active_task_count() + self.len > MAX_TASKS {
+            return false;
+        }
+        let mut installed: TaskMask = 0;
+        for task in self.tasks() {
+            match kernel.try_add(*task) {
+                Ok(idx) => installed |= 1 << idx,
+                Err(_) => {
+                    // 全部か無しか: 登録済みの分を戻す
+                    for idx in mask_slots(installed) {
+                        kernel.scheduler.unregister(idx);
+                    }
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Task descriptor for [`task!`](macro@crate::task) / [`kernel_config!`](crate::kernel_config)
+///
+/// # Panics
+///
+/// A compile error in a `const` context: a name that is empty or longer
+/// than 8 bytes, a zero period, or WCET larger than the period.
+#[must_use]
+pub const fn checked_task(
+    name: &str,
+    func: TaskFn,
+    priority: TaskPriority,
+    period_us: u32,
+    wcet_us: u32,
+) -> Task {
+    assert!(
+        !name.is_empty() && name.len() <= 8,
+        "task name must be 1 to 8 bytes"
+    );
+    assert!(period_us > 0, "task period must be non-zero");
+    assert!(wcet_us <= period_us, "task WCET exceeds its period");
+    Task::new(name.as_bytes(), func, priority, period_us, wcet_us)
+}
+
+/// Validated [`Task`] for the function `func`, built at compile time
+///
+/// `task!(func, PRIORITY, period_us, wcet_us)` names the task after the
+/// function; `task!(func as "name", …)` overrides the name. `PRIORITY` is
+/// a [`TaskPriority`] constant (`CRITICAL`, `HIGH`, …). Expands to a
+/// `const`, so a bad name, a zero period or WCET > period fails the build
+/// (see [`checked_task`]).
+///
+/// ```rust
+/// use alice_rtos::{task, Task};
+///
+/// fn imu(_: &mut [u8]) {}
+///
+/// const IMU: Task = task!(imu, HIGH, 1_000, 150);
+/// let log = task!(imu as "imu-log", LOW, 10_000, 20);
+/// assert_eq!(&IMU.name[..3], b"imu");
+/// assert_eq!(&log.name[..7], b"imu-log");
+/// ```
+///
+/// ```compile_fail
+/// use alice_rtos::{task, Task};
+///
+/// fn imu(_: &mut [u8]) {}
+///
+/// const IMU: Task = task!(imu, HIGH, 100, 150); // WCET > period
+/// ```
+#[macro_export]
+macro_rules! task {
+    ($func:ident $(as $name:literal)?, $priority:ident, $period_us:expr, $wcet_us:expr $(,)?) => {{
+        const TASK: $crate::Task = $crate::config::checked_task(
+            $crate::task!(@name $func $($name)?),
+            $func,
+            $crate::TaskPriority::$priority,
+            $period_us,
+            $wcet_us,
+        );
+        TASK
+    }};
+    (@name $func:ident) => {
+        stringify!($func)
+    };
+    (@name $func:ident $name:literal) => {
+        $name
+    };
+}
+
+/// Declare task functions and a [`KernelConfig`] `const` holding them
+///
+/// ```text
+/// kernel_config! {
+///     <vis> const NAME;
+///     <attrs> fn <func> [as "name"](<scratch pattern>) [PRIORITY, period_us = T, wcet_us = C] { body }
+///     …
+/// }
+/// ```
+///
+/// Each entry becomes `fn func(scratch: &mut [u8])` and a [`task!`](macro@crate::task)
+/// in table order. Besides the per-task checks, the whole set must pass
+/// the Liu & Layland bound ([`KernelBuilder::build`]) — all at compile
+/// time. See the [module docs](self) for an example.
+#[macro_export]
+macro_rules! kernel_config {
+    (
+        $(#[$config_meta:meta])*
+        $vis:vis const $config:ident;
+        $(
+            $(#[$meta:meta])*
+            fn $func:ident $(as $name:literal)? ($scratch:pat)
+                [$priority:ident, period_us = $period_us:expr, wcet_us = $wcet_us:expr $(,)?]
+                $body:block
+        )+
+    ) => {
+        $(
+            $(#[$meta])*
+            fn $func($scratch: &mut [u8]) $body
+        )+
+
+        $(#[$config_meta])*
+        $vis const $config: $crate::config::KernelConfig<{ [$(stringify!($func)),+].len() }> =
+            $crate::config::KernelBuilder::new()
+                $(.add($crate::task!($func $(as $name)?, $priority, $period_us, $wcet_us)))+
+                .build();
+    };
+}
+
+/// Σ ⌈Cᵢ·10⁶ / Tᵢ⌉ over the first `len` tasks
+const fn utilization_ppm(tasks: &[Task], len: usize) -> u32 {
+    let mut total: u64 = 0;
+    let mut i = 0;
+    while i < len {
+        total += tasks[i].utilization_ppm() as u64;
+        i += 1;
+    }
+    if total > u32::MAX as u64 {
+        u32::MAX
+    } else {
+        total as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Admission;
+
+    fn dummy(_: &mut [u8]) {}
+
+    const THREE: KernelConfig<3> = KernelBuilder::new()
+        .task(b"synth", dummy, TaskPriority::CRITICAL, 23, 8)
+        .task(b"motion", dummy, TaskPriority::HIGH, 100, 15)
+        .task(b"edge", dummy, TaskPriority::NORMAL, 1000, 50)
+        .build();
+
+    #[test]
+    fn const_config_evaluates() {
+        assert_eq!(THREE.len(), 3);
+        assert_eq!(&THREE.tasks()[1].name[..6], b"motion");
+        // 8/23 + 15/100 + 50/1000 ≈ 0.5478
+        let u = THREE.utilization_ppm();
+        assert!((547_000..549_000).contains(&u), "u = {u}");
+    }
+
+    #[test]
+    fn install_registers_tasks() {
+        let mut kernel = Kernel::testing();
+        assert!(THREE.install(&mut kernel));
+        assert_eq!(kernel.scheduler.active_task_count(), 3);
+        assert!(kernel.is_schedulable());
+    }
+
+    #[test]
+    fn install_is_all_or_nothing() {
+        // 空きスロット不足: 何も登録しない
+        let mut kernel = Kernel::testing();
+        for _ in 0..MAX_TASKS - 2 {
+            kernel.add_task(b"t", dummy, TaskPriority::LOW, 10_000, 10);
+        }
+        assert!(!THREE.install(&mut kernel));
+        assert_eq!(kernel.scheduler.active_task_count(), MAX_TASKS - 2);
+
+        // 受け入れ制御で 3 つ目 (edge の応答 1 053 µs) が拒否されると前の 2 つも戻す
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hog", dummy, TaskPriority::NORMAL, 1_000, 470);
+        kernel.set_admission(Admission::Reject);
+        assert!(!THREE.install(&mut kernel));
+        assert_eq!(kernel.scheduler.active_task_count(), 1);
+        // 走行中は live registration なしでは登録できない
+        let mut kernel = Kernel::testing();
+        kernel.start().unwrap();
+        assert!(!THREE.install(&mut kernel));
+        assert_eq!(kernel.scheduler.active_task_count(), 0);
+    }
+
+    #[test]
+    fn partial_config_fewer_tasks_than_slots() {
+        let cfg: KernelConfig<4> = KernelBuilder::new()
+            .task(b"a", dummy, TaskPriority::HIGH, 100, 10)
+            .build();
+        assert_eq!(cfg.len(), 1);
+        assert!(!cfg.is_empty());
+    }
+
+    #[test]
+    fn single_task_full_utilization_allowed() {
+        let cfg: KernelConfig<1> = KernelBuilder::new()
+            .task(b"a", dummy, TaskPriority::HIGH, 100, 100)
+            .build();
+        assert_eq!(cfg.utilization_ppm(), 1_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Liu & Layland")]
+    fn build_rejects_unschedulable() {
+        let _ = KernelBuilder::<2>::new()
+            .task(b"a", dummy, TaskPriority::CRITICAL, 100, 90)
+            .task(b"b", dummy, TaskPriority::HIGH, 100, 50)
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = "more tasks")]
+    fn task_rejects_overflow() {
+        let _ = KernelBuilder::<1>::new()
+            .task(b"a", dummy, TaskPriority::HIGH, 100, 1)
+            .task(b"b", dummy, TaskPriority::HIGH, 100, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "WCET")]
+    fn task_rejects_wcet_over_period() {
+        let _ = KernelBuilder::<1>::new().task(b"a", dummy, TaskPriority::HIGH, 100, 101);
+    }
+
+    crate::kernel_config! {
+        const MACRO;
+
+        fn sense(_scratch) [HIGH, period_us = 1_000, wcet_us = 100] {}
+
+        /// 名前を上書き
+        fn telemetry as "tlm"(scratch) [LOW, period_us = 10_000, wcet_us = 500] {
+            scratch[0] = 7;
+        }
+    }
+
+    #[test]
+    fn macro_declares_functions_and_config() {
+        assert_eq!(MACRO.len(), 2);
+        let tasks = MACRO.tasks();
+        assert_eq!(tasks[0].display_name().as_str(), "sense");
+        assert_eq!(tasks[1].display_name().as_str(), "tlm");
+        assert_eq!((tasks[1].period_us, tasks[1].wcet_us), (10_000, 500));
+        assert_eq!(tasks[0].priority, TaskPriority::HIGH);
+        let mut scratch = [0u8; 4];
+        (tasks[1].func.unwrap())(&mut scratch);
+        assert_eq!(scratch[0], 7);
+        // 150_000 ppm
+        assert_eq!(MACRO.utilization_ppm(), 150_000);
+        let single = crate::task!(sense, HIGH, 1_000, 100);
+        assert_eq!(single.name, tasks[0].name);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 to 8 bytes")]
+    fn checked_task_rejects_long_name() {
+        let _ = checked_task("telemetry", dummy, TaskPriority::LOW, 100, 1);
+    }
+
+    #[test]
+    fn bound_table_matches_float_formula() {
+        for n in 1..=8 {
+            let ppm = liu_layland_bound_ppm(n);
+            let exact = n as f64 * (2f64.powf(1.0 / n as f64) - 1.0);
+            assert!((exact * 1e6 - ppm as f64).abs() < 1.0, "n = {n}");
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Explicit per-job absolute deadlines (constrained deadlines)
+//!
+//! The scheduler derives a job's deadline from its release as
+//! `next_activation + period`: the deadline *is* the next release. That
+//! only covers implicit deadlines (D = T). [`DeadlineTracker`] keeps a
+//! relative deadline per task (D ≤ T, default T) and, for every released
+//! job, an explicit absolute deadline `release + D` that is set when the
+//! job is released and cleared when it is dispatched — independently of
+//! where `next_activation` moves in between (e.g. a mode change parking
+//! the next boundary there, or a late job's merged releases).
+//!
+//! A job still pending when its absolute deadline passes misses it at
+//! that tick ([`DeadlineTracker::misses`]), whether or not it ever gets
+//! the CPU. The task table, and with it the kernel's footprint, is left
+//! untouched: the tracker is a [`TickObserver`] and only follows observed
+//! ticks ([`DeadlineTracker::tick`],
+//! [`tick_observed`](SizedKernel::tick_observed) and friends), next to
+//! other managers if needed.
+//!
+//! ```rust
+//! use alice_rtos::deadlines::DeadlineTracker;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"hi", noop, TaskPriority::HIGH, 500, 100).unwrap();
+//! let ctl = kernel.add_task(b"ctl", noop, TaskPriority::LOW, 1_000, 100).unwrap();
+//! let mut tracker = DeadlineTracker::new();
+//! // Output due 200 µs after each release, not at the next period
+//! assert!(tracker.set_relative(&kernel, ctl, 200));
+//! tracker.tick(&mut kernel, 0);
+//! assert_eq!(tracker.absolute(ctl), Some(200));
+//! tracker.run_for(&mut kernel, 10_000, 300);
+//! // Dispatch lags the release by up to one 300 µs tick: D = 200 is missed
+//! assert!(tracker.misses(ctl) > 0);
+//! assert_eq!(kernel.scheduler.get_task(ctl).unwrap().deadline_misses, 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{KernelStats, SizedKernel, TickObserver};
+use crate::policy::SchedulingPolicy;
+use crate::task::{TaskMask, TaskState, MAX_TASKS};
+use crate::timer::{tick_reached, tick_to_u64, ticks_since, Tick};
+
+/// Relative deadlines and the absolute deadline of each pending job
+pub struct DeadlineTracker {
+    /// Relative deadline per task (µs, 0 = the period)
+    relative_us: [u32; MAX_TASKS],
+    /// Absolute deadline of the pending job (µs, kernel time)
+    absolute_us: [u64; MAX_TASKS],
+    /// Tasks with a released job not yet dispatched
+    pending: TaskMask,
+    /// Pending jobs whose deadline has already been counted as missed
+    late: TaskMask,
+    /// Deadlines missed per task
+    misses: [u32; MAX_TASKS],
+    /// `next_activation` per task before the observed tick
+    scheduled: [Tick; MAX_TASKS],
+}
+
+impl Default for DeadlineTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadlineTracker {
+    /// Tracker with implicit deadlines (D = T) for every task
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            relative_us: [0; MAX_TASKS],
+            absolute_us: [0; MAX_TASKS],
+            pending: 0,
+            late: 0,
+            misses: [0; MAX_TASKS],
+            scheduled: [0; MAX_TASKS],
+        }
+    }
+
+    /// Set the relative deadline of task `idx` (0 = its period)
+    ///
+    /// Periodic tasks need D ≤ T: a job must be done before the next one
+    /// is released. Event-driven tasks (period 0) accept any D. Returns
+    /// false for an inactive task or a deadline beyond the period. The
+    /// new deadline applies from the next release.
+    pub fn set_relative<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &SizedKernel<SCRATCH, P>,
+        idx: usize,
+        deadline_us: u32,
+    ) -> bool {
+        match kernel.scheduler.get_task(idx) {
+            Some(task) if task.period_us == 0 || deadline_us <= task.period_us => {
+                self.relative_us[idx] = deadline_us;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Relative deadline of task `idx` (µs, 0 = no deadline)
+    #[must_use]
+    pub fn relative_us<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &SizedKernel<SCRATCH, P>,
+        idx: usize,
+    ) -> u32 {
+        match (self.relative_us.get(idx), kernel.scheduler.get_task(idx)) {
+            (Some(&0), Some(task)) => task.period_us,
+            (Some(&d), Some(_)) => d,
+            _ => 0,
+        }
+    }
+
+    /// Absolute deadline of task `idx`'s pending job (µs, kernel time)
+    #[must_use]
+    pub fn absolute(&self, idx: usize) -> Option<u64> {
+        (idx < MAX_TASKS && self.pending & (1 << idx) != 0).then(|| self.absolute_us[idx])
+    }
+
+    /// Deadlines missed by task `idx`
+    #[must_use]
+    pub fn misses(&self, idx: usize) -> u32 {
+        self.misses.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Tick the kernel and track the jobs released and dispatched
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        delta_us: u64,
+    ) -> Option<usize> {
+        kernel.tick_observed(delta_us, self)
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`](crate::kernel::Kernel::run_for))
+    pub fn run_for<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        total_us: u64,
+        tick_us: u64,
+    ) -> KernelStats {
+        kernel.run_for_observed(total_us, tick_us, self)
+    }
+}
+
+/// Notes the scheduled releases before each tick and tracks the jobs
+/// released and dispatched by it
+impl TickObserver for DeadlineTracker {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        // 時間リリースの起点はディスパッチで上書きされる前に控える
+        self.scheduled = [0; MAX_TASKS];
+        for (idx, task) in kernel.scheduler.tasks() {
+            self.scheduled[idx] = task.next_activation;
+        }
+    }
+
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        executed: Option<usize>,
+    ) {
+        let now = kernel.timer.now_us();
+        let now_tick = kernel.scheduler.now_us();
+
+        for (idx, &scheduled_at) in self.scheduled.iter().enumerate() {
+            let bit: TaskMask = 1 << idx;
+            let Some(task) = kernel.scheduler.get_task(idx) else {
+                self.pending &= !bit;
+                continue;
+            };
+            let released = match task.state {
+                TaskState::Ready => true,
+                TaskState::Sleeping => {
+                    task.time_released() && tick_reached(now_tick, task.next_activation)
+                }
+                _ => false,
+            };
+            if self.pending & bit == 0 && (released || executed == Some(idx)) {
+                // ISR / 連鎖リリースは next_activation = 今、周期リリースは予定時刻
+                let release = if executed == Some(idx) && task.time_released() {
+                    scheduled_at
+                } else {
+                    task.next_activation
+                };
+                let release_us = now - tick_to_u64(ticks_since(now_tick, release));
+                let deadline = match self.relative_us[idx] {
+                    0 => task.period_us,
+                    d => d,
+                };
+                if deadline == 0 {
+                    continue;
+                }
+                self.absolute_us[idx] = release_us + u64::from(deadline);
+                self.pending |= bit;
+                self.late &= !bit;
+            }
+            if self.pending & bit == 0 {
+                continue;
+            }
+            if self.late & bit == 0 && now > self.absolute_us[idx] {
+                self.late |= bit;
+                self.misses[idx] = self.misses[idx].saturating_add(1);
+            }
+            if executed == Some(idx) || !released {
+                // 完了 (実行は tick 内で終わる) または中断・停止
+                self.pending &= !bit;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::task::{Task, TaskPriority};
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_constrained_deadline_missed_without_dispatch() {
+        let mut k = Kernel::testing();
+        k.add_task(b"hog", noop, TaskPriority::HIGH, 100, 10);
+        let lo = k
+            .add_task(b"lo", noop, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let mut tracker = DeadlineTracker::new();
+        assert!(!tracker.set_relative(&k, lo, 1_001));
+        assert!(!tracker.set_relative(&k, 5, 100));
+        assert!(tracker.set_relative(&k, lo, 250));
+        assert_eq!(tracker.relative_us(&k, lo), 250);
+        assert_eq!(tracker.relative_us(&k, 0), 100);
+
+        // hog が毎 tick 実行され、lo は t = 250 の締切を CPU なしで逃す
+        for _ in 0..2 {
+            assert_eq!(tracker.tick(&mut k, 100), Some(0));
+        }
+        assert_eq!(tracker.absolute(lo), Some(250));
+        assert_eq!(tracker.misses(lo), 0);
+        assert_eq!(tracker.tick(&mut k, 100), Some(0));
+        assert_eq!(tracker.misses(lo), 1);
+        // 1 ジョブにつき 1 回だけ
+        tracker.tick(&mut k, 100);
+        assert_eq!(tracker.misses(lo), 1);
+        // 暗黙の締切 (1 000) には間に合っている
+        assert_eq!(k.scheduler.get_task(lo).unwrap().deadline_misses, 0);
+    }
+
+    #[test]
+    fn test_deadline_independent_of_mode_change() {
+        let mut k = Kernel::testing();
+        k.add_task(b"hog", noop, TaskPriority::HIGH, 100, 10);
+        let ctl = k
+            .add_task(b"ctl", noop, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let mut tracker = DeadlineTracker::new();
+        assert!(tracker.set_relative(&k, ctl, 600));
+        assert_eq!(tracker.tick(&mut k, 0), Some(0));
+        assert_eq!(tracker.absolute(ctl), Some(600));
+        // 保留中の周期変更: next_activation は境界 1 000 を持つが締切は 600 のまま
+        assert!(k.scheduler.set_period(ctl, 2_000));
+        assert_eq!(k.scheduler.get_task(ctl).unwrap().next_activation, 1_000);
+        assert_eq!(tracker.tick(&mut k, 100), Some(0));
+        assert_eq!(tracker.absolute(ctl), Some(600));
+        // hog の空き時刻に実行、次ジョブは境界 1 000 + D
+        let mut t = 100;
+        while tracker.tick(&mut k, 50) != Some(ctl) {
+            t += 50;
+        }
+        assert!(t < 600);
+        assert_eq!(tracker.absolute(ctl), None);
+        while k.scheduler.get_task(ctl).unwrap().state != TaskState::Ready {
+            tracker.tick(&mut k, 50);
+        }
+        assert_eq!(tracker.absolute(ctl), Some(1_600));
+        assert_eq!(tracker.misses(ctl), 0);
+    }
+
+    #[test]
+    fn test_isr_release_deadline_from_release_instant() {
+        let mut k = Kernel::testing();
+        let task = Task::new(b"dma", noop, TaskPriority::LOW, 0, 10).released_by_isr();
+        let dma = k.scheduler.register(task).unwrap();
+        k.add_task(b"hog", noop, TaskPriority::HIGH, 100, 10);
+        let mut tracker = DeadlineTracker::new();
+        assert!(tracker.set_relative(&k, dma, 150));
+        tracker.tick(&mut k, 0);
+        tracker.run_for(&mut k, 1_000, 100);
+        assert_eq!(tracker.absolute(dma), None);
+
+        // t = 1 100 にリリース、hog の後 1 150 に実行
+        k.scheduler.release_from_isr(dma);
+        assert_eq!(tracker.tick(&mut k, 100), Some(1));
+        assert_eq!(tracker.absolute(dma), Some(1_250));
+        assert_eq!(tracker.tick(&mut k, 50), Some(dma));
+        assert_eq!((tracker.misses(dma), tracker.absolute(dma)), (0, None));
+
+        // t = 1 200 にリリース、次の tick (1 400) も hog が取る → 1 350 を逃す
+        k.scheduler.release_from_isr(dma);
+        assert_eq!(tracker.tick(&mut k, 50), Some(1));
+        assert_eq!(tracker.absolute(dma), Some(1_350));
+        assert_eq!(tracker.tick(&mut k, 200), Some(1));
+        assert_eq!(tracker.misses(dma), 1);
+    }
+}
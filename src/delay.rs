@@ -0,0 +1,207 @@
+//! embedded-hal 1.0 delays on the RTOS time base (`embedded-hal` feature)
+//!
+//! HAL driver crates take a `DelayNs` implementation; these let them reuse
+//! the kernel's microsecond clock instead of claiming another timer
+//! peripheral.
+//!
+//! - [`SysTimer`] implements `DelayNs` directly. It is a software clock,
+//!   so a delay advances it (as [`SysTimer::delay_us`] does) — useful in
+//!   host tests and single-context code.
+//! - [`SharedTimer`] is the ISR-safe form for real hardware: the tick
+//!   interrupt calls [`SharedTimer::advance`], and `&SharedTimer` busy-waits
+//!   in `DelayNs` until the time has passed. [`Countdown`] is the
+//!   non-blocking variant for polling loops.
+//!
+//! Delays have microsecond resolution; nanosecond requests round up.
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+
+use crate::timer::SysTimer;
+
+/// Whole microseconds covering `ns` (rounded up)
+const fn ns_to_us(ns: u32) -> u64 {
+    (ns as u64).div_ceil(1_000)
+}
+
+impl DelayNs for SysTimer {
+    fn delay_ns(&mut self, ns: u32) {
+        self.advance(ns_to_us(ns));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.advance(us as u64);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.advance(ms as u64 * 1_000);
+    }
+}
+
+/// Microsecond clock shared between the tick interrupt and drivers
+///
+/// Usable in a `static`; every access runs in a critical section, so a
+/// 64-bit time is safe on cores without 64-bit atomics.
+pub struct SharedTimer {
+    /// Current time (µs)
+    now_us: Mutex<Cell<u64>>,
+}
+
+impl Default for SharedTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedTimer {
+    /// Clock at t = 0
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            now_us: Mutex::new(Cell::new(0)),
+        }
+    }
+
+    /// Advance by `us` (call from the tick interrupt)
+    pub fn advance(&self, us: u64) {
+        critical_section::with(|cs| {
+            let now = self.now_us.borrow(cs);
+            now.set(now.get().wrapping_add(us));
+        });
+    }
+
+    /// Copy the time of `timer` (e.g. the kernel's after each tick)
+    pub fn sync(&self, timer: &SysTimer) {
+        critical_section::with(|cs| self.now_us.borrow(cs).set(timer.now_us()));
+    }
+
+    /// Current time (µs)
+    #[must_use]
+    pub fn now_us(&self) -> u64 {
+        critical_section::with(|cs| self.now_us.borrow(cs).get())
+    }
+
+    /// Countdown expiring `us` from now
+    #[must_use]
+    pub fn countdown(&self, us: u64) -> Countdown<'_> {
+        Countdown {
+            timer: self,
+            deadline: self.now_us() + us,
+        }
+    }
+
+    /// Busy-wait until `us` have passed
+    fn wait_us(&self, us: u64) {
+        let countdown = self.countdown(us);
+        while !countdown.is_expired() {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl DelayNs for &SharedTimer {
+    fn delay_ns(&mut self, ns: u32) {
+        self.wait_us(ns_to_us(ns));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.wait_us(us as u64);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.wait_us(ms as u64 * 1_000);
+    }
+}
+
+/// Non-blocking timeout on a [`SharedTimer`]
+pub struct Countdown<'a> {
+    /// Time base
+    timer: &'a SharedTimer,
+    /// Expiry (µs)
+    deadline: u64,
+}
+
+impl Countdown<'_> {
+    /// Has the countdown run out?
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.timer.now_us() >= self.deadline
+    }
+
+    /// Microseconds left (0 once expired)
+    #[must_use]
+    pub fn remaining_us(&self) -> u64 {
+        self.deadline.saturating_sub(self.timer.now_us())
+    }
+
+    /// Restart for `us` from now
+    pub fn restart(&mut self, us: u64) {
+        self.deadline = self.timer.now_us() + us;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::thread;
+
+    /// HAL ドライバ側の典型的な呼び出し
+    fn driver_init<D: DelayNs>(delay: &mut D) {
+        delay.delay_ms(2);
+        delay.delay_us(30);
+        delay.delay_ns(1);
+    }
+
+    #[test]
+    fn test_sys_timer_delay_advances() {
+        let mut timer = SysTimer::software();
+        driver_init(&mut timer);
+        // 1 ns は 1 µs に切り上げ
+        assert_eq!(timer.now_us(), 2_031);
+    }
+
+    #[test]
+    fn test_countdown() {
+        let timer = SharedTimer::new();
+        let mut cd = timer.countdown(100);
+        assert!(!cd.is_expired());
+        timer.advance(60);
+        assert_eq!(cd.remaining_us(), 40);
+        timer.advance(40);
+        assert!(cd.is_expired());
+        cd.restart(10);
+        assert_eq!(cd.remaining_us(), 10);
+
+        let mut sys = SysTimer::software();
+        sys.advance(5_000);
+        timer.sync(&sys);
+        assert_eq!(timer.now_us(), 5_000);
+    }
+
+    #[test]
+    fn test_shared_delay_waits_for_tick_source() {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        static TIMER: SharedTimer = SharedTimer::new();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        // 別スレッドを tick 割り込みに見立てる
+        let ticker = thread::spawn(|| {
+            while !DONE.load(Ordering::Relaxed) {
+                TIMER.advance(10);
+                thread::yield_now();
+            }
+        });
+        let start = TIMER.now_us();
+        let mut delay = &TIMER;
+        delay.delay_us(500);
+        assert!(TIMER.now_us() >= start + 500);
+        DONE.store(true, Ordering::Relaxed);
+        ticker.join().unwrap();
+    }
+}
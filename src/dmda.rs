@@ -5,6 +5,7 @@
 //!
 //! Author: Moroya Sakamoto
 
+use crate::scheduler::Overhead;
 use crate::task::{Task, MAX_TASKS};
 
 /// RTA 結果。
@@ -43,6 +44,37 @@ pub struct DmdaReport {
 /// `task_count` は有効タスク数。
 #[must_use]
 pub fn analyze(tasks: &[Task; MAX_TASKS], task_count: usize) -> DmdaReport {
+    analyze_with_blocking(tasks, task_count, &[0; MAX_TASKS])
+}
+
+/// ブロッキング項付きの Response-Time Analysis。
+///
+///   `R_i(n+1) = C_i + B_i + Σ(j ∈ hp(i)) ⌈R_i(n) / T_j⌉ × C_j`
+///
+/// `blocking[i]` は低優先度タスクによるタスク `i` の最大ブロッキング時間
+/// （例: [`PriorityCeilingManager::blocking_terms`](crate::priority_ceiling::PriorityCeilingManager::blocking_terms)）。
+#[must_use]
+pub fn analyze_with_blocking(
+    tasks: &[Task; MAX_TASKS],
+    task_count: usize,
+    blocking: &[u32; MAX_TASKS],
+) -> DmdaReport {
+    analyze_with_overhead(tasks, task_count, blocking, &Overhead::NONE)
+}
+
+/// スケジューラのオーバーヘッド込みの Response-Time Analysis。
+///
+///   `R_i(n+1) = C'_i + B_i + Σ(j ∈ hp(i)) ⌈R_i(n) / T_j⌉ × C'_j + ⌈R_i(n) / T_tick⌉ × C_tick`
+///
+/// `C'` は 1 ジョブあたり 2 回のコンテキストスイッチを加えた実行時間
+/// （[`Overhead::job_cost_us`]）、最後の項はティック割り込みの干渉。
+#[must_use]
+pub fn analyze_with_overhead(
+    tasks: &[Task; MAX_TASKS],
+    task_count: usize,
+    blocking: &[u32; MAX_TASKS],
+    overhead: &Overhead,
+) -> DmdaReport {
     let mut report = DmdaReport {
         results: [None; MAX_TASKS],
         task_count,
@@ -74,19 +106,20 @@ pub fn analyze(tasks: &[Task; MAX_TASKS], task_count: usize) -> DmdaReport {
     for rank in 0..count {
         let idx = sorted_indices[rank];
         let task = &tasks[idx];
-        let wcet = task.wcet_us;
+        let wcet = overhead.job_cost_us(task.wcet_us);
         let period = task.period_us;
 
         if period == 0 {
             continue;
         }
 
-        // 反復計算: R = C_i + Σ hp ⌈R / T_j⌉ × C_j
-        let mut r = wcet;
+        // 反復計算: R = C_i + B_i + Σ hp ⌈R / T_j⌉ × C_j + ティック干渉
+        let base = wcet.saturating_add(blocking[idx]);
+        let mut r = base;
         let max_iterations = 100_u32;
 
         for _ in 0..max_iterations {
-            let mut interference: u64 = 0;
+            let mut interference = overhead.tick_interference_us(r);
 
             // 高優先度タスク（rank より前）からの干渉
             for &hp_idx in &sorted_indices[..rank] {
@@ -96,10 +129,10 @@ pub fn analyze(tasks: &[Task; MAX_TASKS], task_count: usize) -> DmdaReport {
                 }
                 // ⌈R / T_j⌉ × C_j
                 let preemptions = u64::from(r).div_ceil(u64::from(hp.period_us));
-                interference += preemptions * u64::from(hp.wcet_us);
+                interference += preemptions * u64::from(overhead.job_cost_us(hp.wcet_us));
             }
 
-            let new_r = u64::from(wcet) + interference;
+            let new_r = u64::from(base) + interference;
             let new_r = if new_r > u64::from(u32::MAX) {
                 u32::MAX
             } else {
@@ -321,4 +354,49 @@ mod tests {
         // T1 がデッドライン超過 → マージン負
         assert_eq!(critical.task_index, 1);
     }
+
+    #[test]
+    fn zero_blocking_matches_analyze() {
+        let (tasks, count) = make_tasks(&[
+            (TaskPriority::CRITICAL, 50, 10),
+            (TaskPriority::HIGH, 100, 20),
+        ]);
+        let a = analyze(&tasks, count);
+        let b = analyze_with_blocking(&tasks, count, &[0; MAX_TASKS]);
+        assert_eq!(a.results, b.results);
+    }
+
+    #[test]
+    fn overhead_inflates_response() {
+        // T0: C=10+2×1, T1: C=20+2×1; ティック 2 µs / 25 µs
+        // R0: 12 → 12+⌈12/25⌉×2=14 → 14
+        // R1: 22 → 22+12+2=36 → 22+12+⌈36/25⌉×2=38 → 38
+        let (tasks, count) = make_tasks(&[
+            (TaskPriority::CRITICAL, 50, 10),
+            (TaskPriority::HIGH, 100, 20),
+        ]);
+        let overhead = Overhead::new(1, 2, 25);
+        let report = analyze_with_overhead(&tasks, count, &[0; MAX_TASKS], &overhead);
+        assert_eq!(report.results[0].unwrap().worst_case_response, 14);
+        assert_eq!(report.results[1].unwrap().worst_case_response, 38);
+        assert!(report.all_schedulable);
+
+        // 重いティックでは T1 が締め切りを落とす
+        let heavy = Overhead::new(5, 10, 20);
+        let report = analyze_with_overhead(&tasks, count, &[0; MAX_TASKS], &heavy);
+        assert!(!report.results[1].unwrap().meets_deadline);
+    }
+
+    #[test]
+    fn blocking_adds_to_response() {
+        let (tasks, count) = make_tasks(&[
+            (TaskPriority::CRITICAL, 50, 10),
+            (TaskPriority::HIGH, 100, 20),
+        ]);
+        let mut blocking = [0; MAX_TASKS];
+        blocking[0] = 5;
+        let report = analyze_with_blocking(&tasks, count, &blocking);
+        assert_eq!(report.results[0].unwrap().worst_case_response, 15);
+        assert!(report.all_schedulable);
+    }
 }
@@ -0,0 +1,293 @@
+//! Time domains: several kernels side by side, each on its own clock
+//!
+//! A [`Kernel`] keeps all of its state — task table, timer, scratch,
+//! links, attached pools — in the instance, so any number of kernels can
+//! run side by side, e.g. an audio kernel ticked every 20 µs and a motion
+//! kernel ticked every millisecond, each from its own timer interrupt. The
+//! process-wide pieces are explicit and shared by every instance: the
+//! installed fault [`BlackBox`](crate::fault::BlackBox), the event
+//! backends of [`events`](crate::events), the SVC table of the `mpu`
+//! feature and the software cycle counter of
+//! [`profiler::cycles`](crate::profiler::cycles).
+//!
+//! Each kernel counts µs from its own start, and a disciplined timer may
+//! run at a slightly different rate, so a [`Stamped`] item produced in one
+//! kernel means nothing on the other's clock. A [`TimeDomain`] ties a
+//! kernel's clock to a board-wide reference clock — where the domain's
+//! zero lies on the reference ([`starting_at`](TimeDomain::starting_at)),
+//! the rate error between them ([`with_drift_ppb`](TimeDomain::with_drift_ppb))
+//! and the domain's tick period — and translates instants between
+//! domains. Channels crossing domains are read with
+//! [`Receiver::recv_from`](crate::channel::Receiver::recv_from) /
+//! [`recv_fresh_from`](crate::channel::Receiver::recv_fresh_from), which
+//! move each stamp onto the consumer's clock.
+//!
+//! Translations are exact to the µs, so long as the instants stay within
+//! half the tick range of the domain's anchor (see [`WrappingInstant`]).
+//!
+//! ```rust
+//! use alice_rtos::channel::{Channel, Stamped};
+//! use alice_rtos::domain::TimeDomain;
+//! use alice_rtos::{Kernel, TaskPriority, WrappingInstant};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! static LEVELS: Channel<Stamped<u16>, 8> = Channel::new();
+//!
+//! // Audio starts at power-up, motion 5 ms later
+//! let audio = TimeDomain::new(20);
+//! let motion = TimeDomain::new(1_000).starting_at(WrappingInstant::new(5_000));
+//! let mut audio_k = Kernel::testing();
+//! let mut motion_k = Kernel::testing();
+//! audio_k.add_task(b"mix", noop, TaskPriority::HIGH, 100, 10).unwrap();
+//! motion_k.add_task(b"servo", noop, TaskPriority::HIGH, 2_000, 200).unwrap();
+//!
+//! let (mut tx, mut rx) = LEVELS.split().unwrap();
+//! audio.run_for(&mut audio_k, 6_000);
+//! tx.send_at(512, audio_k.scheduler.now());
+//! motion.run_for(&mut motion_k, 1_000);
+//!
+//! // Sent at audio t = 6 000 µs = motion t = 1 000 µs: fresh for the servo
+//! let level = rx.recv_fresh_from(&audio, &motion, motion_k.scheduler.now(), 500).unwrap();
+//! assert_eq!(level.at, WrappingInstant::new(1_000));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{KernelStats, SizedKernel};
+use crate::policy::SchedulingPolicy;
+use crate::timer::{Tick, TickDiff, WrappingInstant};
+
+#[cfg(doc)]
+use crate::channel::Stamped;
+#[cfg(doc)]
+use crate::kernel::Kernel;
+
+/// One part per billion
+const PPB: i64 = 1_000_000_000;
+
+/// A kernel's clock, placed on the board's reference clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeDomain {
+    /// Tick period of the domain's kernel (µs)
+    tick_us: u32,
+    /// Domain instant of the anchor
+    local: WrappingInstant,
+    /// Reference instant of the anchor
+    reference: WrappingInstant,
+    /// Rate error (ppb, positive = domain clock slow)
+    drift_ppb: i32,
+}
+
+impl TimeDomain {
+    /// Domain ticked every `tick_us`, aligned with the reference at 0
+    #[must_use]
+    pub const fn new(tick_us: u32) -> Self {
+        Self {
+            tick_us: if tick_us == 0 { 1 } else { tick_us },
+            local: WrappingInstant::new(0),
+            reference: WrappingInstant::new(0),
+            drift_ppb: 0,
+        }
+    }
+
+    /// Domain whose time 0 is reference instant `epoch`
+    #[must_use]
+    pub const fn starting_at(mut self, epoch: WrappingInstant) -> Self {
+        self.local = WrappingInstant::new(0);
+        self.reference = epoch;
+        self
+    }
+
+    /// Domain clock running `ppb` parts per billion slow (negative: fast)
+    #[must_use]
+    pub const fn with_drift_ppb(mut self, ppb: i32) -> Self {
+        self.drift_ppb = ppb;
+        self
+    }
+
+    /// Re-anchor: domain instant `local` is reference instant `reference`
+    ///
+    /// Call it with a fresh measurement (a shared sync pulse, a PTP
+    /// exchange) to cancel accumulated drift.
+    pub const fn sync(&mut self, local: WrappingInstant, reference: WrappingInstant) {
+        self.local = local;
+        self.reference = reference;
+    }
+
+    /// Tick period of the domain (µs)
+    #[must_use]
+    pub const fn tick_us(&self) -> u32 {
+        self.tick_us
+    }
+
+    /// Rate error (ppb, positive = domain clock slow)
+    #[must_use]
+    pub const fn drift_ppb(&self) -> i32 {
+        self.drift_ppb
+    }
+
+    /// Domain instant `local` on the reference clock
+    #[must_use]
+    #[allow(clippy::unnecessary_cast)] // i64 → i64 without `tick-u32`
+    pub const fn to_reference(&self, local: WrappingInstant) -> WrappingInstant {
+        let d = local.diff(self.local) as i64;
+        let d = d + scale(d, self.drift_ppb as i64, PPB);
+        self.reference.add(d as TickDiff as Tick)
+    }
+
+    /// Reference instant `reference` on the domain clock
+    #[must_use]
+    #[allow(clippy::unnecessary_cast)] // i64 → i64 without `tick-u32`
+    pub const fn from_reference(&self, reference: WrappingInstant) -> WrappingInstant {
+        let e = reference.diff(self.reference) as i64;
+        // d + d·ppb/1e9 = e  ⇒  d = e − e·ppb/(1e9 + ppb)
+        let d = e - scale(e, self.drift_ppb as i64, PPB + self.drift_ppb as i64);
+        self.local.add(d as TickDiff as Tick)
+    }
+
+    /// Domain instant `at` on the clock of domain `to`
+    #[must_use]
+    pub const fn translate(&self, at: WrappingInstant, to: &Self) -> WrappingInstant {
+        to.from_reference(self.to_reference(at))
+    }
+
+    /// Current time of `kernel` on the reference clock
+    #[must_use]
+    pub fn now<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &SizedKernel<SCRATCH, P>,
+    ) -> WrappingInstant {
+        self.to_reference(kernel.scheduler.now())
+    }
+
+    /// One tick of `kernel` at the domain's period
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) -> Option<usize> {
+        kernel.tick(u64::from(self.tick_us))
+    }
+
+    /// Run `kernel` for `total_us` at the domain's tick period
+    pub fn run_for<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        total_us: u64,
+    ) -> KernelStats {
+        kernel.run_for(total_us, u64::from(self.tick_us))
+    }
+}
+
+/// `x · num / den` without overflowing for any µs span
+const fn scale(x: i64, num: i64, den: i64) -> i64 {
+    x / den * num + x % den * num / den
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::{Channel, Stamped};
+    use crate::kernel::Kernel;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_offset_translation_round_trips() {
+        let a = TimeDomain::new(20);
+        let b = TimeDomain::new(1_000).starting_at(WrappingInstant::new(5_000));
+        assert_eq!(
+            a.translate(WrappingInstant::new(7_250), &b),
+            WrappingInstant::new(2_250)
+        );
+        assert_eq!(
+            b.translate(WrappingInstant::new(2_250), &a),
+            WrappingInstant::new(7_250)
+        );
+        // b の起動前の時刻は負の方向に折り返す
+        assert!(a
+            .translate(WrappingInstant::new(1_000), &b)
+            .is_before(WrappingInstant::new(0)));
+    }
+
+    #[test]
+    fn test_drift_is_inverted_exactly() {
+        // 100 ppm 遅い時計: 10 s で 1 ms 遅れる
+        let slow = TimeDomain::new(100).with_drift_ppb(100_000);
+        let reference = TimeDomain::new(100);
+        let at = WrappingInstant::new(10_000_000);
+        assert_eq!(slow.to_reference(at), WrappingInstant::new(10_001_000));
+        assert_eq!(
+            slow.translate(at, &reference),
+            WrappingInstant::new(10_001_000)
+        );
+        assert_eq!(
+            reference.translate(WrappingInstant::new(10_001_000), &slow),
+            at
+        );
+        assert_eq!(slow.drift_ppb(), 100_000);
+    }
+
+    #[test]
+    fn test_sync_cancels_accumulated_drift() {
+        let mut d = TimeDomain::new(100).with_drift_ppb(-50_000);
+        let local = WrappingInstant::new(20_000_000);
+        assert_eq!(d.to_reference(local), WrappingInstant::new(19_999_000));
+        // 同期パルスで再アンカー
+        d.sync(local, WrappingInstant::new(20_000_000));
+        assert_eq!(d.to_reference(local), WrappingInstant::new(20_000_000));
+        assert_eq!(
+            d.to_reference(WrappingInstant::new(22_000_000)),
+            WrappingInstant::new(21_999_900)
+        );
+    }
+
+    #[test]
+    fn test_two_kernels_are_isolated() {
+        let audio = TimeDomain::new(20);
+        let motion = TimeDomain::new(1_000).starting_at(WrappingInstant::new(300));
+        let mut a = Kernel::testing();
+        let mut m = Kernel::testing();
+        a.add_task(b"mix", noop, TaskPriority::HIGH, 100, 10);
+        a.add_task(b"eq", noop, TaskPriority::LOW, 200, 10);
+        m.add_task(b"servo", noop, TaskPriority::HIGH, 2_000, 200);
+        a.start().unwrap();
+
+        // 交互にティックしても互いの状態に触れない
+        let mut audio_runs = 0;
+        let mut motion_runs = 0;
+        for _ in 0..10 {
+            for _ in 0..50 {
+                audio_runs += u32::from(audio.tick(&mut a).is_some());
+            }
+            motion_runs += u32::from(motion.tick(&mut m).is_some());
+        }
+        assert_eq!(a.scheduler.now_us(), 10_000);
+        assert_eq!(m.scheduler.now_us(), 10_000);
+        assert_eq!((audio_runs, motion_runs), (151, 6));
+        assert_eq!(a.scheduler.active_task_count(), 2);
+        assert_eq!(m.scheduler.active_task_count(), 1);
+        assert!(a.is_running() && !m.is_running());
+        assert_eq!(audio.now(&a), WrappingInstant::new(10_000));
+        assert_eq!(motion.now(&m), WrappingInstant::new(10_300));
+    }
+
+    #[test]
+    fn test_crossing_channel_translates_stamps() {
+        static CH: Channel<Stamped<u8>, 4> = Channel::new();
+        let audio = TimeDomain::new(20);
+        let motion = TimeDomain::new(1_000).starting_at(WrappingInstant::new(4_000));
+        let (mut tx, mut rx) = CH.split().unwrap();
+        tx.send_at(1, WrappingInstant::new(4_500));
+        tx.send_at(2, WrappingInstant::new(5_000));
+        let item = rx.recv_from(&audio, &motion).unwrap();
+        assert_eq!(item.at, WrappingInstant::new(500));
+        // 消費側 t = 2 500: 1 000 µs より古いものは捨てる
+        tx.send_at(3, WrappingInstant::new(6_400));
+        let now = WrappingInstant::new(2_500);
+        let fresh = rx.recv_fresh_from(&audio, &motion, now, 1_000).unwrap();
+        assert_eq!((fresh.value, fresh.at), (3, WrappingInstant::new(2_400)));
+        assert_eq!(rx.stale_dropped(), 1);
+    }
+}
@@ -0,0 +1,277 @@
+//! Inter-processor doorbells: poke the other core when work is queued
+//!
+//! A [`Doorbell`] is one core's handle on a doorbell pair: [`signal`]
+//! rings the remote core, [`poll`] tells whether the remote core rang
+//! this one, [`clear`] acknowledges. Rings carry no data and coalesce —
+//! the woken core looks at its queues, not at the number of rings — and
+//! signalling never blocks.
+//!
+//! - [`SoftDoorbell`] rings through an atomic flag per side of a static
+//!   [`DoorbellPair`], for two kernels sharing one address space (host
+//!   simulation, tests, cores without a mailbox peripheral);
+//! - `SioFifo` (`rp2040` feature) rings through the RP2040 SIO
+//!   inter-core FIFO and sends `SEV`, waking a core parked in `WFE`, or in
+//!   `WFI` with its `SIO_IRQ_PROCn` interrupt enabled.
+//!
+//! The cross-core [`Router`](crate::router::Router) rings the receiving
+//! side on each send ([`send_and_signal`](crate::router::Router::send_and_signal)),
+//! and [`Kernel::tick_core_waking`] rings the other core when it leaves
+//! ready work that only the other core may run.
+//!
+//! [`signal`]: Doorbell::signal
+//! [`poll`]: Doorbell::poll
+//! [`clear`]: Doorbell::clear
+//!
+//! ```rust
+//! use alice_rtos::doorbell::{Doorbell, DoorbellPair};
+//! use alice_rtos::router::Side;
+//!
+//! static BELLS: DoorbellPair = DoorbellPair::new();
+//!
+//! let core0 = BELLS.handle(Side::A);
+//! let core1 = BELLS.handle(Side::B);
+//! core0.signal();
+//! core0.signal();
+//! assert!(core1.poll() && !core0.poll());
+//! assert!(core1.clear());
+//! assert!(!core1.clear());
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicBool, Ordering};
+use crate::router::Side;
+
+#[cfg(doc)]
+use crate::kernel::Kernel;
+
+/// One core's handle on a doorbell between two cores
+pub trait Doorbell {
+    /// Ring the remote core (never blocks; pending rings coalesce)
+    fn signal(&self);
+
+    /// Has the remote core rung this one since the last [`clear`](Self::clear)?
+    fn poll(&self) -> bool;
+
+    /// Acknowledge the pending rings; returns whether there were any
+    ///
+    /// Clear before looking at the queues, so a ring that arrives while
+    /// they are being drained is kept for the next round.
+    fn clear(&self) -> bool;
+}
+
+/// Two doorbell flags in shared memory, one per side
+pub struct DoorbellPair {
+    /// Flag `s` is set when side `s` has been rung
+    rung: [AtomicBool; 2],
+}
+
+impl Default for DoorbellPair {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DoorbellPair {
+    /// Both doorbells quiet (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            rung: [AtomicBool::new(false), AtomicBool::new(false)],
+        }
+    }
+
+    /// Doorbell of side `side`
+    #[must_use]
+    pub const fn handle(&self, side: Side) -> SoftDoorbell<'_> {
+        SoftDoorbell { pair: self, side }
+    }
+}
+
+/// One side's handle on a [`DoorbellPair`]
+#[derive(Clone, Copy)]
+pub struct SoftDoorbell<'a> {
+    /// Shared flags
+    pair: &'a DoorbellPair,
+    /// Side this handle belongs to
+    side: Side,
+}
+
+impl Doorbell for SoftDoorbell<'_> {
+    fn signal(&self) {
+        self.pair.rung[self.side.other() as usize].store(true, Ordering::Release);
+    }
+
+    fn poll(&self) -> bool {
+        self.pair.rung[self.side as usize].load(Ordering::Acquire)
+    }
+
+    fn clear(&self) -> bool {
+        self.pair.rung[self.side as usize].swap(false, Ordering::AcqRel)
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use sio::{SioFifo, DOORBELL_TOKEN, SIO_BASE};
+
+#[cfg(feature = "rp2040")]
+mod sio {
+    use core::ptr;
+
+    use super::Doorbell;
+
+    /// Base address of the RP2040 SIO block (core-local aliases)
+    pub const SIO_BASE: usize = 0xD000_0000;
+
+    /// Word pushed into the FIFO for each ring
+    pub const DOORBELL_TOKEN: u32 = 0xA11C_EDB1;
+
+    /// `FIFO_ST`: status (VLD, RDY, sticky WOF / ROE)
+    const FIFO_ST: usize = 0x50;
+    /// `FIFO_WR`: push towards the other core
+    const FIFO_WR: usize = 0x54;
+    /// `FIFO_RD`: pop from the other core
+    const FIFO_RD: usize = 0x58;
+
+    /// RX FIFO not empty
+    const ST_VLD: u32 = 1 << 0;
+    /// TX FIFO not full
+    const ST_RDY: u32 = 1 << 1;
+    /// Sticky write-on-full / read-on-empty flags (write to clear)
+    const ST_ERRORS: u32 = 0b1100;
+
+    /// Entries in each direction of the FIFO
+    const FIFO_DEPTH: usize = 8;
+
+    /// Doorbell over the RP2040 SIO inter-core FIFO
+    ///
+    /// The SIO registers are aliased per core, so the same value works on
+    /// both cores: each one pushes to the other and pops its own side.
+    pub struct SioFifo {
+        /// SIO base address
+        base: usize,
+    }
+
+    impl SioFifo {
+        /// Doorbell on the SIO of this core
+        ///
+        /// # Safety
+        ///
+        /// Must run on an RP2040 whose cores are both started; from then
+        /// on the inter-core FIFO belongs to the doorbell (every word
+        /// pushed by the other core counts as a ring).
+        #[must_use]
+        pub const unsafe fn new() -> Self {
+            Self { base: SIO_BASE }
+        }
+
+        /// Doorbell on an SIO block at `base`
+        ///
+        /// # Safety
+        ///
+        /// `base` must be an SIO register block; see [`SioFifo::new`].
+        #[must_use]
+        pub const unsafe fn at(base: usize) -> Self {
+            Self { base }
+        }
+
+        fn status(&self) -> u32 {
+            // SAFETY: `new` / `at` の契約により有効な SIO レジスタ
+            unsafe { ptr::read_volatile((self.base + FIFO_ST) as *const u32) }
+        }
+    }
+
+    impl Doorbell for SioFifo {
+        fn signal(&self) {
+            // 満杯なら相手には既に未処理の呼び出しがある
+            if self.status() & ST_RDY != 0 {
+                // SAFETY: `new` / `at` の契約により有効な SIO レジスタ
+                unsafe { ptr::write_volatile((self.base + FIFO_WR) as *mut u32, DOORBELL_TOKEN) };
+            }
+            #[cfg(target_arch = "arm")]
+            // SAFETY: イベント送出のみ
+            unsafe {
+                core::arch::asm!("sev", options(nomem, nostack, preserves_flags));
+            }
+        }
+
+        fn poll(&self) -> bool {
+            self.status() & ST_VLD != 0
+        }
+
+        fn clear(&self) -> bool {
+            let mut rung = false;
+            for _ in 0..FIFO_DEPTH {
+                if self.status() & ST_VLD == 0 {
+                    break;
+                }
+                // SAFETY: `new` / `at` の契約により有効な SIO レジスタ
+                unsafe { ptr::read_volatile((self.base + FIFO_RD) as *const u32) };
+                rung = true;
+            }
+            // SAFETY: 同上 (WOF / ROE のクリア)
+            unsafe { ptr::write_volatile((self.base + FIFO_ST) as *mut u32, ST_ERRORS) };
+            rung
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        extern crate std;
+
+        use super::*;
+        use std::boxed::Box;
+
+        /// ホスト上の疑似 SIO (0x60 バイト)
+        #[repr(C, align(4))]
+        struct FakeSio([u32; 0x18]);
+
+        fn reg(base: usize, offset: usize) -> *mut u32 {
+            (base + offset) as *mut u32
+        }
+
+        #[test]
+        fn test_sio_fifo_registers() {
+            let base = Box::into_raw(Box::new(FakeSio([0; 0x18]))) as usize;
+            // SAFETY: 疑似 SIO
+            let bell = unsafe { SioFifo::at(base) };
+            let (st, wr) = (reg(base, FIFO_ST), reg(base, FIFO_WR));
+            unsafe {
+                // TX 満杯: 書き込まない
+                bell.signal();
+                assert_eq!(ptr::read_volatile(wr), 0);
+                ptr::write_volatile(st, ST_RDY);
+                bell.signal();
+                assert_eq!(ptr::read_volatile(wr), DOORBELL_TOKEN);
+
+                assert!(!bell.poll());
+                assert!(!bell.clear());
+                ptr::write_volatile(st, ST_VLD | ST_RDY);
+                assert!(bell.poll());
+                assert!(bell.clear());
+                assert_eq!(ptr::read_volatile(st), ST_ERRORS);
+                drop(Box::from_raw(base as *mut FakeSio));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_doorbell_rings_the_other_side() {
+        let pair = DoorbellPair::new();
+        let (a, b) = (pair.handle(Side::A), pair.handle(Side::B));
+        assert!(!a.poll() && !b.poll());
+        b.signal();
+        assert!(a.poll() && !b.poll());
+        a.signal();
+        assert!(b.clear());
+        // 解除は自分の側だけ
+        assert!(a.poll());
+        assert!(a.clear());
+        assert!(!a.poll() && !a.clear());
+    }
+}
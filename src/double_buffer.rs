@@ -0,0 +1,300 @@
+//! Double-buffered state exchange (parameter sets for control loops)
+//!
+//! A controller must keep using one consistent set of gains for a whole
+//! job while a low-priority tuning task prepares the next set.
+//! [`DoubleBuffer<T>`] holds two slots: the reader borrows the *front*
+//! slot for as long as it holds a [`Front`] guard, and
+//! [`publish`](DoubleBufferWriter::publish) writes the *back* slot and
+//! then flips the front index. Taking the guard at the start of each job
+//! means new parameters are picked up at period boundaries and never torn.
+//!
+//! The buffer is [`split`](DoubleBuffer::split) once into a
+//! [`DoubleBufferWriter`] and a [`DoubleBufferReader`], so there is a
+//! single writer and a single reader, and the reader holds at most one
+//! guard at a time.
+//!
+//! The reader announces the slot it uses before touching it and checks
+//! that the front did not move meanwhile; the writer refuses to overwrite
+//! an announced slot. Only atomic loads and stores are used (no CAS), so
+//! the buffer also works on Cortex-M0+. On one core a high-priority reader
+//! always completes before the preempted writer resumes, so `publish`
+//! never finds its slot busy; that only happens when the reader keeps a
+//! guard across jobs or runs on another core.
+//!
+//! ```rust
+//! use alice_rtos::double_buffer::DoubleBuffer;
+//!
+//! #[derive(Clone, Copy)]
+//! struct Gains { kp: f32, ki: f32 }
+//!
+//! static GAINS: DoubleBuffer<Gains> = DoubleBuffer::new(Gains { kp: 1.0, ki: 0.0 });
+//! let (mut tuner, mut control) = GAINS.split().unwrap();
+//!
+//! // Tuning task (low priority)
+//! assert!(tuner.publish(Gains { kp: 2.0, ki: 0.1 }).is_ok());
+//! // Control task (high rate): one parameter set for the whole job
+//! let gains = control.read();
+//! assert_eq!((gains.kp, gains.ki), (2.0, 0.1));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+
+/// No slot announced by the reader
+const NOT_READING: u8 = u8::MAX;
+
+/// Two-slot parameter buffer with an atomic front flip
+///
+/// Written and read through the halves of a one-shot
+/// [`split`](Self::split).
+pub struct DoubleBuffer<T> {
+    /// The two parameter sets
+    slots: [UnsafeCell<T>; 2],
+    /// Slot the reader picks up next
+    front: AtomicU8,
+    /// Slot the reader is using (`NOT_READING` = none)
+    reading: AtomicU8,
+    /// Sets published so far
+    generation: AtomicU32,
+    /// Halves already handed out?
+    split: AtomicBool,
+}
+
+// SAFETY: the writer only writes the back slot after checking the reader
+// has not announced it, and the reader re-checks the front after
+// announcing (both sides `SeqCst`), so a slot is never written while it
+// is borrowed. Writer and reader are unique (`split` is one-shot) and the
+// reader holds one guard at a time (`read` borrows it mutably).
+unsafe impl<T: Send> Sync for DoubleBuffer<T> {}
+
+impl<T: Copy> DoubleBuffer<T> {
+    /// Buffer whose front holds `initial` (usable in `static` initializers)
+    #[must_use]
+    pub const fn new(initial: T) -> Self {
+        Self {
+            slots: [UnsafeCell::new(initial), UnsafeCell::new(initial)],
+            front: AtomicU8::new(0),
+            reading: AtomicU8::new(NOT_READING),
+            generation: AtomicU32::new(0),
+            split: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<T> DoubleBuffer<T> {
+    /// Split into writer and reader halves
+    ///
+    /// Returns `None` if the buffer was already split.
+    pub fn split(&self) -> Option<(DoubleBufferWriter<'_, T>, DoubleBufferReader<'_, T>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((
+            DoubleBufferWriter { buffer: self },
+            DoubleBufferReader { buffer: self },
+        ))
+    }
+
+    /// Parameter sets published so far
+    ///
+    /// A reader compares it across jobs to notice a new set.
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn publish(&self, value: T) -> Result<(), T> {
+        let back = self.front.load(Ordering::Relaxed) ^ 1;
+        if self.reading.load(Ordering::SeqCst) == back {
+            return Err(value);
+        }
+        // SAFETY: the single writer owns the back slot; a reader that
+        // announces it from now on sees the front unchanged only after
+        // the flip below, and then reads the finished value.
+        unsafe {
+            *self.slots[back as usize].get() = value;
+        }
+        self.front.store(back, Ordering::SeqCst);
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation
+            .store(generation.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    fn read(&self) -> Front<'_, T> {
+        loop {
+            let front = self.front.load(Ordering::SeqCst);
+            self.reading.store(front, Ordering::SeqCst);
+            // 公開直後の切り替えと競合したら新しい front でやり直す
+            if self.front.load(Ordering::SeqCst) == front {
+                return Front {
+                    buffer: self,
+                    slot: front,
+                };
+            }
+        }
+    }
+}
+
+/// Writer half of a [`DoubleBuffer`]
+pub struct DoubleBufferWriter<'a, T> {
+    buffer: &'a DoubleBuffer<T>,
+}
+
+impl<T> DoubleBufferWriter<'_, T> {
+    /// Write `value` to the back slot and make it the front
+    ///
+    /// The reader's next [`read`](DoubleBufferReader::read) sees it.
+    /// Returns the value back if the reader still holds a guard on the
+    /// back slot (an older front); retry later.
+    pub fn publish(&mut self, value: T) -> Result<(), T> {
+        self.buffer.publish(value)
+    }
+}
+
+/// Reader half of a [`DoubleBuffer`]
+pub struct DoubleBufferReader<'a, T> {
+    buffer: &'a DoubleBuffer<T>,
+}
+
+impl<T> DoubleBufferReader<'_, T> {
+    /// Borrow the front slot until the guard is dropped
+    ///
+    /// Take it at the start of a job so the job uses one parameter set.
+    /// The guard borrows the reader, so a second one cannot be taken
+    /// while the first is alive:
+    ///
+    /// ```compile_fail
+    /// use alice_rtos::double_buffer::DoubleBuffer;
+    ///
+    /// static BUF: DoubleBuffer<u32> = DoubleBuffer::new(0);
+    /// let (_, mut reader) = BUF.split().unwrap();
+    /// let job = reader.read();
+    /// let other = reader.read();
+    /// assert_eq!(*job, *other);
+    /// ```
+    pub fn read(&mut self) -> Front<'_, T> {
+        self.buffer.read()
+    }
+
+    /// Parameter sets published so far
+    pub fn generation(&self) -> u32 {
+        self.buffer.generation()
+    }
+}
+
+impl<T: Copy> DoubleBufferReader<'_, T> {
+    /// Copy of the current front
+    #[must_use]
+    pub fn load(&mut self) -> T {
+        *self.read()
+    }
+}
+
+/// Reader's borrow of the front slot of a [`DoubleBuffer`]
+pub struct Front<'a, T> {
+    /// Buffer borrowed from
+    buffer: &'a DoubleBuffer<T>,
+    /// Slot announced to the writer
+    slot: u8,
+}
+
+impl<T> Deref for Front<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the slot is announced, so the writer does not touch it
+        // until the guard is dropped.
+        unsafe { &*self.buffer.slots[self.slot as usize].get() }
+    }
+}
+
+impl<T> Drop for Front<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.reading.store(NOT_READING, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_flips_front() {
+        let buf = DoubleBuffer::new([1.0f32, 0.5]);
+        let (mut tx, mut rx) = buf.split().unwrap();
+        assert_eq!(rx.load(), [1.0, 0.5]);
+        assert_eq!(rx.generation(), 0);
+        assert!(tx.publish([2.0, 0.25]).is_ok());
+        assert_eq!(rx.load(), [2.0, 0.25]);
+        assert!(tx.publish([3.0, 0.125]).is_ok());
+        assert_eq!(rx.load(), [3.0, 0.125]);
+        assert_eq!(buf.generation(), 2);
+    }
+
+    #[test]
+    fn test_split_once() {
+        let buf = DoubleBuffer::new(0u8);
+        assert!(buf.split().is_some());
+        assert!(buf.split().is_none());
+    }
+
+    #[test]
+    fn test_guard_keeps_set_for_whole_job() {
+        let buf = DoubleBuffer::new(1u32);
+        let (mut tx, mut rx) = buf.split().unwrap();
+        let job = rx.read();
+        // ジョブ中の公開は裏面へ、保持中の値は変わらない
+        assert!(tx.publish(2).is_ok());
+        assert_eq!(*job, 1);
+        // 保持中の面 (次の裏面) は上書きできない
+        assert_eq!(tx.publish(3), Err(3));
+        assert_eq!(*job, 1);
+        drop(job);
+        // 次の周期境界で新しい値を取得
+        assert_eq!(*rx.read(), 2);
+        assert!(tx.publish(3).is_ok());
+        assert_eq!(rx.load(), 3);
+    }
+
+    #[test]
+    fn test_static_buffer() {
+        static GAINS: DoubleBuffer<(i16, i16)> = DoubleBuffer::new((10, -2));
+        let (mut tx, mut rx) = GAINS.split().unwrap();
+        assert!(tx.publish((12, -3)).is_ok());
+        assert_eq!(rx.load(), (12, -3));
+    }
+
+    #[test]
+    fn test_concurrent_reader_never_torn() {
+        extern crate std;
+        use std::thread;
+
+        let buf = DoubleBuffer::new([0u64; 8]);
+        let (mut tx, mut rx) = buf.split().unwrap();
+        thread::scope(|s| {
+            s.spawn(move || {
+                let mut i = 1u64;
+                while i <= 20_000 {
+                    if tx.publish([i; 8]).is_ok() {
+                        i += 1;
+                    }
+                }
+            });
+            s.spawn(|| {
+                let mut last = 0;
+                for _ in 0..20_000 {
+                    let set = rx.read();
+                    // 全要素が同一の公開由来 (ちぎれ無し)
+                    assert!(set.iter().all(|&x| x == set[0]));
+                    assert!(set[0] >= last);
+                    last = set[0];
+                }
+            });
+        });
+        assert_eq!(rx.load(), [20_000; 8]);
+        assert_eq!(buf.generation(), 20_000);
+    }
+}
@@ -0,0 +1,251 @@
+//! Embassy bridge: kernel clock as the `embassy-time` driver (`embassy` feature)
+//!
+//! Async drivers and hard periodic tasks share one core: the periodic
+//! tasks keep their RMS priorities, and an async executor is polled by a
+//! lowest-priority task ([`executor_task`]) in the slack between them.
+//!
+//! [`KernelTimeDriver`] implements `embassy_time_driver::Driver` on the
+//! kernel's µs clock (`TICK_HZ` = 1 MHz). It keeps up to `N` pending
+//! wake-ups in fixed slots; the kernel side pushes time into it with
+//! [`KernelTimeDriver::tick`] (or [`sync`](KernelTimeDriver::sync)),
+//! which wakes every expired timer. The firmware registers it once:
+//!
+//! ```ignore
+//! embassy_time_driver::time_driver_impl!(
+//!     static DRIVER: KernelTimeDriver<8> = KernelTimeDriver::new()
+//! );
+//! ```
+//!
+//! Slot access goes through `critical-section`, so the target (or the
+//! embassy executor crate) must provide an implementation.
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::{Cell, RefCell};
+use core::task::Waker;
+
+use critical_section::Mutex;
+use embassy_time_driver::Driver;
+
+use crate::kernel::Kernel;
+use crate::task::{Task, TaskFn, TaskPriority};
+
+/// Priority of the executor poll task (below every periodic task)
+pub const EXECUTOR_PRIORITY: TaskPriority = TaskPriority::IDLE;
+
+/// Executor poll task: `poll` runs every `period_us` at idle priority
+///
+/// `wcet_us` is the time granted to the executor per period and counts
+/// toward schedulability like any other task.
+pub fn executor_task(poll: TaskFn, period_us: u32, wcet_us: u32) -> Task {
+    Task::new(b"async", poll, EXECUTOR_PRIORITY, period_us, wcet_us)
+}
+
+/// One pending wake-up
+struct Alarm {
+    /// Expiry (kernel µs)
+    at: u64,
+    /// Waker to call at expiry
+    waker: Option<Waker>,
+}
+
+/// `embassy-time` driver backed by the kernel clock, `N` timer slots
+pub struct KernelTimeDriver<const N: usize> {
+    /// Kernel time last pushed by `sync` (µs)
+    now_us: Mutex<Cell<u64>>,
+    /// Pending wake-ups
+    alarms: Mutex<RefCell<[Alarm; N]>>,
+}
+
+impl<const N: usize> Default for KernelTimeDriver<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> KernelTimeDriver<N> {
+    /// Driver at t = 0 with no pending wake-ups (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            now_us: Mutex::new(Cell::new(0)),
+            alarms: Mutex::new(RefCell::new([const { Alarm { at: 0, waker: None } }; N])),
+        }
+    }
+
+    /// Run `kernel.tick(delta_us)` and advance the driver to the new time
+    pub fn tick(&self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        self.sync(kernel.timer.now_us());
+        executed
+    }
+
+    /// Advance to kernel time `now_us` and wake every expired timer
+    ///
+    /// Time never moves backwards: an older `now_us` is ignored.
+    pub fn sync(&self, now_us: u64) {
+        critical_section::with(|cs| {
+            let now = self.now_us.borrow(cs);
+            now.set(now.get().max(now_us));
+            let now = now.get();
+            for alarm in self.alarms.borrow_ref_mut(cs).iter_mut() {
+                if alarm.at <= now {
+                    if let Some(waker) = alarm.waker.take() {
+                        waker.wake();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Earliest pending wake-up, for a tickless idle loop
+    #[must_use]
+    pub fn next_expiration(&self) -> Option<u64> {
+        critical_section::with(|cs| {
+            self.alarms
+                .borrow_ref(cs)
+                .iter()
+                .filter(|a| a.waker.is_some())
+                .map(|a| a.at)
+                .min()
+        })
+    }
+
+    /// Number of pending wake-ups
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        critical_section::with(|cs| {
+            self.alarms
+                .borrow_ref(cs)
+                .iter()
+                .filter(|a| a.waker.is_some())
+                .count()
+        })
+    }
+}
+
+impl<const N: usize> Driver for KernelTimeDriver<N> {
+    fn now(&self) -> u64 {
+        critical_section::with(|cs| self.now_us.borrow(cs).get())
+    }
+
+    /// Queue `waker` for `at`; with all slots taken the waker is woken
+    /// at once and its future simply re-registers on the next poll
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        let woken_now = critical_section::with(|cs| {
+            if at <= self.now_us.borrow(cs).get() {
+                return true;
+            }
+            let mut alarms = self.alarms.borrow_ref_mut(cs);
+            // 同じタスクの登録は早い方の期限に統合
+            if let Some(alarm) = alarms
+                .iter_mut()
+                .find(|a| a.waker.as_ref().is_some_and(|w| w.will_wake(waker)))
+            {
+                alarm.at = alarm.at.min(at);
+                return false;
+            }
+            match alarms.iter_mut().find(|a| a.waker.is_none()) {
+                Some(alarm) => {
+                    alarm.at = at;
+                    alarm.waker = Some(waker.clone());
+                    false
+                }
+                None => true,
+            }
+        });
+        if woken_now {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn counter() -> (Arc<Counter>, Waker) {
+        let c = Arc::new(Counter(AtomicUsize::new(0)));
+        (c.clone(), Waker::from(c))
+    }
+
+    fn woken(c: &Counter) -> usize {
+        c.0.load(Ordering::Relaxed)
+    }
+
+    fn poll(_: &mut [u8]) {}
+
+    #[test]
+    fn test_wake_at_expiry() {
+        let driver = KernelTimeDriver::<4>::new();
+        let (c, waker) = counter();
+        driver.schedule_wake(300, &waker);
+        assert_eq!(driver.next_expiration(), Some(300));
+        driver.sync(299);
+        assert_eq!(woken(&c), 0);
+        driver.sync(300);
+        assert_eq!(woken(&c), 1);
+        assert_eq!(driver.pending(), 0);
+        assert_eq!(driver.now(), 300);
+        // 時刻は巻き戻らない
+        driver.sync(10);
+        assert_eq!(driver.now(), 300);
+    }
+
+    #[test]
+    fn test_past_deadline_and_full_table_wake_immediately() {
+        let driver = KernelTimeDriver::<1>::new();
+        driver.sync(100);
+        let (a, wa) = counter();
+        driver.schedule_wake(50, &wa);
+        assert_eq!(woken(&a), 1);
+        driver.schedule_wake(500, &wa);
+        let (b, wb) = counter();
+        driver.schedule_wake(400, &wb);
+        assert_eq!(woken(&b), 1);
+        // 同じ waker の再登録は早い期限に統合
+        driver.schedule_wake(200, &wa);
+        assert_eq!(driver.pending(), 1);
+        assert_eq!(driver.next_expiration(), Some(200));
+    }
+
+    #[test]
+    fn test_tick_follows_kernel_clock() {
+        let driver = KernelTimeDriver::<4>::new();
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", poll, TaskPriority::HIGH, 100, 10);
+        kernel.scheduler.register(executor_task(poll, 1_000, 200));
+        let (c, waker) = counter();
+        driver.schedule_wake(150, &waker);
+        assert_eq!(driver.tick(&mut kernel, 0), Some(0));
+        // ctl が先、executor は空き時間に実行
+        assert_eq!(driver.tick(&mut kernel, 50), Some(1));
+        assert_eq!(woken(&c), 0);
+        driver.tick(&mut kernel, 100);
+        assert_eq!(woken(&c), 1);
+        assert_eq!(driver.now(), 150);
+    }
+
+    #[test]
+    fn test_global_driver_registration() {
+        embassy_time_driver::time_driver_impl!(
+            static DRIVER: KernelTimeDriver<2> = KernelTimeDriver::new()
+        );
+        DRIVER.sync(1_234);
+        assert_eq!(embassy_time_driver::now(), 1_234);
+        assert_eq!(embassy_time_driver::TICK_HZ, 1_000_000);
+    }
+}
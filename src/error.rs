@@ -0,0 +1,55 @@
+//! Kernel error type
+//!
+//! The `try_*` APIs ([`Kernel::try_add_task`](crate::kernel::Kernel::try_add_task),
+//! [`Scheduler::try_register`](crate::scheduler::Scheduler::try_register),
+//! [`SpscRing::try_push`](crate::spsc::SpscRing::try_push), …) report why
+//! an operation failed with a [`KernelError`]. The older `Option` / `bool`
+//! forms remain as thin wrappers over them.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{InvalidTransition, KernelState};
+
+/// Why a kernel operation failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelError {
+    /// All `MAX_TASKS` task slots are in use
+    TaskTableFull,
+    /// The task set would fail the schedulability test
+    NotSchedulable,
+    /// No active task at that index (or a task paired with itself)
+    InvalidIndex,
+    /// The queue has no free slot
+    QueueFull,
+    /// The channel link table is full
+    LinkTableFull,
+    /// The channel pool has no channel left
+    PoolExhausted,
+    /// The task's scratch arena does not fit next to the others
+    ScratchExhausted,
+    /// Tasks cannot be added while the kernel is running
+    KernelRunning,
+    /// The deadline passed before the operation could complete
+    TimedOut,
+    /// Nothing to receive yet; the deadline has not passed
+    WouldBlock,
+    /// Life-cycle transition not allowed from the current state
+    InvalidState {
+        /// State the kernel was in
+        from: KernelState,
+        /// State that was requested
+        to: KernelState,
+    },
+}
+
+impl From<InvalidTransition> for KernelError {
+    fn from(e: InvalidTransition) -> Self {
+        Self::InvalidState {
+            from: e.from,
+            to: e.to,
+        }
+    }
+}
+
+/// Result of a fallible kernel operation
+pub type KernelResult<T> = Result<T, KernelError>;
@@ -0,0 +1,270 @@
+//! Structured kernel events for `defmt` / `log`
+//!
+//! The kernel reports notable events through [`emit`]. With the `defmt`
+//! feature they go to the defmt logger (embedded), with the `log` feature
+//! to the `log` facade (host simulator); with neither, `emit` compiles to
+//! nothing.
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt;
+
+/// Kernel event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum KernelEvent {
+    /// Task registered in slot `idx`
+    TaskRegistered {
+        /// Task slot
+        idx: u8,
+    },
+    /// Job of `idx` started after its deadline
+    DeadlineMiss {
+        /// Task slot
+        idx: u8,
+        /// Time of the late dispatch (µs)
+        at_us: u64,
+    },
+    /// Task `idx` used more scratch than its budget (or hit the guard)
+    ScratchOverrun {
+        /// Task slot
+        idx: u8,
+        /// Scratch bytes touched
+        used: u16,
+    },
+    /// Task `idx` panicked (std / test builds)
+    TaskFault {
+        /// Task slot
+        idx: u8,
+    },
+    /// Task `idx` used up its execution budget and is throttled
+    BudgetExhausted {
+        /// Task slot
+        idx: u8,
+    },
+    /// Task `idx` shed by the overload manager
+    TaskShed {
+        /// Task slot
+        idx: u8,
+    },
+    /// Shed task `idx` restored to its normal rate
+    TaskRestored {
+        /// Task slot
+        idx: u8,
+    },
+    /// Task `idx` was admitted although the task set now fails the
+    /// schedulability test ([`Admission::Flag`](crate::kernel::Admission::Flag))
+    Overcommitted {
+        /// Task slot
+        idx: u8,
+    },
+    /// Configuration of task `idx` failed its CRC check
+    TaskCorrupted {
+        /// Task slot
+        idx: u8,
+        /// Restored from the golden copy
+        restored: bool,
+    },
+    /// Redundant replicas `a` / `b` disagreed or one did not complete
+    ReplicaFault {
+        /// First replica's task slot
+        a: u8,
+        /// Second replica's task slot
+        b: u8,
+        /// A replica timed out (else the outputs differed)
+        timeout: bool,
+    },
+    /// Task `idx` lost `count` activations to a full activation queue
+    JobsLost {
+        /// Task slot
+        idx: u8,
+        /// Activations lost (saturating)
+        count: u16,
+    },
+    /// A scheduler lock was held past its maximum duration
+    LockOverrun {
+        /// Time the lock had been held (µs)
+        held_us: u32,
+    },
+    /// Operating mode switched
+    ModeChange {
+        /// Previous mode
+        from: u8,
+        /// New mode
+        to: u8,
+    },
+}
+
+impl KernelEvent {
+    /// Does this event indicate a timing or memory fault?
+    #[must_use]
+    pub const fn is_fault(&self) -> bool {
+        matches!(
+            self,
+            Self::DeadlineMiss { .. }
+                | Self::ScratchOverrun { .. }
+                | Self::TaskFault { .. }
+                | Self::BudgetExhausted { .. }
+                | Self::Overcommitted { .. }
+                | Self::TaskCorrupted { .. }
+                | Self::ReplicaFault { .. }
+                | Self::LockOverrun { .. }
+                | Self::JobsLost { .. }
+        )
+    }
+}
+
+impl fmt::Display for KernelEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::TaskRegistered { idx } => write!(f, "task {idx} registered"),
+            Self::DeadlineMiss { idx, at_us } => {
+                write!(f, "task {idx} missed its deadline at {at_us} us")
+            }
+            Self::ScratchOverrun { idx, used } => {
+                write!(f, "task {idx} overran scratch ({used} bytes)")
+            }
+            Self::TaskFault { idx } => write!(f, "task {idx} faulted"),
+            Self::BudgetExhausted { idx } => write!(f, "task {idx} exhausted its budget"),
+            Self::TaskShed { idx } => write!(f, "task {idx} shed under overload"),
+            Self::TaskRestored { idx } => write!(f, "task {idx} restored"),
+            Self::Overcommitted { idx } => {
+                write!(f, "task {idx} admitted past the schedulability bound")
+            }
+            Self::TaskCorrupted { idx, restored } => {
+                let action = if restored { "restored" } else { "not restored" };
+                write!(f, "task {idx} descriptor corrupted ({action})")
+            }
+            Self::ReplicaFault { a, b, timeout } => {
+                let what = if timeout { "timed out" } else { "disagree" };
+                write!(f, "replicas {a}/{b} {what}")
+            }
+            Self::JobsLost { idx, count } => write!(f, "task {idx} lost {count} activations"),
+            Self::LockOverrun { held_us } => {
+                write!(f, "scheduler locked for {held_us} us, past its limit")
+            }
+            Self::ModeChange { from, to } => write!(f, "mode {from} -> {to}"),
+        }
+    }
+}
+
+/// Report a kernel event to the enabled logging backends
+#[inline(always)]
+pub fn emit(event: KernelEvent) {
+    #[cfg(feature = "defmt")]
+    if event.is_fault() {
+        defmt::warn!("alice-rtos: {}", event);
+    } else {
+        defmt::info!("alice-rtos: {}", event);
+    }
+    #[cfg(feature = "log")]
+    if event.is_fault() {
+        log::warn!(target: "alice_rtos", "{event}");
+    } else {
+        log::info!(target: "alice_rtos", "{event}");
+    }
+    let _ = event;
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            KernelEvent::TaskRegistered { idx: 3 }.to_string(),
+            "task 3 registered"
+        );
+        assert_eq!(
+            KernelEvent::DeadlineMiss { idx: 1, at_us: 250 }.to_string(),
+            "task 1 missed its deadline at 250 us"
+        );
+        assert_eq!(
+            KernelEvent::ModeChange { from: 0, to: 2 }.to_string(),
+            "mode 0 -> 2"
+        );
+        assert_eq!(
+            KernelEvent::TaskCorrupted {
+                idx: 2,
+                restored: true
+            }
+            .to_string(),
+            "task 2 descriptor corrupted (restored)"
+        );
+        assert_eq!(
+            KernelEvent::ReplicaFault {
+                a: 0,
+                b: 1,
+                timeout: false
+            }
+            .to_string(),
+            "replicas 0/1 disagree"
+        );
+    }
+
+    #[test]
+    fn test_fault_classification() {
+        assert!(KernelEvent::DeadlineMiss { idx: 0, at_us: 0 }.is_fault());
+        assert!(KernelEvent::ScratchOverrun { idx: 0, used: 8 }.is_fault());
+        assert!(KernelEvent::TaskFault { idx: 0 }.is_fault());
+        assert!(KernelEvent::BudgetExhausted { idx: 0 }.is_fault());
+        assert!(KernelEvent::Overcommitted { idx: 0 }.is_fault());
+        assert!(KernelEvent::TaskCorrupted {
+            idx: 0,
+            restored: false
+        }
+        .is_fault());
+        assert!(!KernelEvent::TaskRegistered { idx: 0 }.is_fault());
+        assert!(!KernelEvent::ModeChange { from: 0, to: 1 }.is_fault());
+        assert!(!KernelEvent::TaskShed { idx: 0 }.is_fault());
+        assert!(!KernelEvent::TaskRestored { idx: 0 }.is_fault());
+    }
+
+    #[test]
+    fn test_emit_without_backend_is_noop() {
+        emit(KernelEvent::TaskFault { idx: 7 });
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_backend_levels() {
+        use std::sync::Mutex;
+        use std::vec::Vec;
+
+        static LINES: Mutex<Vec<(log::Level, std::string::String)>> = Mutex::new(Vec::new());
+
+        struct Capture;
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                if record.target() == "alice_rtos" {
+                    LINES
+                        .lock()
+                        .unwrap()
+                        .push((record.level(), record.args().to_string()));
+                }
+            }
+            fn flush(&self) {}
+        }
+
+        static LOGGER: Capture = Capture;
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Info);
+
+        emit(KernelEvent::ModeChange { from: 1, to: 2 });
+        emit(KernelEvent::DeadlineMiss { idx: 4, at_us: 99 });
+        let lines = LINES.lock().unwrap();
+        assert!(lines.contains(&(log::Level::Info, "mode 1 -> 2".into())));
+        assert!(lines.contains(&(
+            log::Level::Warn,
+            "task 4 missed its deadline at 99 us".into()
+        )));
+    }
+}
@@ -0,0 +1,378 @@
+//! Black-box fault recorder
+//!
+//! A [`BlackBox`] keeps the most recent fault — code, offending task,
+//! kernel time and a detail word — in a static region that survives a
+//! warm reset, so a failure that happens before any console is up can be
+//! read back after the restart. Place it in a section the startup code
+//! does not zero (e.g. cortex-m-rt's `.uninit`):
+//!
+//! ```ignore
+//! #[link_section = ".uninit.BLACK_BOX"]
+//! static mut BLACK_BOX: BlackBox = BlackBox::new();
+//!
+//! // early in main, before the kernel runs
+//! let bb = unsafe { &mut *core::ptr::addr_of_mut!(BLACK_BOX) };
+//! if let Some(last) = bb.last() { /* report the previous fault */ }
+//! fault::install(bb);
+//! ```
+//!
+//! Once installed, the kernel records deadline storms (a configurable
+//! number of misses within a window), scratch overruns, guard-zone
+//! corruption and caught task panics; applications add their own with
+//! [`Kernel::fault`](crate::kernel::Kernel::fault). The record carries a
+//! magic number and CRC-32, so uninitialized RAM after a cold power-up
+//! reads as "no fault".
+//!
+//! Recording happens from the kernel's own context; do not capture from
+//! an interrupt that can preempt the kernel tick.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::snapshot::crc32;
+
+/// Record validity marker
+const MAGIC: u32 = 0xB1AC_B0C5;
+
+/// "No task" in the stored record
+const NO_TASK: u8 = u8::MAX;
+
+/// Fault classification stored in the black box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaultCode(pub u16);
+
+impl FaultCode {
+    /// Deadline misses reached the storm threshold within one window
+    pub const DEADLINE_STORM: Self = Self(1);
+    /// A task overran its scratch budget (detail = bytes used)
+    pub const SCRATCH_OVERRUN: Self = Self(2);
+    /// The scratch guard zone was found corrupted
+    pub const GUARD_CORRUPTION: Self = Self(3);
+    /// A task panicked (std / test builds; detail = fault count)
+    pub const TASK_PANIC: Self = Self(4);
+    /// A task descriptor failed its CRC check (detail = computed CRC)
+    pub const TASK_CORRUPTION: Self = Self(5);
+    /// Redundant replicas produced different outputs (detail = a | b << 8)
+    pub const REPLICA_MISMATCH: Self = Self(6);
+    /// A redundant replica did not complete in time (detail = a | b << 8)
+    pub const REPLICA_TIMEOUT: Self = Self(7);
+    /// The power-on self-test failed (detail = `BistFailures` bits)
+    pub const SELF_TEST: Self = Self(8);
+    /// A scheduler lock was held past its maximum (detail = held µs)
+    pub const LOCK_OVERRUN: Self = Self(9);
+    /// First code available to applications
+    pub const USER: Self = Self(0x100);
+}
+
+/// Decoded black-box contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRecord {
+    /// What happened
+    pub code: FaultCode,
+    /// Offending task slot, if known
+    pub task: Option<u8>,
+    /// Kernel time of the fault (µs)
+    pub tick_us: u64,
+    /// Code-specific detail (miss count, bytes, program state, …)
+    pub detail: u32,
+    /// Faults recorded since the box was last cleared
+    pub count: u32,
+}
+
+/// Static last-fault register block
+#[repr(C)]
+pub struct BlackBox {
+    /// `MAGIC` when the record is valid
+    magic: u32,
+    /// Fault code
+    code: u16,
+    /// Offending task (`NO_TASK` = none)
+    task: u8,
+    /// Padding (kept zero so the CRC is deterministic)
+    _reserved: u8,
+    /// Code-specific detail
+    detail: u32,
+    /// Faults recorded since clear
+    count: u32,
+    /// Kernel time of the fault (µs)
+    tick_us: u64,
+    /// CRC-32 over the fields above
+    crc: u32,
+    /// Deadline misses that make a storm (0 = off); re-armed by `install`
+    storm_threshold: u16,
+    /// Misses in the current window
+    storm_misses: u16,
+    /// Storm window length (µs)
+    storm_window_us: u32,
+    /// Start of the current window (µs)
+    storm_start: u64,
+}
+
+impl Default for BlackBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlackBox {
+    /// Default deadline-storm threshold (misses per window)
+    pub const DEFAULT_STORM_MISSES: u16 = 8;
+    /// Default deadline-storm window (µs)
+    pub const DEFAULT_STORM_WINDOW_US: u32 = 100_000;
+
+    /// Empty black box (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            magic: 0,
+            code: 0,
+            task: NO_TASK,
+            _reserved: 0,
+            detail: 0,
+            count: 0,
+            tick_us: 0,
+            crc: 0,
+            storm_threshold: Self::DEFAULT_STORM_MISSES,
+            storm_misses: 0,
+            storm_window_us: Self::DEFAULT_STORM_WINDOW_US,
+            storm_start: 0,
+        }
+    }
+
+    /// Most recent fault, if the stored record is valid
+    #[must_use]
+    pub fn last(&self) -> Option<FaultRecord> {
+        if self.magic != MAGIC || self.crc != self.checksum() {
+            return None;
+        }
+        Some(FaultRecord {
+            code: FaultCode(self.code),
+            task: (self.task != NO_TASK).then_some(self.task),
+            tick_us: self.tick_us,
+            detail: self.detail,
+            count: self.count,
+        })
+    }
+
+    /// Forget the stored fault (e.g. after it has been reported)
+    pub fn clear(&mut self) {
+        self.magic = 0;
+        self.crc = 0;
+        self.count = 0;
+    }
+
+    /// Record a storm once `misses` deadline misses fall within
+    /// `window_us` (0 misses = storm detection off)
+    pub fn set_storm_threshold(&mut self, misses: u16, window_us: u32) {
+        self.storm_threshold = misses;
+        self.storm_window_us = window_us;
+        self.storm_misses = 0;
+    }
+
+    /// Store a fault, replacing the previous record
+    pub fn record(&mut self, code: FaultCode, task: Option<usize>, tick_us: u64, detail: u32) {
+        let count = self.last().map_or(0, |r| r.count);
+        self.code = code.0;
+        self.task = task.map_or(NO_TASK, |t| t as u8);
+        self._reserved = 0;
+        self.detail = detail;
+        self.count = count.wrapping_add(1);
+        self.tick_us = tick_us;
+        self.magic = MAGIC;
+        self.crc = self.checksum();
+    }
+
+    /// Count a deadline miss; records a storm on reaching the threshold
+    fn deadline_miss(&mut self, idx: usize, at_us: u64) {
+        if self.storm_threshold == 0 {
+            return;
+        }
+        if self.storm_misses == 0
+            || at_us.wrapping_sub(self.storm_start) > self.storm_window_us as u64
+        {
+            self.storm_start = at_us;
+            self.storm_misses = 0;
+        }
+        self.storm_misses = self.storm_misses.saturating_add(1);
+        if self.storm_misses == self.storm_threshold {
+            self.record(
+                FaultCode::DEADLINE_STORM,
+                Some(idx),
+                at_us,
+                self.storm_misses as u32,
+            );
+        }
+    }
+
+    /// CRC-32 over the record fields
+    fn checksum(&self) -> u32 {
+        let mut bytes = [0u8; 24];
+        bytes[0..4].copy_from_slice(&self.magic.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.code.to_le_bytes());
+        bytes[6] = self.task;
+        bytes[7] = self._reserved;
+        bytes[8..12].copy_from_slice(&self.detail.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.tick_us.to_le_bytes());
+        crc32(&bytes)
+    }
+}
+
+#[cfg(not(test))]
+mod slot {
+    use super::BlackBox;
+    use core::ptr;
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    /// Installed black box (null = recording off)
+    static INSTALLED: AtomicPtr<BlackBox> = AtomicPtr::new(ptr::null_mut());
+
+    pub(super) fn set(bb: *mut BlackBox) {
+        INSTALLED.store(bb, Ordering::Release);
+    }
+
+    pub(super) fn get() -> *mut BlackBox {
+        INSTALLED.load(Ordering::Acquire)
+    }
+}
+
+// テストは並列に走るため、インストール先をスレッドごとに分離する
+#[cfg(test)]
+mod slot {
+    extern crate std;
+
+    use super::BlackBox;
+    use core::cell::Cell;
+    use core::ptr;
+
+    std::thread_local! {
+        static INSTALLED: Cell<*mut BlackBox> = const { Cell::new(ptr::null_mut()) };
+    }
+
+    pub(super) fn set(bb: *mut BlackBox) {
+        INSTALLED.with(|p| p.set(bb));
+    }
+
+    pub(super) fn get() -> *mut BlackBox {
+        INSTALLED.with(Cell::get)
+    }
+}
+
+/// Start recording kernel faults into `bb`
+///
+/// Keeps the stored record (read it with [`BlackBox::last`] first) and
+/// re-arms deadline-storm detection with the default threshold.
+pub fn install(bb: &'static mut BlackBox) {
+    bb.set_storm_threshold(
+        BlackBox::DEFAULT_STORM_MISSES,
+        BlackBox::DEFAULT_STORM_WINDOW_US,
+    );
+    slot::set(bb);
+}
+
+/// Stop recording (the black box keeps its contents)
+pub fn uninstall() {
+    slot::set(core::ptr::null_mut());
+}
+
+/// Run `f` on the installed black box, if any
+fn with_installed(f: impl FnOnce(&mut BlackBox)) {
+    let bb = slot::get();
+    if !bb.is_null() {
+        // SAFETY: `install` は &'static mut を受け取り、記録はカーネルの
+        // 単一コンテキストからのみ行われる
+        f(unsafe { &mut *bb });
+    }
+}
+
+/// Record a fault in the installed black box (no-op if none)
+pub fn capture(code: FaultCode, task: Option<usize>, tick_us: u64, detail: u32) {
+    with_installed(|bb| bb.record(code, task, tick_us, detail));
+}
+
+/// Feed a deadline miss to storm detection
+pub(crate) fn deadline_miss(idx: usize, at_us: u64) {
+    with_installed(|bb| bb.deadline_miss(idx, at_us));
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::task::TaskPriority;
+    use std::boxed::Box;
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_record_survives_reread() {
+        let mut bb = BlackBox::new();
+        assert_eq!(bb.last(), None);
+        bb.record(FaultCode::USER, Some(3), 1_234, 0xDEAD);
+        bb.record(FaultCode::SCRATCH_OVERRUN, None, 5_000, 900);
+        let last = bb.last().unwrap();
+        assert_eq!(last.code, FaultCode::SCRATCH_OVERRUN);
+        assert_eq!(last.task, None);
+        assert_eq!(last.tick_us, 5_000);
+        assert_eq!(last.detail, 900);
+        assert_eq!(last.count, 2);
+
+        // リセット後のゴミ (CRC 不一致) は「記録なし」
+        bb.detail ^= 1;
+        assert_eq!(bb.last(), None);
+        bb.record(FaultCode::USER, None, 0, 0);
+        assert_eq!(bb.last().unwrap().count, 1);
+        bb.clear();
+        assert_eq!(bb.last(), None);
+    }
+
+    #[test]
+    fn test_storm_threshold_window() {
+        let mut bb = BlackBox::new();
+        bb.set_storm_threshold(3, 1_000);
+        bb.deadline_miss(1, 0);
+        bb.deadline_miss(1, 500);
+        // 窓 (1 ms) の外: 数え直し
+        bb.deadline_miss(1, 2_000);
+        bb.deadline_miss(2, 2_100);
+        assert_eq!(bb.last(), None);
+        bb.deadline_miss(2, 2_200);
+        let last = bb.last().unwrap();
+        assert_eq!(last.code, FaultCode::DEADLINE_STORM);
+        assert_eq!(last.task, Some(2));
+        assert_eq!(last.tick_us, 2_200);
+        assert_eq!(last.detail, 3);
+    }
+
+    #[test]
+    fn test_kernel_records_into_installed_box() {
+        let bb: &'static mut BlackBox = Box::leak(Box::new(BlackBox::new()));
+        let bb_ptr: *const BlackBox = bb;
+        install(bb);
+        let read = || unsafe { (*bb_ptr).last() };
+
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 100, 10);
+        kernel.tick(0);
+        kernel.tick(250);
+        kernel.fault(FaultCode(FaultCode::USER.0 + 7), 42);
+        let last = read().unwrap();
+        assert_eq!(last.code.0, 0x107);
+        assert_eq!(last.task, Some(0));
+        assert_eq!(last.tick_us, 250);
+        assert_eq!(last.detail, 42);
+
+        // 周期 100 µs を毎回 300 µs 遅れで実行 → 8 回目でストーム
+        for _ in 0..8 {
+            kernel.tick(300);
+        }
+        let last = read().unwrap();
+        assert_eq!(last.code, FaultCode::DEADLINE_STORM);
+        assert_eq!(last.count, 2);
+
+        uninstall();
+        kernel.fault(FaultCode::USER, 0);
+        assert_eq!(read().unwrap().count, 2);
+    }
+}
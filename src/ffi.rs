@@ -11,7 +11,7 @@ use crate::kernel::{Kernel, KernelStats};
 use crate::scheduler::Scheduler;
 use crate::spsc::SpscRing;
 use crate::task::{TaskPriority, TaskState};
-use crate::timer::{Deadline, SysTimer};
+use crate::timer::{tick_to_u64, Deadline, SysTimer};
 
 // ============================================================================
 // Opaque handle types
@@ -334,7 +334,7 @@ pub unsafe extern "C" fn ar_rtos_scheduler_now_us(ptr: *const ArRtosScheduler) -
     if ptr.is_null() {
         return 0;
     }
-    (*ptr).0.now_us()
+    tick_to_u64((*ptr).0.now_us())
 }
 
 /// # Safety
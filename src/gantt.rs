@@ -0,0 +1,215 @@
+//! ASCII Gantt chart of a recorded schedule
+//!
+//! [`Gantt`] renders the ticks of a [`TickLog`](crate::replay::TickLog)
+//! as one row per task and one column per time quantum:
+//!
+//! ```text
+//! fast     |#.#.#.#.|
+//! slow     |-#..-#..|
+//! ```
+//!
+//! | Cell | Meaning within the quantum |
+//! |------|----------------------------|
+//! | `#` | the task was dispatched |
+//! | `-` | the task was released but waiting |
+//! | `.` | otherwise |
+//!
+//! Columns are relative to the first recorded tick. The chart is written
+//! through [`core::fmt::Write`] (via `Display`) or into a byte buffer with
+//! [`Gantt::render_into`], so it works over a serial port as well as in
+//! host tests.
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt::{self, Write};
+
+use crate::kernel::Kernel;
+use crate::replay::TickRecord;
+use crate::task::TaskState;
+
+/// Width of the task name column
+const NAME_WIDTH: usize = 8;
+
+/// Gantt chart of `records` with columns of `quantum_us`
+pub struct Gantt<'a> {
+    /// Kernel the records were taken from (task names)
+    kernel: &'a Kernel,
+    /// Recorded ticks
+    records: &'a [TickRecord],
+    /// Time per column (µs, at least 1)
+    quantum_us: u64,
+}
+
+impl<'a> Gantt<'a> {
+    /// Chart of `records` (taken from `kernel`), `quantum_us` per column
+    #[must_use]
+    pub const fn new(kernel: &'a Kernel, records: &'a [TickRecord], quantum_us: u32) -> Self {
+        Self {
+            kernel,
+            records,
+            quantum_us: if quantum_us == 0 {
+                1
+            } else {
+                quantum_us as u64
+            },
+        }
+    }
+
+    /// Number of time columns
+    #[must_use]
+    pub fn columns(&self) -> usize {
+        if self.records.is_empty() {
+            return 0;
+        }
+        let span: u64 = self.records.iter().map(|r| r.delta_us as u64).sum();
+        let first = self.records[0].delta_us as u64;
+        ((span - first) / self.quantum_us + 1) as usize
+    }
+
+    /// Render into `buf`; returns the bytes written, or 0 if the chart
+    /// does not fit
+    pub fn render_into(&self, buf: &mut [u8]) -> usize {
+        let mut out = BufWriter { buf, pos: 0 };
+        match write!(out, "{self}") {
+            Ok(()) => out.pos,
+            Err(_) => 0,
+        }
+    }
+
+    /// Write the row of task `idx`
+    fn write_row(&self, f: &mut fmt::Formatter<'_>, idx: usize, name: &[u8; 8]) -> fmt::Result {
+        let len = name.iter().position(|&b| b == 0).unwrap_or(NAME_WIDTH);
+        for &b in &name[..len] {
+            f.write_char(if b.is_ascii_graphic() { b as char } else { '?' })?;
+        }
+        for _ in len..NAME_WIDTH {
+            f.write_char(' ')?;
+        }
+        f.write_str(" |")?;
+
+        let mut t = 0u64;
+        let mut col = 0u64;
+        let mut cell = '.';
+        // 列は最初の記録 tick を 0 とする
+        for (i, record) in self.records.iter().enumerate() {
+            if i > 0 {
+                t += record.delta_us as u64;
+            }
+            let c = t / self.quantum_us;
+            while col < c {
+                f.write_char(cell)?;
+                cell = '.';
+                col += 1;
+            }
+            if record.executed() == Some(idx) {
+                cell = '#';
+            } else if cell == '.' && record.ready & (1 << idx) != 0 {
+                cell = '-';
+            }
+        }
+        if !self.records.is_empty() {
+            f.write_char(cell)?;
+        }
+        f.write_str("|\n")
+    }
+}
+
+impl fmt::Display for Gantt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheduler = &self.kernel.scheduler;
+        for idx in 0..scheduler.task_slots() {
+            match scheduler.get_task(idx) {
+                Some(task) if task.state != TaskState::Inactive => {
+                    self.write_row(f, idx, &task.name)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `fmt::Write` over a byte buffer; fails once the buffer is full
+struct BufWriter<'b> {
+    buf: &'b mut [u8],
+    pos: usize,
+}
+
+impl Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.pos + s.len();
+        let dst = self.buf.get_mut(self.pos..end).ok_or(fmt::Error)?;
+        dst.copy_from_slice(s.as_bytes());
+        self.pos = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::TickLog;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn recorded() -> (Kernel, TickLog<16>) {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"slow", noop, TaskPriority::LOW, 200, 20);
+        let mut log = TickLog::new();
+        log.tick(&mut kernel, 0);
+        for _ in 0..7 {
+            log.tick(&mut kernel, 50);
+        }
+        (kernel, log)
+    }
+
+    #[test]
+    fn test_render_one_column_per_tick() {
+        let (kernel, log) = recorded();
+        let gantt = Gantt::new(&kernel, log.records(), 50);
+        assert_eq!(gantt.columns(), 8);
+        let mut buf = [0u8; 64];
+        let len = gantt.render_into(&mut buf);
+        // t=0 は fast が実行され slow は待機
+        assert_eq!(
+            core::str::from_utf8(&buf[..len]).unwrap(),
+            "fast     |#.#.#.#.|\nslow     |-#..-#..|\n"
+        );
+    }
+
+    #[test]
+    fn test_coarse_quantum_merges_ticks() {
+        let (kernel, log) = recorded();
+        let gantt = Gantt::new(&kernel, log.records(), 100);
+        assert_eq!(gantt.columns(), 4);
+        let mut buf = [0u8; 64];
+        let len = gantt.render_into(&mut buf);
+        // 実行 (#) は待機 (-) より優先
+        assert_eq!(
+            core::str::from_utf8(&buf[..len]).unwrap(),
+            "fast     |####|\nslow     |#.#.|\n"
+        );
+    }
+
+    #[test]
+    fn test_render_into_small_buffer() {
+        let (kernel, log) = recorded();
+        let gantt = Gantt::new(&kernel, log.records(), 50);
+        let mut buf = [0u8; 16];
+        assert_eq!(gantt.render_into(&mut buf), 0);
+    }
+
+    #[test]
+    fn test_display_via_fmt_write() {
+        extern crate std;
+        use std::string::ToString;
+
+        let (kernel, log) = recorded();
+        let chart = Gantt::new(&kernel, &log.records()[..1], 50).to_string();
+        assert_eq!(chart, "fast     |#|\nslow     |-|\n");
+        let empty = Gantt::new(&kernel, &[], 50).to_string();
+        assert_eq!(empty, "fast     ||\nslow     ||\n");
+    }
+}
@@ -0,0 +1,608 @@
+//! Static dataflow graphs: schedule equations, not processes
+//!
+//! A [`Node`] is a pure function `Out = eval(In)` with a rate (period)
+//! and WCET. Nodes are wired output-to-input in a const [`GraphBuilder`];
+//! the type system checks that every connection carries the producer's
+//! output type. [`GraphBuilder::build`] then derives the task set:
+//!
+//! - one task per node, running a generated body that pops the node's
+//!   input wire, evaluates the node for every queued value and pushes the
+//!   results to its output wire;
+//! - rate-monotonic priorities from the periods (shortest period →
+//!   highest priority, starting at a base priority);
+//! - a compile-time RMS check over the whole graph.
+//!
+//! [`Graph::install`] registers the tasks, claims each connected wire
+//! (a static [`Channel`] declared next to its node) as a single-producer
+//! single-consumer link, records the link in the kernel and
+//! [`chain`](crate::scheduler::Scheduler::chain)s same-rate edges so a
+//! consumer runs in the tick its producer completes. Slower consumers
+//! drain everything queued since their last run.
+//!
+//! ```rust
+//! use alice_rtos::channel::Channel;
+//! use alice_rtos::graph::{Graph, GraphBuilder, Node, Port};
+//! use alice_rtos::Kernel;
+//!
+//! static RAW: Channel<i32, 4> = Channel::new();
+//! static FILTERED: Channel<i32, 8> = Channel::new();
+//!
+//! struct Adc;
+//! impl Node for Adc {
+//!     type In = ();
+//!     type Out = i32;
+//!     const NAME: &'static [u8] = b"adc";
+//!     const PERIOD_US: u32 = 1_000;
+//!     const WCET_US: u32 = 20;
+//!     fn eval(_: ()) -> i32 { 42 }
+//!     fn output() -> Option<&'static dyn Port<i32>> { Some(&RAW) }
+//! }
+//!
+//! struct Gain;
+//! impl Node for Gain {
+//!     type In = i32;
+//!     type Out = i32;
+//!     const NAME: &'static [u8] = b"gain";
+//!     const PERIOD_US: u32 = 1_000;
+//!     const WCET_US: u32 = 10;
+//!     fn eval(x: i32) -> i32 { 2 * x }
+//!     fn output() -> Option<&'static dyn Port<i32>> { Some(&FILTERED) }
+//! }
+//!
+//! struct Report;
+//! impl Node for Report {
+//!     type In = i32;
+//!     type Out = ();
+//!     const NAME: &'static [u8] = b"report";
+//!     const PERIOD_US: u32 = 4_000;
+//!     const WCET_US: u32 = 100;
+//!     fn eval(_: i32) {}
+//! }
+//!
+//! const GRAPH: Graph<3> = GraphBuilder::new()
+//!     .source::<Adc>()
+//!     .connect::<Adc, Gain>()
+//!     .connect::<Gain, Report>()
+//!     .build();
+//!
+//! let mut kernel = Kernel::testing();
+//! assert_eq!(GRAPH.install(&mut kernel), Ok(0));
+//! assert_eq!(kernel.links().len(), 2);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::channel::Channel;
+use crate::kernel::Kernel;
+use crate::scheduler::rms_schedulable;
+use crate::task::{Task, TaskPriority};
+
+/// Typed wire between two nodes
+///
+/// Implemented by [`Channel`]; a graph pushes from the producing node's
+/// task and pops from the consuming node's task only.
+pub trait Port<T>: Sync {
+    /// Queue a value; false if the wire is full (the value is dropped)
+    fn push(&self, value: T) -> bool;
+    /// Oldest queued value
+    fn pop(&self) -> Option<T>;
+    /// Reserve the wire for one producer / consumer pair
+    ///
+    /// Returns false if it is already in use.
+    fn claim(&self) -> bool;
+}
+
+impl<T: Copy + Send, const N: usize> Port<T> for Channel<T, N> {
+    fn push(&self, value: T) -> bool {
+        Channel::push(self, value)
+    }
+
+    fn pop(&self) -> Option<T> {
+        Channel::pop(self)
+    }
+
+    fn claim(&self) -> bool {
+        self.split().is_some()
+    }
+}
+
+/// One equation of a dataflow graph
+///
+/// Sources take `In = ()`, sinks produce `Out = ()` and have no output
+/// wire.
+pub trait Node: 'static {
+    /// Input value type
+    type In: Copy;
+    /// Output value type
+    type Out: Copy;
+    /// Task name
+    const NAME: &'static [u8];
+    /// Evaluation period (µs); also sets the derived priority
+    const PERIOD_US: u32;
+    /// Worst-case execution time of one run (µs)
+    const WCET_US: u32;
+    /// Compute the output for one input value
+    fn eval(input: Self::In) -> Self::Out;
+    /// Wire the output is sent on (`None` for sinks)
+    fn output() -> Option<&'static dyn Port<Self::Out>> {
+        None
+    }
+}
+
+/// Task body of source `S`: evaluate once per period
+fn run_source<S: Node<In = ()>>(_: &mut [u8]) {
+    let value = S::eval(());
+    if let Some(port) = S::output() {
+        port.push(value);
+    }
+}
+
+/// Task body of `N` fed by `U`: evaluate every queued input
+fn run_from<U: Node, N: Node<In = U::Out>>(_: &mut [u8]) {
+    let Some(input) = U::output() else {
+        return;
+    };
+    let output = N::output();
+    while let Some(x) = input.pop() {
+        let y = N::eval(x);
+        if let Some(port) = output {
+            port.push(y);
+        }
+    }
+}
+
+/// Address of `N`'s output wire (node identity when resolving edges)
+fn wire_of<N: Node>() -> Option<*const ()> {
+    N::output().map(|port| core::ptr::from_ref(port).cast::<()>())
+}
+
+/// Claim `N`'s output wire
+fn claim_of<N: Node>() -> bool {
+    N::output().is_some_and(|port| port.claim())
+}
+
+/// Node entry of a graph
+#[derive(Clone, Copy)]
+struct GraphNode {
+    /// Derived task (priority filled in by `build`)
+    task: Task,
+    /// Own output wire
+    output: fn() -> Option<*const ()>,
+    /// Claims the own output wire
+    claim: fn() -> bool,
+    /// Output wire of the upstream node (`None` for sources)
+    input: Option<fn() -> Option<*const ()>>,
+}
+
+/// Why a graph could not be installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphError {
+    /// Node `node` reads from a node that is not part of the graph or has
+    /// no output wire
+    UnboundInput {
+        /// Node index (declaration order)
+        node: usize,
+    },
+    /// Node `node` reads a wire another node already reads
+    SharedWire {
+        /// Node index (declaration order)
+        node: usize,
+    },
+    /// The output wire of node `node` is already split or claimed
+    WireInUse {
+        /// Node index (declaration order)
+        node: usize,
+    },
+    /// The same-rate edge into node `node` closes a cycle
+    Cycle {
+        /// Node index (declaration order)
+        node: usize,
+    },
+    /// The kernel's task table is full
+    TaskTableFull,
+    /// The kernel's link table is full
+    LinkTableFull,
+}
+
+/// Const-evaluable dataflow graph builder
+///
+/// `N` is the number of node slots.
+pub struct GraphBuilder<const N: usize> {
+    /// Nodes in declaration order
+    nodes: [Option<GraphNode>; N],
+    /// Number of nodes added
+    len: usize,
+    /// Priority of the fastest rate
+    base: TaskPriority,
+}
+
+impl<const N: usize> Default for GraphBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> GraphBuilder<N> {
+    /// Empty graph whose fastest nodes run at `TaskPriority::CRITICAL`
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            nodes: [None; N],
+            len: 0,
+            base: TaskPriority::CRITICAL,
+        }
+    }
+
+    /// Start the derived priorities at `base` instead
+    #[must_use]
+    pub const fn with_base_priority(mut self, base: TaskPriority) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Add source node `S`
+    ///
+    /// # Panics
+    ///
+    /// See [`connect`](Self::connect).
+    #[must_use]
+    pub const fn source<S: Node<In = ()>>(self) -> Self {
+        self.push(GraphNode {
+            task: Task::new(
+                S::NAME,
+                run_source::<S>,
+                TaskPriority::IDLE,
+                S::PERIOD_US,
+                S::WCET_US,
+            ),
+            output: wire_of::<S>,
+            claim: claim_of::<S>,
+            input: None,
+        })
+    }
+
+    /// Add node `To`, fed by the output of node `From`
+    ///
+    /// # Panics
+    ///
+    /// In a `const` context these panics are compile errors: more than
+    /// `N` nodes, a zero period, or WCET larger than the period.
+    #[must_use]
+    pub const fn connect<From: Node, To: Node<In = From::Out>>(self) -> Self {
+        self.push(GraphNode {
+            task: Task::new(
+                To::NAME,
+                run_from::<From, To>,
+                TaskPriority::IDLE,
+                To::PERIOD_US,
+                To::WCET_US,
+            ),
+            output: wire_of::<To>,
+            claim: claim_of::<To>,
+            input: Some(wire_of::<From>),
+        })
+    }
+
+    /// Append a node
+    const fn push(mut self, node: GraphNode) -> Self {
+        assert!(self.len < N, "more nodes than configured slots");
+        assert!(node.task.period_us > 0, "node period must be non-zero");
+        assert!(
+            node.task.wcet_us <= node.task.period_us,
+            "node WCET exceeds its period"
+        );
+        self.nodes[self.len] = Some(node);
+        self.len += 1;
+        self
+    }
+
+    /// Derive priorities and finish the graph
+    ///
+    /// Each node's priority is `base` plus the number of distinct shorter
+    /// periods in the graph.
+    ///
+    /// # Panics
+    ///
+    /// If the derived task set fails the RMS test (Liu & Layland, or
+    /// U ≤ 1 for harmonic periods) — a compile error in a `const` item.
+    #[must_use]
+    pub const fn build(mut self) -> Graph<N> {
+        let mut rates = [(0u32, 0u32); N];
+        let mut i = 0;
+        while i < self.len {
+            if let Some(node) = &self.nodes[i] {
+                rates[i] = (node.task.period_us, node.task.wcet_us);
+            }
+            i += 1;
+        }
+        assert!(
+            rms_schedulable(rates.split_at(self.len).0),
+            "graph exceeds the RMS bound"
+        );
+
+        let mut i = 0;
+        while i < self.len {
+            let mut faster = 0u8;
+            let mut j = 0;
+            while j < self.len {
+                // 短い周期を重複なしで数える
+                let p = rates[j].0;
+                let mut first = p < rates[i].0;
+                let mut k = 0;
+                while first && k < j {
+                    first = rates[k].0 != p;
+                    k += 1;
+                }
+                if first {
+                    faster += 1;
+                }
+                j += 1;
+            }
+            if let Some(node) = &mut self.nodes[i] {
+                node.task.priority = TaskPriority(self.base.0.saturating_add(faster));
+            }
+            i += 1;
+        }
+        Graph {
+            nodes: self.nodes,
+            len: self.len,
+        }
+    }
+}
+
+/// Dataflow graph produced by [`GraphBuilder::build`]
+pub struct Graph<const N: usize> {
+    /// Nodes in declaration order
+    nodes: [Option<GraphNode>; N],
+    /// Number of nodes
+    len: usize,
+}
+
+impl<const N: usize> Graph<N> {
+    /// Number of nodes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Is the graph empty?
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Derived task of node `node` (declaration order)
+    #[must_use]
+    pub fn task(&self, node: usize) -> Option<&Task> {
+        self.nodes.get(node)?.as_ref().map(|n| &n.task)
+    }
+
+    /// Register the graph with `kernel`
+    ///
+    /// Returns the task index of the first node; the others follow in
+    /// declaration order. The wiring is checked before anything is
+    /// registered; a full task or link table can leave the graph
+    /// partially installed.
+    pub fn install(&self, kernel: &mut Kernel) -> Result<usize, GraphError> {
+        let nodes = &self.nodes[..self.len];
+        let mut upstream = [0usize; N];
+        for (i, node) in nodes.iter().flatten().enumerate() {
+            let Some(input) = node.input else {
+                continue;
+            };
+            let wire = input().ok_or(GraphError::UnboundInput { node: i })?;
+            upstream[i] = nodes
+                .iter()
+                .flatten()
+                .position(|n| (n.output)() == Some(wire))
+                .ok_or(GraphError::UnboundInput { node: i })?;
+            let readers = nodes
+                .iter()
+                .flatten()
+                .filter(|n| n.input.is_some_and(|f| f() == Some(wire)))
+                .count();
+            if readers > 1 {
+                return Err(GraphError::SharedWire { node: i });
+            }
+        }
+        for (i, node) in nodes.iter().flatten().enumerate() {
+            if node.input.is_some() && !(nodes[upstream[i]].unwrap().claim)() {
+                return Err(GraphError::WireInUse { node: upstream[i] });
+            }
+        }
+
+        let first = kernel.scheduler.task_slots();
+        for node in nodes.iter().flatten() {
+            kernel
+                .scheduler
+                .register(node.task)
+                .ok_or(GraphError::TaskTableFull)?;
+        }
+        for (i, node) in nodes.iter().flatten().enumerate() {
+            if node.input.is_none() {
+                continue;
+            }
+            let (producer, consumer) = (first + upstream[i], first + i);
+            if !kernel.add_link(producer, consumer) {
+                return Err(GraphError::LinkTableFull);
+            }
+            let same_rate = nodes[upstream[i]].unwrap().task.period_us == node.task.period_us;
+            if same_rate && !kernel.scheduler.chain(producer, consumer) {
+                return Err(GraphError::Cycle { node: i });
+            }
+        }
+        Ok(first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::{AtomicU32, Ordering};
+
+    static SAMPLES: Channel<u32, 4> = Channel::new();
+    static SCALED: Channel<u32, 8> = Channel::new();
+    static NEXT: AtomicU32 = AtomicU32::new(1);
+    static SUM: AtomicU32 = AtomicU32::new(0);
+    static SEEN: AtomicU32 = AtomicU32::new(0);
+
+    struct Sample;
+    impl Node for Sample {
+        type In = ();
+        type Out = u32;
+        const NAME: &'static [u8] = b"sample";
+        const PERIOD_US: u32 = 1_000;
+        const WCET_US: u32 = 10;
+        fn eval(_: ()) -> u32 {
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        }
+        fn output() -> Option<&'static dyn Port<u32>> {
+            Some(&SAMPLES)
+        }
+    }
+
+    struct Scale;
+    impl Node for Scale {
+        type In = u32;
+        type Out = u32;
+        const NAME: &'static [u8] = b"scale";
+        const PERIOD_US: u32 = 1_000;
+        const WCET_US: u32 = 10;
+        fn eval(x: u32) -> u32 {
+            10 * x
+        }
+        fn output() -> Option<&'static dyn Port<u32>> {
+            Some(&SCALED)
+        }
+    }
+
+    struct Total;
+    impl Node for Total {
+        type In = u32;
+        type Out = ();
+        const NAME: &'static [u8] = b"total";
+        const PERIOD_US: u32 = 4_000;
+        const WCET_US: u32 = 40;
+        fn eval(x: u32) {
+            SUM.fetch_add(x, Ordering::Relaxed);
+            SEEN.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    const PIPELINE: Graph<3> = GraphBuilder::new()
+        .with_base_priority(TaskPriority::HIGH)
+        .source::<Sample>()
+        .connect::<Sample, Scale>()
+        .connect::<Scale, Total>()
+        .build();
+
+    #[test]
+    fn test_pipeline_derives_tasks_and_runs() {
+        // 周期から優先度: 1 ms の 2 ノードが base, 4 ms は base + 1
+        assert_eq!(PIPELINE.len(), 3);
+        assert_eq!(PIPELINE.task(0).unwrap().priority, TaskPriority::HIGH);
+        assert_eq!(PIPELINE.task(1).unwrap().priority, TaskPriority::HIGH);
+        assert_eq!(PIPELINE.task(2).unwrap().priority, TaskPriority::NORMAL);
+
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"other", |_| {}, TaskPriority::LOW, 10_000, 10);
+        assert_eq!(PIPELINE.install(&mut kernel), Ok(1));
+        assert!(kernel.is_schedulable());
+        // 同一レートの辺は連鎖、異なるレートはチャネルのみ
+        assert!(kernel.scheduler.get_task(2).unwrap().is_chained());
+        assert!(!kernel.scheduler.get_task(3).unwrap().is_chained());
+        assert_eq!(kernel.links().len(), 2);
+        assert_eq!(kernel.links()[1].producer, 2);
+        // 配線は占有済み
+        assert!(SAMPLES.split().is_none());
+        assert_eq!(
+            PIPELINE.install(&mut Kernel::testing()),
+            Err(GraphError::WireInUse { node: 0 })
+        );
+
+        let stats = kernel.run_tickless(8_000);
+        // total は t=0 / 4 ms に溜まった値をまとめて処理: 1 と 2..=5 の 10 倍
+        assert_eq!(SEEN.load(Ordering::Relaxed), 5);
+        assert_eq!(SUM.load(Ordering::Relaxed), 10 * (1..=5).sum::<u32>());
+        // t=5..7 ms の 3 値は次の実行待ち
+        assert_eq!(SCALED.len(), 3);
+        assert!(stats.per_task().iter().all(|t| t.deadline_misses == 0));
+    }
+
+    #[test]
+    fn test_wiring_errors() {
+        static A_OUT: Channel<u32, 2> = Channel::new();
+
+        struct A;
+        impl Node for A {
+            type In = ();
+            type Out = u32;
+            const NAME: &'static [u8] = b"a";
+            const PERIOD_US: u32 = 100;
+            const WCET_US: u32 = 1;
+            fn eval(_: ()) -> u32 {
+                0
+            }
+            fn output() -> Option<&'static dyn Port<u32>> {
+                Some(&A_OUT)
+            }
+        }
+        struct Sink;
+        impl Node for Sink {
+            type In = u32;
+            type Out = ();
+            const NAME: &'static [u8] = b"sink";
+            const PERIOD_US: u32 = 100;
+            const WCET_US: u32 = 1;
+            fn eval(_: u32) {}
+        }
+
+        // 上流ノードがグラフにない
+        let orphan: Graph<1> = GraphBuilder::new().connect::<A, Sink>().build();
+        assert_eq!(
+            orphan.install(&mut Kernel::testing()),
+            Err(GraphError::UnboundInput { node: 0 })
+        );
+        // 1 本の配線を 2 ノードで読むのは SPSC 違反
+        let fan_out: Graph<3> = GraphBuilder::new()
+            .source::<A>()
+            .connect::<A, Sink>()
+            .connect::<A, Sink>()
+            .build();
+        let mut kernel = Kernel::testing();
+        assert_eq!(
+            fan_out.install(&mut kernel),
+            Err(GraphError::SharedWire { node: 1 })
+        );
+        // 検証で失敗したら何も登録しない
+        assert_eq!(kernel.scheduler.task_slots(), 0);
+        assert!(!A_OUT.is_split());
+    }
+
+    #[test]
+    #[should_panic(expected = "RMS bound")]
+    fn test_build_rejects_unschedulable() {
+        struct Hog;
+        impl Node for Hog {
+            type In = ();
+            type Out = ();
+            const NAME: &'static [u8] = b"hog";
+            const PERIOD_US: u32 = 100;
+            const WCET_US: u32 = 70;
+            fn eval(_: ()) {}
+        }
+        struct Slow;
+        impl Node for Slow {
+            type In = ();
+            type Out = ();
+            const NAME: &'static [u8] = b"slow";
+            const PERIOD_US: u32 = 300;
+            const WCET_US: u32 = 100;
+            fn eval(_: ()) {}
+        }
+        let _ = GraphBuilder::<2>::new()
+            .source::<Hog>()
+            .source::<Slow>()
+            .build();
+    }
+}
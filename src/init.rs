@@ -0,0 +1,131 @@
+//! One-time task initialization before the first release
+//!
+//! An [`InitTable`] gives tasks an init function — filter state, peripheral
+//! setup — run once by [`Kernel::start_with`] before the kernel starts
+//! ticking, hence before any periodic release. Init functions run in
+//! priority order (highest first, ties by slot), in task context: each
+//! gets the scratch (or arena) of its task, as its jobs will. A panic in an
+//! init function counts as a fault of its task (std / test builds).
+//!
+//! Init functions run once per boot: restarting a stopped kernel does not
+//! run them again. With the `mpu` feature they run privileged, so an
+//! unprivileged task can still set up its peripheral.
+//!
+//! ```rust
+//! use alice_rtos::init::InitTable;
+//! use alice_rtos::{Kernel, Task, TaskPriority};
+//!
+//! fn filter(scratch: &mut [u8]) {
+//!     assert_eq!(scratch[0], 1);
+//! }
+//! fn filter_init(scratch: &mut [u8]) {
+//!     scratch[0] = 1;
+//! }
+//!
+//! let mut kernel = Kernel::testing();
+//! let task = Task::new(b"filter", filter, TaskPriority::HIGH, 1_000, 10).with_arena(16);
+//! let idx = kernel.try_add(task).unwrap();
+//! let inits = InitTable::new().with(idx, filter_init);
+//! kernel.start_with(&inits).unwrap();
+//! assert_eq!(kernel.tick(0), Some(idx));
+//! assert_eq!(kernel.scheduler.get_task(idx).unwrap().fault_count, 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::task::{TaskFn, MAX_TASKS};
+
+#[cfg(doc)]
+use crate::kernel::Kernel;
+
+/// Init functions of the tasks that have one
+#[derive(Debug, Clone, Copy)]
+pub struct InitTable {
+    /// Init function per task slot
+    inits: [Option<TaskFn>; MAX_TASKS],
+}
+
+impl Default for InitTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InitTable {
+    /// No init functions
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            inits: [None; MAX_TASKS],
+        }
+    }
+
+    /// Run `init` once for task `idx` before scheduling starts
+    ///
+    /// `idx` must be below `MAX_TASKS`.
+    #[must_use]
+    pub const fn with(mut self, idx: usize, init: TaskFn) -> Self {
+        self.inits[idx] = Some(init);
+        self
+    }
+
+    /// Init function of task `idx`
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<TaskFn> {
+        self.inits.get(idx).copied().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::{AtomicU32, Ordering};
+    use crate::kernel::{Kernel, KernelState};
+    use crate::task::TaskPriority;
+
+    /// 初期化順 (各桁が 1 回の呼び出し)
+    static ORDER: AtomicU32 = AtomicU32::new(0);
+
+    fn noop(_: &mut [u8]) {}
+    fn init_low(_: &mut [u8]) {
+        ORDER.store(ORDER.load(Ordering::Relaxed) * 10 + 1, Ordering::Relaxed);
+    }
+    fn init_high(_: &mut [u8]) {
+        ORDER.store(ORDER.load(Ordering::Relaxed) * 10 + 2, Ordering::Relaxed);
+    }
+    fn init_panics(_: &mut [u8]) {
+        panic!("init failed");
+    }
+
+    #[test]
+    fn test_inits_run_once_in_priority_order() {
+        let mut k = Kernel::testing();
+        let low = k
+            .add_task(b"low", noop, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let high = k
+            .add_task(b"high", noop, TaskPriority::HIGH, 1_000, 10)
+            .unwrap();
+        let bad = k
+            .add_task(b"bad", noop, TaskPriority::NORMAL, 1_000, 10)
+            .unwrap();
+        let inits = InitTable::new()
+            .with(low, init_low)
+            .with(high, init_high)
+            .with(bad, init_panics);
+        assert!(inits.get(MAX_TASKS).is_none());
+
+        assert!(k.start_with(&inits).is_ok());
+        assert_eq!(ORDER.load(Ordering::Relaxed), 21);
+        assert_eq!(k.scheduler.get_task(bad).unwrap().fault_count, 1);
+        // まだ一度もリリースされていない
+        assert_eq!(k.scheduler.get_task(low).unwrap().exec_count, 0);
+
+        // 停止からの再開では再実行しない
+        k.stop();
+        assert!(k.start_with(&inits).is_ok());
+        assert_eq!(ORDER.load(Ordering::Relaxed), 21);
+        assert!(k.start_with(&inits).is_err());
+        assert_eq!(k.state(), KernelState::Running);
+    }
+}
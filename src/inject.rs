@@ -0,0 +1,325 @@
+//! Simulation fault injection (`std` feature and tests)
+//!
+//! [`FaultInjector`] steps a kernel like [`Kernel::run_for`] while
+//! disturbing the tasks given a [`FaultProfile`]:
+//!
+//! - **overruns** — after a job, the CPU stays busy up to `overrun_us`
+//!   longer, so the next scheduling point (and every release behind it)
+//!   comes late, as if the body had exceeded its WCET;
+//! - **release jitter** — a release is held back up to `jitter_us`; the
+//!   deadline stays one period after the nominal release;
+//! - **dropped activations** — a release is skipped entirely, like a lost
+//!   interrupt; the task runs again at its next period.
+//!
+//! Decisions come from a seeded xorshift generator, so a failing run can
+//! be reproduced with the same seed. Deadline misses caused by the
+//! injected faults are counted by the kernel as usual, which is what the
+//! deadline-miss handlers and (m,k) accounting under test observe; the
+//! injector's own counters ([`FaultInjector::injected`]) tell how much
+//! adversity was applied.
+//!
+//! ```rust
+//! use alice_rtos::inject::{FaultInjector, FaultProfile};
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! let idx = kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100).unwrap();
+//! let mut injector = FaultInjector::new(42);
+//! injector.set_profile(idx, FaultProfile::NONE.with_drops(100_000));
+//! injector.run_for(&mut kernel, 100_000, 100);
+//! assert!(injector.injected(idx).drops > 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{Kernel, KernelStats};
+use crate::task::{TaskState, MAX_TASKS};
+use crate::timer::{tick_reached, Tick};
+
+/// Per-task fault probabilities and magnitudes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaultProfile {
+    /// Probability that a job overruns (ppm)
+    pub overrun_ppm: u32,
+    /// Largest overrun (µs); each overrun is uniform in `1..=overrun_us`
+    pub overrun_us: u32,
+    /// Largest release delay (µs); each release is delayed uniformly in
+    /// `0..=jitter_us`
+    pub jitter_us: u32,
+    /// Probability that a release is dropped (ppm)
+    pub drop_ppm: u32,
+}
+
+impl FaultProfile {
+    /// No faults
+    pub const NONE: Self = Self {
+        overrun_ppm: 0,
+        overrun_us: 0,
+        jitter_us: 0,
+        drop_ppm: 0,
+    };
+
+    /// Overrun with probability `ppm` by up to `max_us`
+    #[must_use]
+    pub const fn with_overrun(mut self, ppm: u32, max_us: u32) -> Self {
+        self.overrun_ppm = ppm;
+        self.overrun_us = max_us;
+        self
+    }
+
+    /// Delay every release by up to `max_us`
+    #[must_use]
+    pub const fn with_jitter(mut self, max_us: u32) -> Self {
+        self.jitter_us = max_us;
+        self
+    }
+
+    /// Drop releases with probability `ppm`
+    #[must_use]
+    pub const fn with_drops(mut self, ppm: u32) -> Self {
+        self.drop_ppm = ppm;
+        self
+    }
+}
+
+/// Faults applied to one task so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InjectedFaults {
+    /// Jobs that overran
+    pub overruns: u32,
+    /// Total injected overrun time (µs)
+    pub overrun_us: u64,
+    /// Releases held back by jitter
+    pub delayed: u32,
+    /// Releases dropped
+    pub drops: u32,
+}
+
+/// Seeded fault injector driving a [`Kernel`]
+pub struct FaultInjector {
+    /// xorshift32 state (never 0)
+    rng: u32,
+    /// Fault profile per task slot
+    profiles: [FaultProfile; MAX_TASKS],
+    /// Faults applied per task slot
+    injected: [InjectedFaults; MAX_TASKS],
+    /// Tasks held back by jitter and the instant they are let go
+    held: [Option<Tick>; MAX_TASKS],
+    /// Busy time still owed by an overrunning job (µs)
+    stall_us: u64,
+}
+
+impl FaultInjector {
+    /// Injector with no faults configured, seeded with `seed`
+    #[must_use]
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            rng: if seed == 0 { 0x9E37_79B9 } else { seed },
+            profiles: [FaultProfile::NONE; MAX_TASKS],
+            injected: [InjectedFaults {
+                overruns: 0,
+                overrun_us: 0,
+                delayed: 0,
+                drops: 0,
+            }; MAX_TASKS],
+            held: [None; MAX_TASKS],
+            stall_us: 0,
+        }
+    }
+
+    /// Disturb task `idx` according to `profile`
+    pub fn set_profile(&mut self, idx: usize, profile: FaultProfile) {
+        if let Some(p) = self.profiles.get_mut(idx) {
+            *p = profile;
+        }
+    }
+
+    /// Faults applied to task `idx` so far
+    #[must_use]
+    pub fn injected(&self, idx: usize) -> InjectedFaults {
+        self.injected.get(idx).copied().unwrap_or_default()
+    }
+
+    /// Next pseudo-random value (xorshift32)
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x
+    }
+
+    /// True with probability `ppm`
+    fn chance(&mut self, ppm: u32) -> bool {
+        ppm > 0 && self.next_u32() % 1_000_000 < ppm
+    }
+
+    /// Uniform value in `0..=max`
+    fn upto(&mut self, max: u32) -> u32 {
+        if max == 0 {
+            0
+        } else {
+            (self.next_u32() as u64 % (max as u64 + 1)) as u32
+        }
+    }
+
+    /// Advance the kernel by `delta_us` (plus any owed overrun) with faults
+    ///
+    /// Returns the executed task index, if any.
+    pub fn step(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        // 超過実行の分だけ次のスケジューリング点が遅れる
+        let delta = delta_us + core::mem::take(&mut self.stall_us);
+        let target = kernel.scheduler.now_us().wrapping_add(delta as Tick);
+        for idx in 0..kernel.scheduler.task_slots() {
+            self.disturb_release(kernel, idx, target);
+        }
+
+        let executed = kernel.tick(delta);
+        if let Some(idx) = executed {
+            let profile = self.profiles[idx];
+            if self.chance(profile.overrun_ppm) {
+                let overrun = self.upto(profile.overrun_us.saturating_sub(1)) + 1;
+                self.stall_us = overrun as u64;
+                self.injected[idx].overruns += 1;
+                self.injected[idx].overrun_us += overrun as u64;
+            }
+        }
+        executed
+    }
+
+    /// Drop or hold back a release of `idx` falling before `target`
+    fn disturb_release(&mut self, kernel: &mut Kernel, idx: usize, target: Tick) {
+        let profile = self.profiles[idx];
+        let Some(task) = kernel.scheduler.get_task_mut(idx) else {
+            return;
+        };
+        if let Some(until) = self.held[idx] {
+            // 保留中: 解放時刻に達したら名目上のリリース時刻のまま戻す
+            if task.state != TaskState::Suspended {
+                self.held[idx] = None;
+            } else if tick_reached(target, until) {
+                task.state = TaskState::Sleeping;
+                self.held[idx] = None;
+            }
+            return;
+        }
+        if task.state != TaskState::Sleeping
+            || !task.time_released()
+            || !tick_reached(target, task.next_activation)
+        {
+            return;
+        }
+        let release = task.next_activation;
+        let period = task.period_us;
+        if self.chance(profile.drop_ppm) {
+            task.next_activation = release.wrapping_add(period as Tick);
+            self.injected[idx].drops += 1;
+            return;
+        }
+        let until = release.wrapping_add(self.upto(profile.jitter_us) as Tick);
+        if !tick_reached(target, until) {
+            task.state = TaskState::Suspended;
+            self.held[idx] = Some(until);
+            self.injected[idx].delayed += 1;
+        }
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`])
+    pub fn run_for(&mut self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        while kernel.timer.now_us() - start < total_us {
+            if self.step(kernel, tick_us).is_some() {
+                tasks_executed += 1;
+            }
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    /// Two tasks, the low-priority one with 300 µs of slack
+    fn kernel() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 100);
+        kernel.add_task(b"lo", noop, TaskPriority::LOW, 1_000, 100);
+        kernel
+    }
+
+    fn misses(kernel: &Kernel, idx: usize) -> u32 {
+        kernel.scheduler.get_task(idx).unwrap().deadline_misses
+    }
+
+    #[test]
+    fn test_no_profile_is_transparent() {
+        let mut plain = kernel();
+        let mut injected = kernel();
+        let mut injector = FaultInjector::new(7);
+        let a = plain.run_for(50_000, 100);
+        let b = injector.run_for(&mut injected, 50_000, 100);
+        assert_eq!(a.tasks_executed, b.tasks_executed);
+        assert_eq!(injector.injected(0), InjectedFaults::default());
+        assert_eq!((misses(&injected, 0), misses(&injected, 1)), (0, 0));
+    }
+
+    #[test]
+    fn test_overruns_cause_misses_downstream() {
+        let mut k = kernel();
+        let mut injector = FaultInjector::new(1);
+        // hi が毎回 1.5 ms 近く超過 → lo が締め切りを落とす
+        injector.set_profile(0, FaultProfile::NONE.with_overrun(1_000_000, 1_500));
+        injector.run_for(&mut k, 100_000, 100);
+        let faults = injector.injected(0);
+        assert!(faults.overruns > 10);
+        assert!(faults.overrun_us >= faults.overruns as u64);
+        assert!(faults.overrun_us <= faults.overruns as u64 * 1_500);
+        assert!(misses(&k, 1) > 0);
+        assert_eq!(injector.injected(1), InjectedFaults::default());
+    }
+
+    #[test]
+    fn test_jitter_keeps_nominal_deadline() {
+        // 周期未満のジッタでは締め切りを落とさない
+        let mut k = kernel();
+        let mut injector = FaultInjector::new(3);
+        injector.set_profile(1, FaultProfile::NONE.with_jitter(500));
+        let stats = injector.run_for(&mut k, 100_000, 100);
+        assert!(injector.injected(1).delayed > 50);
+        assert_eq!(misses(&k, 1), 0);
+        // t = 100 ms の lo は保留中
+        assert_eq!(stats.tasks_executed, 201);
+
+        // 周期を超えるジッタは名目上の締め切りに対して遅れとなる
+        let mut k = kernel();
+        let mut injector = FaultInjector::new(3);
+        injector.set_profile(1, FaultProfile::NONE.with_jitter(3_000));
+        injector.run_for(&mut k, 100_000, 100);
+        assert!(misses(&k, 1) > 0);
+    }
+
+    #[test]
+    fn test_drops_skip_jobs_and_are_reproducible() {
+        let run = |seed| {
+            let mut k = kernel();
+            let mut injector = FaultInjector::new(seed);
+            injector.set_profile(0, FaultProfile::NONE.with_drops(250_000));
+            injector.run_for(&mut k, 100_000, 100);
+            let runs = k.scheduler.get_task(0).unwrap().exec_count;
+            (injector.injected(0).drops, runs)
+        };
+        let (drops, runs) = run(11);
+        assert!((10..=45).contains(&drops));
+        // 落とした分だけジョブが減る (t = 0..=100 ms の 101 回)
+        assert_eq!(drops + runs, 101);
+        assert_eq!(run(11), (drops, runs));
+    }
+}
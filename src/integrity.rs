@@ -0,0 +1,302 @@
+//! CRC-protected task table with periodic integrity checks
+//!
+//! Radiation or EMI can flip bits in the static task table. A
+//! [`TaskTableGuard`] keeps a CRC-32 of each task's *configuration* —
+//! name, function, base priority, period, WCET, scratch size,
+//! predecessors, affinity, modes, group and release flags — together with
+//! a golden copy of the descriptor. Runtime fields (state, next
+//! activation, counters) change every tick and are not covered.
+//!
+//! [`TaskTableGuard::tick`] verifies one sealed descriptor per tick in
+//! round-robin order before ticking the kernel, so the whole table is
+//! checked every `MAX_TASKS` ticks at a bounded cost. A mismatch counts
+//! as a corruption: it is reported as [`KernelEvent::TaskCorrupted`] and
+//! recorded in the black box ([`FaultCode::TASK_CORRUPTION`]); with
+//! [`with_restore`](TaskTableGuard::with_restore) the configuration is
+//! also copied back from the golden descriptor.
+//!
+//! Legitimate changes must go through [`TaskTableGuard::update`], which
+//! reseals the table afterwards — including changes made by managers such
+//! as [`OverloadManager`](crate::overload::OverloadManager) down-rating a
+//! task. Tasks registered after [`seal`](TaskTableGuard::seal) are not
+//! checked until the next seal.
+//!
+//! ```rust
+//! use alice_rtos::integrity::TaskTableGuard;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100).unwrap();
+//! let mut guard = TaskTableGuard::new().with_restore();
+//! guard.seal(&kernel);
+//! // Legitimate mode change
+//! guard.update(&mut kernel, |k| k.scheduler.set_period(0, 2_000));
+//! guard.run_for(&mut kernel, 10_000, 100);
+//! assert_eq!(guard.corruptions(), 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::events::{emit, KernelEvent};
+use crate::fault::{self, FaultCode};
+use crate::kernel::{Kernel, KernelStats};
+use crate::snapshot::crc32;
+use crate::task::{Task, TaskMask, CONFIG_FLAGS, MAX_TASKS};
+
+/// Configuration bytes covered by the CRC
+const CONFIG_BYTES: usize = 33;
+
+/// Per-task CRCs and golden descriptors of a kernel's task table
+pub struct TaskTableGuard {
+    /// CRC-32 of each sealed task's configuration
+    crcs: [u32; MAX_TASKS],
+    /// Descriptors as sealed
+    golden: [Task; MAX_TASKS],
+    /// Slots covered by the last seal
+    sealed: TaskMask,
+    /// Copy the configuration back from `golden` on a mismatch
+    restore: bool,
+    /// Next slot checked by `tick`
+    cursor: u8,
+    /// Mismatches found
+    corruptions: u32,
+}
+
+impl Default for TaskTableGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaskTableGuard {
+    /// Guard with nothing sealed that only reports corruption
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            crcs: [0; MAX_TASKS],
+            golden: [Task::empty(); MAX_TASKS],
+            sealed: 0,
+            restore: false,
+            cursor: 0,
+            corruptions: 0,
+        }
+    }
+
+    /// Also restore corrupted configuration from the golden copy
+    #[must_use]
+    pub const fn with_restore(mut self) -> Self {
+        self.restore = true;
+        self
+    }
+
+    /// Record CRCs and golden copies of all active tasks
+    pub fn seal(&mut self, kernel: &Kernel) {
+        self.sealed = 0;
+        for (idx, task) in kernel.scheduler.tasks() {
+            self.crcs[idx] = config_crc(kernel, idx, task);
+            self.golden[idx] = *task;
+            // ブースト中は戻り先の基底優先度を保存
+            self.golden[idx].priority =
+                kernel.scheduler.base_priority(idx).unwrap_or(task.priority);
+            self.sealed |= 1 << idx;
+        }
+    }
+
+    /// Apply a legitimate change with `f`, then reseal the table
+    pub fn update<R>(&mut self, kernel: &mut Kernel, f: impl FnOnce(&mut Kernel) -> R) -> R {
+        let result = f(kernel);
+        self.seal(kernel);
+        result
+    }
+
+    /// Slots covered by the last seal
+    #[must_use]
+    pub const fn sealed(&self) -> TaskMask {
+        self.sealed
+    }
+
+    /// Check task `idx` against its seal
+    ///
+    /// Returns false on a mismatch, after reporting it (and restoring the
+    /// configuration when enabled). Unsealed slots always pass.
+    pub fn verify(&mut self, kernel: &mut Kernel, idx: usize) -> bool {
+        if idx >= MAX_TASKS || self.sealed & (1 << idx) == 0 {
+            return true;
+        }
+        let crc = kernel
+            .scheduler
+            .get_task(idx)
+            .map_or(!self.crcs[idx], |task| config_crc(kernel, idx, task));
+        if crc == self.crcs[idx] {
+            return true;
+        }
+        self.corruptions = self.corruptions.saturating_add(1);
+        let restored = self.restore && self.restore_config(kernel, idx);
+        emit(KernelEvent::TaskCorrupted {
+            idx: idx as u8,
+            restored,
+        });
+        fault::capture(
+            FaultCode::TASK_CORRUPTION,
+            Some(idx),
+            kernel.timer.now_us(),
+            crc,
+        );
+        false
+    }
+
+    /// Check every sealed task; returns the corrupted ones
+    pub fn verify_all(&mut self, kernel: &mut Kernel) -> TaskMask {
+        (0..MAX_TASKS)
+            .filter(|&idx| !self.verify(kernel, idx))
+            .fold(0, |mask, idx| mask | 1 << idx)
+    }
+
+    /// Verify the next sealed task, then tick the kernel
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        if self.sealed != 0 {
+            // 封印済みスロットを 1 tick に 1 つずつ巡回
+            let mut idx = self.cursor as usize;
+            while self.sealed & (1 << idx) == 0 {
+                idx = (idx + 1) % MAX_TASKS;
+            }
+            self.verify(kernel, idx);
+            self.cursor = ((idx + 1) % MAX_TASKS) as u8;
+        }
+        kernel.tick(delta_us)
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`])
+    pub fn run_for(&mut self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        while kernel.timer.now_us() - start < total_us {
+            if self.tick(kernel, tick_us).is_some() {
+                tasks_executed += 1;
+            }
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+
+    /// Corruptions found so far
+    #[must_use]
+    pub const fn corruptions(&self) -> u32 {
+        self.corruptions
+    }
+
+    /// Copy the sealed configuration of `idx` back into the task table
+    ///
+    /// A boosted task keeps its current (boosted) priority. Returns
+    /// whether the task matches its seal again.
+    fn restore_config(&self, kernel: &mut Kernel, idx: usize) -> bool {
+        let boosted = kernel.scheduler.boosted().is_some_and(|(b, _)| b == idx);
+        let golden = &self.golden[idx];
+        let Some(task) = kernel.scheduler.get_task_mut(idx) else {
+            return false;
+        };
+        task.name = golden.name;
+        task.func = golden.func;
+        if !boosted {
+            task.priority = golden.priority;
+        }
+        task.period_us = golden.period_us;
+        task.wcet_us = golden.wcet_us;
+        task.scratch_size = golden.scratch_size;
+        task.predecessors = golden.predecessors;
+        task.affinity = golden.affinity;
+        task.flags = (task.flags & !CONFIG_FLAGS) | (golden.flags & CONFIG_FLAGS);
+        task.modes = golden.modes;
+        task.group = golden.group;
+        let task = *task;
+        config_crc(kernel, idx, &task) == self.crcs[idx]
+    }
+}
+
+/// CRC-32 of the configuration of task `idx`
+fn config_crc(kernel: &Kernel, idx: usize, task: &Task) -> u32 {
+    let priority = kernel.scheduler.base_priority(idx).unwrap_or(task.priority);
+    let func = task.func.map_or(0, |f| f as usize as u64);
+    let mut bytes = [0u8; CONFIG_BYTES];
+    bytes[..8].copy_from_slice(&task.name);
+    bytes[8..16].copy_from_slice(&func.to_le_bytes());
+    bytes[16] = priority.0;
+    bytes[17..21].copy_from_slice(&task.period_us.to_le_bytes());
+    bytes[21..25].copy_from_slice(&task.wcet_us.to_le_bytes());
+    bytes[25..27].copy_from_slice(&task.scratch_size.to_le_bytes());
+    bytes[27..29].copy_from_slice(&task.predecessors.to_le_bytes());
+    bytes[29] = task.affinity;
+    bytes[30] = task.flags & CONFIG_FLAGS;
+    bytes[31] = task.modes;
+    bytes[32] = task.group;
+    crc32(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn other(_: &mut [u8]) {}
+
+    fn kernel() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100);
+        kernel.add_task(b"log", noop, TaskPriority::LOW, 5_000, 200);
+        kernel
+    }
+
+    #[test]
+    fn test_bit_flip_detected_round_robin() {
+        let mut k = kernel();
+        let mut guard = TaskTableGuard::new();
+        guard.seal(&k);
+        assert_eq!(guard.sealed(), 0b11);
+        guard.run_for(&mut k, 5_000, 100);
+        assert_eq!(guard.corruptions(), 0);
+
+        // 周期のビット反転: 次の巡回で検出、復元なし
+        k.scheduler.get_task_mut(1).unwrap().period_us ^= 1 << 3;
+        guard.tick(&mut k, 100);
+        guard.tick(&mut k, 100);
+        assert_eq!(guard.corruptions(), 1);
+        assert_eq!(k.scheduler.get_task(1).unwrap().period_us, 5_000 ^ 8);
+        assert_eq!(guard.verify_all(&mut k), 0b10);
+    }
+
+    #[test]
+    fn test_restore_from_golden_copy() {
+        let mut k = kernel();
+        let mut guard = TaskTableGuard::new().with_restore();
+        guard.seal(&k);
+        k.tick(100);
+        let task = k.scheduler.get_task_mut(0).unwrap();
+        task.func = Some(other);
+        task.priority = TaskPriority(9);
+        assert_eq!(guard.verify_all(&mut k), 0b01);
+        let task = k.scheduler.get_task(0).unwrap();
+        assert_eq!(task.priority, TaskPriority::HIGH);
+        // 実行時の状態は復元対象外
+        assert_eq!(task.exec_count, 1);
+        assert_eq!(guard.verify_all(&mut k), 0);
+        assert_eq!(guard.corruptions(), 1);
+    }
+
+    #[test]
+    fn test_legitimate_changes_reseal() {
+        let mut k = kernel();
+        let mut guard = TaskTableGuard::new();
+        guard.seal(&k);
+        assert!(guard.update(&mut k, |k| k.scheduler.set_period(0, 2_000)));
+        // ブーストは基底優先度で CRC を取るので検出しない
+        assert!(k.scheduler.boost(1, TaskPriority::CRITICAL, 500));
+        // 封印後に登録したタスクは対象外
+        k.add_task(b"new", noop, TaskPriority::NORMAL, 10_000, 10);
+        guard.run_for(&mut k, 20_000, 100);
+        assert_eq!(guard.corruptions(), 0);
+        assert_eq!(guard.sealed(), 0b011);
+    }
+}
@@ -5,353 +5,3445 @@
 //!
 //! Author: Moroya Sakamoto
 
+use core::fmt;
+
+use crate::bist::{self, BistFailures};
+use crate::budget::BudgetTable;
+use crate::calibrate::{CalibrationReport, WcetCalibrator};
+use crate::channel::{ChannelPool, Link, Receiver, Sender, MAX_LINKS};
+use crate::doorbell::Doorbell;
+use crate::error::{KernelError, KernelResult};
+use crate::events::{emit, KernelEvent};
+use crate::fault::{self, FaultCode};
+use crate::init::InitTable;
+use crate::policy::{Rms, SchedulingPolicy};
+use crate::power::{SleepState, SleepStats};
+use crate::profiler::{LoadAverages, TaskProfiler};
+use crate::sched_lock::SchedulerLock;
 use crate::scheduler::Scheduler;
-use crate::task::{Task, TaskFn, TaskPriority};
-use crate::timer::SysTimer;
+use crate::snapshot::{
+    decode_state, encode_state, Reader, SnapshotError, Writer, KERNEL_RECORD_SIZE, TASK_RECORD_SIZE,
+};
+use crate::soft_timer::{SoftTimerPool, TimerCallback};
+#[cfg(feature = "task-table")]
+use crate::table::{self, LoadError, TaskSpec};
+use crate::task::{
+    Mode, Task, TaskFn, TaskMask, TaskPriority, TaskState, ANY_CORE, FLAG_MODE_SUSPENDED,
+    MAX_CORES, MAX_TASKS,
+};
+use crate::telemetry::WireWriter;
+#[cfg(feature = "std")]
+use crate::telemetry::{DecodeError, WireReader};
+use crate::text::{Percent, ShortName};
+use crate::timer::{
+    tick_reached, tick_to_u64, ticks_since, ticks_until, SysTimer, Tick, TickSource,
+};
+
+/// Scratch bytes of the default [`Kernel`]
+pub const DEFAULT_SCRATCH: usize = 1024;
+
+/// Fill pattern for unused scratch (stack canary)
+pub const SCRATCH_CANARY: u8 = 0xA5;
+
+/// Guard bytes at the end of scratch that must stay untouched
+const GUARD_SIZE: usize = 4;
+
+/// `Kernel::flags`: the two low bits hold the [`KernelState`]
+const STATE_MASK: u8 = 0b11;
+
+/// `Kernel::flags`: scratch canary checking enabled
+const FLAG_STACK_CHECK: u8 = 1 << 2;
+
+/// `Kernel::flags`: tasks may be added while running
+const FLAG_LIVE_REGISTRATION: u8 = 1 << 3;
+
+/// `Kernel::flags`: bits 4..6 hold the [`Admission`] mode
+const ADMISSION_SHIFT: u8 = 4;
+const ADMISSION_MASK: u8 = 0b11 << ADMISSION_SHIFT;
+
+/// Schedulability check applied by [`Kernel::add_task`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum Admission {
+    /// Accept every task (check with [`Kernel::is_schedulable`] yourself)
+    #[default]
+    Off = 0,
+    /// Refuse a task that misses its deadline or makes another task miss
+    /// one it met ([`Scheduler::admits`]: RMS bound, then exact
+    /// response-time analysis of the set with and without the task)
+    Reject = 1,
+    /// Accept it, but report [`KernelEvent::Overcommitted`]
+    Flag = 2,
+}
+
+/// Kernel life-cycle state
+///
+/// ```text
+/// Init ──start──▶ Running ──stop──▶ Stopped ──start──▶ Running
+///   │                │                  │
+///   └─────────── enter_fault ───────────┴──▶ Fault ──recover──▶ Init
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KernelState {
+    /// Configuring: tasks are registered, nothing runs yet
+    Init = 0,
+    /// Ticking
+    Running = 1,
+    /// Halted by [`Kernel::stop`] or the end of a `run_for`
+    Stopped = 2,
+    /// Halted by [`Kernel::enter_fault`]; ticks are refused
+    Fault = 3,
+}
+
+impl KernelState {
+    /// Is `self → to` a valid transition?
+    #[must_use]
+    pub const fn can_enter(self, to: Self) -> bool {
+        matches!(
+            (self, to),
+            (Self::Init | Self::Stopped, Self::Running)
+                | (Self::Running, Self::Stopped)
+                | (Self::Init | Self::Running | Self::Stopped, Self::Fault)
+                | (Self::Fault, Self::Init)
+        )
+    }
+
+    /// Decode the state bits of `Kernel::flags`
+    const fn from_bits(bits: u8) -> Self {
+        match bits & STATE_MASK {
+            0 => Self::Init,
+            1 => Self::Running,
+            2 => Self::Stopped,
+            _ => Self::Fault,
+        }
+    }
+}
+
+/// Rejected [`KernelState`] transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    /// State the kernel was in
+    pub from: KernelState,
+    /// State that was requested
+    pub to: KernelState,
+}
+
+/// Job dispatched on a core by [`SizedKernel::dispatch_core`]
+///
+/// Holds what running the task needs, so a core can run it after
+/// releasing the kernel lock and hand it back to
+/// [`complete_core`](SizedKernel::complete_core).
+#[derive(Debug, Clone, Copy)]
+#[must_use]
+pub struct CoreJob {
+    /// Dispatched task
+    pub idx: usize,
+    /// Core it was dispatched on
+    pub core: usize,
+    /// Copy of the task (function and privilege)
+    task: Task,
+    /// Profiler timestamp at dispatch
+    started: Option<u64>,
+    /// Budget clock at dispatch
+    budget_started: Option<u64>,
+    /// Release → dispatch (µs)
+    latency_us: u64,
+}
+
+impl CoreJob {
+    /// Run the task on `scratch`; no kernel access is needed
+    ///
+    /// Returns false if the task panicked (std / test builds with
+    /// `panic = "unwind"`).
+    pub fn run(&self, scratch: &mut [u8]) -> bool {
+        contain(|| self.task.run(scratch))
+    }
+}
+
+/// Run `f`, containing a panic (std / test builds)
+///
+/// Returns false if `f` panicked. Only a build that unwinds can catch a
+/// panic: the std application needs `panic = "unwind"` (Cargo's default)
+/// in the profile it is built with. This crate's own release profile
+/// sets `panic = "abort"` for flash size, so there a task panic aborts
+/// the process and is never counted.
+#[cfg(all(any(feature = "std", test), panic = "unwind"))]
+fn contain(f: impl FnOnce()) -> bool {
+    extern crate std;
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_ok()
+}
+
+/// Run `f` (bare metal, or `panic = "abort"`: a panic is fatal)
+#[cfg(not(all(any(feature = "std", test), panic = "unwind")))]
+fn contain(f: impl FnOnce()) -> bool {
+    f();
+    true
+}
+
+/// Hooks run around each tick of the `*_observed` kernel entry points
+///
+/// Managers that follow the task table from tick to tick
+/// ([`Lifecycle`](crate::lifecycle::Lifecycle),
+/// [`ActivationQueue`](crate::activation::ActivationQueue),
+/// [`DeadlineTracker`](crate::deadlines::DeadlineTracker)) implement it,
+/// so several of them share one tick: a tuple of observers runs each
+/// hook in tuple order, and `&mut` observers compose as well.
+///
+/// The kernel stores no observer (its footprint is unchanged), so only
+/// [`tick_observed`](SizedKernel::tick_observed),
+/// [`run_for_observed`](SizedKernel::run_for_observed),
+/// [`tick_tickless_observed`](SizedKernel::tick_tickless_observed) and
+/// [`run_tickless_observed`](SizedKernel::run_tickless_observed) feed
+/// one. Ticks through [`tick_core`](SizedKernel::tick_core), a
+/// [`SchedulerLock`] or a `StaticKernel` interrupt are not observed.
+///
+/// ```rust
+/// use alice_rtos::activation::ActivationQueue;
+/// use alice_rtos::deadlines::DeadlineTracker;
+/// use alice_rtos::lifecycle::Lifecycle;
+/// use alice_rtos::{Kernel, TaskPriority};
+///
+/// fn noop(_: &mut [u8]) {}
+///
+/// let mut kernel = Kernel::testing();
+/// let ctl = kernel.add_task(b"ctl", noop, TaskPriority::LOW, 1_000, 10).unwrap();
+/// let mut managers = (Lifecycle::new(), ActivationQueue::new(), DeadlineTracker::new());
+/// assert!(managers.2.set_relative(&kernel, ctl, 400));
+/// // One tick drives all three
+/// assert_eq!(kernel.tick_observed(0, &mut managers), Some(ctl));
+/// kernel.run_tickless_observed(10_000, &mut managers);
+/// assert_eq!(managers.2.misses(ctl), 0);
+/// ```
+pub trait TickObserver {
+    /// Before the tick, with the task table as the application left it
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        _kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+    }
+
+    /// After the tick, which executed task `executed`
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        _kernel: &mut SizedKernel<SCRATCH, P>,
+        _executed: Option<usize>,
+    ) {
+    }
+}
+
+/// No observer: the plain entry points
+impl TickObserver for () {}
+
+impl<T: TickObserver + ?Sized> TickObserver for &mut T {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        (**self).before_tick(kernel);
+    }
+
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        executed: Option<usize>,
+    ) {
+        (**self).after_tick(kernel, executed);
+    }
+}
+
+impl<A: TickObserver, B: TickObserver> TickObserver for (A, B) {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        self.0.before_tick(kernel);
+        self.1.before_tick(kernel);
+    }
+
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        executed: Option<usize>,
+    ) {
+        self.0.after_tick(kernel, executed);
+        self.1.after_tick(kernel, executed);
+    }
+}
+
+impl<A: TickObserver, B: TickObserver, C: TickObserver> TickObserver for (A, B, C) {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        self.0.before_tick(kernel);
+        self.1.before_tick(kernel);
+        self.2.before_tick(kernel);
+    }
 
-/// Scratch buffer for task execution
-const SCRATCH_SIZE: usize = 1024;
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        executed: Option<usize>,
+    ) {
+        self.0.after_tick(kernel, executed);
+        self.1.after_tick(kernel, executed);
+        self.2.after_tick(kernel, executed);
+    }
+}
+
+/// Context-switch callback receiving the task index
+#[cfg(feature = "hooks")]
+pub type SwitchHook = fn(usize);
 
-/// ALICE-RTOS Kernel
+/// ALICE-RTOS Kernel with the default 1 KB scratch
 ///
 /// Total memory footprint:
-/// - Scheduler: ~512 bytes (16 tasks × 32 bytes)
-/// - Timer: 16 bytes
+/// - Scheduler: ~920 bytes (16 tasks × 56 bytes)
+/// - Timer: 24 bytes
 /// - Scratch: 1024 bytes
 /// - Total: < 2 KB
-pub struct Kernel {
+pub type Kernel = SizedKernel<DEFAULT_SCRATCH>;
+
+/// Kernel with `SCRATCH` bytes of task scratch
+///
+/// The scratch is laid out at registration: tasks built with
+/// [`Task::with_arena`] get a dedicated arena of their declared size, in
+/// task-index order from the start of scratch, and keep it to themselves;
+/// all other tasks share the rest (split per core by
+/// [`tick_core`](Self::tick_core)). The arenas must fit `SCRATCH` minus
+/// the 4 guard bytes, which [`try_add`](Self::try_add) checks; removing
+/// an arena task moves the arenas of higher-index tasks down. An app
+/// with one 4 KB FFT task and a few small ones uses e.g.
+/// `SizedKernel<5120>` instead of enlarging every task's buffer.
+pub struct SizedKernel<const SCRATCH: usize, P: SchedulingPolicy = Rms> {
     /// Task scheduler
-    pub scheduler: Scheduler,
+    pub scheduler: Scheduler<P>,
     /// System timer
     pub timer: SysTimer,
-    /// Shared scratch buffer for task execution
-    scratch: [u8; SCRATCH_SIZE],
-    /// Kernel state
-    running: bool,
+    /// Task arenas followed by the shared scratch
+    scratch: [u8; SCRATCH],
+    /// Kernel state bits (`FLAG_*`)
+    flags: u8,
     /// Total ticks executed
     pub total_ticks: u64,
+    /// Channel wiring between tasks
+    links: [Link; MAX_LINKS],
+    /// Tasks that overran their scratch budget or hit the guard
+    stack_faults: TaskMask,
+    /// Guard corruptions detected outside task execution
+    guard_corruptions: u16,
+    /// Current operating mode
+    mode: Mode,
+    /// Attached software timer pool
+    timers: Option<&'static mut SoftTimerPool>,
+    /// Attached per-task timing profiler
+    profiler: Option<&'static mut TaskProfiler>,
+    /// Attached execution budgets
+    budgets: Option<&'static mut BudgetTable>,
+    /// Faults after which a task is auto-suspended (0 = never)
+    fault_limit: u16,
+    /// Context-switch instrumentation (enter, exit)
+    #[cfg(feature = "hooks")]
+    switch_hooks: Option<(SwitchHook, SwitchHook)>,
 }
 
-impl Kernel {
+impl<const SCRATCH: usize> SizedKernel<SCRATCH> {
     /// Create kernel with hardware clock
     #[must_use]
     pub const fn new(clock_hz: u32) -> Self {
+        Self::with_policy(SysTimer::new(clock_hz), Rms)
+    }
+
+    /// Create kernel for testing (software timer)
+    #[must_use]
+    pub const fn testing() -> Self {
+        Self::with_policy(SysTimer::software(), Rms)
+    }
+}
+
+impl<const SCRATCH: usize, P: SchedulingPolicy> SizedKernel<SCRATCH, P> {
+    /// Scratch must hold the guard and fit the `u16` watermarks
+    const SCRATCH_OK: () = assert!(
+        SCRATCH > GUARD_SIZE && SCRATCH <= u16::MAX as usize,
+        "scratch must be 5..=65535 bytes"
+    );
+
+    /// Create kernel on `timer` dispatching in `policy`'s order
+    ///
+    /// See [`policy`](crate::policy); [`new`](SizedKernel::new) and
+    /// [`testing`](SizedKernel::testing) use [`Rms`].
+    #[must_use]
+    pub const fn with_policy(timer: SysTimer, policy: P) -> Self {
+        let () = Self::SCRATCH_OK;
         Self {
-            scheduler: Scheduler::new(),
-            timer: SysTimer::new(clock_hz),
-            scratch: [0u8; SCRATCH_SIZE],
-            running: false,
+            scheduler: Scheduler::with_policy(policy),
+            timer,
+            scratch: [0u8; SCRATCH],
+            flags: 0,
             total_ticks: 0,
+            links: [Link {
+                producer: 0,
+                consumer: 0,
+            }; MAX_LINKS],
+            stack_faults: 0,
+            guard_corruptions: 0,
+            mode: Mode(0),
+            timers: None,
+            profiler: None,
+            budgets: None,
+            fault_limit: 0,
+            #[cfg(feature = "hooks")]
+            switch_hooks: None,
+        }
+    }
+
+    /// Register a task
+    ///
+    /// Refused (`None`) while [`Running`](KernelState::Running) unless
+    /// [`allow_live_registration`](Self::allow_live_registration) is set,
+    /// and by [`Admission::Reject`] when the task would break a deadline
+    /// guarantee ([`Scheduler::admits`]).
+    pub fn add_task(
+        &mut self,
+        name: &[u8],
+        func: TaskFn,
+        priority: TaskPriority,
+        period_us: u32,
+        wcet_us: u32,
+    ) -> Option<usize> {
+        self.try_add_task(name, func, priority, period_us, wcet_us)
+            .ok()
+    }
+
+    /// [`add_task`](Self::add_task), reporting why a task was refused
+    pub fn try_add_task(
+        &mut self,
+        name: &[u8],
+        func: TaskFn,
+        priority: TaskPriority,
+        period_us: u32,
+        wcet_us: u32,
+    ) -> KernelResult<usize> {
+        self.try_add(Task::new(name, func, priority, period_us, wcet_us))
+    }
+
+    /// Validate a static task table and register all of it
+    ///
+    /// Row `i` of `specs` runs `funcs[i]`. Nothing is registered unless
+    /// the whole table passes (see [`table`]); returns the
+    /// slots of the loaded tasks.
+    #[cfg(feature = "task-table")]
+    pub fn load_table(
+        &mut self,
+        specs: &[TaskSpec],
+        funcs: &[TaskFn],
+    ) -> Result<TaskMask, LoadError> {
+        table::load(self, specs, funcs)
+    }
+
+    /// Register a prepared task (e.g. built with [`Task::with_arena`])
+    ///
+    /// Same rules as [`try_add_task`](Self::try_add_task); additionally
+    /// refused with [`KernelError::ScratchExhausted`] if the task's arena
+    /// does not fit next to those already laid out.
+    pub fn try_add(&mut self, task: Task) -> KernelResult<usize> {
+        if self.state() == KernelState::Running && self.flags & FLAG_LIVE_REGISTRATION == 0 {
+            return Err(KernelError::KernelRunning);
+        }
+        if task.has_arena()
+            && self.arena_bytes() + task.scratch_size as usize > SCRATCH - GUARD_SIZE
+        {
+            return Err(KernelError::ScratchExhausted);
+        }
+        let idx = self.scheduler.try_register(task)?;
+        let admission = self.admission();
+        if admission != Admission::Off && !self.scheduler.admits(idx) {
+            if admission == Admission::Reject {
+                self.scheduler.unregister(idx);
+                return Err(KernelError::NotSchedulable);
+            }
+            emit(KernelEvent::Overcommitted { idx: idx as u8 });
+        }
+        emit(KernelEvent::TaskRegistered { idx: idx as u8 });
+        Ok(idx)
+    }
+
+    /// Run the schedulability test on every [`add_task`](Self::add_task)
+    pub const fn set_admission(&mut self, admission: Admission) {
+        self.flags = (self.flags & !ADMISSION_MASK) | (admission as u8) << ADMISSION_SHIFT;
+    }
+
+    /// Current admission-control mode
+    #[must_use]
+    pub const fn admission(&self) -> Admission {
+        match (self.flags & ADMISSION_MASK) >> ADMISSION_SHIFT {
+            1 => Admission::Reject,
+            2 => Admission::Flag,
+            _ => Admission::Off,
+        }
+    }
+
+    /// Connect two tasks with a typed channel from a static pool
+    ///
+    /// Returns the `Sender` for the producer task and the `Receiver` for
+    /// the consumer task, and records the link. Fails on invalid or
+    /// identical task indices, a full link table, or an exhausted pool;
+    /// a refused link takes no channel from the pool.
+    ///
+    /// Tasks are plain `fn(&mut [u8])` and have no context, so the kernel
+    /// cannot hand the halves to them: the application keeps each half
+    /// where its task reaches it (a `static`, or the task's arena) — the
+    /// link table only records the wiring.
+    pub fn connect<T: Copy, const N: usize, const C: usize>(
+        &mut self,
+        pool: &'static ChannelPool<T, N, C>,
+        producer: usize,
+        consumer: usize,
+    ) -> Option<(Sender<'static, T, N>, Receiver<'static, T, N>)> {
+        self.try_connect(pool, producer, consumer).ok()
+    }
+
+    /// [`connect`](Self::connect), reporting why the link was refused
+    pub fn try_connect<T: Copy, const N: usize, const C: usize>(
+        &mut self,
+        pool: &'static ChannelPool<T, N, C>,
+        producer: usize,
+        consumer: usize,
+    ) -> KernelResult<(Sender<'static, T, N>, Receiver<'static, T, N>)> {
+        let valid = |idx| self.scheduler.get_task(idx).is_some_and(|t| t.is_active());
+        if producer == consumer || !valid(producer) || !valid(consumer) {
+            return Err(KernelError::InvalidIndex);
+        }
+        if self.link_count() >= MAX_LINKS {
+            return Err(KernelError::LinkTableFull);
         }
+        let halves = pool.alloc_split().ok_or(KernelError::PoolExhausted)?;
+        self.add_link(producer, consumer);
+        Ok(halves)
+    }
+
+    /// Record a producer → consumer link; false if the table is full
+    pub(crate) fn add_link(&mut self, producer: usize, consumer: usize) -> bool {
+        let count = self.link_count();
+        if count >= MAX_LINKS {
+            return false;
+        }
+        self.links[count] = Link {
+            producer: producer as u8,
+            consumer: consumer as u8,
+        };
+        true
+    }
+
+    /// Number of recorded links
+    ///
+    /// A link never connects a task to itself, so the table ends at the
+    /// first unused (`producer == consumer`) entry.
+    fn link_count(&self) -> usize {
+        self.links
+            .iter()
+            .take_while(|l| l.producer != l.consumer)
+            .count()
+    }
+
+    /// Channel links recorded by `connect`
+    #[must_use]
+    pub fn links(&self) -> &[Link] {
+        &self.links[..self.link_count()]
+    }
+
+    /// Set or clear a `FLAG_*` state bit
+    const fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// Run one scheduler tick
+    ///
+    /// Advances time by `delta_us` and executes the highest-priority ready task.
+    /// Returns the task index that was executed, if any. Releases follow
+    /// the timer's disciplined time base ([`SysTimer::discipline`]).
+    ///
+    /// Refused in [`Fault`](KernelState::Fault): time does not advance and
+    /// nothing runs.
+    pub fn tick(&mut self, delta_us: u64) -> Option<usize> {
+        self.tick_excluding(delta_us, 0)
+    }
+
+    /// [`tick`](Self::tick) between the hooks of `observer`
+    pub fn tick_observed<O: TickObserver>(
+        &mut self,
+        delta_us: u64,
+        observer: &mut O,
+    ) -> Option<usize> {
+        observer.before_tick(self);
+        let executed = self.tick(delta_us);
+        observer.after_tick(self, executed);
+        executed
+    }
+
+    /// [`tick`](Self::tick) that leaves the tasks in `exclude` undispatched
+    pub(crate) fn tick_excluding(&mut self, delta_us: u64, exclude: TaskMask) -> Option<usize> {
+        if self.state() == KernelState::Fault {
+            return None;
+        }
+        let delta_us = self.timer.advance(delta_us);
+        self.total_ticks += 1;
+
+        let throttled = self.replenish_budgets() | exclude;
+        let dispatched = self.scheduler.dispatch_on(0, ANY_CORE, delta_us, throttled);
+        self.fire_timers();
+        self.sample_load();
+
+        // Execute the task with scratch buffer
+        if let Some((idx, release)) = dispatched {
+            if self.flags & FLAG_STACK_CHECK != 0 {
+                self.check_guard();
+            }
+            let (offset, len) = self.scratch_region(idx);
+            self.execute(idx, release, offset, len);
+            if self.flags & FLAG_STACK_CHECK != 0 {
+                self.record_watermark(idx, offset, len);
+            }
+        }
+
+        dispatched.map(|(idx, _)| idx)
+    }
+
+    /// Enter a non-preemptive region of at most `max_us`
+    ///
+    /// Dispatching is deferred until the returned guard is dropped; tick
+    /// through [`SchedulerLock::tick`] meanwhile. A region held past
+    /// `max_us` is reported and stops deferring (see
+    /// [`sched_lock`](crate::sched_lock)).
+    pub fn lock_scheduler(&mut self, max_us: u32) -> SchedulerLock<'_, SCRATCH, P> {
+        SchedulerLock::new(self, max_us)
+    }
+
+    /// Run one scheduler tick on `core` (SMP)
+    ///
+    /// Dispatches the highest-priority ready task whose affinity includes
+    /// `core`; each core executes in its own `1 / MAX_CORES` slice of the
+    /// shared scratch (or the task's arena) so concurrent jobs never share
+    /// a buffer.
+    /// `delta_us` is the time since the previous tick on any core.
+    ///
+    /// This runs the job while holding `&mut self`, so with the kernel
+    /// shared through a lock the cores execute one at a time. To run jobs
+    /// in parallel, take the lock only around
+    /// [`dispatch_core`](Self::dispatch_core) and
+    /// [`complete_core`](Self::complete_core) and run the [`CoreJob`] in
+    /// between without it. Scratch canary checking only covers
+    /// [`tick`](Self::tick).
+    pub fn tick_core(&mut self, core: usize, delta_us: u64) -> Option<usize> {
+        let job = self.dispatch_core(core, delta_us)?;
+        let (offset, len) = self.core_region(job.idx, core);
+        let completed = job.run(&mut self.scratch[offset..offset + len]);
+        self.complete_core(job, completed);
+        Some(job.idx)
+    }
+
+    /// Dispatch half of [`tick_core`](Self::tick_core): advance time and
+    /// pick the task for `core`, without running it
+    ///
+    /// Both cores may drive the kernel once it is shared through a lock
+    /// (e.g. an RP2040 SIO spinlock via `critical-section`); the `&mut
+    /// self` receiver keeps the dispatch decision to one core at a time.
+    /// Release the lock, [`run`](CoreJob::run) the job on a scratch
+    /// buffer owned by the core, then lock again for
+    /// [`complete_core`](Self::complete_core). A job run outside the
+    /// kernel does not see its arena (see [`arena`](Self::arena)).
+    pub fn dispatch_core(&mut self, core: usize, delta_us: u64) -> Option<CoreJob> {
+        if core >= MAX_CORES || self.state() == KernelState::Fault {
+            return None;
+        }
+        let delta_us = self.timer.advance(delta_us);
+        self.total_ticks += 1;
+
+        let throttled = self.replenish_budgets();
+        let dispatched = self
+            .scheduler
+            .dispatch_on(core, 1 << core, delta_us, throttled);
+        self.fire_timers();
+        self.sample_load();
+        dispatched.map(|(idx, release)| self.begin_job(idx, core, release))
+    }
+
+    /// Accounting half of [`tick_core`](Self::tick_core) for a job that
+    /// [`run`](CoreJob::run) returned `completed` for: profiling, budget,
+    /// exit hook and fault counting
+    pub fn complete_core(&mut self, job: CoreJob, completed: bool) {
+        self.finish_job(job, completed);
+    }
+
+    /// Scratch of task `idx` on `core`: its arena, or the core's
+    /// `1 / MAX_CORES` slice of the shared scratch
+    fn core_region(&self, idx: usize, core: usize) -> (usize, usize) {
+        let (mut offset, mut len) = self.scratch_region(idx);
+        if offset + len == SCRATCH {
+            // 共有領域はコアごとに分割
+            len /= MAX_CORES;
+            offset += core * len;
+        }
+        (offset, len)
+    }
+
+    /// [`tick_core`](Self::tick_core), waking the other core when needed
+    ///
+    /// Acknowledges this core's `doorbell` first, then ticks; if ready
+    /// work is left that only another core may run, the doorbell is rung
+    /// so that core ticks promptly instead of at its next timer
+    /// interrupt. See [`doorbell`](crate::doorbell).
+    pub fn tick_core_waking<D: Doorbell>(
+        &mut self,
+        core: usize,
+        delta_us: u64,
+        doorbell: &D,
+    ) -> Option<usize> {
+        doorbell.clear();
+        let executed = self.tick_core(core, delta_us);
+        let here = self.scheduler.ready_on(core);
+        if (0..MAX_CORES).any(|other| other != core && self.scheduler.ready_on(other) & !here != 0)
+        {
+            doorbell.signal();
+        }
+        executed
+    }
+
+    /// Scratch arena of task `idx` (None if it has none that fits)
+    ///
+    /// Arena contents persist between activations, so this is where a
+    /// task's last output can be read back.
+    #[must_use]
+    pub fn arena(&self, idx: usize) -> Option<&[u8]> {
+        if !self.scheduler.get_task(idx)?.has_arena() {
+            return None;
+        }
+        let (offset, len) = self.scratch_region(idx);
+        // 収まらないアリーナは共有領域 (末尾まで) で実行される
+        (offset + len < SCRATCH).then(|| &self.scratch[offset..offset + len])
+    }
+
+    /// Address of the first scratch byte (for memory protection setup)
+    #[must_use]
+    pub fn scratch_ptr(&self) -> *const u8 {
+        self.scratch.as_ptr()
+    }
+
+    /// Measure every active task and write back a padded WCET
+    ///
+    /// Runs each task body [`runs`](WcetCalibrator::runs) times on its
+    /// scratch, timed with the calibrator's clock, and stores the largest
+    /// time plus the safety margin as `wcet_us` (see [`calibrate`](crate::calibrate)).
+    /// A body that panics keeps its WCET and is reported as faulted. Call
+    /// it before [`start`](Self::start) — and before sealing a
+    /// [`TaskTableGuard`](crate::integrity::TaskTableGuard); refused with
+    /// [`KernelError::KernelRunning`] while the kernel runs.
+    pub fn calibrate_wcet(
+        &mut self,
+        calibrator: &WcetCalibrator,
+    ) -> KernelResult<CalibrationReport> {
+        if self.state() == KernelState::Running {
+            return Err(KernelError::KernelRunning);
+        }
+        let mut report = CalibrationReport::new();
+        for idx in 0..self.scheduler.task_slots() {
+            let Some(task) = self.scheduler.get_task(idx).filter(|t| t.is_active()) else {
+                continue;
+            };
+            let bit: TaskMask = 1 << idx;
+            report.tasks |= bit;
+            report.names[idx] = task.display_name();
+            report.periods[idx] = task.period_us;
+            report.before[idx] = task.wcet_us;
+
+            let (offset, len) = self.scratch_region(idx);
+            let mut completed = true;
+            for _ in 0..calibrator.run_count() {
+                let (scheduler, scratch) =
+                    (&self.scheduler, &mut self.scratch[offset..offset + len]);
+                let us = calibrator.measure(|| {
+                    completed &= Self::run_isolated(scheduler, idx, scratch);
+                });
+                report.measured[idx] = report.measured[idx].max(us);
+            }
+            report.after[idx] = if completed {
+                calibrator.padded(report.measured[idx])
+            } else {
+                report.faulted |= bit;
+                report.before[idx]
+            };
+            if let Some(task) = self.scheduler.get_task_mut(idx) {
+                task.wcet_us = report.after[idx];
+            }
+        }
+        Ok(report)
+    }
+
+    /// Power-on self-test of the kernel primitives
+    ///
+    /// Checks timer monotonicity, ring-buffer wraparound, scheduler
+    /// priority selection and the scratch RAM (March C-, contents kept);
+    /// see [`bist`]. Run it before the application starts. A
+    /// failure is also recorded in the black box
+    /// ([`FaultCode::SELF_TEST`]).
+    pub fn self_test(&mut self) -> BistFailures {
+        let failures = BistFailures::NONE
+            .with(BistFailures::TIMER, !bist::timer_monotonic())
+            .with(BistFailures::RING, !bist::ring_wraparound())
+            .with(BistFailures::SCHEDULER, !bist::priority_selection())
+            .with(BistFailures::SCRATCH, !bist::march_c(&mut self.scratch));
+        if !failures.is_ok() {
+            fault::capture(
+                FaultCode::SELF_TEST,
+                None,
+                self.timer.now_us(),
+                failures.0 as u32,
+            );
+        }
+        failures
+    }
+
+    /// Bytes reserved by task arenas at the start of scratch
+    #[must_use]
+    pub fn arena_bytes(&self) -> usize {
+        self.scheduler
+            .tasks()
+            .filter(|(_, t)| t.has_arena())
+            .map(|(_, t)| t.scratch_size as usize)
+            .sum()
+    }
+
+    /// Scratch `(offset, len)` task `idx` runs in
+    ///
+    /// The task's arena if it has one, else the shared scratch behind the
+    /// arenas (which alone reaches the end of scratch, guard included).
+    #[must_use]
+    pub fn scratch_region(&self, idx: usize) -> (usize, usize) {
+        let mut offset = 0;
+        for (i, task) in self.scheduler.tasks() {
+            if !task.has_arena() {
+                continue;
+            }
+            let len = task.scratch_size as usize;
+            if i == idx && offset + len <= SCRATCH - GUARD_SIZE {
+                return (offset, len);
+            }
+            offset += len;
+        }
+        // 共有領域 (アリーナの後ろ、ガード込み)
+        let reserved = offset.min(SCRATCH - GUARD_SIZE);
+        (reserved, SCRATCH - reserved)
+    }
+
+    /// Run task `idx`, released at `release`, on `len` bytes of scratch
+    /// starting at `offset`
+    fn execute(&mut self, idx: usize, release: Tick, offset: usize, len: usize) {
+        let job = self.begin_job(idx, 0, release);
+        let completed = job.run(&mut self.scratch[offset..offset + len]);
+        self.finish_job(job, completed);
+    }
+
+    /// Enter hook, start timestamps and release latency of a job
+    /// dispatched now and released at `release`
+    fn begin_job(&mut self, idx: usize, core: usize, release: Tick) -> CoreJob {
+        self.enter_hook(idx);
+        // A parameter change or mode switch pins the pending job's release
+        // to its boundary, which may not have passed yet
+        let now = self.scheduler.now_us();
+        let latency_us = if tick_reached(now, release) {
+            tick_to_u64(ticks_since(now, release))
+        } else {
+            0
+        };
+        CoreJob {
+            idx,
+            core,
+            task: self
+                .scheduler
+                .get_task(idx)
+                .copied()
+                .unwrap_or(Task::empty()),
+            started: self.profiler.as_ref().map(|p| p.begin(idx)),
+            budget_started: self.budgets.as_ref().and_then(|b| b.now()),
+            latency_us,
+        }
+    }
+
+    /// Profiling, budget, exit hook and fault accounting of a finished job
+    fn finish_job(&mut self, job: CoreJob, completed: bool) {
+        let idx = job.idx;
+        if let (Some(started), Some(profiler)) = (job.started, self.profiler.as_mut()) {
+            let exec = profiler.now().saturating_sub(started);
+            profiler.record(idx, job.latency_us, exec);
+        }
+        self.charge_budget(idx, job.budget_started);
+        self.exit_hook(idx);
+        if !completed {
+            self.record_fault(idx);
+        }
+    }
+
+    /// Execute a task, containing a panic to that task (std / test builds
+    /// with `panic = "unwind"`)
+    ///
+    /// Returns false if the task panicked.
+    fn run_isolated(scheduler: &Scheduler<P>, idx: usize, scratch: &mut [u8]) -> bool {
+        contain(|| scheduler.execute_task(idx, scratch))
+    }
+
+    /// Charge the job just run to its budget
+    ///
+    /// Measured CPU time if the table has a clock, the task's WCET
+    /// otherwise.
+    fn charge_budget(&mut self, idx: usize, started: Option<u64>) {
+        let Some(budgets) = self.budgets.as_mut() else {
+            return;
+        };
+        let used = match (started, budgets.now()) {
+            (Some(start), Some(end)) => end.saturating_sub(start).min(u32::MAX as u64) as u32,
+            _ => self.scheduler.get_task(idx).map_or(0, |t| t.wcet_us),
+        };
+        if budgets.charge(idx, used) {
+            emit(KernelEvent::BudgetExhausted { idx: idx as u8 });
+        }
+    }
+
+    /// Refill budgets due by the end of this tick; returns throttled tasks
+    fn replenish_budgets(&mut self) -> TaskMask {
+        let now = self.timer.now_us();
+        self.budgets.as_mut().map_or(0, |b| b.replenish(now))
+    }
+
+    /// Count a fault and auto-suspend the task past the fault limit
+    fn record_fault(&mut self, idx: usize) {
+        let limit = self.fault_limit;
+        let Some(task) = self.scheduler.get_task_mut(idx) else {
+            return;
+        };
+        task.fault_count = task.fault_count.saturating_add(1);
+        emit(KernelEvent::TaskFault { idx: idx as u8 });
+        let count = task.fault_count;
+        fault::capture(
+            FaultCode::TASK_PANIC,
+            Some(idx),
+            self.timer.now_us(),
+            count as u32,
+        );
+        if limit != 0 && task.fault_count >= limit {
+            self.scheduler.suspend(idx);
+        }
+    }
+
+    /// Record an application fault in the installed black box
+    ///
+    /// Stores `code` with the task last dispatched on core 0, the current
+    /// time and `detail` (e.g. a state-machine word) so it can be read
+    /// after a reset; see [`crate::fault`].
+    pub fn fault(&self, code: FaultCode, detail: u32) {
+        let task = self.scheduler.current_on(0);
+        fault::capture(code, task, self.timer.now_us(), detail);
+    }
+
+    /// Auto-suspend a task after `limit` caught panics (0 = never)
+    ///
+    /// Panics are only caught on std / test builds that unwind; with
+    /// `panic = "abort"` (this crate's release profile) or on bare metal
+    /// a task panic is fatal and the limit never triggers.
+    pub const fn set_fault_limit(&mut self, limit: u16) {
+        self.fault_limit = limit;
+    }
+
+    /// Total faults caught across all tasks
+    #[must_use]
+    pub fn total_faults(&self) -> u64 {
+        (0..MAX_TASKS)
+            .map_while(|i| self.scheduler.get_task(i))
+            .map(|t| t.fault_count as u64)
+            .sum()
+    }
+
+    /// Install callbacks invoked right before and after each task runs
+    ///
+    /// Both receive the task index, e.g. to toggle a GPIO per task for a
+    /// logic analyzer. Keep them short: they run on the dispatch path.
+    #[cfg(feature = "hooks")]
+    pub fn set_switch_hooks(&mut self, on_enter: SwitchHook, on_exit: SwitchHook) {
+        self.switch_hooks = Some((on_enter, on_exit));
+    }
+
+    /// Remove the context-switch callbacks
+    #[cfg(feature = "hooks")]
+    pub fn clear_switch_hooks(&mut self) {
+        self.switch_hooks = None;
+    }
+
+    /// Invoke the enter hook (compiled out without `hooks`)
+    #[inline(always)]
+    fn enter_hook(&self, _idx: usize) {
+        #[cfg(feature = "hooks")]
+        if let Some((on_enter, _)) = self.switch_hooks {
+            on_enter(_idx);
+        }
+    }
+
+    /// Invoke the exit hook (compiled out without `hooks`)
+    #[inline(always)]
+    fn exit_hook(&self, _idx: usize) {
+        #[cfg(feature = "hooks")]
+        if let Some((_, on_exit)) = self.switch_hooks {
+            on_exit(_idx);
+        }
+    }
+
+    /// Attach a static software timer pool
+    ///
+    /// Expired timer callbacks then run from every tick, before the
+    /// selected task executes.
+    pub fn attach_timers(&mut self, pool: &'static mut SoftTimerPool) {
+        self.timers = Some(pool);
+    }
+
+    /// Attach a static per-task profiler
+    ///
+    /// Every executed job is then timed with the profiler's clock and its
+    /// maxima show up in [`KernelStats::per_task`].
+    pub fn attach_profiler(&mut self, profiler: &'static mut TaskProfiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Attach a static execution-budget table
+    ///
+    /// Tasks that exhaust their budget are skipped in dispatch until
+    /// their budget replenishes.
+    pub fn attach_budgets(&mut self, budgets: &'static mut BudgetTable) {
+        self.budgets = Some(budgets);
+    }
+
+    /// Reserve `budget_us` of CPU per `period_us` for task `idx`
+    ///
+    /// Returns false without an attached table or for invalid arguments.
+    pub fn set_budget(&mut self, idx: usize, budget_us: u32, period_us: u32) -> bool {
+        let now = self.timer.now_us();
+        self.budgets
+            .as_mut()
+            .is_some_and(|b| b.set_budget(idx, budget_us, period_us, now))
+    }
+
+    /// Times task `idx` was throttled for exhausting its budget
+    #[must_use]
+    pub fn throttle_count(&self, idx: usize) -> u32 {
+        self.budgets.as_ref().map_or(0, |b| b.throttle_count(idx))
+    }
+
+    /// Call `callback` once, `delay_us` from now
+    ///
+    /// Returns the timer id, or None without an attached pool or when
+    /// the pool is full.
+    pub fn arm_timer(&mut self, callback: TimerCallback, delay_us: u32) -> Option<usize> {
+        let now = self.timer.now_us();
+        self.timers.as_mut()?.arm(callback, delay_us, now)
+    }
+
+    /// Stop timer `id` before it fires
+    pub fn cancel_timer(&mut self, id: usize) -> bool {
+        self.timers.as_mut().is_some_and(|t| t.cancel(id))
+    }
+
+    /// Re-arm timer `id` with its original delay, counted from now
+    pub fn restart_timer(&mut self, id: usize) -> bool {
+        let now = self.timer.now_us();
+        self.timers.as_mut().is_some_and(|t| t.restart(id, now))
+    }
+
+    /// Release timer `id`'s slot
+    pub fn delete_timer(&mut self, id: usize) -> bool {
+        self.timers.as_mut().is_some_and(|t| t.delete(id))
+    }
+
+    /// Time left until timer `id` fires (None if not armed)
+    #[must_use]
+    pub fn timer_remaining_us(&self, id: usize) -> Option<u64> {
+        self.timers.as_ref()?.remaining_us(id, self.timer.now_us())
+    }
+
+    /// Fold finished load samples into the profiler's load averages
+    fn sample_load(&mut self) {
+        let now = self.timer.now_us();
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.sample_load(now);
+        }
+    }
+
+    /// CPU load averages over ~1 s / 10 s / 60 s
+    ///
+    /// Built from the execution time measured by the attached profiler;
+    /// `None` without one.
+    #[must_use]
+    pub fn load_averages(&self) -> Option<LoadAverages> {
+        self.profiler.as_ref().map(|p| p.load_averages())
+    }
+
+    /// Upper bound on the `per_mille` response-time percentile of task `idx`
+    ///
+    /// `990` gives p99. Needs a [`ResponseHistogram`](crate::profiler::ResponseHistogram) attached to the
+    /// profiler; `None` without one or before the task's first job.
+    #[must_use]
+    pub fn response_percentile_us(&self, idx: usize, per_mille: u16) -> Option<u32> {
+        self.profiler
+            .as_ref()
+            .and_then(|p| p.response_percentile_us(idx, per_mille))
+    }
+
+    /// Run callbacks of expired software timers
+    fn fire_timers(&mut self) {
+        let now = self.timer.now_us();
+        if let Some(timers) = self.timers.as_mut() {
+            timers.fire_expired(now);
+        }
+    }
+
+    /// Switch the system operating mode
+    ///
+    /// Re-checks RMS schedulability of the new mode's task set first and
+    /// returns false (nothing changed) if it fails. Otherwise, in one step,
+    /// tasks outside `mode` are suspended and the tasks of `mode` that an
+    /// earlier switch suspended are resumed (released immediately). A task
+    /// suspended for another reason — by the application, a fault limit,
+    /// its group or overload shedding — stays suspended.
+    pub fn switch_mode(&mut self, mode: Mode) -> bool {
+        if mode.mask() == 0 || !self.scheduler.is_schedulable_in_mode(mode) {
+            return false;
+        }
+        for idx in 0..MAX_TASKS {
+            let Some(task) = self.scheduler.get_task(idx) else {
+                break;
+            };
+            if !task.is_active() {
+                continue;
+            }
+            if !task.active_in(mode) {
+                if task.state != TaskState::Suspended {
+                    self.scheduler.suspend(idx);
+                    if let Some(task) = self.scheduler.get_task_mut(idx) {
+                        task.flags |= FLAG_MODE_SUSPENDED;
+                    }
+                }
+            } else if task.mode_suspended() {
+                self.scheduler.resume(idx);
+            }
+        }
+        emit(KernelEvent::ModeChange {
+            from: self.mode.0,
+            to: mode.0,
+        });
+        self.mode = mode;
+        true
+    }
+
+    /// Current operating mode
+    #[must_use]
+    pub const fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Enable or disable scratch canary checking
+    ///
+    /// When enabled, scratch is filled with [`SCRATCH_CANARY`]; after each
+    /// task run the highest touched byte is recorded as that task's
+    /// watermark and the used region is re-filled, so shared scratch
+    /// contents do not persist between activations (arena contents do).
+    pub fn enable_stack_check(&mut self, enabled: bool) {
+        self.set_flag(FLAG_STACK_CHECK, enabled);
+        if enabled {
+            self.scratch.fill(SCRATCH_CANARY);
+        }
+    }
+
+    /// Highest scratch byte count ever touched by task `idx`
+    ///
+    /// `None` for invalid indices. Only tracked while stack checking is on.
+    #[must_use]
+    pub fn stack_high_watermark(&self, idx: usize) -> Option<usize> {
+        Some(self.scheduler.get_task(idx)?.scratch_high_water as usize)
+    }
+
+    /// Tasks flagged for scratch overflow (bit i = task i)
+    #[must_use]
+    pub const fn stack_faults(&self) -> TaskMask {
+        self.stack_faults
+    }
+
+    /// Guard corruptions detected between task runs
+    #[must_use]
+    pub const fn guard_corruptions(&self) -> u32 {
+        self.guard_corruptions as u32
+    }
+
+    /// Verify the end-of-scratch guard before the next task runs
+    ///
+    /// Task overruns are caught right after the task returns, so a
+    /// corrupted guard here means something outside task context (ISR,
+    /// DMA) wrote past scratch. Counted and re-armed.
+    fn check_guard(&mut self) {
+        let guard = &mut self.scratch[SCRATCH - GUARD_SIZE..];
+        if guard.iter().any(|&b| b != SCRATCH_CANARY) {
+            self.guard_corruptions = self.guard_corruptions.saturating_add(1);
+            fault::capture(
+                FaultCode::GUARD_CORRUPTION,
+                None,
+                self.timer.now_us(),
+                self.guard_corruptions as u32,
+            );
+            guard.fill(SCRATCH_CANARY);
+        }
+    }
+
+    /// Measure usage of `idx`'s scratch region and re-arm the canary
+    ///
+    /// Arenas are not re-armed: their contents persist, and the last
+    /// non-canary byte already marks the high water.
+    fn record_watermark(&mut self, idx: usize, offset: usize, len: usize) {
+        let used = self.scratch[offset..offset + len]
+            .iter()
+            .rposition(|&b| b != SCRATCH_CANARY)
+            .map_or(0, |p| p + 1);
+        let Some(task) = self.scheduler.get_task_mut(idx) else {
+            return;
+        };
+        task.scratch_high_water = task.scratch_high_water.max(used as u16);
+        let budget = task.scratch_size as usize;
+        let arena = task.has_arena();
+        if offset + used > SCRATCH - GUARD_SIZE || (budget != 0 && used > budget) {
+            self.stack_faults |= 1 << idx;
+            emit(KernelEvent::ScratchOverrun {
+                idx: idx as u8,
+                used: used as u16,
+            });
+            fault::capture(
+                FaultCode::SCRATCH_OVERRUN,
+                Some(idx),
+                self.timer.now_us(),
+                used as u32,
+            );
+        }
+        if !arena {
+            self.scratch[offset..offset + used].fill(SCRATCH_CANARY);
+        }
+    }
+
+    /// Run the kernel for a given duration (testing)
+    ///
+    /// Starts the kernel if it is not running yet and stops it again at
+    /// the end; returns at once in [`Fault`](KernelState::Fault).
+    pub fn run_for(&mut self, total_us: u64, tick_us: u64) -> KernelStats {
+        self.run_for_observed(total_us, tick_us, &mut ())
+    }
+
+    /// [`run_for`](Self::run_for) with every tick observed by `observer`
+    pub fn run_for_observed<O: TickObserver>(
+        &mut self,
+        total_us: u64,
+        tick_us: u64,
+        observer: &mut O,
+    ) -> KernelStats {
+        let started = self.start().is_ok();
+        if !self.is_running() {
+            return self.stats(0, 0);
+        }
+        let mut elapsed = 0u64;
+        let mut tasks_executed = 0u64;
+
+        while elapsed < total_us && self.is_running() {
+            if self.tick_observed(tick_us, observer).is_some() {
+                tasks_executed += 1;
+            }
+            elapsed += tick_us;
+        }
+
+        if started {
+            self.stop();
+        }
+        self.stats(elapsed, tasks_executed)
+    }
+
+    /// Earliest instant (µs) at which the kernel has work to do
+    ///
+    /// Includes the expiry of armed software timers; throttled tasks wake
+    /// at their budget replenishment.
+    #[must_use]
+    pub fn next_wakeup_us(&self) -> Option<u64> {
+        let now = self.timer.now_us();
+        let throttled = self.budgets.as_ref().map_or(0, |b| b.throttled_mask());
+        let task = self
+            .scheduler
+            .next_wakeup_excluding(throttled)
+            .map(|at| now + self.task_wait_us(at));
+        let timer = self
+            .timers
+            .as_ref()
+            .and_then(|t| t.next_expiry_us())
+            .map(|at| at.max(now));
+        let replenish = self
+            .budgets
+            .as_ref()
+            .and_then(|b| b.next_replenish_us())
+            .map(|at| at.max(now));
+        [task, timer, replenish].into_iter().flatten().min()
+    }
+
+    /// Run one tickless step
+    ///
+    /// Advances time directly to the next activation (at most `max_us`
+    /// ahead) and runs one scheduler tick there. Idle ticks are skipped
+    /// entirely. Returns the elapsed time and the executed task, if any.
+    ///
+    /// A zero-length step always runs a task that has not run at this
+    /// instant yet: a time-released task with period 0 is released again
+    /// only once time moves, and does not itself wake the kernel (it runs
+    /// once per wakeup), so a run always makes progress.
+    pub fn tick_tickless(&mut self, max_us: u64) -> (u64, Option<usize>) {
+        self.tick_tickless_observed(max_us, &mut ())
+    }
+
+    /// [`tick_tickless`](Self::tick_tickless) observed by `observer`
+    pub fn tick_tickless_observed<O: TickObserver>(
+        &mut self,
+        max_us: u64,
+        observer: &mut O,
+    ) -> (u64, Option<usize>) {
+        let now = self.timer.now_us();
+        let delta = match self.next_wakeup_us() {
+            Some(at) => at.saturating_sub(now).min(max_us),
+            None => max_us,
+        };
+        (delta, self.tick_observed(delta, observer))
+    }
+
+    /// Run the kernel for a given duration in tickless mode
+    ///
+    /// Equivalent to `run_for` with an infinitely fine tick, but the
+    /// scheduler is only invoked at activation instants.
+    pub fn run_tickless(&mut self, total_us: u64) -> KernelStats {
+        self.run_tickless_observed(total_us, &mut ())
+    }
+
+    /// [`run_tickless`](Self::run_tickless) with every tick observed by
+    /// `observer`
+    pub fn run_tickless_observed<O: TickObserver>(
+        &mut self,
+        total_us: u64,
+        observer: &mut O,
+    ) -> KernelStats {
+        let started = self.start().is_ok();
+        if !self.is_running() {
+            return self.stats(0, 0);
+        }
+        let mut elapsed = 0u64;
+        let mut tasks_executed = 0u64;
+
+        while elapsed < total_us && self.is_running() {
+            let (delta, executed) = self.tick_tickless_observed(total_us - elapsed, observer);
+            if executed.is_some() {
+                tasks_executed += 1;
+            }
+            elapsed += delta;
+        }
+
+        if started {
+            self.stop();
+        }
+        self.stats(elapsed, tasks_executed)
+    }
+
+    /// Program the hardware compare interrupt for the next wakeup
+    ///
+    /// Arms [`next_wakeup_us`](Self::next_wakeup_us): the next task
+    /// activation, software timer expiry or budget replenishment. Returns
+    /// the armed instant, or `None` if nothing is pending (the caller may
+    /// then sleep until an external event).
+    pub fn arm_wakeup<S: TickSource>(&self, source: &mut S) -> Option<u64> {
+        let at = self.next_wakeup_us()?;
+        source.set_compare(at);
+        Some(at)
+    }
+
+    /// Microseconds from now until scheduler instant `at`
+    fn task_wait_us(&self, at: Tick) -> u64 {
+        tick_to_u64(ticks_until(self.scheduler.now_us(), at))
+    }
+
+    /// Catch up with the hardware time after a compare interrupt
+    ///
+    /// Advances the kernel by the time elapsed on `source` since the
+    /// last tick and dispatches the highest-priority ready task.
+    pub fn tick_from<S: TickSource>(&mut self, source: &S) -> Option<usize> {
+        let delta = source.now_us().saturating_sub(self.timer.now_us());
+        self.tick(delta)
+    }
+
+    /// Build a statistics snapshot
+    pub(crate) fn stats(&self, elapsed: u64, tasks_executed: u64) -> KernelStats {
+        let (per_task, task_count) = self.task_stats();
+        KernelStats {
+            total_us: elapsed,
+            total_ticks: self.total_ticks,
+            tasks_executed,
+            context_switches: self.scheduler.context_switches as u64,
+            #[cfg(not(feature = "no-float"))]
+            utilization: self.scheduler.total_utilization(),
+            utilization_ppm: self.scheduler.total_utilization_ppm(),
+            schedulable: self.scheduler.is_schedulable(),
+            task_faults: self.total_faults(),
+            sleep: SleepStats::default(),
+            per_task,
+            task_count,
+        }
+    }
+
+    /// Per-task breakdown of the task table and the number of used slots
+    fn task_stats(&self) -> ([TaskStats; MAX_TASKS], u8) {
+        let mut out = [TaskStats::default(); MAX_TASKS];
+        let mut count = 0;
+        for (idx, entry) in out.iter_mut().enumerate() {
+            let Some(task) = self.scheduler.get_task(idx) else {
+                break;
+            };
+            count += 1;
+            let profiler = self.profiler.as_deref();
+            *entry = TaskStats {
+                name: task.name,
+                exec_count: task.exec_count,
+                deadline_misses: task.deadline_misses,
+                max_exec_us: profiler.map_or(0, |p| p.max_exec_us(idx)),
+                max_response_us: profiler.map_or(0, |p| p.max_response_us(idx)),
+                #[cfg(not(feature = "no-float"))]
+                utilization: if task.is_active() {
+                    task.utilization()
+                } else {
+                    0.0
+                },
+                utilization_ppm: if task.is_active() {
+                    task.utilization_ppm()
+                } else {
+                    0
+                },
+            };
+        }
+        (out, count)
+    }
+
+    /// Serialize the kernel state into `buf` for a warm restart
+    ///
+    /// Returns the snapshot length; see [`snapshot`](crate::snapshot) for
+    /// what is included and the binary layout.
+    ///
+    /// # Errors
+    ///
+    /// [`SnapshotError::BufferTooSmall`] if `buf` cannot hold the snapshot.
+    pub fn save_state(&self, buf: &mut [u8]) -> Result<usize, SnapshotError> {
+        let count = self.scheduler.task_slots();
+        let mut w = Writer::new(buf, count)?;
+        let start = w.position();
+        w.u64(tick_to_u64(self.scheduler.now_us()));
+        w.u64(self.timer.now_us());
+        w.u32(self.timer.overflows());
+        w.u64(self.total_ticks);
+        w.u32(self.scheduler.context_switches);
+        w.u16(self.stack_faults);
+        w.u32(self.guard_corruptions as u32);
+        w.u8(self.mode.0);
+        w.pad_to(start, KERNEL_RECORD_SIZE);
+        for idx in 0..count {
+            let Some(task) = self.scheduler.get_task(idx) else {
+                break;
+            };
+            let start = w.position();
+            w.bytes(&task.name);
+            w.u32(task.period_us);
+            w.u32(task.wcet_us);
+            w.u64(tick_to_u64(task.next_activation));
+            w.u32(task.exec_count);
+            w.u32(task.deadline_misses);
+            w.u16(task.fault_count);
+            w.u16(task.scratch_high_water);
+            w.u16(task.inputs_ready);
+            w.u8(encode_state(task.state));
+            w.u8(task.flags);
+            w.pad_to(start, TASK_RECORD_SIZE);
+        }
+        Ok(w.finish())
+    }
+
+    /// Restore a snapshot taken by [`save_state`](Self::save_state)
+    ///
+    /// The same tasks must already be registered (e.g. by the boot code);
+    /// their run-time state, the clock and the counters are overwritten.
+    /// Nothing is changed unless the whole snapshot is valid.
+    ///
+    /// # Errors
+    ///
+    /// A [`SnapshotError`] for a truncated, corrupt or incompatible
+    /// snapshot, or one taken with a different task set.
+    pub fn restore_state(&mut self, buf: &[u8]) -> Result<(), SnapshotError> {
+        let (reader, count) = Reader::new(buf)?;
+        if count != self.scheduler.task_slots() {
+            return Err(SnapshotError::TaskMismatch {
+                idx: count.min(self.scheduler.task_slots()),
+            });
+        }
+        // Validate every record before touching the kernel
+        let mut check = reader.clone();
+        check.skip_to(check.position(), KERNEL_RECORD_SIZE);
+        for idx in 0..count {
+            let start = check.position();
+            let name: [u8; 8] = check.bytes();
+            if self.scheduler.get_task(idx).map(|t| t.name) != Some(name) {
+                return Err(SnapshotError::TaskMismatch { idx });
+            }
+            check.skip_to(start, TASK_RECORD_SIZE - 2);
+            decode_state(check.u8())?;
+            check.skip_to(start, TASK_RECORD_SIZE);
+        }
+
+        let mut r = reader;
+        let start = r.position();
+        self.scheduler.set_now(r.u64() as Tick);
+        let timer_us = r.u64();
+        let overflows = r.u32();
+        self.timer.restore(timer_us, overflows);
+        self.total_ticks = r.u64();
+        self.scheduler.context_switches = r.u32();
+        self.stack_faults = r.u16();
+        self.guard_corruptions = r.u32().min(u16::MAX as u32) as u16;
+        self.mode = Mode(r.u8());
+        r.skip_to(start, KERNEL_RECORD_SIZE);
+        for idx in 0..count {
+            let start = r.position();
+            let Some(task) = self.scheduler.get_task_mut(idx) else {
+                break;
+            };
+            let _name: [u8; 8] = r.bytes();
+            task.period_us = r.u32();
+            task.wcet_us = r.u32();
+            task.next_activation = r.u64() as Tick;
+            task.exec_count = r.u32();
+            task.deadline_misses = r.u32();
+            task.fault_count = r.u16();
+            task.scratch_high_water = r.u16();
+            task.inputs_ready = r.u16();
+            // A job interrupted mid-execution is released again
+            task.state = match decode_state(r.u8())? {
+                TaskState::Running => TaskState::Ready,
+                state => state,
+            };
+            task.flags = r.u8();
+            r.skip_to(start, TASK_RECORD_SIZE);
+        }
+        Ok(())
+    }
+
+    /// Current life-cycle state
+    #[must_use]
+    pub const fn state(&self) -> KernelState {
+        KernelState::from_bits(self.flags)
+    }
+
+    /// Move to state `to` if the transition is valid
+    pub const fn transition(&mut self, to: KernelState) -> Result<(), InvalidTransition> {
+        let from = self.state();
+        if !from.can_enter(to) {
+            return Err(InvalidTransition { from, to });
+        }
+        self.flags = (self.flags & !STATE_MASK) | to as u8;
+        Ok(())
+    }
+
+    /// Start ticking (from `Init` or `Stopped`)
+    pub const fn start(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(KernelState::Running)
+    }
+
+    /// Run the init functions of `inits`, then start ticking
+    ///
+    /// From `Init`, each active task with an init function runs it once on
+    /// its scratch, highest priority first (see [`init`](crate::init));
+    /// from `Stopped` the kernel restarts without running them again.
+    pub fn start_with(&mut self, inits: &InitTable) -> Result<(), InvalidTransition> {
+        let from = self.state();
+        if !from.can_enter(KernelState::Running) {
+            return Err(InvalidTransition {
+                from,
+                to: KernelState::Running,
+            });
+        }
+        if from == KernelState::Init {
+            let mut order = [0usize; MAX_TASKS];
+            let mut n = 0;
+            for (idx, _) in self.scheduler.tasks() {
+                if inits.get(idx).is_some() {
+                    order[n] = idx;
+                    n += 1;
+                }
+            }
+            order[..n]
+                .sort_unstable_by_key(|&i| (self.scheduler.get_task(i).map(|t| t.priority), i));
+            for &idx in &order[..n] {
+                self.run_init(idx, inits);
+            }
+        }
+        self.start()
+    }
+
+    /// Run the init function of task `idx` on its scratch
+    fn run_init(&mut self, idx: usize, inits: &InitTable) {
+        let Some(init) = inits.get(idx) else {
+            return;
+        };
+        let (offset, len) = self.scratch_region(idx);
+        let scratch = &mut self.scratch[offset..offset + len];
+        let completed = contain(|| init(scratch));
+        if self.flags & FLAG_STACK_CHECK != 0 {
+            self.record_watermark(idx, offset, len);
+        }
+        if !completed {
+            self.record_fault(idx);
+        }
+    }
+
+    /// Stop the kernel (no effect unless `Running`)
+    pub const fn stop(&mut self) {
+        if self.is_running() {
+            self.flags = (self.flags & !STATE_MASK) | KernelState::Stopped as u8;
+        }
+    }
+
+    /// Halt on an unrecoverable condition (HardFault, watchdog, corruption)
+    ///
+    /// Every later tick is refused until [`recover`](Self::recover).
+    pub const fn enter_fault(&mut self) {
+        if !matches!(self.state(), KernelState::Fault) {
+            self.flags = (self.flags & !STATE_MASK) | KernelState::Fault as u8;
+        }
+    }
+
+    /// Leave `Fault` for `Init`, so tasks can be reconfigured and restarted
+    pub const fn recover(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(KernelState::Init)
+    }
+
+    /// Allow or refuse [`add_task`](Self::add_task) while `Running`
+    pub const fn allow_live_registration(&mut self, allowed: bool) {
+        self.set_flag(FLAG_LIVE_REGISTRATION, allowed);
+    }
+
+    /// Is the kernel running?
+    #[must_use]
+    pub const fn is_running(&self) -> bool {
+        matches!(self.state(), KernelState::Running)
+    }
+
+    /// Check RMS schedulability
+    #[must_use]
+    pub fn is_schedulable(&self) -> bool {
+        self.scheduler.is_schedulable()
+    }
+
+    /// Memory footprint estimate
+    #[must_use]
+    pub const fn memory_footprint(&self) -> usize {
+        core::mem::size_of::<Self>()
+    }
+}
+
+/// Kernel execution statistics
+#[derive(Debug, Clone)]
+pub struct KernelStats {
+    /// Total elapsed time (µs)
+    pub total_us: u64,
+    /// Total scheduler ticks
+    pub total_ticks: u64,
+    /// Tasks executed
+    pub tasks_executed: u64,
+    /// Context switches
+    pub context_switches: u64,
+    /// CPU utilization
+    #[cfg(not(feature = "no-float"))]
+    pub utilization: f32,
+    /// CPU utilization in parts per million
+    pub utilization_ppm: u32,
+    /// RMS schedulable
+    pub schedulable: bool,
+    /// Task panics caught (std / test builds with `panic = "unwind"`)
+    pub task_faults: u64,
+    /// Time spent in each sleep state (runs driven by a
+    /// [`PowerManager`](crate::power::PowerManager))
+    pub sleep: SleepStats,
+    /// Per-task breakdown (first `task_count` entries valid)
+    per_task: [TaskStats; MAX_TASKS],
+    /// Task slots in use
+    task_count: u8,
+}
+
+impl KernelStats {
+    /// Per-task breakdown, indexed by task slot
+    #[must_use]
+    pub fn per_task(&self) -> &[TaskStats] {
+        &self.per_task[..self.task_count as usize]
+    }
+
+    /// Counters accumulated since the `earlier` snapshot
+    ///
+    /// Times, ticks, jobs, switches, faults, sleep and per-task runs and
+    /// misses become differences; utilization, schedulability and the
+    /// per-task maxima are kept from `self`. Tasks are matched by slot.
+    #[must_use]
+    pub fn since(&self, earlier: &Self) -> Self {
+        let mut out = self.clone();
+        out.total_us = self.total_us.saturating_sub(earlier.total_us);
+        out.total_ticks = self.total_ticks.saturating_sub(earlier.total_ticks);
+        out.tasks_executed = self.tasks_executed.saturating_sub(earlier.tasks_executed);
+        out.context_switches = self
+            .context_switches
+            .saturating_sub(earlier.context_switches);
+        out.task_faults = self.task_faults.saturating_sub(earlier.task_faults);
+        let mut time_us = [0u64; SleepState::COUNT];
+        let mut entries = [0u32; SleepState::COUNT];
+        for state in SLEEP_STATES {
+            time_us[state.index()] = self
+                .sleep
+                .time_us(state)
+                .saturating_sub(earlier.sleep.time_us(state));
+            entries[state.index()] = self
+                .sleep
+                .entries(state)
+                .saturating_sub(earlier.sleep.entries(state));
+        }
+        out.sleep = SleepStats::from_parts(time_us, entries);
+        for (task, before) in out.per_task.iter_mut().zip(&earlier.per_task) {
+            task.exec_count = task.exec_count.saturating_sub(before.exec_count);
+            task.deadline_misses = task.deadline_misses.saturating_sub(before.deadline_misses);
+        }
+        out
+    }
+
+    /// Encode as a compact telemetry frame (see [`telemetry`](crate::telemetry))
+    ///
+    /// Returns the frame length, or 0 if `buf` is too small; a buffer of
+    /// [`MAX_STATS_LEN`](crate::telemetry::MAX_STATS_LEN) bytes always fits.
+    pub fn encode(&self, buf: &mut [u8]) -> usize {
+        let mut w = WireWriter::new(buf);
+        for v in [
+            self.total_us,
+            self.total_ticks,
+            self.tasks_executed,
+            self.context_switches,
+            u64::from(self.utilization_ppm),
+            self.task_faults,
+        ] {
+            w.varint(v);
+        }
+        w.u8(u8::from(self.schedulable));
+        for state in SLEEP_STATES {
+            w.varint(self.sleep.time_us(state));
+            w.varint(u64::from(self.sleep.entries(state)));
+        }
+        w.u8(self.task_count);
+        for task in self.per_task() {
+            let name = task.display_name();
+            w.u8(name.as_bytes().len() as u8);
+            w.bytes(name.as_bytes());
+            for v in [
+                task.exec_count,
+                task.deadline_misses,
+                task.max_exec_us,
+                task.max_response_us,
+                task.utilization_ppm,
+            ] {
+                w.varint(u64::from(v));
+            }
+        }
+        w.finish()
+    }
+
+    /// Decode a frame produced by [`encode`](Self::encode) (host side)
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeError`] for a truncated, corrupt or newer-version frame.
+    #[cfg(feature = "std")]
+    pub fn decode(frame: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = WireReader::new(frame)?;
+        let total_us = r.varint()?;
+        let total_ticks = r.varint()?;
+        let tasks_executed = r.varint()?;
+        let context_switches = r.varint()?;
+        let utilization_ppm = r.varint_u32()?;
+        let task_faults = r.varint()?;
+        let schedulable = r.u8()? & 1 != 0;
+        let mut time_us = [0u64; SleepState::COUNT];
+        let mut entries = [0u32; SleepState::COUNT];
+        for state in SLEEP_STATES {
+            time_us[state.index()] = r.varint()?;
+            entries[state.index()] = r.varint_u32()?;
+        }
+        let task_count = r.u8()?;
+        if task_count as usize > MAX_TASKS {
+            return Err(DecodeError::Corrupt);
+        }
+        let mut per_task = [TaskStats::default(); MAX_TASKS];
+        for task in &mut per_task[..task_count as usize] {
+            let len = r.u8()? as usize;
+            if len > 8 {
+                return Err(DecodeError::Corrupt);
+            }
+            task.name[..len].copy_from_slice(r.bytes(len)?);
+            task.exec_count = r.varint_u32()?;
+            task.deadline_misses = r.varint_u32()?;
+            task.max_exec_us = r.varint_u32()?;
+            task.max_response_us = r.varint_u32()?;
+            task.utilization_ppm = r.varint_u32()?;
+            #[cfg(not(feature = "no-float"))]
+            {
+                task.utilization = task.utilization_ppm as f32 / 1_000_000.0;
+            }
+        }
+        if !r.at_end() {
+            return Err(DecodeError::Corrupt);
+        }
+        Ok(Self {
+            total_us,
+            total_ticks,
+            tasks_executed,
+            context_switches,
+            #[cfg(not(feature = "no-float"))]
+            utilization: utilization_ppm as f32 / 1_000_000.0,
+            utilization_ppm,
+            schedulable,
+            task_faults,
+            sleep: SleepStats::from_parts(time_us, entries),
+            per_task,
+            task_count,
+        })
+    }
+}
+
+/// Sleep states in telemetry order
+const SLEEP_STATES: [SleepState; SleepState::COUNT] =
+    [SleepState::Wfi, SleepState::Stop, SleepState::Standby];
+
+/// Statistics of a single task
+///
+/// `max_exec_us` / `max_response_us` stay 0 unless a
+/// [`TaskProfiler`] is attached.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaskStats {
+    /// Task name
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::text::serialize_name")
+    )]
+    pub name: [u8; 8],
+    /// Jobs executed
+    pub exec_count: u32,
+    /// Jobs dispatched after their deadline
+    pub deadline_misses: u32,
+    /// Largest observed execution time (µs)
+    pub max_exec_us: u32,
+    /// Largest observed release-to-completion time (µs)
+    pub max_response_us: u32,
+    /// Share of the CPU reserved by this task (WCET / period)
+    #[cfg(not(feature = "no-float"))]
+    pub utilization: f32,
+    /// Reserved CPU share in parts per million
+    pub utilization_ppm: u32,
+}
+
+impl TaskStats {
+    /// Column titles of the [`Display`](fmt::Display) table rows
+    pub const TABLE_HEADER: &'static str = "name         runs       U misses  max us";
+
+    /// Name with the NUL padding trimmed (for display)
+    #[must_use]
+    pub const fn display_name(&self) -> ShortName {
+        ShortName(self.name)
+    }
+}
+
+/// One table row: name, jobs, reserved utilization, misses, max execution time
+impl fmt::Display for TaskStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<8} {:>8} {:>7} {:>6} {:>7}",
+            self.display_name(),
+            self.exec_count,
+            Percent(self.utilization_ppm),
+            self.deadline_misses,
+            self.max_exec_us
+        )
+    }
+}
+
+/// Same fields as the struct; `per_task` holds only the slots in use
+#[cfg(feature = "serde")]
+impl serde::Serialize for KernelStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("KernelStats", 10)?;
+        s.serialize_field("total_us", &self.total_us)?;
+        s.serialize_field("total_ticks", &self.total_ticks)?;
+        s.serialize_field("tasks_executed", &self.tasks_executed)?;
+        s.serialize_field("context_switches", &self.context_switches)?;
+        #[cfg(not(feature = "no-float"))]
+        s.serialize_field("utilization", &self.utilization)?;
+        #[cfg(feature = "no-float")]
+        s.skip_field("utilization")?;
+        s.serialize_field("utilization_ppm", &self.utilization_ppm)?;
+        s.serialize_field("schedulable", &self.schedulable)?;
+        s.serialize_field("task_faults", &self.task_faults)?;
+        s.serialize_field("sleep", &self.sleep)?;
+        s.serialize_field("per_task", self.per_task())?;
+        s.end()
+    }
+}
+
+/// Summary lines followed by the per-task table ([`TaskStats::TABLE_HEADER`])
+impl fmt::Display for KernelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "up {} us, ticks {}, jobs {}, switches {}, faults {}",
+            self.total_us,
+            self.total_ticks,
+            self.tasks_executed,
+            self.context_switches,
+            self.task_faults
+        )?;
+        writeln!(
+            f,
+            "U {}, schedulable {}",
+            Percent(self.utilization_ppm),
+            if self.schedulable { "yes" } else { "no" }
+        )?;
+        writeln!(f, "{}", TaskStats::TABLE_HEADER)?;
+        for task in self.per_task() {
+            writeln!(f, "{task}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_task(_scratch: &mut [u8]) {}
+
+    #[test]
+    fn test_kernel_creation() {
+        let kernel = Kernel::testing();
+        assert_eq!(kernel.scheduler.active_task_count(), 0);
+    }
+
+    #[test]
+    fn test_kernel_add_task() {
+        let mut kernel = Kernel::testing();
+        let idx = kernel.add_task(b"test", noop_task, TaskPriority::NORMAL, 1000, 100);
+        assert_eq!(idx, Some(0));
+        assert_eq!(kernel.scheduler.active_task_count(), 1);
+    }
+
+    #[test]
+    fn test_kernel_tick() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"test", noop_task, TaskPriority::NORMAL, 100, 10);
+
+        let executed = kernel.tick(0);
+        assert_eq!(executed, Some(0));
+    }
+
+    #[test]
+    fn test_kernel_run_for() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"slow", noop_task, TaskPriority::LOW, 1000, 50);
+
+        let stats = kernel.run_for(10_000, 10);
+        assert!(stats.tasks_executed > 0);
+        assert!(stats.schedulable);
+    }
+
+    #[test]
+    fn test_kernel_schedulability() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"synth", noop_task, TaskPriority::CRITICAL, 23, 10);
+        kernel.add_task(b"motion", noop_task, TaskPriority::HIGH, 100, 20);
+        kernel.add_task(b"edge", noop_task, TaskPriority::NORMAL, 1000, 100);
+
+        assert!(kernel.is_schedulable());
+    }
+
+    #[test]
+    fn test_memory_footprint() {
+        let kernel = Kernel::testing();
+        let size = kernel.memory_footprint();
+        // Should be under 2KB
+        assert!(size < 2048, "kernel size should be < 2KB, got {size}");
+    }
+
+    #[test]
+    fn test_kernel_stats() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t1", noop_task, TaskPriority::NORMAL, 100, 10);
+        let stats = kernel.run_for(1000, 100);
+        assert_eq!(stats.total_us, 1000);
+        #[cfg(not(feature = "no-float"))]
+        assert!(stats.utilization > 0.0);
+        assert!(stats.utilization_ppm > 0);
+    }
+
+    #[test]
+    fn test_kernel_stats_display() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t1", noop_task, TaskPriority::NORMAL, 100, 10);
+        let stats = kernel.run_for(1000, 100);
+        let text = std::format!("{stats}");
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("up 1000 us, ticks 10, jobs 10, switches 1, faults 0")
+        );
+        assert_eq!(lines.next(), Some("U 10.00%, schedulable yes"));
+        assert_eq!(lines.next(), Some(TaskStats::TABLE_HEADER));
+        assert_eq!(
+            lines.next(),
+            Some("t1             10  10.00%      0       0")
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    // --- 追加テスト ---
+
+    #[test]
+    fn test_kernel_not_running_initially() {
+        let kernel = Kernel::testing();
+        assert!(!kernel.is_running());
+    }
+
+    #[test]
+    fn test_kernel_state_transitions() {
+        let mut kernel = Kernel::testing();
+        assert_eq!(kernel.state(), KernelState::Init);
+        assert_eq!(kernel.start(), Ok(()));
+        assert!(kernel.is_running());
+        assert_eq!(
+            kernel.start(),
+            Err(InvalidTransition {
+                from: KernelState::Running,
+                to: KernelState::Running
+            })
+        );
+        kernel.stop();
+        assert_eq!(kernel.state(), KernelState::Stopped);
+        assert!(kernel.recover().is_err());
+
+        // 故障中は tick を拒否し時間も進めない
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.enter_fault();
+        assert_eq!(kernel.tick(100), None);
+        assert_eq!(kernel.timer.now_us(), 0);
+        assert_eq!(kernel.run_for(1_000, 100).tasks_executed, 0);
+        assert!(kernel.start().is_err());
+        assert_eq!(kernel.recover(), Ok(()));
+        assert_eq!(kernel.state(), KernelState::Init);
+        assert_eq!(kernel.run_for(1_000, 100).tasks_executed, 10);
+        assert_eq!(kernel.state(), KernelState::Stopped);
+    }
+
+    #[test]
+    fn test_add_task_while_running() {
+        let mut kernel = Kernel::testing();
+        kernel.start().unwrap();
+        assert_eq!(
+            kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10),
+            None
+        );
+        kernel.allow_live_registration(true);
+        assert_eq!(
+            kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10),
+            Some(0)
+        );
+        // 利用者が開始したカーネルは run_for 後も走行中のまま
+        kernel.run_for(1_000, 100);
+        assert!(kernel.is_running());
+    }
+
+    #[test]
+    fn test_kernel_stop() {
+        let mut kernel = Kernel::testing();
+        kernel.stop();
+        assert!(!kernel.is_running());
+    }
+
+    #[test]
+    fn test_kernel_total_ticks_increments() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 100, 10);
+        kernel.tick(0);
+        kernel.tick(100);
+        kernel.tick(100);
+        assert_eq!(kernel.total_ticks, 3);
+    }
+
+    #[test]
+    fn test_kernel_tick_no_task_still_increments_ticks() {
+        let mut kernel = Kernel::testing();
+        kernel.tick(50);
+        kernel.tick(50);
+        assert_eq!(kernel.total_ticks, 2);
+    }
+
+    #[test]
+    fn test_kernel_timer_advances_with_tick() {
+        let mut kernel = Kernel::testing();
+        kernel.tick(123);
+        assert_eq!(kernel.timer.now_us(), 123);
+    }
+
+    #[test]
+    fn test_kernel_releases_follow_disciplined_time() {
+        fn noop(_: &mut [u8]) {}
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"pps", noop, TaskPriority::HIGH, 1_000, 10);
+        // 参照より 20 µs 遅れている: 20 ms かけて寄せる
+        kernel.timer.discipline(20_000, 0);
+        let mut released = 0;
+        for _ in 0..20 {
+            if kernel.tick(1_000).is_some() {
+                released += 1;
+            }
+        }
+        assert_eq!(kernel.timer.now_us(), 20_020);
+        assert_eq!(kernel.scheduler.now_us(), 20_020);
+        assert_eq!(released, 20);
+        assert_eq!(kernel.timer.slew_remaining_ns(), 0);
+    }
+
+    #[test]
+    fn test_kernel_scratch_written_by_task() {
+        fn write_task(s: &mut [u8]) {
+            s[0] = 0xFF;
+        }
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"w", write_task, TaskPriority::NORMAL, 100, 10);
+        // tick(0) でタスクが実行され、scratch[0] が書き換えられる
+        let executed = kernel.tick(0);
+        assert_eq!(executed, Some(0));
+        // scratch は private だが、タスクが正常実行された事実で確認
+    }
+
+    #[test]
+    fn test_kernel_max_tasks() {
+        let mut kernel = Kernel::testing();
+        for i in 0..16 {
+            let result =
+                kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, (100 + i) as u32, 10);
+            assert!(result.is_some(), "task {i} should be added");
+        }
+        // 17個目は None
+        let over = kernel.add_task(b"x", noop_task, TaskPriority::NORMAL, 9999, 10);
+        assert!(over.is_none());
+    }
+
+    #[test]
+    fn test_kernel_run_for_empty_no_tasks_executed() {
+        let mut kernel = Kernel::testing();
+        let stats = kernel.run_for(1000, 100);
+        assert_eq!(stats.tasks_executed, 0);
+        assert_eq!(stats.context_switches, 0);
+    }
+
+    #[test]
+    fn test_kernel_run_for_tasks_executed_count() {
+        let mut kernel = Kernel::testing();
+        // period=100µs、run 1000µs、tick=100µs → 10回実行
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 100, 10);
+        let stats = kernel.run_for(1000, 100);
+        assert_eq!(stats.tasks_executed, 10);
+    }
+
+    #[test]
+    fn test_kernel_run_for_schedulable_flag() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 1000, 100);
+        let stats = kernel.run_for(100, 10);
+        assert!(stats.schedulable);
+    }
+
+    #[test]
+    fn test_kernel_run_for_not_schedulable() {
+        let mut kernel = Kernel::testing();
+        // utilization > 1.0 → not schedulable
+        kernel.add_task(b"t", noop_task, TaskPriority::CRITICAL, 100, 110);
+        let stats = kernel.run_for(100, 10);
+        assert!(!stats.schedulable);
+    }
+
+    #[test]
+    fn test_kernel_memory_footprint_under_2kb() {
+        let kernel = Kernel::testing();
+        assert!(
+            kernel.memory_footprint() < 2048,
+            "footprint {} bytes should be < 2KB",
+            kernel.memory_footprint()
+        );
+    }
+
+    #[test]
+    fn test_kernel_new_with_hardware_clock() {
+        let kernel = Kernel::new(150_000_000); // Raspberry Pi 5
+        assert_eq!(kernel.timer.ticks_per_us(), 150);
+        assert!(!kernel.is_running());
+    }
+
+    #[test]
+    fn test_kernel_is_schedulable_empty() {
+        let kernel = Kernel::testing();
+        assert!(kernel.is_schedulable());
+    }
+
+    #[test]
+    fn test_kernel_stats_fields() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 50, 5);
+        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 200, 10);
+        let stats = kernel.run_for(500, 50);
+        assert_eq!(stats.total_us, 500);
+        assert!(stats.total_ticks > 0);
+        assert!(stats.tasks_executed > 0);
+        #[cfg(not(feature = "no-float"))]
+        assert!(stats.utilization > 0.0);
+        assert!(stats.utilization_ppm > 0);
+    }
+
+    #[test]
+    fn test_stats_since_earlier_snapshot() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 5);
+        let first = kernel.run_for(1_000, 50);
+        let second = kernel.run_for(500, 50);
+        let total = kernel.stats(1_500, first.tasks_executed + second.tasks_executed);
+        let delta = total.since(&first);
+        assert_eq!(delta.total_us, 500);
+        assert_eq!(delta.tasks_executed, second.tasks_executed);
+        assert_eq!(delta.per_task()[0].exec_count, 5);
+        // 比率は累積側のまま
+        assert_eq!(delta.utilization_ppm, total.utilization_ppm);
+        assert_eq!(total.since(&total).total_ticks, 0);
+    }
+
+    #[test]
+    fn test_run_tickless_skips_idle_ticks() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 1000, 100);
+        let stats = kernel.run_tickless(10_000);
+        assert_eq!(stats.total_us, 10_000);
+        // t = 0, 1000, …, 10000 の 11 回起床のみ
+        assert_eq!(stats.tasks_executed, 11);
+        assert_eq!(stats.total_ticks, 11);
+    }
+
+    #[test]
+    fn test_run_tickless_matches_run_for() {
+        let mut a = Kernel::testing();
+        a.add_task(b"fast", noop_task, TaskPriority::HIGH, 100, 10);
+        a.add_task(b"slow", noop_task, TaskPriority::LOW, 250, 50);
+        let mut b = Kernel::testing();
+        b.add_task(b"fast", noop_task, TaskPriority::HIGH, 100, 10);
+        b.add_task(b"slow", noop_task, TaskPriority::LOW, 250, 50);
+
+        let fixed = a.run_for(1000, 1);
+        let tickless = b.run_tickless(1000);
+        assert_eq!(fixed.tasks_executed, tickless.tasks_executed);
+        assert!(tickless.total_ticks < fixed.total_ticks);
+    }
+
+    #[test]
+    fn test_run_tickless_no_tasks_single_step() {
+        let mut kernel = Kernel::testing();
+        let stats = kernel.run_tickless(5_000);
+        assert_eq!(stats.total_us, 5_000);
+        assert_eq!(stats.total_ticks, 1);
+        assert_eq!(kernel.timer.now_us(), 5_000);
+    }
+
+    #[test]
+    fn test_tick_tickless_jumps_to_activation() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 400, 10);
+        assert_eq!(kernel.tick_tickless(u64::MAX), (0, Some(0)));
+        assert_eq!(kernel.tick_tickless(u64::MAX), (400, Some(0)));
+        assert_eq!(kernel.timer.now_us(), 400);
+    }
+
+    #[test]
+    fn test_run_tickless_with_period_zero_task() {
+        // 周期 0 のタスクは起床のたびに 1 回だけ動き、時間を止めない
+        let mut kernel = Kernel::testing();
+        let poll = kernel
+            .add_task(b"poll", noop_task, TaskPriority::HIGH, 0, 10)
+            .unwrap();
+        let stats = kernel.run_tickless(5_000);
+        assert_eq!(stats.total_us, 5_000);
+        assert_eq!(kernel.scheduler.get_task(poll).unwrap().exec_count, 2);
+
+        let mut kernel = Kernel::testing();
+        let poll = kernel
+            .add_task(b"poll", noop_task, TaskPriority::HIGH, 0, 10)
+            .unwrap();
+        let per = kernel
+            .add_task(b"per", noop_task, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let stats = kernel.run_tickless(4_500);
+        assert_eq!(stats.total_us, 4_500);
+        assert_eq!(kernel.scheduler.get_task(per).unwrap().exec_count, 5);
+        assert_eq!(kernel.scheduler.get_task(poll).unwrap().exec_count, 6);
+    }
+
+    struct MockTick {
+        now: u64,
+        compare: Option<u64>,
+    }
+
+    impl TickSource for MockTick {
+        fn now_us(&self) -> u64 {
+            self.now
+        }
+        fn set_compare(&mut self, at_us: u64) {
+            self.compare = Some(at_us);
+        }
+    }
+
+    #[test]
+    fn test_arm_wakeup_programs_compare() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 500, 10);
+        kernel.tick(0);
+        let mut src = MockTick {
+            now: 0,
+            compare: None,
+        };
+        assert_eq!(kernel.arm_wakeup(&mut src), Some(500));
+        assert_eq!(src.compare, Some(500));
+
+        // 割り込み発火 → ハードウェア時刻に追いつく
+        src.now = 500;
+        assert_eq!(kernel.tick_from(&src), Some(0));
+        assert_eq!(kernel.timer.now_us(), 500);
+    }
+
+    #[test]
+    fn test_arm_wakeup_idle_returns_none() {
+        let kernel = Kernel::testing();
+        let mut src = MockTick {
+            now: 0,
+            compare: None,
+        };
+        assert_eq!(kernel.arm_wakeup(&mut src), None);
+        assert!(src.compare.is_none());
+    }
+
+    #[test]
+    fn test_arm_wakeup_for_soft_timer_only() {
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(leak_pool());
+        kernel.arm_timer(noop_task_timer, 5_000).unwrap();
+        let mut src = MockTick {
+            now: 0,
+            compare: None,
+        };
+        // タスクがなくてもタイマの満了で起床する
+        assert_eq!(kernel.next_wakeup_us(), Some(5_000));
+        assert_eq!(kernel.arm_wakeup(&mut src), Some(5_000));
+        assert_eq!(src.compare, Some(5_000));
+    }
+
+    #[test]
+    fn test_arm_wakeup_skips_throttled_task() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hog", noop_task, TaskPriority::NORMAL, 10, 5);
+        kernel.attach_budgets(leak_budgets(BudgetTable::new()));
+        kernel.set_budget(0, 5, 1_000);
+        kernel.tick(0);
+        kernel.tick(10);
+        let mut src = MockTick {
+            now: 10,
+            compare: None,
+        };
+        // Ready だがスロットル中 → 「今」ではなく補充時刻に設定
+        assert_eq!(kernel.arm_wakeup(&mut src), Some(1_000));
+        assert_eq!(src.compare, Some(1_000));
+    }
+
+    #[test]
+    fn test_connect_tasks() {
+        static POOL: ChannelPool<u32, 8, 2> = ChannelPool::new();
+        let mut kernel = Kernel::testing();
+        let p = kernel
+            .add_task(b"prod", noop_task, TaskPriority::HIGH, 100, 10)
+            .unwrap();
+        let c = kernel
+            .add_task(b"cons", noop_task, TaskPriority::LOW, 1000, 10)
+            .unwrap();
+        let (mut tx, mut rx) = kernel.connect(&POOL, p, c).unwrap();
+        assert!(tx.send(42));
+        assert_eq!(rx.recv(), Some(42));
+        assert_eq!(
+            kernel.links(),
+            &[Link {
+                producer: 0,
+                consumer: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_connect_invalid_tasks() {
+        static POOL: ChannelPool<u32, 4, 4> = ChannelPool::new();
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        assert!(kernel.connect(&POOL, 0, 0).is_none());
+        assert!(kernel.connect(&POOL, 0, 5).is_none());
+        // 失敗した接続はプールを消費しない
+        assert_eq!(POOL.allocated(), 0);
+        assert!(kernel.links().is_empty());
+    }
+
+    #[test]
+    fn test_connect_pool_exhausted() {
+        static POOL: ChannelPool<u8, 4, 1> = ChannelPool::new();
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 100, 10);
+        assert!(kernel.connect(&POOL, 0, 1).is_some());
+        assert!(kernel.connect(&POOL, 1, 0).is_none());
+        assert_eq!(kernel.links().len(), 1);
+    }
+
+    #[test]
+    fn test_try_apis_report_reason() {
+        static POOL: ChannelPool<u8, 4, 1> = ChannelPool::new();
+        let mut kernel = Kernel::testing();
+        for _ in 0..MAX_TASKS {
+            kernel
+                .try_add_task(b"t", noop_task, TaskPriority::LOW, 1_000, 10)
+                .unwrap();
+        }
+        assert_eq!(
+            kernel.try_add_task(b"x", noop_task, TaskPriority::LOW, 1_000, 10),
+            Err(KernelError::TaskTableFull)
+        );
+        assert_eq!(
+            kernel.try_connect(&POOL, 0, 0).err(),
+            Some(KernelError::InvalidIndex)
+        );
+        assert!(kernel.try_connect(&POOL, 0, 1).is_ok());
+        assert_eq!(
+            kernel.try_connect(&POOL, 1, 2).err(),
+            Some(KernelError::PoolExhausted)
+        );
+        assert_eq!(kernel.scheduler.try_unregister(0), Ok(()));
+        assert_eq!(
+            kernel.scheduler.try_unregister(0),
+            Err(KernelError::InvalidIndex)
+        );
+
+        // 走行中の登録拒否と状態遷移の失敗
+        kernel.start().unwrap();
+        assert_eq!(
+            kernel.try_add_task(b"y", noop_task, TaskPriority::LOW, 1_000, 10),
+            Err(KernelError::KernelRunning)
+        );
+        let err: KernelError = kernel.recover().unwrap_err().into();
+        assert_eq!(
+            err,
+            KernelError::InvalidState {
+                from: KernelState::Running,
+                to: KernelState::Init
+            }
+        );
+    }
+
+    #[test]
+    fn test_admission_control() {
+        let mut kernel = Kernel::testing();
+        assert_eq!(kernel.admission(), Admission::Off);
+        kernel.set_admission(Admission::Reject);
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 1_000, 500);
+        // 0.5 + 0.6 > 1 → 拒否され、スロットも残らない
+        assert_eq!(
+            kernel.try_add_task(b"b", noop_task, TaskPriority::LOW, 1_000, 600),
+            Err(KernelError::NotSchedulable)
+        );
+        assert_eq!(kernel.scheduler.task_slots(), 1);
+        assert!(kernel.is_schedulable());
+
+        // Flag は受け入れて通知のみ
+        kernel.set_admission(Admission::Flag);
+        assert_eq!(kernel.admission(), Admission::Flag);
+        assert_eq!(
+            kernel.try_add_task(b"b", noop_task, TaskPriority::LOW, 1_000, 600),
+            Ok(1)
+        );
+        assert!(!kernel.is_schedulable());
+        // 他のフラグを壊さない
+        kernel.set_admission(Admission::Off);
+        assert_eq!(kernel.state(), KernelState::Init);
+    }
+
+    #[test]
+    fn test_admission_compares_set_with_and_without_task() {
+        let mut kernel = Kernel::testing();
+        kernel.set_admission(Admission::Reject);
+        // U = 0.9 は Liu & Layland (0.828) 超だが応答時間解析では成立
+        let a = kernel.try_add_task(b"a", noop_task, TaskPriority::HIGH, 400, 200);
+        let b = kernel.try_add_task(b"b", noop_task, TaskPriority::LOW, 1_000, 400);
+        assert_eq!((a, b), (Ok(0), Ok(1)));
+        assert!(!kernel.is_schedulable());
+
+        // 既に破綻した集合 (Off で登録) にも負荷のないタスクは入る
+        kernel.set_admission(Admission::Off);
+        kernel.add_task(b"c", noop_task, TaskPriority::LOW, 1_000, 500);
+        kernel.set_admission(Admission::Reject);
+        assert_eq!(
+            kernel.try_add_task(b"idle", noop_task, TaskPriority::IDLE, 1_000, 0),
+            Ok(3)
+        );
+        // 低優先度側で自分だけ落ちるタスクは拒否
+        assert_eq!(
+            kernel.try_add_task(b"d", noop_task, TaskPriority::IDLE, 2_000, 10),
+            Err(KernelError::NotSchedulable)
+        );
+        // 守れていた a の締め切りを奪うタスクも拒否
+        assert_eq!(
+            kernel.try_add_task(b"e", noop_task, TaskPriority::CRITICAL, 400, 250),
+            Err(KernelError::NotSchedulable)
+        );
+        assert_eq!(kernel.scheduler.active_task_count(), 4);
+    }
+
+    fn use_64_bytes(s: &mut [u8]) {
+        s[..64].fill(1);
+    }
+
+    fn use_all_scratch(s: &mut [u8]) {
+        s.fill(0);
+    }
+
+    #[test]
+    fn test_stack_watermark_recorded() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", use_64_bytes, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 100, 10);
+        kernel.enable_stack_check(true);
+        kernel.tick(0);
+        kernel.tick(0);
+        assert_eq!(kernel.stack_high_watermark(0), Some(64));
+        assert_eq!(kernel.stack_high_watermark(1), Some(0));
+        assert_eq!(kernel.stack_high_watermark(5), None);
+        assert_eq!(kernel.stack_faults(), 0);
+    }
+
+    #[test]
+    fn test_stack_watermark_disabled_by_default() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", use_64_bytes, TaskPriority::HIGH, 100, 10);
+        kernel.tick(0);
+        assert_eq!(kernel.stack_high_watermark(0), Some(0));
+    }
+
+    #[test]
+    fn test_stack_budget_overflow_flagged() {
+        let mut kernel = Kernel::testing();
+        // スクラッチ予算 32 B に対して 64 B 使用
+        let task = Task::new(b"a", use_64_bytes, TaskPriority::HIGH, 100, 10).with_scratch(32);
+        kernel.scheduler.register(task);
+        kernel.enable_stack_check(true);
+        kernel.tick(0);
+        assert_eq!(kernel.stack_faults(), 0b1);
+    }
+
+    #[test]
+    fn test_stack_guard_corruption_flagged() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hog", use_all_scratch, TaskPriority::HIGH, 100, 10);
+        kernel.enable_stack_check(true);
+        kernel.tick(0);
+        assert_eq!(kernel.stack_faults(), 0b1);
+        assert_eq!(kernel.stack_high_watermark(0), Some(DEFAULT_SCRATCH));
+        assert_eq!(kernel.guard_corruptions(), 0);
+    }
+
+    #[test]
+    fn test_guard_corruption_outside_task() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.enable_stack_check(true);
+        // ISR 相当の外部書き込み
+        kernel.scratch[DEFAULT_SCRATCH - 1] = 0;
+        kernel.tick(0);
+        assert_eq!(kernel.guard_corruptions(), 1);
+        assert_eq!(kernel.stack_faults(), 0);
+    }
+
+    fn fill_scratch(scratch: &mut [u8]) {
+        scratch.fill(0x42);
+    }
+
+    fn count_runs(scratch: &mut [u8]) {
+        scratch[0] = scratch[0].wrapping_add(1);
+    }
+
+    fn fft_4k(scratch: &mut [u8]) {
+        assert!(scratch.len() >= 4_000);
+        scratch[..4_000].fill(7);
+    }
+
+    #[test]
+    fn test_arenas_persist_and_stay_independent() {
+        let mut kernel = Kernel::testing();
+        let a = Task::new(b"a", count_runs, TaskPriority::HIGH, 100, 10).with_arena(16);
+        assert_eq!(kernel.try_add(a), Ok(0));
+        assert_eq!(
+            kernel.try_add_task(b"f", fill_scratch, TaskPriority::LOW, 100, 10),
+            Ok(1)
+        );
+        let b = Task::new(b"b", count_runs, TaskPriority::NORMAL, 100, 10).with_arena(32);
+        assert_eq!(kernel.try_add(b), Ok(2));
+        // タスク番号順にアリーナ、その後ろが共有領域
+        assert_eq!(kernel.arena_bytes(), 48);
+        assert_eq!(kernel.scratch_region(0), (0, 16));
+        assert_eq!(kernel.scratch_region(2), (16, 32));
+        assert_eq!(kernel.scratch_region(1), (48, DEFAULT_SCRATCH - 48));
+
+        for delta in [0, 0, 0, 100, 0, 0] {
+            kernel.tick(delta);
+        }
+        // 共有タスクの書き込みはアリーナに届かず、カウンタは保持される
+        assert_eq!((kernel.scratch[0], kernel.scratch[16]), (2, 2));
+        assert!(kernel.scratch[48..].iter().all(|&b| b == 0x42));
+        assert_eq!(kernel.arena(2).map(|a| (a.len(), a[0])), Some((32, 2)));
+        assert_eq!(kernel.arena(1), None);
+
+        // 自己診断はアリーナの内容とガードを壊さない
+        assert!(kernel.self_test().is_ok());
+        assert_eq!((kernel.scratch[0], kernel.scratch[16]), (2, 2));
+        kernel.tick(100);
+        assert_eq!(kernel.guard_corruptions(), 0);
+    }
+
+    #[test]
+    fn test_arena_overcommit_rejected() {
+        let mut kernel = Kernel::testing();
+        let big = |name| Task::new(name, noop_task, TaskPriority::LOW, 1_000, 10).with_arena(600);
+        assert_eq!(kernel.try_add(big(b"a")), Ok(0));
+        assert_eq!(
+            kernel.try_add(big(b"b")),
+            Err(KernelError::ScratchExhausted)
+        );
+        assert_eq!(kernel.scheduler.active_task_count(), 1);
+        // ガードを除いた残り 420 B はちょうど収まる
+        let rest = Task::new(b"c", noop_task, TaskPriority::LOW, 1_000, 10).with_arena(420);
+        assert_eq!(kernel.try_add(rest), Ok(1));
+        assert_eq!(kernel.scratch_region(2), (1_020, 4));
+    }
+
+    #[test]
+    fn test_sized_kernel_hosts_large_arena() {
+        let fft = || Task::new(b"fft", fft_4k, TaskPriority::HIGH, 1_000, 100).with_arena(4_000);
+        let mut kernel = Kernel::testing();
+        assert_eq!(kernel.try_add(fft()), Err(KernelError::ScratchExhausted));
+
+        let mut kernel = SizedKernel::<5_120>::testing();
+        assert_eq!(kernel.try_add(fft()), Ok(0));
+        kernel.add_task(b"ui", use_64_bytes, TaskPriority::LOW, 1_000, 10);
+        assert_eq!(kernel.scratch_region(1), (4_000, 1_120));
+        kernel.enable_stack_check(true);
+        kernel.tick(0);
+        kernel.tick(0);
+        assert_eq!(kernel.stack_high_watermark(0), Some(4_000));
+        assert_eq!(kernel.stack_high_watermark(1), Some(64));
+        assert_eq!(kernel.stack_faults(), 0);
+        assert_eq!(kernel.guard_corruptions(), 0);
+    }
+
+    #[test]
+    fn test_tick_core_dispatches_by_affinity() {
+        let mut kernel = Kernel::testing();
+        let a = kernel
+            .scheduler
+            .register(Task::new(b"a", noop_task, TaskPriority::HIGH, 100, 10).with_affinity(0b01))
+            .unwrap();
+        let b = kernel
+            .scheduler
+            .register(
+                Task::new(b"b", noop_task, TaskPriority::CRITICAL, 100, 10).with_affinity(0b10),
+            )
+            .unwrap();
+        // コア0 は b (コア1 専用) を無視して a を実行
+        assert_eq!(kernel.tick_core(0, 0), Some(a));
+        assert_eq!(kernel.tick_core(1, 0), Some(b));
+        assert_eq!(kernel.tick_core(0, 0), None);
+        assert_eq!(kernel.tick_core(MAX_CORES, 0), None);
+    }
+
+    #[test]
+    fn test_tick_core_uses_core_scratch_slice() {
+        let mut kernel = Kernel::testing();
+        kernel.scheduler.register(
+            Task::new(b"f", fill_scratch, TaskPriority::HIGH, 100, 10).with_affinity(0b10),
+        );
+        assert_eq!(kernel.tick_core(1, 0), Some(0));
+        let slice = DEFAULT_SCRATCH / MAX_CORES;
+        assert!(kernel.scratch[..slice].iter().all(|&b| b == 0));
+        assert!(kernel.scratch[slice..].iter().all(|&b| b == 0x42));
+    }
+
+    #[test]
+    fn test_dispatch_core_runs_outside_kernel() {
+        let mut kernel = Kernel::testing();
+        kernel.scheduler.register(
+            Task::new(b"a", fill_scratch, TaskPriority::HIGH, 100, 10).with_affinity(0b01),
+        );
+        kernel.scheduler.register(
+            Task::new(b"b", fill_scratch, TaskPriority::HIGH, 100, 10).with_affinity(0b10),
+        );
+        // 両コアのジョブを同時に保持し、カーネル外のバッファで実行できる
+        let job0 = kernel.dispatch_core(0, 0).unwrap();
+        let job1 = kernel.dispatch_core(1, 0).unwrap();
+        assert_eq!((job0.idx, job0.core, job1.idx, job1.core), (0, 0, 1, 1));
+        let (mut buf0, mut buf1) = ([0u8; 8], [0u8; 8]);
+        assert!(job1.run(&mut buf1));
+        assert!(job0.run(&mut buf0));
+        assert_eq!((buf0, buf1), ([0x42; 8], [0x42; 8]));
+        kernel.complete_core(job1, true);
+        kernel.complete_core(job0, true);
+        assert!(kernel.scratch.iter().all(|&b| b == 0));
+        assert_eq!(kernel.scheduler.get_task(0).unwrap().exec_count, 1);
+        assert!(kernel.dispatch_core(0, 0).is_none());
+    }
+
+    #[test]
+    fn test_tick_core_waking_rings_for_pinned_work() {
+        use crate::doorbell::{Doorbell, DoorbellPair};
+        use crate::router::Side;
+
+        let bells = DoorbellPair::new();
+        let (core0, core1) = (bells.handle(Side::A), bells.handle(Side::B));
+        let mut kernel = Kernel::testing();
+        kernel.scheduler.register(
+            Task::new(b"any", noop_task, TaskPriority::HIGH, 100, 10).with_affinity(ANY_CORE),
+        );
+        kernel
+            .scheduler
+            .register(Task::new(b"c1", noop_task, TaskPriority::LOW, 100, 10).with_affinity(0b10));
+        // コア0 は any を実行し、コア1 専用の c1 が残るので鳴らす
+        assert_eq!(kernel.tick_core_waking(0, 0, &core0), Some(0));
+        assert!(core1.poll());
+        assert_eq!(kernel.tick_core_waking(1, 0, &core1), Some(1));
+        assert!(!core1.poll());
+        // 残りなし: 鳴らさない
+        assert_eq!(kernel.tick_core_waking(0, 10, &core0), None);
+        assert!(!core1.poll());
+        assert_eq!(kernel.scheduler.ready_on(MAX_CORES), 0);
+    }
+
+    #[test]
+    fn test_tick_core_shares_time() {
+        let mut kernel = Kernel::testing();
+        kernel.tick_core(0, 30);
+        kernel.tick_core(1, 20);
+        assert_eq!(kernel.scheduler.now_us(), 50);
+        assert_eq!(kernel.total_ticks, 2);
+    }
+
+    // --- 動作モードテスト ---
+
+    const BOOT: Mode = Mode(0);
+    const RUN: Mode = Mode(1);
+    const FAILSAFE: Mode = Mode(2);
+
+    fn add_mode_tasks(kernel: &mut Kernel) {
+        let boot = BOOT.mask();
+        let run = RUN.mask();
+        let safe = FAILSAFE.mask();
+        for task in [
+            Task::new(b"init", noop_task, TaskPriority::HIGH, 1000, 100).in_modes(boot),
+            Task::new(b"ctl", noop_task, TaskPriority::HIGH, 100, 30).in_modes(run),
+            Task::new(b"log", noop_task, TaskPriority::LOW, 1000, 100).in_modes(run | safe),
+            Task::new(b"safe", noop_task, TaskPriority::CRITICAL, 100, 10).in_modes(safe),
+        ] {
+            kernel.scheduler.register(task);
+        }
+    }
+
+    fn states(kernel: &Kernel) -> [TaskState; 4] {
+        core::array::from_fn(|i| kernel.scheduler.get_task(i).unwrap().state)
+    }
+
+    #[test]
+    fn test_switch_mode_suspends_and_resumes() {
+        let mut kernel = Kernel::testing();
+        add_mode_tasks(&mut kernel);
+        assert!(kernel.switch_mode(BOOT));
+        assert_eq!(kernel.mode(), BOOT);
+        let s = states(&kernel);
+        assert_eq!(s[0], TaskState::Ready);
+        assert!(s[1..].iter().all(|&st| st == TaskState::Suspended));
+
+        assert!(kernel.switch_mode(RUN));
+        let s = states(&kernel);
+        assert_eq!(s[0], TaskState::Suspended);
+        assert_eq!(s[1], TaskState::Ready);
+        assert_eq!(s[2], TaskState::Ready);
+        assert_eq!(s[3], TaskState::Suspended);
+    }
+
+    #[test]
+    fn test_switch_mode_keeps_other_suspensions() {
+        let mut kernel = Kernel::testing();
+        add_mode_tasks(&mut kernel);
+        assert!(kernel.switch_mode(BOOT));
+        // アプリが止めたタスクはモードが戻っても止まったまま
+        kernel.scheduler.suspend(0);
+        assert!(kernel.switch_mode(RUN));
+        assert!(kernel.switch_mode(BOOT));
+        assert_eq!(states(&kernel)[0], TaskState::Suspended);
+        assert!(!kernel.scheduler.get_task(0).unwrap().mode_suspended());
+        // モードで止めたタスクを手動で再開すると印は消える
+        assert!(kernel.scheduler.get_task(1).unwrap().mode_suspended());
+        kernel.scheduler.resume(1);
+        assert!(!kernel.scheduler.get_task(1).unwrap().mode_suspended());
+    }
+
+    #[test]
+    fn test_switch_mode_only_runs_mode_tasks() {
+        let mut kernel = Kernel::testing();
+        add_mode_tasks(&mut kernel);
+        assert!(kernel.switch_mode(FAILSAFE));
+        for _ in 0..20 {
+            if let Some(idx) = kernel.tick(50) {
+                assert!(kernel.scheduler.get_task(idx).unwrap().active_in(FAILSAFE));
+            }
+        }
+        assert_eq!(kernel.scheduler.get_task(1).unwrap().exec_count, 0);
+        assert!(kernel.scheduler.get_task(3).unwrap().exec_count > 0);
+    }
+
+    #[test]
+    fn test_switch_mode_rejects_unschedulable() {
+        let mut kernel = Kernel::testing();
+        add_mode_tasks(&mut kernel);
+        kernel.scheduler.register(
+            Task::new(b"hog", noop_task, TaskPriority::NORMAL, 100, 90).in_modes(RUN.mask()),
+        );
+        assert!(kernel.switch_mode(BOOT));
+        assert!(!kernel.switch_mode(RUN));
+        assert_eq!(kernel.mode(), BOOT);
+        assert_eq!(states(&kernel)[1], TaskState::Suspended);
+    }
+
+    #[test]
+    fn test_switch_mode_invalid_mode() {
+        let mut kernel = Kernel::testing();
+        assert!(!kernel.switch_mode(Mode(8)));
+        assert_eq!(kernel.mode(), Mode(0));
     }
 
-    /// Create kernel for testing (software timer)
-    #[must_use]
-    pub const fn testing() -> Self {
-        Self {
-            scheduler: Scheduler::new(),
-            timer: SysTimer::software(),
-            scratch: [0u8; SCRATCH_SIZE],
-            running: false,
-            total_ticks: 0,
+    // --- ソフトウェアタイマテスト ---
+
+    fn leak_pool() -> &'static mut SoftTimerPool {
+        extern crate std;
+        std::boxed::Box::leak(std::boxed::Box::new(SoftTimerPool::new()))
+    }
+
+    #[test]
+    fn test_timer_fires_from_tick() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        static FIRED: AtomicU32 = AtomicU32::new(0);
+        fn cb() {
+            FIRED.fetch_add(1, Ordering::Relaxed);
         }
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(leak_pool());
+        let id = kernel.arm_timer(cb, 5_000).unwrap();
+        kernel.tick(4_000);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 0);
+        assert_eq!(kernel.timer_remaining_us(id), Some(1_000));
+        kernel.tick(1_000);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+        assert_eq!(kernel.timer_remaining_us(id), None);
+        kernel.tick(10_000);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 1);
     }
 
-    /// Register a task
-    pub fn add_task(
-        &mut self,
-        name: &[u8],
-        func: TaskFn,
-        priority: TaskPriority,
-        period_us: u32,
-        wcet_us: u32,
-    ) -> Option<usize> {
-        let task = Task::new(name, func, priority, period_us, wcet_us);
-        self.scheduler.register(task)
+    #[test]
+    fn test_timer_does_not_use_task_slots() {
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(leak_pool());
+        kernel.arm_timer(noop_task_timer, 100);
+        assert_eq!(kernel.scheduler.active_task_count(), 0);
     }
 
-    /// Run one scheduler tick
-    ///
-    /// Advances time by `delta_us` and executes the highest-priority ready task.
-    /// Returns the task index that was executed, if any.
-    pub fn tick(&mut self, delta_us: u64) -> Option<usize> {
-        self.timer.advance(delta_us);
-        self.total_ticks += 1;
+    fn noop_task_timer() {}
 
-        let executed = self.scheduler.tick(delta_us);
+    #[test]
+    fn test_timer_cancel_restart_delete() {
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(leak_pool());
+        let id = kernel.arm_timer(noop_task_timer, 1_000).unwrap();
+        assert!(kernel.cancel_timer(id));
+        assert_eq!(kernel.timer_remaining_us(id), None);
+        kernel.tick(500);
+        assert!(kernel.restart_timer(id));
+        assert_eq!(kernel.timer_remaining_us(id), Some(1_000));
+        assert!(kernel.delete_timer(id));
+        assert!(!kernel.restart_timer(id));
+    }
 
-        // Execute the task with scratch buffer
-        if let Some(idx) = executed {
-            self.scheduler.execute_task(idx, &mut self.scratch);
-        }
+    #[test]
+    fn test_timer_without_pool() {
+        let mut kernel = Kernel::testing();
+        assert_eq!(kernel.arm_timer(noop_task_timer, 10), None);
+        assert!(!kernel.cancel_timer(0));
+        assert_eq!(kernel.timer_remaining_us(0), None);
+    }
 
-        executed
+    #[test]
+    fn test_tickless_wakes_for_timer() {
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(leak_pool());
+        let id = kernel.arm_timer(noop_task_timer, 700).unwrap();
+        assert_eq!(kernel.next_wakeup_us(), Some(700));
+        let (delta, _) = kernel.tick_tickless(10_000);
+        assert_eq!(delta, 700);
+        assert_eq!(kernel.timer_remaining_us(id), None);
     }
 
-    /// Run the kernel for a given duration (testing)
-    pub fn run_for(&mut self, total_us: u64, tick_us: u64) -> KernelStats {
-        self.running = true;
-        let mut elapsed = 0u64;
-        let mut tasks_executed = 0u64;
+    // --- スイッチフックテスト ---
 
-        while elapsed < total_us && self.running {
-            if self.tick(tick_us).is_some() {
-                tasks_executed += 1;
+    #[cfg(feature = "hooks")]
+    mod hooks {
+        use super::*;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TRACE: [AtomicUsize; 4] = [const { AtomicUsize::new(usize::MAX) }; 4];
+        static POS: AtomicUsize = AtomicUsize::new(0);
+
+        fn record(v: usize) {
+            let i = POS.fetch_add(1, Ordering::Relaxed);
+            if i < TRACE.len() {
+                TRACE[i].store(v, Ordering::Relaxed);
             }
-            elapsed += tick_us;
         }
 
-        self.running = false;
-        KernelStats {
-            total_us: elapsed,
-            total_ticks: self.total_ticks,
-            tasks_executed,
-            context_switches: self.scheduler.context_switches as u64,
-            utilization: self.scheduler.total_utilization(),
-            schedulable: self.scheduler.is_schedulable(),
+        fn on_enter(idx: usize) {
+            record(idx);
+        }
+
+        fn on_exit(idx: usize) {
+            record(100 + idx);
+        }
+
+        fn body(_: &mut [u8]) {
+            record(50);
+        }
+
+        #[test]
+        fn test_hooks_wrap_task_execution() {
+            let mut kernel = Kernel::testing();
+            kernel.add_task(b"t", body, TaskPriority::NORMAL, 100, 10);
+            kernel.set_switch_hooks(on_enter, on_exit);
+            kernel.tick(0);
+            kernel.clear_switch_hooks();
+            kernel.tick(100);
+            let trace: [usize; 4] = core::array::from_fn(|i| TRACE[i].load(Ordering::Relaxed));
+            assert_eq!(trace, [0, 50, 100, 50]);
         }
     }
 
-    /// Stop the kernel
-    pub const fn stop(&mut self) {
-        self.running = false;
+    // --- 障害分離テスト (巻き戻しのあるビルドのみ) ---
+
+    #[cfg(panic = "unwind")]
+    fn faulty_task(_: &mut [u8]) {
+        panic!("task fault");
     }
 
-    /// Is the kernel running?
-    #[must_use]
-    pub const fn is_running(&self) -> bool {
-        self.running
+    #[test]
+    #[cfg(panic = "unwind")]
+    fn test_panicking_task_isolated() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"bad", faulty_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"good", noop_task, TaskPriority::LOW, 100, 10);
+        let stats = kernel.run_for(1_000, 50);
+        assert!(kernel.scheduler.get_task(0).unwrap().fault_count > 0);
+        assert_eq!(kernel.scheduler.get_task(1).unwrap().fault_count, 0);
+        assert!(kernel.scheduler.get_task(1).unwrap().exec_count > 0);
+        assert_eq!(stats.task_faults, kernel.total_faults());
     }
 
-    /// Check RMS schedulability
-    #[must_use]
-    pub fn is_schedulable(&self) -> bool {
-        self.scheduler.is_schedulable()
+    #[test]
+    #[cfg(panic = "unwind")]
+    fn test_fault_limit_auto_suspends() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"bad", faulty_task, TaskPriority::HIGH, 100, 10);
+        kernel.set_fault_limit(3);
+        kernel.run_for(2_000, 100);
+        let task = kernel.scheduler.get_task(0).unwrap();
+        assert_eq!(task.fault_count, 3);
+        assert_eq!(task.state, TaskState::Suspended);
     }
 
-    /// Memory footprint estimate
-    #[must_use]
-    pub const fn memory_footprint(&self) -> usize {
-        core::mem::size_of::<Self>()
+    #[test]
+    #[cfg(panic = "unwind")]
+    fn test_no_fault_limit_keeps_running() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"bad", faulty_task, TaskPriority::HIGH, 100, 10);
+        kernel.run_for(1_000, 100);
+        let task = kernel.scheduler.get_task(0).unwrap();
+        assert_eq!(task.fault_count, 10);
+        assert_ne!(task.state, TaskState::Suspended);
     }
-}
 
-/// Kernel execution statistics
-#[derive(Debug, Clone)]
-pub struct KernelStats {
-    /// Total elapsed time (µs)
-    pub total_us: u64,
-    /// Total scheduler ticks
-    pub total_ticks: u64,
-    /// Tasks executed
-    pub tasks_executed: u64,
-    /// Context switches
-    pub context_switches: u64,
-    /// CPU utilization
-    pub utilization: f32,
-    /// RMS schedulable
-    pub schedulable: bool,
-}
+    // --- タスク別統計テスト ---
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    use core::sync::atomic::{AtomicU64, Ordering};
 
-    fn noop_task(_scratch: &mut [u8]) {}
+    static PROFILE_CLOCK: AtomicU64 = AtomicU64::new(0);
 
-    #[test]
-    fn test_kernel_creation() {
-        let kernel = Kernel::testing();
-        assert_eq!(kernel.scheduler.active_task_count(), 0);
+    fn profile_clock() -> u64 {
+        PROFILE_CLOCK.load(Ordering::Relaxed)
+    }
+
+    fn busy_10(_: &mut [u8]) {
+        PROFILE_CLOCK.fetch_add(10, Ordering::Relaxed);
+    }
+
+    fn busy_30(_: &mut [u8]) {
+        PROFILE_CLOCK.fetch_add(30, Ordering::Relaxed);
     }
 
     #[test]
-    fn test_kernel_add_task() {
+    fn test_per_task_breakdown_with_profiler() {
+        extern crate std;
+        let profiler =
+            std::boxed::Box::leak(std::boxed::Box::new(TaskProfiler::new(profile_clock)));
         let mut kernel = Kernel::testing();
-        let idx = kernel.add_task(b"test", noop_task, TaskPriority::NORMAL, 1000, 100);
-        assert_eq!(idx, Some(0));
-        assert_eq!(kernel.scheduler.active_task_count(), 1);
+        kernel.add_task(b"fast", busy_10, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"slow", busy_30, TaskPriority::LOW, 100, 30);
+        kernel.attach_profiler(profiler);
+        // t=0: fast 実行, t=50: slow 実行 (リリースから 50 µs 遅延)
+        kernel.tick(0);
+        kernel.tick(50);
+        let stats = kernel.run_for(0, 1);
+        let per_task = stats.per_task();
+        assert_eq!(per_task.len(), 2);
+        assert_eq!(&per_task[0].name[..4], b"fast");
+        assert_eq!(per_task[0].exec_count, 1);
+        assert_eq!(per_task[0].max_exec_us, 10);
+        assert_eq!(per_task[0].max_response_us, 10);
+        assert_eq!(per_task[1].max_exec_us, 30);
+        assert_eq!(per_task[1].max_response_us, 80);
+        assert_eq!(per_task[1].utilization_ppm, 300_000);
     }
 
     #[test]
-    fn test_kernel_tick() {
+    fn test_profiler_latency_from_release_at_dispatch() {
+        extern crate std;
+        // 実行時間 0 の時計: 応答時間 = リリースからディスパッチまでの遅延
+        fn still() -> u64 {
+            0
+        }
+        let profiler = std::boxed::Box::leak(std::boxed::Box::new(TaskProfiler::new(still)));
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"test", noop_task, TaskPriority::NORMAL, 100, 10);
+        let hog = Task::new(b"hog", noop_task, TaskPriority::HIGH, 0, 10).released_by_isr();
+        let hog = kernel.scheduler.register(hog).unwrap();
+        let ev = Task::new(b"ev", noop_task, TaskPriority::LOW, 0, 10).released_by_isr();
+        let ev = kernel.scheduler.register(ev).unwrap();
+        let ctl = kernel
+            .add_task(b"ctl", noop_task, TaskPriority::NORMAL, 100, 10)
+            .unwrap();
+        kernel.attach_profiler(profiler);
+        let response = |k: &Kernel, idx: usize| k.stats(0, 0).per_task()[idx].max_response_us;
+        assert_eq!(kernel.tick(0), Some(ctl));
 
-        let executed = kernel.tick(0);
-        assert_eq!(executed, Some(0));
+        // 周期 0: t = 10 にリリース、hog の後 t = 25 で実行
+        kernel.scheduler.release_from_isr(hog);
+        kernel.scheduler.release_from_isr(ev);
+        assert_eq!(kernel.tick(10), Some(hog));
+        assert_eq!(kernel.tick(15), Some(ev));
+        assert_eq!(response(&kernel, ev), 15);
+
+        // 周期変更後も次のリリース (t = 100) から数える
+        assert!(kernel.scheduler.set_period(ctl, 400));
+        kernel.scheduler.release_from_isr(hog);
+        assert_eq!(kernel.tick(75), Some(hog));
+        assert_eq!(kernel.tick(40), Some(ctl));
+        assert_eq!(response(&kernel, ctl), 40);
+
+        // 取りこぼしで併合されたジョブは最後のリリース (t = 900) から
+        assert_eq!(kernel.tick(850), Some(ctl));
+        assert_eq!(response(&kernel, ctl), 90);
     }
 
     #[test]
-    fn test_kernel_run_for() {
+    fn test_load_averages_from_profiler() {
+        extern crate std;
+        // 他テストの PROFILE_CLOCK と干渉しない専用クロック
+        static LOAD_CLOCK: AtomicU64 = AtomicU64::new(0);
+        fn load_clock() -> u64 {
+            LOAD_CLOCK.load(Ordering::Relaxed)
+        }
+        fn busy(_: &mut [u8]) {
+            LOAD_CLOCK.fetch_add(30, Ordering::Relaxed);
+        }
+        let profiler = std::boxed::Box::leak(std::boxed::Box::new(TaskProfiler::new(load_clock)));
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"fast", noop_task, TaskPriority::HIGH, 100, 10);
-        kernel.add_task(b"slow", noop_task, TaskPriority::LOW, 1000, 50);
-
-        let stats = kernel.run_for(10_000, 10);
-        assert!(stats.tasks_executed > 0);
-        assert!(stats.schedulable);
+        assert_eq!(kernel.load_averages(), None);
+        // 100 µs 周期で 30 µs 実行 = 30 % 負荷
+        kernel.add_task(b"busy", busy, TaskPriority::HIGH, 100, 30);
+        kernel.attach_profiler(profiler);
+        kernel.run_tickless(5_000_000);
+        let load = kernel.load_averages().unwrap();
+        assert!((295_000..=300_000).contains(&load.short_ppm));
+        assert!(load.short_ppm > load.medium_ppm);
+        assert!(load.medium_ppm > load.long_ppm);
+        assert!(load.long_ppm > 0);
     }
 
     #[test]
-    fn test_kernel_schedulability() {
+    fn test_response_percentile_from_histogram() {
+        extern crate std;
+        use std::boxed::Box;
+        static P99_CLOCK: AtomicU64 = AtomicU64::new(0);
+        fn p99_clock() -> u64 {
+            P99_CLOCK.load(Ordering::Relaxed)
+        }
+        fn fast(_: &mut [u8]) {
+            P99_CLOCK.fetch_add(20, Ordering::Relaxed);
+        }
+        fn slow(_: &mut [u8]) {
+            P99_CLOCK.fetch_add(200, Ordering::Relaxed);
+        }
+        let profiler = Box::leak(Box::new(TaskProfiler::new(p99_clock)));
+        profiler.attach_histogram(Box::leak(Box::new(
+            crate::profiler::ResponseHistogram::new(10),
+        )));
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"synth", noop_task, TaskPriority::CRITICAL, 23, 10);
-        kernel.add_task(b"motion", noop_task, TaskPriority::HIGH, 100, 20);
-        kernel.add_task(b"edge", noop_task, TaskPriority::NORMAL, 1000, 100);
-
-        assert!(kernel.is_schedulable());
+        kernel.add_task(b"fast", fast, TaskPriority::HIGH, 1_000, 20);
+        kernel.add_task(b"slow", slow, TaskPriority::LOW, 1_000, 200);
+        assert_eq!(kernel.response_percentile_us(0, 990), None);
+        kernel.attach_profiler(profiler);
+        kernel.run_tickless(100_000);
+        // fast: 応答 20 µs → 区間 [20, 40)
+        assert_eq!(kernel.response_percentile_us(0, 990), Some(20));
+        // slow: 応答 200 µs → 区間 [160, 320) を最大値で頭打ち
+        assert_eq!(kernel.response_percentile_us(1, 990), Some(200));
     }
 
     #[test]
-    fn test_memory_footprint() {
-        let kernel = Kernel::testing();
-        let size = kernel.memory_footprint();
-        // Should be under 2KB
-        assert!(size < 2048, "kernel size should be < 2KB, got {size}");
+    fn test_per_task_without_profiler() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 200, 50);
+        let stats = kernel.run_for(1_000, 50);
+        let per_task = stats.per_task();
+        assert_eq!(per_task.len(), 2);
+        for (idx, t) in per_task.iter().enumerate() {
+            assert!(t.exec_count > 0);
+            assert_eq!(
+                t.exec_count,
+                kernel.scheduler.get_task(idx).unwrap().exec_count
+            );
+        }
+        let total: u64 = per_task.iter().map(|t| t.exec_count as u64).sum();
+        assert_eq!(total, stats.tasks_executed);
+        assert_eq!(per_task[0].max_exec_us, 0);
+        assert_eq!(per_task[1].max_response_us, 0);
+        let sum: u32 = per_task.iter().map(|t| t.utilization_ppm).sum();
+        assert_eq!(sum, stats.utilization_ppm);
     }
 
     #[test]
-    fn test_kernel_stats() {
+    fn test_per_task_deadline_misses_and_unregistered() {
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"t1", noop_task, TaskPriority::NORMAL, 100, 10);
-        let stats = kernel.run_for(1000, 100);
-        assert_eq!(stats.total_us, 1000);
-        assert!(stats.utilization > 0.0);
+        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 100, 10);
+        kernel.tick(0);
+        kernel.tick(250);
+        kernel.scheduler.unregister(1);
+        let stats = kernel.run_for(0, 1);
+        let per_task = stats.per_task();
+        assert_eq!(per_task[0].deadline_misses, 1);
+        assert!(per_task.len() <= 2);
+        assert!(per_task.iter().skip(1).all(|t| t.utilization_ppm == 0));
     }
 
-    // --- 追加テスト ---
+    // --- 実行バジェットテスト ---
 
-    #[test]
-    fn test_kernel_not_running_initially() {
-        let kernel = Kernel::testing();
-        assert!(!kernel.is_running());
+    fn leak_budgets(table: BudgetTable) -> &'static mut BudgetTable {
+        extern crate std;
+        std::boxed::Box::leak(std::boxed::Box::new(table))
     }
 
     #[test]
-    fn test_kernel_stop() {
+    fn test_runaway_task_starves_without_budget() {
         let mut kernel = Kernel::testing();
-        kernel.stop();
-        assert!(!kernel.is_running());
+        kernel.add_task(b"hog", noop_task, TaskPriority::NORMAL, 10, 1);
+        kernel.add_task(b"low", noop_task, TaskPriority::LOW, 100, 10);
+        kernel.run_for(1_000, 10);
+        assert_eq!(kernel.scheduler.get_task(1).unwrap().exec_count, 0);
     }
 
     #[test]
-    fn test_kernel_total_ticks_increments() {
+    fn test_budget_throttles_runaway_task() {
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 100, 10);
-        kernel.tick(0);
-        kernel.tick(100);
-        kernel.tick(100);
-        assert_eq!(kernel.total_ticks, 3);
+        kernel.add_task(b"hog", noop_task, TaskPriority::NORMAL, 10, 1);
+        kernel.add_task(b"low", noop_task, TaskPriority::LOW, 100, 10);
+        kernel.attach_budgets(leak_budgets(BudgetTable::new()));
+        // WCET 課金: 100 µs あたり 5 ジョブまで
+        assert!(kernel.set_budget(0, 5, 100));
+        kernel.run_for(1_000, 10);
+        let hog = kernel.scheduler.get_task(0).unwrap().exec_count;
+        assert!(kernel.scheduler.get_task(1).unwrap().exec_count > 0);
+        // t=0..1000 の 11 補充区間 × 5 ジョブが上限
+        assert!(hog <= 55, "hog ran {hog} times");
+        assert!(kernel.throttle_count(0) >= 9);
+        assert_eq!(kernel.throttle_count(1), 0);
     }
 
-    #[test]
-    fn test_kernel_tick_no_task_still_increments_ticks() {
-        let mut kernel = Kernel::testing();
-        kernel.tick(50);
-        kernel.tick(50);
-        assert_eq!(kernel.total_ticks, 2);
+    static BUDGET_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+    fn budget_clock() -> u64 {
+        BUDGET_CLOCK.load(Ordering::Relaxed)
+    }
+
+    fn burn_40(_: &mut [u8]) {
+        BUDGET_CLOCK.fetch_add(40, Ordering::Relaxed);
     }
 
     #[test]
-    fn test_kernel_timer_advances_with_tick() {
+    fn test_budget_charges_measured_time() {
         let mut kernel = Kernel::testing();
-        kernel.tick(123);
-        assert_eq!(kernel.timer.now_us(), 123);
+        kernel.add_task(b"burn", burn_40, TaskPriority::NORMAL, 10, 1);
+        kernel.attach_budgets(leak_budgets(BudgetTable::with_clock(budget_clock)));
+        kernel.set_budget(0, 100, 1_000);
+        // 40 + 40 + 40 ≥ 100 → 3 ジョブ目でスロットル
+        kernel.run_for(100, 10);
+        assert_eq!(kernel.scheduler.get_task(0).unwrap().exec_count, 3);
+        assert_eq!(kernel.throttle_count(0), 1);
     }
 
     #[test]
-    fn test_kernel_scratch_written_by_task() {
-        fn write_task(s: &mut [u8]) {
-            s[0] = 0xFF;
-        }
+    fn test_throttled_task_wakes_at_replenish() {
         let mut kernel = Kernel::testing();
-        kernel.add_task(b"w", write_task, TaskPriority::NORMAL, 100, 10);
-        // tick(0) でタスクが実行され、scratch[0] が書き換えられる
-        let executed = kernel.tick(0);
+        kernel.add_task(b"hog", noop_task, TaskPriority::NORMAL, 10, 5);
+        kernel.attach_budgets(leak_budgets(BudgetTable::new()));
+        kernel.set_budget(0, 5, 1_000);
+        kernel.tick(0);
+        kernel.tick(10);
+        // Ready だがスロットル中 → 次の起床は補充時刻
+        assert_eq!(kernel.next_wakeup_us(), Some(1_000));
+        let (delta, executed) = kernel.tick_tickless(5_000);
+        assert_eq!(delta, 990);
         assert_eq!(executed, Some(0));
-        // scratch は private だが、タスクが正常実行された事実で確認
     }
 
     #[test]
-    fn test_kernel_max_tasks() {
+    fn test_set_budget_without_table() {
         let mut kernel = Kernel::testing();
-        for i in 0..16 {
-            let result =
-                kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, (100 + i) as u32, 10);
-            assert!(result.is_some(), "task {i} should be added");
-        }
-        // 17個目は None
-        let over = kernel.add_task(b"x", noop_task, TaskPriority::NORMAL, 9999, 10);
-        assert!(over.is_none());
+        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 100, 10);
+        assert!(!kernel.set_budget(0, 10, 100));
+        assert_eq!(kernel.throttle_count(0), 0);
     }
 
-    #[test]
-    fn test_kernel_run_for_empty_no_tasks_executed() {
+    // --- スナップショットテスト ---
+
+    fn booted() -> Kernel {
         let mut kernel = Kernel::testing();
-        let stats = kernel.run_for(1000, 100);
-        assert_eq!(stats.tasks_executed, 0);
-        assert_eq!(stats.context_switches, 0);
+        kernel.add_task(b"ctl", noop_task, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"log", noop_task, TaskPriority::LOW, 1_000, 50);
+        kernel
     }
 
     #[test]
-    fn test_kernel_run_for_tasks_executed_count() {
-        let mut kernel = Kernel::testing();
-        // period=100µs、run 1000µs、tick=100µs → 10回実行
-        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 100, 10);
-        let stats = kernel.run_for(1000, 100);
-        assert_eq!(stats.tasks_executed, 10);
+    fn test_snapshot_round_trip() {
+        let mut kernel = booted();
+        kernel.run_for(2_550, 50);
+        kernel.scheduler.set_period(1, 500);
+        kernel.scheduler.suspend(1);
+        let mut buf = [0u8; crate::snapshot::snapshot_size(MAX_TASKS)];
+        let len = kernel.save_state(&mut buf).unwrap();
+        assert_eq!(len, crate::snapshot::snapshot_size(2));
+
+        // 電源断後の再起動: 同じタスクを登録して復元
+        let mut warm = booted();
+        warm.restore_state(&buf[..len]).unwrap();
+        assert_eq!(warm.timer.now_us(), kernel.timer.now_us());
+        assert_eq!(warm.scheduler.now_us(), kernel.scheduler.now_us());
+        assert_eq!(warm.total_ticks, kernel.total_ticks);
+        assert_eq!(
+            warm.scheduler.context_switches,
+            kernel.scheduler.context_switches
+        );
+        for idx in 0..2 {
+            let (a, b) = (
+                kernel.scheduler.get_task(idx).unwrap(),
+                warm.scheduler.get_task(idx).unwrap(),
+            );
+            assert_eq!(a.exec_count, b.exec_count);
+            assert_eq!(a.next_activation, b.next_activation);
+            assert_eq!(a.period_us, b.period_us);
+            assert_eq!(a.state, b.state);
+            assert_eq!(a.mode_change_pending(), b.mode_change_pending());
+        }
+        // 以降の実行も一致
+        assert_eq!(warm.tick(100), kernel.tick(100));
     }
 
     #[test]
-    fn test_kernel_run_for_schedulable_flag() {
-        let mut kernel = Kernel::testing();
-        kernel.add_task(b"t", noop_task, TaskPriority::NORMAL, 1000, 100);
-        let stats = kernel.run_for(100, 10);
-        assert!(stats.schedulable);
+    fn test_snapshot_buffer_too_small() {
+        let kernel = booted();
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            kernel.save_state(&mut buf),
+            Err(SnapshotError::BufferTooSmall {
+                needed: crate::snapshot::snapshot_size(2)
+            })
+        );
     }
 
     #[test]
-    fn test_kernel_run_for_not_schedulable() {
-        let mut kernel = Kernel::testing();
-        // utilization > 1.0 → not schedulable
-        kernel.add_task(b"t", noop_task, TaskPriority::CRITICAL, 100, 110);
-        let stats = kernel.run_for(100, 10);
-        assert!(!stats.schedulable);
+    fn test_restore_rejects_other_task_set() {
+        let kernel = booted();
+        let mut buf = [0u8; 256];
+        let len = kernel.save_state(&mut buf).unwrap();
+
+        let mut renamed = Kernel::testing();
+        renamed.add_task(b"ctl", noop_task, TaskPriority::HIGH, 100, 10);
+        renamed.add_task(b"tlm", noop_task, TaskPriority::LOW, 1_000, 50);
+        assert_eq!(
+            renamed.restore_state(&buf[..len]),
+            Err(SnapshotError::TaskMismatch { idx: 1 })
+        );
+        let mut fewer = Kernel::testing();
+        fewer.add_task(b"ctl", noop_task, TaskPriority::HIGH, 100, 10);
+        assert!(matches!(
+            fewer.restore_state(&buf[..len]),
+            Err(SnapshotError::TaskMismatch { .. })
+        ));
     }
 
     #[test]
-    fn test_kernel_memory_footprint_under_2kb() {
-        let kernel = Kernel::testing();
-        assert!(
-            kernel.memory_footprint() < 2048,
-            "footprint {} bytes should be < 2KB",
-            kernel.memory_footprint()
+    fn test_restore_corrupt_leaves_kernel_untouched() {
+        let mut kernel = booted();
+        kernel.run_for(1_000, 100);
+        let mut buf = [0u8; 256];
+        let len = kernel.save_state(&mut buf).unwrap();
+        buf[60] ^= 0xFF;
+        let mut fresh = booted();
+        assert_eq!(
+            fresh.restore_state(&buf[..len]),
+            Err(SnapshotError::Corrupt)
         );
+        assert_eq!(fresh.total_ticks, 0);
+        assert_eq!(fresh.scheduler.get_task(0).unwrap().exec_count, 0);
+        assert!(matches!(
+            fresh.restore_state(&buf[..20]),
+            Err(SnapshotError::BufferTooSmall { .. })
+        ));
     }
 
+    // --- テレメトリテスト ---
+
     #[test]
-    fn test_kernel_new_with_hardware_clock() {
-        let kernel = Kernel::new(150_000_000); // Raspberry Pi 5
-        assert_eq!(kernel.timer.ticks_per_us(), 150);
-        assert!(!kernel.is_running());
+    fn test_stats_encode_compact() {
+        let mut kernel = booted();
+        let stats = kernel.run_for(10_000, 50);
+        let mut buf = [0u8; crate::telemetry::MAX_STATS_LEN];
+        let len = stats.encode(&mut buf);
+        // 2 タスクでも数十バイトに収まる
+        assert!(len > 0 && len < 64, "len = {len}");
+        assert_eq!(buf[0], crate::telemetry::STATS_WIRE_VERSION);
+        // バッファ不足は 0
+        assert_eq!(stats.encode(&mut buf[..len - 1]), 0);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn test_kernel_is_schedulable_empty() {
-        let kernel = Kernel::testing();
-        assert!(kernel.is_schedulable());
+    fn test_stats_serialize_json() {
+        use crate::trace::TraceEntry;
+
+        let mut kernel = booted();
+        let stats = kernel.run_for(10_000, 50);
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["total_us"], 10_000);
+        assert_eq!(json["utilization_ppm"], stats.utilization_ppm);
+        // 使用中のスロットだけ、名前は文字列
+        let per_task = json["per_task"].as_array().unwrap();
+        assert_eq!(per_task.len(), 2);
+        assert_eq!(per_task[0]["name"], "ctl");
+        assert_eq!(per_task[0]["exec_count"], stats.per_task()[0].exec_count);
+
+        let task = serde_json::to_value(kernel.scheduler.get_task(1).unwrap()).unwrap();
+        assert_eq!(task["name"], "log");
+        assert_eq!(task["priority"], 3);
+        assert!(task.get("func").is_none());
+
+        let entry = TraceEntry::Event(KernelEvent::DeadlineMiss { idx: 1, at_us: 42 });
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"Event":{"DeadlineMiss":{"idx":1,"at_us":42}}}"#
+        );
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_kernel_stats_fields() {
-        let mut kernel = Kernel::testing();
-        kernel.add_task(b"a", noop_task, TaskPriority::HIGH, 50, 5);
-        kernel.add_task(b"b", noop_task, TaskPriority::LOW, 200, 10);
-        let stats = kernel.run_for(500, 50);
-        assert_eq!(stats.total_us, 500);
-        assert!(stats.total_ticks > 0);
-        assert!(stats.tasks_executed > 0);
-        assert!(stats.utilization > 0.0);
+    fn test_stats_decode_round_trip() {
+        use crate::power::SleepState;
+        use crate::telemetry::DecodeError;
+
+        let mut kernel = booted();
+        let mut stats = kernel.run_for(10_000, 50);
+        stats.sleep.record(SleepState::Stop, 1_234);
+        let mut buf = [0u8; crate::telemetry::MAX_STATS_LEN];
+        let len = stats.encode(&mut buf);
+        let decoded = KernelStats::decode(&buf[..len]).unwrap();
+        assert_eq!(decoded.total_us, stats.total_us);
+        assert_eq!(decoded.tasks_executed, stats.tasks_executed);
+        assert_eq!(decoded.context_switches, stats.context_switches);
+        assert_eq!(decoded.utilization_ppm, stats.utilization_ppm);
+        assert_eq!(decoded.schedulable, stats.schedulable);
+        assert_eq!(decoded.sleep, stats.sleep);
+        assert_eq!(decoded.per_task().len(), 2);
+        assert_eq!(decoded.per_task()[1].name, stats.per_task()[1].name);
+        assert_eq!(
+            decoded.per_task()[0].exec_count,
+            stats.per_task()[0].exec_count
+        );
+        // 切り詰め・改竄は検出される
+        assert!(KernelStats::decode(&buf[..len - 1]).is_err());
+        buf[3] ^= 0x40;
+        assert_eq!(
+            KernelStats::decode(&buf[..len]).err(),
+            Some(DecodeError::Corrupt)
+        );
     }
 }
@@ -12,22 +12,97 @@
 //!
 //! | Module | Description |
 //! |--------|-------------|
-//! | [`task`] | Static no-alloc task descriptors with priority and WCET |
+//! | [`task`](mod@task) | Static no-alloc task descriptors with priority and WCET |
 //! | [`scheduler`] | Rate-Monotonic scheduler with deadline tracking |
+//! | [`policy`] | `SchedulingPolicy` dispatch-order trait (`Rms`, `Edf`), a zero-cost type parameter of `Scheduler` / `SizedKernel` |
+//! | [`runqueue`] | `RunQueue` policy: priority-ordered ready bitmap kept by the scheduler, O(1) dispatch without a table scan, releases and deadlines on a timing wheel |
+//! | [`sched_lock`] | Scheduler lock guard: non-preemptive regions with a max-duration watchdog |
+//! | [`timeout`] | Channel receives with a timeout: consumers blocked until data or deadline, `TimedOut` instead of polling |
 //! | [`timer`] | Hardware-abstracted system timer (tick / µs / ms) |
-//! | [`spsc`] | Lock-free single-producer single-consumer ring buffer |
+//! | [`spsc`] | Lock-free single-producer single-consumer ring buffer, splittable into ISR-safe halves |
+//! | [`aligned_ring`] | Cache-line aligned SPSC ring with DMA producer access |
+//! | [`block`] | Bresenham block sizes between producer / consumer rates, whole-block ring reads |
+//! | [`channel`] | Typed SPSC channels wired between tasks, time-stamped items with freshness limits |
+//! | [`mailbox`] | Latest-value seqlock mailbox (overwrite semantics) |
+//! | [`double_buffer`] | Two-slot parameter buffer flipped atomically, read per job without tearing |
+//! | [`params`] | Versioned read-copy-update table of typed configuration entries |
+//! | [`broadcast`] | Single-producer multi-consumer broadcast ring with lag detection |
+//! | [`pool`] | Lock-free fixed-size block pool |
 //! | [`kernel`] | Top-level kernel combining scheduler + timer + scratch |
+//! | [`router`] | Two-way message router between kernels / cores: addressed SPSC ring pair, dispatcher task filling per-task inboxes |
+//! | [`doorbell`] | `Doorbell` inter-core wake-up (signal / poll / clear): shared-memory pair, RP2040 SIO FIFO (`rp2040` feature) |
+//! | [`domain`] | `TimeDomain`: side-by-side kernels on their own clocks, timestamp translation for channels crossing them |
+//! | [`lifecycle`] | Per-task `on_start` / `on_stop` / `on_overrun` callbacks on suspend, resume and deadline misses |
+//! | [`log_task`](mod@log_task) | Rate-limited log drain task over static per-task line buffers |
+//! | [`soft_timer`] | Deferred one-shot software timers fired from the kernel tick |
+//! | [`error`] | `KernelError` returned by the `try_*` APIs |
+//! | [`events`] | Structured kernel events routed to `defmt` / `log` |
+//! | [`fault`] | Reset-surviving black box of the last fault (storms, overruns, panics) |
+//! | [`init`] | Per-task init functions run once, in priority order, before the first release |
+//! | [`integrity`] | CRC-32 over each task descriptor, round-robin verification, golden-copy restore |
+//! | `simulator` | Wall-clock paced host simulator (`std` feature) |
+//! | `testkit` | Random task sets, exact hyperperiod simulation, RMS / kernel deadline assertions (`std` feature) |
+//! | `inject` | Seeded fault injection: overruns, release jitter, dropped activations (`std` feature) |
+//! | `chrome_trace` | Chrome / Perfetto trace-event JSON export (`std` feature) |
+//! | `mpu` | Per-task Cortex-M MPU regions (stack, scratch, granted channels) programmed on context switch (`mpu` feature) |
+//! | `syscall` | Unprivileged tasks with SVC kernel calls (suspend / resume / release, port send / receive) (`mpu` feature) |
+//! | `codegen` | Build-script generation of the task table, channels and a utilization report from a TOML schedule (`codegen` feature) |
+//! | `table` | `Kernel::load_table`: validated all-or-nothing registration from a const `TaskSpec` table (`task-table` feature) |
+//! | `mtimer` | RISC-V CLINT / ACLINT `mtime` tick source and timer trap body (`arch-riscv` feature) |
+//! | `delay` | embedded-hal `DelayNs` on `SysTimer` / ISR-safe `SharedTimer` (`embedded-hal` feature) |
+//! | `embassy` | `embassy-time` driver on the kernel clock + executor poll task (`embassy` feature) |
+//! | `static_kernel` | `StaticKernel`: critical-section shared kernel with lock-free ISR releases (`static-kernel` feature) |
+//! | [`bist`] | Power-on self-test: timer monotonicity, ring wraparound, priority selection, scratch March C- |
+//! | [`calibrate`] | WCET calibration: measured maxima plus a safety margin written back to the task table |
+//! | [`budget`] | Per-task CPU budgets with throttling (CBS-style reservation) |
+//! | [`config`] | Const kernel builder with compile-time schedulability check, `task!` / `kernel_config!` declaration macros |
+//! | [`activation`] | Bounded per-task activation queues: explicit job-loss counting, back-to-back catch-up |
+//! | [`deadlines`] | Constrained relative deadlines and explicit per-job absolute deadlines |
+//! | [`profiler`] | Per-task max execution / response time profiler, response-time histogram, CPU load averages, cycle stopwatch (DWT `CYCCNT`) |
+//! | [`replay`] | Deterministic tick record / replay for debugging, execution-sequence hash for determinism audits |
+//! | [`gantt`] | ASCII Gantt chart of a recorded schedule |
+//! | [`graph`] | Static dataflow graphs compiled onto tasks, rate-monotonic priorities and channels |
+//! | [`redundancy`] | Redundant replica pairs with output comparison, mismatch / timeout faults |
+//! | [`overload`] | Window-based overload detection with load shedding / restore |
+//! | [`mixed_criticality`] | Vestal LO / HI criticality modes: drop LO tasks on a HI overrun, AMC-rtb dual analysis, idle-instant recovery |
+//! | [`shell`] | Line-based runtime console (`ps`, `suspend`, `stats`, `trace on`) over byte rings |
+//! | [`slack`] | Slack stealing: background work in the slack of periodic tasks without deadline misses |
+//! | [`snapshot`] | Versioned kernel snapshot / restore for warm restart |
+//! | [`stats_task`](mod@stats_task) | Periodic statistics export task: `KernelStats` frames, cumulative or delta, into a user sink |
+//! | [`telemetry`] | Compact versioned wire format for `KernelStats` |
+//! | [`text`] | `ShortName` task names, truncating fixed-buffer `fmt::Write` target, ppm `Percent` display |
+//! | [`threshold`] | Preemption-threshold scheduling: per-task thresholds, dispatcher rule, response-time analysis and threshold maximization |
+//! | `timeline` | Preemptive timeline simulation charging each job its execution time (`std` feature) |
+//! | [`trace`] | 4 / 8-byte binary execution trace drained into RTT / ITM sinks, drop-and-count backpressure |
+//! | [`wheel`] | Static hierarchical timing wheel: O(1) amortized expiry of software timers and `RunQueue` task releases, next-wakeup lookup |
+//! | [`power`] | Slack-driven sleep-state policy with per-state accounting |
 //!
 //! # Feature Flags
 //!
 //! | Feature | Default | Description |
 //! |---------|---------|-------------|
-//! | `cortex-m` | no | ARM Cortex-M (M0/M4/M7) target support |
+//! | `cortex-m` | no | ARM Cortex-M (M0/M4/M7) target support; DWT `CYCCNT` behind `profiler::Stopwatch` |
+//! | `mpu` | no | Cortex-M PMSAv7 / PMSAv8 regions per task (`mpu::Mpu`), unprivileged tasks with SVC calls (`syscall`) |
+//! | `rp2040` | no | RP2040 SIO inter-core FIFO `Doorbell` (`doorbell::SioFifo`) |
 //! | `riscv` | no | RISC-V (ESP32-C3, GD32VF103) target support |
+//! | `arch-riscv` | no | RISC-V `mtime` / `mtimecmp` `TickSource` (`MTimer`) |
 //! | `esp32` | no | Xtensa ESP32/ESP32-S3 target support |
 //! | `edge` | no | ALICE-Edge task templates (1 kHz inference) |
 //! | `synth` | no | ALICE-Synth task templates (44.1 kHz audio) |
 //! | `motion` | no | ALICE-Motion task templates (10 kHz trajectory) |
+//! | `std` | no | Host build: panic isolation, wall-clock `Simulator`, `ChromeTrace` |
+//! | `hooks` | no | Context-switch enter/exit callbacks (`Kernel::set_switch_hooks`) |
+//! | `defmt` | no | Structured kernel events via `defmt` |
+//! | `log` | no | Structured kernel events via the `log` facade |
+//! | `task-table` | no | `Kernel::load_table` from a const `table::TaskSpec` table with per-row error reporting |
+//! | `codegen` | no | `codegen::generate` for `build.rs`: task table, channels and utilization report from a schedule file (implies `std`, `task-table`) |
+//! | `serde` | no | `Serialize` for `KernelStats` / `TaskStats`, `Task`, `KernelEvent`, `TraceEntry` (and `SimReport` with `std`) for host JSON reports |
+//! | `embassy` | no | `embassy-time` driver on the kernel clock, async executor in slack time |
+//! | `embedded-hal` | no | embedded-hal 1.0 `DelayNs` on the RTOS time base |
+//! | `no-float` | no | Integer (ppm) admission / schedulability math for FPU-less cores |
+//! | `static-kernel` | no | `StaticKernel`: kernel in a `static` shared with ISRs via `critical-section` |
+//! | `tick-u32` | no | Wrapping 32-bit kernel `Tick` for 8/16-bit cores (AVR, MSP430) |
+//! | `critical-section` | no | Atomics emulated in critical sections; `SpscRing` indices guarded by `critical-section` (Cortex-M0/M0+) |
 //! | `ffi` | no | C-ABI FFI for Unity/UE5 (66 functions) |
 //! | `python` | no | `PyO3` Python bindings |
 //!
@@ -59,29 +134,189 @@
 )]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(all(feature = "no-float", any(feature = "ffi", feature = "python")))]
+compile_error!(
+    "`no-float` is for bare-metal targets; the `ffi` / `python` bindings report f32 utilization"
+);
+
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+pub mod activation;
+pub mod aligned_ring;
+mod atomic;
+pub mod bist;
+pub mod block;
+pub mod broadcast;
+pub mod budget;
+pub mod calibrate;
+pub mod channel;
+#[cfg(feature = "std")]
+pub mod chrome_trace;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod config;
+pub mod deadlines;
+#[cfg(feature = "embedded-hal")]
+pub mod delay;
 pub mod dmda;
+pub mod domain;
+pub mod doorbell;
+pub mod double_buffer;
 #[cfg(feature = "edge")]
 pub mod edge_tasks;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+pub mod error;
+pub mod events;
+pub mod fault;
 #[cfg(feature = "ffi")]
 pub mod ffi;
+pub mod gantt;
+pub mod graph;
+pub mod init;
+#[cfg(any(feature = "std", test))]
+pub mod inject;
+pub mod integrity;
 pub mod kernel;
+pub mod lifecycle;
+pub mod log_task;
+pub mod mailbox;
+pub mod mixed_criticality;
 #[cfg(feature = "motion")]
 pub mod motion_tasks;
+#[cfg(feature = "mpu")]
+pub mod mpu;
+#[cfg(feature = "arch-riscv")]
+pub mod mtimer;
+pub mod overload;
+pub mod params;
+pub mod policy;
+pub mod pool;
+pub mod power;
 pub mod prelude;
+pub mod priority_ceiling;
 pub mod priority_inherit;
+pub mod profiler;
 #[cfg(feature = "python")]
 mod python;
+pub mod redundancy;
+pub mod replay;
+pub mod router;
+pub mod runqueue;
+pub mod sched_lock;
 pub mod scheduler;
+pub mod shell;
+#[cfg(feature = "std")]
+pub mod simulator;
+pub mod slack;
+pub mod snapshot;
+pub mod soft_timer;
 pub mod spsc;
+#[cfg(feature = "static-kernel")]
+pub mod static_kernel;
+pub mod stats_task;
 #[cfg(feature = "synth")]
 pub mod synth_tasks;
+#[cfg(feature = "mpu")]
+pub mod syscall;
+#[cfg(feature = "task-table")]
+pub mod table;
 pub mod task;
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod testkit;
+pub mod text;
+pub mod threshold;
+#[cfg(any(feature = "std", test))]
+pub mod timeline;
+pub mod timeout;
 pub mod timer;
+pub mod trace;
+pub mod wheel;
 
+pub use activation::ActivationQueue;
+pub use aligned_ring::{
+    Align128, Align16, Align32, Align64, AlignedConsumer, AlignedProducer, AlignedSpscRing,
+    Alignment,
+};
+pub use bist::BistFailures;
+pub use block::BlockSizer;
+pub use broadcast::{Broadcast, BroadcastReceiver, BroadcastSender, RecvError};
+pub use budget::BudgetTable;
+pub use calibrate::{CalibrationReport, WcetCalibrator};
+pub use channel::{Channel, ChannelPool, Link, Receiver, Sender, Stamped};
+#[cfg(feature = "std")]
+pub use chrome_trace::{ChromeTrace, TraceEvent};
+pub use config::{KernelBuilder, KernelConfig};
+pub use deadlines::DeadlineTracker;
+#[cfg(feature = "embedded-hal")]
+pub use delay::{Countdown, SharedTimer};
 pub use dmda::{analyze as dmda_analyze, DmdaReport, RtaResult};
-pub use kernel::{Kernel, KernelStats};
+pub use domain::TimeDomain;
+pub use doorbell::{Doorbell, DoorbellPair, SoftDoorbell};
+pub use double_buffer::{DoubleBuffer, DoubleBufferReader, DoubleBufferWriter, Front};
+pub use error::{KernelError, KernelResult};
+pub use events::KernelEvent;
+pub use fault::{BlackBox, FaultCode, FaultRecord};
+pub use gantt::Gantt;
+pub use graph::{Graph, GraphBuilder, GraphError, Node, Port};
+pub use init::InitTable;
+#[cfg(feature = "std")]
+pub use inject::{FaultInjector, FaultProfile, InjectedFaults};
+pub use integrity::TaskTableGuard;
+pub use kernel::{
+    Admission, CoreJob, InvalidTransition, Kernel, KernelState, KernelStats, SizedKernel,
+    TaskStats, TickObserver, DEFAULT_SCRATCH,
+};
+pub use lifecycle::{Lifecycle, LifecycleCallbacks, LifecycleFn};
+pub use log_task::{log_task, LogLine, LogTask};
+pub use mailbox::Mailbox;
+pub use mixed_criticality::{CritMode, Criticality, McReport, MixedCriticality};
+pub use overload::{OverloadAction, OverloadManager, ShedAction};
+pub use params::{ParamKey, ParamStore, ParamTxn, ParamValue, ParamView, MAX_PARAM_WORDS};
+pub use policy::{Edf, Rms, SchedulingPolicy};
+pub use pool::{Pool, PoolBlock};
+pub use power::{PowerManager, PowerPolicy, SleepState, SleepStats, ThresholdPolicy};
+pub use priority_ceiling::{PcpResult, PriorityCeilingManager, Resource};
 pub use priority_inherit::{PipResult, PriorityInheritTracker, PriorityResource};
-pub use scheduler::Scheduler;
-pub use spsc::SpscRing;
-pub use task::{Task, TaskFn, TaskPriority, TaskState};
-pub use timer::{Deadline, SysTimer};
+pub use profiler::{
+    CycleProfile, CycleScope, LoadAverages, ProfileClock, ResponseHistogram, Stopwatch,
+    TaskProfiler, HISTOGRAM_BINS,
+};
+pub use redundancy::{CompareFn, RedundancyMonitor, MAX_PAIRS};
+pub use replay::{Divergence, ExecHash, TickLog, TickRecord};
+pub use router::{dispatch_task, Envelope, Inboxes, Router, Side};
+pub use runqueue::RunQueue;
+pub use sched_lock::SchedulerLock;
+pub use scheduler::{
+    harmonic, liu_layland_bound_ppm, rms_schedulable, utilization_ppm, Overhead, PartitionError,
+    RmsBound, Scheduler,
+};
+pub use shell::Shell;
+#[cfg(feature = "std")]
+pub use simulator::Simulator;
+pub use slack::{available_slack_us, BackgroundFn, SlackServer};
+pub use snapshot::{snapshot_size, SnapshotError, SNAPSHOT_VERSION};
+pub use soft_timer::{SoftTimerPool, TimerCallback, MAX_SOFT_TIMERS};
+pub use spsc::{SpscConsumer, SpscProducer, SpscRing, Watermark};
+#[cfg(feature = "static-kernel")]
+pub use static_kernel::StaticKernel;
+pub use stats_task::{stats_task, StatsExport, StatsMode};
+#[cfg(feature = "task-table")]
+pub use table::{LoadError, LoadErrorKind};
+pub use task::{
+    CoreMask, Mode, ModeMask, Task, TaskFn, TaskMask, TaskPriority, TaskState, ALL_MODES, ANY_CORE,
+    MAX_CORES,
+};
+pub use telemetry::{DecodeError, MAX_STATS_LEN, STATS_WIRE_VERSION};
+#[cfg(feature = "std")]
+pub use testkit::{SimReport, TaskSetGen, TaskSpec};
+pub use text::{FmtBuf, Percent, ShortName};
+pub use threshold::PreemptionThresholds;
+#[cfg(feature = "std")]
+pub use timeline::{ExecTimeFn, TimelineSim};
+pub use timeout::{RecvTimeouts, WaitSource};
+pub use timer::{Deadline, SysTimer, Tick, TickDiff, TickSource, WrappingInstant};
+pub use trace::{TraceBuffer, TraceDecoder, TraceEntry};
+pub use wheel::{TimerWheel, MAX_WHEEL_ENTRIES};
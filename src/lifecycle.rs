@@ -0,0 +1,338 @@
+//! Task lifecycle callbacks: `on_start`, `on_stop`, `on_overrun`
+//!
+//! Drivers enable their peripheral when their task starts running and
+//! disable it when the task is suspended. A [`Lifecycle`] table holds up
+//! to three callbacks per task and calls them on state transitions seen
+//! around each observed kernel tick ([`Lifecycle::tick`], or the
+//! lifecycle as the [`TickObserver`] of
+//! [`tick_observed`](SizedKernel::tick_observed) and friends):
+//!
+//! - `on_start`: the task became runnable — on the first tick after it
+//!   was registered, or after a resume (explicit, group or mode switch);
+//!   called before the task's next job;
+//! - `on_stop`: the task was suspended (explicit, group, mode switch,
+//!   fault limit) or unregistered;
+//! - `on_overrun`: the task missed one or more deadlines during the tick.
+//!
+//! The table lives outside the task descriptors, which stay at their
+//! fixed size whether callbacks are used or not. Callbacks receive the
+//! task index and run in the context that ticks the kernel.
+//!
+//! Only observed ticks drive the callbacks: the scheduler calls nothing
+//! on `suspend`, `resume` or `unregister`, and a plain
+//! [`Kernel::tick`](crate::kernel::Kernel::tick), `tick_core` or a
+//! `StaticKernel` interrupt leaves the table unchecked. Transitions are
+//! found by comparing the task table with the last check, so a suspend
+//! and a resume between the same two checks cancel out (no callback),
+//! and several misses between two checks call `on_overrun` once.
+//!
+//! ```rust
+//! use alice_rtos::lifecycle::{Lifecycle, LifecycleCallbacks};
+//! use alice_rtos::{Kernel, TaskPriority};
+//! use core::sync::atomic::{AtomicBool, Ordering};
+//!
+//! static ADC_ON: AtomicBool = AtomicBool::new(false);
+//!
+//! fn adc_enable(_: usize) {
+//!     ADC_ON.store(true, Ordering::Relaxed);
+//! }
+//! fn adc_disable(_: usize) {
+//!     ADC_ON.store(false, Ordering::Relaxed);
+//! }
+//! fn sample(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! let adc = kernel.add_task(b"adc", sample, TaskPriority::HIGH, 1_000, 50).unwrap();
+//! let mut lifecycle = Lifecycle::new();
+//! lifecycle.set(adc, LifecycleCallbacks::new().on_start(adc_enable).on_stop(adc_disable));
+//!
+//! lifecycle.tick(&mut kernel, 0);
+//! assert!(ADC_ON.load(Ordering::Relaxed));
+//! kernel.scheduler.suspend(adc);
+//! lifecycle.tick(&mut kernel, 1_000);
+//! assert!(!ADC_ON.load(Ordering::Relaxed));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{KernelStats, SizedKernel, TickObserver};
+use crate::policy::SchedulingPolicy;
+use crate::task::{TaskMask, TaskState, MAX_TASKS};
+
+/// Lifecycle callback receiving the task index
+pub type LifecycleFn = fn(usize);
+
+/// Callbacks of one task (all optional)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LifecycleCallbacks {
+    /// Task became runnable
+    pub on_start: Option<LifecycleFn>,
+    /// Task was suspended or unregistered
+    pub on_stop: Option<LifecycleFn>,
+    /// Task missed a deadline
+    pub on_overrun: Option<LifecycleFn>,
+}
+
+impl LifecycleCallbacks {
+    /// No callbacks
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            on_start: None,
+            on_stop: None,
+            on_overrun: None,
+        }
+    }
+
+    /// Call `f` when the task becomes runnable
+    #[must_use]
+    pub const fn on_start(mut self, f: LifecycleFn) -> Self {
+        self.on_start = Some(f);
+        self
+    }
+
+    /// Call `f` when the task is suspended or unregistered
+    #[must_use]
+    pub const fn on_stop(mut self, f: LifecycleFn) -> Self {
+        self.on_stop = Some(f);
+        self
+    }
+
+    /// Call `f` when the task misses a deadline
+    #[must_use]
+    pub const fn on_overrun(mut self, f: LifecycleFn) -> Self {
+        self.on_overrun = Some(f);
+        self
+    }
+}
+
+/// Lifecycle callbacks of every task and the state they were last seen in
+pub struct Lifecycle {
+    /// Callbacks per task
+    callbacks: [LifecycleCallbacks; MAX_TASKS],
+    /// Tasks seen runnable at the last check
+    running: TaskMask,
+    /// Deadline misses seen per task at the last check
+    misses: [u32; MAX_TASKS],
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lifecycle {
+    /// No callbacks installed
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            callbacks: [LifecycleCallbacks::new(); MAX_TASKS],
+            running: 0,
+            misses: [0; MAX_TASKS],
+        }
+    }
+
+    /// Install the callbacks of task `idx` (false if out of range)
+    pub fn set(&mut self, idx: usize, callbacks: LifecycleCallbacks) -> bool {
+        match self.callbacks.get_mut(idx) {
+            Some(slot) => {
+                *slot = callbacks;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Callbacks of task `idx`
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<&LifecycleCallbacks> {
+        self.callbacks.get(idx)
+    }
+
+    /// Tick the kernel, calling the callbacks of the transitions around it
+    ///
+    /// Transitions made between ticks (e.g. a resume from application
+    /// code) are handled before the kernel ticks, so `on_start` runs
+    /// before the resumed task's job; those made by the tick itself
+    /// (fault-limit suspension, deadline misses) right after it.
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        delta_us: u64,
+    ) -> Option<usize> {
+        kernel.tick_observed(delta_us, self)
+    }
+
+    /// Run for `total_us` in `tick_us` steps (see [`Kernel::run_for`](crate::kernel::Kernel::run_for))
+    pub fn run_for<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        total_us: u64,
+        tick_us: u64,
+    ) -> KernelStats {
+        kernel.run_for_observed(total_us, tick_us, self)
+    }
+
+    /// Call the callbacks of the transitions since the last check
+    pub fn sync<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &SizedKernel<SCRATCH, P>,
+    ) {
+        for (idx, callbacks) in self.callbacks.iter().enumerate() {
+            let bit: TaskMask = 1 << idx;
+            let task = kernel.scheduler.get_task(idx);
+            let running = task.is_some_and(|t| t.is_active() && t.state != TaskState::Suspended);
+            let misses = task.map_or(0, |t| t.deadline_misses);
+
+            match (self.running & bit != 0, running) {
+                (false, true) => {
+                    self.running |= bit;
+                    if let Some(f) = callbacks.on_start {
+                        f(idx);
+                    }
+                }
+                (true, false) => {
+                    self.running &= !bit;
+                    if let Some(f) = callbacks.on_stop {
+                        f(idx);
+                    }
+                }
+                _ => {}
+            }
+            // 再登録で減った場合は数え直し
+            if misses > self.misses[idx] {
+                if let Some(f) = callbacks.on_overrun {
+                    f(idx);
+                }
+            }
+            self.misses[idx] = misses;
+        }
+    }
+}
+
+/// Checks the transitions before each tick and after it
+impl TickObserver for Lifecycle {
+    fn before_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) {
+        self.sync(kernel);
+    }
+
+    fn after_tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        _executed: Option<usize>,
+    ) {
+        self.sync(kernel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::{AtomicU32, Ordering};
+    use crate::deadlines::DeadlineTracker;
+    use crate::kernel::Kernel;
+    use crate::task::TaskPriority;
+
+    static STARTS: AtomicU32 = AtomicU32::new(0);
+    static STOPS: AtomicU32 = AtomicU32::new(0);
+    static OVERRUNS: AtomicU32 = AtomicU32::new(0);
+
+    fn started(idx: usize) {
+        assert_eq!(idx, 1);
+        STARTS.fetch_add(1, Ordering::Relaxed);
+    }
+    fn stopped(_: usize) {
+        STOPS.fetch_add(1, Ordering::Relaxed);
+    }
+    fn overran(_: usize) {
+        OVERRUNS.fetch_add(1, Ordering::Relaxed);
+    }
+    fn noop(_: &mut [u8]) {}
+
+    static OBSERVED_STARTS: AtomicU32 = AtomicU32::new(0);
+
+    fn observed_start(_: usize) {
+        OBSERVED_STARTS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_start_stop_on_suspend_resume_and_modes() {
+        let mut k = Kernel::testing();
+        k.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 10);
+        let drv = k
+            .add_task(b"drv", noop, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let mut lc = Lifecycle::new();
+        assert!(lc.set(
+            drv,
+            LifecycleCallbacks::new().on_start(started).on_stop(stopped)
+        ));
+        assert!(!lc.set(MAX_TASKS, LifecycleCallbacks::new()));
+
+        lc.tick(&mut k, 0);
+        assert_eq!(STARTS.load(Ordering::Relaxed), 1);
+        // 変化がなければ呼ばない
+        lc.tick(&mut k, 500);
+        assert_eq!(STARTS.load(Ordering::Relaxed), 1);
+
+        k.scheduler.suspend(drv);
+        lc.tick(&mut k, 500);
+        assert_eq!(STOPS.load(Ordering::Relaxed), 1);
+        k.scheduler.resume(drv);
+        lc.tick(&mut k, 500);
+        assert_eq!(STARTS.load(Ordering::Relaxed), 2);
+
+        // 登録解除も停止扱い
+        k.scheduler.try_unregister(drv).unwrap();
+        lc.sync(&k);
+        assert_eq!(STOPS.load(Ordering::Relaxed), 2);
+        assert!(lc.get(drv).unwrap().on_start.is_some());
+        assert!(lc.get(MAX_TASKS).is_none());
+    }
+
+    #[test]
+    fn test_overrun_on_deadline_miss() {
+        let mut k = Kernel::testing();
+        let idx = k
+            .add_task(b"late", noop, TaskPriority::NORMAL, 100, 10)
+            .unwrap();
+        let mut lc = Lifecycle::new();
+        lc.set(idx, LifecycleCallbacks::new().on_overrun(overran));
+        lc.tick(&mut k, 0);
+        lc.tick(&mut k, 50);
+        assert_eq!(OVERRUNS.load(Ordering::Relaxed), 0);
+        // 3 周期ぶん飛ばす: 取りこぼしは 1 ティックにつき 1 回の通知
+        lc.tick(&mut k, 300);
+        assert_eq!(OVERRUNS.load(Ordering::Relaxed), 1);
+        assert!(k.scheduler.get_task(idx).unwrap().deadline_misses > 0);
+        lc.run_for(&mut k, 1_000, 50);
+        assert_eq!(OVERRUNS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_observer_with_tickless_run_and_other_managers() {
+        let mut k = Kernel::testing();
+        let drv = k
+            .add_task(b"drv", noop, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        let mut lc = Lifecycle::new();
+        lc.set(drv, LifecycleCallbacks::new().on_start(observed_start));
+        let mut tracker = DeadlineTracker::new();
+        assert!(tracker.set_relative(&k, drv, 500));
+
+        // 観測されない tick では呼ばない
+        assert_eq!(k.tick(0), Some(drv));
+        assert_eq!(OBSERVED_STARTS.load(Ordering::Relaxed), 0);
+        // 1 回の tick を 2 つの管理器で共有
+        let stats = k.run_tickless_observed(5_000, &mut (&mut lc, &mut tracker));
+        assert_eq!(stats.total_us, 5_000);
+        assert_eq!(OBSERVED_STARTS.load(Ordering::Relaxed), 1);
+        assert_eq!(tracker.misses(drv), 0);
+        assert_eq!(tracker.absolute(drv), None);
+        assert_eq!(k.scheduler.get_task(drv).unwrap().exec_count, 6);
+    }
+}
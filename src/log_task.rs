@@ -0,0 +1,294 @@
+//! Rate-limited logging task with static line buffers
+//!
+//! Each task formats at most one pending line into its own slot of a
+//! static [`LogTask`] (`write!` through [`LogLine`]); a low-priority task
+//! built with [`log_task`] calls [`LogTask::drain`], which hands completed
+//! lines to a user `fn(&[u8])` sink (UART, RTT, …). No allocation, and the
+//! periodic tasks never wait on the sink.
+//!
+//! Rate limiting comes from the drain task's period plus a per-run line
+//! limit ([`LogTask::with_lines_per_run`]); slots are visited round-robin
+//! so one chatty task cannot starve the others. A line written while the
+//! previous one of the same slot is still pending is dropped and counted.
+//!
+//! ```ignore
+//! static LOG: LogTask<4, 64> = LogTask::new(uart_write).with_lines_per_run(2);
+//!
+//! fn drain(_: &mut [u8]) {
+//!     LOG.drain();
+//! }
+//!
+//! fn control(_: &mut [u8]) {
+//!     if let Some(mut line) = LOG.line(0) {
+//!         let _ = write!(line, "err={}", 3);
+//!     }
+//! }
+//!
+//! kernel.scheduler.register(log_task(drain, 10_000, 200));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::UnsafeCell;
+use core::fmt;
+
+use crate::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use crate::task::{Task, TaskFn, TaskPriority};
+
+/// Priority of the drain task
+pub const LOG_PRIORITY: TaskPriority = TaskPriority::LOW;
+
+/// Slot states
+const FREE: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+
+/// Drain task: `drain` runs every `period_us` at [`LOG_PRIORITY`]
+///
+/// `wcet_us` must cover `lines_per_run` sink calls.
+pub fn log_task(drain: TaskFn, period_us: u32, wcet_us: u32) -> Task {
+    Task::new(b"log", drain, LOG_PRIORITY, period_us, wcet_us)
+}
+
+/// One line buffer
+struct Slot<const LEN: usize> {
+    /// `FREE` / `WRITING` / `READY`
+    state: AtomicU8,
+    /// Bytes used
+    len: UnsafeCell<usize>,
+    /// Line text
+    buf: UnsafeCell<[u8; LEN]>,
+}
+
+/// `SLOTS` static line buffers of `LEN` bytes drained into a sink
+pub struct LogTask<const SLOTS: usize, const LEN: usize> {
+    /// Per-task line buffers
+    slots: [Slot<LEN>; SLOTS],
+    /// Line output
+    sink: fn(&[u8]),
+    /// Lines emitted per `drain` (0 = unlimited)
+    lines_per_run: usize,
+    /// Slot the next `drain` starts at
+    cursor: AtomicUsize,
+    /// Lines lost because the slot was still pending
+    dropped: AtomicU32,
+}
+
+// SAFETY: a slot's buffer is only written by the unique `LogLine` holding
+// it in `WRITING` (claimed by compare-exchange) and only read by `drain`
+// after observing `READY` with Acquire.
+unsafe impl<const SLOTS: usize, const LEN: usize> Sync for LogTask<SLOTS, LEN> {}
+
+impl<const SLOTS: usize, const LEN: usize> LogTask<SLOTS, LEN> {
+    /// Empty buffers draining into `sink` (usable in `static`s)
+    #[must_use]
+    pub const fn new(sink: fn(&[u8])) -> Self {
+        Self {
+            slots: [const {
+                Slot {
+                    state: AtomicU8::new(FREE),
+                    len: UnsafeCell::new(0),
+                    buf: UnsafeCell::new([0; LEN]),
+                }
+            }; SLOTS],
+            sink,
+            lines_per_run: 0,
+            cursor: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// Emit at most `lines` lines per [`drain`](Self::drain)
+    #[must_use]
+    pub const fn with_lines_per_run(mut self, lines: usize) -> Self {
+        self.lines_per_run = lines;
+        self
+    }
+
+    /// Start a line in `slot` (normally the task's own index)
+    ///
+    /// The line is published when the returned writer is dropped. Returns
+    /// `None` (and counts a drop) while the slot's previous line has not
+    /// been drained yet, or for an out-of-range slot.
+    pub fn line(&self, slot: usize) -> Option<LogLine<'_, LEN>> {
+        let s = self.slots.get(slot)?;
+        if s.state
+            .compare_exchange(FREE, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        // SAFETY: WRITING を取得したのでこのスロットは排他
+        unsafe { *s.len.get() = 0 };
+        Some(LogLine { slot: s })
+    }
+
+    /// Pass pending lines to the sink; returns the number emitted
+    ///
+    /// Call from the drain task.
+    pub fn drain(&self) -> usize {
+        let limit = if self.lines_per_run == 0 {
+            SLOTS
+        } else {
+            self.lines_per_run
+        };
+        let start = self.cursor.load(Ordering::Relaxed);
+        let mut emitted = 0;
+        let mut next = start;
+        for k in 0..SLOTS {
+            if emitted == limit {
+                break;
+            }
+            let i = (start + k) % SLOTS;
+            let s = &self.slots[i];
+            if s.state.load(Ordering::Acquire) != READY {
+                continue;
+            }
+            // SAFETY: READY のスロットは書き手がいない
+            let line = unsafe { &(&*s.buf.get())[..*s.len.get()] };
+            (self.sink)(line);
+            s.state.store(FREE, Ordering::Release);
+            emitted += 1;
+            next = (i + 1) % SLOTS;
+        }
+        self.cursor.store(next, Ordering::Relaxed);
+        emitted
+    }
+
+    /// Lines waiting for the next drain
+    pub fn pending(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|s| s.state.load(Ordering::Relaxed) == READY)
+            .count()
+    }
+
+    /// Lines dropped because their slot was still pending
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Line being formatted into a [`LogTask`] slot
+///
+/// Implements `core::fmt::Write`; text beyond the slot size is truncated.
+pub struct LogLine<'a, const LEN: usize> {
+    slot: &'a Slot<LEN>,
+}
+
+impl<const LEN: usize> fmt::Write for LogLine<'_, LEN> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // SAFETY: WRITING の間はこの LogLine だけがバッファに触る
+        let (len, buf) = unsafe { (&mut *self.slot.len.get(), &mut *self.slot.buf.get()) };
+        let n = s.len().min(LEN - *len);
+        buf[*len..*len + n].copy_from_slice(&s.as_bytes()[..n]);
+        *len += n;
+        Ok(())
+    }
+}
+
+impl<const LEN: usize> Drop for LogLine<'_, LEN> {
+    fn drop(&mut self) {
+        self.slot.state.store(READY, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::kernel::Kernel;
+    use core::fmt::Write;
+    use std::string::String;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_line_published_on_drop() {
+        static OUT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        fn sink(line: &[u8]) {
+            OUT.lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(line).into_owned());
+        }
+        let log: LogTask<2, 16> = LogTask::new(sink);
+        {
+            let mut line = log.line(1).unwrap();
+            write!(line, "t={} ok", 42).unwrap();
+            // 公開前は drain されない
+            assert_eq!(log.drain(), 0);
+        }
+        assert_eq!(log.pending(), 1);
+        // 前の行が未出力ならドロップ
+        assert!(log.line(1).is_none());
+        assert_eq!(log.dropped(), 1);
+        assert!(log.line(2).is_none());
+
+        assert_eq!(log.drain(), 1);
+        assert_eq!(*OUT.lock().unwrap(), ["t=42 ok"]);
+        assert!(log.line(1).is_some());
+    }
+
+    #[test]
+    fn test_long_line_truncated() {
+        fn sink(line: &[u8]) {
+            assert_eq!(line, b"abcdefgh");
+        }
+        let log: LogTask<1, 8> = LogTask::new(sink);
+        write!(log.line(0).unwrap(), "abcdefghijkl").unwrap();
+        assert_eq!(log.drain(), 1);
+    }
+
+    #[test]
+    fn test_rate_limited_round_robin() {
+        static OUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+        fn sink(line: &[u8]) {
+            OUT.lock().unwrap().push(line[0]);
+        }
+        let log: LogTask<3, 4> = LogTask::new(sink).with_lines_per_run(2);
+        for round in 0..2u8 {
+            for slot in 0..3 {
+                if let Some(mut line) = log.line(slot) {
+                    write!(line, "{}", slot as u8 + round * 3).unwrap();
+                }
+            }
+            // 1 回の drain は 2 行まで、次回は続きのスロットから
+            assert_eq!(log.drain(), 2);
+        }
+        assert_eq!(log.drain(), 1);
+        assert_eq!(log.drain(), 0);
+        // 2 巡目の slot 2 は前の行が未出力のため捨てられる
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(*OUT.lock().unwrap(), [b'0', b'1', b'2', b'3', b'4']);
+    }
+
+    #[test]
+    fn test_drain_task_in_kernel() {
+        static LOG: LogTask<2, 32> = LogTask::new(sink).with_lines_per_run(1);
+        static LINES: AtomicU32 = AtomicU32::new(0);
+        fn sink(_: &[u8]) {
+            LINES.fetch_add(1, Ordering::Relaxed);
+        }
+        fn drain(_: &mut [u8]) {
+            LOG.drain();
+        }
+        fn sensor(_: &mut [u8]) {
+            if let Some(mut line) = LOG.line(0) {
+                let _ = write!(line, "sensor");
+            }
+        }
+
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"sensor", sensor, TaskPriority::HIGH, 1_000, 50);
+        kernel
+            .scheduler
+            .register(log_task(drain, 2_000, 100))
+            .unwrap();
+        kernel.run_for(10_000, 500);
+        // 2 ms ごとに 1 行: 5 回の drain
+        assert_eq!(LINES.load(Ordering::Relaxed), 5);
+        assert!(LOG.dropped() > 0);
+    }
+}
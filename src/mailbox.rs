@@ -0,0 +1,200 @@
+//! Latest-value mailbox (seqlock)
+//!
+//! A single-slot `Mailbox<T>` for state such as sensor readings: the
+//! producer overwrites the slot at any time and never blocks, and readers
+//! always get the newest *consistent* value instead of draining a FIFO.
+//!
+//! Writes bump a sequence counter to an odd value, store the data and
+//! bump it back to even. A reader retries whenever the counter was odd or
+//! changed while it copied the data. Only atomic loads and stores are
+//! used, so the mailbox also works on cores without CAS (Cortex-M0+).
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{fence, AtomicU32, Ordering};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+/// Single-slot overwrite channel with seqlock protection
+///
+/// Exactly one producer may call [`write`](Self::write); any number of
+/// readers may call [`read`](Self::read) concurrently.
+pub struct Mailbox<T> {
+    /// Latest value (initialized once `seq > 0`)
+    slot: UnsafeCell<MaybeUninit<T>>,
+    /// Write sequence: odd while a write is in progress, 0 = never written
+    seq: AtomicU32,
+}
+
+// SAFETY: readers copy the slot with a volatile read and discard the copy
+// unless the sequence was even and unchanged around it, so a torn value is
+// never returned. `T: Copy` means a discarded copy needs no drop.
+unsafe impl<T: Copy + Send> Sync for Mailbox<T> {}
+
+impl<T: Copy> Default for Mailbox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy> Mailbox<T> {
+    /// Empty mailbox (usable in `static` initializers)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slot: UnsafeCell::new(MaybeUninit::uninit()),
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Publish `value`, overwriting the previous one (producer side)
+    ///
+    /// Never blocks. Must only be called from a single producer.
+    pub fn write(&self, value: T) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        // SAFETY: the only writer; concurrent readers detect the odd
+        // sequence and discard what they copied.
+        unsafe {
+            core::ptr::write_volatile(self.slot.get(), MaybeUninit::new(value));
+        }
+        // Skip 0 on wrap-around: it means "never written"
+        self.seq
+            .store(seq.wrapping_add(2).max(2), Ordering::Release);
+    }
+
+    /// Newest value (None if nothing was ever written)
+    ///
+    /// Retries while a write is in progress.
+    pub fn read(&self) -> Option<T> {
+        self.read_with_seq().map(|(value, _)| value)
+    }
+
+    /// Newest value if it was published after sequence `*seen`
+    ///
+    /// Updates `*seen`; start with 0 to accept the first value.
+    pub fn read_newer(&self, seen: &mut u32) -> Option<T> {
+        if self.seq.load(Ordering::Acquire) == *seen {
+            return None;
+        }
+        let (value, seq) = self.read_with_seq()?;
+        *seen = seq;
+        Some(value)
+    }
+
+    /// Current write sequence (even; advances by 2 per write)
+    pub fn sequence(&self) -> u32 {
+        self.seq.load(Ordering::Acquire) & !1
+    }
+
+    /// Has a value ever been written?
+    pub fn is_empty(&self) -> bool {
+        self.seq.load(Ordering::Acquire) == 0
+    }
+
+    /// Consistent copy of the slot with the sequence it belongs to
+    fn read_with_seq(&self) -> Option<(T, u32)> {
+        loop {
+            let before = self.seq.load(Ordering::Acquire);
+            if before == 0 {
+                return None;
+            }
+            if before & 1 != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+            // SAFETY: seq > 0 means the slot was initialized; a copy torn
+            // by a concurrent write is rejected by the check below.
+            let value = unsafe { core::ptr::read_volatile(self.slot.get()) };
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == before {
+                // SAFETY: see above.
+                return Some((unsafe { value.assume_init() }, before));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_mailbox() {
+        let mb = Mailbox::<u32>::new();
+        assert!(mb.is_empty());
+        assert_eq!(mb.read(), None);
+        assert_eq!(mb.sequence(), 0);
+    }
+
+    #[test]
+    fn test_overwrite_keeps_latest() {
+        let mb = Mailbox::new();
+        mb.write(1u32);
+        mb.write(2);
+        mb.write(3);
+        assert_eq!(mb.read(), Some(3));
+        // 読み出しで値は消費されない
+        assert_eq!(mb.read(), Some(3));
+        assert_eq!(mb.sequence(), 6);
+    }
+
+    #[test]
+    fn test_read_newer() {
+        let mb = Mailbox::new();
+        let mut seen = 0;
+        assert_eq!(mb.read_newer(&mut seen), None);
+        mb.write((1.5f32, 7u8));
+        assert_eq!(mb.read_newer(&mut seen), Some((1.5, 7)));
+        assert_eq!(mb.read_newer(&mut seen), None);
+        mb.write((2.5, 8));
+        assert_eq!(mb.read_newer(&mut seen), Some((2.5, 8)));
+    }
+
+    #[test]
+    fn test_sequence_wrap_skips_empty() {
+        let mb = Mailbox::new();
+        mb.seq.store(u32::MAX - 1, Ordering::Relaxed);
+        mb.write(9u8);
+        assert!(!mb.is_empty());
+        assert_eq!(mb.sequence(), 2);
+        assert_eq!(mb.read(), Some(9));
+    }
+
+    #[test]
+    fn test_static_mailbox() {
+        static STATE: Mailbox<[i16; 3]> = Mailbox::new();
+        STATE.write([1, -2, 3]);
+        assert_eq!(STATE.read(), Some([1, -2, 3]));
+    }
+
+    #[test]
+    fn test_concurrent_reads_never_torn() {
+        extern crate std;
+        use std::thread;
+
+        let mb = Mailbox::<[u64; 8]>::new();
+        mb.write([0; 8]);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=20_000u64 {
+                    mb.write([i; 8]);
+                }
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    for _ in 0..20_000 {
+                        let v = mb.read().unwrap();
+                        // 全要素が同一書き込み由来 (ちぎれ無し)
+                        assert!(v.iter().all(|&x| x == v[0]));
+                        assert!(v[0] >= last);
+                        last = v[0];
+                    }
+                });
+            }
+        });
+        assert_eq!(mb.read(), Some([20_000; 8]));
+    }
+}
@@ -0,0 +1,499 @@
+//! Mixed-criticality scheduling (Vestal model, LO / HI modes)
+//!
+//! Every task has a LO-criticality WCET — its declared
+//! [`Task::wcet_us`](crate::task::Task::wcet_us) — and HI-criticality
+//! tasks additionally a larger, certification-grade HI WCET. The system
+//! starts in [`CritMode::Lo`]. As soon as a HI task runs longer than its
+//! LO WCET, [`MixedCriticality`] switches to [`CritMode::Hi`] and drops
+//! (suspends) every LO-criticality task, so the HI tasks get the
+//! processor time their HI WCETs need. At the first idle instant after
+//! the switch — and once the optional minimum HI dwell has passed — the
+//! dropped tasks are resumed and the system is back in LO mode.
+//!
+//! [`MixedCriticality::analyze`] checks both modes and the switch between
+//! them with AMC-rtb response-time analysis (Baruah, Burns & Davis 2011):
+//!
+//! - LO mode: `R_i = C_i(LO) + Σ hp(i) ⌈R_i / T_j⌉ C_j(LO)` for all tasks;
+//! - HI mode (HI tasks only):
+//!   `R*_i = C_i(HI) + Σ hpH(i) ⌈R*_i / T_j⌉ C_j(HI) + Σ hpL(i) ⌈R_i / T_k⌉ C_k(LO)`,
+//!   the last term being the LO work released before the switch.
+//!
+//! Execution times come from an optional µs clock read around each tick
+//! (like [`BudgetTable`](crate::budget::BudgetTable)) or are reported with
+//! [`MixedCriticality::charge`]. Dropped and resumed LO tasks are reported
+//! as [`KernelEvent::TaskShed`] / [`KernelEvent::TaskRestored`].
+//!
+//! Author: Moroya Sakamoto
+
+use crate::events::{emit, KernelEvent};
+use crate::kernel::Kernel;
+use crate::profiler::ProfileClock;
+use crate::task::{Task, TaskMask, TaskState, MAX_TASKS};
+
+/// Criticality level of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Criticality {
+    /// Dropped in HI mode
+    #[default]
+    Lo,
+    /// Guaranteed with its HI WCET in both modes
+    Hi,
+}
+
+/// System criticality mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CritMode {
+    /// Every task runs, budgeted with its LO WCET
+    #[default]
+    Lo,
+    /// Only HI-criticality tasks run
+    Hi,
+}
+
+/// Worst-case response times of one task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McResponse {
+    /// Response time in LO mode (µs)
+    pub lo_us: u32,
+    /// Response time across and after a switch to HI mode (HI tasks, µs)
+    pub hi_us: Option<u32>,
+    /// Deadline (= period, µs)
+    pub deadline_us: u32,
+}
+
+/// Result of the dual-mode analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McReport {
+    /// Response times per task slot (None = inactive slot)
+    pub tasks: [Option<McResponse>; MAX_TASKS],
+    /// Every task meets its deadline in LO mode
+    pub lo_ok: bool,
+    /// Every HI task meets its deadline across the switch and in HI mode
+    pub hi_ok: bool,
+}
+
+impl McReport {
+    /// Schedulable in both modes and across the switch?
+    #[must_use]
+    pub const fn schedulable(&self) -> bool {
+        self.lo_ok && self.hi_ok
+    }
+}
+
+/// LO / HI mode manager driving a [`Kernel`]
+pub struct MixedCriticality {
+    /// Execution-time clock (None = reported through `charge`)
+    clock: Option<ProfileClock>,
+    /// HI-criticality tasks (bit i = task i)
+    hi: TaskMask,
+    /// HI WCET per task slot (µs)
+    wcet_hi: [u32; MAX_TASKS],
+    /// Current mode
+    mode: CritMode,
+    /// LO tasks suspended by the switch to HI mode
+    dropped: TaskMask,
+    /// Time of the last switch to HI mode (µs)
+    hi_since: u64,
+    /// Minimum time in HI mode before recovery (µs)
+    min_hi_us: u64,
+    /// Switches to HI mode so far
+    switches: u32,
+    /// Jobs that exceeded the WCET of their criticality level
+    overruns: u32,
+}
+
+impl Default for MixedCriticality {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MixedCriticality {
+    /// Manager with every task LO-critical, fed through [`charge`](Self::charge)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            clock: None,
+            hi: 0,
+            wcet_hi: [0; MAX_TASKS],
+            mode: CritMode::Lo,
+            dropped: 0,
+            hi_since: 0,
+            min_hi_us: 0,
+            switches: 0,
+            overruns: 0,
+        }
+    }
+
+    /// Manager measuring each job with `clock` (µs)
+    #[must_use]
+    pub const fn with_clock(clock: ProfileClock) -> Self {
+        let mut mc = Self::new();
+        mc.clock = Some(clock);
+        mc
+    }
+
+    /// Stay in HI mode at least `min_hi_us` before recovering
+    #[must_use]
+    pub const fn with_min_hi_dwell(mut self, min_hi_us: u64) -> Self {
+        self.min_hi_us = min_hi_us;
+        self
+    }
+
+    /// Make task `idx` HI-critical with a HI WCET of `wcet_hi_us`
+    ///
+    /// The task's declared WCET is its LO WCET, so `wcet_hi_us` must not
+    /// be smaller. Returns false for an inactive slot or a smaller HI WCET.
+    pub fn set_hi(&mut self, kernel: &Kernel, idx: usize, wcet_hi_us: u32) -> bool {
+        let Some(task) = kernel.scheduler.get_task(idx) else {
+            return false;
+        };
+        if !task.is_active() || wcet_hi_us < task.wcet_us {
+            return false;
+        }
+        self.hi |= 1 << idx;
+        self.wcet_hi[idx] = wcet_hi_us;
+        true
+    }
+
+    /// Make task `idx` LO-critical again
+    pub fn set_lo(&mut self, idx: usize) {
+        if idx < MAX_TASKS {
+            self.hi &= !(1 << idx);
+        }
+    }
+
+    /// Criticality of task `idx`
+    #[must_use]
+    pub const fn criticality(&self, idx: usize) -> Criticality {
+        if idx < MAX_TASKS && self.hi & (1 << idx) != 0 {
+            Criticality::Hi
+        } else {
+            Criticality::Lo
+        }
+    }
+
+    /// Current mode
+    #[must_use]
+    pub const fn mode(&self) -> CritMode {
+        self.mode
+    }
+
+    /// LO tasks currently dropped
+    #[must_use]
+    pub const fn dropped_mask(&self) -> TaskMask {
+        self.dropped
+    }
+
+    /// Switches to HI mode so far
+    #[must_use]
+    pub const fn switches(&self) -> u32 {
+        self.switches
+    }
+
+    /// Jobs that ran past the WCET of their own criticality level
+    ///
+    /// A LO task over its (only) WCET, or a HI task over its HI WCET:
+    /// both are outside the model the analysis certified.
+    #[must_use]
+    pub const fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Tick the kernel, account the executed job and recover when idle
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let start = self.clock.map(|clock| clock());
+        let executed = kernel.tick(delta_us);
+        match (executed, start, self.clock) {
+            (Some(idx), Some(start), Some(clock)) => {
+                let used = clock().saturating_sub(start).min(u64::from(u32::MAX)) as u32;
+                self.charge(kernel, idx, used);
+            }
+            (None, _, _) => self.recover_if_idle(kernel),
+            _ => {}
+        }
+        executed
+    }
+
+    /// Account `used_us` of execution to the last job of `idx`
+    ///
+    /// Switches to HI mode (and drops the LO tasks) if a HI task ran
+    /// longer than its LO WCET. Returns true on such a switch.
+    pub fn charge(&mut self, kernel: &mut Kernel, idx: usize, used_us: u32) -> bool {
+        let Some(task) = kernel.scheduler.get_task(idx) else {
+            return false;
+        };
+        let hi = self.criticality(idx) == Criticality::Hi;
+        let budget = if hi && self.mode == CritMode::Hi {
+            self.wcet_hi[idx]
+        } else {
+            task.wcet_us
+        };
+        if used_us <= budget {
+            return false;
+        }
+        if !hi || self.mode == CritMode::Hi || used_us > self.wcet_hi[idx] {
+            self.overruns += 1;
+        }
+        if hi && self.mode == CritMode::Lo {
+            self.switch_to_hi(kernel);
+            return true;
+        }
+        false
+    }
+
+    /// Switch to HI mode now, dropping every LO-criticality task
+    pub fn switch_to_hi(&mut self, kernel: &mut Kernel) {
+        if self.mode == CritMode::Hi {
+            return;
+        }
+        self.mode = CritMode::Hi;
+        self.hi_since = kernel.timer.now_us();
+        self.switches += 1;
+        for idx in 0..kernel.scheduler.task_slots() {
+            let Some(task) = kernel.scheduler.get_task(idx) else {
+                continue;
+            };
+            if self.hi & (1 << idx) != 0
+                || matches!(task.state, TaskState::Inactive | TaskState::Suspended)
+            {
+                continue;
+            }
+            kernel.scheduler.suspend(idx);
+            self.dropped |= 1 << idx;
+            emit(KernelEvent::TaskShed { idx: idx as u8 });
+        }
+    }
+
+    /// Return to LO mode now, resuming the dropped tasks
+    pub fn recover(&mut self, kernel: &mut Kernel) {
+        if self.mode == CritMode::Lo {
+            return;
+        }
+        self.mode = CritMode::Lo;
+        for idx in 0..MAX_TASKS {
+            if self.dropped & (1 << idx) != 0 {
+                // 削除済みのスロットは resume しても何も起きない
+                kernel.scheduler.resume(idx);
+                emit(KernelEvent::TaskRestored { idx: idx as u8 });
+            }
+        }
+        self.dropped = 0;
+    }
+
+    /// Recover at an idle instant once the minimum HI dwell has passed
+    fn recover_if_idle(&mut self, kernel: &mut Kernel) {
+        if self.mode == CritMode::Hi
+            && kernel.scheduler.ready_mask() == 0
+            && kernel.timer.now_us() - self.hi_since >= self.min_hi_us
+        {
+            self.recover(kernel);
+        }
+    }
+
+    /// Dual-mode AMC-rtb response-time analysis of the kernel's task set
+    #[must_use]
+    pub fn analyze(&self, kernel: &Kernel) -> McReport {
+        let tasks: [Option<&Task>; MAX_TASKS] =
+            core::array::from_fn(|idx| kernel.scheduler.get_task(idx).filter(|t| t.is_active()));
+        let mut report = McReport {
+            tasks: [None; MAX_TASKS],
+            lo_ok: true,
+            hi_ok: true,
+        };
+        for (i, task) in tasks.iter().enumerate() {
+            let Some(task) = task else {
+                continue;
+            };
+            let deadline = task.period_us;
+            // hp(i): 優先度が高い (同じなら若い番号の) タスク
+            let hp = |j: usize, t: &Task| {
+                j != i && (t.priority < task.priority || (t.priority == task.priority && j < i))
+            };
+
+            let lo = response_time(task.wcet_us, deadline, |r| {
+                interference(&tasks, r, |j, t| hp(j, t).then_some(t.wcet_us))
+            });
+            let hi = (self.hi & (1 << i) != 0).then(|| {
+                // 切替前に解放された LO タスクの分は R_LO で打ち切る
+                let carried = interference(&tasks, lo, |j, t| {
+                    (hp(j, t) && self.hi & (1 << j) == 0).then_some(t.wcet_us)
+                });
+                response_time(self.wcet_hi[i], deadline, |r| {
+                    carried
+                        + interference(&tasks, r, |j, t| {
+                            (hp(j, t) && self.hi & (1 << j) != 0).then_some(self.wcet_hi[j])
+                        })
+                })
+            });
+
+            report.lo_ok &= lo <= deadline;
+            report.hi_ok &= hi.is_none_or(|r| r <= deadline);
+            report.tasks[i] = Some(McResponse {
+                lo_us: lo,
+                hi_us: hi,
+                deadline_us: deadline,
+            });
+        }
+        report
+    }
+}
+
+/// Σ ⌈window / T_j⌉ × C_j over the tasks `cost` selects
+fn interference(
+    tasks: &[Option<&Task>; MAX_TASKS],
+    window_us: u32,
+    cost: impl Fn(usize, &Task) -> Option<u32>,
+) -> u64 {
+    tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(j, t)| t.map(|t| (j, t)))
+        .filter(|(_, t)| t.period_us > 0)
+        .filter_map(|(j, t)| cost(j, t).map(|c| (t.period_us, c)))
+        .map(|(period, c)| u64::from(window_us).div_ceil(u64::from(period)) * u64::from(c))
+        .sum()
+}
+
+/// Fixed point of `R = own + interference(R)`, abandoned past `deadline_us`
+fn response_time(own_us: u32, deadline_us: u32, interference: impl Fn(u32) -> u64) -> u32 {
+    let mut r = own_us;
+    loop {
+        let next = (u64::from(own_us) + interference(r)).min(u64::from(u32::MAX)) as u32;
+        if next == r || next > deadline_us {
+            return next;
+        }
+        r = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn noop(_: &mut [u8]) {}
+
+    /// HI 制御 (LO 200 / HI 400) + LO 表示 2 つ
+    fn setup() -> (Kernel, MixedCriticality) {
+        let mut k = Kernel::testing();
+        k.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 200);
+        k.add_task(b"ui", noop, TaskPriority::NORMAL, 1_000, 300);
+        k.add_task(b"log", noop, TaskPriority::LOW, 2_000, 200);
+        let mut mc = MixedCriticality::new();
+        assert!(mc.set_hi(&k, 0, 400));
+        (k, mc)
+    }
+
+    #[test]
+    fn test_set_hi_validates() {
+        let (k, mut mc) = setup();
+        // HI WCET は LO WCET 以上
+        assert!(!mc.set_hi(&k, 1, 100));
+        assert!(!mc.set_hi(&k, 7, 400));
+        assert_eq!(mc.criticality(0), Criticality::Hi);
+        assert_eq!(mc.criticality(1), Criticality::Lo);
+        mc.set_lo(0);
+        assert_eq!(mc.criticality(0), Criticality::Lo);
+    }
+
+    #[test]
+    fn test_hi_overrun_drops_lo_tasks() {
+        let (mut k, mut mc) = setup();
+        assert_eq!(mc.tick(&mut k, 0), Some(0));
+        // LO WCET 以内なら LO モードのまま
+        assert!(!mc.charge(&mut k, 0, 200));
+        assert_eq!(mc.mode(), CritMode::Lo);
+        // LO タスクの超過は数えるだけ
+        assert!(!mc.charge(&mut k, 1, 350));
+        assert_eq!((mc.mode(), mc.overruns()), (CritMode::Lo, 1));
+
+        assert!(mc.charge(&mut k, 0, 350));
+        assert_eq!(mc.mode(), CritMode::Hi);
+        assert_eq!(mc.dropped_mask(), 0b110);
+        assert_eq!(mc.switches(), 1);
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Suspended);
+        // HI WCET 以内は想定内
+        assert_eq!(mc.overruns(), 1);
+        // HI モードでは LO タスクは動かない
+        assert_eq!(mc.tick(&mut k, 0), None);
+    }
+
+    #[test]
+    fn test_recovers_at_idle_instant() {
+        let (mut k, mut mc) = setup();
+        let mut mc_dwell = MixedCriticality::new().with_min_hi_dwell(5_000);
+        assert!(mc_dwell.set_hi(&k, 0, 400));
+        mc.switch_to_hi(&mut k);
+        assert_eq!(mc.mode(), CritMode::Hi);
+        // t=0 の ctl 実行後、最初のアイドルで LO に戻る
+        assert_eq!(mc.tick(&mut k, 0), Some(0));
+        assert_eq!(mc.tick(&mut k, 100), None);
+        assert_eq!(mc.mode(), CritMode::Lo);
+        assert_eq!(mc.dropped_mask(), 0);
+        assert_eq!(mc.tick(&mut k, 0), Some(1));
+
+        // 最小滞在時間があればアイドルでも戻らない
+        let (mut k, _) = setup();
+        mc_dwell.switch_to_hi(&mut k);
+        for _ in 0..40 {
+            mc_dwell.tick(&mut k, 100);
+        }
+        assert_eq!(mc_dwell.mode(), CritMode::Hi);
+        for _ in 0..20 {
+            mc_dwell.tick(&mut k, 100);
+        }
+        assert_eq!(mc_dwell.mode(), CritMode::Lo);
+    }
+
+    #[test]
+    fn test_clock_measures_jobs() {
+        static CLOCK: AtomicU64 = AtomicU64::new(0);
+        fn now() -> u64 {
+            CLOCK.load(Ordering::Relaxed)
+        }
+        fn overrunning(_: &mut [u8]) {
+            // LO WCET 100 を超えて 150 µs 実行
+            CLOCK.fetch_add(150, Ordering::Relaxed);
+        }
+        let mut k = Kernel::testing();
+        k.add_task(b"ctl", overrunning, TaskPriority::HIGH, 1_000, 100);
+        k.add_task(b"ui", noop, TaskPriority::LOW, 1_000, 100);
+        let mut mc = MixedCriticality::with_clock(now);
+        assert!(mc.set_hi(&k, 0, 200));
+        assert_eq!(mc.tick(&mut k, 0), Some(0));
+        assert_eq!(mc.mode(), CritMode::Hi);
+        assert_eq!(mc.dropped_mask(), 0b10);
+    }
+
+    #[test]
+    fn test_amc_rtb_analysis() {
+        let (k, mc) = setup();
+        let report = mc.analyze(&k);
+        // LO: ctl=200, ui=200+300=500, log=200+300+200=700
+        assert_eq!(report.tasks[0].unwrap().lo_us, 200);
+        assert_eq!(report.tasks[1].unwrap().lo_us, 500);
+        assert_eq!(report.tasks[2].unwrap().lo_us, 700);
+        assert_eq!(report.tasks[0].unwrap().hi_us, Some(400));
+        assert_eq!(report.tasks[1].unwrap().hi_us, None);
+        assert!(report.schedulable());
+
+        // 低優先度の HI タスク: 切替前の LO 干渉は R_LO までで打ち切る
+        let mut k = Kernel::testing();
+        k.add_task(b"ui", noop, TaskPriority::HIGH, 500, 200);
+        k.add_task(b"ctl", noop, TaskPriority::LOW, 1_000, 200);
+        let mut mc = MixedCriticality::new();
+        assert!(mc.set_hi(&k, 1, 500));
+        let report = mc.analyze(&k);
+        // R_LO = 200 + ⌈400/500⌉×200 = 400、R* = 500 + ⌈400/500⌉×200 = 700
+        assert_eq!(report.tasks[1].unwrap().lo_us, 400);
+        assert_eq!(report.tasks[1].unwrap().hi_us, Some(700));
+        assert!(report.schedulable());
+        // HI WCET が大きすぎれば HI モードで落ちる (850 + 200 > 1000)
+        assert!(mc.set_hi(&k, 1, 850));
+        let report = mc.analyze(&k);
+        assert!(report.lo_ok);
+        assert!(!report.hi_ok);
+    }
+}
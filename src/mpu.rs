@@ -0,0 +1,446 @@
+//! Per-task Cortex-M MPU regions (`mpu` feature)
+//!
+//! [`Mpu`] keeps a small region set per task — the stack it runs on, its
+//! scratch slice and the channels or buffers it was granted — encoded for
+//! the core's MPU (PMSAv7 on ARMv7-M, PMSAv8 on ARMv8-M). On each context
+//! switch [`program`](Mpu::program) loads the task's regions into the MPU
+//! slots from `first_region` up, and [`unmap`](Mpu::unmap) disables them
+//! again when the job ends. Regions below `first_region` are left to the
+//! application (flash, peripherals shared by every task).
+//!
+//! Regions are read / write (or read-only) data, never executable. Their
+//! bounds are widened to what the MPU can express: 32-byte granules on
+//! PMSAv8, the smallest aligned power-of-two block on PMSAv7. Aligning
+//! stacks, arenas and channels accordingly keeps neighbours out.
+//!
+//! [`enable`](Mpu::enable) sets `PRIVDEFENA`: privileged code (the kernel)
+//! keeps the default memory map, while code running unprivileged
+//! (`CONTROL.nPRIV`) can only touch the programmed regions. With the
+//! `hooks` feature the switch hooks drive the MPU:
+//!
+//! ```ignore
+//! static mut MPU: Mpu = unsafe { Mpu::cortex_m(Pmsa::V8, 2) };
+//!
+//! fn on_enter(idx: usize) { unsafe { (*addr_of_mut!(MPU)).program(idx) } }
+//! fn on_exit(_: usize) { unsafe { (*addr_of_mut!(MPU)).unmap() } }
+//!
+//! let mpu = unsafe { &mut *addr_of_mut!(MPU) };
+//! mpu.set_stack(ctl, Region::new(stack_base, 1024, Access::ReadWrite));
+//! mpu.map_scratch(&KERNEL, ctl);
+//! mpu.grant(ctl, &SAMPLES, Access::ReadOnly);
+//! assert!(mpu.enable());
+//! KERNEL.set_switch_hooks(on_enter, on_exit);
+//! ```
+//!
+//! Scratch slices are absolute addresses: map them once the kernel sits
+//! at its final location, and again after arena tasks are added or
+//! removed.
+//!
+//! Author: Moroya Sakamoto
+
+use core::ptr;
+
+use crate::kernel::SizedKernel;
+use crate::policy::SchedulingPolicy;
+use crate::task::MAX_TASKS;
+
+/// Address of the MPU register block (`MPU_TYPE`) on ARMv7-M / ARMv8-M
+pub const MPU_BASE: usize = 0xE000_ED90;
+
+/// MPU regions per task: stack, scratch and four grants
+pub const TASK_REGIONS: usize = 6;
+
+/// Slot of the task's stack region
+const STACK_SLOT: usize = 0;
+/// Slot of the task's scratch region
+const SCRATCH_SLOT: usize = 1;
+
+/// `MPU_TYPE` (region count in bits 15:8)
+const TYPE: usize = 0x00;
+/// `MPU_CTRL`
+const CTRL: usize = 0x04;
+/// `MPU_RNR` (region number)
+const RNR: usize = 0x08;
+/// `MPU_RBAR` (region base)
+const RBAR: usize = 0x0C;
+/// `MPU_RASR` (PMSAv7) / `MPU_RLAR` (PMSAv8)
+const RASR: usize = 0x10;
+/// `MPU_MAIR0` (PMSAv8 memory attributes)
+const MAIR0: usize = 0x30;
+
+/// `MPU_CTRL.ENABLE`
+const CTRL_ENABLE: u32 = 1 << 0;
+/// `MPU_CTRL.PRIVDEFENA`: default map for privileged accesses
+const CTRL_PRIVDEFENA: u32 = 1 << 2;
+/// MAIR attribute 0: normal memory, write-back, read / write allocate
+const ATTR_NORMAL: u32 = 0xFF;
+
+/// Protected memory system architecture of the core
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pmsa {
+    /// ARMv7-M (Cortex-M3 / M4 / M7): power-of-two regions
+    V7,
+    /// ARMv8-M (Cortex-M23 / M33 / M55): base / limit regions
+    V8,
+}
+
+/// Access granted to a task (privileged and unprivileged alike)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Loads only
+    ReadOnly,
+    /// Loads and stores
+    ReadWrite,
+}
+
+/// Data region covering `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// First byte
+    pub start: u32,
+    /// One past the last byte
+    pub end: u32,
+    /// Access granted
+    pub access: Access,
+}
+
+impl Region {
+    /// Region over `len` bytes at `start`
+    #[must_use]
+    pub const fn new(start: usize, len: usize, access: Access) -> Self {
+        Self {
+            start: start as u32,
+            end: (start + len) as u32,
+            access,
+        }
+    }
+
+    /// Region over `value` (a channel, mailbox, buffer, …)
+    #[must_use]
+    pub fn of<T>(value: &T, access: Access) -> Self {
+        Self::new(
+            ptr::from_ref(value) as usize,
+            core::mem::size_of::<T>(),
+            access,
+        )
+    }
+
+    /// `(RBAR, RASR / RLAR)` for `pmsa`, bounds widened to fit
+    #[must_use]
+    pub fn encode(self, pmsa: Pmsa) -> (u32, u32) {
+        let (start, end) = (u64::from(self.start), u64::from(self.end.max(self.start)));
+        match pmsa {
+            Pmsa::V7 => {
+                // 開始を含み終端まで届く最小の整列済み 2^n ブロック
+                let mut size_log = 5;
+                while size_log < 32 && (start & !((1 << size_log) - 1)) + (1 << size_log) < end {
+                    size_log += 1;
+                }
+                let base = (start & !((1u64 << size_log) - 1)) as u32;
+                let ap = match self.access {
+                    Access::ReadOnly => 0b110,
+                    Access::ReadWrite => 0b011,
+                };
+                // XN | AP | S C B (通常メモリ, ライトバック) | SIZE | ENABLE
+                let rasr = 1 << 28 | ap << 24 | 0b111 << 16 | (size_log - 1) << 1 | 1;
+                (base, rasr)
+            }
+            Pmsa::V8 => {
+                let base = (start & !31) as u32;
+                let limit = (end.max(start + 1) + 31) & !31;
+                let ap = match self.access {
+                    Access::ReadOnly => 0b11,
+                    Access::ReadWrite => 0b01,
+                };
+                // AP | XN、RLAR は AttrIndx 0 | EN
+                (base | ap << 1 | 1, (limit - 32) as u32 | 1)
+            }
+        }
+    }
+}
+
+/// Per-task MPU region sets and the MPU they are programmed into
+pub struct Mpu {
+    /// Address of `MPU_TYPE`
+    base: usize,
+    /// Architecture the regions are encoded for
+    pmsa: Pmsa,
+    /// First MPU region used for task regions
+    first: u8,
+    /// Encoded `(RBAR, RASR / RLAR)` per task and slot (`(0, 0)` = unused)
+    regions: [[(u32, u32); TASK_REGIONS]; MAX_TASKS],
+}
+
+impl Mpu {
+    /// MPU whose register block starts at `base`
+    ///
+    /// Task regions occupy MPU regions `first_region` to
+    /// `first_region + TASK_REGIONS - 1`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the MPU register block of this core (or memory
+    /// standing in for it), and nothing else may program those regions.
+    #[must_use]
+    pub const unsafe fn new(base: usize, pmsa: Pmsa, first_region: u8) -> Self {
+        Self {
+            base,
+            pmsa,
+            first: first_region,
+            regions: [[(0, 0); TASK_REGIONS]; MAX_TASKS],
+        }
+    }
+
+    /// The core's own MPU (see [`Mpu::new`])
+    ///
+    /// # Safety
+    ///
+    /// Must run on an ARMv7-M / ARMv8-M core with an MPU; see [`Mpu::new`].
+    #[must_use]
+    pub const unsafe fn cortex_m(pmsa: Pmsa, first_region: u8) -> Self {
+        Self::new(MPU_BASE, pmsa, first_region)
+    }
+
+    /// Set the stack task `idx` runs on
+    pub fn set_stack(&mut self, idx: usize, region: Region) -> bool {
+        self.set(idx, STACK_SLOT, region)
+    }
+
+    /// Map the scratch slice task `idx` runs in (its arena or the shared
+    /// scratch)
+    pub fn map_scratch<const SCRATCH: usize, P: SchedulingPolicy>(
+        &mut self,
+        kernel: &SizedKernel<SCRATCH, P>,
+        idx: usize,
+    ) -> bool {
+        if kernel.scheduler.get_task(idx).is_none() {
+            return false;
+        }
+        let (offset, len) = kernel.scratch_region(idx);
+        let start = kernel.scratch_ptr() as usize + offset;
+        self.set(
+            idx,
+            SCRATCH_SLOT,
+            Region::new(start, len, Access::ReadWrite),
+        )
+    }
+
+    /// Grant task `idx` access to `region` (false if all grants are used)
+    pub fn grant_region(&mut self, idx: usize, region: Region) -> bool {
+        let Some(slots) = self.regions.get(idx) else {
+            return false;
+        };
+        match (SCRATCH_SLOT + 1..TASK_REGIONS).find(|&slot| slots[slot] == (0, 0)) {
+            Some(slot) => self.set(idx, slot, region),
+            None => false,
+        }
+    }
+
+    /// Grant task `idx` access to `value` (e.g. a channel it uses)
+    pub fn grant<T>(&mut self, idx: usize, value: &T, access: Access) -> bool {
+        self.grant_region(idx, Region::of(value, access))
+    }
+
+    /// Drop every region of task `idx`
+    pub fn clear(&mut self, idx: usize) {
+        if let Some(slots) = self.regions.get_mut(idx) {
+            *slots = [(0, 0); TASK_REGIONS];
+        }
+    }
+
+    /// MPU regions the core implements (`MPU_TYPE.DREGION`)
+    #[must_use]
+    pub fn region_count(&self) -> u8 {
+        (self.read(TYPE) >> 8) as u8
+    }
+
+    /// Enable the MPU with the default map for privileged code
+    ///
+    /// Returns false (leaving the MPU off) if the core has fewer regions
+    /// than `first_region + TASK_REGIONS`.
+    pub fn enable(&mut self) -> bool {
+        if usize::from(self.region_count()) < usize::from(self.first) + TASK_REGIONS {
+            return false;
+        }
+        self.unmap();
+        if self.pmsa == Pmsa::V8 {
+            let mair = self.read(MAIR0);
+            self.write(MAIR0, (mair & !0xFF) | ATTR_NORMAL);
+        }
+        self.write(CTRL, CTRL_PRIVDEFENA | CTRL_ENABLE);
+        barrier();
+        true
+    }
+
+    /// Turn the MPU off
+    pub fn disable(&mut self) {
+        self.write(CTRL, 0);
+        barrier();
+    }
+
+    /// Load task `idx`'s regions (context switch to `idx`)
+    pub fn program(&mut self, idx: usize) {
+        let Some(&slots) = self.regions.get(idx) else {
+            return self.unmap();
+        };
+        for (slot, (rbar, attr)) in slots.into_iter().enumerate() {
+            self.write(RNR, u32::from(self.first) + slot as u32);
+            // 旧領域を無効化してから付け替え (途中状態を晒さない)
+            self.write(RASR, 0);
+            self.write(RBAR, rbar);
+            self.write(RASR, attr);
+        }
+        barrier();
+    }
+
+    /// Disable all task regions (back to the kernel)
+    pub fn unmap(&mut self) {
+        for slot in 0..TASK_REGIONS {
+            self.write(RNR, u32::from(self.first) + slot as u32);
+            self.write(RASR, 0);
+        }
+        barrier();
+    }
+
+    /// Encode `region` into slot `slot` of task `idx`
+    fn set(&mut self, idx: usize, slot: usize, region: Region) -> bool {
+        match self.regions.get_mut(idx) {
+            Some(slots) => {
+                slots[slot] = region.encode(self.pmsa);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        // SAFETY: `new` の契約により有効な MPU レジスタ
+        unsafe { ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&mut self, offset: usize, value: u32) {
+        // SAFETY: `new` の契約により有効な MPU レジスタ
+        unsafe { ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+/// Complete MPU writes before the next access (`DSB`, `ISB`)
+fn barrier() {
+    #[cfg(target_arch = "arm")]
+    // SAFETY: バリア命令のみ
+    unsafe {
+        core::arch::asm!("dsb", "isb", options(nostack, preserves_flags));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::task::{Task, TaskPriority};
+    use std::boxed::Box;
+
+    /// ホスト上の疑似 MPU レジスタ (テスト終了まで解放しない)
+    fn fake_mpu(regions: u32) -> usize {
+        let regs = Box::into_raw(Box::new([0u32; 16])) as usize;
+        // SAFETY: 確保したばかりの疑似レジスタ
+        unsafe { ptr::write_volatile((regs + TYPE) as *mut u32, regions << 8) };
+        regs
+    }
+
+    fn reg(base: usize, offset: usize) -> u32 {
+        // SAFETY: 疑似 MPU 内のアドレス
+        unsafe { ptr::read_volatile((base + offset) as *const u32) }
+    }
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_pmsav7_power_of_two_blocks() {
+        let rw = Region::new(0x2000_0100, 0x100, Access::ReadWrite);
+        assert_eq!(
+            rw.encode(Pmsa::V7),
+            (
+                0x2000_0100,
+                1 << 28 | 0b011 << 24 | 0b111 << 16 | 7 << 1 | 1
+            )
+        );
+        // 整列していない 256 バイトは 0x2000_0000 からの 1 KB に広がる
+        let (base, rasr) = Region::new(0x2000_0110, 0x100, Access::ReadOnly).encode(Pmsa::V7);
+        assert_eq!((base, rasr >> 1 & 0x1F), (0x2000_0000, 9));
+        assert_eq!(rasr >> 24 & 0b111, 0b110);
+        // 最小 32 バイト
+        assert_eq!(
+            Region::new(0x40, 4, Access::ReadWrite).encode(Pmsa::V7).1 >> 1 & 0x1F,
+            4
+        );
+    }
+
+    #[test]
+    fn test_pmsav8_granules() {
+        let (rbar, rlar) = Region::new(0x2000_0110, 0x100, Access::ReadWrite).encode(Pmsa::V8);
+        assert_eq!(rbar, 0x2000_0100 | 0b01 << 1 | 1);
+        assert_eq!(rlar, 0x2000_0200 | 1);
+        let (rbar, rlar) = Region::new(0x2000_0000, 32, Access::ReadOnly).encode(Pmsa::V8);
+        assert_eq!((rbar, rlar), (0x2000_0000 | 0b11 << 1 | 1, 0x2000_0000 | 1));
+    }
+
+    #[test]
+    fn test_program_and_unmap() {
+        let base = fake_mpu(8);
+        // SAFETY: 疑似 MPU はテスト中有効
+        let mut mpu = unsafe { Mpu::new(base, Pmsa::V8, 2) };
+        let stack = Region::new(0x2000_1000, 0x400, Access::ReadWrite);
+        assert!(mpu.set_stack(0, stack));
+        for i in 0..4 {
+            assert!(mpu.grant_region(0, Region::new(0x2000_2000 + i * 64, 64, Access::ReadOnly)));
+        }
+        // 付与枠は 4 つまで
+        assert!(!mpu.grant_region(0, Region::new(0x2000_3000, 64, Access::ReadOnly)));
+        assert!(!mpu.set_stack(MAX_TASKS, stack));
+
+        assert!(mpu.enable());
+        assert_eq!(reg(base, CTRL), CTRL_PRIVDEFENA | CTRL_ENABLE);
+        assert_eq!(reg(base, MAIR0) & 0xFF, ATTR_NORMAL);
+        // 最後に書かれるのは最後のスロット (4 つ目の付与)
+        mpu.program(0);
+        assert_eq!(reg(base, RNR), 2 + 5);
+        let last = Region::new(0x2000_2000 + 3 * 64, 64, Access::ReadOnly).encode(Pmsa::V8);
+        assert_eq!((reg(base, RBAR), reg(base, RASR)), last);
+        mpu.unmap();
+        assert_eq!(reg(base, RASR), 0);
+
+        mpu.clear(0);
+        assert!(mpu.grant_region(0, stack));
+        mpu.disable();
+        assert_eq!(reg(base, CTRL), 0);
+    }
+
+    #[test]
+    fn test_enable_needs_enough_regions() {
+        // SAFETY: 疑似 MPU はテスト中有効
+        let mut mpu = unsafe { Mpu::new(fake_mpu(8), Pmsa::V7, 3) };
+        assert_eq!(mpu.region_count(), 8);
+        assert!(!mpu.enable());
+        assert_eq!(mpu.read(CTRL), 0);
+    }
+
+    #[test]
+    fn test_scratch_region_follows_arena() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"log", noop, TaskPriority::LOW, 5_000, 10);
+        let ctl = kernel
+            .try_add(Task::new(b"ctl", noop, TaskPriority::HIGH, 1_000, 10).with_arena(64))
+            .unwrap();
+        let base = fake_mpu(16);
+        // SAFETY: 疑似 MPU はテスト中有効
+        let mut mpu = unsafe { Mpu::new(base, Pmsa::V8, 0) };
+        assert!(mpu.map_scratch(&kernel, ctl));
+        assert!(!mpu.map_scratch(&kernel, 5));
+        let arena = kernel.arena(ctl).unwrap();
+        let expected = Region::new(arena.as_ptr() as usize, 64, Access::ReadWrite);
+        assert_eq!(mpu.regions[ctl][SCRATCH_SLOT], expected.encode(Pmsa::V8));
+    }
+}
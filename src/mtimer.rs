@@ -0,0 +1,260 @@
+//! RISC-V machine timer backend (`arch-riscv` feature)
+//!
+//! [`MTimer`] drives the tickless kernel from the memory-mapped `mtime` /
+//! `mtimecmp` pair of a CLINT or ACLINT MTIMER (GD32VF103, ESP32-C3
+//! CLINT, QEMU `virt`, …). It implements [`TickSource`]: `now_us` scales
+//! `mtime` to microseconds and `set_compare` programs `mtimecmp`, which
+//! raises the machine timer interrupt (MTIP) when reached.
+//!
+//! The machine timer trap handler calls [`on_timer_interrupt`], which
+//! catches the kernel up, runs the next job and re-arms the compare for
+//! the kernel's next wakeup: a task activation, a software timer expiry
+//! or a budget replenishment. On RISC-V targets `enable_timer_interrupt` sets
+//! `mie.MTIE` and `mstatus.MIE`.
+//!
+//! Jobs still run to completion inside the handler; preemptive context
+//! switching (saving task frames through `mscratch`) needs a per-task
+//! stack model the kernel does not have yet.
+//!
+//! Author: Moroya Sakamoto
+
+use core::ptr;
+
+use crate::kernel::Kernel;
+use crate::timer::TickSource;
+
+/// Offset of `mtimecmp` (hart 0) in the SiFive-compatible CLINT
+pub const CLINT_MTIMECMP_OFFSET: usize = 0x4000;
+
+/// Offset of `mtime` in the SiFive-compatible CLINT
+pub const CLINT_MTIME_OFFSET: usize = 0xBFF8;
+
+/// `mtime` / `mtimecmp` register pair of one hart
+pub struct MTimer {
+    /// Address of `mtime`
+    mtime: usize,
+    /// Address of this hart's `mtimecmp`
+    mtimecmp: usize,
+    /// `mtime` ticks per microsecond
+    ticks_per_us: u32,
+}
+
+impl MTimer {
+    /// Timer over explicit `mtime` / `mtimecmp` addresses
+    ///
+    /// `mtime_hz` is the `mtime` increment rate (at least 1 MHz).
+    ///
+    /// # Safety
+    ///
+    /// Both addresses must be the 8-byte aligned timer registers of this
+    /// hart, and nothing else may program this `mtimecmp`.
+    #[must_use]
+    pub const unsafe fn new(mtime: usize, mtimecmp: usize, mtime_hz: u32) -> Self {
+        let ticks_per_us = mtime_hz / 1_000_000;
+        Self {
+            mtime,
+            mtimecmp,
+            ticks_per_us: if ticks_per_us == 0 { 1 } else { ticks_per_us },
+        }
+    }
+
+    /// Timer of `hart` in a CLINT at `base`
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the CLINT of this system; see [`MTimer::new`].
+    #[must_use]
+    pub const unsafe fn clint(base: usize, hart: usize, mtime_hz: u32) -> Self {
+        Self::new(
+            base + CLINT_MTIME_OFFSET,
+            base + CLINT_MTIMECMP_OFFSET + 8 * hart,
+            mtime_hz,
+        )
+    }
+
+    /// Raw `mtime` value
+    ///
+    /// Read as two 32-bit halves (high, low, high again) so a carry
+    /// between the halves is never observed on RV32.
+    #[must_use]
+    pub fn mtime(&self) -> u64 {
+        let lo = self.mtime as *const u32;
+        let hi = (self.mtime + 4) as *const u32;
+        loop {
+            // SAFETY: `new` の契約により有効なタイマレジスタ
+            let (h1, l, h2) = unsafe {
+                (
+                    ptr::read_volatile(hi),
+                    ptr::read_volatile(lo),
+                    ptr::read_volatile(hi),
+                )
+            };
+            if h1 == h2 {
+                return ((h1 as u64) << 32) | l as u64;
+            }
+        }
+    }
+
+    /// Program `mtimecmp` with a raw tick value
+    ///
+    /// The low half is first set to all ones, so no intermediate value
+    /// below both the old and new compare can raise a spurious interrupt.
+    pub fn set_mtimecmp(&mut self, ticks: u64) {
+        let lo = self.mtimecmp as *mut u32;
+        let hi = (self.mtimecmp + 4) as *mut u32;
+        // SAFETY: `new` の契約により有効なタイマレジスタ
+        unsafe {
+            ptr::write_volatile(lo, u32::MAX);
+            ptr::write_volatile(hi, (ticks >> 32) as u32);
+            ptr::write_volatile(lo, ticks as u32);
+        }
+    }
+
+    /// Push the compare to the far future (clears a pending MTIP)
+    pub fn disarm(&mut self) {
+        self.set_mtimecmp(u64::MAX);
+    }
+
+    /// `mtime` ticks per microsecond
+    #[must_use]
+    pub const fn ticks_per_us(&self) -> u32 {
+        self.ticks_per_us
+    }
+}
+
+impl TickSource for MTimer {
+    fn now_us(&self) -> u64 {
+        self.mtime() / self.ticks_per_us as u64
+    }
+
+    fn set_compare(&mut self, at_us: u64) {
+        self.set_mtimecmp(at_us.saturating_mul(self.ticks_per_us as u64));
+    }
+}
+
+/// Machine timer trap body: tick the kernel and re-arm the compare
+///
+/// Returns the executed task. Software timers due by now fire in the
+/// tick, and the compare is set to [`Kernel::next_wakeup_us`], so a task
+/// throttled by its budget waits for the replenishment instead of
+/// re-raising MTIP at once. With nothing left to schedule or fire the
+/// timer is disarmed, so MTIP does not fire again until the next
+/// `arm_wakeup`.
+pub fn on_timer_interrupt(kernel: &mut Kernel, timer: &mut MTimer) -> Option<usize> {
+    let executed = kernel.tick_from(timer);
+    if kernel.arm_wakeup(timer).is_none() {
+        timer.disarm();
+    }
+    executed
+}
+
+/// Enable the machine timer interrupt (`mie.MTIE`, `mstatus.MIE`)
+///
+/// # Safety
+///
+/// A machine timer trap handler must be installed in `mtvec`.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+pub unsafe fn enable_timer_interrupt() {
+    // mie.MTIE = bit 7, mstatus.MIE = bit 3
+    unsafe {
+        core::arch::asm!("csrs mie, {0}", in(reg) 1usize << 7);
+        core::arch::asm!("csrs mstatus, {0}", in(reg) 1usize << 3);
+    }
+}
+
+/// Disable the machine timer interrupt (`mie.MTIE`)
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+pub fn disable_timer_interrupt() {
+    // SAFETY: MTIE のクリアは割り込みを止めるだけ
+    unsafe { core::arch::asm!("csrc mie, {0}", in(reg) 1usize << 7) };
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::soft_timer::SoftTimerPool;
+    use crate::task::TaskPriority;
+    use std::boxed::Box;
+
+    /// ホスト上の疑似 CLINT (0xC000 バイト, テスト終了まで解放しない)
+    #[repr(C, align(8))]
+    struct FakeClint([u32; 0x3000]);
+
+    fn fake_clint() -> usize {
+        Box::into_raw(Box::new(FakeClint([0; 0x3000]))) as usize
+    }
+
+    fn set_mtime(base: usize, ticks: u64) {
+        let p = (base + CLINT_MTIME_OFFSET) as *mut u32;
+        // SAFETY: 疑似 CLINT 内のアドレス
+        unsafe {
+            ptr::write_volatile(p, ticks as u32);
+            ptr::write_volatile(p.add(1), (ticks >> 32) as u32);
+        }
+    }
+
+    fn mtimecmp(base: usize, hart: usize) -> u64 {
+        let p = (base + CLINT_MTIMECMP_OFFSET + 8 * hart) as *const u32;
+        // SAFETY: 疑似 CLINT 内のアドレス
+        unsafe { ((ptr::read_volatile(p.add(1)) as u64) << 32) | ptr::read_volatile(p) as u64 }
+    }
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_mtime_scaled_to_us() {
+        let base = fake_clint();
+        // SAFETY: 疑似 CLINT はテスト中有効
+        let mut timer = unsafe { MTimer::clint(base, 1, 10_000_000) };
+        set_mtime(base, 0x1_0000_0000 + 25);
+        assert_eq!(timer.mtime(), 0x1_0000_0000 + 25);
+        assert_eq!(timer.now_us(), (0x1_0000_0000 + 25) / 10);
+        timer.set_compare(0x2_0000_0001);
+        assert_eq!(mtimecmp(base, 1), 0x2_0000_0001 * 10);
+        assert_eq!(mtimecmp(base, 0), 0);
+        timer.disarm();
+        assert_eq!(mtimecmp(base, 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_interrupt_rearms_next_activation() {
+        let base = fake_clint();
+        // SAFETY: 疑似 CLINT はテスト中有効
+        let mut timer = unsafe { MTimer::clint(base, 0, 1_000_000) };
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 10);
+        assert_eq!(on_timer_interrupt(&mut kernel, &mut timer), Some(0));
+        assert_eq!(mtimecmp(base, 0), 1_000);
+        set_mtime(base, 1_000);
+        assert_eq!(on_timer_interrupt(&mut kernel, &mut timer), Some(0));
+        assert_eq!(mtimecmp(base, 0), 2_000);
+
+        kernel.scheduler.suspend(0);
+        on_timer_interrupt(&mut kernel, &mut timer);
+        assert_eq!(mtimecmp(base, 0), u64::MAX);
+    }
+
+    #[test]
+    fn test_interrupt_arms_soft_timer() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        static FIRED: AtomicU32 = AtomicU32::new(0);
+        fn cb() {
+            FIRED.fetch_add(1, Ordering::Relaxed);
+        }
+        let base = fake_clint();
+        // SAFETY: 疑似 CLINT はテスト中有効
+        let mut timer = unsafe { MTimer::clint(base, 0, 1_000_000) };
+        let mut kernel = Kernel::testing();
+        kernel.attach_timers(Box::leak(Box::new(SoftTimerPool::new())));
+        kernel.arm_timer(cb, 5_000).unwrap();
+        // タスクなし・タイマのみ → 満了時刻に設定 (解除しない)
+        assert_eq!(on_timer_interrupt(&mut kernel, &mut timer), None);
+        assert_eq!(mtimecmp(base, 0), 5_000);
+        set_mtime(base, 5_000);
+        on_timer_interrupt(&mut kernel, &mut timer);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+        assert_eq!(mtimecmp(base, 0), u64::MAX);
+    }
+}
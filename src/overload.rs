@@ -0,0 +1,389 @@
+//! Overload management — graceful degradation by load shedding
+//!
+//! Under transient overload (thermal throttling, a burst of events) it is
+//! better to drop optional work systematically than to miss the deadlines
+//! of critical tasks. [`OverloadManager`] watches the kernel over fixed
+//! windows: when the processor demand of the jobs executed in a window
+//! exceeds a threshold, or deadline misses pile up, it sheds one task
+//! marked with [`Task::allow_shedding`](crate::task::Task::allow_shedding)
+//! per window, lowest priority first. Once the load has fallen below a
+//! lower restore threshold without misses, shed tasks come back one per
+//! window, highest priority first.
+//!
+//! Shedding either skips the task (it is suspended) or down-rates it
+//! (its period is stretched by a factor); see [`ShedAction`].
+//!
+//! Author: Moroya Sakamoto
+
+use crate::events::{emit, KernelEvent};
+use crate::kernel::Kernel;
+use crate::task::{TaskMask, TaskState, MAX_TASKS};
+
+/// How a shed task is degraded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedAction {
+    /// Stop releasing the task until load subsides
+    Skip,
+    /// Multiply the task's period by this factor (≥ 2)
+    DownRate(u8),
+}
+
+/// Decision taken at the end of a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadAction {
+    /// Task shed
+    Shed(usize),
+    /// Task restored to its normal rate
+    Restored(usize),
+}
+
+/// Window-based overload detector and load shedder
+pub struct OverloadManager {
+    /// How shed tasks are degraded
+    action: ShedAction,
+    /// Evaluation window (µs)
+    window_us: u64,
+    /// Shed when the window's load exceeds this (ppm)
+    shed_above_ppm: u32,
+    /// Restore when the window's load is below this (ppm)
+    restore_below_ppm: u32,
+    /// Shed when a window has at least this many misses (0 = ignore misses)
+    miss_limit: u32,
+    /// Start of the current window (None = not started)
+    window_start: Option<u64>,
+    /// `exec_count` of each task at the window start
+    exec_seen: [u32; MAX_TASKS],
+    /// Deadline misses of all tasks at the window start
+    misses_seen: u64,
+    /// Normal period of each shed task (µs)
+    normal_period: [u32; MAX_TASKS],
+    /// Tasks currently shed
+    shed: TaskMask,
+    /// Load measured in the last complete window (ppm)
+    load_ppm: u32,
+    /// Tasks shed so far
+    shed_count: u32,
+}
+
+impl OverloadManager {
+    /// Manager evaluating every `window_us`
+    ///
+    /// Sheds when the executed demand exceeds `shed_above_ppm` of the
+    /// window or a window sees `miss_limit` deadline misses (0 disables
+    /// the miss trigger); restores below `restore_below_ppm`.
+    #[must_use]
+    pub const fn new(
+        action: ShedAction,
+        window_us: u64,
+        shed_above_ppm: u32,
+        restore_below_ppm: u32,
+        miss_limit: u32,
+    ) -> Self {
+        Self {
+            action,
+            window_us,
+            shed_above_ppm,
+            restore_below_ppm,
+            miss_limit,
+            window_start: None,
+            exec_seen: [0; MAX_TASKS],
+            misses_seen: 0,
+            normal_period: [0; MAX_TASKS],
+            shed: 0,
+            load_ppm: 0,
+            shed_count: 0,
+        }
+    }
+
+    /// Tick the kernel, then evaluate the window if it is complete
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        self.check(kernel);
+        executed
+    }
+
+    /// Evaluate the current window if it is complete
+    ///
+    /// Call regularly (e.g. after every tick). Returns the shedding
+    /// decision taken at the end of a window, if any.
+    pub fn check(&mut self, kernel: &mut Kernel) -> Option<OverloadAction> {
+        let now = kernel.timer.now_us();
+        let Some(start) = self.window_start else {
+            self.start_window(kernel, now);
+            return None;
+        };
+        let elapsed = now - start;
+        if elapsed < self.window_us || elapsed == 0 {
+            return None;
+        }
+
+        let mut demand_us = 0u64;
+        for (idx, seen) in self.exec_seen.iter().enumerate() {
+            if let Some(task) = kernel.scheduler.get_task(idx) {
+                demand_us += u64::from(task.exec_count.wrapping_sub(*seen)) * task.wcet_us as u64;
+            }
+        }
+        let misses = total_misses(kernel) - self.misses_seen;
+        self.load_ppm = (demand_us * 1_000_000 / elapsed).min(u32::MAX as u64) as u32;
+        self.start_window(kernel, now);
+
+        let overloaded = self.load_ppm > self.shed_above_ppm
+            || (self.miss_limit > 0 && misses >= u64::from(self.miss_limit));
+        if overloaded {
+            self.shed_next(kernel)
+        } else if misses == 0 && self.load_ppm < self.restore_below_ppm {
+            self.restore_next(kernel)
+        } else {
+            None
+        }
+    }
+
+    /// Bring every shed task back immediately
+    pub fn restore_all(&mut self, kernel: &mut Kernel) {
+        for idx in 0..MAX_TASKS {
+            if self.shed & (1 << idx) != 0 {
+                self.restore(kernel, idx);
+            }
+        }
+    }
+
+    /// Tasks currently shed
+    #[must_use]
+    pub const fn shed_mask(&self) -> TaskMask {
+        self.shed
+    }
+
+    /// Load of the last complete window (ppm of the window length)
+    #[must_use]
+    pub const fn load_ppm(&self) -> u32 {
+        self.load_ppm
+    }
+
+    /// Tasks shed since creation
+    #[must_use]
+    pub const fn shed_count(&self) -> u32 {
+        self.shed_count
+    }
+
+    /// Snapshot the counters at the start of a window
+    fn start_window(&mut self, kernel: &Kernel, now: u64) {
+        for (idx, seen) in self.exec_seen.iter_mut().enumerate() {
+            *seen = kernel.scheduler.get_task(idx).map_or(0, |t| t.exec_count);
+        }
+        self.misses_seen = total_misses(kernel);
+        self.window_start = Some(now);
+    }
+
+    /// Shed the lowest-priority sheddable task that is still running
+    fn shed_next(&mut self, kernel: &mut Kernel) -> Option<OverloadAction> {
+        let mut victim: Option<usize> = None;
+        for idx in 0..MAX_TASKS {
+            let Some(task) = kernel.scheduler.get_task(idx) else {
+                break;
+            };
+            if !task.shed_allowed()
+                || self.shed & (1 << idx) != 0
+                || matches!(task.state, TaskState::Inactive | TaskState::Suspended)
+            {
+                continue;
+            }
+            let lower = victim
+                .and_then(|v| kernel.scheduler.get_task(v))
+                .is_none_or(|v| task.priority >= v.priority);
+            if lower {
+                victim = Some(idx);
+            }
+        }
+        let idx = victim?;
+        let period = kernel.scheduler.get_task(idx)?.period_us;
+        let applied = match self.action {
+            ShedAction::Skip => {
+                kernel.scheduler.suspend(idx);
+                true
+            }
+            ShedAction::DownRate(factor) => {
+                let stretched = period.saturating_mul(u32::from(factor.max(2)));
+                kernel.scheduler.set_period(idx, stretched)
+            }
+        };
+        if !applied {
+            return None;
+        }
+        self.normal_period[idx] = period;
+        self.shed |= 1 << idx;
+        self.shed_count += 1;
+        emit(KernelEvent::TaskShed { idx: idx as u8 });
+        Some(OverloadAction::Shed(idx))
+    }
+
+    /// Restore the highest-priority shed task if the headroom allows it
+    fn restore_next(&mut self, kernel: &mut Kernel) -> Option<OverloadAction> {
+        let mut best: Option<usize> = None;
+        for idx in 0..MAX_TASKS {
+            if self.shed & (1 << idx) == 0 {
+                continue;
+            }
+            let Some(task) = kernel.scheduler.get_task(idx) else {
+                continue;
+            };
+            let higher = best
+                .and_then(|b| kernel.scheduler.get_task(b))
+                .is_none_or(|b| task.priority < b.priority);
+            if higher {
+                best = Some(idx);
+            }
+        }
+        let idx = best?;
+        let task = kernel.scheduler.get_task(idx)?;
+        // Only come back if the restored load stays under the threshold
+        let util = crate::scheduler::utilization_ppm(task.wcet_us, self.normal_period[idx]);
+        if self.load_ppm.saturating_add(util) >= self.restore_below_ppm {
+            return None;
+        }
+        self.restore(kernel, idx)
+            .then_some(OverloadAction::Restored(idx))
+    }
+
+    /// Undo the shedding of `idx`
+    fn restore(&mut self, kernel: &mut Kernel, idx: usize) -> bool {
+        let restored = match self.action {
+            ShedAction::Skip => {
+                kernel.scheduler.resume(idx);
+                true
+            }
+            ShedAction::DownRate(_) => kernel.scheduler.set_period(idx, self.normal_period[idx]),
+        };
+        // A task removed meanwhile has nothing left to restore
+        if restored
+            || kernel
+                .scheduler
+                .get_task(idx)
+                .is_none_or(|t| !t.is_active())
+        {
+            self.shed &= !(1 << idx);
+            emit(KernelEvent::TaskRestored { idx: idx as u8 });
+        }
+        restored
+    }
+}
+
+/// Deadline misses summed over the task table
+fn total_misses(kernel: &Kernel) -> u64 {
+    (0..MAX_TASKS)
+        .map_while(|idx| kernel.scheduler.get_task(idx))
+        .map(|t| u64::from(t.deadline_misses))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Task, TaskPriority};
+
+    fn noop(_: &mut [u8]) {}
+
+    /// 制御 (必須) + 2 つの縮退可能タスク
+    fn kernel() -> Kernel {
+        let mut k = Kernel::testing();
+        k.scheduler
+            .register(Task::new(b"ctl", noop, TaskPriority::CRITICAL, 1_000, 400));
+        k.scheduler
+            .register(Task::new(b"vis", noop, TaskPriority::NORMAL, 1_000, 300).allow_shedding());
+        k.scheduler
+            .register(Task::new(b"log", noop, TaskPriority::LOW, 1_000, 200).allow_shedding());
+        k
+    }
+
+    fn run(mgr: &mut OverloadManager, k: &mut Kernel, ticks: usize) {
+        for _ in 0..ticks {
+            mgr.tick(k, 100);
+        }
+    }
+
+    #[test]
+    fn test_sheds_lowest_priority_first() {
+        let mut k = kernel();
+        let mut mgr = OverloadManager::new(ShedAction::Skip, 10_000, 800_000, 500_000, 0);
+        // 負荷 90% > 80% → 1 窓ごとに 1 タスクずつ縮退
+        run(&mut mgr, &mut k, 101);
+        assert_eq!(mgr.shed_mask(), 0b100);
+        assert_eq!(k.scheduler.get_task(2).unwrap().state, TaskState::Suspended);
+        run(&mut mgr, &mut k, 100);
+        // 70% ≤ 80% → これ以上縮退しない、復帰もしない (70% ≥ 50%)
+        assert_eq!(mgr.shed_mask(), 0b100);
+        assert_eq!(mgr.load_ppm(), 700_000);
+        assert_eq!(mgr.shed_count(), 1);
+    }
+
+    #[test]
+    fn test_restores_when_load_subsides() {
+        let mut k = kernel();
+        let mut mgr = OverloadManager::new(ShedAction::Skip, 10_000, 800_000, 950_000, 0);
+        run(&mut mgr, &mut k, 101);
+        assert_eq!(mgr.shed_mask(), 0b100);
+        // 熱が下がった: 制御タスクの WCET が減る
+        k.scheduler.set_wcet(0, 100);
+        run(&mut mgr, &mut k, 200);
+        assert_eq!(mgr.shed_mask(), 0);
+        assert_ne!(k.scheduler.get_task(2).unwrap().state, TaskState::Suspended);
+    }
+
+    #[test]
+    fn test_restore_needs_headroom_for_task() {
+        let mut k = kernel();
+        let mut mgr = OverloadManager::new(ShedAction::Skip, 10_000, 800_000, 750_000, 0);
+        run(&mut mgr, &mut k, 101);
+        assert_eq!(mgr.shed_mask(), 0b100);
+        // 70% < 75% だが戻すと 90% → 復帰しない (振動防止)
+        run(&mut mgr, &mut k, 300);
+        assert_eq!(mgr.shed_mask(), 0b100);
+    }
+
+    #[test]
+    fn test_deadline_misses_trigger_shedding() {
+        let mut k = Kernel::testing();
+        k.scheduler
+            .register(Task::new(b"ctl", noop, TaskPriority::CRITICAL, 10_000, 10));
+        k.scheduler
+            .register(Task::new(b"bg", noop, TaskPriority::LOW, 100, 10).allow_shedding());
+        let mut mgr = OverloadManager::new(ShedAction::Skip, 1_000, u32::MAX, 0, 1);
+        mgr.tick(&mut k, 0);
+        // bg は t = 0 にリリース、t = 1 000 までに締切 100..900 の 9 回を逃す
+        assert_eq!(mgr.tick(&mut k, 1_000), Some(1));
+        assert_eq!(k.scheduler.get_task(1).unwrap().deadline_misses, 9);
+        assert_eq!(mgr.shed_mask(), 0b10);
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Suspended);
+    }
+
+    #[test]
+    fn test_down_rate_and_restore_period() {
+        let mut k = Kernel::testing();
+        k.scheduler
+            .register(Task::new(b"ctl", noop, TaskPriority::CRITICAL, 1_000, 300));
+        k.scheduler
+            .register(Task::new(b"log", noop, TaskPriority::LOW, 1_000, 200).allow_shedding());
+        let mut mgr = OverloadManager::new(ShedAction::DownRate(4), 10_000, 400_000, 0, 0);
+        run(&mut mgr, &mut k, 101);
+        assert_eq!(mgr.check(&mut k), None);
+        assert_eq!(mgr.shed_mask(), 0b10);
+        // 周期 4 倍でリリースは継続
+        assert_eq!(k.scheduler.get_task(1).unwrap().period_us, 4_000);
+        let before = k.scheduler.get_task(1).unwrap().exec_count;
+        run(&mut mgr, &mut k, 100);
+        assert!(k.scheduler.get_task(1).unwrap().exec_count - before >= 2);
+        mgr.restore_all(&mut k);
+        assert_eq!(mgr.shed_mask(), 0);
+        assert_eq!(k.scheduler.get_task(1).unwrap().period_us, 1_000);
+    }
+
+    #[test]
+    fn test_non_sheddable_tasks_untouched() {
+        let mut k = Kernel::testing();
+        k.scheduler
+            .register(Task::new(b"ctl", noop, TaskPriority::CRITICAL, 1_000, 950));
+        let mut mgr = OverloadManager::new(ShedAction::Skip, 1_000, 500_000, 0, 0);
+        run(&mut mgr, &mut k, 50);
+        assert_eq!(mgr.shed_mask(), 0);
+        assert_eq!(k.scheduler.get_task(0).unwrap().state, TaskState::Sleeping);
+    }
+}
@@ -0,0 +1,451 @@
+//! Read-copy-update parameter store for rarely changed configuration
+//!
+//! [`ParamStore<N>`] is a static table of `N` 32-bit words holding typed
+//! entries: each entry is addressed by a [`ParamKey<T>`] laid out at
+//! compile time (`ParamKey::first()`, then `.next()` per entry), and any
+//! [`ParamValue`] — integers, `f32`, `bool`, 64-bit values, small arrays —
+//! can be stored.
+//!
+//! The table is kept twice. One writer [`update`](ParamStore::update)s
+//! by copying the live table into the spare one, changing it there and
+//! publishing it with a version bump; readers (tasks and ISRs) read the
+//! live table without locks and retry only if the version moved while
+//! they read, i.e. a whole update was published meanwhile. A reader
+//! interrupting the writer mid-update is never torn and never waits: the
+//! half-written table is not the live one.
+//!
+//! ```rust
+//! use alice_rtos::params::{ParamKey, ParamStore};
+//!
+//! const KP: ParamKey<f32> = ParamKey::first();
+//! const KI: ParamKey<f32> = KP.next();
+//! const LIMIT: ParamKey<i16> = KI.next();
+//!
+//! static PARAMS: ParamStore<{ LIMIT.end() }> = ParamStore::new();
+//!
+//! // Configuration task: one consistent update of several entries
+//! PARAMS.update(|p| {
+//!     p.set(KP, 1.5);
+//!     p.set(KI, 0.02);
+//!     p.set(LIMIT, -400);
+//! });
+//! // Any task / ISR
+//! assert_eq!(PARAMS.get(LIMIT), Some(-400));
+//! let (kp, ki) = PARAMS.read(|p| (p.get(KP), p.get(KI)));
+//! assert_eq!((kp, ki), (Some(1.5), Some(0.02)));
+//! assert_eq!(PARAMS.version(), 1);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{fence, AtomicU32, Ordering};
+use core::marker::PhantomData;
+
+/// Largest entry, in 32-bit words (16 bytes)
+pub const MAX_PARAM_WORDS: usize = 4;
+
+/// Value storable in a [`ParamStore`] as 32-bit words
+pub trait ParamValue: Copy {
+    /// Words occupied (at most [`MAX_PARAM_WORDS`])
+    const WORDS: usize;
+
+    /// Encode into `words` (`WORDS` long)
+    fn to_words(self, words: &mut [u32]);
+
+    /// Decode from `words` (`WORDS` long)
+    fn from_words(words: &[u32]) -> Self;
+}
+
+/// 1 ワードに収まる整数
+macro_rules! word_param {
+    ($($ty:ty),*) => {$(
+        impl ParamValue for $ty {
+            const WORDS: usize = 1;
+
+            fn to_words(self, words: &mut [u32]) {
+                words[0] = self as u32;
+            }
+
+            fn from_words(words: &[u32]) -> Self {
+                words[0] as $ty
+            }
+        }
+    )*};
+}
+
+word_param!(u8, u16, u32, i8, i16, i32);
+
+impl ParamValue for bool {
+    const WORDS: usize = 1;
+
+    fn to_words(self, words: &mut [u32]) {
+        words[0] = u32::from(self);
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        words[0] != 0
+    }
+}
+
+impl ParamValue for f32 {
+    const WORDS: usize = 1;
+
+    fn to_words(self, words: &mut [u32]) {
+        words[0] = self.to_bits();
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        Self::from_bits(words[0])
+    }
+}
+
+impl ParamValue for u64 {
+    const WORDS: usize = 2;
+
+    fn to_words(self, words: &mut [u32]) {
+        words[0] = self as u32;
+        words[1] = (self >> 32) as u32;
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        u64::from(words[0]) | u64::from(words[1]) << 32
+    }
+}
+
+impl ParamValue for i64 {
+    const WORDS: usize = 2;
+
+    fn to_words(self, words: &mut [u32]) {
+        (self as u64).to_words(words);
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        u64::from_words(words) as i64
+    }
+}
+
+impl ParamValue for f64 {
+    const WORDS: usize = 2;
+
+    fn to_words(self, words: &mut [u32]) {
+        self.to_bits().to_words(words);
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        Self::from_bits(u64::from_words(words))
+    }
+}
+
+impl<T: ParamValue, const M: usize> ParamValue for [T; M] {
+    const WORDS: usize = T::WORDS * M;
+
+    fn to_words(self, words: &mut [u32]) {
+        for (value, chunk) in self.into_iter().zip(words.chunks_mut(T::WORDS)) {
+            value.to_words(chunk);
+        }
+    }
+
+    fn from_words(words: &[u32]) -> Self {
+        core::array::from_fn(|i| T::from_words(&words[i * T::WORDS..]))
+    }
+}
+
+/// Typed location of an entry in a [`ParamStore`]
+pub struct ParamKey<T> {
+    /// First word of the entry
+    offset: usize,
+    /// Entry type
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ParamKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ParamKey<T> {}
+
+impl<T: ParamValue> ParamKey<T> {
+    /// Entry at word 0
+    #[must_use]
+    pub const fn first() -> Self {
+        Self::at(0)
+    }
+
+    /// Entry at word `offset`
+    ///
+    /// # Panics
+    ///
+    /// If `T` is larger than [`MAX_PARAM_WORDS`] (at compile time for
+    /// `const` keys).
+    #[must_use]
+    pub const fn at(offset: usize) -> Self {
+        assert!(T::WORDS <= MAX_PARAM_WORDS, "parameter entry too large");
+        Self {
+            offset,
+            _value: PhantomData,
+        }
+    }
+
+    /// Entry of type `U` right after this one
+    #[must_use]
+    pub const fn next<U: ParamValue>(self) -> ParamKey<U> {
+        ParamKey::at(self.end())
+    }
+
+    /// First word of the entry
+    #[must_use]
+    pub const fn offset(self) -> usize {
+        self.offset
+    }
+
+    /// Word just past the entry (table size when it is the last one)
+    #[must_use]
+    pub const fn end(self) -> usize {
+        self.offset + T::WORDS
+    }
+}
+
+/// Parameter table of `N` words, read lock-free, updated by one writer
+pub struct ParamStore<const N: usize> {
+    /// Live table (`version & 1`) and spare table
+    tables: [[AtomicU32; N]; 2],
+    /// Updates published (selects the live table)
+    version: AtomicU32,
+}
+
+impl<const N: usize> Default for ParamStore<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ParamStore<N> {
+    /// All-zero table (usable in `static` initializers)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tables: [
+                [const { AtomicU32::new(0) }; N],
+                [const { AtomicU32::new(0) }; N],
+            ],
+            version: AtomicU32::new(0),
+        }
+    }
+
+    /// Current value of `key` (None if it lies outside the table)
+    pub fn get<T: ParamValue>(&self, key: ParamKey<T>) -> Option<T> {
+        self.read(|p| p.get(key))
+    }
+
+    /// Run `f` on a consistent view of the table (any task / ISR)
+    ///
+    /// `f` is run again if an update was published while it read, so it
+    /// should only read.
+    pub fn read<R>(&self, f: impl Fn(&ParamView<'_, N>) -> R) -> R {
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            let result = f(&ParamView {
+                words: &self.tables[version as usize & 1],
+            });
+            fence(Ordering::Acquire);
+            if self.version.load(Ordering::Relaxed) == version {
+                return result;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Change entries and publish them together (single writer)
+    ///
+    /// `f` edits a copy of the live table; readers keep seeing the old
+    /// values until it returns. Must only be called from one task.
+    pub fn update(&self, f: impl FnOnce(&mut ParamTxn<'_, N>)) {
+        let version = self.version.load(Ordering::Relaxed);
+        let live = &self.tables[version as usize & 1];
+        let spare = &self.tables[version.wrapping_add(1) as usize & 1];
+        // 前回の公開より後に書き込む (古い版を読む reader が検出できる)
+        fence(Ordering::Release);
+        for (dst, src) in spare.iter().zip(live) {
+            dst.store(src.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        f(&mut ParamTxn { words: spare });
+        self.version
+            .store(version.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Set one entry (see [`update`](Self::update))
+    ///
+    /// Returns false, publishing nothing, if `key` lies outside the table.
+    pub fn set<T: ParamValue>(&self, key: ParamKey<T>, value: T) -> bool {
+        if key.end() > N {
+            return false;
+        }
+        self.update(|p| {
+            p.set(key, value);
+        });
+        true
+    }
+
+    /// Updates published so far
+    ///
+    /// A reader compares it across jobs to notice new configuration.
+    pub fn version(&self) -> u32 {
+        self.version.load(Ordering::Acquire)
+    }
+}
+
+/// Read-only view of one version of a [`ParamStore`]
+pub struct ParamView<'a, const N: usize> {
+    /// Table being read
+    words: &'a [AtomicU32; N],
+}
+
+impl<const N: usize> ParamView<'_, N> {
+    /// Value of `key` (None if it lies outside the table)
+    pub fn get<T: ParamValue>(&self, key: ParamKey<T>) -> Option<T> {
+        load(self.words, key)
+    }
+}
+
+/// Pending update of a [`ParamStore`]
+pub struct ParamTxn<'a, const N: usize> {
+    /// Spare table being written
+    words: &'a [AtomicU32; N],
+}
+
+impl<const N: usize> ParamTxn<'_, N> {
+    /// Value of `key` including changes made so far in this update
+    pub fn get<T: ParamValue>(&self, key: ParamKey<T>) -> Option<T> {
+        load(self.words, key)
+    }
+
+    /// Set `key` (false if it lies outside the table)
+    pub fn set<T: ParamValue>(&mut self, key: ParamKey<T>, value: T) -> bool {
+        let Some(words) = self.words.get(key.offset..key.end()) else {
+            return false;
+        };
+        let mut buf = [0u32; MAX_PARAM_WORDS];
+        value.to_words(&mut buf[..T::WORDS]);
+        for (dst, &word) in words.iter().zip(&buf) {
+            dst.store(word, Ordering::Relaxed);
+        }
+        true
+    }
+}
+
+/// Decode `key` from `table`
+fn load<T: ParamValue>(table: &[AtomicU32], key: ParamKey<T>) -> Option<T> {
+    let words = table.get(key.offset..key.end())?;
+    let mut buf = [0u32; MAX_PARAM_WORDS];
+    for (dst, src) in buf.iter_mut().zip(words) {
+        *dst = src.load(Ordering::Relaxed);
+    }
+    Some(T::from_words(&buf[..T::WORDS]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAIN: ParamKey<f32> = ParamKey::first();
+    const OFFSET: ParamKey<i16> = GAIN.next();
+    const ENABLED: ParamKey<bool> = OFFSET.next();
+    const SERIAL: ParamKey<u64> = ENABLED.next();
+    const AXIS: ParamKey<[f32; 2]> = SERIAL.next();
+    const WORDS: usize = AXIS.end();
+
+    #[test]
+    fn test_typed_entries_round_trip() {
+        assert_eq!((SERIAL.offset(), AXIS.offset(), WORDS), (3, 5, 7));
+        let store = ParamStore::<WORDS>::new();
+        assert_eq!(store.get(GAIN), Some(0.0));
+        assert_eq!(store.get(ENABLED), Some(false));
+        store.update(|p| {
+            assert!(p.set(GAIN, -2.5));
+            assert!(p.set(OFFSET, -300));
+            assert!(p.set(ENABLED, true));
+            assert!(p.set(SERIAL, 0x1234_5678_9abc_def0));
+            assert!(p.set(AXIS, [0.5, -1.0]));
+        });
+        assert_eq!(store.get(GAIN), Some(-2.5));
+        assert_eq!(store.get(OFFSET), Some(-300));
+        assert_eq!(store.get(ENABLED), Some(true));
+        assert_eq!(store.get(SERIAL), Some(0x1234_5678_9abc_def0));
+        assert_eq!(store.get(AXIS), Some([0.5, -1.0]));
+        assert_eq!(store.version(), 1);
+    }
+
+    #[test]
+    fn test_key_outside_table() {
+        let store = ParamStore::<3>::new();
+        assert_eq!(store.get(SERIAL), None);
+        assert!(!store.set(SERIAL, 1));
+        assert_eq!(store.version(), 0);
+        assert!(store.set(ENABLED, true));
+        assert_eq!(store.get(ENABLED), Some(true));
+    }
+
+    #[test]
+    fn test_update_keeps_other_entries() {
+        let store = ParamStore::<WORDS>::new();
+        store.set(GAIN, 1.0);
+        store.set(OFFSET, 7);
+        store.update(|p| {
+            let offset = p.get(OFFSET).unwrap();
+            p.set(OFFSET, offset * 2);
+        });
+        assert_eq!((store.get(GAIN), store.get(OFFSET)), (Some(1.0), Some(14)));
+        assert_eq!(store.version(), 3);
+    }
+
+    #[test]
+    fn test_reader_during_update_sees_old_version() {
+        let store = ParamStore::<WORDS>::new();
+        store.set(GAIN, 1.0);
+        store.update(|p| {
+            p.set(GAIN, 2.0);
+            // 更新途中に割り込んだ ISR 相当: 待たずに旧版を読む
+            assert_eq!(store.get(GAIN), Some(1.0));
+            p.set(OFFSET, 5);
+        });
+        assert_eq!(
+            store.read(|p| (p.get(GAIN), p.get(OFFSET))),
+            (Some(2.0), Some(5))
+        );
+    }
+
+    #[test]
+    fn test_concurrent_reads_never_torn() {
+        extern crate std;
+        use std::thread;
+
+        const A: ParamKey<i32> = ParamKey::first();
+        const B: ParamKey<i32> = A.next();
+        static STORE: ParamStore<2> = ParamStore::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=20_000 {
+                    STORE.update(|p| {
+                        p.set(A, i);
+                        p.set(B, -i);
+                    });
+                }
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    for _ in 0..20_000 {
+                        let (a, b) = STORE.read(|p| (p.get(A).unwrap(), p.get(B).unwrap()));
+                        // 両エントリが同じ更新由来 (ちぎれ無し)
+                        assert_eq!(a, -b);
+                        assert!(a >= last);
+                        last = a;
+                    }
+                });
+            }
+        });
+        assert_eq!(STORE.get(A), Some(20_000));
+    }
+}
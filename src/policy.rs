@@ -0,0 +1,242 @@
+//! Pluggable dispatch order for the scheduler
+//!
+//! The scheduler does everything around a dispatch — releases, inputs,
+//! affinity, budget exclusion, deadline accounting — and leaves the
+//! choice among the dispatchable tasks to a [`SchedulingPolicy`].
+//! [`Scheduler`] and [`SizedKernel`](crate::kernel::SizedKernel) take the
+//! policy as a type parameter, defaulting to [`Rms`], so the choice is
+//! monomorphized and inlined: a zero-sized policy costs neither memory
+//! nor an indirect call.
+//!
+//! - [`Rms`]: fixed priorities (rate-monotonic when priorities follow
+//!   the periods), ties to the lower slot — the kernel's behaviour;
+//! - [`Edf`]: earliest deadline first on each job's implicit deadline,
+//!   ties by priority; event-driven tasks (period 0) have no deadline and
+//!   run after every periodic job;
+//! - [`RunQueue`]: the [`Rms`] order from a priority-ordered ready
+//!   bitmap the scheduler updates, so a tick does not rescan the table.
+//!
+//! A policy may carry state (weights, a temperature reading) and is
+//! reached through [`Scheduler::policy_mut`]. Schedulability analysis
+//! (`is_schedulable`, [`dmda`](crate::dmda)) assumes fixed priorities and
+//! does not follow a custom order.
+//!
+//! ```rust
+//! use alice_rtos::policy::SchedulingPolicy;
+//! use alice_rtos::timer::WrappingInstant;
+//! use alice_rtos::{Scheduler, Task, TaskMask, TaskPriority};
+//!
+//! /// Skip tasks marked as heating the die while any other one is ready
+//! struct Thermal {
+//!     hot: TaskMask,
+//! }
+//!
+//! impl SchedulingPolicy for Thermal {
+//!     fn select(&self, tasks: &[Task], ready: TaskMask, _now: WrappingInstant) -> Option<usize> {
+//!         let cool = ready & !self.hot;
+//!         let pick = if cool != 0 { cool } else { ready };
+//!         (0..tasks.len())
+//!             .filter(|&i| pick & (1 << i) != 0)
+//!             .min_by_key(|&i| (tasks[i].priority, i))
+//!     }
+//! }
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut sched = Scheduler::with_policy(Thermal { hot: 0 });
+//! let fft = sched.register(Task::new(b"fft", noop, TaskPriority::HIGH, 1_000, 100)).unwrap();
+//! let log = sched.register(Task::new(b"log", noop, TaskPriority::LOW, 1_000, 100)).unwrap();
+//! sched.policy_mut().hot = 1 << fft;
+//! assert_eq!(sched.tick(0), Some(log));
+//! assert_eq!(sched.tick(10), Some(fft));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::runqueue::RunQueue;
+#[cfg(doc)]
+use crate::scheduler::Scheduler;
+use crate::task::{Task, TaskMask, TaskPriority};
+use crate::timer::WrappingInstant;
+
+/// Dispatch order: picks the next task among the dispatchable ones
+pub trait SchedulingPolicy {
+    /// Task to dispatch among `ready` (bit i = `tasks[i]`, never 0)
+    ///
+    /// Every task in `ready` is released, has its inputs and may run on
+    /// the dispatching core. `now` is the scheduler time. Returning None
+    /// or a task outside `ready` leaves the core idle for this tick.
+    fn select(&self, tasks: &[Task], ready: TaskMask, now: WrappingInstant) -> Option<usize>;
+
+    /// Run queue the scheduler keeps up to date instead of scanning the
+    /// table each tick ([`RunQueue`]); None for a stateless policy
+    fn run_queue_mut(&mut self) -> Option<&mut RunQueue> {
+        None
+    }
+
+    /// Read access to the [`run_queue_mut`](Self::run_queue_mut) queue
+    fn run_queue(&self) -> Option<&RunQueue> {
+        None
+    }
+}
+
+/// Tasks in `ready` with their slots, lowest slot first
+///
+/// Find-first-set over the mask: the built-in policies take one step per
+/// ready task rather than one per table slot.
+fn ready_tasks(tasks: &[Task], ready: TaskMask) -> impl Iterator<Item = (usize, &Task)> {
+    let mut bits = ready;
+    core::iter::from_fn(move || {
+        while bits != 0 {
+            let i = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            if let Some(task) = tasks.get(i) {
+                return Some((i, task));
+            }
+        }
+        None
+    })
+}
+
+/// Fixed-priority order: highest priority, then lowest slot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rms;
+
+impl SchedulingPolicy for Rms {
+    #[inline]
+    fn select(&self, tasks: &[Task], ready: TaskMask, _now: WrappingInstant) -> Option<usize> {
+        let mut best: Option<(TaskPriority, usize)> = None;
+        for (i, task) in ready_tasks(tasks, ready) {
+            if best.is_none_or(|(priority, _)| task.priority < priority) {
+                best = Some((task.priority, i));
+            }
+        }
+        best.map(|(_, i)| i)
+    }
+}
+
+/// Earliest-deadline-first order on implicit (period) deadlines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Edf;
+
+impl Edf {
+    /// Does `a` go before `b`?
+    fn earlier(a: &Task, b: &Task) -> bool {
+        match (a.period_us, b.period_us) {
+            (0, 0) => a.priority < b.priority,
+            (0, _) => false,
+            (_, 0) => true,
+            _ => {
+                let (da, db) = (a.job_deadline(), b.job_deadline());
+                da.is_before(db) || (da == db && a.priority < b.priority)
+            }
+        }
+    }
+}
+
+impl SchedulingPolicy for Edf {
+    #[inline]
+    fn select(&self, tasks: &[Task], ready: TaskMask, _now: WrappingInstant) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, task) in ready_tasks(tasks, ready) {
+            if best.is_none_or(|b| Self::earlier(task, &tasks[b])) {
+                best = Some(i);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{Kernel, SizedKernel};
+    use crate::scheduler::Scheduler;
+    use crate::task::TaskPriority;
+    use crate::timer::SysTimer;
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_edf_follows_deadlines_not_priorities() {
+        // RMS なら優先度の高い slow が先、EDF なら締め切りの近い fast が先
+        let mut edf = Scheduler::with_policy(Edf);
+        let slow = edf
+            .register(Task::new(b"slow", noop, TaskPriority::HIGH, 1_000, 100))
+            .unwrap();
+        let fast = edf
+            .register(Task::new(b"fast", noop, TaskPriority::LOW, 300, 100))
+            .unwrap();
+        assert_eq!(edf.tick(0), Some(fast));
+        assert_eq!(edf.tick(10), Some(slow));
+
+        let mut rms = Scheduler::new();
+        rms.register(Task::new(b"slow", noop, TaskPriority::HIGH, 1_000, 100));
+        rms.register(Task::new(b"fast", noop, TaskPriority::LOW, 300, 100));
+        assert_eq!(rms.tick(0), Some(slow));
+    }
+
+    #[test]
+    fn test_edf_runs_event_tasks_last() {
+        let mut edf = Scheduler::with_policy(Edf);
+        let ev = edf
+            .register(Task::new(b"ev", noop, TaskPriority::CRITICAL, 0, 10))
+            .unwrap();
+        let per = edf
+            .register(Task::new(b"per", noop, TaskPriority::LOW, 5_000, 10))
+            .unwrap();
+        assert_eq!(edf.tick(0), Some(per));
+        assert_eq!(edf.tick(10), Some(ev));
+    }
+
+    #[test]
+    fn test_kernel_with_edf_policy() {
+        let mut k: SizedKernel<64, Edf> = SizedKernel::with_policy(SysTimer::software(), Edf);
+        let slow = k
+            .add_task(b"slow", noop, TaskPriority::CRITICAL, 2_000, 100)
+            .unwrap();
+        let fast = k
+            .add_task(b"fast", noop, TaskPriority::LOW, 500, 100)
+            .unwrap();
+        assert_eq!(k.tick(0), Some(fast));
+        assert_eq!(k.tick(10), Some(slow));
+        // 既定の Kernel は RMS のまま
+        let mut rms = Kernel::testing();
+        rms.add_task(b"slow", noop, TaskPriority::CRITICAL, 2_000, 100);
+        rms.add_task(b"fast", noop, TaskPriority::LOW, 500, 100);
+        assert_eq!(rms.tick(0), Some(slow));
+    }
+
+    #[test]
+    fn test_select_only_visits_ready_bits() {
+        let tasks = [
+            Task::new(b"a", noop, TaskPriority::CRITICAL, 1_000, 10),
+            Task::new(b"b", noop, TaskPriority::LOW, 1_000, 10),
+            Task::new(b"c", noop, TaskPriority::HIGH, 1_000, 10),
+            Task::new(b"d", noop, TaskPriority::HIGH, 1_000, 10),
+        ];
+        let now = WrappingInstant(0);
+        // 同じ優先度なら若いスロット、表の外のビットは無視
+        assert_eq!(Rms.select(&tasks, 0b1_1110, now), Some(2));
+        assert_eq!(Rms.select(&tasks, 0b1_0010, now), Some(1));
+        assert_eq!(Rms.select(&tasks, 1 << 15, now), None);
+        assert_eq!(Edf.select(&tasks, 0b1_1000, now), Some(3));
+        assert_eq!(
+            ready_tasks(&tasks, 0b1011).map(|(i, _)| i).sum::<usize>(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_policies_are_zero_sized() {
+        assert_eq!(
+            core::mem::size_of::<SizedKernel<64, Edf>>(),
+            core::mem::size_of::<SizedKernel<64>>()
+        );
+        assert_eq!(
+            core::mem::size_of::<Scheduler<Edf>>(),
+            core::mem::size_of::<Scheduler>()
+        );
+        assert_eq!(core::mem::size_of::<Rms>(), 0);
+    }
+}
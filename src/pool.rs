@@ -0,0 +1,280 @@
+//! Fixed-size block pool
+//!
+//! Statically sized pool of `COUNT` blocks of `BLOCK` bytes for buffers
+//! larger than a task's scratch slice, without a heap. Free blocks form a
+//! lock-free stack (Treiber stack) whose head carries a 16-bit tag against
+//! ABA, so alloc and free are O(1) and safe from any task or ISR on cores
+//! with compare-and-swap.
+//!
+//! Blocks are handed out as [`PoolBlock`] guards that return themselves to
+//! the pool on drop.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicU16, AtomicU32, AtomicUsize, Ordering};
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+/// End-of-list marker in the free stack
+const NIL: u32 = 0xFFFF;
+
+/// Lock-free pool of `COUNT` blocks of `BLOCK` bytes
+pub struct Pool<const BLOCK: usize, const COUNT: usize> {
+    /// Block storage
+    blocks: [UnsafeCell<[u8; BLOCK]>; COUNT],
+    /// Free-stack links (index of the next free block)
+    next: [AtomicU16; COUNT],
+    /// Free-stack head: `tag << 16 | index`
+    head: AtomicU32,
+    /// Blocks currently allocated
+    in_use: AtomicUsize,
+    /// Highest `in_use` observed
+    peak: AtomicUsize,
+    /// Allocations refused because the pool was empty
+    exhausted: AtomicU32,
+}
+
+// SAFETY: a block index is owned by exactly one `PoolBlock` between a
+// successful pop and the matching push, so block contents are never
+// shared; the free stack itself is only touched through atomics.
+unsafe impl<const BLOCK: usize, const COUNT: usize> Sync for Pool<BLOCK, COUNT> {}
+
+impl<const BLOCK: usize, const COUNT: usize> Default for Pool<BLOCK, COUNT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const BLOCK: usize, const COUNT: usize> Pool<BLOCK, COUNT> {
+    /// Pool with every block free (usable in `static` initializers)
+    ///
+    /// # Panics
+    ///
+    /// If `COUNT` exceeds 65 534 blocks (0xFFFF marks the end of the free
+    /// list; a compile error in a `static`).
+    #[must_use]
+    pub const fn new() -> Self {
+        assert!(COUNT < NIL as usize, "pool holds at most 65534 blocks");
+        let mut next = [const { AtomicU16::new(0) }; COUNT];
+        let mut i = 0;
+        while i < COUNT {
+            next[i] = AtomicU16::new(if i + 1 < COUNT {
+                (i + 1) as u16
+            } else {
+                NIL as u16
+            });
+            i += 1;
+        }
+        Self {
+            blocks: [const { UnsafeCell::new([0u8; BLOCK]) }; COUNT],
+            next,
+            head: AtomicU32::new(if COUNT > 0 { 0 } else { NIL }),
+            in_use: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            exhausted: AtomicU32::new(0),
+        }
+    }
+
+    /// Take a free block (contents are whatever the last owner left)
+    ///
+    /// Returns None when the pool is exhausted.
+    pub fn alloc(&self) -> Option<PoolBlock<'_, BLOCK, COUNT>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let idx = head & 0xFFFF;
+            if idx == NIL {
+                self.exhausted.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            let next = self.next[idx as usize].load(Ordering::Relaxed) as u32;
+            let new = ((head >> 16).wrapping_add(1) << 16) | next;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    let used = self.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+                    self.peak.fetch_max(used, Ordering::Relaxed);
+                    return Some(PoolBlock {
+                        pool: self,
+                        idx: idx as u16,
+                    });
+                }
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Push block `idx` back onto the free stack
+    fn free(&self, idx: u16) {
+        // Count first so `in_use` never exceeds the blocks actually owned
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            self.next[idx as usize].store((head & 0xFFFF) as u16, Ordering::Relaxed);
+            let new = ((head >> 16).wrapping_add(1) << 16) | idx as u32;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Blocks currently allocated
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Relaxed)
+    }
+
+    /// Blocks currently free
+    pub fn available(&self) -> usize {
+        COUNT.saturating_sub(self.in_use())
+    }
+
+    /// Most blocks ever allocated at once
+    pub fn peak_in_use(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Allocations refused because the pool was empty
+    pub fn exhausted_count(&self) -> u32 {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    /// Total blocks
+    pub const fn capacity(&self) -> usize {
+        COUNT
+    }
+
+    /// Bytes per block
+    pub const fn block_size(&self) -> usize {
+        BLOCK
+    }
+}
+
+/// An allocated block; returned to its pool on drop
+pub struct PoolBlock<'a, const BLOCK: usize, const COUNT: usize> {
+    pool: &'a Pool<BLOCK, COUNT>,
+    idx: u16,
+}
+
+impl<const BLOCK: usize, const COUNT: usize> PoolBlock<'_, BLOCK, COUNT> {
+    /// Index of the block within its pool
+    pub const fn index(&self) -> usize {
+        self.idx as usize
+    }
+}
+
+impl<const BLOCK: usize, const COUNT: usize> Deref for PoolBlock<'_, BLOCK, COUNT> {
+    type Target = [u8; BLOCK];
+
+    fn deref(&self) -> &[u8; BLOCK] {
+        // SAFETY: this guard exclusively owns block `idx` (see `Sync` impl).
+        unsafe { &*self.pool.blocks[self.idx as usize].get() }
+    }
+}
+
+impl<const BLOCK: usize, const COUNT: usize> DerefMut for PoolBlock<'_, BLOCK, COUNT> {
+    fn deref_mut(&mut self) -> &mut [u8; BLOCK] {
+        // SAFETY: this guard exclusively owns block `idx` (see `Sync` impl).
+        unsafe { &mut *self.pool.blocks[self.idx as usize].get() }
+    }
+}
+
+impl<const BLOCK: usize, const COUNT: usize> Drop for PoolBlock<'_, BLOCK, COUNT> {
+    fn drop(&mut self) {
+        self.pool.free(self.idx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_write_free() {
+        let pool = Pool::<64, 4>::new();
+        let mut block = pool.alloc().unwrap();
+        block[0] = 0xAB;
+        block[63] = 0xCD;
+        assert_eq!(block.len(), 64);
+        assert_eq!(pool.in_use(), 1);
+        drop(block);
+        assert_eq!(pool.in_use(), 0);
+        assert_eq!(pool.available(), 4);
+    }
+
+    #[test]
+    fn test_exhaustion_statistics() {
+        let pool = Pool::<16, 3>::new();
+        let a = pool.alloc().unwrap();
+        let b = pool.alloc().unwrap();
+        let c = pool.alloc().unwrap();
+        assert!(pool.alloc().is_none());
+        assert!(pool.alloc().is_none());
+        assert_eq!(pool.exhausted_count(), 2);
+        assert_eq!(pool.peak_in_use(), 3);
+        drop((a, b));
+        assert_eq!(pool.available(), 2);
+        // ピークは解放後も保持
+        assert_eq!(pool.peak_in_use(), 3);
+        drop(c);
+    }
+
+    #[test]
+    fn test_freed_block_reused_lifo() {
+        let pool = Pool::<8, 4>::new();
+        let a = pool.alloc().unwrap();
+        let idx = a.index();
+        drop(a);
+        assert_eq!(pool.alloc().unwrap().index(), idx);
+    }
+
+    #[test]
+    fn test_distinct_blocks() {
+        let pool = Pool::<8, 4>::new();
+        let blocks: [_; 4] = core::array::from_fn(|_| pool.alloc().unwrap());
+        let mut seen = [false; 4];
+        for b in &blocks {
+            assert!(!seen[b.index()]);
+            seen[b.index()] = true;
+        }
+    }
+
+    #[test]
+    fn test_static_pool_and_zero_count() {
+        static POOL: Pool<128, 2> = Pool::new();
+        assert_eq!(POOL.capacity(), 2);
+        assert_eq!(POOL.block_size(), 128);
+        assert!(POOL.alloc().is_some());
+        let empty = Pool::<8, 0>::new();
+        assert!(empty.alloc().is_none());
+    }
+
+    #[test]
+    fn test_concurrent_alloc_free_exclusive() {
+        extern crate std;
+        use std::thread;
+
+        let pool = Pool::<32, 8>::new();
+        thread::scope(|s| {
+            for t in 0..4u8 {
+                let pool = &pool;
+                s.spawn(move || {
+                    for _ in 0..5_000 {
+                        if let Some(mut block) = pool.alloc() {
+                            block.fill(t);
+                            core::hint::spin_loop();
+                            // 他スレッドと同じブロックを共有していない
+                            assert!(block.iter().all(|&b| b == t));
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.in_use(), 0);
+        assert!(pool.peak_in_use() <= 8);
+    }
+}
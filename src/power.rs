@@ -0,0 +1,321 @@
+//! Power management — sleep states chosen from slack time
+//!
+//! Between jobs the kernel knows exactly how long it may stay idle: the
+//! slack up to [`Kernel::next_wakeup_us`](crate::kernel::Kernel::next_wakeup_us).
+//! A [`PowerPolicy`] turns that slack into a sleep state (WFI, stop,
+//! standby) and enters it; deeper states save more power but take longer
+//! to wake from, so a policy only picks them when the slack covers the
+//! exit latency.
+//!
+//! [`PowerManager`] drives the kernel with a policy and accounts the time
+//! spent in each state, reported through
+//! [`KernelStats::sleep`](crate::kernel::KernelStats::sleep).
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{Kernel, KernelStats};
+
+/// Low-power state entered while the kernel is idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SleepState {
+    /// Core clock gated, wakes on any interrupt (Cortex-M `WFI`)
+    Wfi,
+    /// Clocks stopped, RAM and registers retained
+    Stop,
+    /// Deepest state: only the wake-up timer keeps running
+    Standby,
+}
+
+impl SleepState {
+    /// Number of sleep states
+    pub const COUNT: usize = 3;
+
+    /// Slot of this state in per-state tables
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Chooses and enters sleep states for idle periods
+pub trait PowerPolicy {
+    /// Sleep state for an idle period of `slack_us` (`u64::MAX` = nothing
+    /// scheduled); `None` keeps the core awake
+    fn select(&mut self, slack_us: u64) -> Option<SleepState>;
+
+    /// Enter `state` for at most `slack_us` and return the time actually
+    /// slept (an interrupt may wake the core early)
+    ///
+    /// The default does not touch the hardware and reports the full
+    /// slack, which is what host simulation needs.
+    fn enter(&mut self, state: SleepState, slack_us: u64) -> u64 {
+        let _ = state;
+        slack_us
+    }
+}
+
+/// Policy picking the deepest state whose threshold the slack reaches
+///
+/// Thresholds should include each state's wake-up latency.
+#[derive(Debug, Clone, Copy)]
+pub struct ThresholdPolicy {
+    /// Minimum slack for `Wfi` (µs)
+    pub wfi_us: u64,
+    /// Minimum slack for `Stop` (µs)
+    pub stop_us: u64,
+    /// Minimum slack for `Standby` (µs)
+    pub standby_us: u64,
+}
+
+impl ThresholdPolicy {
+    /// Policy with the given minimum slack per state
+    #[must_use]
+    pub const fn new(wfi_us: u64, stop_us: u64, standby_us: u64) -> Self {
+        Self {
+            wfi_us,
+            stop_us,
+            standby_us,
+        }
+    }
+}
+
+impl PowerPolicy for ThresholdPolicy {
+    fn select(&mut self, slack_us: u64) -> Option<SleepState> {
+        if slack_us >= self.standby_us {
+            Some(SleepState::Standby)
+        } else if slack_us >= self.stop_us {
+            Some(SleepState::Stop)
+        } else if slack_us >= self.wfi_us {
+            Some(SleepState::Wfi)
+        } else {
+            None
+        }
+    }
+}
+
+/// Time spent in each sleep state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SleepStats {
+    /// Microseconds slept per state
+    time_us: [u64; SleepState::COUNT],
+    /// Times each state was entered
+    entries: [u32; SleepState::COUNT],
+}
+
+impl SleepStats {
+    /// Microseconds spent in `state`
+    #[must_use]
+    pub const fn time_us(&self, state: SleepState) -> u64 {
+        self.time_us[state.index()]
+    }
+
+    /// Times `state` was entered
+    #[must_use]
+    pub const fn entries(&self, state: SleepState) -> u32 {
+        self.entries[state.index()]
+    }
+
+    /// Microseconds spent in any sleep state
+    #[must_use]
+    pub fn total_sleep_us(&self) -> u64 {
+        self.time_us.iter().sum()
+    }
+
+    /// Accounting from per-state totals (telemetry decoding, deltas)
+    pub(crate) const fn from_parts(
+        time_us: [u64; SleepState::COUNT],
+        entries: [u32; SleepState::COUNT],
+    ) -> Self {
+        Self { time_us, entries }
+    }
+
+    /// Account one stay of `slept_us` in `state`
+    pub fn record(&mut self, state: SleepState, slept_us: u64) {
+        self.time_us[state.index()] += slept_us;
+        self.entries[state.index()] += 1;
+    }
+}
+
+/// Drives a kernel's idle periods through a [`PowerPolicy`]
+pub struct PowerManager<P> {
+    /// Sleep-state policy
+    policy: P,
+    /// Accounting since creation or the last `reset_stats`
+    stats: SleepStats,
+}
+
+impl<P: PowerPolicy> PowerManager<P> {
+    /// Manager using `policy`
+    #[must_use]
+    pub const fn new(policy: P) -> Self {
+        Self {
+            policy,
+            stats: SleepStats {
+                time_us: [0; SleepState::COUNT],
+                entries: [0; SleepState::COUNT],
+            },
+        }
+    }
+
+    /// Idle until the kernel's next wake-up, if the policy allows sleeping
+    ///
+    /// Call from the idle loop after the ready tasks have run. Returns the
+    /// state entered, or `None` if there was no slack or the policy chose
+    /// to stay awake.
+    pub fn idle(&mut self, kernel: &Kernel) -> Option<SleepState> {
+        self.sleep(Self::slack_us(kernel))
+    }
+
+    /// Run `kernel` tickless for `total_us`, sleeping through every gap
+    ///
+    /// Like [`Kernel::run_tickless`], with the sleep accounting of this
+    /// run in [`KernelStats::sleep`].
+    pub fn run_tickless(&mut self, kernel: &mut Kernel, total_us: u64) -> KernelStats {
+        let before = self.stats;
+        let mut elapsed = 0u64;
+        let mut tasks_executed = 0u64;
+
+        while elapsed < total_us {
+            let remaining = total_us - elapsed;
+            self.sleep(Self::slack_us(kernel).min(remaining));
+            let (delta, executed) = kernel.tick_tickless(remaining);
+            if executed.is_some() {
+                tasks_executed += 1;
+            }
+            elapsed += delta;
+        }
+
+        let mut stats = kernel.stats(elapsed, tasks_executed);
+        for (i, time) in stats.sleep.time_us.iter_mut().enumerate() {
+            *time = self.stats.time_us[i] - before.time_us[i];
+            stats.sleep.entries[i] = self.stats.entries[i] - before.entries[i];
+        }
+        stats
+    }
+
+    /// Accumulated sleep accounting
+    #[must_use]
+    pub const fn stats(&self) -> &SleepStats {
+        &self.stats
+    }
+
+    /// Clear the sleep accounting
+    pub fn reset_stats(&mut self) {
+        self.stats = SleepStats::default();
+    }
+
+    /// The policy
+    pub const fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Time until the kernel has work (`u64::MAX` = nothing scheduled)
+    fn slack_us(kernel: &Kernel) -> u64 {
+        let now = kernel.timer.now_us();
+        kernel
+            .next_wakeup_us()
+            .map_or(u64::MAX, |at| at.saturating_sub(now))
+    }
+
+    /// Consult the policy for `slack_us` of idle time and account it
+    fn sleep(&mut self, slack_us: u64) -> Option<SleepState> {
+        if slack_us == 0 {
+            return None;
+        }
+        let state = self.policy.select(slack_us)?;
+        let slept = self.policy.enter(state, slack_us);
+        self.stats.record(state, slept);
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn policy() -> ThresholdPolicy {
+        ThresholdPolicy::new(10, 500, 5_000)
+    }
+
+    #[test]
+    fn test_threshold_policy_selects_deepest() {
+        let mut p = policy();
+        assert_eq!(p.select(5), None);
+        assert_eq!(p.select(10), Some(SleepState::Wfi));
+        assert_eq!(p.select(499), Some(SleepState::Wfi));
+        assert_eq!(p.select(500), Some(SleepState::Stop));
+        assert_eq!(p.select(u64::MAX), Some(SleepState::Standby));
+    }
+
+    #[test]
+    fn test_idle_uses_kernel_slack() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 100);
+        let mut pm = PowerManager::new(policy());
+        // 未実行のタスクがある → スラック 0 で眠らない
+        assert_eq!(pm.idle(&kernel), None);
+        kernel.tick(0);
+        // 次の起床まで 1 000 µs → Stop
+        assert_eq!(pm.idle(&kernel), Some(SleepState::Stop));
+        assert_eq!(pm.stats().time_us(SleepState::Stop), 1_000);
+        assert_eq!(pm.stats().entries(SleepState::Stop), 1);
+    }
+
+    #[test]
+    fn test_idle_without_tasks_sleeps_deepest() {
+        let kernel = Kernel::testing();
+        let mut pm = PowerManager::new(policy());
+        assert_eq!(pm.idle(&kernel), Some(SleepState::Standby));
+    }
+
+    #[test]
+    fn test_run_tickless_accounts_gaps() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 100);
+        let mut pm = PowerManager::new(policy());
+        let stats = pm.run_tickless(&mut kernel, 10_000);
+        // t = 0, 1 000, …, 10 000 で実行、その間の 10 区間で Stop
+        assert_eq!(stats.tasks_executed, 11);
+        assert_eq!(stats.sleep.entries(SleepState::Stop), 10);
+        assert_eq!(stats.sleep.time_us(SleepState::Stop), 10_000);
+        assert_eq!(stats.sleep.total_sleep_us(), 10_000);
+        // 2 回目の実行は差分だけを報告
+        let again = pm.run_tickless(&mut kernel, 2_000);
+        assert_eq!(again.sleep.entries(SleepState::Stop), 2);
+        assert_eq!(pm.stats().entries(SleepState::Stop), 12);
+    }
+
+    #[test]
+    fn test_early_wakeup_reported_by_policy() {
+        struct EarlyWake;
+        impl PowerPolicy for EarlyWake {
+            fn select(&mut self, _: u64) -> Option<SleepState> {
+                Some(SleepState::Wfi)
+            }
+            fn enter(&mut self, _: SleepState, slack_us: u64) -> u64 {
+                slack_us / 2
+            }
+        }
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop, TaskPriority::HIGH, 400, 10);
+        kernel.tick(0);
+        let mut pm = PowerManager::new(EarlyWake);
+        pm.idle(&kernel);
+        assert_eq!(pm.stats().time_us(SleepState::Wfi), 200);
+        pm.reset_stats();
+        assert_eq!(pm.stats().total_sleep_us(), 0);
+    }
+
+    #[test]
+    fn test_run_for_reports_no_sleep() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 100);
+        let stats = kernel.run_for(5_000, 100);
+        assert_eq!(stats.sleep, SleepStats::default());
+    }
+}
@@ -1,14 +1,74 @@
 //! Convenience re-export (= `use alice_rtos::prelude::*;` で主要 API 一括取得)
 //!
-//! RTOS 6 core module (task / scheduler / timer / spsc / kernel / priority_inherit +
+//! RTOS core module (task / scheduler / policy / runqueue / sched_lock / timeout / timer / spsc / aligned_ring / block / channel / mailbox / double_buffer / params / broadcast / pool / power / overload / redundancy / mixed_criticality / slack / shell / config / activation / deadlines / soft_timer / profiler / bist / budget / calibrate / replay / gantt / graph / snapshot / stats_task / telemetry / text / threshold / trace / wheel / lifecycle / log_task / error / fault / init / integrity / kernel / domain / doorbell / router / priority_inherit +
 //! dmda 分析) から主要型 + 関数を re-export する
 //! `edge_tasks` / `motion_tasks` / `synth_tasks` / `ffi` / `python` は
 //! feature-gated
 
+pub use crate::activation::ActivationQueue;
+pub use crate::aligned_ring::{
+    Align128, Align16, Align32, Align64, AlignedConsumer, AlignedProducer, AlignedSpscRing,
+    Alignment,
+};
+pub use crate::bist::BistFailures;
+pub use crate::block::BlockSizer;
+pub use crate::broadcast::{Broadcast, BroadcastReceiver, BroadcastSender, RecvError};
+pub use crate::budget::BudgetTable;
+pub use crate::calibrate::{CalibrationReport, WcetCalibrator};
+pub use crate::channel::{Channel, ChannelPool, Link, Receiver, Sender, Stamped};
+pub use crate::config::{KernelBuilder, KernelConfig};
+pub use crate::deadlines::DeadlineTracker;
 pub use crate::dmda::{analyze as dmda_analyze, DmdaReport, RtaResult};
-pub use crate::kernel::{Kernel, KernelStats};
+pub use crate::domain::TimeDomain;
+pub use crate::doorbell::{Doorbell, DoorbellPair, SoftDoorbell};
+pub use crate::double_buffer::{DoubleBuffer, DoubleBufferReader, DoubleBufferWriter, Front};
+pub use crate::error::{KernelError, KernelResult};
+pub use crate::events::KernelEvent;
+pub use crate::fault::{BlackBox, FaultCode, FaultRecord};
+pub use crate::gantt::Gantt;
+pub use crate::graph::{Graph, GraphBuilder, GraphError, Node, Port};
+pub use crate::init::InitTable;
+pub use crate::integrity::TaskTableGuard;
+pub use crate::kernel::{
+    Admission, CoreJob, InvalidTransition, Kernel, KernelState, KernelStats, SizedKernel,
+    TaskStats, TickObserver, DEFAULT_SCRATCH,
+};
+pub use crate::lifecycle::{Lifecycle, LifecycleCallbacks, LifecycleFn};
+pub use crate::log_task::{log_task, LogLine, LogTask};
+pub use crate::mailbox::Mailbox;
+pub use crate::mixed_criticality::{CritMode, Criticality, McReport, MixedCriticality};
+pub use crate::overload::{OverloadAction, OverloadManager, ShedAction};
+pub use crate::params::{ParamKey, ParamStore, ParamTxn, ParamValue, ParamView, MAX_PARAM_WORDS};
+pub use crate::policy::{Edf, Rms, SchedulingPolicy};
+pub use crate::pool::{Pool, PoolBlock};
+pub use crate::power::{PowerManager, PowerPolicy, SleepState, SleepStats, ThresholdPolicy};
+pub use crate::priority_ceiling::{PcpResult, PriorityCeilingManager, Resource};
 pub use crate::priority_inherit::{PipResult, PriorityInheritTracker, PriorityResource};
-pub use crate::scheduler::Scheduler;
-pub use crate::spsc::SpscRing;
-pub use crate::task::{Task, TaskFn, TaskPriority, TaskState};
-pub use crate::timer::{Deadline, SysTimer};
+pub use crate::profiler::{
+    CycleProfile, LoadAverages, ProfileClock, ResponseHistogram, Stopwatch, TaskProfiler,
+};
+pub use crate::redundancy::{CompareFn, RedundancyMonitor, MAX_PAIRS};
+pub use crate::replay::{Divergence, ExecHash, TickLog, TickRecord};
+pub use crate::router::{dispatch_task, Envelope, Inboxes, Router, Side};
+pub use crate::runqueue::RunQueue;
+pub use crate::sched_lock::SchedulerLock;
+pub use crate::scheduler::{
+    harmonic, liu_layland_bound_ppm, rms_schedulable, utilization_ppm, Overhead, PartitionError,
+    RmsBound, Scheduler,
+};
+pub use crate::shell::Shell;
+pub use crate::slack::{available_slack_us, BackgroundFn, SlackServer};
+pub use crate::snapshot::{snapshot_size, SnapshotError, SNAPSHOT_VERSION};
+pub use crate::soft_timer::{SoftTimerPool, TimerCallback, MAX_SOFT_TIMERS};
+pub use crate::spsc::{SpscConsumer, SpscProducer, SpscRing, Watermark};
+pub use crate::stats_task::{stats_task, StatsExport, StatsMode};
+pub use crate::task::{
+    CoreMask, Task, TaskFn, TaskMask, TaskPriority, TaskState, ANY_CORE, MAX_CORES,
+};
+pub use crate::telemetry::{DecodeError, MAX_STATS_LEN, STATS_WIRE_VERSION};
+pub use crate::text::{FmtBuf, Percent, ShortName};
+pub use crate::threshold::PreemptionThresholds;
+pub use crate::timeout::{RecvTimeouts, WaitSource};
+pub use crate::timer::{Deadline, SysTimer, Tick, TickDiff, TickSource, WrappingInstant};
+pub use crate::trace::{TraceBuffer, TraceEntry};
+pub use crate::wheel::{TimerWheel, MAX_WHEEL_ENTRIES};
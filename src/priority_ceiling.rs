@@ -0,0 +1,374 @@
+//! 即時優先度上限プロトコル (Immediate Priority Ceiling Protocol)
+//!
+//! 各リソースに上限優先度 (ceiling) を静的に割り当て、ロック獲得と同時に
+//! 保持タスクの優先度を ceiling まで引き上げる。優先度継承と異なり
+//! ブロッキングは高々 1 クリティカルセクションに抑えられ、デッドロックも
+//! 発生しないため、認証向けのハードリアルタイム解析に適する。
+//!
+//! Author: Moroya Sakamoto
+
+use crate::task::{Task, TaskPriority, MAX_TASKS};
+
+/// 最大リソース数。
+pub const MAX_CEILING_RESOURCES: usize = 8;
+
+/// 上限優先度付きリソース。
+#[derive(Debug, Clone, Copy)]
+pub struct Resource {
+    /// リソース名（8 ASCII 文字まで）。
+    pub name: [u8; 8],
+    /// 上限優先度（使用する全タスク中の最高優先度）。
+    pub ceiling: TaskPriority,
+    /// 現在ロックしているタスクのインデックス（`None` = 空き）。
+    pub holder: Option<usize>,
+}
+
+impl Resource {
+    /// 空リソース。
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            name: [0u8; 8],
+            ceiling: TaskPriority::IDLE,
+            holder: None,
+        }
+    }
+
+    /// 名前と上限優先度を指定して作成。
+    #[must_use]
+    pub fn new(name: &[u8], ceiling: TaskPriority) -> Self {
+        let mut n = [0u8; 8];
+        let len = name.len().min(8);
+        n[..len].copy_from_slice(&name[..len]);
+        Self {
+            name: n,
+            ceiling,
+            holder: None,
+        }
+    }
+
+    /// リソースが使用中か。
+    #[must_use]
+    pub const fn is_locked(&self) -> bool {
+        self.holder.is_some()
+    }
+}
+
+/// 上限優先度プロトコル操作の結果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcpResult {
+    /// 成功。
+    Ok,
+    /// リソースが既に別タスクに保持されている（IPCP 下では設定ミスを示す）。
+    Locked { holder: usize },
+    /// タスクの基本優先度がリソースの ceiling より高い。
+    CeilingViolation,
+    /// リソースが見つからない。
+    ResourceNotFound,
+    /// リソーステーブルが満杯。
+    TableFull,
+    /// このタスクはリソースを保持していない。
+    NotHolder,
+}
+
+/// 即時優先度上限プロトコルのリソースマネージャ。
+///
+/// ロック中の有効優先度と、応答時間解析用のクリティカルセクション長を管理する。
+pub struct PriorityCeilingManager {
+    /// リソーステーブル。
+    resources: [Resource; MAX_CEILING_RESOURCES],
+    /// 登録済みリソース数。
+    resource_count: usize,
+    /// タスクごとの基本優先度。
+    base_priorities: [TaskPriority; MAX_TASKS],
+    /// タスクごとの有効優先度（ceiling 適用後）。
+    effective_priorities: [TaskPriority; MAX_TASKS],
+    /// リソース × タスクごとの最長クリティカルセクション（µs）。
+    critical_sections: [[u32; MAX_TASKS]; MAX_CEILING_RESOURCES],
+}
+
+impl PriorityCeilingManager {
+    /// 新規作成。
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            resources: [Resource::empty(); MAX_CEILING_RESOURCES],
+            resource_count: 0,
+            base_priorities: [TaskPriority::IDLE; MAX_TASKS],
+            effective_priorities: [TaskPriority::IDLE; MAX_TASKS],
+            critical_sections: [[0; MAX_TASKS]; MAX_CEILING_RESOURCES],
+        }
+    }
+
+    /// タスクの基本優先度を登録。
+    pub const fn register_task(&mut self, task_idx: usize, priority: TaskPriority) {
+        if task_idx < MAX_TASKS {
+            self.base_priorities[task_idx] = priority;
+            self.effective_priorities[task_idx] = priority;
+        }
+    }
+
+    /// 上限優先度付きリソースを登録。
+    pub fn register_resource(&mut self, name: &[u8], ceiling: TaskPriority) -> PcpResult {
+        if self.resource_count >= MAX_CEILING_RESOURCES {
+            return PcpResult::TableFull;
+        }
+        self.resources[self.resource_count] = Resource::new(name, ceiling);
+        self.resource_count += 1;
+        PcpResult::Ok
+    }
+
+    /// タスクがリソースを使用することを宣言する（解析用）。
+    ///
+    /// `cs_us` はそのタスクがリソースを保持する最長時間。
+    /// タスクの優先度が ceiling より高い場合は `CeilingViolation`。
+    pub fn declare_usage(&mut self, resource_idx: usize, task_idx: usize, cs_us: u32) -> PcpResult {
+        if resource_idx >= self.resource_count || task_idx >= MAX_TASKS {
+            return PcpResult::ResourceNotFound;
+        }
+        if self.base_priorities[task_idx] < self.resources[resource_idx].ceiling {
+            return PcpResult::CeilingViolation;
+        }
+        let slot = &mut self.critical_sections[resource_idx][task_idx];
+        *slot = (*slot).max(cs_us);
+        PcpResult::Ok
+    }
+
+    /// リソースをロックし、保持タスクの優先度を即座に ceiling へ引き上げる。
+    pub fn lock(&mut self, resource_idx: usize, task_idx: usize) -> PcpResult {
+        if resource_idx >= self.resource_count || task_idx >= MAX_TASKS {
+            return PcpResult::ResourceNotFound;
+        }
+        let res = &mut self.resources[resource_idx];
+        if self.base_priorities[task_idx] < res.ceiling {
+            return PcpResult::CeilingViolation;
+        }
+        match res.holder {
+            Some(holder) if holder == task_idx => PcpResult::Ok,
+            Some(holder) => PcpResult::Locked { holder },
+            None => {
+                res.holder = Some(task_idx);
+                if res.ceiling < self.effective_priorities[task_idx] {
+                    self.effective_priorities[task_idx] = res.ceiling;
+                }
+                PcpResult::Ok
+            }
+        }
+    }
+
+    /// リソースを解放し、残りの保持リソースから有効優先度を再計算する。
+    pub fn unlock(&mut self, resource_idx: usize, task_idx: usize) -> PcpResult {
+        if resource_idx >= self.resource_count {
+            return PcpResult::ResourceNotFound;
+        }
+        if self.resources[resource_idx].holder != Some(task_idx) {
+            return PcpResult::NotHolder;
+        }
+        self.resources[resource_idx].holder = None;
+
+        let mut prio = self.base_priorities[task_idx];
+        for res in &self.resources[..self.resource_count] {
+            if res.holder == Some(task_idx) && res.ceiling < prio {
+                prio = res.ceiling;
+            }
+        }
+        self.effective_priorities[task_idx] = prio;
+        PcpResult::Ok
+    }
+
+    /// 各タスクのブロッキング項 `B_i` を計算する。
+    ///
+    /// `B_i = max { cs(r, j) | prio(j) < prio(i) かつ ceiling(r) ≥ prio(i) }`
+    /// （低優先度タスク `j` が、`i` 以上の ceiling を持つリソース `r` を
+    /// 保持する最長時間）。IPCP では高々 1 区間しかブロックされない。
+    #[must_use]
+    pub fn blocking_terms(&self, tasks: &[Task; MAX_TASKS], task_count: usize) -> [u32; MAX_TASKS] {
+        let mut blocking = [0u32; MAX_TASKS];
+        for (i, task) in tasks.iter().enumerate().take(task_count) {
+            if !task.is_active() {
+                continue;
+            }
+            for (r, res) in self.resources[..self.resource_count].iter().enumerate() {
+                if res.ceiling > task.priority {
+                    continue;
+                }
+                for (j, lower) in tasks.iter().enumerate().take(task_count) {
+                    if lower.is_active() && lower.priority > task.priority {
+                        blocking[i] = blocking[i].max(self.critical_sections[r][j]);
+                    }
+                }
+            }
+        }
+        blocking
+    }
+
+    /// タスクの有効優先度を取得。
+    #[must_use]
+    pub const fn effective_priority(&self, task_idx: usize) -> TaskPriority {
+        if task_idx < MAX_TASKS {
+            self.effective_priorities[task_idx]
+        } else {
+            TaskPriority::IDLE
+        }
+    }
+
+    /// リソース参照。
+    #[must_use]
+    pub const fn get_resource(&self, idx: usize) -> Option<&Resource> {
+        if idx < self.resource_count {
+            Some(&self.resources[idx])
+        } else {
+            None
+        }
+    }
+
+    /// 登録済みリソース数。
+    #[must_use]
+    pub const fn resource_count(&self) -> usize {
+        self.resource_count
+    }
+}
+
+impl Default for PriorityCeilingManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dmda;
+
+    fn dummy(_: &mut [u8]) {}
+
+    fn setup() -> PriorityCeilingManager {
+        let mut mgr = PriorityCeilingManager::new();
+        mgr.register_task(0, TaskPriority::CRITICAL);
+        mgr.register_task(1, TaskPriority::NORMAL);
+        mgr.register_task(2, TaskPriority::LOW);
+        mgr.register_resource(b"bus", TaskPriority::CRITICAL);
+        mgr
+    }
+
+    #[test]
+    fn lock_raises_to_ceiling() {
+        let mut mgr = setup();
+        assert_eq!(mgr.lock(0, 2), PcpResult::Ok);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::CRITICAL);
+        assert!(mgr.get_resource(0).unwrap().is_locked());
+    }
+
+    #[test]
+    fn unlock_restores_base() {
+        let mut mgr = setup();
+        mgr.lock(0, 2);
+        assert_eq!(mgr.unlock(0, 2), PcpResult::Ok);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::LOW);
+    }
+
+    #[test]
+    fn nested_locks_keep_highest_ceiling() {
+        let mut mgr = setup();
+        mgr.register_resource(b"spi", TaskPriority::NORMAL);
+        mgr.lock(1, 2);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::NORMAL);
+        mgr.lock(0, 2);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::CRITICAL);
+        mgr.unlock(0, 2);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::NORMAL);
+        mgr.unlock(1, 2);
+        assert_eq!(mgr.effective_priority(2), TaskPriority::LOW);
+    }
+
+    #[test]
+    fn ceiling_violation_rejected() {
+        let mut mgr = setup();
+        mgr.register_resource(b"log", TaskPriority::LOW);
+        // CRITICAL タスクが ceiling=LOW のリソースを使おうとする
+        assert_eq!(mgr.lock(1, 0), PcpResult::CeilingViolation);
+        assert_eq!(mgr.declare_usage(1, 0, 5), PcpResult::CeilingViolation);
+    }
+
+    #[test]
+    fn locked_by_other_reported() {
+        let mut mgr = setup();
+        mgr.lock(0, 2);
+        assert_eq!(mgr.lock(0, 1), PcpResult::Locked { holder: 2 });
+        assert_eq!(mgr.lock(0, 2), PcpResult::Ok); // 再入
+    }
+
+    #[test]
+    fn unlock_not_holder() {
+        let mut mgr = setup();
+        mgr.lock(0, 2);
+        assert_eq!(mgr.unlock(0, 1), PcpResult::NotHolder);
+        assert_eq!(mgr.unlock(9, 1), PcpResult::ResourceNotFound);
+    }
+
+    #[test]
+    fn table_full() {
+        let mut mgr = PriorityCeilingManager::new();
+        for _ in 0..MAX_CEILING_RESOURCES {
+            assert_eq!(
+                mgr.register_resource(b"r", TaskPriority::HIGH),
+                PcpResult::Ok
+            );
+        }
+        assert_eq!(
+            mgr.register_resource(b"x", TaskPriority::HIGH),
+            PcpResult::TableFull
+        );
+    }
+
+    #[test]
+    fn blocking_terms_from_lower_priority_users() {
+        let mut tasks = [Task::empty(); MAX_TASKS];
+        tasks[0] = Task::new(b"hi", dummy, TaskPriority::CRITICAL, 100, 10);
+        tasks[1] = Task::new(b"mid", dummy, TaskPriority::NORMAL, 200, 20);
+        tasks[2] = Task::new(b"lo", dummy, TaskPriority::LOW, 400, 40);
+        let mut mgr = setup();
+        mgr.declare_usage(0, 0, 3);
+        mgr.declare_usage(0, 2, 7);
+
+        let b = mgr.blocking_terms(&tasks, 3);
+        // hi と mid は lo の 7µs 区間でブロックされ得る、lo はブロックされない
+        assert_eq!(b[0], 7);
+        assert_eq!(b[1], 7);
+        assert_eq!(b[2], 0);
+    }
+
+    #[test]
+    fn blocking_ignores_low_ceiling_resources() {
+        let mut tasks = [Task::empty(); MAX_TASKS];
+        tasks[0] = Task::new(b"hi", dummy, TaskPriority::CRITICAL, 100, 10);
+        tasks[2] = Task::new(b"lo", dummy, TaskPriority::LOW, 400, 40);
+        let mut mgr = setup();
+        mgr.register_resource(b"log", TaskPriority::LOW);
+        mgr.declare_usage(1, 2, 50);
+        let b = mgr.blocking_terms(&tasks, 3);
+        assert_eq!(b[0], 0);
+    }
+
+    #[test]
+    fn rta_with_blocking_term() {
+        let mut tasks = [Task::empty(); MAX_TASKS];
+        tasks[0] = Task::new(b"hi", dummy, TaskPriority::CRITICAL, 20, 10);
+        tasks[1] = Task::new(b"lo", dummy, TaskPriority::LOW, 100, 20);
+        let mut mgr = PriorityCeilingManager::new();
+        mgr.register_task(0, TaskPriority::CRITICAL);
+        mgr.register_task(1, TaskPriority::LOW);
+        mgr.register_resource(b"bus", TaskPriority::CRITICAL);
+        mgr.declare_usage(0, 1, 12);
+
+        let blocking = mgr.blocking_terms(&tasks, 2);
+        let report = dmda::analyze_with_blocking(&tasks, 2, &blocking);
+        // R0 = 10 + 12 = 22 > 20 → miss
+        assert_eq!(report.results[0].unwrap().worst_case_response, 22);
+        assert!(!report.all_schedulable);
+    }
+}
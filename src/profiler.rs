@@ -0,0 +1,807 @@
+//! Per-task execution-time profiler
+//!
+//! Records the largest observed execution time and response time of each
+//! task. Like the software timer pool it lives outside the kernel (the
+//! task table has no room left under the 2 KB budget) and is attached
+//! with [`Kernel::attach_profiler`](crate::kernel::Kernel::attach_profiler).
+//!
+//! Execution time is measured with a user-supplied µs clock read right
+//! before and after the task body (e.g. DWT `CYCCNT` scaled to µs on
+//! Cortex-M). Response time is the job's release-to-completion time:
+//! dispatch latency plus execution time.
+//!
+//! The measured execution time also feeds CPU load averages: every
+//! [`LOAD_SAMPLE_US`] of kernel time the busy fraction is folded into
+//! exponentially weighted moving averages over 1 s, 10 s and 60 s
+//! ([`Kernel::load_averages`](crate::kernel::Kernel::load_averages)).
+//!
+//! For tail latency an optional static [`ResponseHistogram`] can be
+//! attached to the profiler: every response time is counted in one of
+//! [`HISTOGRAM_BINS`] log2-spaced bins, and
+//! [`TaskProfiler::response_percentile_us`] reports a conservative p99
+//! (or any other per-mille percentile) on target.
+//!
+//! Microseconds are too coarse for short jobs (a 23 µs audio block), so
+//! [`Stopwatch`] measures in CPU cycles: DWT `CYCCNT` with the `cortex-m`
+//! feature on an ARM target, a software counter advanced with
+//! [`advance_soft_cycles`] elsewhere. A static [`CycleProfile`] attached
+//! with [`TaskProfiler::attach_cycles`] knows which task the kernel is
+//! running, and each [`CycleProfile::scope`] guard charges the cycles
+//! between its creation and drop to that task.
+//!
+//! ```rust
+//! use alice_rtos::profiler::{advance_soft_cycles, CycleProfile};
+//!
+//! static CYCLES: CycleProfile = CycleProfile::new();
+//!
+//! CYCLES.enter(3); // done by the kernel with an attached profiler
+//! {
+//!     let _scope = CYCLES.scope();
+//!     advance_soft_cycles(1_200); // the measured work
+//! }
+//! CYCLES.leave();
+//! assert!(CYCLES.max_cycles(3) >= 1_200);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicU32, AtomicUsize, Ordering};
+use crate::task::MAX_TASKS;
+
+/// Free-running microsecond clock used to time task bodies
+pub type ProfileClock = fn() -> u64;
+
+/// Load-average sampling interval (µs of kernel time)
+pub const LOAD_SAMPLE_US: u64 = 100_000;
+
+/// Load-average time constants: short / medium / long (µs)
+pub const LOAD_WINDOWS_US: [u64; 3] = [1_000_000, 10_000_000, 60_000_000];
+
+/// Fixed-point one (parts per billion)
+const PPB: u64 = 1_000_000_000;
+
+/// CPU load averages in parts per million of one core
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadAverages {
+    /// Over ~1 s
+    pub short_ppm: u32,
+    /// Over ~10 s
+    pub medium_ppm: u32,
+    /// Over ~60 s
+    pub long_ppm: u32,
+}
+
+/// Number of response-time histogram bins
+pub const HISTOGRAM_BINS: usize = 16;
+
+/// Per-task response-time histogram with fixed log2-spaced bins
+///
+/// Bin 0 counts responses below `base_us`, bin `i` (1..=14) those in
+/// `[base_us << (i - 1), base_us << i)`, and the last bin everything from
+/// `base_us << 14` up. With `base_us = 10` the bins span 10 µs … 164 ms.
+pub struct ResponseHistogram {
+    /// Upper bound of bin 0 (µs)
+    base_us: u32,
+    /// Response counts per task and bin
+    counts: [[u32; HISTOGRAM_BINS]; MAX_TASKS],
+}
+
+impl ResponseHistogram {
+    /// Empty histogram whose first bin ends at `base_us` (usable in `static`s)
+    #[must_use]
+    pub const fn new(base_us: u32) -> Self {
+        Self {
+            base_us: if base_us == 0 { 1 } else { base_us },
+            counts: [[0; HISTOGRAM_BINS]; MAX_TASKS],
+        }
+    }
+
+    /// Bin a response time of `response_us` falls into
+    #[must_use]
+    pub const fn bin_of(&self, response_us: u32) -> usize {
+        let mut bin = 0;
+        while bin < HISTOGRAM_BINS - 1 && (response_us as u64) >= self.bin_upper_us(bin) as u64 {
+            bin += 1;
+        }
+        bin
+    }
+
+    /// Exclusive upper bound of `bin` (µs), `u32::MAX` for the last bin
+    #[must_use]
+    pub const fn bin_upper_us(&self, bin: usize) -> u32 {
+        if bin >= HISTOGRAM_BINS - 1 {
+            return u32::MAX;
+        }
+        let upper = (self.base_us as u64) << bin;
+        if upper > u32::MAX as u64 {
+            u32::MAX
+        } else {
+            upper as u32
+        }
+    }
+
+    /// Count one response of `idx`
+    pub fn record(&mut self, idx: usize, response_us: u32) {
+        if idx < MAX_TASKS {
+            let bin = self.bin_of(response_us);
+            self.counts[idx][bin] = self.counts[idx][bin].saturating_add(1);
+        }
+    }
+
+    /// Response counts of `idx` per bin
+    #[must_use]
+    pub fn counts(&self, idx: usize) -> [u32; HISTOGRAM_BINS] {
+        self.counts.get(idx).copied().unwrap_or([0; HISTOGRAM_BINS])
+    }
+
+    /// Number of responses recorded for `idx`
+    #[must_use]
+    pub fn total(&self, idx: usize) -> u64 {
+        self.counts(idx).iter().map(|&c| c as u64).sum()
+    }
+
+    /// Bin holding the `per_mille` percentile of `idx` (990 = p99)
+    ///
+    /// Returns `None` before the first response.
+    #[must_use]
+    pub fn percentile_bin(&self, idx: usize, per_mille: u16) -> Option<usize> {
+        let total = self.total(idx);
+        if total == 0 {
+            return None;
+        }
+        // 順位 = ceil(total * p / 1000), 最低 1
+        let rank = (total * per_mille.min(1_000) as u64).div_ceil(1_000).max(1);
+        let mut seen = 0;
+        self.counts(idx).iter().position(|&c| {
+            seen += c as u64;
+            seen >= rank
+        })
+    }
+
+    /// Clear the counts of `idx`
+    pub fn reset_task(&mut self, idx: usize) {
+        if idx < MAX_TASKS {
+            self.counts[idx] = [0; HISTOGRAM_BINS];
+        }
+    }
+
+    /// Clear all counts
+    pub fn reset(&mut self) {
+        self.counts = [[0; HISTOGRAM_BINS]; MAX_TASKS];
+    }
+}
+
+/// DWT registers (ARMv7-M / ARMv8-M)
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+mod dwt {
+    /// Debug Exception and Monitor Control Register
+    pub const DEMCR: usize = 0xE000_EDFC;
+    /// `DEMCR.TRCENA`: enable the DWT / ITM blocks
+    pub const DEMCR_TRCENA: u32 = 1 << 24;
+    /// DWT control register
+    pub const CTRL: usize = 0xE000_1000;
+    /// `DWT_CTRL.CYCCNTENA`
+    pub const CTRL_CYCCNTENA: u32 = 1 << 0;
+    /// Cycle count register
+    pub const CYCCNT: usize = 0xE000_1004;
+}
+
+/// Software cycle counter (targets without `CYCCNT`)
+#[cfg(not(all(feature = "cortex-m", target_arch = "arm")))]
+static SOFT_CYCLES: AtomicU32 = AtomicU32::new(0);
+
+/// Start the cycle counter
+///
+/// Sets `DEMCR.TRCENA` and `DWT_CTRL.CYCCNTENA` with the `cortex-m`
+/// feature on ARM; a no-op for the software counter.
+///
+/// # Safety
+///
+/// On Cortex-M the core must have a DWT cycle counter (M3 and up, not
+/// M0 / M0+), and nothing else may be reconfiguring the DWT.
+pub unsafe fn enable_cycle_counter() {
+    #[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+    {
+        use core::ptr;
+        // SAFETY: 呼び出し側が DWT の存在を保証
+        unsafe {
+            let demcr = ptr::read_volatile(dwt::DEMCR as *const u32);
+            ptr::write_volatile(dwt::DEMCR as *mut u32, demcr | dwt::DEMCR_TRCENA);
+            let ctrl = ptr::read_volatile(dwt::CTRL as *const u32);
+            ptr::write_volatile(dwt::CTRL as *mut u32, ctrl | dwt::CTRL_CYCCNTENA);
+        }
+    }
+}
+
+/// Current value of the free-running 32-bit cycle counter
+#[inline(always)]
+#[must_use]
+pub fn cycles() -> u32 {
+    #[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+    {
+        // SAFETY: CYCCNT は常に読み出し可能 (未有効なら止まっているだけ)
+        unsafe { core::ptr::read_volatile(dwt::CYCCNT as *const u32) }
+    }
+    #[cfg(not(all(feature = "cortex-m", target_arch = "arm")))]
+    {
+        SOFT_CYCLES.load(Ordering::Relaxed)
+    }
+}
+
+/// Advance the software cycle counter (host simulation, tests)
+#[cfg(not(all(feature = "cortex-m", target_arch = "arm")))]
+pub fn advance_soft_cycles(n: u32) {
+    SOFT_CYCLES.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Cycle-resolution stopwatch over [`cycles`]
+///
+/// Differences wrap correctly, so any interval shorter than 2³² cycles
+/// (~8.9 s at 480 MHz) is measured exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stopwatch {
+    /// Counter value at the start of the interval
+    start: u32,
+}
+
+impl Stopwatch {
+    /// Start measuring now
+    #[must_use]
+    pub fn start() -> Self {
+        Self { start: cycles() }
+    }
+
+    /// Cycles since the start
+    #[must_use]
+    pub fn elapsed_cycles(&self) -> u32 {
+        cycles().wrapping_sub(self.start)
+    }
+
+    /// Nanoseconds since the start on a core clocked at `cpu_hz`
+    #[must_use]
+    pub fn elapsed_ns(&self, cpu_hz: u32) -> u64 {
+        self.elapsed_cycles() as u64 * 1_000_000_000 / cpu_hz.max(1) as u64
+    }
+
+    /// Cycles since the start, restarting the interval
+    pub fn lap(&mut self) -> u32 {
+        let now = cycles();
+        let elapsed = now.wrapping_sub(self.start);
+        self.start = now;
+        elapsed
+    }
+}
+
+/// Marker for "no task running" in [`CycleProfile`]
+const NO_TASK: usize = usize::MAX;
+
+/// Per-task cycle counts of scoped measurements
+///
+/// Lives in a `static`; the kernel marks the running task through an
+/// attached [`TaskProfiler`] (or a switch hook calls
+/// [`enter`](Self::enter) / [`leave`](Self::leave)). Scopes opened while
+/// no task runs (interrupts, idle) are not charged.
+pub struct CycleProfile {
+    /// Task currently executing (`NO_TASK` if none)
+    current: AtomicUsize,
+    /// Cycles of the most recent scope per task
+    last: [AtomicU32; MAX_TASKS],
+    /// Longest scope per task
+    max: [AtomicU32; MAX_TASKS],
+    /// Completed scopes per task
+    scopes: [AtomicU32; MAX_TASKS],
+}
+
+impl CycleProfile {
+    /// Empty profile (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(NO_TASK),
+            last: [const { AtomicU32::new(0) }; MAX_TASKS],
+            max: [const { AtomicU32::new(0) }; MAX_TASKS],
+            scopes: [const { AtomicU32::new(0) }; MAX_TASKS],
+        }
+    }
+
+    /// Mark `idx` as the running task
+    pub fn enter(&self, idx: usize) {
+        self.current.store(idx, Ordering::Relaxed);
+    }
+
+    /// Mark that no task is running
+    pub fn leave(&self) {
+        self.current.store(NO_TASK, Ordering::Relaxed);
+    }
+
+    /// Task scopes are currently charged to
+    #[must_use]
+    pub fn current(&self) -> Option<usize> {
+        let idx = self.current.load(Ordering::Relaxed);
+        (idx < MAX_TASKS).then_some(idx)
+    }
+
+    /// Measure until the returned guard is dropped
+    ///
+    /// The cycles are charged to the task running when the scope opened.
+    #[must_use = "the scope is measured until the guard is dropped"]
+    pub fn scope(&self) -> CycleScope<'_> {
+        CycleScope {
+            profile: self,
+            task: self.current.load(Ordering::Relaxed),
+            watch: Stopwatch::start(),
+        }
+    }
+
+    /// Charge one measured scope of `cycles` to `idx`
+    pub fn charge(&self, idx: usize, cycles: u32) {
+        if idx >= MAX_TASKS {
+            return;
+        }
+        self.last[idx].store(cycles, Ordering::Relaxed);
+        self.max[idx].fetch_max(cycles, Ordering::Relaxed);
+        self.scopes[idx].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Cycles of the most recent scope of `idx`
+    #[must_use]
+    pub fn last_cycles(&self, idx: usize) -> u32 {
+        self.last.get(idx).map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// Longest scope of `idx` (cycles)
+    #[must_use]
+    pub fn max_cycles(&self, idx: usize) -> u32 {
+        self.max.get(idx).map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// Completed scopes of `idx`
+    #[must_use]
+    pub fn scopes(&self, idx: usize) -> u32 {
+        self.scopes
+            .get(idx)
+            .map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// Forget the counts of `idx`
+    pub fn reset_task(&self, idx: usize) {
+        if idx < MAX_TASKS {
+            self.last[idx].store(0, Ordering::Relaxed);
+            self.max[idx].store(0, Ordering::Relaxed);
+            self.scopes[idx].store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for CycleProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scoped cycle measurement from [`CycleProfile::scope`]
+pub struct CycleScope<'a> {
+    /// Profile charged on drop
+    profile: &'a CycleProfile,
+    /// Task running when the scope opened
+    task: usize,
+    /// Interval being measured
+    watch: Stopwatch,
+}
+
+impl CycleScope<'_> {
+    /// Cycles measured so far
+    #[must_use]
+    pub fn elapsed_cycles(&self) -> u32 {
+        self.watch.elapsed_cycles()
+    }
+}
+
+impl Drop for CycleScope<'_> {
+    fn drop(&mut self) {
+        self.profile.charge(self.task, self.watch.elapsed_cycles());
+    }
+}
+
+/// `base ^ exp` in parts-per-billion fixed point
+const fn pow_ppb(mut base: u64, mut exp: u64) -> u64 {
+    let mut acc = PPB;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc * base / PPB;
+        }
+        base = base * base / PPB;
+        exp >>= 1;
+    }
+    acc
+}
+
+/// Per-task timing maxima
+pub struct TaskProfiler {
+    /// Execution-time clock
+    clock: ProfileClock,
+    /// Largest execution time per task (µs)
+    max_exec_us: [u32; MAX_TASKS],
+    /// Largest response time per task (µs)
+    max_response_us: [u32; MAX_TASKS],
+    /// Execution time measured in the current load sample (µs)
+    busy_us: u64,
+    /// Kernel time the current load sample started (µs)
+    sample_start: u64,
+    /// Load averages (ppb), short / medium / long
+    load_ppb: [u32; 3],
+    /// Optional response-time histogram
+    histogram: Option<&'static mut ResponseHistogram>,
+    /// Optional cycle profile told which task is running
+    cycles: Option<&'static CycleProfile>,
+}
+
+impl TaskProfiler {
+    /// Empty profiler timing tasks with `clock` (usable in `static`s)
+    #[must_use]
+    pub const fn new(clock: ProfileClock) -> Self {
+        Self {
+            clock,
+            max_exec_us: [0; MAX_TASKS],
+            max_response_us: [0; MAX_TASKS],
+            busy_us: 0,
+            sample_start: 0,
+            load_ppb: [0; 3],
+            histogram: None,
+            cycles: None,
+        }
+    }
+
+    /// Also count every response time in `histogram`
+    pub fn attach_histogram(&mut self, histogram: &'static mut ResponseHistogram) {
+        self.histogram = Some(histogram);
+    }
+
+    /// Attached response-time histogram
+    #[must_use]
+    pub fn histogram(&self) -> Option<&ResponseHistogram> {
+        self.histogram.as_deref()
+    }
+
+    /// Tell `cycles` which task the kernel is running
+    pub fn attach_cycles(&mut self, cycles: &'static CycleProfile) {
+        self.cycles = Some(cycles);
+    }
+
+    /// Attached cycle profile
+    #[must_use]
+    pub fn cycles(&self) -> Option<&'static CycleProfile> {
+        self.cycles
+    }
+
+    /// Read the profiling clock
+    #[inline(always)]
+    pub(crate) fn now(&self) -> u64 {
+        (self.clock)()
+    }
+
+    /// Mark the start of a job of `idx` and read the profiling clock
+    #[inline(always)]
+    pub(crate) fn begin(&self, idx: usize) -> u64 {
+        if let Some(cycles) = self.cycles {
+            cycles.enter(idx);
+        }
+        self.now()
+    }
+
+    /// Record one job of `idx`
+    ///
+    /// `latency_us` is release → dispatch, `exec_us` the measured body.
+    pub fn record(&mut self, idx: usize, latency_us: u64, exec_us: u64) {
+        if let Some(cycles) = self.cycles {
+            cycles.leave();
+        }
+        if idx >= MAX_TASKS {
+            return;
+        }
+        let exec = exec_us.min(u32::MAX as u64) as u32;
+        let response = (latency_us + exec_us).min(u32::MAX as u64) as u32;
+        self.max_exec_us[idx] = self.max_exec_us[idx].max(exec);
+        self.max_response_us[idx] = self.max_response_us[idx].max(response);
+        self.busy_us += exec_us;
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.record(idx, response);
+        }
+    }
+
+    /// Close every load sample that ended by kernel time `now_us`
+    ///
+    /// The first closed sample carries the busy time measured so far;
+    /// further whole samples (a long tickless sleep) count as idle.
+    pub fn sample_load(&mut self, now_us: u64) {
+        let elapsed = now_us.saturating_sub(self.sample_start);
+        let samples = elapsed / LOAD_SAMPLE_US;
+        if samples == 0 {
+            return;
+        }
+        let busy_ppb = (self.busy_us.min(LOAD_SAMPLE_US) * PPB / LOAD_SAMPLE_US) as i64;
+        for (w, window) in LOAD_WINDOWS_US.iter().enumerate() {
+            let alpha = (LOAD_SAMPLE_US * PPB / window) as i64;
+            let avg = self.load_ppb[w] as i64;
+            let avg = avg + (busy_ppb - avg) * alpha / PPB as i64;
+            // 残りの空きサンプルは (1 - α)^k でまとめて減衰
+            let decay = pow_ppb(PPB - alpha as u64, samples - 1);
+            self.load_ppb[w] = (avg as u64 * decay / PPB) as u32;
+        }
+        self.busy_us = 0;
+        self.sample_start += samples * LOAD_SAMPLE_US;
+    }
+
+    /// Current load averages
+    #[must_use]
+    pub const fn load_averages(&self) -> LoadAverages {
+        LoadAverages {
+            short_ppm: self.load_ppb[0] / 1_000,
+            medium_ppm: self.load_ppb[1] / 1_000,
+            long_ppm: self.load_ppb[2] / 1_000,
+        }
+    }
+
+    /// Largest observed execution time of `idx` (µs)
+    #[must_use]
+    pub fn max_exec_us(&self, idx: usize) -> u32 {
+        self.max_exec_us.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Largest observed response time of `idx` (µs)
+    #[must_use]
+    pub fn max_response_us(&self, idx: usize) -> u32 {
+        self.max_response_us.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Upper bound on the `per_mille` response-time percentile of `idx` (µs)
+    ///
+    /// The end of the histogram bin holding the percentile, capped at the
+    /// largest observed response; `990` gives p99. Returns `None` without
+    /// an attached histogram or before the first response.
+    #[must_use]
+    pub fn response_percentile_us(&self, idx: usize, per_mille: u16) -> Option<u32> {
+        let histogram = self.histogram()?;
+        let bin = histogram.percentile_bin(idx, per_mille)?;
+        Some(histogram.bin_upper_us(bin).min(self.max_response_us(idx)))
+    }
+
+    /// Forget the maxima and histogram of `idx` (e.g. when its slot is reused)
+    pub fn reset_task(&mut self, idx: usize) {
+        if idx < MAX_TASKS {
+            self.max_exec_us[idx] = 0;
+            self.max_response_us[idx] = 0;
+        }
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.reset_task(idx);
+        }
+        if let Some(cycles) = self.cycles {
+            cycles.reset_task(idx);
+        }
+    }
+
+    /// Forget all maxima, histogram and cycle counts and load averages
+    pub fn reset(&mut self) {
+        self.max_exec_us = [0; MAX_TASKS];
+        self.max_response_us = [0; MAX_TASKS];
+        self.busy_us = 0;
+        self.load_ppb = [0; 3];
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.reset();
+        }
+        if let Some(cycles) = self.cycles {
+            (0..MAX_TASKS).for_each(|idx| cycles.reset_task(idx));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    fn zero_clock() -> u64 {
+        0
+    }
+
+    #[test]
+    fn test_record_keeps_maxima() {
+        let mut p = TaskProfiler::new(zero_clock);
+        p.record(2, 10, 30);
+        p.record(2, 0, 50);
+        p.record(2, 5, 20);
+        assert_eq!(p.max_exec_us(2), 50);
+        // 応答時間 = 遅延 + 実行時間の最大
+        assert_eq!(p.max_response_us(2), 50);
+        p.record(2, 40, 15);
+        assert_eq!(p.max_response_us(2), 55);
+        assert_eq!(p.max_exec_us(0), 0);
+    }
+
+    #[test]
+    fn test_out_of_range_ignored() {
+        let mut p = TaskProfiler::new(zero_clock);
+        p.record(MAX_TASKS, 1, 1);
+        assert_eq!(p.max_exec_us(MAX_TASKS), 0);
+        assert_eq!(p.max_response_us(MAX_TASKS), 0);
+    }
+
+    #[test]
+    fn test_saturates_at_u32() {
+        let mut p = TaskProfiler::new(zero_clock);
+        p.record(0, u64::MAX / 2, u64::MAX / 2);
+        assert_eq!(p.max_exec_us(0), u32::MAX);
+        assert_eq!(p.max_response_us(0), u32::MAX);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut p = TaskProfiler::new(zero_clock);
+        p.record(0, 1, 2);
+        p.record(1, 3, 4);
+        p.reset_task(0);
+        assert_eq!(p.max_exec_us(0), 0);
+        assert_eq!(p.max_exec_us(1), 4);
+        p.reset();
+        assert_eq!(p.max_response_us(1), 0);
+    }
+
+    #[test]
+    fn test_load_average_converges_and_decays() {
+        let mut p = TaskProfiler::new(zero_clock);
+        let mut now = 0;
+        // 30 % 負荷を 60 s 続ける
+        for _ in 0..600 {
+            p.record(0, 0, 30_000);
+            now += LOAD_SAMPLE_US;
+            p.sample_load(now);
+        }
+        let load = p.load_averages();
+        assert!((299_000..=300_000).contains(&load.short_ppm));
+        assert!(load.medium_ppm > 290_000);
+        // 長期窓は 60 s で 1 - e^-1 ≈ 63 %
+        assert!((185_000..=195_000).contains(&load.long_ppm));
+        assert!(load.short_ppm >= load.medium_ppm && load.medium_ppm >= load.long_ppm);
+
+        // tickless で 10 s 空く: 一括減衰
+        now += 10_000_000;
+        p.sample_load(now);
+        let idle = p.load_averages();
+        assert!(idle.short_ppm < 100);
+        assert!(idle.medium_ppm < 120_000);
+        assert!(idle.long_ppm > 150_000);
+    }
+
+    #[test]
+    fn test_histogram_bins_log2() {
+        let h = ResponseHistogram::new(10);
+        assert_eq!(h.bin_of(0), 0);
+        assert_eq!(h.bin_of(9), 0);
+        assert_eq!(h.bin_of(10), 1);
+        assert_eq!(h.bin_of(19), 1);
+        assert_eq!(h.bin_of(20), 2);
+        assert_eq!(h.bin_upper_us(14), 10 << 14);
+        assert_eq!(h.bin_of(10 << 14), HISTOGRAM_BINS - 1);
+        assert_eq!(h.bin_of(u32::MAX), HISTOGRAM_BINS - 1);
+        assert_eq!(h.bin_upper_us(HISTOGRAM_BINS - 1), u32::MAX);
+        // 大きな base でも上限は飽和
+        assert_eq!(ResponseHistogram::new(u32::MAX).bin_upper_us(3), u32::MAX);
+    }
+
+    #[test]
+    fn test_histogram_p99() {
+        let histogram = std::boxed::Box::leak(std::boxed::Box::new(ResponseHistogram::new(10)));
+        let mut p = TaskProfiler::new(zero_clock);
+        assert_eq!(p.response_percentile_us(0, 990), None);
+        p.attach_histogram(histogram);
+        assert_eq!(p.response_percentile_us(0, 990), None);
+
+        // 990 回は 50 µs 前後, 10 回だけ 700 µs のテール
+        for i in 0..990 {
+            p.record(0, i % 5, 45);
+        }
+        for _ in 0..10 {
+            p.record(0, 600, 100);
+        }
+        let h = p.histogram().unwrap();
+        assert_eq!(h.total(0), 1_000);
+        assert_eq!(h.counts(0)[3], 990);
+        // p99 はまだ 40..80 µs の区間, p99.9 はテールの区間 (最大値で頭打ち)
+        assert_eq!(p.response_percentile_us(0, 990), Some(80));
+        assert_eq!(p.response_percentile_us(0, 999), Some(700));
+        assert_eq!(p.response_percentile_us(0, 0), Some(80));
+
+        p.reset_task(0);
+        assert_eq!(p.response_percentile_us(0, 990), None);
+    }
+
+    #[test]
+    fn test_pow_ppb() {
+        assert_eq!(pow_ppb(500_000_000, 0), PPB);
+        assert_eq!(pow_ppb(500_000_000, 3), 125_000_000);
+    }
+
+    /// ソフトウェアサイクルカウンタを使うテストの直列化
+    static SOFT_CYCLES_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_stopwatch_counts_cycles() {
+        let _lock = SOFT_CYCLES_LOCK.lock().unwrap();
+        // SAFETY: ソフトウェアカウンタでは何もしない
+        unsafe { enable_cycle_counter() };
+        let mut watch = Stopwatch::start();
+        advance_soft_cycles(480);
+        assert_eq!(watch.elapsed_cycles(), 480);
+        // 480 MHz で 1 µs
+        assert_eq!(watch.elapsed_ns(480_000_000), 1_000);
+        assert_eq!(watch.lap(), 480);
+        advance_soft_cycles(20);
+        assert_eq!(watch.elapsed_cycles(), 20);
+
+        // 32 bit の桁あふれをまたいでも差分は正しい
+        advance_soft_cycles(u32::MAX - cycles());
+        let watch = Stopwatch::start();
+        advance_soft_cycles(100);
+        assert_eq!(watch.elapsed_cycles(), 100);
+    }
+
+    #[test]
+    fn test_scope_charges_running_task() {
+        let _lock = SOFT_CYCLES_LOCK.lock().unwrap();
+        let profile = CycleProfile::new();
+        // タスク外のスコープは計上しない
+        drop(profile.scope());
+        assert_eq!(profile.current(), None);
+
+        profile.enter(2);
+        for work in [300, 900, 600] {
+            let scope = profile.scope();
+            advance_soft_cycles(work);
+            assert_eq!(scope.elapsed_cycles(), work);
+        }
+        profile.leave();
+        assert_eq!(profile.last_cycles(2), 600);
+        assert_eq!(profile.max_cycles(2), 900);
+        assert_eq!(profile.scopes(2), 3);
+        assert_eq!(profile.scopes(0), 0);
+        profile.reset_task(2);
+        assert_eq!((profile.max_cycles(2), profile.scopes(2)), (0, 0));
+        assert_eq!(profile.max_cycles(MAX_TASKS), 0);
+    }
+
+    #[test]
+    fn test_kernel_marks_task_for_cycle_scopes() {
+        use crate::kernel::Kernel;
+        use crate::task::TaskPriority;
+
+        static CYCLES: CycleProfile = CycleProfile::new();
+        fn audio(_: &mut [u8]) {
+            let _scope = CYCLES.scope();
+            advance_soft_cycles(11_000);
+        }
+        fn control(_: &mut [u8]) {
+            let _scope = CYCLES.scope();
+            advance_soft_cycles(2_500);
+        }
+
+        let _lock = SOFT_CYCLES_LOCK.lock().unwrap();
+        let profiler = std::boxed::Box::leak(std::boxed::Box::new(TaskProfiler::new(zero_clock)));
+        profiler.attach_cycles(&CYCLES);
+        assert!(profiler.cycles().is_some());
+        let mut kernel = Kernel::testing();
+        let a = kernel
+            .add_task(b"audio", audio, TaskPriority::HIGH, 23, 20)
+            .unwrap();
+        let c = kernel
+            .add_task(b"ctl", control, TaskPriority::LOW, 1_000, 100)
+            .unwrap();
+        kernel.attach_profiler(profiler);
+        kernel.tick(0);
+        kernel.tick(10);
+        // 各タスクのスコープはそのタスクに計上される
+        assert_eq!(CYCLES.max_cycles(a), 11_000);
+        assert_eq!(CYCLES.max_cycles(c), 2_500);
+        assert_eq!(CYCLES.current(), None);
+    }
+}
@@ -10,7 +10,7 @@ use crate::kernel::Kernel as RustKernel;
 use crate::scheduler::Scheduler as RustScheduler;
 use crate::spsc::SpscRing;
 use crate::task::TaskPriority;
-use crate::timer::SysTimer as RustSysTimer;
+use crate::timer::{tick_to_u64, SysTimer as RustSysTimer};
 
 /// ALICE-RTOS Kernel for scheduling simulation
 #[pyclass(name = "Kernel")]
@@ -215,7 +215,7 @@ impl PyScheduler {
 
     #[getter]
     fn now_us(&self) -> u64 {
-        self.inner.now_us()
+        tick_to_u64(self.inner.now_us())
     }
 
     #[getter]
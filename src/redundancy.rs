@@ -0,0 +1,352 @@
+//! Redundant execution of critical tasks with output comparison
+//!
+//! A safety function computes the same equation twice — possibly with
+//! diverse implementations — and only trusts the result if both replicas
+//! agree. [`RedundancyMonitor::redundant_pair`] ties two tasks of the
+//! same period together: each replica writes its output into its own
+//! scratch arena ([`Task::with_arena`](crate::task::Task::with_arena)),
+//! and once both have completed a job the monitor compares the arenas
+//! with the pair's [`CompareFn`].
+//!
+//! A fault is raised when the outputs disagree, or when one replica
+//! completes and the other does not follow within the pair's timeout
+//! (by default one period): the monitor counts it, reports
+//! [`KernelEvent::ReplicaFault`] and records it in the black box
+//! ([`FaultCode::REPLICA_MISMATCH`] / [`FaultCode::REPLICA_TIMEOUT`]).
+//!
+//! ```rust
+//! use alice_rtos::redundancy::RedundancyMonitor;
+//! use alice_rtos::{Kernel, Task, TaskPriority};
+//!
+//! fn law_a(out: &mut [u8]) { out[0] = 3 * 7; }
+//! fn law_b(out: &mut [u8]) { out[0] = 7 + 7 + 7; }
+//!
+//! let mut kernel = Kernel::testing();
+//! let a = kernel.try_add(Task::new(b"law_a", law_a, TaskPriority::HIGH, 1_000, 50).with_arena(4)).unwrap();
+//! let b = kernel.try_add(Task::new(b"law_b", law_b, TaskPriority::HIGH, 1_000, 50).with_arena(4)).unwrap();
+//! let mut monitor = RedundancyMonitor::new();
+//! let pair = monitor.redundant_pair(&kernel, a, b, |x, y| x == y).unwrap();
+//! monitor.run_for(&mut kernel, 10_000, 100);
+//! assert_eq!(monitor.agreed(pair), 10);
+//! assert_eq!((monitor.mismatches(pair), monitor.timeouts(pair)), (0, 0));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::error::{KernelError, KernelResult};
+use crate::events::{emit, KernelEvent};
+use crate::fault::{self, FaultCode};
+use crate::kernel::{Kernel, KernelStats};
+
+/// Maximum redundant pairs per monitor
+pub const MAX_PAIRS: usize = 4;
+
+/// Do the outputs (arenas) of replica a and replica b agree?
+pub type CompareFn = fn(a: &[u8], b: &[u8]) -> bool;
+
+/// Replica a completed in the current round
+const DONE_A: u8 = 1 << 0;
+/// Replica b completed in the current round
+const DONE_B: u8 = 1 << 1;
+
+/// Two replicas of one safety function
+#[derive(Clone, Copy)]
+struct Pair {
+    /// Replica task slots
+    a: u8,
+    b: u8,
+    /// Output comparison
+    compare: CompareFn,
+    /// Longest gap between the two completions (µs)
+    timeout_us: u32,
+    /// Replicas completed in the current round (`DONE_*`)
+    done: u8,
+    /// First completion of the current round (µs)
+    first_us: u64,
+    /// Rounds in which the outputs agreed
+    agreed: u32,
+    /// Rounds in which the outputs disagreed
+    mismatches: u32,
+    /// Rounds abandoned because a replica did not complete
+    timeouts: u32,
+}
+
+/// Pairs up replicas, compares their outputs and raises faults
+pub struct RedundancyMonitor {
+    /// Registered pairs
+    pairs: [Option<Pair>; MAX_PAIRS],
+}
+
+impl Default for RedundancyMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RedundancyMonitor {
+    /// Monitor without pairs
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pairs: [None; MAX_PAIRS],
+        }
+    }
+
+    /// Run tasks `a` and `b` as replicas compared by `compare`
+    ///
+    /// Both must be distinct active tasks with the same period and a
+    /// scratch arena each ([`KernelError::InvalidIndex`] otherwise); at
+    /// most [`MAX_PAIRS`] pairs fit ([`KernelError::TaskTableFull`]). The
+    /// timeout starts at one period. Returns the pair index.
+    pub fn redundant_pair(
+        &mut self,
+        kernel: &Kernel,
+        a: usize,
+        b: usize,
+        compare: CompareFn,
+    ) -> KernelResult<usize> {
+        let period = |idx| {
+            kernel
+                .scheduler
+                .get_task(idx)
+                .filter(|t| t.is_active() && kernel.arena(idx).is_some())
+                .map(|t| t.period_us)
+        };
+        let timeout_us = match (period(a), period(b)) {
+            (Some(pa), Some(pb)) if a != b && pa == pb => pa,
+            _ => return Err(KernelError::InvalidIndex),
+        };
+        let slot = self
+            .pairs
+            .iter()
+            .position(Option::is_none)
+            .ok_or(KernelError::TaskTableFull)?;
+        self.pairs[slot] = Some(Pair {
+            a: a as u8,
+            b: b as u8,
+            compare,
+            timeout_us,
+            done: 0,
+            first_us: 0,
+            agreed: 0,
+            mismatches: 0,
+            timeouts: 0,
+        });
+        Ok(slot)
+    }
+
+    /// Allow up to `timeout_us` between the completions of pair `pair`
+    pub fn set_timeout(&mut self, pair: usize, timeout_us: u32) -> bool {
+        match self.pairs.get_mut(pair) {
+            Some(Some(p)) => {
+                p.timeout_us = timeout_us;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stop monitoring pair `pair`
+    pub fn remove(&mut self, pair: usize) -> bool {
+        self.pairs.get_mut(pair).is_some_and(|p| p.take().is_some())
+    }
+
+    /// Tick the kernel, then compare completed rounds and check timeouts
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        let now = kernel.timer.now_us();
+        for p in self.pairs.iter_mut().flatten() {
+            if p.done != 0 && now - p.first_us > u64::from(p.timeout_us) {
+                p.timeout(kernel);
+            }
+            let done = match executed {
+                Some(idx) if idx == p.a as usize => DONE_A,
+                Some(idx) if idx == p.b as usize => DONE_B,
+                _ => 0,
+            };
+            if done != 0 {
+                if p.done & done != 0 {
+                    // 同じレプリカが 2 回完了: 相方はこのラウンドに間に合わず
+                    p.timeout(kernel);
+                }
+                if p.done == 0 {
+                    p.first_us = now;
+                }
+                p.done |= done;
+                if p.done == DONE_A | DONE_B {
+                    p.compare(kernel);
+                }
+            }
+        }
+        executed
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`])
+    pub fn run_for(&mut self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        while kernel.timer.now_us() - start < total_us {
+            if self.tick(kernel, tick_us).is_some() {
+                tasks_executed += 1;
+            }
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+
+    /// Rounds of pair `pair` whose outputs agreed
+    #[must_use]
+    pub fn agreed(&self, pair: usize) -> u32 {
+        self.get(pair).map_or(0, |p| p.agreed)
+    }
+
+    /// Rounds of pair `pair` whose outputs disagreed
+    #[must_use]
+    pub fn mismatches(&self, pair: usize) -> u32 {
+        self.get(pair).map_or(0, |p| p.mismatches)
+    }
+
+    /// Rounds of pair `pair` in which a replica did not complete in time
+    #[must_use]
+    pub fn timeouts(&self, pair: usize) -> u32 {
+        self.get(pair).map_or(0, |p| p.timeouts)
+    }
+
+    /// Pair `pair`, if registered
+    fn get(&self, pair: usize) -> Option<&Pair> {
+        self.pairs.get(pair)?.as_ref()
+    }
+}
+
+impl Pair {
+    /// Compare the outputs of a completed round
+    fn compare(&mut self, kernel: &Kernel) {
+        self.done = 0;
+        let agree = match (kernel.arena(self.a as usize), kernel.arena(self.b as usize)) {
+            (Some(a), Some(b)) => (self.compare)(a, b),
+            _ => false,
+        };
+        if agree {
+            self.agreed = self.agreed.saturating_add(1);
+            return;
+        }
+        self.mismatches = self.mismatches.saturating_add(1);
+        self.raise(kernel, FaultCode::REPLICA_MISMATCH, self.a, false);
+    }
+
+    /// Abandon a round in which a replica did not complete
+    fn timeout(&mut self, kernel: &Kernel) {
+        let missing = if self.done & DONE_A == 0 {
+            self.a
+        } else {
+            self.b
+        };
+        self.done = 0;
+        self.timeouts = self.timeouts.saturating_add(1);
+        self.raise(kernel, FaultCode::REPLICA_TIMEOUT, missing, true);
+    }
+
+    /// Report a replica fault against task `idx`
+    fn raise(&self, kernel: &Kernel, code: FaultCode, idx: u8, timeout: bool) {
+        emit(KernelEvent::ReplicaFault {
+            a: self.a,
+            b: self.b,
+            timeout,
+        });
+        fault::capture(
+            code,
+            Some(idx as usize),
+            kernel.timer.now_us(),
+            u32::from(self.a) | u32::from(self.b) << 8,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Task, TaskPriority};
+
+    fn replica_a(out: &mut [u8]) {
+        out[0] = out[0].wrapping_add(1);
+    }
+
+    fn replica_b(out: &mut [u8]) {
+        out[0] += 1;
+    }
+
+    fn faulty(out: &mut [u8]) {
+        out[0] += 2;
+    }
+
+    fn same(a: &[u8], b: &[u8]) -> bool {
+        a == b
+    }
+
+    fn pair() -> (Kernel, RedundancyMonitor, usize) {
+        let mut kernel = Kernel::testing();
+        let a = Task::new(b"a", replica_a, TaskPriority::HIGH, 1_000, 50).with_arena(8);
+        let b = Task::new(b"b", replica_b, TaskPriority::NORMAL, 1_000, 50).with_arena(8);
+        kernel.try_add(a).unwrap();
+        kernel.try_add(b).unwrap();
+        let mut monitor = RedundancyMonitor::new();
+        let pair = monitor.redundant_pair(&kernel, 0, 1, same).unwrap();
+        (kernel, monitor, pair)
+    }
+
+    #[test]
+    fn test_pair_validation() {
+        let mut kernel = Kernel::testing();
+        let a = Task::new(b"a", replica_a, TaskPriority::HIGH, 1_000, 50).with_arena(8);
+        kernel.try_add(a).unwrap();
+        kernel.add_task(b"plain", replica_a, TaskPriority::HIGH, 1_000, 50);
+        let slow = Task::new(b"slow", replica_b, TaskPriority::HIGH, 2_000, 50).with_arena(8);
+        kernel.try_add(slow).unwrap();
+        let mut m = RedundancyMonitor::new();
+        // アリーナなし・周期違い・同一タスク
+        for (a, b) in [(0, 1), (0, 2), (0, 0), (0, 9)] {
+            assert_eq!(
+                m.redundant_pair(&kernel, a, b, same),
+                Err(KernelError::InvalidIndex)
+            );
+        }
+        assert!(!m.set_timeout(0, 10));
+        assert!(!m.remove(0));
+    }
+
+    #[test]
+    fn test_mismatch_raises_fault() {
+        let (mut k, mut m, p) = pair();
+        m.run_for(&mut k, 5_000, 100);
+        assert_eq!((m.agreed(p), m.mismatches(p)), (5, 0));
+        // b の出力を 1 周期だけ狂わせる
+        k.scheduler.get_task_mut(1).unwrap().func = Some(faulty);
+        m.run_for(&mut k, 1_000, 100);
+        assert_eq!(m.mismatches(p), 1);
+        k.scheduler.get_task_mut(1).unwrap().func = Some(replica_b);
+        // 以降は b の出力がずれたまま
+        m.run_for(&mut k, 2_000, 100);
+        assert_eq!((m.agreed(p), m.mismatches(p)), (5, 3));
+        assert_eq!(m.timeouts(p), 0);
+    }
+
+    #[test]
+    fn test_missing_replica_times_out() {
+        let (mut k, mut m, p) = pair();
+        m.run_for(&mut k, 2_000, 100);
+        k.scheduler.suspend(1);
+        m.run_for(&mut k, 3_000, 100);
+        // a だけが完了し、毎周期タイムアウト
+        assert_eq!(m.agreed(p), 2);
+        assert!((2..=3).contains(&m.timeouts(p)), "{}", m.timeouts(p));
+        assert_eq!(m.mismatches(p), 0);
+
+        // 連続 tick (100 µs 差) の完了も許さないタイムアウト
+        let (mut k, mut m, p) = pair();
+        assert!(m.set_timeout(p, 50));
+        m.run_for(&mut k, 3_000, 100);
+        assert_eq!(m.agreed(p), 0);
+        assert!(m.timeouts(p) >= 3);
+        assert!(m.remove(p));
+        assert_eq!(m.timeouts(p), 0);
+    }
+}
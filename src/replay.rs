@@ -0,0 +1,367 @@
+//! Deterministic record / replay of kernel ticks
+//!
+//! In record mode every `tick(delta)` is logged together with the
+//! scheduler's decision (executed task + ready set after dispatch) into a
+//! fixed [`TickLog`]. Replay feeds the identical delta sequence into a
+//! freshly configured kernel and checks each decision, stopping at the
+//! first divergence. Replaying only a prefix (`limit`) lets a failure be
+//! bisected step by step.
+//!
+//! For long runs, or to compare a device against the simulator without
+//! shipping a log, [`ExecHash`] folds every execution (kernel time, task)
+//! into a running FNV-1a hash: equal hashes and execution counts mean the
+//! same execution sequence.
+//!
+//! ```rust
+//! use alice_rtos::replay::ExecHash;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let run = || {
+//!     let mut kernel = Kernel::testing();
+//!     kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 100, 10).unwrap();
+//!     kernel.add_task(b"log", noop, TaskPriority::LOW, 250, 20).unwrap();
+//!     let mut audit = ExecHash::new();
+//!     for _ in 0..100 {
+//!         audit.tick(&mut kernel, 50);
+//!     }
+//!     audit
+//! };
+//! assert_eq!(run(), run());
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::Kernel;
+use crate::task::TaskMask;
+
+/// Marker for "no task executed"
+const IDLE: u8 = u8::MAX;
+
+/// FNV-1a 32-bit offset basis
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+
+/// FNV-1a 32-bit prime
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// One recorded tick (8 bytes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickRecord {
+    /// Time advanced by this tick (µs)
+    pub delta_us: u32,
+    /// Ready set after dispatch
+    pub ready: TaskMask,
+    /// Executed task index (`u8::MAX` = idle)
+    executed: u8,
+}
+
+impl TickRecord {
+    /// Executed task, if any
+    #[must_use]
+    pub const fn executed(&self) -> Option<usize> {
+        if self.executed == IDLE {
+            None
+        } else {
+            Some(self.executed as usize)
+        }
+    }
+}
+
+/// First tick at which a replay disagreed with the recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the diverging tick
+    pub step: usize,
+    /// Recorded decision
+    pub expected: TickRecord,
+    /// Decision observed during replay
+    pub actual: TickRecord,
+}
+
+/// Fixed-capacity tick log
+pub struct TickLog<const N: usize> {
+    /// Records
+    records: [TickRecord; N],
+    /// Valid records
+    len: usize,
+    /// Ticks dropped because the log was full
+    dropped: u32,
+}
+
+impl<const N: usize> Default for TickLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TickLog<N> {
+    /// Empty log
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            records: [TickRecord {
+                delta_us: 0,
+                ready: 0,
+                executed: IDLE,
+            }; N],
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Run `kernel.tick(delta_us)` and record the decision
+    ///
+    /// Once the log is full, ticks still run but are counted as dropped.
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u32) -> Option<usize> {
+        let executed = kernel.tick(delta_us as u64);
+        let record = Self::observe(kernel, delta_us, executed);
+        if self.len < N {
+            self.records[self.len] = record;
+            self.len += 1;
+        } else {
+            self.dropped += 1;
+        }
+        executed
+    }
+
+    /// Feed the first `limit` recorded ticks into `kernel`
+    ///
+    /// `kernel` must be configured exactly as the recorded one was.
+    /// Returns the number of ticks replayed, or the first divergence.
+    pub fn replay(&self, kernel: &mut Kernel, limit: usize) -> Result<usize, Divergence> {
+        let steps = limit.min(self.len);
+        for (step, expected) in self.records[..steps].iter().enumerate() {
+            let executed = kernel.tick(expected.delta_us as u64);
+            let actual = Self::observe(kernel, expected.delta_us, executed);
+            if actual != *expected {
+                return Err(Divergence {
+                    step,
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Recorded ticks
+    #[must_use]
+    pub fn records(&self) -> &[TickRecord] {
+        &self.records[..self.len]
+    }
+
+    /// Ticks that did not fit in the log
+    #[must_use]
+    pub const fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Forget all records
+    pub const fn clear(&mut self) {
+        self.len = 0;
+        self.dropped = 0;
+    }
+
+    /// Capture the scheduler decision after a tick
+    fn observe(kernel: &Kernel, delta_us: u32, executed: Option<usize>) -> TickRecord {
+        TickRecord {
+            delta_us,
+            ready: kernel.scheduler.ready_mask(),
+            executed: executed.map_or(IDLE, |i| i as u8),
+        }
+    }
+}
+
+/// Running hash of the execution sequence (determinism audit)
+///
+/// Two runs executed the same tasks at the same kernel times iff their
+/// `ExecHash`es compare equal (up to hash collisions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecHash {
+    /// FNV-1a over (time, task) of every execution
+    hash: u32,
+    /// Executions hashed
+    executions: u64,
+}
+
+impl Default for ExecHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecHash {
+    /// Hash of the empty sequence
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            hash: FNV_OFFSET,
+            executions: 0,
+        }
+    }
+
+    /// Run `kernel.tick(delta_us)` and hash the execution, if any
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        if let Some(idx) = executed {
+            self.record(kernel.timer.now_us(), idx);
+        }
+        executed
+    }
+
+    /// Hash task `idx` executing at kernel time `at_us`
+    ///
+    /// For kernels ticked elsewhere, e.g. a `StaticKernel` in the tick
+    /// interrupt.
+    pub fn record(&mut self, at_us: u64, idx: usize) {
+        for byte in at_us.to_le_bytes().into_iter().chain([idx as u8]) {
+            self.hash = (self.hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+        self.executions += 1;
+    }
+
+    /// Hash of the executions so far
+    #[must_use]
+    pub const fn hash(&self) -> u32 {
+        self.hash
+    }
+
+    /// Executions hashed so far
+    #[must_use]
+    pub const fn executions(&self) -> u64 {
+        self.executions
+    }
+
+    /// Start a new sequence
+    pub const fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn setup() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop, TaskPriority::HIGH, 100, 10);
+        kernel.add_task(b"slow", noop, TaskPriority::LOW, 350, 30);
+        kernel
+    }
+
+    fn jittery_deltas() -> impl Iterator<Item = u32> {
+        [10u32, 90, 55, 5, 120, 60, 40, 200, 15, 85]
+            .into_iter()
+            .cycle()
+            .take(40)
+    }
+
+    #[test]
+    fn test_record_then_replay_identical() {
+        let mut log = TickLog::<64>::new();
+        let mut kernel = setup();
+        for d in jittery_deltas() {
+            log.tick(&mut kernel, d);
+        }
+        assert_eq!(log.records().len(), 40);
+
+        let mut fresh = setup();
+        assert_eq!(log.replay(&mut fresh, usize::MAX), Ok(40));
+        assert_eq!(fresh.scheduler.now_us(), kernel.scheduler.now_us());
+    }
+
+    #[test]
+    fn test_replay_detects_divergence() {
+        let mut log = TickLog::<64>::new();
+        let mut kernel = setup();
+        for d in jittery_deltas() {
+            log.tick(&mut kernel, d);
+        }
+        // 構成が異なるカーネル → 最初の判断で不一致
+        let mut other = Kernel::testing();
+        other.add_task(b"fast", noop, TaskPriority::HIGH, 100, 10);
+        let err = log.replay(&mut other, usize::MAX).unwrap_err();
+        assert!(err.step < 40);
+        assert_ne!(err.expected, err.actual);
+    }
+
+    #[test]
+    fn test_replay_prefix_for_bisect() {
+        let mut log = TickLog::<64>::new();
+        let mut kernel = setup();
+        for d in jittery_deltas() {
+            log.tick(&mut kernel, d);
+        }
+        let mut fresh = setup();
+        assert_eq!(log.replay(&mut fresh, 7), Ok(7));
+        let elapsed: u64 = log.records()[..7].iter().map(|r| r.delta_us as u64).sum();
+        assert_eq!(fresh.timer.now_us(), elapsed);
+    }
+
+    #[test]
+    fn test_full_log_counts_dropped() {
+        let mut log = TickLog::<4>::new();
+        let mut kernel = setup();
+        for _ in 0..6 {
+            log.tick(&mut kernel, 50);
+        }
+        assert_eq!(log.records().len(), 4);
+        assert_eq!(log.dropped(), 2);
+        log.clear();
+        assert!(log.records().is_empty());
+    }
+
+    #[test]
+    fn test_exec_hash_identical_runs_match() {
+        let run = || {
+            let mut kernel = setup();
+            let mut audit = ExecHash::new();
+            for d in jittery_deltas() {
+                audit.tick(&mut kernel, u64::from(d));
+            }
+            audit
+        };
+        let audit = run();
+        assert_eq!(audit, run());
+        assert_ne!(audit.hash(), ExecHash::new().hash());
+        assert!(audit.executions() > 0);
+    }
+
+    #[test]
+    fn test_exec_hash_detects_divergence() {
+        let mut a = ExecHash::new();
+        let mut b = ExecHash::new();
+        let mut ka = setup();
+        let mut kb = setup();
+        for (i, d) in jittery_deltas().enumerate() {
+            a.tick(&mut ka, u64::from(d));
+            // 1 tick だけ 1 µs ずれる → 実行時刻が異なる
+            b.tick(&mut kb, u64::from(d) + u64::from(i == 10));
+        }
+        assert_ne!(a.hash(), b.hash());
+
+        // 同じ実行を別タスクとして記録しても不一致
+        let (mut x, mut y) = (ExecHash::new(), ExecHash::new());
+        x.record(100, 0);
+        y.record(100, 1);
+        assert_ne!(x, y);
+        x.reset();
+        assert_eq!(x, ExecHash::new());
+    }
+
+    #[test]
+    fn test_record_executed_decoding() {
+        let mut log = TickLog::<4>::new();
+        let mut kernel = Kernel::testing();
+        log.tick(&mut kernel, 10);
+        assert_eq!(log.records()[0].executed(), None);
+        kernel.add_task(b"t", noop, TaskPriority::NORMAL, 100, 10);
+        log.tick(&mut kernel, 10);
+        assert_eq!(log.records()[1].executed(), Some(0));
+        assert_eq!(core::mem::size_of::<TickRecord>(), 8);
+    }
+}
@@ -0,0 +1,373 @@
+//! Message router between two kernels or cores
+//!
+//! A static [`Router`] joins two sides — two [`Kernel`] instances, or the
+//! kernels of two cores — with a pair of SPSC rings, one per direction.
+//! Every message travels in an [`Envelope`] whose small header names the
+//! destination task on the receiving side. There, a dispatcher task built
+//! with [`dispatch_task`] calls [`Router::dispatch`], which moves each
+//! message into the destination task's queue of a static [`Inboxes`];
+//! the task reads its own queue with [`Inboxes::recv`].
+//!
+//! Like [`Mailbox`](crate::mailbox::Mailbox), the router trusts its
+//! callers with the single-producer / single-consumer rule: all sends of
+//! one side come from one execution context (the tasks of one kernel,
+//! which run to completion, but not its interrupt handlers), all
+//! dispatches of a side from its dispatcher, and each inbox is read only
+//! by its task. Messages for an unknown task or a full inbox are dropped
+//! and counted ([`Inboxes::dropped`]); a full ring makes
+//! [`send`](Router::send) return false and leaves the choice to the sender.
+//!
+//! Across cores, [`send_and_signal`](Router::send_and_signal) rings a
+//! [`Doorbell`] so the receiving core wakes up promptly, and its
+//! dispatcher acknowledges with [`clear_and_dispatch`](Router::clear_and_dispatch).
+//!
+//! Time-stamped payloads crossing kernels are put on the receiver's clock
+//! with a [`TimeDomain`](crate::domain::TimeDomain).
+//!
+//! ```rust
+//! use alice_rtos::router::{dispatch_task, Inboxes, Router, Side};
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! static ROUTER: Router<u16, 8> = Router::new();
+//! static MOTION_INBOX: Inboxes<u16, 4> = Inboxes::new();
+//!
+//! const SERVO: usize = 1;
+//!
+//! fn beat(_: &mut [u8]) {
+//!     ROUTER.send(Side::A, SERVO, 120);
+//! }
+//! fn route(_: &mut [u8]) {
+//!     ROUTER.dispatch(Side::B, &MOTION_INBOX);
+//! }
+//! fn servo(scratch: &mut [u8]) {
+//!     while let Some(bpm) = MOTION_INBOX.recv(SERVO) {
+//!         scratch[0] = bpm as u8;
+//!     }
+//! }
+//!
+//! let mut audio = Kernel::testing();
+//! let mut motion = Kernel::testing();
+//! audio.add_task(b"beat", beat, TaskPriority::HIGH, 1_000, 10).unwrap();
+//! motion.scheduler.register(dispatch_task(route, 500, 20)).unwrap();
+//! motion.add_task(b"servo", servo, TaskPriority::NORMAL, 1_000, 50).unwrap();
+//!
+//! audio.tick(10);
+//! assert_eq!(ROUTER.pending(Side::B), 1);
+//! motion.run_for(1_000, 100);
+//! assert_eq!(ROUTER.pending(Side::B), 0);
+//! assert_eq!(MOTION_INBOX.dropped(), 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicU32, Ordering};
+use crate::channel::Channel;
+use crate::doorbell::Doorbell;
+use crate::task::{Task, TaskFn, TaskMask, TaskPriority, MAX_TASKS};
+
+#[cfg(doc)]
+use crate::kernel::Kernel;
+
+/// Priority of the dispatcher task
+pub const DISPATCH_PRIORITY: TaskPriority = TaskPriority::HIGH;
+
+/// Dispatcher task: `dispatch` runs every `period_us` at [`DISPATCH_PRIORITY`]
+///
+/// `wcet_us` must cover delivering a full ring.
+pub fn dispatch_task(dispatch: TaskFn, period_us: u32, wcet_us: u32) -> Task {
+    Task::new(b"router", dispatch, DISPATCH_PRIORITY, period_us, wcet_us)
+}
+
+/// One end of a [`Router`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// First kernel / core
+    A,
+    /// Second kernel / core
+    B,
+}
+
+impl Side {
+    /// The opposite end
+    #[must_use]
+    pub const fn other(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+
+    /// Ring this side sends on
+    const fn outgoing(self) -> usize {
+        self as usize
+    }
+}
+
+/// Message with its addressing header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<T> {
+    /// Destination task slot on the receiving side
+    pub dest: u8,
+    /// Payload
+    pub msg: T,
+}
+
+/// Two SPSC rings of `N - 1` messages each, one per direction
+pub struct Router<T, const N: usize> {
+    /// Ring `s` carries messages sent from side `s`
+    rings: [Channel<Envelope<T>, N>; 2],
+}
+
+impl<T: Copy, const N: usize> Default for Router<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> Router<T, N> {
+    /// Empty router (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            rings: [Channel::new(), Channel::new()],
+        }
+    }
+
+    /// Send `msg` from side `from` to task `dest` of the other side
+    ///
+    /// Returns false if the ring is full or `dest` is not a task slot.
+    pub fn send(&self, from: Side, dest: usize, msg: T) -> bool {
+        dest < MAX_TASKS
+            && self.rings[from.outgoing()].push(Envelope {
+                dest: dest as u8,
+                msg,
+            })
+    }
+
+    /// [`send`](Self::send), then ring the receiving side's `doorbell`
+    ///
+    /// `doorbell` is the sending side's handle; it rings only when the
+    /// message was queued.
+    pub fn send_and_signal<D: Doorbell>(
+        &self,
+        from: Side,
+        dest: usize,
+        msg: T,
+        doorbell: &D,
+    ) -> bool {
+        let sent = self.send(from, dest, msg);
+        if sent {
+            doorbell.signal();
+        }
+        sent
+    }
+
+    /// Oldest message waiting for side `at`, header included
+    pub fn recv(&self, at: Side) -> Option<Envelope<T>> {
+        self.rings[at.other().outgoing()].pop()
+    }
+
+    /// Messages waiting for side `at`
+    pub fn pending(&self, at: Side) -> usize {
+        self.rings[at.other().outgoing()].len()
+    }
+
+    /// Deliver every message waiting for side `at` into `inboxes`
+    ///
+    /// Call from the dispatcher task of side `at`. Returns the tasks that
+    /// received a message, e.g. to release event-driven ones.
+    pub fn dispatch<const M: usize>(&self, at: Side, inboxes: &Inboxes<T, M>) -> TaskMask {
+        let mut delivered: TaskMask = 0;
+        while let Some(envelope) = self.recv(at) {
+            let idx = usize::from(envelope.dest);
+            if inboxes.deliver(idx, envelope.msg) {
+                delivered |= 1 << idx;
+            }
+        }
+        delivered
+    }
+
+    /// Acknowledge side `at`'s `doorbell`, then [`dispatch`](Self::dispatch)
+    ///
+    /// Messages whose ring arrives during the dispatch are delivered now
+    /// or on the next call, never left without a pending ring.
+    pub fn clear_and_dispatch<D: Doorbell, const M: usize>(
+        &self,
+        at: Side,
+        inboxes: &Inboxes<T, M>,
+        doorbell: &D,
+    ) -> TaskMask {
+        doorbell.clear();
+        self.dispatch(at, inboxes)
+    }
+}
+
+/// Per-task message queues of `M - 1` messages filled by a dispatcher
+pub struct Inboxes<T, const M: usize> {
+    /// Queue of each task slot
+    queues: [Channel<T, M>; MAX_TASKS],
+    /// Messages lost to a full queue
+    dropped: AtomicU32,
+}
+
+impl<T: Copy, const M: usize> Default for Inboxes<T, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const M: usize> Inboxes<T, M> {
+    /// Empty inboxes (usable in `static`s)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            queues: [const { Channel::new() }; MAX_TASKS],
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// Oldest message for task `idx` (call from that task only)
+    pub fn recv(&self, idx: usize) -> Option<T> {
+        self.queues.get(idx)?.pop()
+    }
+
+    /// Messages waiting for task `idx`
+    pub fn len(&self, idx: usize) -> usize {
+        self.queues.get(idx).map_or(0, Channel::len)
+    }
+
+    /// Messages dropped because the task's queue was full
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue `msg` for task `idx`, counting a drop if it is full
+    fn deliver(&self, idx: usize, msg: T) -> bool {
+        let delivered = self.queues.get(idx).is_some_and(|q| q.push(msg));
+        if !delivered {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doorbell::DoorbellPair;
+    use crate::kernel::Kernel;
+
+    #[test]
+    fn test_sides_are_independent_directions() {
+        let r: Router<u32, 4> = Router::new();
+        assert!(r.send(Side::A, 2, 10));
+        assert!(r.send(Side::B, 5, 20));
+        assert_eq!(r.pending(Side::B), 1);
+        assert_eq!(r.recv(Side::B), Some(Envelope { dest: 2, msg: 10 }));
+        assert_eq!(r.recv(Side::B), None);
+        assert_eq!(r.recv(Side::A), Some(Envelope { dest: 5, msg: 20 }));
+        // 範囲外の宛先とリング満杯は送信側で拒否
+        assert!(!r.send(Side::A, MAX_TASKS, 1));
+        for i in 0..3 {
+            assert!(r.send(Side::A, 0, i));
+        }
+        assert!(!r.send(Side::A, 0, 3));
+    }
+
+    #[test]
+    fn test_dispatch_fills_inboxes_and_counts_drops() {
+        let r: Router<u8, 8> = Router::new();
+        let inbox: Inboxes<u8, 3> = Inboxes::new();
+        for msg in 0..4 {
+            r.send(Side::B, 1, msg);
+        }
+        r.send(Side::B, 4, 9);
+        assert_eq!(r.dispatch(Side::A, &inbox), 1 << 1 | 1 << 4);
+        // 受信箱は 2 件まで: 残り 2 件は捨てる
+        assert_eq!(inbox.len(1), 2);
+        assert_eq!(inbox.dropped(), 2);
+        assert_eq!(
+            (inbox.recv(1), inbox.recv(1), inbox.recv(1)),
+            (Some(0), Some(1), None)
+        );
+        assert_eq!(inbox.recv(4), Some(9));
+        assert_eq!(inbox.recv(MAX_TASKS), None);
+        assert_eq!(r.dispatch(Side::A, &inbox), 0);
+    }
+
+    #[test]
+    fn test_doorbell_rings_only_on_queued_messages() {
+        let r: Router<u8, 2> = Router::new();
+        let inbox: Inboxes<u8, 4> = Inboxes::new();
+        let bells = DoorbellPair::new();
+        let (core0, core1) = (bells.handle(Side::A), bells.handle(Side::B));
+        assert!(r.send_and_signal(Side::A, 3, 1, &core0));
+        assert!(core1.poll());
+        assert!(core1.clear());
+        // リング満杯: 送れず、鳴らさない
+        assert!(!r.send_and_signal(Side::A, 3, 2, &core0));
+        assert!(!core1.poll());
+        core0.signal();
+        assert_eq!(r.clear_and_dispatch(Side::B, &inbox, &core1), 1 << 3);
+        assert!(!core1.poll());
+        assert_eq!(inbox.recv(3), Some(1));
+    }
+
+    static PINGS: Router<u32, 8> = Router::new();
+    static INBOX_A: Inboxes<u32, 4> = Inboxes::new();
+    static INBOX_B: Inboxes<u32, 4> = Inboxes::new();
+    static ECHOED: AtomicU32 = AtomicU32::new(0);
+
+    fn ping(_: &mut [u8]) {
+        // A の 1 番から B の 1 番へ
+        PINGS.send(Side::A, 1, 7);
+    }
+
+    fn route_a(_: &mut [u8]) {
+        PINGS.dispatch(Side::A, &INBOX_A);
+    }
+
+    fn route_b(_: &mut [u8]) {
+        PINGS.dispatch(Side::B, &INBOX_B);
+    }
+
+    fn echo(_: &mut [u8]) {
+        while let Some(n) = INBOX_B.recv(1) {
+            PINGS.send(Side::B, 2, n * 2);
+        }
+    }
+
+    fn pong(_: &mut [u8]) {
+        while let Some(n) = INBOX_A.recv(2) {
+            ECHOED.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_between_kernels() {
+        let mut a = Kernel::testing();
+        let mut b = Kernel::testing();
+        a.scheduler
+            .register(dispatch_task(route_a, 500, 20))
+            .unwrap();
+        a.add_task(b"ping", ping, TaskPriority::NORMAL, 1_000, 10)
+            .unwrap();
+        a.add_task(b"pong", pong, TaskPriority::LOW, 1_000, 10)
+            .unwrap();
+        b.scheduler
+            .register(dispatch_task(route_b, 500, 20))
+            .unwrap();
+        b.add_task(b"echo", echo, TaskPriority::NORMAL, 500, 10)
+            .unwrap();
+
+        // 2 つのカーネルを交互に 100 µs ずつ進める
+        for _ in 0..30 {
+            a.run_for(100, 10);
+            b.run_for(100, 10);
+        }
+        // 3 ms で ping は 3 回、応答は往復の遅れで 2 回分届いている
+        assert_eq!(ECHOED.load(Ordering::Relaxed), 2 * 14);
+        assert_eq!(INBOX_A.dropped() + INBOX_B.dropped(), 0);
+        assert_eq!(b.scheduler.get_task(0).unwrap().priority, DISPATCH_PRIORITY);
+    }
+}
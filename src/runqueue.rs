@@ -0,0 +1,400 @@
+//! Fixed-priority run queue: O(1) dispatch without rescanning the table
+//!
+//! With the default [`Rms`] policy every tick walks the whole task table
+//! to release tasks and collect the ready ones. A [`RunQueue`] is an
+//! [`Rms`]-ordered policy that keeps that state itself, and the scheduler
+//! updates it on release, dispatch and every change to the table:
+//!
+//! - a ready bitmap in priority order: bit `r` is the `r`-th task by
+//!   (priority, slot), so each priority level is a contiguous run of bits
+//!   — that level's task list, in slot order — and find-first-set gives
+//!   the highest-priority ready task in one step;
+//! - per-core masks in the same order (affinity), and each task's
+//!   successors, so a completed job publishes its output without a walk;
+//! - each task's next release or deadline in a [`TimerWheel`]; a tick
+//!   only looks at the tasks the wheel reports due, the interrupt
+//!   releases and the task it dispatches, and the tickless wakeup is the
+//!   wheel's earliest expiry.
+//!
+//! Any change made between ticks (suspend, resume, priorities, periods,
+//! dependencies, registration) marks the queue stale and the next tick
+//! rebuilds it with one walk of the table. Dispatch order, releases and
+//! deadline accounting are those of [`Rms`]; the tickless wakeup also
+//! covers the first instant a pending job misses its deadline, so the
+//! miss is reported then rather than at the next release.
+//!
+//! The queue lives in the policy slot of the scheduler, so the default
+//! kernel keeps its size; a kernel using it is larger by
+//! `size_of::<RunQueue>()`.
+//!
+//! ```rust
+//! use alice_rtos::runqueue::RunQueue;
+//! use alice_rtos::timer::SysTimer;
+//! use alice_rtos::{SizedKernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel: SizedKernel<256, RunQueue> =
+//!     SizedKernel::with_policy(SysTimer::software(), RunQueue::new());
+//! let slow = kernel.add_task(b"slow", noop, TaskPriority::LOW, 10_000, 10).unwrap();
+//! let fast = kernel.add_task(b"fast", noop, TaskPriority::HIGH, 1_000, 10).unwrap();
+//! assert_eq!(kernel.tick(0), Some(fast));
+//! assert_eq!(kernel.tick(10), Some(slow));
+//! assert_eq!(kernel.tick(10), None);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::policy::{Rms, SchedulingPolicy};
+use crate::task::{
+    mask_slots, CoreMask, Task, TaskMask, TaskState, ANY_CORE, MAX_CORES, MAX_TASKS,
+};
+use crate::timer::{tick_to_u64, WrappingInstant};
+use crate::wheel::TimerWheel;
+
+/// Ready set and pending events of a fixed-priority scheduler
+#[derive(Debug, Clone)]
+pub struct RunQueue {
+    /// Slot of each rank (priority order, ties by slot)
+    order: [u8; MAX_TASKS],
+    /// Rank of each slot
+    rank: [u8; MAX_TASKS],
+    /// Ready tasks with their inputs in, by rank
+    ready: TaskMask,
+    /// Tasks allowed on each core, by rank
+    on_core: [TaskMask; MAX_CORES],
+    /// Tasks consuming the output of each slot (bit i = task i)
+    successors: [TaskMask; MAX_TASKS],
+    /// Time-released tasks with period 0, looked at every tick
+    polled: TaskMask,
+    /// Next release or deadline of each task (`clock` µs)
+    wheel: TimerWheel,
+    /// Time accumulated from the tick deltas (µs, never wraps)
+    clock: u64,
+}
+
+impl Default for RunQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunQueue {
+    /// Empty queue; the first tick builds it from the task table
+    #[must_use]
+    pub const fn new() -> Self {
+        let mut order = [0u8; MAX_TASKS];
+        let mut i = 0;
+        while i < MAX_TASKS {
+            order[i] = i as u8;
+            i += 1;
+        }
+        Self {
+            order,
+            rank: order,
+            ready: 0,
+            on_core: [0; MAX_CORES],
+            successors: [0; MAX_TASKS],
+            polled: 0,
+            wheel: TimerWheel::new(1),
+            clock: 0,
+        }
+    }
+
+    /// Ready tasks with their inputs in (bit i = task i)
+    #[must_use]
+    pub fn ready_tasks(&self) -> TaskMask {
+        self.slot_mask(self.ready)
+    }
+
+    /// Time until the earliest pending release or deadline (µs)
+    #[must_use]
+    pub fn next_event_us(&self) -> Option<u64> {
+        self.wheel
+            .next_expiry_us()
+            .map(|at| at.saturating_sub(self.clock))
+    }
+
+    /// Advance the clock; returns the tasks whose release or deadline is
+    /// due, and the period-0 ones released at every tick
+    pub(crate) fn advance(&mut self, delta_us: u64) -> TaskMask {
+        self.clock = self.clock.saturating_add(delta_us);
+        self.wheel.advance(self.clock) | self.polled
+    }
+
+    /// Rebuild the priority order, core and successor masks from the table
+    pub(crate) fn rebuild(&mut self, tasks: &[Task]) {
+        // Insertion sort by (priority, slot); slots past the table go last
+        let key = |slot: u8| {
+            tasks
+                .get(slot as usize)
+                .map_or((1, 0), |t| (0, t.priority.0))
+        };
+        for i in 0..MAX_TASKS {
+            let mut j = i;
+            while j > 0 && key(i as u8) < key(self.order[j - 1]) {
+                self.order[j] = self.order[j - 1];
+                j -= 1;
+            }
+            self.order[j] = i as u8;
+        }
+        for (r, &slot) in self.order.iter().enumerate() {
+            self.rank[slot as usize] = r as u8;
+        }
+        self.on_core = [0; MAX_CORES];
+        self.successors = [0; MAX_TASKS];
+        self.polled = 0;
+        for (i, task) in tasks.iter().enumerate() {
+            if task.time_released() && task.period_us == 0 {
+                self.polled |= 1 << i;
+            }
+            for (core, mask) in self.on_core.iter_mut().enumerate() {
+                if task.affinity & (1 << core) != 0 {
+                    *mask |= 1 << self.rank[i];
+                }
+            }
+            for pred in mask_slots(task.predecessors) {
+                self.successors[pred] |= 1 << i;
+            }
+        }
+    }
+
+    /// Recompute the ready set and the next event from the table
+    pub(crate) fn refresh(&mut self, tasks: &[Task], now: WrappingInstant) {
+        self.ready = 0;
+        for (i, task) in tasks.iter().enumerate() {
+            self.track(i, task, now);
+        }
+        for i in tasks.len()..MAX_TASKS {
+            self.wheel.remove(i);
+        }
+    }
+
+    /// Follow a change of task `idx`'s state made by the tick
+    pub(crate) fn track(&mut self, idx: usize, task: &Task, now: WrappingInstant) {
+        let bit: TaskMask = 1 << self.rank[idx];
+        if task.state == TaskState::Ready && task.inputs_complete() {
+            self.ready |= bit;
+        } else {
+            self.ready &= !bit;
+        }
+        match Self::wait_us(task, now) {
+            Some(wait) => self.wheel.insert(idx, self.clock.saturating_add(wait)),
+            None => self.wheel.remove(idx),
+        };
+    }
+
+    /// Highest-priority ready task allowed on `cores` ([`ANY_CORE`]: on
+    /// any, affinity ignored), not in `exclude`
+    pub(crate) fn pick(&self, cores: CoreMask, exclude: TaskMask) -> Option<usize> {
+        let mut allowed: TaskMask = if cores == ANY_CORE { TaskMask::MAX } else { 0 };
+        for (core, mask) in self.on_core.iter().enumerate() {
+            if cores & (1 << core) != 0 {
+                allowed |= mask;
+            }
+        }
+        let candidates = self.ready & allowed & !self.rank_mask(exclude);
+        (candidates != 0).then(|| self.order[candidates.trailing_zeros() as usize] as usize)
+    }
+
+    /// Tasks consuming the output of `idx`
+    pub(crate) fn successors(&self, idx: usize) -> TaskMask {
+        self.successors[idx]
+    }
+
+    /// Time until the tick must look at `task` again (None = only on an
+    /// interrupt release or a predecessor's completion)
+    fn wait_us(task: &Task, now: WrappingInstant) -> Option<u64> {
+        // A pending job misses its deadline on the first tick after it
+        let overdue =
+            || (task.period_us != 0).then(|| tick_to_u64(now.until(task.job_deadline())) + 1);
+        match task.state {
+            TaskState::Ready => overdue(),
+            // Period 0: in `polled`, not in the wheel
+            TaskState::Sleeping if task.time_released() && task.period_us != 0 => {
+                if !task.inputs_complete() {
+                    overdue()
+                } else if task.mode_change_pending() {
+                    Some(0)
+                } else {
+                    Some(tick_to_u64(now.until(task.next_release())))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Slot mask → rank mask
+    fn rank_mask(&self, slots: TaskMask) -> TaskMask {
+        mask_slots(slots).fold(0, |mask, i| mask | 1 << self.rank[i])
+    }
+
+    /// Rank mask → slot mask
+    fn slot_mask(&self, ranks: TaskMask) -> TaskMask {
+        mask_slots(ranks).fold(0, |mask, r| mask | 1 << self.order[r])
+    }
+}
+
+impl SchedulingPolicy for RunQueue {
+    #[inline]
+    fn select(&self, tasks: &[Task], ready: TaskMask, now: WrappingInstant) -> Option<usize> {
+        Rms.select(tasks, ready, now)
+    }
+
+    fn run_queue_mut(&mut self) -> Option<&mut RunQueue> {
+        Some(self)
+    }
+
+    fn run_queue(&self) -> Option<&RunQueue> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::Scheduler;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    /// 同じ操作列を Rms と RunQueue で実行し、ディスパッチと締め切りミスが一致すること
+    #[test]
+    fn test_matches_rms_scan() {
+        let mut rms = Scheduler::new();
+        let mut rq = Scheduler::with_policy(RunQueue::new());
+        let mut seed = 0x9e37_79b9_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for i in 0..12u8 {
+            let r = next();
+            let period = [1_000, 2_500, 4_000, 10_000, 0][(r % 5) as usize];
+            let task = Task::new(b"t", noop, TaskPriority(((r >> 8) % 4) as u8), period, 10)
+                .with_affinity(1 + ((r >> 16) % 3) as CoreMask);
+            let task = if r >> 40 & 7 == 0 {
+                task.released_by_isr()
+            } else {
+                task
+            };
+            assert_eq!(rms.register(task), rq.register(task));
+            if i > 2 && r >> 20 & 3 == 0 {
+                let pred = ((r >> 24) % u64::from(i)) as usize;
+                assert_eq!(rms.chain(pred, i as usize), rq.chain(pred, i as usize));
+            }
+        }
+        let mut exclude: TaskMask = 0;
+        for step in 0..20_000 {
+            let r = next();
+            let idx = (r % 12) as usize;
+            match r >> 56 {
+                0 => {
+                    rms.suspend(idx);
+                    rq.suspend(idx);
+                }
+                1 | 2 => {
+                    rms.resume(idx);
+                    rq.resume(idx);
+                }
+                3 => {
+                    assert_eq!(rms.set_period(idx, 5_000), rq.set_period(idx, 5_000));
+                }
+                4 => {
+                    let p = TaskPriority::CRITICAL;
+                    assert_eq!(rms.boost(idx, p, 300), rq.boost(idx, p, 300));
+                }
+                5..=8 => {
+                    assert_eq!(rms.release_from_isr(idx), rq.release_from_isr(idx));
+                }
+                _ => {}
+            }
+            let delta = (r >> 8) % 40;
+            let cores = 1 + ((r >> 32) % 3) as CoreMask;
+            // 予算切れの除外で飢餓状態を作り、締め切りミスも比較する
+            if step % 512 == 0 {
+                exclude = (r >> 36) as TaskMask;
+            }
+            assert_eq!(
+                rms.tick_on(0, cores, delta, exclude),
+                rq.tick_on(0, cores, delta, exclude),
+                "step {step}"
+            );
+            if step % 7 == 0 {
+                assert_eq!(rms.tick(delta), rq.tick(delta), "step {step}");
+            }
+        }
+        assert!(rms.tasks().any(|(_, t)| t.deadline_misses > 0));
+        for ((_, a), (_, b)) in rms.tasks().zip(rq.tasks()) {
+            assert_eq!(a.exec_count, b.exec_count);
+            assert_eq!(a.deadline_misses, b.deadline_misses);
+        }
+    }
+
+    #[test]
+    fn test_starved_job_misses_without_other_events() {
+        let mut s = Scheduler::with_policy(RunQueue::new());
+        let idx = s
+            .register(Task::new(b"t", noop, TaskPriority::HIGH, 1_000, 10))
+            .unwrap();
+        // 除外され続けたジョブは他のイベントがなくても締め切り直後にミス
+        let mut t = 0;
+        while t <= 1_000 {
+            assert_eq!(s.tick_on(0, 1, if t == 0 { 0 } else { 10 }, 1 << idx), None);
+            t += 10;
+        }
+        assert_eq!(s.get_task(idx).unwrap().deadline_misses, 0);
+        s.tick_on(0, 1, 1, 1 << idx);
+        assert_eq!(s.get_task(idx).unwrap().deadline_misses, 1);
+        assert_eq!(s.tick(10), Some(idx));
+        assert_eq!(s.get_task(idx).unwrap().deadline_misses, 1);
+    }
+
+    #[test]
+    fn test_tickless_matches_rms() {
+        use crate::kernel::SizedKernel;
+        use crate::timer::SysTimer;
+
+        fn kernel<P: SchedulingPolicy>(policy: P) -> SizedKernel<64, P> {
+            let mut k = SizedKernel::with_policy(SysTimer::software(), policy);
+            k.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 100);
+            k.add_task(b"b", noop, TaskPriority::NORMAL, 2_500, 300);
+            k.add_task(b"c", noop, TaskPriority::LOW, 7_000, 500);
+            k
+        }
+        let mut rms = kernel(Rms);
+        let mut rq = kernel(RunQueue::new());
+        // ホイールから求めた起床時刻でも同じ時刻に同じタスクが動く
+        for _ in 0..200 {
+            assert_eq!(rms.tick_tickless(u64::MAX), rq.tick_tickless(u64::MAX));
+        }
+        assert_eq!(
+            rms.scheduler.next_wakeup_us(),
+            rq.scheduler.next_wakeup_us()
+        );
+    }
+
+    #[test]
+    fn test_priority_levels_are_contiguous() {
+        let mut s = Scheduler::with_policy(RunQueue::new());
+        let low = s.register(Task::new(b"low", noop, TaskPriority::LOW, 1_000, 10));
+        let a = s.register(Task::new(b"a", noop, TaskPriority::HIGH, 1_000, 10));
+        let b = s.register(Task::new(b"b", noop, TaskPriority::HIGH, 1_000, 10));
+        // 同じ優先度ではスロット順、レベル内は連続したビット
+        assert_eq!(s.tick(0), a);
+        let q = s.policy();
+        assert_eq!(q.ready_tasks(), 1 << b.unwrap() | 1 << low.unwrap());
+        assert_eq!(
+            q.order[..3],
+            [a.unwrap() as u8, b.unwrap() as u8, low.unwrap() as u8]
+        );
+        assert_eq!(s.tick(10), b);
+        assert_eq!(s.tick(10), low);
+        assert_eq!(s.policy().ready_tasks(), 0);
+        // 次に表を見るのは 1 ms 後のリリース
+        assert_eq!(s.policy().next_event_us(), Some(980));
+        assert_eq!(s.next_wakeup_us(), Some(1_000));
+    }
+}
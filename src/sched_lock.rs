@@ -0,0 +1,213 @@
+//! Scheduler lock: non-preemptive regions with a watchdog
+//!
+//! [`Kernel::lock_scheduler`] returns a [`SchedulerLock`] guard. While it
+//! is held, ticks go through [`SchedulerLock::tick`]: time advances, jobs
+//! are released and miss their deadlines as usual, but nothing is
+//! dispatched — the code holding the lock is not switched out. Dropping
+//! the guard (or [`unlock`](SchedulerLock::unlock)) ends the region; the
+//! deferred jobs are dispatched by the following ticks in priority order.
+//!
+//! Every lock carries a maximum duration. A region still open past it is
+//! treated as abuse: the overrun is reported ([`KernelEvent::LockOverrun`]
+//! and [`FaultCode::LOCK_OVERRUN`] with the held time in µs) and the lock
+//! stops deferring — the guard's ticks dispatch normally from then on, so
+//! a forgotten unlock cannot starve the task set. An overrun noticed only
+//! at unlock (no tick in between) is reported there.
+//!
+//! The guard borrows the kernel mutably, so the region can only be ticked
+//! through it; read access goes through `Deref`.
+//!
+//! ```rust
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn ctl(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! let idx = kernel.add_task(b"ctl", ctl, TaskPriority::HIGH, 1_000, 100).unwrap();
+//!
+//! let mut lock = kernel.lock_scheduler(500);
+//! // ctl is ready but the region keeps it waiting
+//! assert_eq!(lock.tick(200), None);
+//! assert_eq!(lock.unlock(), 200);
+//! assert_eq!(kernel.tick(10), Some(idx));
+//!
+//! // Held too long: the watchdog fires and dispatching resumes
+//! let mut lock = kernel.lock_scheduler(500);
+//! assert_eq!(lock.tick(300), None);
+//! lock.tick(300);
+//! assert!(lock.overrun());
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::ops::Deref;
+
+use crate::events::{emit, KernelEvent};
+use crate::fault::{self, FaultCode};
+use crate::kernel::SizedKernel;
+use crate::policy::{Rms, SchedulingPolicy};
+use crate::task::TaskMask;
+
+#[cfg(doc)]
+use crate::kernel::Kernel;
+
+/// Guard of a non-preemptive region (see [`Kernel::lock_scheduler`])
+pub struct SchedulerLock<'a, const SCRATCH: usize, P: SchedulingPolicy = Rms> {
+    /// The locked kernel
+    kernel: &'a mut SizedKernel<SCRATCH, P>,
+    /// Kernel time the lock was taken (µs)
+    since_us: u64,
+    /// Longest allowed region (µs)
+    max_us: u32,
+    /// The watchdog fired: dispatching has resumed
+    overrun: bool,
+}
+
+impl<'a, const SCRATCH: usize, P: SchedulingPolicy> SchedulerLock<'a, SCRATCH, P> {
+    /// Lock `kernel` for at most `max_us`
+    pub(crate) fn new(kernel: &'a mut SizedKernel<SCRATCH, P>, max_us: u32) -> Self {
+        let since_us = kernel.timer.now_us();
+        Self {
+            kernel,
+            since_us,
+            max_us,
+            overrun: false,
+        }
+    }
+
+    /// Advance time by `delta_us` without dispatching
+    ///
+    /// Releases and deadline accounting happen as in
+    /// [`Kernel::tick`]. If the tick takes the region past its maximum,
+    /// the overrun is reported and this tick dispatches normally, as do
+    /// all later ones. Returns the task executed, if any.
+    pub fn tick(&mut self, delta_us: u64) -> Option<usize> {
+        if !self.overrun && self.held_us().saturating_add(delta_us) > u64::from(self.max_us) {
+            let executed = self.kernel.tick(delta_us);
+            self.report_overrun();
+            return executed;
+        }
+        let exclude = if self.overrun { 0 } else { TaskMask::MAX };
+        self.kernel.tick_excluding(delta_us, exclude)
+    }
+
+    /// Time the lock has been held (µs of kernel time)
+    #[must_use]
+    pub fn held_us(&self) -> u64 {
+        self.kernel.timer.now_us().saturating_sub(self.since_us)
+    }
+
+    /// Longest allowed region (µs)
+    #[must_use]
+    pub const fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    /// Did the region outlive its maximum?
+    #[must_use]
+    pub const fn overrun(&self) -> bool {
+        self.overrun
+    }
+
+    /// End the region; returns how long it was held (µs)
+    pub fn unlock(self) -> u64 {
+        // 超過の報告は drop で
+        self.held_us()
+    }
+
+    /// Report the watchdog firing (once)
+    fn report_overrun(&mut self) {
+        let held_us = self.held_us().min(u64::from(u32::MAX)) as u32;
+        self.overrun = true;
+        emit(KernelEvent::LockOverrun { held_us });
+        fault::capture(
+            FaultCode::LOCK_OVERRUN,
+            None,
+            self.kernel.timer.now_us(),
+            held_us,
+        );
+    }
+}
+
+impl<const SCRATCH: usize, P: SchedulingPolicy> Drop for SchedulerLock<'_, SCRATCH, P> {
+    fn drop(&mut self) {
+        if !self.overrun && self.held_us() > u64::from(self.max_us) {
+            self.report_overrun();
+        }
+    }
+}
+
+impl<const SCRATCH: usize, P: SchedulingPolicy> Deref for SchedulerLock<'_, SCRATCH, P> {
+    type Target = SizedKernel<SCRATCH, P>;
+
+    fn deref(&self) -> &Self::Target {
+        self.kernel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fault::{self, BlackBox, FaultCode};
+    use crate::kernel::Kernel;
+    use crate::task::TaskPriority;
+    use std::boxed::Box;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn kernel() -> Kernel {
+        let mut k = Kernel::testing();
+        k.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 100);
+        k.add_task(b"lo", noop, TaskPriority::LOW, 2_000, 100);
+        k
+    }
+
+    #[test]
+    fn test_lock_defers_then_priority_order() {
+        let mut k = kernel();
+        let mut lock = k.lock_scheduler(1_000);
+        for _ in 0..5 {
+            assert_eq!(lock.tick(100), None);
+        }
+        assert_eq!(lock.held_us(), 500);
+        assert!(!lock.overrun());
+        assert_eq!(lock.unlock(), 500);
+        // 保留されていたジョブが優先度順に走る
+        assert_eq!(k.tick(10), Some(0));
+        assert_eq!(k.tick(10), Some(1));
+        assert_eq!(k.scheduler.get_task(0).unwrap().deadline_misses, 0);
+    }
+
+    #[test]
+    fn test_deadlines_keep_running_while_locked() {
+        let mut k = kernel();
+        let mut lock = k.lock_scheduler(5_000);
+        for _ in 0..12 {
+            assert_eq!(lock.tick(100), None);
+        }
+        drop(lock);
+        // 1000 µs の締め切りはロック中に過ぎている
+        assert_eq!(k.scheduler.get_task(0).unwrap().deadline_misses, 1);
+        assert_eq!(k.scheduler.get_task(1).unwrap().deadline_misses, 0);
+    }
+
+    #[test]
+    fn test_watchdog_resumes_dispatch_and_records_fault() {
+        let bb: &'static mut BlackBox = Box::leak(Box::new(BlackBox::new()));
+        let bb_ptr: *const BlackBox = bb;
+        fault::install(bb);
+
+        let mut k = kernel();
+        let mut lock = k.lock_scheduler(250);
+        assert_eq!(lock.tick(200), None);
+        // 250 µs を超えるティックで解除され、そのまま配送される
+        assert_eq!(lock.tick(100), Some(0));
+        assert!(lock.overrun());
+        assert_eq!(lock.tick(10), Some(1));
+        drop(lock);
+
+        let last = unsafe { (*bb_ptr).last() }.unwrap();
+        assert_eq!(last.code, FaultCode::LOCK_OVERRUN);
+        assert_eq!(last.detail, 300);
+        fault::uninstall();
+    }
+}
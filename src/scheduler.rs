@@ -5,23 +5,66 @@
 //!
 //! Author: Moroya Sakamoto
 
-use crate::task::{Task, TaskPriority, TaskState, MAX_TASKS};
+use core::fmt;
+
+use crate::atomic::{AtomicU16, Ordering};
+
+use crate::error::{KernelError, KernelResult};
+use crate::events::{emit, KernelEvent};
+use crate::fault;
+use crate::policy::{Rms, SchedulingPolicy};
+#[cfg(doc)]
+use crate::runqueue::RunQueue;
+use crate::task::{
+    mask_slots, normalize_affinity, short_name, CoreMask, Mode, Task, TaskMask, TaskPriority,
+    TaskState, ANY_CORE, FLAG_CHAINED, FLAG_MODE_SUSPENDED, MAX_CORES, MAX_TASKS,
+};
+use crate::text::Percent;
+use crate::timer::{tick_to_u64, Tick, WrappingInstant};
+
+/// `Scheduler::current` marker for an idle core
+const NO_TASK: u8 = u8::MAX;
+
+/// Longest [`Scheduler::boost`] (µs, about 35 minutes)
+pub const MAX_BOOST_US: u32 = i32::MAX as u32;
+
+/// Tasks that [`Scheduler::partition`] could not place on any core
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionError {
+    /// Unplaced tasks (bit i = task i)
+    pub unplaced: TaskMask,
+}
 
 /// Rate-Monotonic Scheduler
 ///
 /// Static task table, no dynamic allocation.
-/// Size: `MAX_TASKS` × sizeof(Task) + overhead ≈ 512 + 32 bytes
-pub struct Scheduler {
+/// Size: `MAX_TASKS` × sizeof(Task) + overhead ≈ 896 + 32 bytes
+///
+/// The dispatch order is the [`SchedulingPolicy`] `P` ([`Rms`] by
+/// default, zero-sized); see [`policy`](crate::policy).
+pub struct Scheduler<P: SchedulingPolicy = Rms> {
     /// Static task table
     tasks: [Task; MAX_TASKS],
     /// Number of registered tasks
-    task_count: usize,
-    /// Currently running task index (None = idle)
-    current_task: Option<usize>,
-    /// System tick counter (microseconds)
-    tick_us: u64,
+    task_count: u8,
+    /// Task last dispatched on each core (`NO_TASK` = idle)
+    current: [u8; MAX_CORES],
+    /// Tasks released from interrupt context since the last tick
+    isr_pending: AtomicU16,
+    /// System tick counter (microseconds, wrapping with `tick-u32`)
+    tick_us: Tick,
     /// Total context switches
     pub context_switches: u32,
+    /// Low 32 bits of the instant the boost ends
+    boost_until: u32,
+    /// Boosted task (`NO_TASK` = none)
+    boost_task: u8,
+    /// Priority the boosted task returns to
+    boost_base: TaskPriority,
+    /// Table changed outside `tick` (a [`RunQueue`] policy rebuilds)
+    stale: bool,
+    /// Dispatch order
+    policy: P,
 }
 
 impl Default for Scheduler {
@@ -30,95 +73,534 @@ impl Default for Scheduler {
     }
 }
 
+/// Task table ([`Task::TABLE_HEADER`] and one row per active task) and
+/// a total-utilization line
+impl<P: SchedulingPolicy> fmt::Display for Scheduler<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", Task::TABLE_HEADER)?;
+        for (_, task) in self.tasks() {
+            writeln!(f, "{task}")?;
+        }
+        writeln!(
+            f,
+            "{} tasks, U {}",
+            self.active_task_count(),
+            Percent(self.total_utilization_ppm())
+        )
+    }
+}
+
 impl Scheduler {
     /// Create empty scheduler
     #[must_use]
     pub const fn new() -> Self {
+        Self::with_policy(Rms)
+    }
+}
+
+impl<P: SchedulingPolicy> Scheduler<P> {
+    /// Create empty scheduler dispatching in `policy`'s order
+    #[must_use]
+    pub const fn with_policy(policy: P) -> Self {
         Self {
             tasks: [Task::empty(); MAX_TASKS],
             task_count: 0,
-            current_task: None,
+            current: [NO_TASK; MAX_CORES],
+            isr_pending: AtomicU16::new(0),
             tick_us: 0,
             context_switches: 0,
+            boost_until: 0,
+            boost_task: NO_TASK,
+            boost_base: TaskPriority::IDLE,
+            stale: true,
+            policy,
         }
     }
 
+    /// Dispatch policy
+    #[must_use]
+    pub const fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Dispatch policy, for stateful policies (weights, thermal input)
+    pub const fn policy_mut(&mut self) -> &mut P {
+        self.stale = true;
+        &mut self.policy
+    }
+
     /// Register a task, returns slot index
+    ///
+    /// `None` when the task table is full (see [`try_register`](Self::try_register)).
     pub const fn register(&mut self, task: Task) -> Option<usize> {
-        if self.task_count >= MAX_TASKS {
-            return None;
+        match self.try_register(task) {
+            Ok(idx) => Some(idx),
+            Err(_) => None,
+        }
+    }
+
+    /// Register a task, returns slot index or [`KernelError::TaskTableFull`]
+    pub const fn try_register(&mut self, task: Task) -> KernelResult<usize> {
+        let idx = self.task_slots();
+        if idx >= MAX_TASKS {
+            return Err(KernelError::TaskTableFull);
         }
-        let idx = self.task_count;
         self.tasks[idx] = task;
-        self.tasks[idx].next_activation = self.tick_us;
+        self.tasks[idx].affinity = normalize_affinity(task.affinity);
+        // An unregistered task's `next_activation` is its release offset
+        self.tasks[idx].next_activation = self.tick_us.wrapping_add(task.next_activation);
+        if (task.isr_release() || task.next_activation > 0)
+            && !matches!(task.state, TaskState::Inactive)
+        {
+            // Waits for its first interrupt / phase instead of starting released
+            self.tasks[idx].state = TaskState::Sleeping;
+        }
         self.task_count += 1;
-        Some(idx)
+        self.stale = true;
+        Ok(idx)
     }
 
     /// Advance system time by `delta_us` microseconds and run ready tasks
     ///
+    /// Uniprocessor entry point: core affinity is ignored, every Ready
+    /// task is a candidate.
     /// Returns the index of the task that was executed, if any.
     pub fn tick(&mut self, delta_us: u64) -> Option<usize> {
-        self.tick_us += delta_us;
+        self.tick_on(0, ANY_CORE, delta_us, 0)
+    }
 
-        // Mark tasks whose period has elapsed as Ready
-        for i in 0..self.task_count {
-            if self.tasks[i].state == TaskState::Sleeping
-                && self.tick_us >= self.tasks[i].next_activation
-            {
-                self.tasks[i].state = TaskState::Ready;
+    /// Advance time and dispatch on `core`
+    ///
+    /// Only tasks whose affinity includes `core` are considered, so each
+    /// core works through its own run queue. `delta_us` is the time since
+    /// the previous tick on *any* core (read from a shared timer such as
+    /// the RP2040 TIMER). Returns None for an out-of-range core.
+    pub fn tick_core(&mut self, core: usize, delta_us: u64) -> Option<usize> {
+        if core >= MAX_CORES {
+            return None;
+        }
+        self.tick_on(core, 1 << core, delta_us, 0)
+    }
+
+    /// Shared release + dispatch path for `tick` / `tick_core`
+    ///
+    /// Tasks in `exclude` are released as usual but not dispatched
+    /// (budget throttling); they stay Ready until no longer excluded.
+    #[inline]
+    pub(crate) fn tick_on(
+        &mut self,
+        core: usize,
+        cores: CoreMask,
+        delta_us: u64,
+        exclude: TaskMask,
+    ) -> Option<usize> {
+        self.dispatch_on(core, cores, delta_us, exclude)
+            .map(|(idx, _)| idx)
+    }
+
+    /// [`tick_on`](Self::tick_on), also returning the release instant of
+    /// the dispatched job
+    ///
+    /// The release is read before the dispatch moves `next_activation` to
+    /// the next one, so it stays right after a period change or a merge
+    /// of overdue releases into the job.
+    pub(crate) fn dispatch_on(
+        &mut self,
+        core: usize,
+        cores: CoreMask,
+        delta_us: u64,
+        exclude: TaskMask,
+    ) -> Option<(usize, Tick)> {
+        self.tick_us = self.tick_us.wrapping_add(delta_us as Tick);
+        if self.boost_task != NO_TASK && self.boost_expired() {
+            self.unboost(self.boost_task as usize);
+        }
+
+        // Releases signalled from interrupt context since the last tick
+        let isr = self.isr_pending.swap(0, Ordering::Acquire);
+        let mut deferred: TaskMask = 0;
+        let now = self.now();
+        let slots = self.task_slots();
+
+        // A run queue is only rescanned when the table changed; otherwise
+        // the tick visits the tasks its wheel reports due and the
+        // interrupt releases
+        let stale = core::mem::replace(&mut self.stale, false);
+        let due = self.policy.run_queue_mut().map(|rq| rq.advance(delta_us));
+        let rescan = stale || due.is_none();
+        let visit =
+            (isr | due.unwrap_or(0)) & !TaskMask::MAX.checked_shl(slots as u32).unwrap_or(0);
+
+        // One pass over the visited slots: releases, misses of pending jobs
+        // and the dispatchable set, so the dispatch below only looks at bits
+        let mut ready: TaskMask = 0;
+        if rescan {
+            for i in 0..slots {
+                deferred |= Self::release(&mut self.tasks[i], i, isr, now);
+                if Self::is_dispatchable(&self.tasks[i], cores) {
+                    ready |= 1 << i;
+                }
+            }
+        } else {
+            for i in mask_slots(visit) {
+                deferred |= Self::release(&mut self.tasks[i], i, isr, now);
             }
         }
+        if deferred != 0 {
+            self.isr_pending.fetch_or(deferred, Ordering::Release);
+        }
 
-        // Find highest-priority ready task
-        let next = self.find_highest_priority_ready();
+        // Highest-priority ready task (find-first-set over the ready bits)
+        let next = match self.policy.run_queue_mut() {
+            Some(rq) => {
+                let tasks = &self.tasks[..slots];
+                if rescan {
+                    rq.rebuild(tasks);
+                    rq.refresh(tasks, now);
+                } else {
+                    for i in mask_slots(visit) {
+                        rq.track(i, &tasks[i], now);
+                    }
+                }
+                rq.pick(cores, exclude)
+            }
+            None => self.select(ready & !exclude),
+        };
 
         if let Some(idx) = next {
             // Context switch?
-            if self.current_task != Some(idx) {
+            if self.current[core] != idx as u8 {
                 self.context_switches += 1;
-                self.current_task = Some(idx);
+                self.current[core] = idx as u8;
             }
 
+            // Job boundary: deadline and next release
+            let boundary = self.tasks[idx].job_deadline();
+
             // Check deadline
-            if self.tick_us > self.tasks[idx].next_activation + self.tasks[idx].period_us as u64 {
+            if boundary.is_before(self.now()) {
                 self.tasks[idx].deadline_misses += 1;
+                emit(KernelEvent::DeadlineMiss {
+                    idx: idx as u8,
+                    at_us: tick_to_u64(self.tick_us),
+                });
+                fault::deadline_miss(idx, tick_to_u64(self.tick_us));
             }
 
             // Execute task
+            let release = self.tasks[idx].next_activation;
             self.tasks[idx].state = TaskState::Running;
             self.tasks[idx].exec_count += 1;
 
-            // Schedule next activation
-            self.tasks[idx].next_activation += self.tasks[idx].period_us as u64;
+            // Schedule next activation (new parameters take over here); a
+            // period-0 task is next released at the next instant
+            self.tasks[idx].next_activation = if self.tasks[idx].period_us == 0 {
+                self.tick_us
+            } else {
+                boundary.ticks()
+            };
+            self.tasks[idx].set_mode_change_pending(false);
             self.tasks[idx].state = TaskState::Sleeping;
 
-            Some(idx)
+            // Consume inputs, publish output to successors; chained
+            // successors are released at this tick once all inputs are in
+            self.tasks[idx].inputs_ready = 0;
+            let bit: TaskMask = 1 << idx;
+            let successors = match self.policy.run_queue_mut() {
+                Some(rq) => rq.successors(idx),
+                None => (0..slots)
+                    .filter(|&i| self.tasks[i].predecessors & bit != 0)
+                    .fold(0, |mask: TaskMask, i| mask | 1 << i),
+            };
+            for i in mask_slots(successors) {
+                let succ = &mut self.tasks[i];
+                succ.inputs_ready |= bit;
+                if succ.is_chained() && succ.state == TaskState::Sleeping && succ.inputs_complete()
+                {
+                    succ.next_activation = self.tick_us;
+                    succ.set_mode_change_pending(false);
+                    succ.state = TaskState::Ready;
+                }
+            }
+            if let Some(rq) = self.policy.run_queue_mut() {
+                for i in mask_slots(successors | bit) {
+                    rq.track(i, &self.tasks[i], now);
+                }
+            }
+
+            Some((idx, release))
         } else {
-            self.current_task = None;
+            self.current[core] = NO_TASK;
             None
         }
     }
 
+    /// Release task `idx` if due and charge a pending job's misses
+    ///
+    /// Returns its bit if an interrupt release has to wait for inputs.
+    fn release(task: &mut Task, idx: usize, isr: TaskMask, now: WrappingInstant) -> TaskMask {
+        let mut deferred: TaskMask = 0;
+        if task.state == TaskState::Sleeping {
+            let by_isr = isr & (1 << idx) != 0;
+            // Period 0: once per instant, not again at a zero-length tick
+            let due = now.has_reached(task.next_release())
+                && (task.period_us != 0 || task.next_release() != now);
+            let by_time = task.time_released() && (due || task.mode_change_pending());
+            if !task.inputs_complete() {
+                if by_isr {
+                    deferred = 1 << idx;
+                }
+            } else if by_isr {
+                // Released now: the deadline is one period from here
+                task.next_activation = now.ticks();
+                task.set_mode_change_pending(false);
+                task.state = TaskState::Ready;
+            } else if by_time {
+                task.state = TaskState::Ready;
+            }
+        }
+        // Released jobs past their deadline miss it whether or not they run
+        Self::charge_overdue(task, idx, now);
+        deferred
+    }
+
+    /// Count deadline misses of a released job that has not been dispatched
+    ///
+    /// A periodic job that is Ready (or released by time but still waiting
+    /// for its inputs) when its deadline passes misses it at that tick,
+    /// however long it is then starved. The job stays pending; each further
+    /// period boundary that passes before it runs is merged into it and
+    /// counted as one more miss, so a job is never charged twice and a
+    /// starved task does not burst through a backlog once it gets the CPU.
+    fn charge_overdue(task: &mut Task, idx: usize, now: WrappingInstant) {
+        let released = match task.state {
+            TaskState::Ready => true,
+            TaskState::Sleeping => task.time_released() && now.has_reached(task.next_release()),
+            _ => false,
+        };
+        let deadline = task.job_deadline();
+        if !released || task.period_us == 0 || deadline.has_reached(now) {
+            return;
+        }
+        // Boundaries strictly before now, starting at the deadline
+        let period = task.period_us as Tick;
+        let missed = (now.since(deadline) - 1) / period + 1;
+        let missed_jobs = u32::try_from(tick_to_u64(missed)).unwrap_or(u32::MAX);
+        task.deadline_misses = task.deadline_misses.saturating_add(missed_jobs);
+        task.next_activation = deadline.add((missed - 1) * period).ticks();
+        task.set_mode_change_pending(false);
+        emit(KernelEvent::DeadlineMiss {
+            idx: idx as u8,
+            at_us: tick_to_u64(now.ticks()),
+        });
+        fault::deadline_miss(idx, tick_to_u64(now.ticks()));
+    }
+
+    /// Task last dispatched on `core` (None = idle or invalid core)
+    #[must_use]
+    pub fn current_on(&self, core: usize) -> Option<usize> {
+        self.current
+            .get(core)
+            .copied()
+            .filter(|&c| c != NO_TASK)
+            .map(usize::from)
+    }
+
+    /// Release task `idx` from an interrupt handler (e.g. an audio timer)
+    ///
+    /// Lock-free and allocation-free: only sets a bit in an atomic mask,
+    /// which the next `tick` turns into a release at that tick's time.
+    /// Intended for tasks built with [`Task::released_by_isr`], so
+    /// high-rate work is released by hardware while the rest stays
+    /// tick-polled. A release arriving while the previous job is still
+    /// pending is merged into it. Returns false for an invalid index.
+    ///
+    /// Takes `&self` so the handler only needs a shared reference to the
+    /// scheduler (e.g. via a `static` kernel); keep the main loop's
+    /// exclusive borrow short.
+    pub fn release_from_isr(&self, idx: usize) -> bool {
+        if idx >= MAX_TASKS {
+            return false;
+        }
+        self.isr_pending.fetch_or(1 << idx, Ordering::Release);
+        true
+    }
+
+    /// Interrupt releases not yet consumed by `tick`
+    #[must_use]
+    pub fn isr_pending(&self) -> TaskMask {
+        self.isr_pending.load(Ordering::Acquire)
+    }
+
     /// Execute a specific task (call its function with scratch buffer)
     pub fn execute_task(&self, idx: usize, scratch: &mut [u8]) {
-        if let Some(func) = self.tasks[idx].func {
-            func(scratch);
+        self.tasks[idx].run(scratch);
+    }
+
+    /// Earliest instant (µs) at which `tick` has work to do
+    ///
+    /// Returns the current time if a dispatchable task is already Ready,
+    /// otherwise the earliest `next_activation` among Sleeping tasks.
+    /// `None` when no task will ever become ready (idle forever).
+    /// Used by the tickless kernel loop to skip idle ticks entirely.
+    ///
+    /// A time-released task with period 0 is released at every tick that
+    /// advances time; it does not wake a tickless kernel and runs once per
+    /// wakeup instead.
+    ///
+    /// With a [`RunQueue`] policy this is the earliest entry of its timing
+    /// wheel instead of a scan of the table, and also covers the deadline
+    /// of a job still pending.
+    #[must_use]
+    pub fn next_wakeup_us(&self) -> Option<Tick> {
+        self.next_wakeup_excluding(0)
+    }
+
+    /// [`next_wakeup_us`](Self::next_wakeup_us) ignoring Ready tasks in
+    /// `exclude` (throttled tasks wake on budget replenishment instead)
+    pub(crate) fn next_wakeup_excluding(&self, exclude: TaskMask) -> Option<Tick> {
+        if self.isr_pending() != 0 {
+            return Some(self.tick_us);
+        }
+        // Up-to-date run queue: its ready bits and its wheel's earliest entry
+        if let Some(rq) = self.policy.run_queue().filter(|_| !self.stale) {
+            if rq.pick(ANY_CORE, exclude).is_some() {
+                return Some(self.tick_us);
+            }
+            return rq
+                .next_event_us()
+                .map(|wait| self.tick_us.wrapping_add(wait as Tick));
+        }
+        if self.find_ready_on(ANY_CORE, exclude).is_some() {
+            return Some(self.tick_us);
+        }
+        // Earliest as distance from now, so the comparison survives a wrap
+        let mut earliest: Option<Tick> = None;
+        for i in 0..self.task_slots() {
+            // A period-0 task is released at every tick: it runs at each
+            // wakeup but does not cause one
+            if self.tasks[i].state == TaskState::Sleeping
+                && self.tasks[i].time_released()
+                && self.tasks[i].period_us != 0
+                && self.tasks[i].inputs_complete()
+            {
+                let wait = if self.tasks[i].mode_change_pending() {
+                    0
+                } else {
+                    self.now().until(self.tasks[i].next_release())
+                };
+                earliest = Some(earliest.map_or(wait, |e| e.min(wait)));
+            }
+        }
+        earliest.map(|wait| self.tick_us.wrapping_add(wait))
+    }
+
+    /// Require `pred` to complete before each release of `idx`
+    ///
+    /// Builds a dataflow edge `pred → idx`: `idx` consumes the output
+    /// `pred` produced in the current period. Returns false for invalid
+    /// indices or if the edge would close a cycle (which would deadlock).
+    pub fn add_predecessor(&mut self, idx: usize, pred: usize) -> bool {
+        if idx >= self.task_slots() || pred >= self.task_slots() || idx == pred {
+            return false;
+        }
+        if !self.tasks[idx].is_active() || !self.tasks[pred].is_active() {
+            return false;
+        }
+        if self.ancestors(pred) & (1 << idx) != 0 {
+            return false;
+        }
+        self.tasks[idx].predecessors |= 1 << pred;
+        self.stale = true;
+        true
+    }
+
+    /// Release `b` whenever `a` completes
+    ///
+    /// Builds a pipeline edge `a → b` (see [`add_predecessor`](Self::add_predecessor),
+    /// including its cycle check): `b` is no longer released by its
+    /// period but at the tick in which its last chained predecessor
+    /// finishes, and is then dispatched by priority like any Ready task.
+    /// `b`'s period remains its relative deadline and its rate for the
+    /// schedulability analysis. A `b` that has not run yet waits for `a`.
+    /// Returns false for invalid indices, interrupt-released tasks or if
+    /// the edge would close a cycle.
+    pub fn chain(&mut self, a: usize, b: usize) -> bool {
+        if b >= self.task_slots() || self.tasks[b].isr_release() || !self.add_predecessor(b, a) {
+            return false;
         }
+        let task = &mut self.tasks[b];
+        task.flags |= FLAG_CHAINED;
+        if task.state == TaskState::Ready && task.exec_count == 0 {
+            task.state = TaskState::Sleeping;
+        }
+        true
     }
 
-    /// Find highest-priority (lowest number) ready task
-    fn find_highest_priority_ready(&self) -> Option<usize> {
-        let mut best_idx = None;
-        let mut best_priority = TaskPriority::IDLE;
+    /// Remove all precedence constraints (and chaining) of `idx`
+    ///
+    /// A chained task goes back to period-driven release.
+    pub const fn clear_predecessors(&mut self, idx: usize) {
+        if idx < self.task_slots() {
+            self.stale = true;
+            self.tasks[idx].predecessors = 0;
+            self.tasks[idx].inputs_ready = 0;
+            if self.tasks[idx].is_chained() {
+                self.tasks[idx].flags &= !FLAG_CHAINED;
+                self.tasks[idx].next_activation = self.tick_us;
+            }
+        }
+    }
 
-        for i in 0..self.task_count {
-            if self.tasks[i].state == TaskState::Ready && self.tasks[i].priority < best_priority {
-                best_priority = self.tasks[i].priority;
-                best_idx = Some(i);
+    /// Transitive predecessor set of `idx`
+    fn ancestors(&self, idx: usize) -> TaskMask {
+        let mut seen: TaskMask = 0;
+        let mut frontier = self.tasks[idx].predecessors;
+        while frontier & !seen != 0 {
+            let new = frontier & !seen;
+            seen |= new;
+            frontier = 0;
+            for i in 0..self.task_slots() {
+                if new & (1 << i) != 0 {
+                    frontier |= self.tasks[i].predecessors;
+                }
             }
         }
-        best_idx
+        seen
+    }
+
+    /// Task the policy picks among the ready ones allowed on `cores`
+    ///
+    /// Ready tasks still waiting on a predecessor, or in `exclude`, are
+    /// not offered.
+    fn find_ready_on(&self, cores: CoreMask, exclude: TaskMask) -> Option<usize> {
+        self.select(self.dispatchable(cores) & !exclude)
+    }
+
+    /// Task the policy picks among `ready` (None if empty)
+    fn select(&self, ready: TaskMask) -> Option<usize> {
+        if ready == 0 {
+            return None;
+        }
+        self.policy
+            .select(&self.tasks[..self.task_slots()], ready, self.now())
+            .filter(|&i| ready & (1 << i) != 0)
+    }
+
+    /// Ready tasks with their inputs in, allowed on any core in `cores`
+    fn dispatchable(&self, cores: CoreMask) -> TaskMask {
+        (0..self.task_slots())
+            .filter(|&i| Self::is_dispatchable(&self.tasks[i], cores))
+            .fold(0, |mask: TaskMask, i| mask | 1 << i)
+    }
+
+    /// Is `task` Ready with its inputs in and allowed on any core in `cores`?
+    ///
+    /// [`ANY_CORE`] (the uniprocessor [`tick`](Self::tick)) ignores affinity.
+    fn is_dispatchable(task: &Task, cores: CoreMask) -> bool {
+        task.state == TaskState::Ready
+            && (cores == ANY_CORE || task.affinity & cores != 0)
+            && task.inputs_complete()
     }
 
     /// RMS schedulability test
@@ -126,22 +608,67 @@ impl Scheduler {
     /// Liu & Layland bound: U ≤ n(2^(1/n) - 1)
     /// For n=3: U ≤ 0.780
     /// For n→∞: U ≤ ln(2) ≈ 0.693
+    ///
+    /// Harmonic period sets use the exact bound U ≤ 1 instead; see
+    /// [`rms_bound`](Self::rms_bound).
     #[must_use]
     pub fn is_schedulable(&self) -> bool {
-        let n = self.active_task_count();
-        if n == 0 {
+        self.rms_test_where(|_| true)
+    }
+
+    /// Does task `idx` keep every deadline guarantee the set had without it?
+    ///
+    /// The admission test of [`Admission::Reject`](crate::kernel::Admission::Reject)
+    /// / [`Flag`](crate::kernel::Admission::Flag). The
+    /// [`is_schedulable`](Self::is_schedulable) bound is tried first;
+    /// failing it, exact response-time analysis
+    /// ([`dmda`](crate::dmda)) of the set with and without the task
+    /// decides: `idx` is refused if it misses its own deadline or makes a
+    /// task that met its deadline miss it. A set already past the bound
+    /// (admitted with the check off, or over-committed) thus still takes
+    /// a task that costs no one a deadline, such as one without load.
+    #[must_use]
+    pub fn admits(&self, idx: usize) -> bool {
+        if self.is_schedulable() {
             return true;
         }
-        let total_u = self.total_utilization();
-        let bound = liu_layland_bound(n);
-        total_u <= bound
+        let with = self.response_times(&Overhead::NONE);
+        if with.all_schedulable {
+            return true;
+        }
+        let misses = |report: &crate::dmda::DmdaReport, i: usize| {
+            report.results[i].is_some_and(|r| !r.meets_deadline)
+        };
+        if idx >= MAX_TASKS || misses(&with, idx) {
+            return false;
+        }
+        let mut rest = self.tasks;
+        rest[idx] = Task::empty();
+        let without = crate::dmda::analyze(&rest, self.task_slots());
+        (0..self.task_slots()).all(|i| i == idx || !misses(&with, i) || misses(&without, i))
+    }
+
+    /// Bound [`is_schedulable`](Self::is_schedulable) applies to the task set
+    #[must_use]
+    pub fn rms_bound(&self) -> RmsBound {
+        if self.is_harmonic() {
+            RmsBound::Harmonic
+        } else {
+            RmsBound::LiuLayland
+        }
+    }
+
+    /// Does every active task's period divide every longer one?
+    #[must_use]
+    pub fn is_harmonic(&self) -> bool {
+        self.harmonic_where(|_| true)
     }
 
     /// Total CPU utilization (sum of Ci/Ti for all tasks)
     #[must_use]
     pub fn total_utilization(&self) -> f32 {
         let mut u = 0.0f32;
-        for i in 0..self.task_count {
+        for i in 0..self.task_slots() {
             if self.tasks[i].is_active() {
                 u += self.tasks[i].utilization();
             }
@@ -149,10 +676,182 @@ impl Scheduler {
         u
     }
 
+    /// Total CPU utilization in parts per million (integer only)
+    #[must_use]
+    pub fn total_utilization_ppm(&self) -> u32 {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active())
+            .map(Task::utilization_ppm)
+            .sum()
+    }
+
+    /// Utilization of the tasks allowed on `core`
+    ///
+    /// Partitioned view: a task with several affinity bits is charged to
+    /// every core it may run on, which is pessimistic unless tasks are
+    /// pinned to a single core.
+    #[must_use]
+    pub fn core_utilization(&self, core: usize) -> f32 {
+        let mut u = 0.0f32;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].is_active() && self.tasks[i].runs_on(core) {
+                u += self.tasks[i].utilization();
+            }
+        }
+        u
+    }
+
+    /// Partitioned RMS test for one core (same bounds on its task set)
+    #[must_use]
+    pub fn is_schedulable_on(&self, core: usize) -> bool {
+        if core >= MAX_CORES {
+            return false;
+        }
+        self.rms_test_where(|t| t.runs_on(core))
+    }
+
+    /// Partitioned RMS test across all cores
+    #[must_use]
+    pub fn is_schedulable_smp(&self) -> bool {
+        (0..MAX_CORES).all(|core| self.is_schedulable_on(core))
+    }
+
+    /// Utilization of the task set belonging to `mode`
+    #[must_use]
+    pub fn mode_utilization(&self, mode: Mode) -> f32 {
+        let mut u = 0.0f32;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].is_active() && self.tasks[i].active_in(mode) {
+                u += self.tasks[i].utilization();
+            }
+        }
+        u
+    }
+
+    /// RMS test for the task set belonging to `mode`
+    #[must_use]
+    pub fn is_schedulable_in_mode(&self, mode: Mode) -> bool {
+        self.rms_test_where(|t| t.active_in(mode))
+    }
+
+    /// Assign tasks to cores with first-fit decreasing utilization
+    ///
+    /// Tasks are taken in order of decreasing Cᵢ/Tᵢ and pinned to the
+    /// first of `num_cores` cores whose task set still passes its own
+    /// Liu & Layland bound. Affinities are only rewritten when every
+    /// active task fits; otherwise the error lists the tasks that could
+    /// not be placed and the table is left untouched.
+    pub fn partition(&mut self, num_cores: usize) -> Result<(), PartitionError> {
+        let mut order = [0usize; MAX_TASKS];
+        let mut n = 0;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].is_active() {
+                // Insertion sort by decreasing utilization
+                let u = util_of(&self.tasks[i]);
+                let mut j = n;
+                while j > 0 && util_of(&self.tasks[order[j - 1]]) < u {
+                    order[j] = order[j - 1];
+                    j -= 1;
+                }
+                order[j] = i;
+                n += 1;
+            }
+        }
+
+        let cores = num_cores.min(MAX_CORES);
+        let mut load = [Util::default(); MAX_CORES];
+        let mut count = [0usize; MAX_CORES];
+        let mut assigned = [0 as CoreMask; MAX_TASKS];
+        let mut unplaced: TaskMask = 0;
+        for &idx in &order[..n] {
+            let u = util_of(&self.tasks[idx]);
+            let fit = (0..cores).find(|&c| load[c] + u <= bound(count[c] + 1));
+            match fit {
+                Some(c) => {
+                    load[c] += u;
+                    count[c] += 1;
+                    assigned[idx] = 1 << c;
+                }
+                None => unplaced |= 1 << idx,
+            }
+        }
+
+        if unplaced != 0 {
+            return Err(PartitionError { unplaced });
+        }
+        for &idx in &order[..n] {
+            self.tasks[idx].affinity = assigned[idx];
+        }
+        self.stale = true;
+        Ok(())
+    }
+
+    /// Utilization of the active tasks matching `filter`, in [`Util`] units
+    fn utilization_where(&self, filter: impl Fn(&Task) -> bool) -> Util {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active() && filter(t))
+            .map(util_of)
+            .sum()
+    }
+
+    /// RMS test on the active tasks matching `filter`: U ≤ 1 for a
+    /// harmonic set, the Liu & Layland bound otherwise
+    fn rms_test_where(&self, filter: impl Fn(&Task) -> bool) -> bool {
+        let n = self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active() && filter(t))
+            .count();
+        if n == 0 {
+            return true;
+        }
+        let limit = if self.harmonic_where(&filter) {
+            FULL_UTIL
+        } else {
+            bound(n)
+        };
+        self.utilization_where(&filter) <= limit
+    }
+
+    /// Are the periods of the active tasks matching `filter` harmonic?
+    fn harmonic_where(&self, filter: impl Fn(&Task) -> bool) -> bool {
+        let mut periods = [0u32; MAX_TASKS];
+        let mut n = 0;
+        for t in &self.tasks[..self.task_slots()] {
+            if t.is_active() && filter(t) {
+                periods[n] = t.period_us;
+                n += 1;
+            }
+        }
+        harmonic(&periods[..n])
+    }
+
+    /// Tasks currently released and waiting for dispatch (bit i = task i)
+    #[must_use]
+    pub fn ready_mask(&self) -> TaskMask {
+        let mut mask: TaskMask = 0;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].state == TaskState::Ready {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+
+    /// Tasks `core` could dispatch now: ready, inputs in, affinity incl. `core`
+    #[must_use]
+    pub fn ready_on(&self, core: usize) -> TaskMask {
+        if core >= MAX_CORES {
+            return 0;
+        }
+        self.dispatchable(1 << core)
+    }
+
     /// Number of active tasks
     #[must_use]
     pub fn active_task_count(&self) -> usize {
-        self.tasks[..self.task_count]
+        self.tasks[..self.task_slots()]
             .iter()
             .filter(|t| t.is_active())
             .count()
@@ -161,22 +860,70 @@ impl Scheduler {
     /// Get task by index
     #[must_use]
     pub const fn get_task(&self, idx: usize) -> Option<&Task> {
-        if idx < self.task_count {
+        if idx < self.task_slots() {
             Some(&self.tasks[idx])
         } else {
             None
         }
     }
 
-    /// Current system time in microseconds
+    /// Active tasks with their slot index, in registration order
+    pub fn tasks(&self) -> impl Iterator<Item = (usize, &Task)> + '_ {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.is_active())
+    }
+
+    /// Slot of the active task called `name`
+    ///
+    /// Names are compared like they are stored: truncated to 8 bytes.
+    #[must_use]
+    pub fn find_by_name(&self, name: &[u8]) -> Option<usize> {
+        let name = short_name(name);
+        self.tasks()
+            .find(|(_, t)| t.name == name)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Mutable task access for kernel bookkeeping
+    pub(crate) const fn get_task_mut(&mut self, idx: usize) -> Option<&mut Task> {
+        if idx < self.task_slots() {
+            self.stale = true;
+            Some(&mut self.tasks[idx])
+        } else {
+            None
+        }
+    }
+
+    /// Current system time in microseconds (wrapping with `tick-u32`)
     #[must_use]
-    pub const fn now_us(&self) -> u64 {
+    pub const fn now_us(&self) -> Tick {
         self.tick_us
     }
 
+    /// Current system time as a wrap-safe instant
+    #[must_use]
+    pub const fn now(&self) -> WrappingInstant {
+        WrappingInstant(self.tick_us)
+    }
+
+    /// Registered task slots (including inactive ones below the last task)
+    #[must_use]
+    pub const fn task_slots(&self) -> usize {
+        self.task_count as usize
+    }
+
+    /// Set the system time (snapshot restore)
+    pub(crate) const fn set_now(&mut self, tick_us: Tick) {
+        self.tick_us = tick_us;
+        self.stale = true;
+    }
+
     /// Suspend a task
     pub const fn suspend(&mut self, idx: usize) {
-        if idx < self.task_count {
+        if idx < self.task_slots() {
+            self.stale = true;
             self.tasks[idx].state = TaskState::Suspended;
         }
     }
@@ -186,48 +933,624 @@ impl Scheduler {
     /// 削除されたタスクのスロットは `Inactive` に戻り、
     /// 次の `register()` で再利用される（末尾スロット縮小のみ）。
     pub fn unregister(&mut self, idx: usize) -> bool {
-        if idx >= self.task_count || self.tasks[idx].state == TaskState::Inactive {
-            return false;
+        self.try_unregister(idx).is_ok()
+    }
+
+    /// [`unregister`](Self::unregister), failing with
+    /// [`KernelError::InvalidIndex`] for an empty or out-of-range slot
+    pub fn try_unregister(&mut self, idx: usize) -> KernelResult<()> {
+        if idx >= self.task_slots() || self.tasks[idx].state == TaskState::Inactive {
+            return Err(KernelError::InvalidIndex);
+        }
+        if self.boost_task == idx as u8 {
+            self.boost_task = NO_TASK;
         }
         self.tasks[idx] = Task::empty();
-        if self.current_task == Some(idx) {
-            self.current_task = None;
+        self.stale = true;
+        // 依存関係から削除されたタスクを外す
+        let bit: TaskMask = 1 << idx;
+        for i in 0..self.task_slots() {
+            self.tasks[i].predecessors &= !bit;
+            self.tasks[i].inputs_ready &= !bit;
+            // 連鎖元を全て失ったタスクは周期駆動に戻す
+            if self.tasks[i].predecessors == 0 && self.tasks[i].is_chained() {
+                self.tasks[i].flags &= !FLAG_CHAINED;
+                self.tasks[i].next_activation = self.tick_us;
+            }
+        }
+        for current in &mut self.current {
+            if *current == idx as u8 {
+                *current = NO_TASK;
+            }
         }
         // 末尾の Inactive スロットを縮小
-        while self.task_count > 0 && self.tasks[self.task_count - 1].state == TaskState::Inactive {
+        while self.task_count > 0 && self.tasks[self.task_slots() - 1].state == TaskState::Inactive
+        {
             self.task_count -= 1;
         }
-        true
+        Ok(())
     }
 
     /// Resume a suspended task
     pub fn resume(&mut self, idx: usize) {
-        if idx < self.task_count && self.tasks[idx].state == TaskState::Suspended {
+        if idx < self.task_slots() && self.tasks[idx].state == TaskState::Suspended {
+            self.stale = true;
+            self.tasks[idx].flags &= !FLAG_MODE_SUSPENDED;
             self.tasks[idx].state = TaskState::Ready;
             self.tasks[idx].next_activation = self.tick_us;
+            self.tasks[idx].set_mode_change_pending(false);
         }
     }
-}
 
-/// Liu & Layland bound: n(2^(1/n) - 1)
-///
-/// Uses precomputed table for small n, approximation for large n.
-fn liu_layland_bound(n: usize) -> f32 {
-    // Precomputed bounds for common task counts
-    const BOUNDS: [f32; 10] = [
-        1.000, // n=0: unused (returns early)
-        1.000, // n=1: U ≤ 1.000
-        0.828, // n=2: U ≤ 0.828
-        0.780, // n=3: U ≤ 0.780
-        0.757, // n=4: U ≤ 0.757
-        0.743, // n=5: U ≤ 0.743
-        0.735, // n=6: U ≤ 0.735
-        0.729, // n=7: U ≤ 0.729
-        0.724, // n=8: U ≤ 0.724
-        0.693, // n≥9: U ≤ ln(2) ≈ 0.693
-    ];
-    if n == 0 {
-        return 1.0;
+    /// Raise task `idx` to `priority` for `duration_us`
+    ///
+    /// The task returns to its own priority on the first tick at or after
+    /// the deadline, or on [`unboost`](Self::unboost). One task is boosted
+    /// at a time: boosting another one first restores the previous task,
+    /// and boosting the same task again replaces priority and deadline.
+    /// Returns false (nothing changed) for an inactive slot or a
+    /// `priority` that would not outrank the task's own.
+    ///
+    /// The deadline is tracked in 32 bits, so `duration_us` is capped at
+    /// [`MAX_BOOST_US`].
+    pub fn boost(&mut self, idx: usize, priority: TaskPriority, duration_us: u32) -> bool {
+        let Some(task) = self.get_task(idx) else {
+            return false;
+        };
+        let base = if self.boost_task == idx as u8 {
+            self.boost_base
+        } else {
+            task.priority
+        };
+        if task.state == TaskState::Inactive || priority >= base {
+            return false;
+        }
+        if self.boost_task != idx as u8 {
+            self.unboost(self.boost_task as usize);
+            self.boost_task = idx as u8;
+            self.boost_base = base;
+        }
+        self.tasks[idx].priority = priority;
+        self.stale = true;
+        self.boost_until =
+            (tick_to_u64(self.tick_us) as u32).wrapping_add(duration_us.min(MAX_BOOST_US));
+        true
+    }
+
+    /// End the boost of task `idx` early
+    ///
+    /// Returns false if `idx` is not boosted.
+    pub fn unboost(&mut self, idx: usize) -> bool {
+        if idx >= MAX_TASKS || self.boost_task != idx as u8 {
+            return false;
+        }
+        self.tasks[idx].priority = self.boost_base;
+        self.boost_task = NO_TASK;
+        self.stale = true;
+        true
+    }
+
+    /// Boosted task and the time left on its boost (µs)
+    ///
+    /// An expired boost reads 0 µs left until the next tick restores it.
+    #[must_use]
+    pub const fn boosted(&self) -> Option<(usize, u32)> {
+        if self.boost_task == NO_TASK {
+            return None;
+        }
+        let left = if self.boost_expired() {
+            0
+        } else {
+            self.boost_until
+                .wrapping_sub(tick_to_u64(self.tick_us) as u32)
+        };
+        Some((self.boost_task as usize, left))
+    }
+
+    /// Priority task `idx` returns to once unboosted
+    #[must_use]
+    pub fn base_priority(&self, idx: usize) -> Option<TaskPriority> {
+        let task = self.get_task(idx)?;
+        Some(if self.boost_task == idx as u8 {
+            self.boost_base
+        } else {
+            task.priority
+        })
+    }
+
+    /// Has the boost deadline been reached? (wrapping 32-bit compare)
+    const fn boost_expired(&self) -> bool {
+        (tick_to_u64(self.tick_us) as u32).wrapping_sub(self.boost_until) as i32 >= 0
+    }
+
+    /// Delay the first release of task `idx` to `phase_us` from now
+    ///
+    /// Only for periodic tasks that have not run yet; returns false
+    /// otherwise.
+    pub fn set_phase(&mut self, idx: usize, phase_us: u32) -> bool {
+        let now = self.tick_us;
+        let Some(task) = self.get_task_mut(idx) else {
+            return false;
+        };
+        if task.exec_count > 0
+            || !task.time_released()
+            || !matches!(task.state, TaskState::Ready | TaskState::Sleeping)
+        {
+            return false;
+        }
+        task.next_activation = now.wrapping_add(phase_us as Tick);
+        task.state = if phase_us > 0 {
+            TaskState::Sleeping
+        } else {
+            TaskState::Ready
+        };
+        true
+    }
+
+    /// Choose release offsets that spread jobs apart to flatten peak load
+    ///
+    /// Greedy: tasks are placed in rate-monotonic order, each at the
+    /// offset (within its period) whose jobs overlap least with those of
+    /// the tasks already placed. Two tasks' releases repeat relative to
+    /// each other with the gcd of their periods, so the overlap is exact
+    /// over the hyperperiod. Only tasks that have not run yet are
+    /// re-phased (see [`set_phase`](Self::set_phase)); call before the
+    /// first tick.
+    pub fn stagger_phases(&mut self) {
+        let mut order = [0usize; MAX_TASKS];
+        let mut n = 0;
+        for i in 0..self.task_slots() {
+            let t = &self.tasks[i];
+            if t.is_active() && t.time_released() && t.exec_count == 0 && t.period_us > 0 {
+                order[n] = i;
+                n += 1;
+            }
+        }
+        order[..n].sort_unstable_by_key(|&i| (self.tasks[i].period_us, i));
+
+        let mut phase = [0u32; MAX_TASKS];
+        for k in 0..n {
+            let i = order[k];
+            let placed = &order[..k];
+            let cost = |offset: u32| -> u64 {
+                placed
+                    .iter()
+                    .map(|&j| job_overlap(&self.tasks[i], offset, &self.tasks[j], phase[j]))
+                    .sum()
+            };
+            // Candidates: 0 and right after each placed task's job
+            let mut best = (cost(0), 0u32);
+            for &j in placed {
+                let g = gcd(self.tasks[i].period_us, self.tasks[j].period_us);
+                let offset = ((phase[j] as u64 + self.tasks[j].wcet_us as u64) % g as u64) as u32;
+                best = best.min((cost(offset), offset));
+            }
+            phase[i] = best.1;
+            self.set_phase(i, best.1);
+        }
+    }
+
+    /// Active tasks in subsystem group `group` (bit i = task i)
+    #[must_use]
+    pub fn group_mask(&self, group: u8) -> TaskMask {
+        let mut mask: TaskMask = 0;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].is_active() && self.tasks[i].group == group {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+
+    /// Suspend every task of `group` in one step
+    ///
+    /// No tick can run between two suspensions, so the subsystem stops as
+    /// a whole (e.g. under thermal pressure). Returns the tasks that were
+    /// suspended by this call.
+    pub fn suspend_group(&mut self, group: u8) -> TaskMask {
+        let mut suspended: TaskMask = 0;
+        for i in 0..self.task_slots() {
+            let task = &self.tasks[i];
+            if task.group == group && task.is_active() && task.state != TaskState::Suspended {
+                self.suspend(i);
+                suspended |= 1 << i;
+            }
+        }
+        suspended
+    }
+
+    /// Resume every suspended task of `group`; all restart released now
+    ///
+    /// Returns the tasks that were resumed by this call.
+    pub fn resume_group(&mut self, group: u8) -> TaskMask {
+        let mut resumed: TaskMask = 0;
+        for i in 0..self.task_slots() {
+            if self.tasks[i].group == group && self.tasks[i].state == TaskState::Suspended {
+                self.resume(i);
+                resumed |= 1 << i;
+            }
+        }
+        resumed
+    }
+
+    /// Utilization of the tasks in `group` (suspended ones included)
+    #[must_use]
+    pub fn group_utilization(&self, group: u8) -> f32 {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active() && t.group == group)
+            .map(Task::utilization)
+            .sum()
+    }
+
+    /// Utilization of the tasks in `group` in parts per million
+    #[must_use]
+    pub fn group_utilization_ppm(&self, group: u8) -> u32 {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active() && t.group == group)
+            .map(Task::utilization_ppm)
+            .sum()
+    }
+
+    /// Total utilization including scheduler overheads (ppm)
+    ///
+    /// Each job is charged [`Overhead::job_cost_us`] and the tick
+    /// handler's share is added on top. With [`Overhead::NONE`] this is
+    /// [`total_utilization_ppm`](Self::total_utilization_ppm).
+    #[must_use]
+    pub fn effective_utilization_ppm(&self, overhead: &Overhead) -> u32 {
+        self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active())
+            .map(|t| utilization_ppm(overhead.job_cost_us(t.wcet_us), t.period_us))
+            .sum::<u32>()
+            .saturating_add(overhead.tick_utilization_ppm())
+    }
+
+    /// RMS test on the overhead-inflated task set
+    ///
+    /// The tick counts as one more periodic task, both for the Liu &
+    /// Layland bound and for the harmonic check. For an exact per-task
+    /// answer use [`response_times`](Self::response_times).
+    #[must_use]
+    pub fn is_schedulable_with(&self, overhead: &Overhead) -> bool {
+        let mut periods = [0u32; MAX_TASKS + 1];
+        let mut n = 0;
+        for t in self.tasks[..self.task_slots()]
+            .iter()
+            .filter(|t| t.is_active())
+        {
+            periods[n] = t.period_us;
+            n += 1;
+        }
+        if n == 0 {
+            return true;
+        }
+        if overhead.has_tick() {
+            periods[n] = overhead.tick_period_us;
+            n += 1;
+        }
+        let limit = if harmonic(&periods[..n]) {
+            1_000_000
+        } else {
+            liu_layland_bound_ppm(n)
+        };
+        self.effective_utilization_ppm(overhead) <= limit
+    }
+
+    /// Worst-case response time of every active task, overheads included
+    ///
+    /// See [`dmda::analyze_with_overhead`](crate::dmda::analyze_with_overhead).
+    #[must_use]
+    pub fn response_times(&self, overhead: &Overhead) -> crate::dmda::DmdaReport {
+        crate::dmda::analyze_with_overhead(
+            &self.tasks,
+            self.task_slots(),
+            &[0; MAX_TASKS],
+            overhead,
+        )
+    }
+
+    /// Change the period of task `idx` (mode change)
+    ///
+    /// Admitted only if the task set stays RMS-schedulable during the
+    /// transition, i.e. with the task charged max(old, new) utilization;
+    /// a change that does not raise the task's utilization is always
+    /// admitted.
+    /// A job already released keeps its old deadline and next release;
+    /// the new period takes effect from that period boundary. Returns
+    /// false (and changes nothing) if rejected.
+    pub fn set_period(&mut self, idx: usize, period_us: u32) -> bool {
+        match self.get_task(idx) {
+            Some(task) => self.change_params(idx, period_us, task.wcet_us),
+            None => false,
+        }
+    }
+
+    /// Change the WCET of task `idx` (mode change)
+    ///
+    /// Same admission and period-boundary rules as [`set_period`](Self::set_period).
+    pub fn set_wcet(&mut self, idx: usize, wcet_us: u32) -> bool {
+        match self.get_task(idx) {
+            Some(task) => self.change_params(idx, task.period_us, wcet_us),
+            None => false,
+        }
+    }
+
+    /// Shared admission + deferral path for `set_period` / `set_wcet`
+    fn change_params(&mut self, idx: usize, period_us: u32, wcet_us: u32) -> bool {
+        let task = self.tasks[idx];
+        if !task.is_active() || period_us == 0 || wcet_us > period_us {
+            return false;
+        }
+
+        // Transition analysis: old and new jobs may overlap in one hyperperiod
+        let mut changed = task;
+        changed.period_us = period_us;
+        changed.wcet_us = wcet_us;
+        let old_u = util_of(&task);
+        let new_u = util_of(&changed);
+        let transition_u = self.utilization_where(|_| true) - old_u + old_u.max(new_u);
+        // Shedding load is always allowed, even from an overloaded set
+        if new_u > old_u && transition_u > bound(self.active_task_count()) {
+            return false;
+        }
+
+        // Pin the boundary of a job that is already released
+        let in_flight = task.state == TaskState::Ready
+            || (task.state == TaskState::Sleeping && self.now().has_reached(task.next_release()));
+        if in_flight && !task.mode_change_pending() {
+            self.tasks[idx].next_activation =
+                task.next_activation.wrapping_add(task.period_us as Tick);
+            self.tasks[idx].set_mode_change_pending(true);
+        }
+        self.tasks[idx].period_us = period_us;
+        self.tasks[idx].wcet_us = wcet_us;
+        self.stale = true;
+        true
+    }
+}
+
+/// Greatest common divisor (0 only if both are 0)
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Execution overlap (µs) between the jobs of `a` released at `offset_a`
+/// and those of `b` released at `offset_b`, per common release pattern
+fn job_overlap(a: &Task, offset_a: u32, b: &Task, offset_b: u32) -> u64 {
+    let g = gcd(a.period_us, b.period_us) as i64;
+    // Distance from a release of `b` to the next release of `a`
+    let d = (offset_a as i64 - offset_b as i64).rem_euclid(g) as u64;
+    let (ca, cb, g) = (a.wcet_us as u64, b.wcet_us as u64, g as u64);
+    ca.min(cb.saturating_sub(d)) + cb.min((d + ca).saturating_sub(g))
+}
+
+/// Scheduler overheads charged by the schedulability analysis (µs)
+///
+/// Every job pays two context switches (dispatch, and the return to the
+/// context it preempted); the tick handler interferes with every task
+/// like a top-priority task of `tick_us` every `tick_period_us`. Measure
+/// both on the target, e.g. with [`Stopwatch`](crate::profiler::Stopwatch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overhead {
+    /// Cost of one context switch
+    pub switch_us: u32,
+    /// Cost of one tick interrupt
+    pub tick_us: u32,
+    /// Tick interval (0 = no periodic tick, e.g. tickless)
+    pub tick_period_us: u32,
+}
+
+impl Overhead {
+    /// Free scheduler (the textbook model)
+    pub const NONE: Self = Self::new(0, 0, 0);
+
+    /// Overheads of `switch_us` per switch and `tick_us` every `tick_period_us`
+    #[must_use]
+    pub const fn new(switch_us: u32, tick_us: u32, tick_period_us: u32) -> Self {
+        Self {
+            switch_us,
+            tick_us,
+            tick_period_us,
+        }
+    }
+
+    /// Execution time charged to a job of `wcet_us`
+    #[must_use]
+    pub const fn job_cost_us(&self, wcet_us: u32) -> u32 {
+        wcet_us.saturating_add(self.switch_us.saturating_mul(2))
+    }
+
+    /// Processor share taken by the tick handler (ppm, rounded up)
+    #[must_use]
+    pub const fn tick_utilization_ppm(&self) -> u32 {
+        utilization_ppm(self.tick_us, self.tick_period_us)
+    }
+
+    /// Worst-case tick handler time inside a window of `window_us`
+    #[must_use]
+    pub const fn tick_interference_us(&self, window_us: u32) -> u64 {
+        if self.tick_period_us == 0 {
+            0
+        } else {
+            (window_us as u64).div_ceil(self.tick_period_us as u64) * self.tick_us as u64
+        }
+    }
+
+    /// Does the tick count as an extra periodic load?
+    const fn has_tick(&self) -> bool {
+        self.tick_us > 0 && self.tick_period_us > 0
+    }
+}
+
+/// Utilization bound applied by the RMS schedulability test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RmsBound {
+    /// Liu & Layland n(2^(1/n) − 1): sufficient for any period set
+    LiuLayland,
+    /// U ≤ 1: exact when every period divides every longer one
+    Harmonic,
+}
+
+/// Utilization unit of admission / schedulability decisions: a fraction,
+/// or parts per million with the `no-float` feature
+#[cfg(not(feature = "no-float"))]
+type Util = f32;
+#[cfg(feature = "no-float")]
+type Util = u32;
+
+/// Full processor in [`Util`] units (exact bound of harmonic sets)
+#[cfg(not(feature = "no-float"))]
+const FULL_UTIL: Util = 1.0;
+#[cfg(feature = "no-float")]
+const FULL_UTIL: Util = 1_000_000;
+
+/// Utilization of one task in [`Util`] units
+#[cfg(not(feature = "no-float"))]
+fn util_of(task: &Task) -> Util {
+    task.utilization()
+}
+
+/// Utilization of one task in [`Util`] units
+#[cfg(feature = "no-float")]
+const fn util_of(task: &Task) -> Util {
+    task.utilization_ppm()
+}
+
+/// Liu & Layland bound for `n` tasks in [`Util`] units
+#[cfg(not(feature = "no-float"))]
+fn bound(n: usize) -> Util {
+    liu_layland_bound(n)
+}
+
+/// Liu & Layland bound for `n` tasks in [`Util`] units
+#[cfg(feature = "no-float")]
+const fn bound(n: usize) -> Util {
+    liu_layland_bound_ppm(n)
+}
+
+/// Liu & Layland bound n(2^(1/n) - 1) in parts per million (rounded down)
+///
+/// Integer-only and `const`, so firmware can assert schedulability at
+/// compile time:
+///
+/// ```rust
+/// use alice_rtos::scheduler::{liu_layland_bound_ppm, utilization_ppm};
+///
+/// const U: u32 = utilization_ppm(8, 23) + utilization_ppm(15, 100);
+/// const _: () = assert!(U <= liu_layland_bound_ppm(2));
+/// ```
+#[must_use]
+pub const fn liu_layland_bound_ppm(n: usize) -> u32 {
+    const BOUNDS_PPM: [u32; 10] = [
+        1_000_000, // n=0: unused
+        1_000_000, // n=1
+        828_427,   // n=2
+        779_763,   // n=3
+        756_828,   // n=4
+        743_491,   // n=5
+        734_772,   // n=6
+        728_626,   // n=7
+        724_061,   // n=8
+        693_147,   // n≥9: ln(2)
+    ];
+    BOUNDS_PPM[if n < 9 { n } else { 9 }]
+}
+
+/// Utilization Cᵢ/Tᵢ in parts per million (rounded up; 0 for a zero period)
+#[must_use]
+pub const fn utilization_ppm(wcet_us: u32, period_us: u32) -> u32 {
+    if period_us == 0 {
+        0
+    } else {
+        (wcet_us as u64 * 1_000_000).div_ceil(period_us as u64) as u32
+    }
+}
+
+/// RMS test on `(period_us, wcet_us)` pairs, evaluable in `const`
+///
+/// Uses the exact bound U ≤ 1 for harmonic periods and the Liu & Layland
+/// bound otherwise, like [`Scheduler::is_schedulable`].
+///
+/// ```rust
+/// use alice_rtos::scheduler::rms_schedulable;
+///
+/// const TASKS: [(u32, u32); 3] = [(23, 8), (100, 15), (1_000, 50)];
+/// const _: () = assert!(rms_schedulable(&TASKS));
+/// // Harmonic: 95 % still schedulable
+/// const _: () = assert!(rms_schedulable(&[(250, 125), (1_000, 200), (2_000, 100)]));
+/// ```
+#[must_use]
+pub const fn rms_schedulable(tasks: &[(u32, u32)]) -> bool {
+    let mut total: u64 = 0;
+    let mut harmonic_set = true;
+    let mut i = 0;
+    while i < tasks.len() {
+        total += utilization_ppm(tasks[i].1, tasks[i].0) as u64;
+        let mut j = 0;
+        while j < i {
+            harmonic_set &= divides_either(tasks[i].0, tasks[j].0);
+            j += 1;
+        }
+        i += 1;
+    }
+    let limit = if harmonic_set {
+        1_000_000
+    } else {
+        liu_layland_bound_ppm(tasks.len())
+    };
+    tasks.is_empty() || total <= limit as u64
+}
+
+/// Does every period divide every longer one? (true for 0 or 1 periods)
+#[must_use]
+pub const fn harmonic(periods: &[u32]) -> bool {
+    let mut i = 0;
+    while i < periods.len() {
+        let mut j = 0;
+        while j < i {
+            if !divides_either(periods[i], periods[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Is one of two (non-zero) periods a multiple of the other?
+const fn divides_either(a: u32, b: u32) -> bool {
+    a != 0 && b != 0 && (a.is_multiple_of(b) || b.is_multiple_of(a))
+}
+
+/// Liu & Layland bound: n(2^(1/n) - 1)
+///
+/// Uses precomputed table for small n, approximation for large n.
+#[cfg_attr(feature = "no-float", allow(dead_code))]
+fn liu_layland_bound(n: usize) -> f32 {
+    // Precomputed bounds for common task counts
+    const BOUNDS: [f32; 10] = [
+        1.000, // n=0: unused (returns early)
+        1.000, // n=1: U ≤ 1.000
+        0.828, // n=2: U ≤ 0.828
+        0.780, // n=3: U ≤ 0.780
+        0.757, // n=4: U ≤ 0.757
+        0.743, // n=5: U ≤ 0.743
+        0.735, // n=6: U ≤ 0.735
+        0.729, // n=7: U ≤ 0.729
+        0.724, // n=8: U ≤ 0.724
+        0.693, // n≥9: U ≤ ln(2) ≈ 0.693
+    ];
+    if n == 0 {
+        return 1.0;
     }
     let idx = n.min(9);
     BOUNDS[idx]
@@ -237,6 +1560,8 @@ fn liu_layland_bound(n: usize) -> f32 {
 mod tests {
     use super::*;
     use crate::task::{Task, TaskPriority};
+    use crate::text::FmtBuf;
+    use core::fmt::Write;
 
     fn dummy_task(_: &mut [u8]) {}
 
@@ -280,6 +1605,16 @@ mod tests {
         assert_eq!(executed, Some(1)); // high is at index 1
     }
 
+    #[test]
+    fn test_idle_priority_task_runs_in_slack() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"bg", dummy_task, TaskPriority::IDLE, 1000, 100));
+        sched.register(Task::new(b"high", dummy_task, TaskPriority::HIGH, 100, 10));
+        assert_eq!(sched.tick(0), Some(1));
+        // 他に実行可能なタスクがなければ IDLE 優先度も実行される
+        assert_eq!(sched.tick(10), Some(0));
+    }
+
     #[test]
     fn test_periodic_execution() {
         let mut sched = Scheduler::new();
@@ -493,6 +1828,19 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_periodic_release_across_tick_wrap() {
+        let mut sched = Scheduler::new();
+        let start = Tick::MAX - 149;
+        sched.set_now(start);
+        sched.register(Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10));
+        // 10 µs 刻みで 400 µs 進め、途中で Tick が桁あふれする
+        let runs = (0..40).filter(|_| sched.tick(10).is_some()).count();
+        assert_eq!(runs, 5);
+        assert_eq!(sched.get_task(0).unwrap().deadline_misses, 0);
+        assert_eq!(sched.next_wakeup_us(), Some(start.wrapping_add(500)));
+    }
+
     #[test]
     fn test_resume_sets_next_activation_to_now() {
         let mut sched = Scheduler::new();
@@ -533,6 +1881,132 @@ mod tests {
         assert!((liu_layland_bound(8) - 0.724).abs() < 0.001);
     }
 
+    #[test]
+    fn test_liu_layland_ppm_matches_float_table() {
+        for n in 1..=12 {
+            let diff = liu_layland_bound(n) * 1e6 - liu_layland_bound_ppm(n) as f32;
+            assert!(diff.abs() < 1_000.0, "n = {n}");
+        }
+    }
+
+    #[test]
+    fn test_const_rms_schedulable() {
+        const { assert!(rms_schedulable(&[(23, 8), (100, 15), (1_000, 50)])) };
+        const { assert!(!rms_schedulable(&[(100, 90), (100, 50)])) };
+        assert!(rms_schedulable(&[]));
+        // 1 タスクは 100% まで可
+        assert!(rms_schedulable(&[(100, 100)]));
+        assert!(!rms_schedulable(&[(100, 101)]));
+    }
+
+    #[test]
+    fn test_const_utilization_ppm() {
+        const U: u32 = utilization_ppm(1, 3);
+        assert_eq!(U, 333_334);
+        assert_eq!(utilization_ppm(5, 0), 0);
+        assert_eq!(
+            utilization_ppm(10, 100),
+            Task::new(b"t", dummy_task, TaskPriority::LOW, 100, 10).utilization_ppm()
+        );
+    }
+
+    #[test]
+    fn test_total_utilization_ppm() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::LOW, 1_000, 250));
+        assert_eq!(sched.total_utilization_ppm(), 350_000);
+        sched.suspend(1);
+        // 停止中タスクも計上 (Inactive のみ除外)
+        assert_eq!(sched.total_utilization_ppm(), 350_000);
+        sched.unregister(1);
+        assert_eq!(sched.total_utilization_ppm(), 100_000);
+    }
+
+    #[test]
+    fn test_overhead_inflates_utilization() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::LOW, 1_000, 250));
+        assert_eq!(sched.effective_utilization_ppm(&Overhead::NONE), 350_000);
+        assert!(sched.is_schedulable_with(&Overhead::NONE));
+
+        // スイッチ 2 µs ×2: a=14/100, b=254/1000、ティック 5/100
+        let harmonic_tick = Overhead::new(2, 5, 100);
+        assert_eq!(sched.effective_utilization_ppm(&harmonic_tick), 444_000);
+        assert!(sched.is_schedulable_with(&harmonic_tick));
+
+        // 非調和なティック周期は 3 タスクの Liu & Layland 境界 (779_763)
+        assert_eq!(
+            sched.effective_utilization_ppm(&Overhead::new(10, 6, 30)),
+            770_000
+        );
+        assert!(sched.is_schedulable_with(&Overhead::new(10, 6, 30)));
+        assert!(!sched.is_schedulable_with(&Overhead::new(10, 7, 30)));
+
+        let report = sched.response_times(&harmonic_tick);
+        // a: 14 + ⌈R/100⌉×5 = 19
+        assert_eq!(report.results[0].unwrap().worst_case_response, 19);
+        assert!(report.all_schedulable);
+    }
+
+    #[test]
+    fn test_boost_outranks_then_expires() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"audio",
+            dummy_task,
+            TaskPriority::HIGH,
+            1_000,
+            10,
+        ));
+        sched.register(Task::new(
+            b"motion",
+            dummy_task,
+            TaskPriority::LOW,
+            1_000,
+            10,
+        ));
+        // 自分より低い優先度へは上げない
+        assert!(!sched.boost(1, TaskPriority::LOW, 500));
+        assert!(sched.boost(1, TaskPriority::CRITICAL, 500));
+        assert_eq!(sched.boosted(), Some((1, 500)));
+        assert_eq!(sched.base_priority(1), Some(TaskPriority::LOW));
+        assert_eq!(sched.tick(0), Some(1));
+        assert_eq!(sched.tick(0), Some(0));
+        assert_eq!(sched.tick(400), None);
+        assert_eq!(sched.boosted(), Some((1, 100)));
+
+        // 期限後の最初の tick で元に戻る
+        assert_eq!(sched.tick(600), Some(0));
+        assert_eq!(sched.boosted(), None);
+        assert_eq!(sched.get_task(1).unwrap().priority, TaskPriority::LOW);
+        assert!(!sched.unboost(1));
+    }
+
+    #[test]
+    fn test_boost_single_slot_and_unboost() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::NORMAL, 1_000, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::LOW, 1_000, 10));
+        assert!(sched.boost(0, TaskPriority::HIGH, 1_000));
+        // 再ブーストは優先度と期限を置き換え、元の優先度は保持
+        assert!(sched.boost(0, TaskPriority::CRITICAL, 2_000));
+        assert_eq!(sched.base_priority(0), Some(TaskPriority::NORMAL));
+        // 別タスクのブーストで先のタスクは戻る
+        assert!(sched.boost(1, TaskPriority::HIGH, 1_000));
+        assert_eq!(sched.get_task(0).unwrap().priority, TaskPriority::NORMAL);
+        assert!(sched.unboost(1));
+        assert_eq!(sched.get_task(1).unwrap().priority, TaskPriority::LOW);
+        assert_eq!(sched.boosted(), None);
+
+        // 削除されたタスクのブーストは消える
+        assert!(sched.boost(1, TaskPriority::HIGH, 1_000));
+        assert!(sched.unregister(1));
+        assert_eq!(sched.boosted(), None);
+        assert!(!sched.boost(1, TaskPriority::HIGH, 1_000));
+    }
+
     #[test]
     fn test_liu_layland_large_n_clamps_to_ln2() {
         // n >= 9 は ln(2) ≈ 0.693 に固定
@@ -656,10 +2130,891 @@ mod tests {
     }
 
     #[test]
-    fn test_execute_task_inactive_slot_no_panic() {
+    fn test_next_wakeup_empty_is_none() {
         let sched = Scheduler::new();
-        // func が None のタスクスロット、パニックしないこと
-        let mut buf = [0u8; 8];
-        sched.execute_task(0, &mut buf); // slot は empty (func=None)
+        assert_eq!(sched.next_wakeup_us(), None);
+    }
+
+    #[test]
+    fn test_next_wakeup_ready_is_now() {
+        let mut sched = Scheduler::new();
+        sched.tick(50);
+        sched.register(Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10));
+        assert_eq!(sched.next_wakeup_us(), Some(50));
+    }
+
+    #[test]
+    fn test_next_wakeup_earliest_sleeping() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 300, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::NORMAL, 200, 10));
+        sched.tick(0); // a 実行 → next=300
+        sched.tick(0); // b 実行 → next=200
+        assert_eq!(sched.next_wakeup_us(), Some(200));
+    }
+
+    #[test]
+    fn test_next_wakeup_ignores_suspended() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10));
+        sched.suspend(0);
+        assert_eq!(sched.next_wakeup_us(), None);
+    }
+
+    #[test]
+    fn test_predecessor_gates_release() {
+        let mut sched = Scheduler::new();
+        // consumer の方が高優先度だが、producer の完了を待つ
+        sched.register(Task::new(b"cons", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"prod", dummy_task, TaskPriority::LOW, 100, 10));
+        assert!(sched.add_predecessor(0, 1));
+
+        assert_eq!(sched.tick(0), Some(1)); // prod が先
+        assert_eq!(sched.tick(0), Some(0)); // その後 cons
+        assert_eq!(sched.tick(0), None);
+    }
+
+    #[test]
+    fn test_predecessor_consumed_each_period() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cons", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"prod", dummy_task, TaskPriority::LOW, 200, 10));
+        sched.add_predecessor(0, 1);
+        sched.tick(0); // prod
+        sched.tick(0); // cons
+                       // t=100: cons の周期だが prod は未実行 → 解放されない
+        assert_eq!(sched.tick(100), None);
+        // t=200: prod → cons の順
+        assert_eq!(sched.tick(100), Some(1));
+        assert_eq!(sched.tick(0), Some(0));
+    }
+
+    #[test]
+    fn test_pipeline_three_stages() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"out",
+            dummy_task,
+            TaskPriority::CRITICAL,
+            100,
+            10,
+        ));
+        sched.register(Task::new(b"filt", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"samp", dummy_task, TaskPriority::LOW, 100, 10));
+        assert!(sched.add_predecessor(0, 1));
+        assert!(sched.add_predecessor(1, 2));
+        assert_eq!(sched.tick(0), Some(2));
+        assert_eq!(sched.tick(0), Some(1));
+        assert_eq!(sched.tick(0), Some(0));
+    }
+
+    #[test]
+    fn test_predecessor_cycle_rejected() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::NORMAL, 100, 10));
+        sched.register(Task::new(b"c", dummy_task, TaskPriority::LOW, 100, 10));
+        assert!(sched.add_predecessor(1, 0));
+        assert!(sched.add_predecessor(2, 1));
+        assert!(!sched.add_predecessor(0, 2)); // a→b→c→a
+        assert!(!sched.add_predecessor(0, 0)); // 自己依存
+        assert!(!sched.add_predecessor(0, 9)); // 範囲外
+    }
+
+    #[test]
+    fn test_blocked_task_not_a_wakeup() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cons", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"prod", dummy_task, TaskPriority::LOW, 1000, 10));
+        sched.add_predecessor(0, 1);
+        sched.tick(0);
+        sched.tick(0);
+        sched.tick(100); // cons は入力待ち
+        assert_eq!(sched.next_wakeup_us(), Some(1000));
+    }
+
+    #[test]
+    fn test_unregister_clears_dependency() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cons", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"prod", dummy_task, TaskPriority::LOW, 100, 10));
+        sched.add_predecessor(0, 1);
+        sched.unregister(1);
+        assert_eq!(sched.get_task(0).unwrap().predecessors, 0);
+        assert_eq!(sched.tick(0), Some(0));
+    }
+
+    #[test]
+    fn test_clear_predecessors() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cons", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"prod", dummy_task, TaskPriority::LOW, 100, 10));
+        sched.add_predecessor(0, 1);
+        sched.clear_predecessors(0);
+        assert_eq!(sched.tick(0), Some(0));
+    }
+
+    #[test]
+    fn test_chain_releases_in_same_tick() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"sample",
+            dummy_task,
+            TaskPriority::NORMAL,
+            1_000,
+            10,
+        ));
+        sched.register(Task::new(
+            b"filter",
+            dummy_task,
+            TaskPriority::HIGH,
+            1_000,
+            10,
+        ));
+        sched.register(Task::new(
+            b"output",
+            dummy_task,
+            TaskPriority::LOW,
+            1_000,
+            10,
+        ));
+        sched.register(Task::new(
+            b"ctl",
+            dummy_task,
+            TaskPriority::CRITICAL,
+            2_000,
+            10,
+        ));
+        assert!(sched.chain(0, 1));
+        assert!(sched.chain(1, 2));
+        assert!(sched.get_task(2).unwrap().is_chained());
+
+        for period in 0..3u64 {
+            sched.tick(if period == 0 { 0 } else { 1_000 });
+            // sample は ctl (2 ms 周期) の後、優先度どおり
+            if period % 2 == 0 {
+                assert_eq!(sched.current_on(0), Some(3));
+                assert_eq!(sched.tick(0), Some(0));
+            } else {
+                assert_eq!(sched.current_on(0), Some(0));
+            }
+            // sample 完了で filter, filter 完了で output が同じ時刻に解放
+            assert_eq!(sched.next_wakeup_us(), Some(sched.now_us()));
+            assert_eq!(sched.tick(0), Some(1));
+            assert_eq!(sched.tick(0), Some(2));
+            assert_eq!(sched.tick(0), None);
+            // 周期では解放されない: 次は sample の周期境界
+            assert_eq!(sched.next_wakeup_us(), Some(sched.now_us() + 1_000));
+        }
+        for i in 0..4 {
+            assert_eq!(sched.get_task(i).unwrap().deadline_misses, 0);
+        }
+        assert_eq!(sched.get_task(2).unwrap().exec_count, 3);
+    }
+
+    #[test]
+    fn test_chain_rejects_cycles_and_clears() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched
+            .register(Task::new(b"irq", dummy_task, TaskPriority::HIGH, 100, 10).released_by_isr());
+        assert!(sched.chain(0, 1));
+        assert!(!sched.chain(1, 0)); // a→b→a
+        assert!(!sched.chain(1, 1));
+        assert!(!sched.chain(0, 2)); // 割り込み解放タスクは連鎖不可
+        assert!(!sched.chain(0, 7));
+
+        // 連鎖を外すと周期駆動に戻る
+        sched.clear_predecessors(1);
+        assert!(!sched.get_task(1).unwrap().is_chained());
+        assert_eq!(sched.tick(0), Some(0));
+        assert_eq!(sched.tick(0), Some(1));
+
+        // 連鎖元の削除でも周期駆動に戻る
+        assert!(sched.chain(0, 1));
+        sched.unregister(0);
+        assert!(!sched.get_task(1).unwrap().is_chained());
+        assert_eq!(sched.tick(100), Some(1));
+    }
+
+    #[test]
+    fn test_execute_task_inactive_slot_no_panic() {
+        let sched = Scheduler::new();
+        // func が None のタスクスロット、パニックしないこと
+        let mut buf = [0u8; 8];
+        sched.execute_task(0, &mut buf); // slot は empty (func=None)
+    }
+
+    // --- SMP テスト ---
+
+    #[test]
+    fn test_tick_core_respects_affinity() {
+        let mut sched = Scheduler::new();
+        sched
+            .register(Task::new(b"c0", dummy_task, TaskPriority::LOW, 100, 10).with_affinity(0b01));
+        sched.register(
+            Task::new(b"c1", dummy_task, TaskPriority::CRITICAL, 100, 10).with_affinity(0b10),
+        );
+        assert_eq!(sched.tick_core(0, 0), Some(0));
+        assert_eq!(sched.tick_core(1, 0), Some(1));
+        assert_eq!(sched.current_on(0), Some(0));
+        assert_eq!(sched.current_on(1), Some(1));
+        assert_eq!(sched.tick_core(1, 0), None);
+        assert_eq!(sched.current_on(1), None);
+    }
+
+    #[test]
+    fn test_tick_core_any_core_task_runs_once() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"any", dummy_task, TaskPriority::NORMAL, 100, 10));
+        assert_eq!(sched.tick_core(1, 0), Some(0));
+        // 同じ周期のジョブは他コアで再実行されない
+        assert_eq!(sched.tick_core(0, 0), None);
+        assert_eq!(sched.get_task(0).unwrap().exec_count, 1);
+    }
+
+    #[test]
+    fn test_tick_core_invalid_core() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10));
+        assert_eq!(sched.tick_core(MAX_CORES, 0), None);
+        assert_eq!(sched.current_on(MAX_CORES), None);
+    }
+
+    #[test]
+    fn test_period_zero_task_runs_once_per_instant() {
+        let mut sched = Scheduler::new();
+        let poll = sched.register(Task::new(b"poll", dummy_task, TaskPriority::HIGH, 0, 10));
+        let low = sched.register(Task::new(b"low", dummy_task, TaskPriority::LOW, 1_000, 10));
+        assert_eq!(sched.tick(0), poll);
+        // 同じ時刻では再リリースされず、下位のタスクが動ける
+        assert_eq!(sched.tick(0), low);
+        assert_eq!(sched.next_wakeup_us(), Some(1_000));
+        assert_eq!(sched.tick(10), poll);
+    }
+
+    #[test]
+    fn test_uniprocessor_tick_ignores_affinity() {
+        let mut sched = Scheduler::new();
+        sched.register(
+            Task::new(b"c1", dummy_task, TaskPriority::NORMAL, 100, 10).with_affinity(0b10),
+        );
+        assert_eq!(sched.tick(0), Some(0));
+        // 登録時にコアのないマスクは全コアに正規化
+        let mut task = Task::new(b"none", dummy_task, TaskPriority::NORMAL, 100, 10);
+        task.affinity = 0;
+        let idx = sched.register(task).unwrap();
+        assert_eq!(sched.get_task(idx).unwrap().affinity, ANY_CORE);
+        assert_eq!(sched.tick_core(1, 0), Some(idx));
+    }
+
+    #[test]
+    fn test_partitioned_schedulability() {
+        let mut sched = Scheduler::new();
+        // 各コア U=0.6 → 単一コアでは 1.2 だがパーティションでは OK
+        sched
+            .register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 60).with_affinity(0b01));
+        sched
+            .register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 60).with_affinity(0b10));
+        assert!(!sched.is_schedulable());
+        assert!((sched.core_utilization(0) - 0.6).abs() < 1e-6);
+        assert!(sched.is_schedulable_on(0));
+        assert!(sched.is_schedulable_on(1));
+        assert!(sched.is_schedulable_smp());
+        assert!(!sched.is_schedulable_on(MAX_CORES));
+    }
+
+    #[test]
+    fn test_partitioned_overloaded_core() {
+        let mut sched = Scheduler::new();
+        sched
+            .register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 60).with_affinity(0b01));
+        sched
+            .register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 150, 45).with_affinity(0b01));
+        assert!(!sched.is_schedulable_on(0));
+        assert!(sched.is_schedulable_on(1));
+        assert!(!sched.is_schedulable_smp());
+    }
+
+    #[test]
+    fn test_unregister_clears_current_on_core() {
+        let mut sched = Scheduler::new();
+        sched.register(
+            Task::new(b"a", dummy_task, TaskPriority::NORMAL, 100, 10).with_affinity(0b10),
+        );
+        sched.tick_core(1, 0);
+        sched.unregister(0);
+        assert_eq!(sched.current_on(1), None);
+    }
+
+    #[test]
+    fn test_partition_two_cores() {
+        let mut sched = Scheduler::new();
+        // 合計 U=1.2 → 単一コアでは不可、2 コアに分割
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 50));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 40));
+        sched.register(Task::new(b"c", dummy_task, TaskPriority::HIGH, 100, 30));
+        assert_eq!(sched.partition(2), Ok(()));
+        // FFD: a(0.5)→0, b(0.4)→1 (0.9 > 0.828), c(0.3)→0 (0.8 ≤ 0.828)
+        assert_eq!(sched.get_task(0).unwrap().affinity, 0b01);
+        assert_eq!(sched.get_task(1).unwrap().affinity, 0b10);
+        assert_eq!(sched.get_task(2).unwrap().affinity, 0b01);
+        assert!(sched.is_schedulable_smp());
+    }
+
+    #[test]
+    fn test_partition_reports_unplaced() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 90));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 90));
+        sched.register(Task::new(b"c", dummy_task, TaskPriority::HIGH, 100, 20));
+        let err = sched.partition(2).unwrap_err();
+        assert_eq!(err.unplaced, 0b100);
+        // 失敗時はアフィニティを変更しない
+        assert_eq!(sched.get_task(0).unwrap().affinity, ANY_CORE);
+    }
+
+    #[test]
+    fn test_partition_single_core() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 30));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 30));
+        assert_eq!(sched.partition(1), Ok(()));
+        assert_eq!(sched.get_task(1).unwrap().affinity, 0b01);
+    }
+
+    #[test]
+    fn test_partition_zero_cores_fails_all() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        assert_eq!(sched.partition(0), Err(PartitionError { unplaced: 0b1 }));
+    }
+
+    #[test]
+    fn test_partition_skips_inactive() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 10));
+        sched.unregister(0);
+        assert_eq!(sched.partition(2), Ok(()));
+        assert_eq!(sched.get_task(1).unwrap().affinity, 0b01);
+    }
+
+    // --- モード変更テスト ---
+
+    #[test]
+    fn test_set_period_sleeping_applies_at_next_release() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"ctl", dummy_task, TaskPriority::HIGH, 1000, 100));
+        sched.tick(0); // job 0 実行 → 次回 1000
+        assert!(sched.set_period(0, 100));
+        assert!(!sched.get_task(0).unwrap().mode_change_pending());
+        // 次の境界 (1000) までは解放されない
+        assert_eq!(sched.tick(500), None);
+        assert_eq!(sched.tick(500), Some(0));
+        // 以降は 100µs 周期
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 1100);
+        assert_eq!(sched.tick(100), Some(0));
+        assert_eq!(sched.get_task(0).unwrap().deadline_misses, 0);
+    }
+
+    #[test]
+    fn test_set_period_in_flight_keeps_old_boundary() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"hi",
+            dummy_task,
+            TaskPriority::CRITICAL,
+            100,
+            10,
+        ));
+        sched.register(Task::new(b"ctl", dummy_task, TaskPriority::HIGH, 1000, 10));
+        sched.tick(0); // hi 実行、ctl は Ready のまま
+        assert_eq!(sched.get_task(1).unwrap().state, TaskState::Ready);
+        // 1 kHz → 10 kHz: 実行中ジョブの期限は旧周期 (1000) のまま
+        assert!(sched.set_period(1, 100));
+        assert!(sched.get_task(1).unwrap().mode_change_pending());
+        assert_eq!(sched.tick(50), Some(1));
+        let t = sched.get_task(1).unwrap();
+        assert_eq!(t.deadline_misses, 0);
+        assert_eq!(t.next_activation, 1000);
+        assert!(!t.mode_change_pending());
+    }
+
+    #[test]
+    fn test_set_period_in_flight_late_job_counts_old_deadline() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"ctl", dummy_task, TaskPriority::HIGH, 1000, 100));
+        assert!(sched.set_period(0, 2000));
+        // 旧期限 1000 を過ぎて実行 → ミス、1000 のリリースは遅れたジョブに統合
+        assert_eq!(sched.tick(1500), Some(0));
+        assert_eq!(sched.get_task(0).unwrap().deadline_misses, 1);
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 3000);
+    }
+
+    #[test]
+    fn test_starved_task_misses_counted_without_running() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"hog",
+            dummy_task,
+            TaskPriority::CRITICAL,
+            100,
+            10,
+        ));
+        sched.register(Task::new(b"lo", dummy_task, TaskPriority::LOW, 300, 10));
+        // hog が毎 tick 実行され、lo は CPU を得られない
+        assert_eq!(sched.tick(0), Some(0));
+        for _ in 0..3 {
+            assert_eq!(sched.tick(100), Some(0));
+        }
+        // t = 300 ちょうどはまだ締切内
+        assert_eq!(sched.get_task(1).unwrap().deadline_misses, 0);
+        assert_eq!(sched.tick(100), Some(0));
+        assert_eq!(sched.get_task(1).unwrap().deadline_misses, 1);
+        for _ in 0..3 {
+            sched.tick(100);
+        }
+        // 締切 300 / 600 を逃した、ジョブは 1 つのまま保留
+        let lo = sched.get_task(1).unwrap();
+        assert_eq!((lo.deadline_misses, lo.exec_count), (2, 0));
+        assert_eq!(lo.state, TaskState::Ready);
+        assert_eq!(lo.next_activation, 600);
+    }
+
+    #[test]
+    fn test_set_period_rejects_unschedulable() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 40));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 1000, 100));
+        // b: 0.1 → 1.0 で合計 1.4 > 0.828
+        assert!(!sched.set_period(1, 100));
+        assert_eq!(sched.get_task(1).unwrap().period_us, 1000);
+        assert!(!sched.get_task(1).unwrap().mode_change_pending());
+    }
+
+    #[test]
+    fn test_set_period_lowering_load_always_admitted() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 60));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 60));
+        assert!(!sched.is_schedulable());
+        // 過負荷でも負荷を下げる変更は通す
+        assert!(sched.set_period(1, 400));
+        assert!(sched.is_schedulable());
+        // 負荷を上げる変更は従来どおり判定 (0.6 + 0.3 > 0.828)
+        assert!(!sched.set_period(1, 200));
+    }
+
+    #[test]
+    fn test_set_period_validates_arguments() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1000, 100));
+        assert!(!sched.set_period(0, 0));
+        assert!(!sched.set_period(0, 50)); // WCET > period
+        assert!(!sched.set_period(5, 1000));
+        sched.unregister(0);
+        assert!(!sched.set_period(0, 1000));
+    }
+
+    #[test]
+    fn test_set_period_transition_uses_max_utilization() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 50));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 100, 30));
+        // b を 0.3 → 0.1 に縮小: 遷移中は 0.8 (≤ 0.828) で可
+        assert!(sched.set_period(1, 300));
+        assert!((sched.total_utilization() - 0.6).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_set_wcet() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 10));
+        assert!(sched.set_wcet(0, 50));
+        assert_eq!(sched.get_task(0).unwrap().wcet_us, 50);
+        assert!(!sched.set_wcet(0, 101));
+        assert!(!sched.set_wcet(3, 10));
+    }
+
+    #[test]
+    fn test_repeated_change_keeps_first_boundary() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1000, 10));
+        assert!(sched.set_period(0, 500));
+        assert!(sched.set_period(0, 200));
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 1000);
+        sched.tick(0);
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 1000);
+        sched.tick(1000);
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 1200);
+    }
+
+    // --- 割り込みリリーステスト ---
+
+    #[test]
+    fn test_isr_task_not_released_by_tick() {
+        let mut sched = Scheduler::new();
+        sched.register(
+            Task::new(b"audio", dummy_task, TaskPriority::CRITICAL, 23, 5).released_by_isr(),
+        );
+        assert_eq!(sched.tick(0), None);
+        assert_eq!(sched.tick(1_000), None);
+        assert_eq!(sched.next_wakeup_us(), None);
+    }
+
+    #[test]
+    fn test_release_from_isr_runs_on_next_tick() {
+        let mut sched = Scheduler::new();
+        sched.register(
+            Task::new(b"audio", dummy_task, TaskPriority::CRITICAL, 23, 5).released_by_isr(),
+        );
+        sched.register(Task::new(b"ui", dummy_task, TaskPriority::LOW, 1_000, 50));
+        sched.tick(0); // ui (tick ポーリング)
+        assert!(sched.release_from_isr(0));
+        assert_eq!(sched.isr_pending(), 0b1);
+        assert_eq!(sched.next_wakeup_us(), Some(0));
+        assert_eq!(sched.tick(7), Some(0));
+        assert_eq!(sched.isr_pending(), 0);
+        // 締切はリリース時刻 + 周期
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 30);
+        assert_eq!(sched.tick(7), None);
+    }
+
+    #[test]
+    fn test_isr_release_deadline_from_release_time() {
+        let mut sched = Scheduler::new();
+        sched.register(
+            Task::new(b"audio", dummy_task, TaskPriority::CRITICAL, 23, 5).released_by_isr(),
+        );
+        sched.tick(500);
+        sched.release_from_isr(0);
+        sched.tick(1);
+        assert_eq!(sched.get_task(0).unwrap().deadline_misses, 0);
+        assert_eq!(sched.get_task(0).unwrap().exec_count, 1);
+    }
+
+    #[test]
+    fn test_release_from_isr_invalid_and_merged() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 5).released_by_isr());
+        assert!(!sched.release_from_isr(MAX_TASKS));
+        sched.release_from_isr(0);
+        sched.release_from_isr(0);
+        assert_eq!(sched.tick(1), Some(0));
+        assert_eq!(sched.get_task(0).unwrap().exec_count, 1);
+    }
+
+    #[test]
+    fn test_isr_release_waits_for_predecessor() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"src", dummy_task, TaskPriority::LOW, 100, 5));
+        sched.register(Task::new(b"dst", dummy_task, TaskPriority::HIGH, 100, 5).released_by_isr());
+        sched.add_predecessor(1, 0);
+        sched.release_from_isr(1);
+        // 先行タスク未完了 → リリースは保留
+        assert_eq!(sched.tick(0), Some(0));
+        assert_eq!(sched.isr_pending(), 0b10);
+        assert_eq!(sched.tick(1), Some(1));
+        assert_eq!(sched.isr_pending(), 0);
+    }
+
+    #[test]
+    fn test_release_from_isr_concurrent() {
+        extern crate std;
+        let mut sched = Scheduler::new();
+        for _ in 0..4 {
+            sched.register(
+                Task::new(b"t", dummy_task, TaskPriority::HIGH, 100, 1).released_by_isr(),
+            );
+        }
+        std::thread::scope(|s| {
+            for idx in 0..4 {
+                let sched = &sched;
+                s.spawn(move || assert!(sched.release_from_isr(idx)));
+            }
+        });
+        assert_eq!(sched.isr_pending(), 0b1111);
+    }
+
+    // --- タスクグループテスト ---
+
+    #[test]
+    fn test_group_default_zero() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
+        assert_eq!(task.group, 0);
+        assert_eq!(task.in_group(3).group, 3);
+    }
+
+    #[test]
+    fn test_suspend_group_stops_whole_subsystem() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cam", dummy_task, TaskPriority::HIGH, 1_000, 100).in_group(1));
+        sched.register(Task::new(
+            b"ctl",
+            dummy_task,
+            TaskPriority::CRITICAL,
+            1_000,
+            100,
+        ));
+        sched.register(Task::new(b"enc", dummy_task, TaskPriority::NORMAL, 1_000, 100).in_group(1));
+        assert_eq!(sched.group_mask(1), 0b101);
+        assert_eq!(sched.suspend_group(1), 0b101);
+        // 既に停止済み → 何もしない
+        assert_eq!(sched.suspend_group(1), 0);
+        assert_eq!(sched.tick(0), Some(1));
+        assert_eq!(sched.tick(0), None);
+        assert_eq!(sched.get_task(0).unwrap().state, TaskState::Suspended);
+        assert_eq!(sched.get_task(2).unwrap().state, TaskState::Suspended);
+    }
+
+    #[test]
+    fn test_resume_group_releases_now() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"cam", dummy_task, TaskPriority::HIGH, 1_000, 100).in_group(2));
+        sched.register(Task::new(b"enc", dummy_task, TaskPriority::NORMAL, 1_000, 100).in_group(2));
+        sched.suspend_group(2);
+        sched.tick(5_000);
+        // 個別に停止したタスクもグループ再開で戻る
+        assert_eq!(sched.resume_group(2), 0b11);
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 5_000);
+        assert_eq!(sched.tick(0), Some(0));
+        assert_eq!(sched.tick(0), Some(1));
+        assert_eq!(sched.resume_group(2), 0);
+    }
+
+    #[test]
+    fn test_group_utilization() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1_000, 100).in_group(1));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::HIGH, 1_000, 200).in_group(1));
+        sched.register(Task::new(b"c", dummy_task, TaskPriority::HIGH, 1_000, 400));
+        sched.suspend_group(1);
+        // 停止中のタスクも集計に含める
+        assert!((sched.group_utilization(1) - 0.3).abs() < 1e-4);
+        assert_eq!(sched.group_utilization_ppm(1), 300_000);
+        assert_eq!(sched.group_utilization_ppm(0), 400_000);
+        assert_eq!(sched.group_utilization_ppm(7), 0);
+        sched.unregister(0);
+        assert_eq!(sched.group_mask(1), 0b10);
+    }
+
+    // --- 位相オフセットテスト ---
+
+    #[test]
+    fn test_phase_delays_first_release() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1_000, 100));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::LOW, 1_000, 100).with_phase(300));
+        assert_eq!(sched.get_task(1).unwrap().state, TaskState::Sleeping);
+        assert_eq!(sched.tick(0), Some(0));
+        assert_eq!(sched.tick(200), None);
+        assert_eq!(sched.tick(100), Some(1));
+        // 締切・次回リリースも位相基準
+        assert_eq!(sched.get_task(1).unwrap().next_activation, 1_300);
+    }
+
+    #[test]
+    fn test_phase_relative_to_registration_time() {
+        let mut sched = Scheduler::new();
+        sched.tick(5_000);
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1_000, 100).with_phase(250));
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 5_250);
+        assert_eq!(sched.next_wakeup_us(), Some(5_250));
+    }
+
+    #[test]
+    fn test_set_phase_only_before_first_job() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1_000, 100));
+        assert!(sched.set_phase(0, 400));
+        assert_eq!(sched.tick(0), None);
+        assert!(sched.set_phase(0, 0));
+        assert_eq!(sched.tick(0), Some(0));
+        assert!(!sched.set_phase(0, 100));
+        assert!(!sched.set_phase(3, 100));
+    }
+
+    #[test]
+    fn test_find_by_name_and_iterate() {
+        let mut sched = Scheduler::new();
+        for name in [&b"ctl"[..], b"log", b"telemetry"] {
+            sched.register(Task::new(
+                name,
+                dummy_task,
+                TaskPriority::NORMAL,
+                1_000,
+                100,
+            ));
+        }
+        assert_eq!(sched.find_by_name(b"log"), Some(1));
+        // 8 バイトに切り詰めた名前で一致
+        assert_eq!(sched.find_by_name(b"telemetry"), Some(2));
+        assert_eq!(sched.find_by_name(b"telemetr"), Some(2));
+        assert_eq!(sched.find_by_name(b"lo"), None);
+
+        // 登録解除したタスクは列挙も検索もされない
+        sched.unregister(1);
+        assert_eq!(sched.find_by_name(b"log"), None);
+        let names: [[u8; 8]; 2] = {
+            let mut it = sched.tasks().map(|(_, t)| t.name);
+            [it.next().unwrap(), it.next().unwrap()]
+        };
+        assert_eq!(names, [short_name(b"ctl"), short_name(b"telemetry")]);
+        assert_eq!(sched.tasks().map(|(i, _)| i).sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn test_stagger_equal_periods() {
+        let mut sched = Scheduler::new();
+        for name in [b"a", b"b", b"c"] {
+            sched.register(Task::new(
+                name,
+                dummy_task,
+                TaskPriority::NORMAL,
+                1_000,
+                100,
+            ));
+        }
+        sched.stagger_phases();
+        let at: [Tick; 3] = core::array::from_fn(|i| sched.get_task(i).unwrap().next_activation);
+        assert_eq!(at, [0, 100, 200]);
+    }
+
+    #[test]
+    fn test_stagger_harmonic_periods_no_overlap() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"slow",
+            dummy_task,
+            TaskPriority::LOW,
+            1_000,
+            200,
+        ));
+        sched.register(Task::new(b"fast", dummy_task, TaskPriority::HIGH, 500, 100));
+        sched.stagger_phases();
+        // 短周期が先に 0 に置かれ、長周期はその直後
+        assert_eq!(sched.get_task(1).unwrap().next_activation, 0);
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 100);
+        let fast = *sched.get_task(1).unwrap();
+        let slow = *sched.get_task(0).unwrap();
+        assert_eq!(job_overlap(&slow, 100, &fast, 0), 0);
+        assert_eq!(job_overlap(&slow, 0, &fast, 0), 100);
+    }
+
+    #[test]
+    fn test_stagger_skips_started_and_isr_tasks() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 1_000, 100));
+        sched.tick(0);
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::LOW, 1_000, 100));
+        sched.register(
+            Task::new(b"irq", dummy_task, TaskPriority::LOW, 1_000, 100).released_by_isr(),
+        );
+        sched.stagger_phases();
+        assert_eq!(sched.get_task(0).unwrap().next_activation, 1_000);
+        assert_eq!(sched.get_task(1).unwrap().next_activation, 0);
+        assert_eq!(sched.get_task(2).unwrap().state, TaskState::Sleeping);
+    }
+
+    // --- 調和周期テスト ---
+
+    #[test]
+    fn test_harmonic_detection() {
+        assert!(harmonic(&[]));
+        assert!(harmonic(&[100]));
+        assert!(harmonic(&[1_000, 250, 500]));
+        assert!(!harmonic(&[100, 300, 200]));
+        assert!(!harmonic(&[0, 100]));
+    }
+
+    #[test]
+    fn test_harmonic_set_uses_exact_bound() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 250, 150));
+        sched.register(Task::new(
+            b"b",
+            dummy_task,
+            TaskPriority::NORMAL,
+            1_000,
+            250,
+        ));
+        sched.register(Task::new(b"c", dummy_task, TaskPriority::LOW, 2_000, 100));
+        // U = 0.9 > LL(3) = 0.780 だが調和周期なので U ≤ 1 で判定
+        assert!(sched.is_harmonic());
+        assert_eq!(sched.rms_bound(), RmsBound::Harmonic);
+        assert!(sched.is_schedulable());
+    }
+
+    #[test]
+    fn test_non_harmonic_keeps_liu_layland() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 300, 150));
+        sched.register(Task::new(
+            b"b",
+            dummy_task,
+            TaskPriority::NORMAL,
+            1_000,
+            400,
+        ));
+        // U = 0.9 > 0.828、300 ∤ 1000
+        assert_eq!(sched.rms_bound(), RmsBound::LiuLayland);
+        assert!(!sched.is_schedulable());
+    }
+
+    #[test]
+    fn test_harmonic_over_full_not_schedulable() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 60));
+        sched.register(Task::new(b"b", dummy_task, TaskPriority::NORMAL, 200, 90));
+        assert_eq!(sched.rms_bound(), RmsBound::Harmonic);
+        assert!(!sched.is_schedulable());
+    }
+
+    #[test]
+    fn test_harmonic_per_core_and_const() {
+        let mut sched = Scheduler::new();
+        sched
+            .register(Task::new(b"a", dummy_task, TaskPriority::HIGH, 100, 50).with_affinity(0b01));
+        sched.register(
+            Task::new(b"b", dummy_task, TaskPriority::HIGH, 400, 180).with_affinity(0b01),
+        );
+        sched
+            .register(Task::new(b"c", dummy_task, TaskPriority::HIGH, 300, 10).with_affinity(0b10));
+        // コア 0 は {100, 400} で調和 → 0.95 でも可
+        assert!(!sched.is_harmonic());
+        assert!(sched.is_schedulable_on(0));
+        assert!(rms_schedulable(&[(100, 50), (400, 180)]));
+        assert!(!rms_schedulable(&[(100, 50), (150, 70)]));
+    }
+
+    #[test]
+    fn test_display_task_table() {
+        let mut sched = Scheduler::new();
+        sched.register(Task::new(
+            b"ctl",
+            dummy_task,
+            TaskPriority::HIGH,
+            1_000,
+            100,
+        ));
+        sched.register(Task::new(
+            b"log",
+            dummy_task,
+            TaskPriority::LOW,
+            10_000,
+            500,
+        ));
+        let mut out: FmtBuf<256> = FmtBuf::new();
+        write!(out, "{sched}").unwrap();
+        assert!(!out.is_truncated());
+        assert_eq!(
+            out.as_str(),
+            "name     prio   period       U misses\n\
+             ctl         1     1000  10.00%      0\n\
+             log         3    10000   5.00%      0\n\
+             2 tasks, U 15.00%\n"
+        );
     }
 }
@@ -0,0 +1,355 @@
+//! Line-based runtime console over byte rings (UART, RTT)
+//!
+//! [`Shell`] reads command lines from the consumer half of a byte
+//! [`AlignedSpscRing`] — filled by a UART receive interrupt or DMA — and
+//! writes its replies to the producer half of another one, which the
+//! transmit side drains. Call [`Shell::poll`] from the idle loop or after
+//! each kernel tick; it needs the kernel itself, so it cannot run as an
+//! ordinary task body.
+//!
+//! | Command | Reply |
+//! |---------|-------|
+//! | `ps` | One line per task: slot, name, state, priority, period, WCET, jobs, misses |
+//! | `suspend <name>` / `resume <name>` | `ok`, or `no such task` |
+//! | `stats` | Uptime, ticks, jobs, context switches, reserved load, misses |
+//! | `trace on` / `trace off` | Enables / disables [`Shell::tick`] recording into a [`TraceBuffer`] |
+//! | `help` | Command list |
+//!
+//! Lines end with `\n` or `\r`; a line longer than `LINE` bytes is
+//! discarded with `line too long`. Reply bytes that do not fit into the
+//! transmit ring are dropped and counted ([`Shell::dropped`]).
+//!
+//! ```rust
+//! use alice_rtos::aligned_ring::AlignedSpscRing;
+//! use alice_rtos::shell::Shell;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100).unwrap();
+//!
+//! let rx: AlignedSpscRing<u8, 64> = AlignedSpscRing::new();
+//! let tx: AlignedSpscRing<u8, 256> = AlignedSpscRing::new();
+//! let (mut uart_rx, mut shell_rx) = rx.split().unwrap();
+//! let (mut shell_tx, mut uart_tx) = tx.split().unwrap();
+//!
+//! for &b in b"suspend ctl\n" {
+//!     uart_rx.push(b);
+//! }
+//! let mut shell: Shell<32> = Shell::new();
+//! assert_eq!(shell.poll(&mut kernel, &mut shell_rx, &mut shell_tx), 1);
+//! assert_eq!(uart_tx.pop(), Some(b'o'));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt::{self, Write};
+
+#[cfg(doc)]
+use crate::aligned_ring::AlignedSpscRing;
+use crate::aligned_ring::{AlignedConsumer, AlignedProducer, Alignment};
+use crate::kernel::Kernel;
+use crate::task::TaskState;
+use crate::text::Percent;
+use crate::trace::TraceBuffer;
+
+/// Reply to `help`
+const HELP: &str = "ps | suspend <name> | resume <name> | stats | trace on|off | help\n";
+
+/// Command processor with a `LINE`-byte line buffer
+pub struct Shell<const LINE: usize> {
+    /// Line being received
+    line: [u8; LINE],
+    /// Bytes in `line`
+    len: usize,
+    /// The current line exceeded `LINE` bytes
+    overflow: bool,
+    /// `trace on` is in effect
+    tracing: bool,
+    /// Reply bytes lost to a full transmit ring
+    dropped: u32,
+}
+
+impl<const LINE: usize> Default for Shell<LINE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LINE: usize> Shell<LINE> {
+    /// Idle shell, tracing off
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            line: [0; LINE],
+            len: 0,
+            overflow: false,
+            tracing: false,
+            dropped: 0,
+        }
+    }
+
+    /// Process the received bytes; returns the number of commands run
+    pub fn poll<const R: usize, const T: usize, A: Alignment, B: Alignment>(
+        &mut self,
+        kernel: &mut Kernel,
+        rx: &mut AlignedConsumer<'_, u8, R, A>,
+        tx: &mut AlignedProducer<'_, u8, T, B>,
+    ) -> usize {
+        let mut commands = 0;
+        while let Some(b) = rx.pop() {
+            if b != b'\n' && b != b'\r' {
+                if self.len < LINE {
+                    self.line[self.len] = b;
+                    self.len += 1;
+                } else {
+                    self.overflow = true;
+                }
+                continue;
+            }
+            let mut out = Reply { tx, dropped: 0 };
+            if core::mem::take(&mut self.overflow) {
+                let _ = out.write_str("line too long\n");
+            } else if self.len > 0 {
+                let line = &self.line[..self.len];
+                let _ = execute(kernel, line, &mut self.tracing, &mut out);
+                commands += 1;
+            }
+            self.dropped += out.dropped;
+            self.len = 0;
+        }
+        commands
+    }
+
+    /// Tick the kernel, recording into `trace` while `trace on` is set
+    pub fn tick<const N: usize>(
+        &self,
+        kernel: &mut Kernel,
+        delta_us: u64,
+        trace: &TraceBuffer<N>,
+    ) -> Option<usize> {
+        if self.tracing {
+            trace.tick(kernel, delta_us)
+        } else {
+            kernel.tick(delta_us)
+        }
+    }
+
+    /// Is `trace on` in effect?
+    #[must_use]
+    pub const fn tracing(&self) -> bool {
+        self.tracing
+    }
+
+    /// Reply bytes dropped because the transmit ring was full
+    #[must_use]
+    pub const fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+/// Run one command line
+fn execute(
+    kernel: &mut Kernel,
+    line: &[u8],
+    tracing: &mut bool,
+    out: &mut impl Write,
+) -> fmt::Result {
+    let mut words = line
+        .split(|&b| b == b' ' || b == b'\t')
+        .filter(|w| !w.is_empty());
+    let cmd = words.next().unwrap_or_default();
+    let arg = words.next();
+    match (cmd, arg) {
+        (b"ps", None) => ps(kernel, out),
+        (b"stats", None) => stats(kernel, out),
+        (b"suspend" | b"resume", Some(name)) => {
+            let Some(idx) = kernel.scheduler.find_by_name(name) else {
+                return out.write_str("no such task\n");
+            };
+            if cmd == b"suspend" {
+                kernel.scheduler.suspend(idx);
+            } else {
+                kernel.scheduler.resume(idx);
+            }
+            out.write_str("ok\n")
+        }
+        (b"trace", Some(b"on" | b"off")) => {
+            *tracing = arg == Some(b"on");
+            out.write_str("ok\n")
+        }
+        (b"help", None) => out.write_str(HELP),
+        _ => out.write_str("unknown command (try help)\n"),
+    }
+}
+
+/// `ps`: one line per active task
+fn ps(kernel: &Kernel, out: &mut impl Write) -> fmt::Result {
+    out.write_str("id name     state prio period   wcet     runs misses\n")?;
+    for (idx, task) in kernel.scheduler.tasks() {
+        let name = task.display_name();
+        let state = match task.state {
+            TaskState::Ready => "ready",
+            TaskState::Running => "run",
+            TaskState::Sleeping => "sleep",
+            TaskState::Suspended => "susp",
+            TaskState::Inactive => "-",
+        };
+        writeln!(
+            out,
+            "{idx:<2} {name:<8} {state:<5} {:>4} {:>6} {:>6} {:>8} {:>6}",
+            task.priority.0, task.period_us, task.wcet_us, task.exec_count, task.deadline_misses
+        )?;
+    }
+    Ok(())
+}
+
+/// `stats`: kernel-wide counters
+fn stats(kernel: &Kernel, out: &mut impl Write) -> fmt::Result {
+    let (jobs, misses) = kernel
+        .scheduler
+        .tasks()
+        .fold((0u64, 0u64), |(j, m), (_, t)| {
+            (
+                j + u64::from(t.exec_count),
+                m + u64::from(t.deadline_misses),
+            )
+        });
+    let s = kernel.stats(kernel.timer.now_us(), jobs);
+    writeln!(
+        out,
+        "up {} us, ticks {}, jobs {}, switches {}",
+        s.total_us, s.total_ticks, s.tasks_executed, s.context_switches
+    )?;
+    writeln!(
+        out,
+        "load {}, schedulable {}, misses {}, faults {}",
+        Percent(s.utilization_ppm),
+        if s.schedulable { "yes" } else { "no" },
+        misses,
+        s.task_faults
+    )
+}
+
+/// Formatter writing into the transmit ring, counting what does not fit
+struct Reply<'r, 'a, const T: usize, B: Alignment> {
+    tx: &'r mut AlignedProducer<'a, u8, T, B>,
+    dropped: u32,
+}
+
+impl<const T: usize, B: Alignment> Write for Reply<'_, '_, T, B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            if !self.tx.push(b) {
+                self.dropped += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aligned_ring::AlignedSpscRing;
+    use crate::task::TaskPriority;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn noop(_: &mut [u8]) {}
+    fn sink(_: &[u8]) {}
+
+    fn kernel() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100);
+        kernel.add_task(b"log", noop, TaskPriority::LOW, 10_000, 500);
+        kernel
+    }
+
+    /// Feed `input` and return the reply text
+    fn run<const LINE: usize>(
+        shell: &mut Shell<LINE>,
+        kernel: &mut Kernel,
+        input: &[u8],
+    ) -> String {
+        let rx: AlignedSpscRing<u8, 128> = AlignedSpscRing::new();
+        let tx: AlignedSpscRing<u8, 1024> = AlignedSpscRing::new();
+        let (mut uart_rx, mut shell_rx) = rx.split().unwrap();
+        let (mut shell_tx, mut uart_tx) = tx.split().unwrap();
+        for &b in input {
+            assert!(uart_rx.push(b));
+        }
+        shell.poll(kernel, &mut shell_rx, &mut shell_tx);
+        let mut reply = Vec::new();
+        while let Some(b) = uart_tx.pop() {
+            reply.push(b);
+        }
+        String::from_utf8(reply).unwrap()
+    }
+
+    #[test]
+    fn test_ps_lists_tasks() {
+        let mut k = kernel();
+        k.run_for(2_000, 100);
+        let mut shell: Shell<32> = Shell::new();
+        let reply = run(&mut shell, &mut k, b"ps\r\n");
+        let lines: Vec<&str> = reply.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("id name"));
+        assert!(lines[1].starts_with("0  ctl      sleep    1   1000    100"));
+        assert!(lines[2].starts_with("1  log"));
+    }
+
+    #[test]
+    fn test_suspend_and_resume_by_name() {
+        let mut k = kernel();
+        let mut shell: Shell<32> = Shell::new();
+        assert_eq!(run(&mut shell, &mut k, b"suspend  log\n"), "ok\n");
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Suspended);
+        assert_eq!(run(&mut shell, &mut k, b"resume log\n"), "ok\n");
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Ready);
+        assert_eq!(run(&mut shell, &mut k, b"suspend nope\n"), "no such task\n");
+        // 引数なしは不明なコマンド扱い
+        assert!(run(&mut shell, &mut k, b"suspend\n").starts_with("unknown"));
+    }
+
+    #[test]
+    fn test_stats_and_trace_toggle() {
+        static TRACE: TraceBuffer<256> = TraceBuffer::new(sink);
+        let mut k = kernel();
+        let mut shell: Shell<32> = Shell::new();
+        k.run_for(10_000, 100);
+        let reply = run(&mut shell, &mut k, b"stats\n");
+        assert!(
+            reply.starts_with("up 10000 us, ticks 100, jobs 12,"),
+            "{reply}"
+        );
+        // 100/1000 + 500/10000 = 15%
+        assert!(reply.contains("load 15.00%, schedulable yes, misses 0"));
+
+        shell.tick(&mut k, 100, &TRACE);
+        assert_eq!(TRACE.pending(), 0);
+        assert_eq!(run(&mut shell, &mut k, b"trace on\n"), "ok\n");
+        assert!(shell.tracing());
+        // 次の ctl リリースまで進めて記録されることを確認
+        for _ in 0..10 {
+            shell.tick(&mut k, 100, &TRACE);
+        }
+        assert!(TRACE.pending() > 0);
+        assert_eq!(run(&mut shell, &mut k, b"trace off\n"), "ok\n");
+        assert!(!shell.tracing());
+    }
+
+    #[test]
+    fn test_long_lines_and_partial_input() {
+        let mut k = kernel();
+        let mut shell: Shell<8> = Shell::new();
+        assert_eq!(run(&mut shell, &mut k, b"suspend log\n"), "line too long\n");
+        // 改行が届くまでは実行しない
+        assert_eq!(run(&mut shell, &mut k, b"he"), "");
+        assert_eq!(run(&mut shell, &mut k, b"lp\n\n"), HELP);
+        assert!(run(&mut shell, &mut k, b"reboot\n").starts_with("unknown"));
+        assert_eq!(shell.dropped(), 0);
+    }
+}
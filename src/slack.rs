@@ -0,0 +1,358 @@
+//! Slack stealing — background work without endangering periodic deadlines
+//!
+//! An [`IDLE`](crate::task::TaskPriority::IDLE) task only runs when no
+//! periodic job is ready, which under load may be never, even though
+//! every periodic job could afford to start later and still finish in
+//! time. [`available_slack_us`] computes how long the processor can be
+//! taken away from the periodic tasks at the current instant; a
+//! [`SlackServer`] hands that time to a background function (diagnostics,
+//! self-tests, logging) right after each kernel tick.
+//!
+//! For every pending or upcoming job `i` with absolute deadline `d_i`:
+//!
+//! ```text
+//! slack_i = (d_i − now) − B_i − Σ_{j ∈ hp(i) ∪ {i}} C_j · n_j(d_i)
+//! ```
+//!
+//! where `n_j(d_i)` counts the jobs of `j` released before `d_i` that
+//! have not completed yet, and `B_i` is the largest WCET of a
+//! lower-priority task (jobs run to completion, so one may have just
+//! started). The available slack is the minimum over all tasks. Taking
+//! at most that much time at once keeps every deadline, provided the
+//! slack is re-evaluated before each grant. Tasks without a periodic
+//! release (ISR-released, chained) are assumed to be released now;
+//! suspended tasks are ignored.
+//!
+//! ```rust
+//! use alice_rtos::slack::SlackServer;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//! fn diagnostics(budget_us: u32) -> u32 {
+//!     budget_us.min(50)
+//! }
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 400).unwrap();
+//! let mut server = SlackServer::new(diagnostics).with_margin(100);
+//! server.run_for(&mut kernel, 10_000, 100);
+//! assert!(server.grants() > 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{Kernel, KernelStats};
+use crate::scheduler::Scheduler;
+use crate::task::{Task, TaskState};
+use crate::timer::{Tick, WrappingInstant};
+
+/// Background work run in stolen slack
+///
+/// Receives the granted budget (µs) and returns the time it used (µs).
+pub type BackgroundFn = fn(budget_us: u32) -> u32;
+
+/// Processor time (µs) that can be stolen now without a deadline miss
+///
+/// `u32::MAX` when no periodic task is active; 0 when a deadline has
+/// already passed or the task set leaves no room.
+#[must_use]
+pub fn available_slack_us(sched: &Scheduler) -> u32 {
+    let now = sched.now_us();
+    let mut slack = i64::from(u32::MAX);
+    for i in 0..sched.task_slots() {
+        let Some(task) = sched.get_task(i).filter(|t| competes(t)) else {
+            continue;
+        };
+        if task.period_us == 0 {
+            continue;
+        }
+        let deadline = release_offset(task, now) + i64::from(task.period_us);
+        let mut demand = 0i64;
+        let mut blocking = 0i64;
+        for j in 0..sched.task_slots() {
+            let Some(other) = sched.get_task(j).filter(|t| competes(t)) else {
+                continue;
+            };
+            if other.priority > task.priority {
+                blocking = blocking.max(i64::from(other.wcet_us));
+            } else {
+                demand += i64::from(other.wcet_us) * jobs_before(other, now, deadline);
+            }
+        }
+        slack = slack.min(deadline - blocking - demand);
+    }
+    slack.max(0) as u32
+}
+
+/// Does the task take processor time from the periodic schedule?
+fn competes(task: &Task) -> bool {
+    matches!(
+        task.state,
+        TaskState::Ready | TaskState::Running | TaskState::Sleeping
+    )
+}
+
+/// Signed distance (µs) from `now` to the task's current or next release
+#[allow(clippy::useless_conversion)] // i64 → i64 without `tick-u32`
+fn release_offset(task: &Task, now: Tick) -> i64 {
+    if !task.time_released() {
+        // 周期リリースでないタスクは今リリースされたとみなす
+        return 0;
+    }
+    i64::from(task.next_release().diff(WrappingInstant(now)))
+}
+
+/// Uncompleted jobs of `task` released before `deadline` (offset from `now`)
+fn jobs_before(task: &Task, now: Tick, deadline: i64) -> i64 {
+    let release = release_offset(task, now);
+    if release >= deadline {
+        return 0;
+    }
+    match task.period_us {
+        // 非周期タスクは待機中のジョブ 1 つだけ
+        0 => i64::from(task.state != TaskState::Sleeping),
+        period => ((deadline - release) as u64).div_ceil(u64::from(period)) as i64,
+    }
+}
+
+/// Runs background work in the slack of the periodic tasks
+pub struct SlackServer {
+    /// Background work
+    work: BackgroundFn,
+    /// Slack kept in reserve for tick latency and overheads (µs)
+    margin_us: u32,
+    /// Smallest grant worth running the work for (µs)
+    min_grant_us: u32,
+    /// Largest single grant (µs)
+    max_grant_us: u32,
+    /// Time used by the last grant, still to be charged (µs)
+    owed_us: u64,
+    /// Grants handed out
+    grants: u32,
+    /// Instants where the slack was below `min_grant_us`
+    denied: u32,
+    /// Grants whose work reported more than its budget
+    overruns: u32,
+    /// Total stolen time (µs)
+    stolen_us: u64,
+}
+
+impl SlackServer {
+    /// Server running `work` whenever at least 1 µs of slack is available
+    #[must_use]
+    pub const fn new(work: BackgroundFn) -> Self {
+        Self {
+            work,
+            margin_us: 0,
+            min_grant_us: 1,
+            max_grant_us: u32::MAX,
+            owed_us: 0,
+            grants: 0,
+            denied: 0,
+            overruns: 0,
+            stolen_us: 0,
+        }
+    }
+
+    /// Keep `margin_us` of slack in reserve
+    ///
+    /// Releases are only noticed at the next tick, so the margin should
+    /// cover at least one tick period plus the context-switch overhead.
+    #[must_use]
+    pub const fn with_margin(mut self, margin_us: u32) -> Self {
+        self.margin_us = margin_us;
+        self
+    }
+
+    /// Grant between `min_us` and `max_us` at a time
+    #[must_use]
+    pub const fn with_grant(mut self, min_us: u32, max_us: u32) -> Self {
+        self.min_grant_us = if min_us == 0 { 1 } else { min_us };
+        self.max_grant_us = max_us;
+        self
+    }
+
+    /// Tick the kernel, then steal slack for the background work
+    ///
+    /// The executed job is assumed to have taken its full WCET.
+    pub fn tick(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        let busy = executed
+            .and_then(|idx| kernel.scheduler.get_task(idx))
+            .map_or(0, |t| t.wcet_us);
+        self.steal(kernel, busy);
+        executed
+    }
+
+    /// Run the background work if enough slack is available
+    ///
+    /// `elapsed_us` is the time already spent since the kernel's clock was
+    /// last advanced (e.g. the job just executed); slack shrinks by at
+    /// most that much. Returns the time used by the work (µs).
+    pub fn steal(&mut self, kernel: &Kernel, elapsed_us: u32) -> u32 {
+        let slack = available_slack_us(&kernel.scheduler)
+            .saturating_sub(elapsed_us)
+            .saturating_sub(self.margin_us);
+        if slack < self.min_grant_us {
+            self.denied += 1;
+            return 0;
+        }
+        let budget = slack.min(self.max_grant_us);
+        let used = (self.work)(budget);
+        if used > budget {
+            self.overruns += 1;
+        }
+        self.grants += 1;
+        self.stolen_us += u64::from(used);
+        self.owed_us = u64::from(used);
+        used
+    }
+
+    /// Run for `total_us` in `tick_us` steps (see [`Kernel::run_for`])
+    ///
+    /// Stolen time is charged to the timeline: the next tick comes once
+    /// the background work has finished.
+    pub fn run_for(&mut self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        let mut delta = tick_us;
+        while kernel.timer.now_us() - start < total_us {
+            if self.tick(kernel, delta).is_some() {
+                tasks_executed += 1;
+            }
+            delta = tick_us.max(core::mem::take(&mut self.owed_us));
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+
+    /// Grants handed out so far
+    #[must_use]
+    pub const fn grants(&self) -> u32 {
+        self.grants
+    }
+
+    /// Instants where the slack was too small for a grant
+    #[must_use]
+    pub const fn denied(&self) -> u32 {
+        self.denied
+    }
+
+    /// Grants whose work used more than its budget
+    #[must_use]
+    pub const fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Total time given to the background work (µs)
+    #[must_use]
+    pub const fn stolen_us(&self) -> u64 {
+        self.stolen_us
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    fn greedy(budget_us: u32) -> u32 {
+        budget_us
+    }
+
+    fn misses(kernel: &Kernel) -> u32 {
+        (0..kernel.scheduler.task_slots())
+            .filter_map(|i| kernel.scheduler.get_task(i))
+            .map(|t| t.deadline_misses)
+            .sum()
+    }
+
+    /// U = 0.4 + 0.4 = 0.8
+    fn loaded() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 400);
+        kernel.add_task(b"lo", noop, TaskPriority::LOW, 2_000, 800);
+        kernel
+    }
+
+    /// Each job takes its WCET; stolen time is charged likewise
+    fn run_wcet(server: &mut SlackServer, kernel: &mut Kernel, total_us: u64, tick_us: u64) {
+        let mut delta = tick_us;
+        while kernel.timer.now_us() < total_us {
+            let busy = server
+                .tick(kernel, delta)
+                .and_then(|idx| kernel.scheduler.get_task(idx))
+                .map_or(0, |t| u64::from(t.wcet_us));
+            delta = tick_us.max(busy + core::mem::take(&mut server.owed_us));
+        }
+    }
+
+    #[test]
+    fn test_slack_at_instant() {
+        let mut kernel = Kernel::testing();
+        assert_eq!(available_slack_us(&kernel.scheduler), u32::MAX);
+
+        kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 200);
+        kernel.add_task(b"lo", noop, TaskPriority::LOW, 2_000, 500);
+        // t = 0: hi は 1000 - 200 - 阻害 500 = 300
+        assert_eq!(available_slack_us(&kernel.scheduler), 300);
+
+        // hi 実行後: 次の hi (1000 リリース) と lo で 2000 - 700
+        assert_eq!(kernel.tick(0), Some(0));
+        assert_eq!(available_slack_us(&kernel.scheduler), 1_300);
+
+        // 中断中のタスクは数えない
+        kernel.scheduler.suspend(1);
+        assert_eq!(available_slack_us(&kernel.scheduler), 1_800);
+    }
+
+    #[test]
+    fn test_overdue_deadline_leaves_no_slack() {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 200);
+        kernel.add_task(b"lo", noop, TaskPriority::LOW, 1_000, 900);
+        assert_eq!(available_slack_us(&kernel.scheduler), 0);
+    }
+
+    #[test]
+    fn test_background_runs_under_load_without_misses() {
+        let mut kernel = loaded();
+        let mut server = SlackServer::new(greedy).with_margin(100);
+        run_wcet(&mut server, &mut kernel, 200_000, 100);
+        assert_eq!(misses(&kernel), 0);
+        // 残り 20% のうち余裕分を背景処理が使う
+        assert!(server.stolen_us() > 10_000, "{}", server.stolen_us());
+        assert!(server.denied() > 0);
+        assert_eq!(server.overruns(), 0);
+    }
+
+    #[test]
+    fn test_unchecked_background_misses_deadlines() {
+        // 対照: 余裕を見ずに毎ティック 300 µs 奪うと締め切りを落とす
+        let mut kernel = loaded();
+        let mut delta = 100;
+        while kernel.timer.now_us() < 200_000 {
+            let busy = kernel
+                .tick(delta)
+                .and_then(|idx| kernel.scheduler.get_task(idx))
+                .map_or(0, |t| u64::from(t.wcet_us));
+            delta = 100u64.max(busy + 300);
+        }
+        assert!(misses(&kernel) > 0);
+    }
+
+    #[test]
+    fn test_grant_limits() {
+        fn probe(budget_us: u32) -> u32 {
+            assert!((100..=250).contains(&budget_us));
+            100
+        }
+        let mut kernel = loaded();
+        let mut server = SlackServer::new(probe).with_grant(100, 250);
+        server.run_for(&mut kernel, 50_000, 100);
+        assert!(server.grants() > 0);
+        assert_eq!(server.stolen_us(), server.grants() as u64 * 100);
+        assert_eq!(misses(&kernel), 0);
+    }
+}
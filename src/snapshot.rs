@@ -0,0 +1,311 @@
+//! Kernel snapshot / restore for warm restart
+//!
+//! [`Kernel::save_state`](crate::kernel::Kernel::save_state) serializes
+//! the mutable kernel state — clock, counters and the run-time part of
+//! every task descriptor — into a caller-provided buffer, e.g. a
+//! retained-RAM section or backup registers written on brownout
+//! detection. After reset the firmware registers the same tasks again
+//! and calls [`Kernel::restore_state`](crate::kernel::Kernel::restore_state)
+//! to continue where it stopped.
+//!
+//! Task functions cannot be serialized, so a snapshot only restores onto
+//! a kernel with the same tasks (checked by name). Attached tables
+//! (software timers, profiler, budgets) live outside the kernel and are
+//! not included.
+//!
+//! # Layout (version 1, little-endian)
+//!
+//! | Offset | Size | Content |
+//! |--------|------|---------|
+//! | 0 | 4 | magic `"ARTS"` |
+//! | 4 | 1 | layout version |
+//! | 5 | 1 | task count `n` |
+//! | 6 | 2 | task record size (40) |
+//! | 8 | 40 | kernel: scheduler time, timer time, timer overflows, total ticks, context switches, stack faults, guard corruptions, mode |
+//! | 48 | 40·n | task records: name, period, WCET, next activation, jobs, misses, faults, scratch high water, inputs ready, state, flags |
+//! | 48 + 40·n | 4 | CRC-32 of everything before |
+//!
+//! Author: Moroya Sakamoto
+
+use crate::task::TaskState;
+
+/// Current snapshot layout version
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Snapshot magic
+const MAGIC: [u8; 4] = *b"ARTS";
+
+/// Header bytes (magic, version, task count, record size)
+const HEADER_SIZE: usize = 8;
+
+/// Kernel section bytes
+pub(crate) const KERNEL_RECORD_SIZE: usize = 40;
+
+/// Bytes per task record
+pub(crate) const TASK_RECORD_SIZE: usize = 40;
+
+/// Snapshot size for `task_count` task slots
+#[must_use]
+pub const fn snapshot_size(task_count: usize) -> usize {
+    HEADER_SIZE + KERNEL_RECORD_SIZE + task_count * TASK_RECORD_SIZE + 4
+}
+
+/// Why a snapshot could not be saved or restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The buffer is shorter than the snapshot
+    BufferTooSmall {
+        /// Bytes required
+        needed: usize,
+    },
+    /// Not a snapshot (magic mismatch)
+    BadMagic,
+    /// Written by an incompatible layout version
+    UnsupportedVersion(u8),
+    /// Checksum mismatch or invalid field
+    Corrupt,
+    /// Task slot `idx` differs from the registered task set
+    TaskMismatch {
+        /// Task slot
+        idx: usize,
+    },
+}
+
+/// Little-endian cursor over an output buffer
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Writer for a snapshot of `task_count` slots; writes the header
+    pub(crate) fn new(buf: &'a mut [u8], task_count: usize) -> Result<Self, SnapshotError> {
+        let needed = snapshot_size(task_count);
+        if buf.len() < needed {
+            return Err(SnapshotError::BufferTooSmall { needed });
+        }
+        let mut w = Self { buf, pos: 0 };
+        w.bytes(&MAGIC);
+        w.u8(SNAPSHOT_VERSION);
+        w.u8(task_count as u8);
+        w.u16(TASK_RECORD_SIZE as u16);
+        Ok(w)
+    }
+
+    pub(crate) fn bytes(&mut self, data: &[u8]) {
+        self.buf[self.pos..self.pos + data.len()].copy_from_slice(data);
+        self.pos += data.len();
+    }
+
+    pub(crate) fn u8(&mut self, v: u8) {
+        self.bytes(&[v]);
+    }
+
+    pub(crate) fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, v: u64) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    /// Zero-fill up to the end of the current record of `size` bytes
+    /// starting at `start`
+    pub(crate) fn pad_to(&mut self, start: usize, size: usize) {
+        while self.pos < start + size {
+            self.u8(0);
+        }
+    }
+
+    pub(crate) const fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Append the checksum; returns the snapshot length
+    pub(crate) fn finish(mut self) -> usize {
+        let crc = crc32(&self.buf[..self.pos]);
+        self.u32(crc);
+        self.pos
+    }
+}
+
+/// Little-endian cursor over a validated snapshot
+#[derive(Clone)]
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Validate header and checksum; returns the reader (positioned at
+    /// the kernel section) and the task count
+    pub(crate) fn new(buf: &'a [u8]) -> Result<(Self, usize), SnapshotError> {
+        if buf.len() < HEADER_SIZE {
+            return Err(SnapshotError::BufferTooSmall {
+                needed: snapshot_size(0),
+            });
+        }
+        if buf[..4] != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        if buf[4] != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(buf[4]));
+        }
+        let count = buf[5] as usize;
+        if u16::from_le_bytes([buf[6], buf[7]]) as usize != TASK_RECORD_SIZE {
+            return Err(SnapshotError::Corrupt);
+        }
+        let len = snapshot_size(count);
+        if buf.len() < len {
+            return Err(SnapshotError::BufferTooSmall { needed: len });
+        }
+        let stored = u32::from_le_bytes([buf[len - 4], buf[len - 3], buf[len - 2], buf[len - 1]]);
+        if crc32(&buf[..len - 4]) != stored {
+            return Err(SnapshotError::Corrupt);
+        }
+        Ok((
+            Self {
+                buf: &buf[..len - 4],
+                pos: HEADER_SIZE,
+            },
+            count,
+        ))
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0u8; N];
+        out.copy_from_slice(&self.buf[self.pos..self.pos + N]);
+        self.pos += N;
+        out
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        self.bytes::<1>()[0]
+    }
+
+    pub(crate) fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.bytes())
+    }
+
+    pub(crate) fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes())
+    }
+
+    pub(crate) fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+
+    /// Skip to the end of the record of `size` bytes starting at `start`
+    pub(crate) const fn skip_to(&mut self, start: usize, size: usize) {
+        self.pos = start + size;
+    }
+
+    pub(crate) const fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// Wire code of a task state
+pub(crate) const fn encode_state(state: TaskState) -> u8 {
+    match state {
+        TaskState::Ready => 0,
+        TaskState::Running => 1,
+        TaskState::Sleeping => 2,
+        TaskState::Suspended => 3,
+        TaskState::Inactive => 4,
+    }
+}
+
+/// Task state of a wire code
+pub(crate) const fn decode_state(code: u8) -> Result<TaskState, SnapshotError> {
+    Ok(match code {
+        0 => TaskState::Ready,
+        1 => TaskState::Running,
+        2 => TaskState::Sleeping,
+        3 => TaskState::Suspended,
+        4 => TaskState::Inactive,
+        _ => return Err(SnapshotError::Corrupt),
+    })
+}
+
+/// CRC-32 (IEEE 802.3, reflected), bitwise: no table in flash
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_reference_vector() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_state_codes_round_trip() {
+        for state in [
+            TaskState::Ready,
+            TaskState::Running,
+            TaskState::Sleeping,
+            TaskState::Suspended,
+            TaskState::Inactive,
+        ] {
+            assert_eq!(decode_state(encode_state(state)), Ok(state));
+        }
+        assert_eq!(decode_state(9), Err(SnapshotError::Corrupt));
+    }
+
+    #[test]
+    fn test_writer_reader_round_trip() {
+        let mut buf = [0u8; snapshot_size(0)];
+        let mut w = Writer::new(&mut buf, 0).unwrap();
+        w.u64(0x0102_0304_0506_0708);
+        w.u32(7);
+        w.pad_to(HEADER_SIZE, KERNEL_RECORD_SIZE);
+        assert_eq!(w.finish(), snapshot_size(0));
+        let (mut r, count) = Reader::new(&buf).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(r.u64(), 0x0102_0304_0506_0708);
+        assert_eq!(r.u32(), 7);
+    }
+
+    #[test]
+    fn test_header_validation() {
+        let mut buf = [0u8; snapshot_size(0)];
+        assert_eq!(
+            Writer::new(&mut buf[..10], 0).err(),
+            Some(SnapshotError::BufferTooSmall {
+                needed: snapshot_size(0)
+            })
+        );
+        let mut w = Writer::new(&mut buf, 0).unwrap();
+        w.pad_to(HEADER_SIZE, KERNEL_RECORD_SIZE);
+        w.finish();
+        let mut bad = buf;
+        bad[0] = b'X';
+        assert_eq!(Reader::new(&bad).err(), Some(SnapshotError::BadMagic));
+        let mut bad = buf;
+        bad[4] = 99;
+        assert_eq!(
+            Reader::new(&bad).err(),
+            Some(SnapshotError::UnsupportedVersion(99))
+        );
+        let mut bad = buf;
+        bad[20] ^= 0x10;
+        assert_eq!(Reader::new(&bad).err(), Some(SnapshotError::Corrupt));
+    }
+}
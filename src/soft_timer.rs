@@ -0,0 +1,275 @@
+//! Deferred one-shot software timers
+//!
+//! A fixed-size pool of "call this function once in N µs" timers that
+//! lives outside the task table. The pool is declared statically and
+//! attached to the kernel with
+//! [`Kernel::attach_timers`](crate::kernel::Kernel::attach_timers); expired
+//! callbacks run from the kernel tick, before task dispatch.
+//!
+//! Slot lifecycle: Free → Armed → (expiry / cancel) → Dormant → restart →
+//! Armed. Dormant slots keep their callback and delay until deleted.
+//!
+//! Armed expiries are kept in a [`TimerWheel`], so a tick finds the
+//! expired timers — and the tickless loop the next expiry — without
+//! scanning the pool.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::wheel::TimerWheel;
+
+/// Timers per pool (at most `MAX_WHEEL_ENTRIES`)
+pub const MAX_SOFT_TIMERS: usize = 8;
+
+/// Timer expiry callback
+pub type TimerCallback = fn();
+
+/// One software timer slot
+#[derive(Clone, Copy)]
+pub struct SoftTimer {
+    /// Callback (None = free slot)
+    callback: Option<TimerCallback>,
+    /// Delay applied by arm / restart (µs)
+    delay_us: u32,
+    /// Absolute expiry time (µs), valid while armed
+    expires_at: u64,
+    /// Counting down?
+    armed: bool,
+}
+
+impl SoftTimer {
+    /// Free slot
+    const EMPTY: Self = Self {
+        callback: None,
+        delay_us: 0,
+        expires_at: 0,
+        armed: false,
+    };
+}
+
+/// Static pool of one-shot software timers
+pub struct SoftTimerPool {
+    /// Timer slots
+    timers: [SoftTimer; MAX_SOFT_TIMERS],
+    /// Expiries of the armed slots (1 µs resolution)
+    wheel: TimerWheel,
+}
+
+impl Default for SoftTimerPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftTimerPool {
+    /// Empty pool (usable in `static` initializers)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            timers: [SoftTimer::EMPTY; MAX_SOFT_TIMERS],
+            wheel: TimerWheel::new(1),
+        }
+    }
+
+    /// Allocate a slot and arm it to fire `delay_us` after `now_us`
+    ///
+    /// Returns the timer id, or None when every slot is in use.
+    pub fn arm(&mut self, callback: TimerCallback, delay_us: u32, now_us: u64) -> Option<usize> {
+        let id = self.timers.iter().position(|t| t.callback.is_none())?;
+        self.timers[id] = SoftTimer {
+            callback: Some(callback),
+            delay_us,
+            expires_at: now_us + delay_us as u64,
+            armed: true,
+        };
+        self.wheel.insert(id, now_us + delay_us as u64);
+        Some(id)
+    }
+
+    /// Stop an armed timer without firing it (slot stays allocated)
+    ///
+    /// Returns false if `id` was not armed.
+    pub fn cancel(&mut self, id: usize) -> bool {
+        match self.timers.get_mut(id) {
+            Some(t) if t.armed => {
+                t.armed = false;
+                self.wheel.remove(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Re-arm `id` with its original delay, counted from `now_us`
+    ///
+    /// Works on armed (pushes expiry out) and dormant timers.
+    pub fn restart(&mut self, id: usize, now_us: u64) -> bool {
+        match self.timers.get_mut(id) {
+            Some(t) if t.callback.is_some() => {
+                t.expires_at = now_us + t.delay_us as u64;
+                t.armed = true;
+                self.wheel.insert(id, t.expires_at);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Release the slot of `id`
+    pub fn delete(&mut self, id: usize) -> bool {
+        match self.timers.get_mut(id) {
+            Some(t) if t.callback.is_some() => {
+                *t = SoftTimer::EMPTY;
+                self.wheel.remove(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Time left until `id` fires (None if not armed)
+    #[must_use]
+    pub fn remaining_us(&self, id: usize, now_us: u64) -> Option<u64> {
+        let t = self.timers.get(id)?;
+        t.armed.then(|| t.expires_at.saturating_sub(now_us))
+    }
+
+    /// Is `id` counting down?
+    #[must_use]
+    pub fn is_armed(&self, id: usize) -> bool {
+        self.timers.get(id).is_some_and(|t| t.armed)
+    }
+
+    /// Earliest armed expiry
+    #[must_use]
+    pub fn next_expiry_us(&self) -> Option<u64> {
+        self.wheel.next_expiry_us()
+    }
+
+    /// Fire every timer that expired at or before `now_us`
+    ///
+    /// Fired timers become dormant; they fire in id order. Returns the
+    /// number fired.
+    pub fn fire_expired(&mut self, now_us: u64) -> usize {
+        let mut due = self.wheel.advance(now_us);
+        let mut fired = 0;
+        while due != 0 {
+            let t = &mut self.timers[due.trailing_zeros() as usize];
+            due &= due - 1;
+            t.armed = false;
+            if let Some(callback) = t.callback {
+                callback();
+            }
+            fired += 1;
+        }
+        fired
+    }
+
+    /// Number of allocated slots
+    #[must_use]
+    pub fn allocated(&self) -> usize {
+        self.timers.iter().filter(|t| t.callback.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static HITS: AtomicU32 = AtomicU32::new(0);
+
+    fn hit() {
+        HITS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn noop() {}
+
+    #[test]
+    fn test_arm_fires_once() {
+        static FIRED: AtomicU32 = AtomicU32::new(0);
+        fn cb() {
+            FIRED.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut pool = SoftTimerPool::new();
+        let id = pool.arm(cb, 5_000, 0).unwrap();
+        assert_eq!(pool.fire_expired(4_999), 0);
+        assert_eq!(pool.fire_expired(5_000), 1);
+        assert_eq!(pool.fire_expired(10_000), 0);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+        assert!(!pool.is_armed(id));
+        assert_eq!(pool.allocated(), 1);
+    }
+
+    #[test]
+    fn test_remaining() {
+        let mut pool = SoftTimerPool::new();
+        let id = pool.arm(noop, 1_000, 100).unwrap();
+        assert_eq!(pool.remaining_us(id, 600), Some(500));
+        assert_eq!(pool.remaining_us(id, 2_000), Some(0));
+        pool.cancel(id);
+        assert_eq!(pool.remaining_us(id, 600), None);
+        assert_eq!(pool.remaining_us(MAX_SOFT_TIMERS, 0), None);
+    }
+
+    #[test]
+    fn test_cancel_prevents_fire() {
+        let mut pool = SoftTimerPool::new();
+        let id = pool.arm(hit, 10, 0).unwrap();
+        let before = HITS.load(Ordering::Relaxed);
+        assert!(pool.cancel(id));
+        assert!(!pool.cancel(id));
+        assert_eq!(pool.fire_expired(100), 0);
+        assert_eq!(HITS.load(Ordering::Relaxed), before);
+    }
+
+    #[test]
+    fn test_restart_dormant_and_armed() {
+        let mut pool = SoftTimerPool::new();
+        let id = pool.arm(noop, 100, 0).unwrap();
+        // 期限前の restart は期限を延長
+        assert!(pool.restart(id, 50));
+        assert_eq!(pool.next_expiry_us(), Some(150));
+        assert_eq!(pool.fire_expired(150), 1);
+        // 休止中タイマも再始動可能
+        assert!(pool.restart(id, 200));
+        assert_eq!(pool.remaining_us(id, 200), Some(100));
+    }
+
+    #[test]
+    fn test_pool_exhaustion_and_delete() {
+        let mut pool = SoftTimerPool::new();
+        for _ in 0..MAX_SOFT_TIMERS {
+            assert!(pool.arm(noop, 10, 0).is_some());
+        }
+        assert!(pool.arm(noop, 10, 0).is_none());
+        assert!(pool.delete(3));
+        assert!(!pool.delete(3));
+        assert!(!pool.restart(3, 0));
+        assert_eq!(pool.arm(noop, 10, 0), Some(3));
+    }
+
+    #[test]
+    fn test_long_delay_across_jumps() {
+        let mut pool = SoftTimerPool::new();
+        let id = pool.arm(noop, 30_000_000, 0).unwrap();
+        pool.arm(noop, 20, 0);
+        // ティックレスの大きな跳躍でも期限前には発火しない
+        assert_eq!(pool.fire_expired(1_000), 1);
+        assert_eq!(pool.fire_expired(29_999_999), 0);
+        assert_eq!(pool.next_expiry_us(), Some(30_000_000));
+        assert_eq!(pool.fire_expired(30_000_000), 1);
+        assert!(!pool.is_armed(id));
+        assert_eq!(pool.next_expiry_us(), None);
+    }
+
+    #[test]
+    fn test_next_expiry_earliest_armed() {
+        let mut pool = SoftTimerPool::new();
+        assert_eq!(pool.next_expiry_us(), None);
+        pool.arm(noop, 300, 0);
+        let b = pool.arm(noop, 100, 0).unwrap();
+        assert_eq!(pool.next_expiry_us(), Some(100));
+        pool.cancel(b);
+        assert_eq!(pool.next_expiry_us(), Some(300));
+    }
+}
@@ -3,24 +3,219 @@
 //! Zero-copy inter-task communication. No heap, no mutex, no critical sections.
 //! Uses atomic read/write indices for ISR-safe operation.
 //!
+//! With the `critical-section` feature the indices are instead read and
+//! written inside `critical_section::with`, so the same API builds on
+//! cores whose atomics are incomplete (Cortex-M0/M0+, thumbv6m).
+//!
+//! The index protocol lives in `Indices`, which only needs `&self`; its
+//! orderings are model-checked with `loom` (see `atomic.rs`).
+//! [`Channel`](crate::channel::Channel) and
+//! [`AlignedSpscRing`](crate::aligned_ring::AlignedSpscRing) keep their
+//! indices in the same `Indices`, over their own index cells.
+//!
 //! Author: Moroya Sakamoto
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use crate::error::{KernelError, KernelResult};
+use core::cell::UnsafeCell;
+
+/// Ring index shared by producer and consumer
+#[cfg(not(feature = "critical-section"))]
+pub(crate) type Index = crate::atomic::IndexAtomic;
+
+/// Ring index shared by producer and consumer (critical-section guarded)
+///
+/// Mirrors the `AtomicUsize` load / store API; the critical section
+/// orders the accesses, so the `Ordering` argument is not needed.
+#[cfg(feature = "critical-section")]
+pub(crate) struct Index(critical_section::Mutex<core::cell::Cell<usize>>);
+
+#[cfg(feature = "critical-section")]
+impl Index {
+    const fn new(value: usize) -> Self {
+        Self(critical_section::Mutex::new(core::cell::Cell::new(value)))
+    }
+}
+
+/// Load / store access to one ring index
+///
+/// [`Indices`] runs the same protocol over the ring's [`Index`], a plain
+/// atomic (rings that stay `const`-constructible under `loom`) or a
+/// cache-padded cell.
+pub(crate) trait IndexCell {
+    fn load(&self, order: Ordering) -> usize;
+    fn store(&self, value: usize, order: Ordering);
+}
+
+impl IndexCell for AtomicUsize {
+    #[inline]
+    fn load(&self, order: Ordering) -> usize {
+        AtomicUsize::load(self, order)
+    }
+
+    #[inline]
+    fn store(&self, value: usize, order: Ordering) {
+        AtomicUsize::store(self, value, order);
+    }
+}
+
+#[cfg(all(loom, not(feature = "critical-section")))]
+impl IndexCell for Index {
+    fn load(&self, order: Ordering) -> usize {
+        Index::load(self, order)
+    }
+
+    fn store(&self, value: usize, order: Ordering) {
+        Index::store(self, value, order);
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl IndexCell for Index {
+    fn load(&self, _order: Ordering) -> usize {
+        critical_section::with(|cs| self.0.borrow(cs).get())
+    }
+
+    fn store(&self, value: usize, _order: Ordering) {
+        critical_section::with(|cs| self.0.borrow(cs).set(value));
+    }
+}
+
+/// Read / write indices of a ring of `N` slots (one kept free)
+///
+/// The producer owns `write` and the consumer owns `read`. A side loads
+/// its own index `Relaxed` and the peer's `Acquire`, and publishes with
+/// `Release` only after the slots are written (producer) or read
+/// (consumer): the consumer never sees a slot before its value, and the
+/// producer never reuses a slot the consumer is still reading.
+pub(crate) struct Indices<const N: usize, I = Index> {
+    /// Next slot to fill (owned by producer)
+    write: I,
+    /// Next slot to drain (owned by consumer)
+    read: I,
+}
+
+impl<const N: usize> Indices<N> {
+    #[cfg(not(loom))]
+    const fn new() -> Self {
+        Self {
+            write: Index::new(0),
+            read: Index::new(0),
+        }
+    }
+
+    #[cfg(loom)]
+    fn new() -> Self {
+        Self {
+            write: Index::new(0),
+            read: Index::new(0),
+        }
+    }
+}
+
+impl<const N: usize, I: IndexCell> Indices<N, I> {
+    /// Indices over the given cells, both holding 0 (empty ring)
+    pub(crate) const fn with_cells(write: I, read: I) -> Self {
+        Self { write, read }
+    }
+
+    /// Producer view: first free slot and number of free slots
+    pub(crate) fn free(&self) -> (usize, usize) {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        (write, (read + N - write - 1) % N)
+    }
+
+    /// Consumer view: oldest queued slot and number of queued slots
+    pub(crate) fn queued(&self) -> (usize, usize) {
+        let read = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        (read, (write + N - read) % N)
+    }
+
+    /// Publish `count` slots filled from `write` on (producer side)
+    pub(crate) fn publish(&self, write: usize, count: usize) {
+        self.write.store((write + count) % N, Ordering::Release);
+    }
+
+    /// Release `count` slots drained from `read` on (consumer side)
+    pub(crate) fn consume(&self, read: usize, count: usize) {
+        self.read.store((read + count) % N, Ordering::Release);
+    }
+
+    /// Queued slots, as seen from either side
+    ///
+    /// Both indices are loaded `Acquire`, so a level observed here is
+    /// never ahead of the slot contents visible to the caller.
+    pub(crate) fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        (write + N - read) % N
+    }
+
+    /// Back to empty (requires exclusive access)
+    fn clear(&self) {
+        self.read.store(0, Ordering::Relaxed);
+        self.write.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Fill-level threshold crossed by a [`SpscRing`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watermark {
+    /// Length rose to the high watermark (a batch is ready)
+    High,
+    /// Length fell back to the low watermark
+    Low,
+}
 
 /// Lock-free SPSC ring buffer
 ///
 /// Fixed-size, no-alloc, interrupt-safe.
 /// Producer and consumer can run on different cores/priorities
 /// without any locking.
+///
+/// The `&mut self` methods need no coordination at all. To share one
+/// ring between a task and an ISR, declare it as a plain `static` and
+/// [`split`](Self::split) it once into a [`SpscProducer`] /
+/// [`SpscConsumer`] pair — never hand out `&mut` to a `static mut`
+/// from two contexts.
 pub struct SpscRing<const N: usize> {
-    /// Ring buffer storage
-    buffer: [u32; N],
-    /// Write index (owned by producer)
-    write_idx: AtomicUsize,
-    /// Read index (owned by consumer)
-    read_idx: AtomicUsize,
+    /// Ring buffer storage (slots are accessed through raw pointers only)
+    buffer: UnsafeCell<[u32; N]>,
+    /// Producer / consumer indices
+    indices: Indices<N>,
+    /// Halves already handed out?
+    split: AtomicBool,
+    /// Values dropped by `push_overwrite` (cumulative)
+    overwritten: u32,
+    /// High watermark (0 = notifications off)
+    high: usize,
+    /// Low watermark (re-arms `High` once reached)
+    low: usize,
+    /// Above the high watermark since the last `Low`?
+    above: AtomicBool,
+    /// Latest crossing not yet taken (`CROSSED_*`)
+    crossed: AtomicU8,
+    /// Called on every crossing
+    on_watermark: Option<fn(Watermark)>,
 }
 
+/// No crossing pending
+const CROSSED_NONE: u8 = 0;
+/// [`Watermark::High`] pending
+const CROSSED_HIGH: u8 = 1;
+/// [`Watermark::Low`] pending
+const CROSSED_LOW: u8 = 2;
+
+// SAFETY: slots are only touched through the `unsafe` enqueue / dequeue
+// methods, whose callers are the unique producer and the unique consumer
+// (either `&mut self` or one half of a one-shot `split`). The producer
+// writes only free slots before publishing them with a Release store;
+// the consumer reads only slots it observed through an Acquire load and
+// hands them back with a Release store. Everything else shared is atomic.
+unsafe impl<const N: usize> Sync for SpscRing<N> {}
+
 impl<const N: usize> Default for SpscRing<N> {
     fn default() -> Self {
         Self::new()
@@ -29,70 +224,303 @@ impl<const N: usize> Default for SpscRing<N> {
 
 impl<const N: usize> SpscRing<N> {
     /// Create a new empty ring buffer
+    #[cfg(not(loom))]
     #[must_use]
     pub const fn new() -> Self {
+        Self::with_indices(Indices::new())
+    }
+
+    /// Create a new empty ring buffer (loom atomics are not `const`)
+    #[cfg(loom)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_indices(Indices::new())
+    }
+
+    const fn with_indices(indices: Indices<N>) -> Self {
         Self {
-            buffer: [0u32; N],
-            write_idx: AtomicUsize::new(0),
-            read_idx: AtomicUsize::new(0),
+            buffer: UnsafeCell::new([0u32; N]),
+            indices,
+            split: AtomicBool::new(false),
+            overwritten: 0,
+            high: 0,
+            low: 0,
+            above: AtomicBool::new(false),
+            crossed: AtomicU8::new(CROSSED_NONE),
+            on_watermark: None,
+        }
+    }
+
+    /// Split into producer and consumer halves
+    ///
+    /// Returns `None` if the ring was already split. Configure the
+    /// watermarks first: that needs `&mut self`.
+    pub fn split(&self) -> Option<(SpscProducer<'_, N>, SpscConsumer<'_, N>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((SpscProducer { ring: self }, SpscConsumer { ring: self }))
+    }
+
+    /// Has the ring been split into halves?
+    pub fn is_split(&self) -> bool {
+        self.split.load(Ordering::Acquire)
+    }
+
+    /// Notify when the length reaches `high` and again when it falls
+    /// back to `low`
+    ///
+    /// The hysteresis between the two avoids repeated notifications while
+    /// the length hovers around one threshold. Returns false (unchanged)
+    /// unless `low < high <= capacity`.
+    pub fn set_watermarks(&mut self, low: usize, high: usize) -> bool {
+        if low >= high || high > self.capacity() {
+            return false;
+        }
+        self.low = low;
+        self.high = high;
+        self.above.store(false, Ordering::Relaxed);
+        self.crossed.store(CROSSED_NONE, Ordering::Relaxed);
+        self.update_watermark();
+        true
+    }
+
+    /// Call `callback` on every watermark crossing
+    ///
+    /// Runs in the context that moved the length (producer or consumer).
+    /// To release a consumer task only when a batch is ready, build it
+    /// with [`Task::released_by_isr`](crate::task::Task::released_by_isr)
+    /// and call [`Scheduler::release_from_isr`](crate::scheduler::Scheduler::release_from_isr)
+    /// from here on [`Watermark::High`].
+    pub fn set_watermark_callback(&mut self, callback: fn(Watermark)) {
+        self.on_watermark = Some(callback);
+    }
+
+    /// Take the latest watermark crossing (event flag for polling)
+    pub fn take_watermark(&mut self) -> Option<Watermark> {
+        self.take_crossing()
+    }
+
+    fn take_crossing(&self) -> Option<Watermark> {
+        match self.crossed.swap(CROSSED_NONE, Ordering::AcqRel) {
+            CROSSED_HIGH => Some(Watermark::High),
+            CROSSED_LOW => Some(Watermark::Low),
+            _ => None,
         }
     }
 
+    /// Detect a watermark crossing after the length changed
+    ///
+    /// Producer and consumer may both get here at once; the swap on
+    /// `above` lets exactly one of them report each crossing.
+    fn update_watermark(&self) {
+        if self.high == 0 {
+            return;
+        }
+        let len = self.len();
+        let crossed = if len >= self.high && !self.above.swap(true, Ordering::AcqRel) {
+            Watermark::High
+        } else if len <= self.low && self.above.swap(false, Ordering::AcqRel) {
+            Watermark::Low
+        } else {
+            return;
+        };
+        let code = match crossed {
+            Watermark::High => CROSSED_HIGH,
+            Watermark::Low => CROSSED_LOW,
+        };
+        self.crossed.store(code, Ordering::Release);
+        if let Some(callback) = self.on_watermark {
+            callback(crossed);
+        }
+    }
+
+    /// Pointer to slot `idx` (`idx < N`)
+    fn slot(&self, idx: usize) -> *mut u32 {
+        debug_assert!(idx < N);
+        self.buffer.get().cast::<u32>().wrapping_add(idx)
+    }
+
     /// Push a value (producer side)
     ///
     /// Returns false if buffer is full.
     pub fn push(&mut self, value: u32) -> bool {
-        let write = self.write_idx.load(Ordering::Relaxed);
-        let read = self.read_idx.load(Ordering::Acquire);
-        let next_write = (write + 1) % N;
+        // SAFETY: &mut self なので生産者は自分だけ
+        unsafe { self.enqueue(value) }
+    }
 
-        if next_write == read {
-            return false; // Full
+    /// Push a value, failing with [`KernelError::QueueFull`]
+    pub fn try_push(&mut self, value: u32) -> KernelResult<()> {
+        if self.push(value) {
+            Ok(())
+        } else {
+            Err(KernelError::QueueFull)
         }
-
-        self.buffer[write] = value;
-        self.write_idx.store(next_write, Ordering::Release);
-        true
     }
 
     /// Pop a value (consumer side)
     ///
     /// Returns None if buffer is empty.
     pub fn pop(&mut self) -> Option<u32> {
-        let read = self.read_idx.load(Ordering::Relaxed);
-        let write = self.write_idx.load(Ordering::Acquire);
+        // SAFETY: &mut self なので消費者は自分だけ
+        unsafe { self.dequeue() }
+    }
+
+    /// Push a value, dropping the oldest one if the buffer is full
+    ///
+    /// Drop-oldest policy for telemetry: the producer never fails. Returns
+    /// true if a value was overwritten (counted in
+    /// [`overwritten`](Self::overwritten)). Moves the consumer's index
+    /// too, so it needs exclusive access and has no split counterpart.
+    pub fn push_overwrite(&mut self, value: u32) -> bool {
+        let (write, free) = self.indices.free();
+        let full = free == 0;
+        if full {
+            // 最古の要素を捨てる (&mut self なので消費側と競合しない)
+            self.indices.consume((write + 1) % N, 1);
+            self.overwritten = self.overwritten.wrapping_add(1);
+        }
+        self.buffer.get_mut()[write] = value;
+        self.indices.publish(write, 1);
+        self.update_watermark();
+        full
+    }
+
+    /// Values dropped by [`push_overwrite`](Self::push_overwrite) so far
+    #[must_use]
+    pub const fn overwritten(&self) -> u32 {
+        self.overwritten
+    }
 
-        if read == write {
+    /// Oldest value without consuming it (consumer side)
+    pub fn peek(&mut self) -> Option<u32> {
+        // SAFETY: &mut self なので消費者は自分だけ
+        unsafe { self.front() }
+    }
+
+    /// Push as many values from `values` as fit (producer side)
+    ///
+    /// Values are published with a single index update. Returns the
+    /// number pushed.
+    pub fn push_slice(&mut self, values: &[u32]) -> usize {
+        // SAFETY: &mut self なので生産者は自分だけ
+        unsafe { self.enqueue_slice(values) }
+    }
+
+    /// Pop up to `out.len()` values into `out` (consumer side)
+    ///
+    /// Drains everything available in one call, e.g. all samples a fast
+    /// producer queued since the consumer's last activation. Returns the
+    /// number popped.
+    pub fn pop_slice(&mut self, out: &mut [u32]) -> usize {
+        // SAFETY: &mut self なので消費者は自分だけ
+        unsafe { self.dequeue_slice(out) }
+    }
+
+    /// Push one value
+    ///
+    /// # Safety
+    ///
+    /// The caller is the only producer for the duration of the call.
+    unsafe fn enqueue(&self, value: u32) -> bool {
+        let (write, free) = self.indices.free();
+        if free == 0 {
+            return false; // Full
+        }
+        // SAFETY: slot `write` is free (the consumer handed it back with
+        // the Release store observed in `free`) and only we write it.
+        unsafe { self.slot(write).write(value) };
+        self.indices.publish(write, 1);
+        self.update_watermark();
+        true
+    }
+
+    /// Push as many values as fit
+    ///
+    /// # Safety
+    ///
+    /// The caller is the only producer for the duration of the call.
+    unsafe fn enqueue_slice(&self, values: &[u32]) -> usize {
+        let (write, free) = self.indices.free();
+        let count = values.len().min(free);
+        // 末尾までと折り返し後の 2 区間に分けてコピー
+        let first = count.min(N - write);
+        // SAFETY: the `count` slots from `write` on (wrapping) are free
+        // and only we write them; the two runs stay inside the buffer.
+        unsafe {
+            core::ptr::copy_nonoverlapping(values.as_ptr(), self.slot(write), first);
+            core::ptr::copy_nonoverlapping(values[first..].as_ptr(), self.slot(0), count - first);
+        }
+        self.indices.publish(write, count);
+        self.update_watermark();
+        count
+    }
+
+    /// Oldest value, left queued
+    ///
+    /// # Safety
+    ///
+    /// The caller is the only consumer for the duration of the call.
+    unsafe fn front(&self) -> Option<u32> {
+        let (read, queued) = self.indices.queued();
+        if queued == 0 {
             return None; // Empty
         }
+        // SAFETY: slot `read` was published by the Release store observed
+        // in `queued`, and the producer won't reuse it until we consume it.
+        Some(unsafe { self.slot(read).read() })
+    }
 
-        let value = self.buffer[read];
-        let next_read = (read + 1) % N;
-        self.read_idx.store(next_read, Ordering::Release);
+    /// Pop one value
+    ///
+    /// # Safety
+    ///
+    /// The caller is the only consumer for the duration of the call.
+    unsafe fn dequeue(&self) -> Option<u32> {
+        let (read, queued) = self.indices.queued();
+        if queued == 0 {
+            return None; // Empty
+        }
+        // SAFETY: see `front`; the slot is handed back only below.
+        let value = unsafe { self.slot(read).read() };
+        self.indices.consume(read, 1);
+        self.update_watermark();
         Some(value)
     }
 
+    /// Pop up to `out.len()` values
+    ///
+    /// # Safety
+    ///
+    /// The caller is the only consumer for the duration of the call.
+    unsafe fn dequeue_slice(&self, out: &mut [u32]) -> usize {
+        let (read, queued) = self.indices.queued();
+        let count = out.len().min(queued);
+        let first = count.min(N - read);
+        // SAFETY: the `count` slots from `read` on (wrapping) are
+        // published and stay ours until the Release store below.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.slot(read), out.as_mut_ptr(), first);
+            core::ptr::copy_nonoverlapping(self.slot(0), out[first..].as_mut_ptr(), count - first);
+        }
+        self.indices.consume(read, count);
+        self.update_watermark();
+        count
+    }
+
     /// Number of items in the buffer
     pub fn len(&self) -> usize {
-        let write = self.write_idx.load(Ordering::Relaxed);
-        let read = self.read_idx.load(Ordering::Relaxed);
-        if write >= read {
-            write - read
-        } else {
-            N - read + write
-        }
+        self.indices.len()
     }
 
     /// Is the buffer empty?
     pub fn is_empty(&self) -> bool {
-        self.write_idx.load(Ordering::Relaxed) == self.read_idx.load(Ordering::Relaxed)
+        self.indices.len() == 0
     }
 
     /// Is the buffer full?
     pub fn is_full(&self) -> bool {
-        let write = self.write_idx.load(Ordering::Relaxed);
-        let read = self.read_idx.load(Ordering::Relaxed);
-        (write + 1) % N == read
+        self.indices.len() == self.capacity()
     }
 
     /// Available capacity
@@ -102,8 +530,95 @@ impl<const N: usize> SpscRing<N> {
 
     /// Clear the buffer
     pub fn clear(&mut self) {
-        self.read_idx.store(0, Ordering::Relaxed);
-        self.write_idx.store(0, Ordering::Relaxed);
+        self.indices.clear();
+        self.update_watermark();
+    }
+}
+
+/// Producer half of a split [`SpscRing`]
+pub struct SpscProducer<'a, const N: usize> {
+    ring: &'a SpscRing<N>,
+}
+
+impl<const N: usize> SpscProducer<'_, N> {
+    /// Push a value
+    ///
+    /// Returns false if the ring is full.
+    pub fn push(&mut self, value: u32) -> bool {
+        // SAFETY: split は一度きりなのでこの半分が唯一の生産者
+        unsafe { self.ring.enqueue(value) }
+    }
+
+    /// Push a value, failing with [`KernelError::QueueFull`]
+    pub fn try_push(&mut self, value: u32) -> KernelResult<()> {
+        if self.push(value) {
+            Ok(())
+        } else {
+            Err(KernelError::QueueFull)
+        }
+    }
+
+    /// Push as many values from `values` as fit; returns the number pushed
+    pub fn push_slice(&mut self, values: &[u32]) -> usize {
+        // SAFETY: split は一度きりなのでこの半分が唯一の生産者
+        unsafe { self.ring.enqueue_slice(values) }
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Is the ring empty?
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Is the ring full?
+    pub fn is_full(&self) -> bool {
+        self.ring.is_full()
+    }
+}
+
+/// Consumer half of a split [`SpscRing`]
+pub struct SpscConsumer<'a, const N: usize> {
+    ring: &'a SpscRing<N>,
+}
+
+impl<const N: usize> SpscConsumer<'_, N> {
+    /// Pop the oldest value
+    ///
+    /// Returns `None` if the ring is empty.
+    pub fn pop(&mut self) -> Option<u32> {
+        // SAFETY: split は一度きりなのでこの半分が唯一の消費者
+        unsafe { self.ring.dequeue() }
+    }
+
+    /// Pop up to `out.len()` values into `out`; returns the number popped
+    pub fn pop_slice(&mut self, out: &mut [u32]) -> usize {
+        // SAFETY: split は一度きりなのでこの半分が唯一の消費者
+        unsafe { self.ring.dequeue_slice(out) }
+    }
+
+    /// Oldest value without consuming it
+    pub fn peek(&mut self) -> Option<u32> {
+        // SAFETY: split は一度きりなのでこの半分が唯一の消費者
+        unsafe { self.ring.front() }
+    }
+
+    /// Take the latest watermark crossing
+    pub fn take_watermark(&mut self) -> Option<Watermark> {
+        self.ring.take_crossing()
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Is the ring empty?
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
     }
 }
 
@@ -230,6 +745,17 @@ mod tests {
         assert!(!ring.push(99)); // 2個目は入らない
     }
 
+    #[test]
+    fn test_try_push_reports_full() {
+        let ring = SpscRing::<2>::new();
+        let (mut tx, _rx) = ring.split().unwrap();
+        assert_eq!(tx.try_push(1), Ok(()));
+        assert_eq!(tx.try_push(2), Err(KernelError::QueueFull));
+        let mut ring = SpscRing::<2>::new();
+        assert_eq!(ring.try_push(1), Ok(()));
+        assert_eq!(ring.try_push(2), Err(KernelError::QueueFull));
+    }
+
     #[test]
     fn test_single_slot_buffer() {
         let mut ring = SpscRing::<2>::new();
@@ -322,4 +848,330 @@ mod tests {
                       // read=2, write=0 → write < read → N - read + write = 4 - 2 + 0 = 2
         assert_eq!(ring.len(), 2);
     }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_ring_shareable_with_isr() {
+        // 割り込みと共有できるよう static に置ける (どちらの Index 実装でも)
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<SpscRing<8>>();
+        static mut RING: SpscRing<4> = SpscRing::new();
+        // SAFETY: このテストだけが RING に触る
+        let ring = unsafe { &mut *core::ptr::addr_of_mut!(RING) };
+        assert!(ring.push(7));
+        assert_eq!(ring.pop(), Some(7));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_split_static_ring() {
+        // static mut なしで ISR と共有する
+        static RING: SpscRing<4> = SpscRing::new();
+        let (mut tx, mut rx) = RING.split().unwrap();
+        assert!(RING.is_split());
+        assert!(RING.split().is_none());
+        assert!(tx.push(1));
+        assert_eq!(tx.push_slice(&[2, 3, 4]), 2);
+        assert!(tx.is_full());
+        assert_eq!(rx.peek(), Some(1));
+        let mut out = [0u32; 4];
+        assert_eq!(rx.pop_slice(&mut out), 3);
+        assert_eq!(out[..3], [1, 2, 3]);
+        assert_eq!(rx.pop(), None);
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_split_halves_across_threads() {
+        // Miri では件数を減らす (データ競合があれば Miri が検出する)
+        const ITEMS: u32 = if cfg!(miri) { 64 } else { 20_000 };
+        let ring = SpscRing::<8>::new();
+        let (mut tx, mut rx) = ring.split().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                let mut next = 0;
+                while next < ITEMS {
+                    // 単発と一括を交互に使い、折り返しも通す
+                    let pushed = if next % 2 == 0 {
+                        usize::from(tx.push(next))
+                    } else {
+                        let batch = [next, next + 1, next + 2];
+                        let n = batch.len().min((ITEMS - next) as usize);
+                        tx.push_slice(&batch[..n])
+                    };
+                    next += pushed as u32;
+                    if pushed == 0 {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let mut expected = 0;
+            let mut out = [0u32; 5];
+            while expected < ITEMS {
+                let n = rx.pop_slice(&mut out);
+                for &v in &out[..n] {
+                    assert_eq!(v, expected);
+                    expected += 1;
+                }
+                if n == 0 {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_split_consumer_sees_watermarks() {
+        let mut ring = SpscRing::<8>::new();
+        ring.set_watermarks(1, 4);
+        let (mut tx, mut rx) = ring.split().unwrap();
+        tx.push_slice(&[1, 2, 3, 4]);
+        assert_eq!(rx.take_watermark(), Some(Watermark::High));
+        assert_eq!(rx.take_watermark(), None);
+        rx.pop_slice(&mut [0u32; 3]);
+        assert_eq!(rx.take_watermark(), Some(Watermark::Low));
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let mut ring = SpscRing::<4>::new();
+        assert_eq!(ring.peek(), None);
+        ring.push(5);
+        ring.push(6);
+        assert_eq!(ring.peek(), Some(5));
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.pop(), Some(5));
+        assert_eq!(ring.peek(), Some(6));
+    }
+
+    #[test]
+    fn test_push_slice_partial_when_full() {
+        let mut ring = SpscRing::<8>::new();
+        assert_eq!(ring.push_slice(&[1, 2, 3, 4, 5]), 5);
+        // 残り容量 2 だけ書き込む
+        assert_eq!(ring.push_slice(&[6, 7, 8, 9]), 2);
+        assert!(ring.is_full());
+        assert_eq!(ring.push_slice(&[10]), 0);
+        for i in 1..=7 {
+            assert_eq!(ring.pop(), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_pop_slice_across_wrap() {
+        let mut ring = SpscRing::<8>::new();
+        for i in 0..6 {
+            ring.push(i);
+        }
+        let mut out = [0u32; 4];
+        assert_eq!(ring.pop_slice(&mut out), 4);
+        assert_eq!(out, [0, 1, 2, 3]);
+        // write=6 → 末尾を跨いで 3 つ追加
+        assert_eq!(ring.push_slice(&[6, 7, 8, 9, 10]), 5);
+        let mut out = [0u32; 16];
+        assert_eq!(ring.pop_slice(&mut out), 7);
+        assert_eq!(&out[..7], &[4, 5, 6, 7, 8, 9, 10]);
+        assert!(ring.is_empty());
+        assert_eq!(ring.pop_slice(&mut out), 0);
+    }
+
+    #[test]
+    fn test_drain_audio_rate_into_control_rate() {
+        // 44.1 kHz 生産者 → 1 kHz 消費者: 1 ms あたり 44 サンプルを一括取得
+        let mut ring = SpscRing::<64>::new();
+        let mut out = [0u32; 64];
+        let mut next = 0u32;
+        for _ in 0..10 {
+            for _ in 0..44 {
+                assert!(ring.push(next));
+                next += 1;
+            }
+            let n = ring.pop_slice(&mut out);
+            assert_eq!(n, 44);
+            assert_eq!(out[43], next - 1);
+        }
+    }
+
+    #[test]
+    fn test_push_overwrite_drops_oldest() {
+        let mut ring = SpscRing::<4>::new();
+        assert!(!ring.push_overwrite(1));
+        assert!(!ring.push_overwrite(2));
+        assert!(!ring.push_overwrite(3));
+        assert_eq!(ring.overwritten(), 0);
+        // 満杯: 1 と 2 が捨てられる
+        assert!(ring.push_overwrite(4));
+        assert!(ring.push_overwrite(5));
+        assert_eq!(ring.overwritten(), 2);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.pop(), Some(3));
+        assert_eq!(ring.pop(), Some(4));
+        assert_eq!(ring.pop(), Some(5));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn test_watermarks_with_hysteresis() {
+        let mut ring = SpscRing::<16>::new();
+        assert!(!ring.set_watermarks(8, 8));
+        assert!(!ring.set_watermarks(2, 16)); // 容量 15 を超える
+        assert!(ring.set_watermarks(2, 8));
+
+        assert_eq!(ring.push_slice(&[0; 7]), 7);
+        assert_eq!(ring.take_watermark(), None);
+        ring.push(7);
+        assert_eq!(ring.take_watermark(), Some(Watermark::High));
+        assert_eq!(ring.take_watermark(), None);
+        // 8 付近を上下しても Low まで再通知しない
+        ring.pop();
+        ring.push(8);
+        assert_eq!(ring.take_watermark(), None);
+
+        let mut out = [0u32; 5];
+        assert_eq!(ring.pop_slice(&mut out), 5);
+        assert_eq!(ring.take_watermark(), None);
+        ring.pop();
+        assert_eq!(ring.take_watermark(), Some(Watermark::Low));
+    }
+
+    #[test]
+    fn test_watermark_releases_batch_consumer() {
+        use crate::scheduler::Scheduler;
+        use crate::task::{Task, TaskPriority};
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static HIGHS: AtomicU32 = AtomicU32::new(0);
+        fn on_watermark(mark: Watermark) {
+            if mark == Watermark::High {
+                HIGHS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        fn consumer(_: &mut [u8]) {}
+
+        let mut sched = Scheduler::new();
+        let idx = sched
+            .register(Task::new(b"batch", consumer, TaskPriority::LOW, 1_000, 10).released_by_isr())
+            .unwrap();
+        let mut ring = SpscRing::<64>::new();
+        ring.set_watermarks(0, 32);
+        ring.set_watermark_callback(on_watermark);
+
+        // 生産者: 32 個溜まった時点でバッチ消費タスクを解放
+        let mut runs = 0;
+        for sample in 0..96 {
+            ring.push(sample);
+            if ring.take_watermark() == Some(Watermark::High) {
+                sched.release_from_isr(idx);
+            }
+            if sched.tick(10) == Some(idx) {
+                runs += 1;
+                let mut out = [0u32; 64];
+                assert_eq!(ring.pop_slice(&mut out), 32);
+            }
+        }
+        assert_eq!(runs, 3);
+        assert_eq!(HIGHS.load(Ordering::Relaxed), 3);
+    }
+}
+
+/// Model checking of the index protocol (`RUSTFLAGS="--cfg loom"`)
+#[cfg(all(test, loom, not(feature = "critical-section")))]
+mod loom_tests {
+    use super::Indices;
+    use loom::cell::UnsafeCell;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    /// Indices plus loom-tracked slots, shared by two threads
+    struct Shared<const N: usize> {
+        indices: Indices<N>,
+        slots: [UnsafeCell<u32>; N],
+    }
+
+    // SAFETY: Indices がスロットへのアクセスを排他にする (それを検査する)
+    unsafe impl<const N: usize> Sync for Shared<N> {}
+
+    impl<const N: usize> Shared<N> {
+        fn new() -> Self {
+            Self {
+                indices: Indices::new(),
+                slots: core::array::from_fn(|_| UnsafeCell::new(0)),
+            }
+        }
+
+        /// `SpscRing::enqueue_slice` の手順
+        fn push_slice(&self, values: &[u32]) -> usize {
+            let (write, free) = self.indices.free();
+            let count = values.len().min(free);
+            for (i, &v) in values[..count].iter().enumerate() {
+                self.slots[(write + i) % N].with_mut(|p| unsafe { *p = v });
+            }
+            self.indices.publish(write, count);
+            count
+        }
+
+        /// `SpscRing::dequeue_slice` の手順
+        fn pop_slice(&self, out: &mut [u32]) -> usize {
+            let (read, queued) = self.indices.queued();
+            let count = out.len().min(queued);
+            for (i, o) in out[..count].iter_mut().enumerate() {
+                *o = self.slots[(read + i) % N].with(|p| unsafe { *p });
+            }
+            self.indices.consume(read, count);
+            count
+        }
+    }
+
+    /// Producer pushes `values` while the consumer drains them
+    ///
+    /// The spin loops make the state space unbounded, so exploration is
+    /// limited to two preemptions (enough to cover every pair of
+    /// racing index accesses).
+    fn run<const N: usize>(values: &'static [u32], batch: usize) {
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.check(move || {
+            let ring = Arc::new(Shared::<N>::new());
+            let producer = {
+                let ring = ring.clone();
+                thread::spawn(move || {
+                    let mut sent = 0;
+                    while sent < values.len() {
+                        let end = (sent + batch).min(values.len());
+                        match ring.push_slice(&values[sent..end]) {
+                            0 => thread::yield_now(),
+                            n => sent += n,
+                        }
+                    }
+                })
+            };
+            let mut got = [0u32; 8];
+            let mut received = 0;
+            while received < values.len() {
+                let end = (received + batch).min(values.len());
+                match ring.pop_slice(&mut got[received..end]) {
+                    0 => thread::yield_now(),
+                    n => received += n,
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(&got[..received], values);
+            assert_eq!(ring.indices.len(), 0);
+        });
+    }
+
+    #[test]
+    fn loom_push_pop_reuses_slots() {
+        // 容量 1: 毎回同じスロットを再利用する
+        run::<2>(&[1, 2, 3], 1);
+    }
+
+    #[test]
+    fn loom_slices_wrap_around() {
+        // 容量 2 に 2 個ずつ: 折り返しを含む一括公開
+        run::<3>(&[1, 2, 3, 4], 2);
+    }
 }
@@ -0,0 +1,191 @@
+//! Kernel shared between the main loop and interrupt handlers
+//! (`static-kernel` feature)
+//!
+//! Firmware keeps the kernel in a `static` that the tick interrupt and
+//! the main loop both touch. [`StaticKernel`] is the supported way to do
+//! that without `static mut`: every access goes through
+//! [`StaticKernel::with`], which runs the closure inside a
+//! `critical-section` with exclusive access to the [`Kernel`].
+//!
+//! Interrupt-driven releases take a lock-free fast path:
+//! [`StaticKernel::release_from_isr`] only sets a bit in an atomic mask
+//! next to the kernel, which the next `with` (or [`StaticKernel::tick`])
+//! hands to [`Scheduler::release_from_isr`](crate::scheduler::Scheduler::release_from_isr)
+//! before running its closure. A high-rate audio or DMA interrupt
+//! therefore never waits for — or masks interrupts around — a tick.
+//!
+//! `tick` executes the dispatched job inside the critical section. Keep
+//! job bodies short when ticking from an interrupt, or tick from the main
+//! loop and let the interrupt only release.
+//!
+//! ```rust
+//! use alice_rtos::static_kernel::StaticKernel;
+//! use alice_rtos::{Kernel, Task, TaskPriority};
+//!
+//! fn audio(_: &mut [u8]) {}
+//!
+//! static KERNEL: StaticKernel = StaticKernel::new(Kernel::testing());
+//!
+//! let idx = KERNEL.with(|k| {
+//!     let task = Task::new(b"audio", audio, TaskPriority::CRITICAL, 0, 10).released_by_isr();
+//!     k.scheduler.register(task).unwrap()
+//! });
+//!
+//! // DMA half-transfer interrupt
+//! assert!(KERNEL.release_from_isr(idx));
+//! // SysTick interrupt
+//! assert_eq!(KERNEL.tick(100), Some(idx));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::atomic::{AtomicU16, Ordering};
+use crate::kernel::Kernel;
+use crate::task::{TaskMask, MAX_TASKS};
+
+/// [`Kernel`] in a `static`, shared by the main loop and interrupts
+pub struct StaticKernel {
+    /// The kernel, borrowed inside a critical section
+    kernel: Mutex<RefCell<Kernel>>,
+    /// Interrupt releases not yet handed to the scheduler
+    isr_pending: AtomicU16,
+}
+
+impl StaticKernel {
+    /// Wrap `kernel` (usable in `static`s)
+    #[must_use]
+    pub const fn new(kernel: Kernel) -> Self {
+        Self {
+            kernel: Mutex::new(RefCell::new(kernel)),
+            isr_pending: AtomicU16::new(0),
+        }
+    }
+
+    /// Run `f` with exclusive access to the kernel
+    ///
+    /// Interrupt releases recorded so far are applied first.
+    ///
+    /// # Panics
+    ///
+    /// When called from inside another `with` closure (re-entry); use
+    /// [`try_with`](Self::try_with) where that can happen.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Kernel) -> R) -> R {
+        critical_section::with(|cs| {
+            let mut kernel = self.kernel.borrow_ref_mut(cs);
+            self.apply_releases(&kernel);
+            f(&mut kernel)
+        })
+    }
+
+    /// Like [`with`](Self::with), but `None` instead of panicking on re-entry
+    pub fn try_with<R>(&self, f: impl FnOnce(&mut Kernel) -> R) -> Option<R> {
+        critical_section::with(|cs| {
+            let mut kernel = self.kernel.borrow(cs).try_borrow_mut().ok()?;
+            self.apply_releases(&kernel);
+            Some(f(&mut kernel))
+        })
+    }
+
+    /// Run one kernel tick (call from the tick interrupt)
+    pub fn tick(&self, delta_us: u64) -> Option<usize> {
+        self.with(|k| k.tick(delta_us))
+    }
+
+    /// Release task `idx` from an interrupt handler
+    ///
+    /// Lock-free: never enters a critical section, so it is safe from any
+    /// interrupt priority. Returns false for an invalid index.
+    pub fn release_from_isr(&self, idx: usize) -> bool {
+        if idx >= MAX_TASKS {
+            return false;
+        }
+        self.isr_pending.fetch_or(1 << idx, Ordering::Release);
+        true
+    }
+
+    /// Interrupt releases not yet handed to the scheduler
+    #[must_use]
+    pub fn isr_pending(&self) -> TaskMask {
+        self.isr_pending.load(Ordering::Acquire)
+    }
+
+    /// Hand the recorded interrupt releases to the scheduler
+    fn apply_releases(&self, kernel: &Kernel) {
+        let mut pending = self.isr_pending.swap(0, Ordering::Acquire);
+        while pending != 0 {
+            let idx = pending.trailing_zeros() as usize;
+            kernel.scheduler.release_from_isr(idx);
+            pending &= pending - 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Task, TaskPriority};
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_with_and_reentry() {
+        static KERNEL: StaticKernel = StaticKernel::new(Kernel::testing());
+        let idx = KERNEL.with(|k| k.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 100));
+        assert_eq!(idx, Some(0));
+        assert_eq!(KERNEL.tick(100), Some(0));
+        // 入れ子のアクセスは try_with で検出できる
+        let nested = KERNEL.with(|_| KERNEL.try_with(|k| k.timer.now_us()));
+        assert_eq!(nested, None);
+        assert_eq!(KERNEL.try_with(|k| k.timer.now_us()), Some(100));
+    }
+
+    #[test]
+    fn test_isr_release_fast_path() {
+        static KERNEL: StaticKernel = StaticKernel::new(Kernel::testing());
+        let idx = KERNEL.with(|k| {
+            let task = Task::new(b"dma", noop, TaskPriority::CRITICAL, 0, 10).released_by_isr();
+            k.scheduler.register(task).unwrap()
+        });
+        assert!(!KERNEL.release_from_isr(MAX_TASKS));
+        assert_eq!(KERNEL.tick(100), None);
+
+        // 割り込み側はマスクに記録するだけ、次のアクセスで反映
+        assert!(KERNEL.release_from_isr(idx));
+        assert_eq!(KERNEL.isr_pending(), 1 << idx);
+        assert_eq!(KERNEL.tick(100), Some(idx));
+        assert_eq!(KERNEL.isr_pending(), 0);
+    }
+
+    #[test]
+    fn test_isr_releases_from_another_thread() {
+        static KERNEL: StaticKernel = StaticKernel::new(Kernel::testing());
+        let idx = KERNEL.with(|k| {
+            let task = Task::new(b"dma", noop, TaskPriority::CRITICAL, 0, 10).released_by_isr();
+            k.scheduler.register(task).unwrap()
+        });
+        let isr = std::thread::spawn(move || {
+            for _ in 0..200 {
+                // 前のリリースが処理されるまで待つ (合流を避ける)
+                while KERNEL.isr_pending() != 0 {
+                    std::thread::yield_now();
+                }
+                KERNEL.release_from_isr(idx);
+            }
+        });
+        let mut runs = 0;
+        while runs < 200 {
+            if KERNEL.tick(10).is_some() {
+                runs += 1;
+            }
+        }
+        isr.join().unwrap();
+        assert_eq!(
+            KERNEL.with(|k| k.scheduler.get_task(idx).unwrap().exec_count),
+            200
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Periodic statistics export task with a user sink
+//!
+//! A low-priority task built with [`stats_task`] exports a
+//! [`KernelStats`] snapshot (kernel counters and the per-task table) at
+//! its own period: tick the kernel through [`StatsExport::tick`], which
+//! captures the snapshot when the task is released, and call
+//! [`StatsExport::export`] from the task body, which encodes it as a
+//! [`telemetry`](crate::telemetry) frame in the task's scratch and hands
+//! the frame to a user `fn(&[u8])` sink (UART, RTT, a channel, …).
+//! Capturing is a copy; encoding and the sink run on the task's budget.
+//!
+//! [`StatsMode::Cumulative`] exports the counters since boot,
+//! [`StatsMode::Delta`] the counters accumulated since the previous
+//! snapshot ([`KernelStats::since`]). A snapshot captured while the
+//! previous one is still unexported replaces it and is counted as
+//! [`skipped`](StatsExport::skipped).
+//!
+//! ```ignore
+//! const STATS: usize = 3;
+//! static EXPORT: StatsExport = StatsExport::new(STATS, uart_write).with_mode(StatsMode::Delta);
+//!
+//! fn export(scratch: &mut [u8]) {
+//!     EXPORT.export(scratch);
+//! }
+//!
+//! // Once per second; the scratch must hold a frame (`MAX_STATS_LEN`)
+//! kernel.scheduler.register(stats_task(export, 1_000_000, 500));
+//! loop {
+//!     EXPORT.tick(&mut kernel, 1_000);
+//! }
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::UnsafeCell;
+
+use crate::atomic::{AtomicU32, AtomicU8, Ordering};
+use crate::kernel::{KernelStats, SizedKernel};
+use crate::policy::SchedulingPolicy;
+use crate::task::{Task, TaskFn, TaskPriority, TaskState, MAX_TASKS};
+use crate::timer::{tick_reached, Tick};
+
+/// Priority of the export task
+pub const STATS_PRIORITY: TaskPriority = TaskPriority::LOW;
+
+/// Snapshot slot states
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+const READING: u8 = 3;
+
+/// Export task: `export` runs every `period_us` at [`STATS_PRIORITY`]
+///
+/// `wcet_us` must cover encoding one frame and the sink call.
+pub fn stats_task(export: TaskFn, period_us: u32, wcet_us: u32) -> Task {
+    Task::new(b"stats", export, STATS_PRIORITY, period_us, wcet_us)
+}
+
+/// What an exported snapshot counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsMode {
+    /// Counters since boot
+    Cumulative,
+    /// Counters since the previous snapshot
+    Delta,
+}
+
+/// Snapshot handover between the kernel tick and the export task
+/// (declare as a `static`)
+pub struct StatsExport {
+    /// Slot of the export task
+    task: usize,
+    /// Frame output
+    sink: fn(&[u8]),
+    /// Cumulative or delta counters
+    mode: StatsMode,
+    /// `EMPTY` / `WRITING` / `READY` / `READING`
+    state: AtomicU8,
+    /// Snapshot waiting for the export task
+    snapshot: UnsafeCell<Option<KernelStats>>,
+    /// Cumulative counters of the previous snapshot (delta mode)
+    previous: UnsafeCell<Option<KernelStats>>,
+    /// Frames handed to the sink
+    exported: AtomicU32,
+    /// Snapshots replaced before export, or captured during one
+    skipped: AtomicU32,
+}
+
+// SAFETY: `snapshot` and `previous` are only written by the holder of the
+// `WRITING` state and `snapshot` only read by the holder of `READING`,
+// both claimed by compare-exchange.
+unsafe impl Sync for StatsExport {}
+
+impl StatsExport {
+    /// Export through `sink` from the task in slot `task` (cumulative)
+    #[must_use]
+    pub const fn new(task: usize, sink: fn(&[u8])) -> Self {
+        Self {
+            task,
+            sink,
+            mode: StatsMode::Cumulative,
+            state: AtomicU8::new(EMPTY),
+            snapshot: UnsafeCell::new(None),
+            previous: UnsafeCell::new(None),
+            exported: AtomicU32::new(0),
+            skipped: AtomicU32::new(0),
+        }
+    }
+
+    /// Export cumulative or delta counters
+    #[must_use]
+    pub const fn with_mode(mut self, mode: StatsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Tick the kernel, capturing a snapshot when the export task is due
+    ///
+    /// The snapshot is taken at the start of the tick that releases the
+    /// task (for a task already ready without one, of the next tick).
+    /// Returns the task executed, if any.
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        delta_us: u64,
+    ) -> Option<usize> {
+        let at = kernel.scheduler.now_us().wrapping_add(delta_us as Tick);
+        let due = kernel.scheduler.get_task(self.task).is_some_and(|t| {
+            (t.state == TaskState::Sleeping && tick_reached(at, t.next_activation))
+                || (t.state == TaskState::Ready && self.state.load(Ordering::Relaxed) == EMPTY)
+        });
+        if due {
+            self.capture(kernel);
+        }
+        kernel.tick(delta_us)
+    }
+
+    /// Encode the pending snapshot into `buf` and hand it to the sink
+    ///
+    /// Call from the export task body with its scratch. Returns the frame
+    /// length, or 0 if no snapshot is pending or `buf` is shorter than
+    /// the frame (the snapshot is then dropped and counted as skipped).
+    pub fn export(&self, buf: &mut [u8]) -> usize {
+        if self
+            .state
+            .compare_exchange(READY, READING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return 0;
+        }
+        // SAFETY: READING を保持している間はこのスナップショットを他が触らない
+        let len = unsafe { &*self.snapshot.get() }
+            .as_ref()
+            .map_or(0, |stats| stats.encode(buf));
+        self.state.store(EMPTY, Ordering::Release);
+        if len == 0 {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            return 0;
+        }
+        (self.sink)(&buf[..len]);
+        self.exported.fetch_add(1, Ordering::Relaxed);
+        len
+    }
+
+    /// Frames handed to the sink
+    #[must_use]
+    pub fn exported(&self) -> u32 {
+        self.exported.load(Ordering::Relaxed)
+    }
+
+    /// Snapshots lost: replaced before export, captured during one, or
+    /// too large for the export buffer
+    #[must_use]
+    pub fn skipped(&self) -> u32 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Store a snapshot of `kernel` for the export task
+    fn capture<const SCRATCH: usize, P: SchedulingPolicy>(&self, kernel: &SizedKernel<SCRATCH, P>) {
+        if !self.claim(EMPTY) {
+            // 未送出のスナップショットは置き換え、書き出し中なら見送る
+            let replaced = self.claim(READY);
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            if !replaced {
+                return;
+            }
+        }
+        let jobs = (0..MAX_TASKS)
+            .map_while(|i| kernel.scheduler.get_task(i))
+            .map(|t| u64::from(t.exec_count))
+            .sum();
+        let now = kernel.stats(kernel.timer.now_us(), jobs);
+        // SAFETY: WRITING を保持している
+        let (snapshot, previous) =
+            unsafe { (&mut *self.snapshot.get(), &mut *self.previous.get()) };
+        *snapshot = Some(if self.mode == StatsMode::Delta {
+            let delta = previous
+                .as_ref()
+                .map_or_else(|| now.clone(), |before| now.since(before));
+            *previous = Some(now);
+            delta
+        } else {
+            now
+        });
+        self.state.store(READY, Ordering::Release);
+    }
+
+    /// Take the slot for writing if it is in state `from`
+    fn claim(&self, from: u8) -> bool {
+        self.state
+            .compare_exchange(from, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::AtomicUsize;
+    use crate::kernel::Kernel;
+
+    const STATS: usize = 1;
+    static FRAME: [AtomicU8; 128] = [const { AtomicU8::new(0) }; 128];
+    static FRAME_LEN: AtomicUsize = AtomicUsize::new(0);
+    static EXPORT: StatsExport = StatsExport::new(STATS, keep).with_mode(StatsMode::Delta);
+
+    fn keep(frame: &[u8]) {
+        for (slot, &b) in FRAME.iter().zip(frame) {
+            slot.store(b, Ordering::Relaxed);
+        }
+        FRAME_LEN.store(frame.len(), Ordering::Relaxed);
+    }
+
+    fn noop(_: &mut [u8]) {}
+
+    fn export(scratch: &mut [u8]) {
+        EXPORT.export(scratch);
+    }
+
+    #[test]
+    fn test_delta_frames_at_task_period() {
+        let mut k = Kernel::testing();
+        k.add_task(b"ctl", noop, TaskPriority::HIGH, 1_000, 10);
+        assert_eq!(
+            k.scheduler.register(stats_task(export, 5_000, 100)),
+            Some(STATS)
+        );
+        k.tick(0);
+        for _ in 0..40 {
+            EXPORT.tick(&mut k, 500);
+        }
+        assert_eq!(EXPORT.exported(), 4);
+        assert_eq!(EXPORT.skipped(), 0);
+        assert!(FRAME_LEN.load(Ordering::Relaxed) > 0);
+        assert_eq!(
+            FRAME[0].load(Ordering::Relaxed),
+            crate::telemetry::STATS_WIRE_VERSION
+        );
+
+        #[cfg(feature = "std")]
+        {
+            let len = FRAME_LEN.load(Ordering::Relaxed);
+            let bytes: std::vec::Vec<u8> = FRAME[..len]
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .collect();
+            let stats = KernelStats::decode(&bytes).unwrap();
+            // 直前のスナップショットからの 5 ms 分
+            assert_eq!(stats.total_us, 5_000);
+            assert_eq!(stats.per_task()[0].exec_count, 5);
+            assert_eq!(stats.per_task()[1].exec_count, 1);
+        }
+    }
+
+    #[test]
+    fn test_replaced_and_oversized_snapshots_are_skipped() {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        fn count(_: &[u8]) {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+        let export = StatsExport::new(0, count);
+        let mut k = Kernel::testing();
+        k.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 10);
+
+        assert_eq!(export.export(&mut [0; 256]), 0);
+        export.capture(&k);
+        export.capture(&k);
+        assert_eq!(export.skipped(), 1);
+        assert_eq!(export.export(&mut [0; 4]), 0);
+        assert_eq!(export.skipped(), 2);
+        export.capture(&k);
+        assert!(export.export(&mut [0; 256]) > 0);
+        assert_eq!((export.exported(), COUNT.load(Ordering::Relaxed)), (1, 1));
+    }
+}
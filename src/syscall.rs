@@ -0,0 +1,528 @@
+//! Unprivileged tasks and SVC kernel calls (`mpu` feature)
+//!
+//! A task built with [`Task::unprivileged`](crate::task::Task::unprivileged)
+//! runs with `CONTROL.nPRIV` set: together with its [`Mpu`](crate::mpu::Mpu)
+//! regions it can touch only its own stack, scratch and granted buffers,
+//! not the kernel. Kernel services go through `SVC` instead — the thin
+//! wrappers in this module ([`suspend`], [`resume`], [`release`], [`send`],
+//! [`recv`]) trap into the handler, which validates the arguments and
+//! answers from a [`Syscalls`] table.
+//!
+//! Task-state requests are only recorded by the handler (the kernel is
+//! busy running the caller) and applied after the job by
+//! [`Syscalls::tick`] / [`apply`](Syscalls::apply). Channel operations
+//! are answered at once through the table's [`Port`]s, e.g. a
+//! [`Mailbox<u32>`].
+//!
+//! When the job returns, the dispatcher traps with [`SYS_RETURN`] to get
+//! its privilege back. The handler only grants it to the dispatcher's own
+//! return point at the stack depth recorded before privilege was dropped,
+//! so a task cannot raise itself. Jobs must be dispatched from thread
+//! mode (handler mode is always privileged), and the task's stack region
+//! must end below the dispatcher's frame.
+//!
+//! ```ignore
+//! static PORTS: [&dyn Port; 1] = [&SETPOINT];
+//! static SYSCALLS: Syscalls = Syscalls::new(&PORTS);
+//!
+//! fn filter(_: &mut [u8]) {
+//!     if syscall::send(0, 1_250).is_err() { /* port full */ }
+//!     let _ = syscall::release(LOGGER);
+//! }
+//!
+//! syscall::install(&SYSCALLS);
+//! // Link with `--defsym=SVCall=alice_rtos_svcall` (cortex-m-rt)
+//! kernel.try_add(Task::new(b"filter", filter, TaskPriority::HIGH, 1_000, 50).unprivileged())?;
+//! loop { SYSCALLS.tick(&mut kernel, 100); }
+//! ```
+//!
+//! Off target (host builds) the wrappers call the installed table
+//! directly, so the same task code runs in tests and simulation.
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicPtr, AtomicU16, AtomicU32, AtomicUsize, Ordering};
+use crate::error::{KernelError, KernelResult};
+use crate::kernel::{Kernel, KernelStats, SizedKernel};
+use crate::mailbox::Mailbox;
+use crate::policy::SchedulingPolicy;
+use crate::task::{TaskMask, MAX_TASKS};
+
+/// Dispatcher regains privilege after a job (kernel only)
+pub const SYS_RETURN: u8 = 0;
+/// Suspend task `r0`
+pub const SYS_SUSPEND: u8 = 1;
+/// Resume task `r0`
+pub const SYS_RESUME: u8 = 2;
+/// Release task `r0` (as from an interrupt)
+pub const SYS_RELEASE: u8 = 3;
+/// Send word `r1` to port `r0`
+pub const SYS_SEND: u8 = 4;
+/// Receive a word from port `r0` (into `r1`)
+pub const SYS_RECV: u8 = 5;
+
+/// Call succeeded (`r0`)
+pub const SYS_OK: u32 = 0;
+/// Port full or empty (`r0`)
+pub const SYS_WOULD_BLOCK: u32 = 1;
+/// Unknown call, task or port (`r0`)
+pub const SYS_INVALID: u32 = 2;
+
+/// Channel endpoint reachable through [`SYS_SEND`] / [`SYS_RECV`]
+pub trait Port: Sync {
+    /// Accept `word` (false if full)
+    fn send(&self, word: u32) -> bool;
+
+    /// Next word, if any
+    fn recv(&self) -> Option<u32>;
+}
+
+/// Latest-value port: sends overwrite, receives return the newest word
+impl Port for Mailbox<u32> {
+    fn send(&self, word: u32) -> bool {
+        self.write(word);
+        true
+    }
+
+    fn recv(&self) -> Option<u32> {
+        self.read()
+    }
+}
+
+/// Kernel side of the system calls: ports and deferred task requests
+pub struct Syscalls {
+    /// Ports addressed by index
+    ports: &'static [&'static dyn Port],
+    /// Tasks to suspend after the current job
+    suspend: AtomicU16,
+    /// Tasks to resume after the current job
+    resume: AtomicU16,
+    /// Tasks to release after the current job
+    release: AtomicU16,
+    /// Calls refused (bad number, index or privilege request)
+    rejected: AtomicU32,
+}
+
+impl Syscalls {
+    /// Table over `ports` (usable in `static` initializers)
+    #[must_use]
+    pub const fn new(ports: &'static [&'static dyn Port]) -> Self {
+        Self {
+            ports,
+            suspend: AtomicU16::new(0),
+            resume: AtomicU16::new(0),
+            release: AtomicU16::new(0),
+            rejected: AtomicU32::new(0),
+        }
+    }
+
+    /// Serve call `nr` with arguments `a`, `b`; returns `(r0, r1)`
+    pub fn dispatch(&self, nr: u8, a: u32, b: u32) -> (u32, u32) {
+        let task = |mask: &AtomicU16| match a as usize {
+            idx if idx < MAX_TASKS => {
+                mask.fetch_or(1 << idx, Ordering::AcqRel);
+                SYS_OK
+            }
+            _ => SYS_INVALID,
+        };
+        let port = self.ports.get(a as usize);
+        let (r0, r1) = match (nr, port) {
+            (SYS_SUSPEND, _) => (task(&self.suspend), 0),
+            (SYS_RESUME, _) => (task(&self.resume), 0),
+            (SYS_RELEASE, _) => (task(&self.release), 0),
+            (SYS_SEND, Some(port)) if port.send(b) => (SYS_OK, 0),
+            (SYS_SEND, Some(_)) => (SYS_WOULD_BLOCK, 0),
+            (SYS_RECV, Some(port)) => port.recv().map_or((SYS_WOULD_BLOCK, 0), |w| (SYS_OK, w)),
+            _ => (SYS_INVALID, 0),
+        };
+        if r0 == SYS_INVALID {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        (r0, r1)
+    }
+
+    /// SVC handler body
+    ///
+    /// Decodes the call number from the `SVC` instruction before the
+    /// stacked PC, serves it and writes the results to the stacked
+    /// `r0` / `r1`. `exc_return` is the handler's `LR`.
+    ///
+    /// # Safety
+    ///
+    /// `frame` must be the exception frame stacked for an `SVC`.
+    pub unsafe fn on_svc(&self, frame: *mut u32, exc_return: u32) {
+        // SAFETY: 呼び出し側の契約により SVC の例外フレーム (r0-r3, r12, lr, pc, xpsr)
+        unsafe {
+            let nr = ((frame.add(6).read_volatile() as usize - 2) as *const u8).read_volatile();
+            self.serve(
+                nr,
+                &mut *frame.cast::<[u32; 8]>(),
+                frame as usize,
+                exc_return,
+            );
+        }
+    }
+
+    /// Serve call `nr` on the stacked registers `regs` (stacked at `sp`)
+    fn serve(&self, nr: u8, regs: &mut [u32; 8], sp: usize, exc_return: u32) {
+        if nr == SYS_RETURN {
+            if return_allowed(sp, exc_return, regs[7], regs[6] as usize) {
+                regain_privilege();
+            } else {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        let (r0, r1) = self.dispatch(nr, regs[0], regs[1]);
+        regs[0] = r0;
+        regs[1] = r1;
+    }
+
+    /// Apply the task requests recorded since the last call
+    ///
+    /// Suspends first, then resumes, then releases; inactive tasks are
+    /// ignored. Returns the tasks touched.
+    pub fn apply<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+    ) -> TaskMask {
+        let suspend = self.suspend.swap(0, Ordering::AcqRel);
+        let resume = self.resume.swap(0, Ordering::AcqRel);
+        let release = self.release.swap(0, Ordering::AcqRel);
+        let mut touched: TaskMask = 0;
+        for idx in 0..MAX_TASKS {
+            let bit: TaskMask = 1 << idx;
+            if (suspend | resume | release) & bit == 0 || kernel.scheduler.get_task(idx).is_none() {
+                continue;
+            }
+            if suspend & bit != 0 {
+                kernel.scheduler.suspend(idx);
+            }
+            if resume & bit != 0 {
+                kernel.scheduler.resume(idx);
+            }
+            if release & bit != 0 {
+                kernel.scheduler.release_from_isr(idx);
+            }
+            touched |= bit;
+        }
+        touched
+    }
+
+    /// Tick the kernel, then apply the requests its job made
+    pub fn tick(&self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let executed = kernel.tick(delta_us);
+        self.apply(kernel);
+        executed
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`])
+    pub fn run_for(&self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        while kernel.timer.now_us() - start < total_us {
+            if self.tick(kernel, tick_us).is_some() {
+                tasks_executed += 1;
+            }
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+
+    /// Calls refused so far
+    pub fn rejected(&self) -> u32 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+/// Table the SVC handler serves
+static TABLE: AtomicPtr<Syscalls> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Dispatcher stack pointer recorded before a job drops privilege
+static KERNEL_SP: AtomicUsize = AtomicUsize::new(0);
+
+/// Serve system calls from `table` (before the first unprivileged job)
+pub fn install(table: &'static Syscalls) {
+    TABLE.store(core::ptr::from_ref(table).cast_mut(), Ordering::Release);
+}
+
+/// Installed table
+fn installed() -> Option<&'static Syscalls> {
+    // SAFETY: `install` は 'static 参照のみ格納する
+    unsafe { TABLE.load(Ordering::Acquire).as_ref() }
+}
+
+/// Suspend task `idx` after the current job
+pub fn suspend(idx: usize) -> KernelResult<()> {
+    status(call::<SYS_SUSPEND>(idx as u32, 0).0)
+}
+
+/// Resume task `idx` after the current job
+pub fn resume(idx: usize) -> KernelResult<()> {
+    status(call::<SYS_RESUME>(idx as u32, 0).0)
+}
+
+/// Release task `idx` after the current job (as from an interrupt)
+pub fn release(idx: usize) -> KernelResult<()> {
+    status(call::<SYS_RELEASE>(idx as u32, 0).0)
+}
+
+/// Send `word` to port `port` ([`KernelError::QueueFull`] if full)
+pub fn send(port: usize, word: u32) -> KernelResult<()> {
+    status(call::<SYS_SEND>(port as u32, word).0)
+}
+
+/// Receive a word from port `port`
+pub fn recv(port: usize) -> Option<u32> {
+    match call::<SYS_RECV>(port as u32, 0) {
+        (SYS_OK, word) => Some(word),
+        _ => None,
+    }
+}
+
+/// Map a call status to a result
+fn status(r0: u32) -> KernelResult<()> {
+    match r0 {
+        SYS_OK => Ok(()),
+        SYS_WOULD_BLOCK => Err(KernelError::QueueFull),
+        _ => Err(KernelError::InvalidIndex),
+    }
+}
+
+/// Trap into the kernel with call `NR`
+#[cfg(target_arch = "arm")]
+fn call<const NR: u8>(a: u32, b: u32) -> (u32, u32) {
+    let (r0, r1);
+    // SAFETY: ハンドラはフレームの r0 / r1 のみ書き換える
+    unsafe {
+        core::arch::asm!(
+            "svc {nr}",
+            nr = const NR,
+            inlateout("r0") a => r0,
+            inlateout("r1") b => r1,
+            options(nostack),
+        );
+    }
+    (r0, r1)
+}
+
+/// Serve call `NR` from the installed table (host builds)
+#[cfg(not(target_arch = "arm"))]
+fn call<const NR: u8>(a: u32, b: u32) -> (u32, u32) {
+    installed().map_or((SYS_INVALID, 0), |table| table.dispatch(NR, a, b))
+}
+
+/// May an `SVC` stacked at `frame` return privilege to the dispatcher?
+fn return_allowed(frame: usize, exc_return: u32, xpsr: u32, pc: usize) -> bool {
+    // 基本フレーム 32 バイト、FP 拡張 (EXC_RETURN bit 4 = 0) は 104、整列詰め物 4
+    let mut size = if exc_return & (1 << 4) == 0 {
+        0x68
+    } else {
+        0x20
+    };
+    if xpsr & (1 << 9) != 0 {
+        size += 4;
+    }
+    pc == return_point() && frame + size == KERNEL_SP.load(Ordering::Acquire)
+}
+
+#[cfg(target_arch = "arm")]
+extern "C" {
+    /// Drop privilege, run `func(ptr, len)`, trap back with `SYS_RETURN`
+    fn alice_rtos_run_unprivileged(func: *const (), ptr: *mut u8, len: usize);
+    /// Instruction after the dispatcher's `SVC #0`
+    static alice_rtos_unprivileged_return: u8;
+}
+
+#[cfg(target_arch = "arm")]
+core::arch::global_asm!(
+    ".section .text.alice_rtos_run_unprivileged,\"ax\",%progbits",
+    ".global alice_rtos_run_unprivileged",
+    ".type alice_rtos_run_unprivileged,%function",
+    ".thumb_func",
+    "alice_rtos_run_unprivileged:",
+    "push {{r4, lr}}",
+    "ldr r3, ={kernel_sp}",
+    "mov r12, sp",
+    "str r12, [r3]",
+    "mrs r3, CONTROL",
+    "orr r3, r3, #1",
+    "msr CONTROL, r3",
+    "isb",
+    "bl {entry}",
+    "svc #0",
+    ".global alice_rtos_unprivileged_return",
+    "alice_rtos_unprivileged_return:",
+    "pop {{r4, pc}}",
+    ".ltorg",
+    "",
+    ".section .text.alice_rtos_svcall,\"ax\",%progbits",
+    ".global alice_rtos_svcall",
+    ".type alice_rtos_svcall,%function",
+    ".thumb_func",
+    "alice_rtos_svcall:",
+    "tst lr, #4",
+    "ite eq",
+    "mrseq r0, msp",
+    "mrsne r0, psp",
+    "mov r1, lr",
+    "b {svc}",
+    kernel_sp = sym KERNEL_SP,
+    entry = sym job_entry,
+    svc = sym svc_entry,
+);
+
+/// Unprivileged side of the dispatcher: run the task function
+#[cfg(target_arch = "arm")]
+unsafe extern "C" fn job_entry(func: *const (), ptr: *mut u8, len: usize) {
+    // SAFETY: `run_unprivileged` が渡した TaskFn とスクラッチ
+    unsafe {
+        let func: crate::task::TaskFn = core::mem::transmute(func);
+        func(core::slice::from_raw_parts_mut(ptr, len));
+    }
+}
+
+/// `alice_rtos_svcall` continues here with the frame and `EXC_RETURN`
+#[cfg(target_arch = "arm")]
+unsafe extern "C" fn svc_entry(frame: *mut u32, exc_return: u32) {
+    if let Some(table) = installed() {
+        // SAFETY: トランポリンが SVC の例外フレームを渡す
+        unsafe { table.on_svc(frame, exc_return) };
+    }
+}
+
+/// Run `func` on `scratch` with `CONTROL.nPRIV` set
+#[cfg(target_arch = "arm")]
+pub(crate) fn run_unprivileged(func: crate::task::TaskFn, scratch: &mut [u8]) {
+    // SAFETY: ディスパッチャは SYS_RETURN で特権を取り戻してから戻る
+    unsafe {
+        alice_rtos_run_unprivileged(func as *const (), scratch.as_mut_ptr(), scratch.len());
+    }
+}
+
+/// Address the dispatcher's `SYS_RETURN` comes from
+#[cfg(target_arch = "arm")]
+fn return_point() -> usize {
+    // SAFETY: アドレスのみ取得
+    unsafe { core::ptr::addr_of!(alice_rtos_unprivileged_return) as usize }
+}
+
+/// No dispatcher off target: privilege is never returned
+#[cfg(not(target_arch = "arm"))]
+fn return_point() -> usize {
+    usize::MAX
+}
+
+/// Clear `CONTROL.nPRIV` (handler mode)
+fn regain_privilege() {
+    #[cfg(target_arch = "arm")]
+    // SAFETY: ハンドラモードでは CONTROL.nPRIV を書き換えられる
+    unsafe {
+        core::arch::asm!(
+            "mrs {t}, CONTROL",
+            "bic {t}, {t}, #1",
+            "msr CONTROL, {t}",
+            "isb",
+            t = out(reg) _,
+            options(nostack),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Task, TaskPriority, TaskState};
+
+    static SETPOINT: Mailbox<u32> = Mailbox::new();
+    static PORTS: [&dyn Port; 1] = [&SETPOINT];
+    static SYSCALLS: Syscalls = Syscalls::new(&PORTS);
+    static LOGGED: AtomicU32 = AtomicU32::new(0);
+
+    fn filter(_: &mut [u8]) {
+        assert_eq!(send(0, 1_250), Ok(()));
+        assert_eq!(release(1), Ok(()));
+        assert_eq!(suspend(MAX_TASKS), Err(KernelError::InvalidIndex));
+        assert_eq!(recv(3), None);
+    }
+
+    fn logger(_: &mut [u8]) {
+        LOGGED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn noop(_: &mut [u8]) {}
+
+    #[test]
+    fn test_unprivileged_task_uses_kernel_calls() {
+        install(&SYSCALLS);
+        let mut k = Kernel::testing();
+        let filter = Task::new(b"filter", filter, TaskPriority::HIGH, 1_000, 50).unprivileged();
+        assert!(filter.is_unprivileged());
+        k.try_add(filter).unwrap();
+        let logger = Task::new(b"log", logger, TaskPriority::LOW, 0, 10).released_by_isr();
+        k.try_add(logger).unwrap();
+        SYSCALLS.run_for(&mut k, 5_000, 100);
+        // filter の各ジョブが logger を 1 回ずつリリース (最後の 1 回は未実行)
+        let jobs = k.scheduler.get_task(0).unwrap().exec_count;
+        assert_eq!(jobs, 6);
+        assert_eq!(LOGGED.load(Ordering::Relaxed), 5);
+        assert_eq!(k.scheduler.isr_pending(), 0b10);
+        assert_eq!(recv(0), Some(1_250));
+        // 不正なタスク番号とポート番号: 1 ジョブあたり 2 回拒否
+        assert_eq!(SYSCALLS.rejected(), 2 * jobs);
+    }
+
+    #[test]
+    fn test_requests_applied_after_job() {
+        let sys = Syscalls::new(&PORTS);
+        let mut k = Kernel::testing();
+        k.add_task(b"a", noop, TaskPriority::HIGH, 1_000, 10);
+        k.add_task(b"b", noop, TaskPriority::LOW, 1_000, 10);
+        assert_eq!(sys.dispatch(SYS_SUSPEND, 1, 0), (SYS_OK, 0));
+        assert_eq!(sys.dispatch(SYS_SUSPEND, 7, 0), (SYS_OK, 0));
+        // 記録のみ、適用は apply で (未登録の 7 は無視)
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Ready);
+        assert_eq!(sys.apply(&mut k), 0b10);
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Suspended);
+        assert_eq!(sys.dispatch(SYS_RESUME, 1, 0), (SYS_OK, 0));
+        sys.apply(&mut k);
+        assert_eq!(k.scheduler.get_task(1).unwrap().state, TaskState::Ready);
+        assert_eq!(sys.apply(&mut k), 0);
+        assert_eq!(sys.dispatch(9, 0, 0), (SYS_INVALID, 0));
+        assert_eq!(sys.rejected(), 1);
+    }
+
+    #[test]
+    fn test_stacked_registers_carry_results() {
+        static SLOT: Mailbox<u32> = Mailbox::new();
+        static PORTS: [&dyn Port; 1] = [&SLOT];
+        let sys = Syscalls::new(&PORTS);
+        // スタックされた r0 = ポート, r1 = 値
+        let mut regs = [0, 77, 0, 0, 0, 0, 0x0800_0102, 1 << 24];
+        sys.serve(SYS_SEND, &mut regs, 0x2000_03E0, 0xFFFF_FFF9);
+        assert_eq!(&regs[..2], &[SYS_OK, 0]);
+        regs[..2].copy_from_slice(&[0, 0]);
+        sys.serve(SYS_RECV, &mut regs, 0x2000_03E0, 0xFFFF_FFF9);
+        assert_eq!(&regs[..2], &[SYS_OK, 77]);
+        // タスクからの SYS_RETURN は拒否され、レジスタも変わらない
+        sys.serve(SYS_RETURN, &mut regs, 0x2000_03E0, 0xFFFF_FFF9);
+        assert_eq!((regs[0], sys.rejected()), (SYS_OK, 1));
+    }
+
+    #[test]
+    fn test_return_needs_dispatcher_frame() {
+        // 記録された深さ・戻り番地と一致しない SYS_RETURN は拒否
+        KERNEL_SP.store(0x2000_0400, Ordering::Release);
+        assert!(!return_allowed(
+            0x2000_03E0,
+            0xFFFF_FFF9,
+            1 << 24,
+            0x0800_0100
+        ));
+        assert!(!return_allowed(
+            0x2000_0300,
+            0xFFFF_FFF9,
+            1 << 24,
+            return_point()
+        ));
+    }
+}
@@ -0,0 +1,426 @@
+//! Task set loaded from a static data table (`task-table` feature)
+//!
+//! Keeps the task set — names, priorities, periods, WCETs — in a `const`
+//! table of [`TaskSpec`] rows, separate from the code. A build-time code
+//! generator can emit the table, and the task bodies go in a parallel
+//! `&[TaskFn]` slice. [`Kernel::load_table`] validates the whole table
+//! before registering anything:
+//!
+//! | Check | Error |
+//! |-------|-------|
+//! | One function per row | [`LoadErrorKind::LengthMismatch`] |
+//! | Name of 1–8 bytes | [`LoadErrorKind::EmptyName`] / [`LoadErrorKind::NameTooLong`] |
+//! | Unique within the table and the kernel | [`LoadErrorKind::DuplicateName`] / [`LoadErrorKind::NameTaken`] |
+//! | `0 < wcet_us ≤ period_us` | [`LoadErrorKind::ZeroPeriod`] / [`LoadErrorKind::WcetExceedsPeriod`] |
+//! | Enough free task slots | [`LoadErrorKind::TooManyTasks`] |
+//! | RMS test of [`Scheduler::is_schedulable`](crate::scheduler::Scheduler::is_schedulable), registered tasks included | [`LoadErrorKind::NotSchedulable`] |
+//!
+//! If the kernel still refuses a task (e.g. it is running without live
+//! registration), the tasks already loaded are unregistered and the [`KernelError`] is
+//! returned as [`LoadErrorKind::Kernel`], so a table loads completely or
+//! not at all.
+//!
+//! ```rust
+//! use alice_rtos::table::{LoadErrorKind, TaskSpec};
+//! use alice_rtos::{Kernel, TaskFn, TaskPriority};
+//!
+//! fn imu(_: &mut [u8]) {}
+//! fn fusion(_: &mut [u8]) {}
+//!
+//! const SPECS: [TaskSpec; 2] = [
+//!     TaskSpec::new("imu", TaskPriority::HIGH, 1_000, 150),
+//!     TaskSpec::new("fusion", TaskPriority::NORMAL, 5_000, 900),
+//! ];
+//! const FUNCS: [TaskFn; 2] = [imu, fusion];
+//!
+//! let mut kernel = Kernel::testing();
+//! assert_eq!(kernel.load_table(&SPECS, &FUNCS), Ok(0b11));
+//!
+//! // Loading again collides with the registered names
+//! let err = kernel.load_table(&SPECS, &FUNCS).unwrap_err();
+//! assert_eq!(err.kind, LoadErrorKind::NameTaken { slot: 0 });
+//! assert_eq!(err.to_string(), "task spec 0 \"imu\": name already used by task 0");
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt;
+
+use crate::error::KernelError;
+#[cfg(doc)]
+use crate::kernel::Kernel;
+use crate::kernel::SizedKernel;
+use crate::policy::SchedulingPolicy;
+use crate::scheduler::{harmonic, liu_layland_bound_ppm, rms_schedulable, utilization_ppm};
+use crate::task::{Task, TaskFn, TaskMask, TaskPriority, MAX_TASKS};
+use crate::text::{Percent, ShortName};
+
+/// One row of a task table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskSpec {
+    /// Task name (1–8 bytes)
+    pub name: &'static str,
+    /// Fixed priority (lower = higher)
+    pub priority: TaskPriority,
+    /// Period in microseconds
+    pub period_us: u32,
+    /// Worst-case execution time in microseconds
+    pub wcet_us: u32,
+}
+
+impl TaskSpec {
+    /// Table row (const-evaluable)
+    #[must_use]
+    pub const fn new(
+        name: &'static str,
+        priority: TaskPriority,
+        period_us: u32,
+        wcet_us: u32,
+    ) -> Self {
+        Self {
+            name,
+            priority,
+            period_us,
+            wcet_us,
+        }
+    }
+
+    /// CPU share in parts per million (rounded up)
+    #[must_use]
+    pub const fn utilization_ppm(&self) -> u32 {
+        utilization_ppm(self.wcet_us, self.period_us)
+    }
+}
+
+/// Why a table was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadErrorKind {
+    /// `specs` and `funcs` differ in length
+    LengthMismatch {
+        /// Rows in the table
+        specs: usize,
+        /// Task functions supplied
+        funcs: usize,
+    },
+    /// The name is empty
+    EmptyName,
+    /// The name is longer than 8 bytes
+    NameTooLong,
+    /// An earlier row has the same name
+    DuplicateName {
+        /// Row that first used the name
+        first: usize,
+    },
+    /// A registered task already has this name
+    NameTaken {
+        /// Slot of that task
+        slot: usize,
+    },
+    /// The period is zero
+    ZeroPeriod,
+    /// The WCET exceeds the period
+    WcetExceedsPeriod,
+    /// Not enough free task slots
+    TooManyTasks {
+        /// Slots left in the kernel
+        free: usize,
+    },
+    /// The registered tasks plus the table fail the RMS utilization test
+    NotSchedulable {
+        /// Total utilization with the table loaded (ppm)
+        utilization_ppm: u32,
+        /// Harmonic (100 %) or Liu & Layland bound of the resulting set (ppm)
+        bound_ppm: u32,
+    },
+    /// The kernel refused the task during registration
+    Kernel(KernelError),
+}
+
+/// Table validation failure: the offending row (if any) and the reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadError {
+    /// Row of the table, `None` for whole-table errors
+    pub index: Option<usize>,
+    /// Name of that row
+    pub name: ShortName,
+    /// What was wrong
+    pub kind: LoadErrorKind,
+}
+
+impl LoadError {
+    /// Error about row `index` of `specs`
+    fn at(specs: &[TaskSpec], index: usize, kind: LoadErrorKind) -> Self {
+        Self {
+            index: Some(index),
+            name: ShortName::from(specs[index].name),
+            kind,
+        }
+    }
+
+    /// Error about the table as a whole
+    const fn table(kind: LoadErrorKind) -> Self {
+        Self {
+            index: None,
+            name: ShortName([0; 8]),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::LengthMismatch { specs, funcs } => {
+                write!(f, "{specs} task specs but {funcs} task functions")
+            }
+            Self::EmptyName => f.write_str("empty name"),
+            Self::NameTooLong => f.write_str("name longer than 8 bytes"),
+            Self::DuplicateName { first } => write!(f, "same name as task spec {first}"),
+            Self::NameTaken { slot } => write!(f, "name already used by task {slot}"),
+            Self::ZeroPeriod => f.write_str("zero period"),
+            Self::WcetExceedsPeriod => f.write_str("WCET exceeds the period"),
+            Self::TooManyTasks { free } => write!(f, "only {free} task slots free"),
+            Self::NotSchedulable {
+                utilization_ppm,
+                bound_ppm,
+            } => write!(
+                f,
+                "utilization {} exceeds the bound {}",
+                Percent(utilization_ppm),
+                Percent(bound_ppm)
+            ),
+            Self::Kernel(e) => write!(f, "refused by the kernel ({e:?})"),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "task spec {index} \"{}\": {}", self.name, self.kind),
+            None => write!(f, "task table: {}", self.kind),
+        }
+    }
+}
+
+/// Validate `specs` against `kernel`, then register them with `funcs`
+pub(crate) fn load<const SCRATCH: usize, P: SchedulingPolicy>(
+    kernel: &mut SizedKernel<SCRATCH, P>,
+    specs: &[TaskSpec],
+    funcs: &[TaskFn],
+) -> Result<TaskMask, LoadError> {
+    validate(kernel, specs, funcs)?;
+    let mut loaded: TaskMask = 0;
+    for (i, (spec, &func)) in specs.iter().zip(funcs).enumerate() {
+        let task = Task::new(
+            spec.name.as_bytes(),
+            func,
+            spec.priority,
+            spec.period_us,
+            spec.wcet_us,
+        );
+        match kernel.try_add(task) {
+            Ok(idx) => loaded |= 1 << idx,
+            Err(e) => {
+                // 全部か無しか: 登録済みの分を戻す
+                for idx in (0..MAX_TASKS).filter(|&idx| loaded & (1 << idx) != 0) {
+                    kernel.scheduler.unregister(idx);
+                }
+                return Err(LoadError::at(specs, i, LoadErrorKind::Kernel(e)));
+            }
+        }
+    }
+    Ok(loaded)
+}
+
+/// Run the checks listed in the module docs; the first failure wins
+fn validate<const SCRATCH: usize, P: SchedulingPolicy>(
+    kernel: &SizedKernel<SCRATCH, P>,
+    specs: &[TaskSpec],
+    funcs: &[TaskFn],
+) -> Result<(), LoadError> {
+    if specs.len() != funcs.len() {
+        return Err(LoadError::table(LoadErrorKind::LengthMismatch {
+            specs: specs.len(),
+            funcs: funcs.len(),
+        }));
+    }
+    for (i, spec) in specs.iter().enumerate() {
+        let name = spec.name.as_bytes();
+        let kind = if name.is_empty() {
+            Some(LoadErrorKind::EmptyName)
+        } else if name.len() > 8 {
+            Some(LoadErrorKind::NameTooLong)
+        } else if let Some(first) = specs[..i].iter().position(|s| s.name == spec.name) {
+            Some(LoadErrorKind::DuplicateName { first })
+        } else if let Some(slot) = kernel.scheduler.find_by_name(name) {
+            Some(LoadErrorKind::NameTaken { slot })
+        } else if spec.period_us == 0 {
+            Some(LoadErrorKind::ZeroPeriod)
+        } else if spec.wcet_us > spec.period_us {
+            Some(LoadErrorKind::WcetExceedsPeriod)
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            return Err(LoadError::at(specs, i, kind));
+        }
+    }
+
+    // 新しいタスクは末尾に追加される (空きスロットは再利用されない)
+    let free = MAX_TASKS - kernel.scheduler.task_slots();
+    if specs.len() > free {
+        return Err(LoadError::table(LoadErrorKind::TooManyTasks { free }));
+    }
+    let utilization_ppm = specs.iter().map(TaskSpec::utilization_ppm).fold(
+        kernel.scheduler.total_utilization_ppm(),
+        u32::saturating_add,
+    );
+    // 登録済みタスクとテーブルを合わせて Scheduler::is_schedulable と同じ判定
+    let mut set = [(0u32, 0u32); MAX_TASKS];
+    let mut periods = [0u32; MAX_TASKS];
+    let mut n = 0;
+    let registered = kernel
+        .scheduler
+        .tasks()
+        .map(|(_, t)| (t.period_us, t.wcet_us));
+    for task in registered.chain(specs.iter().map(|s| (s.period_us, s.wcet_us))) {
+        set[n] = task;
+        periods[n] = task.0;
+        n += 1;
+    }
+    if !rms_schedulable(&set[..n]) {
+        let bound_ppm = if harmonic(&periods[..n]) {
+            1_000_000
+        } else {
+            liu_layland_bound_ppm(n)
+        };
+        return Err(LoadError::table(LoadErrorKind::NotSchedulable {
+            utilization_ppm,
+            bound_ppm,
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+
+    fn noop(_: &mut [u8]) {}
+
+    const SPECS: [TaskSpec; 3] = [
+        TaskSpec::new("imu", TaskPriority::HIGH, 1_000, 150),
+        TaskSpec::new("fusion", TaskPriority::NORMAL, 5_000, 900),
+        TaskSpec::new("telemetry", TaskPriority::LOW, 20_000, 1_000),
+    ];
+    const FUNCS: [TaskFn; 3] = [noop, noop, noop];
+
+    fn load_err(kernel: &mut Kernel, specs: &[TaskSpec]) -> LoadError {
+        let funcs = [noop as TaskFn; 4];
+        kernel.load_table(specs, &funcs[..specs.len()]).unwrap_err()
+    }
+
+    #[test]
+    fn test_rows_validated_with_details() {
+        let mut k = Kernel::testing();
+        // 9 バイト名は切り詰めず拒否
+        let err = load_err(&mut k, &SPECS);
+        assert_eq!(err.index, Some(2));
+        assert_eq!(err.kind, LoadErrorKind::NameTooLong);
+        assert_eq!(err.name, ShortName::from("telemetr"));
+
+        let mut specs = SPECS;
+        specs[2].name = "tlm";
+        specs[1].wcet_us = 5_001;
+        let err = load_err(&mut k, &specs);
+        assert_eq!(
+            std::format!("{err}"),
+            "task spec 1 \"fusion\": WCET exceeds the period"
+        );
+        specs[1].wcet_us = 900;
+        specs[2].name = "imu";
+        assert_eq!(
+            load_err(&mut k, &specs).kind,
+            LoadErrorKind::DuplicateName { first: 0 }
+        );
+        specs[2].name = "";
+        assert_eq!(load_err(&mut k, &specs).kind, LoadErrorKind::EmptyName);
+        specs[2] = TaskSpec::new("tlm", TaskPriority::LOW, 0, 0);
+        assert_eq!(load_err(&mut k, &specs).kind, LoadErrorKind::ZeroPeriod);
+        // 何も登録されていない
+        assert_eq!(k.scheduler.active_task_count(), 0);
+
+        specs[2].period_us = 20_000;
+        assert_eq!(k.load_table(&specs, &FUNCS), Ok(0b111));
+        let task = k.scheduler.get_task(1).unwrap();
+        assert_eq!(task.display_name().as_str(), "fusion");
+        assert_eq!((task.period_us, task.wcet_us), (5_000, 900));
+    }
+
+    #[test]
+    fn test_table_wide_checks() {
+        let mut k = Kernel::testing();
+        let err = k.load_table(&SPECS[..2], &FUNCS).unwrap_err();
+        assert_eq!(err.index, None);
+        assert_eq!(
+            std::format!("{err}"),
+            "task table: 2 task specs but 3 task functions"
+        );
+
+        // 登録済みタスク込みで判定: 0.7 + 0.15 + 0.18 > 0.7798
+        k.add_task(b"hog", noop, TaskPriority::CRITICAL, 1_500, 1_050);
+        let err = load_err(&mut k, &SPECS[..2]);
+        assert_eq!(
+            err.kind,
+            LoadErrorKind::NotSchedulable {
+                utilization_ppm: 1_030_000,
+                bound_ppm: liu_layland_bound_ppm(3),
+            }
+        );
+        assert_eq!(k.scheduler.active_task_count(), 1);
+
+        let mut full = Kernel::testing();
+        for i in 0..MAX_TASKS - 1 {
+            full.add_task(&[b'a' + i as u8], noop, TaskPriority::LOW, 1_000_000, 1);
+        }
+        assert_eq!(
+            load_err(&mut full, &SPECS[..2]).kind,
+            LoadErrorKind::TooManyTasks { free: 1 }
+        );
+    }
+
+    #[test]
+    fn test_harmonic_set_up_to_full_load() {
+        let mut k = Kernel::testing();
+        k.add_task(b"base", noop, TaskPriority::HIGH, 1_000, 500);
+        // 調和周期なら 0.5 + 0.3 + 0.2 = 1.0 まで可
+        let specs = [
+            TaskSpec::new("a", TaskPriority::NORMAL, 2_000, 600),
+            TaskSpec::new("b", TaskPriority::LOW, 4_000, 800),
+        ];
+        assert_eq!(k.load_table(&specs, &FUNCS[..2]), Ok(0b110));
+        assert!(k.is_schedulable());
+        k.scheduler.unregister(2);
+
+        let late = [TaskSpec::new("c", TaskPriority::LOW, 4_000, 801)];
+        assert_eq!(
+            load_err(&mut k, &late).kind,
+            LoadErrorKind::NotSchedulable {
+                utilization_ppm: 1_000_250,
+                bound_ppm: 1_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_kernel_refusal_reported() {
+        let mut k = Kernel::testing();
+        k.start().unwrap();
+        let err = k.load_table(&SPECS[..1], &FUNCS[..1]).unwrap_err();
+        assert_eq!(err.index, Some(0));
+        assert_eq!(err.kind, LoadErrorKind::Kernel(KernelError::KernelRunning));
+        assert_eq!(k.scheduler.task_slots(), 0);
+    }
+}
@@ -5,17 +5,125 @@
 //!
 //! Author: Moroya Sakamoto
 
+use core::fmt;
+
+use crate::text::{Percent, ShortName};
+use crate::timer::{Tick, WrappingInstant};
+
 /// Maximum tasks the kernel can manage
 pub const MAX_TASKS: usize = 16;
 
+/// Bitmask over task slots (bit i = slot i)
+///
+/// Sized so that every slot of the static table fits in one word.
+pub type TaskMask = u16;
+
+const _: () = assert!(MAX_TASKS <= TaskMask::BITS as usize);
+
+/// Slots set in `mask`, lowest first (one step per set bit)
+pub(crate) fn mask_slots(mut mask: TaskMask) -> impl Iterator<Item = usize> {
+    core::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let i = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some(i)
+    })
+}
+
+/// Maximum CPU cores the scheduler dispatches to (RP2040 / RP2350 / ESP32)
+pub const MAX_CORES: usize = 2;
+
+/// Bitmask over CPU cores (bit c = core c)
+pub type CoreMask = u8;
+
+/// Affinity allowing every core
+pub const ANY_CORE: CoreMask = ((1u16 << MAX_CORES) - 1) as CoreMask;
+
+/// `mask` restricted to existing cores, [`ANY_CORE`] if none is left
+pub(crate) const fn normalize_affinity(mask: CoreMask) -> CoreMask {
+    match mask & ANY_CORE {
+        0 => ANY_CORE,
+        m => m,
+    }
+}
+
+const _: () = assert!(MAX_CORES <= CoreMask::BITS as usize);
+
+/// Bitmask over system operating modes (bit m = mode m)
+pub type ModeMask = u8;
+
+/// Membership in every operating mode
+pub const ALL_MODES: ModeMask = ModeMask::MAX;
+
+/// System operating mode (e.g. boot / run / failsafe), 0..8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode(pub u8);
+
+impl Mode {
+    /// Bit of this mode in a [`ModeMask`] (0 for out-of-range modes)
+    #[must_use]
+    pub const fn mask(self) -> ModeMask {
+        if (self.0 as u32) < ModeMask::BITS {
+            1 << self.0
+        } else {
+            0
+        }
+    }
+}
+
+/// `Task::flags`: period/WCET changed while a job was in flight, so
+/// `next_activation` holds that job's boundary (deadline and next release)
+pub(crate) const FLAG_MODE_CHANGE: u8 = 1 << 0;
+
+/// `Task::flags`: released only by `Scheduler::release_from_isr`, never by
+/// the tick
+pub(crate) const FLAG_ISR_RELEASE: u8 = 1 << 1;
+
+/// `Task::flags`: may be skipped or down-rated under overload
+pub(crate) const FLAG_SHED_ALLOWED: u8 = 1 << 2;
+
+/// `Task::flags`: released by the completion of its predecessors
+/// (`Scheduler::chain`), never by the tick
+pub(crate) const FLAG_CHAINED: u8 = 1 << 3;
+
+/// `Task::flags`: `scratch_size` is a dedicated arena, not just a budget
+pub(crate) const FLAG_OWN_ARENA: u8 = 1 << 4;
+
+/// `Task::flags`: runs unprivileged, kernel services only through SVC
+pub(crate) const FLAG_UNPRIVILEGED: u8 = 1 << 5;
+
+/// `Task::flags`: suspended by `SizedKernel::switch_mode` (not by the
+/// application or a fault policy), so the next mode that includes the task
+/// resumes it
+pub(crate) const FLAG_MODE_SUSPENDED: u8 = 1 << 6;
+
+/// `Task::flags` set at registration (the rest is runtime state)
+pub(crate) const CONFIG_FLAGS: u8 =
+    FLAG_ISR_RELEASE | FLAG_SHED_ALLOWED | FLAG_CHAINED | FLAG_OWN_ARENA | FLAG_UNPRIVILEGED;
+
 /// Task function pointer — called each period
 pub type TaskFn = fn(&mut [u8]);
 
+/// Copy up to 8 bytes of `name` into a zero-padded array (const-evaluable)
+#[must_use]
+pub const fn short_name(name: &[u8]) -> [u8; 8] {
+    let mut n = [0u8; 8];
+    let mut i = 0;
+    while i < name.len() && i < 8 {
+        n[i] = name[i];
+        i += 1;
+    }
+    n
+}
+
 /// Task priority (lower number = higher priority)
 ///
 /// Rate-Monotonic: priority = 1 / period
 /// Synth (44.1kHz, period=23µs) > Motion (10kHz, 100µs) > Edge (1kHz, 1ms)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TaskPriority(pub u8);
 
 impl TaskPriority {
@@ -33,6 +141,7 @@ impl TaskPriority {
 
 /// Task execution state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum TaskState {
     /// Task is ready to run
     Ready,
@@ -48,10 +157,16 @@ pub enum TaskState {
 
 /// Static task descriptor — 32 bytes, no heap
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Task {
     /// Task name (8 ASCII chars max)
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::text::serialize_name")
+    )]
     pub name: [u8; 8],
     /// Task function pointer
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub func: Option<TaskFn>,
     /// Priority (lower = higher priority)
     pub priority: TaskPriority,
@@ -62,16 +177,36 @@ pub struct Task {
     /// Current state
     pub state: TaskState,
     /// Next activation tick (absolute)
-    pub next_activation: u64,
+    pub next_activation: Tick,
     /// Execution count
     pub exec_count: u32,
     /// Deadline miss count
     pub deadline_misses: u32,
-    /// Scratch buffer size (bytes in shared scratch space)
+    /// Panics caught while executing (std / test builds)
+    pub fault_count: u16,
+    /// Scratch budget or arena size (bytes)
     pub scratch_size: u16,
+    /// Highest scratch byte count touched (canary check)
+    pub scratch_high_water: u16,
+    /// Predecessor tasks that must complete before each release
+    pub predecessors: TaskMask,
+    /// Predecessors that have completed since this task last ran
+    pub inputs_ready: TaskMask,
+    /// Cores this task may be dispatched on
+    pub affinity: CoreMask,
+    /// Internal state bits (`FLAG_*`)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) flags: u8,
+    /// Operating modes in which this task is active
+    pub modes: ModeMask,
+    /// Subsystem group for collective suspend/resume (0 = default group)
+    pub group: u8,
 }
 
 impl Task {
+    /// Column titles of the [`Display`](fmt::Display) table rows
+    pub const TABLE_HEADER: &'static str = "name     prio   period       U misses";
+
     /// Empty task slot
     #[must_use]
     pub const fn empty() -> Self {
@@ -85,24 +220,29 @@ impl Task {
             next_activation: 0,
             exec_count: 0,
             deadline_misses: 0,
+            fault_count: 0,
             scratch_size: 0,
+            scratch_high_water: 0,
+            predecessors: 0,
+            inputs_ready: 0,
+            affinity: ANY_CORE,
+            flags: 0,
+            modes: ALL_MODES,
+            group: 0,
         }
     }
 
     /// Create a new periodic task
-    pub fn new(
+    #[must_use]
+    pub const fn new(
         name: &[u8],
         func: TaskFn,
         priority: TaskPriority,
         period_us: u32,
         wcet_us: u32,
     ) -> Self {
-        let mut n = [0u8; 8];
-        let len = name.len().min(8);
-        n[..len].copy_from_slice(&name[..len]);
-
         Self {
-            name: n,
+            name: short_name(name),
             func: Some(func),
             priority,
             period_us,
@@ -111,16 +251,227 @@ impl Task {
             next_activation: 0,
             exec_count: 0,
             deadline_misses: 0,
+            fault_count: 0,
             scratch_size: 0,
+            scratch_high_water: 0,
+            predecessors: 0,
+            inputs_ready: 0,
+            affinity: ANY_CORE,
+            flags: 0,
+            modes: ALL_MODES,
+            group: 0,
+        }
+    }
+
+    /// Declare the task's scratch budget in bytes
+    ///
+    /// Used by the kernel's canary check to flag overruns.
+    #[must_use]
+    pub const fn with_scratch(mut self, bytes: u16) -> Self {
+        self.scratch_size = bytes;
+        self
+    }
+
+    /// Give the task a dedicated scratch arena of `bytes`
+    ///
+    /// The kernel lays arenas out at registration and refuses a task whose
+    /// arena does not fit (see
+    /// [`SizedKernel::try_add`](crate::kernel::SizedKernel::try_add)). The
+    /// task always runs on its own arena, whose contents persist between
+    /// activations; the size is also its canary budget.
+    #[must_use]
+    pub const fn with_arena(mut self, bytes: u16) -> Self {
+        self.scratch_size = bytes;
+        self.flags |= FLAG_OWN_ARENA;
+        self
+    }
+
+    /// Restrict the task to the cores in `mask`
+    ///
+    /// Partitioned RMS: pin each task to one core so per-core analysis holds.
+    /// Bits of cores beyond [`MAX_CORES`] are dropped; a mask naming no
+    /// existing core (0 included) would never be dispatched and is
+    /// normalised to [`ANY_CORE`].
+    #[must_use]
+    pub const fn with_affinity(mut self, mask: CoreMask) -> Self {
+        self.affinity = normalize_affinity(mask);
+        self
+    }
+
+    /// Activate the task only in the modes in `mask`
+    #[must_use]
+    pub const fn in_modes(mut self, mask: ModeMask) -> Self {
+        self.modes = mask;
+        self
+    }
+
+    /// Delay the first release by `phase_us` after registration
+    ///
+    /// Staggered offsets keep tasks with related periods from all
+    /// releasing in the same instant. Until the task is registered,
+    /// `next_activation` carries this offset.
+    #[must_use]
+    pub const fn with_phase(mut self, phase_us: u32) -> Self {
+        self.next_activation = phase_us as Tick;
+        self
+    }
+
+    /// Release this task from an interrupt instead of by period
+    ///
+    /// The period still defines the deadline (release + period) and the
+    /// utilization used for analysis.
+    #[must_use]
+    pub const fn released_by_isr(mut self) -> Self {
+        self.flags |= FLAG_ISR_RELEASE;
+        self
+    }
+
+    /// Let an [`OverloadManager`](crate::overload::OverloadManager) skip
+    /// or down-rate this task while the system is overloaded
+    #[must_use]
+    pub const fn allow_shedding(mut self) -> Self {
+        self.flags |= FLAG_SHED_ALLOWED;
+        self
+    }
+
+    /// Run the task unprivileged (`CONTROL.nPRIV`)
+    ///
+    /// It then reaches kernel services only through the SVC calls of the
+    /// `syscall` module (`mpu` feature on Cortex-M; elsewhere the flag has
+    /// no effect).
+    #[must_use]
+    pub const fn unprivileged(mut self) -> Self {
+        self.flags |= FLAG_UNPRIVILEGED;
+        self
+    }
+
+    /// Put the task in subsystem group `group`
+    ///
+    /// See [`Scheduler::suspend_group`](crate::scheduler::Scheduler::suspend_group).
+    #[must_use]
+    pub const fn in_group(mut self, group: u8) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// Is this task released from an interrupt rather than by period?
+    #[must_use]
+    pub const fn isr_release(&self) -> bool {
+        self.flags & FLAG_ISR_RELEASE != 0
+    }
+
+    /// Name with the NUL padding trimmed (for display)
+    #[must_use]
+    pub const fn display_name(&self) -> ShortName {
+        ShortName(self.name)
+    }
+
+    /// Does this task run unprivileged?
+    #[must_use]
+    pub const fn is_unprivileged(&self) -> bool {
+        self.flags & FLAG_UNPRIVILEGED != 0
+    }
+
+    /// Was the task suspended by a mode switch (and not resumed since)?
+    #[must_use]
+    pub const fn mode_suspended(&self) -> bool {
+        self.flags & FLAG_MODE_SUSPENDED != 0
+    }
+
+    /// Is this task released by its predecessors' completion rather than by period?
+    ///
+    /// See [`Scheduler::chain`](crate::scheduler::Scheduler::chain).
+    #[must_use]
+    pub const fn is_chained(&self) -> bool {
+        self.flags & FLAG_CHAINED != 0
+    }
+
+    /// Does this task run on a dedicated scratch arena?
+    #[must_use]
+    pub const fn has_arena(&self) -> bool {
+        self.flags & FLAG_OWN_ARENA != 0 && self.scratch_size != 0
+    }
+
+    /// Is this task released by the tick when its period elapses?
+    pub(crate) const fn time_released(&self) -> bool {
+        self.flags & (FLAG_ISR_RELEASE | FLAG_CHAINED) == 0
+    }
+
+    /// May this task be shed under overload?
+    #[must_use]
+    pub const fn shed_allowed(&self) -> bool {
+        self.flags & FLAG_SHED_ALLOWED != 0
+    }
+
+    /// Is a period/WCET change waiting for the in-flight job's boundary?
+    #[must_use]
+    pub const fn mode_change_pending(&self) -> bool {
+        self.flags & FLAG_MODE_CHANGE != 0
+    }
+
+    /// Implicit deadline of the pending job: its period boundary, which
+    /// is also the next release (already stored if a mode change arrived
+    /// while the job was in flight). Constrained deadlines are tracked by
+    /// [`DeadlineTracker`](crate::deadlines::DeadlineTracker).
+    #[must_use]
+    pub const fn job_deadline(&self) -> WrappingInstant {
+        if self.mode_change_pending() {
+            self.next_release()
+        } else {
+            self.next_release().add(self.period_us as Tick)
         }
     }
 
+    /// Next (or current, while pending) release instant
+    #[must_use]
+    pub const fn next_release(&self) -> WrappingInstant {
+        WrappingInstant(self.next_activation)
+    }
+
+    /// Set or clear the mode-change-pending flag
+    pub(crate) const fn set_mode_change_pending(&mut self, pending: bool) {
+        if pending {
+            self.flags |= FLAG_MODE_CHANGE;
+        } else {
+            self.flags &= !FLAG_MODE_CHANGE;
+        }
+    }
+
+    /// Is this task part of `mode`'s task set?
+    #[must_use]
+    pub const fn active_in(&self, mode: Mode) -> bool {
+        self.modes & mode.mask() != 0
+    }
+
+    /// Call the task function on `scratch` (unprivileged under `mpu`)
+    pub(crate) fn run(&self, scratch: &mut [u8]) {
+        if let Some(func) = self.func {
+            #[cfg(all(feature = "mpu", target_arch = "arm"))]
+            if self.is_unprivileged() {
+                return crate::syscall::run_unprivileged(func, scratch);
+            }
+            func(scratch);
+        }
+    }
+
+    /// May this task run on `core`?
+    #[must_use]
+    pub const fn runs_on(&self, core: usize) -> bool {
+        core < MAX_CORES && self.affinity & (1 << core) != 0
+    }
+
     /// Is this task slot active?
     #[must_use]
     pub fn is_active(&self) -> bool {
         self.state != TaskState::Inactive
     }
 
+    /// Have all predecessors produced output for the pending job?
+    #[must_use]
+    pub const fn inputs_complete(&self) -> bool {
+        self.inputs_ready & self.predecessors == self.predecessors
+    }
+
     /// Frequency in Hz
     #[must_use]
     pub fn frequency_hz(&self) -> f32 {
@@ -140,6 +491,44 @@ impl Task {
             self.wcet_us as f32 / self.period_us as f32
         }
     }
+
+    /// CPU utilization in parts per million (rounded up, integer only)
+    #[must_use]
+    pub const fn utilization_ppm(&self) -> u32 {
+        crate::scheduler::utilization_ppm(self.wcet_us, self.period_us)
+    }
+}
+
+/// One table row: name, priority, period (µs), utilization, misses
+///
+/// Columns line up under [`Task::TABLE_HEADER`].
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<8} {:>4} {:>8} {:>7} {:>6}",
+            self.display_name(),
+            self.priority.0,
+            self.period_us,
+            Percent(self.utilization_ppm()),
+            self.deadline_misses
+        )
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("name", &self.display_name())
+            .field("priority", &self.priority)
+            .field("period_us", &self.period_us)
+            .field("wcet_us", &self.wcet_us)
+            .field("state", &self.state)
+            .field("next_activation", &self.next_activation)
+            .field("exec_count", &self.exec_count)
+            .field("deadline_misses", &self.deadline_misses)
+            .finish_non_exhaustive()
+    }
 }
 
 #[cfg(test)]
@@ -227,12 +616,37 @@ mod tests {
         assert_eq!(task.next_activation, 0);
     }
 
+    #[test]
+    fn test_with_phase_stored_as_offset() {
+        // 登録前は next_activation がオフセットを保持
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100).with_phase(250);
+        assert_eq!(task.next_activation, 250);
+    }
+
     #[test]
     fn test_task_initial_scratch_size_zero() {
         let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100);
         assert_eq!(task.scratch_size, 0);
     }
 
+    #[test]
+    fn test_with_scratch_sets_budget() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100).with_scratch(128);
+        assert_eq!(task.scratch_size, 128);
+    }
+
+    #[test]
+    fn test_with_arena_sets_size() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100).with_arena(256);
+        assert_eq!(task.scratch_size, 256);
+        assert!(task.has_arena());
+        // 0 バイトのアリーナは共有領域で実行
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100).with_arena(0);
+        assert!(!task.has_arena());
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 1000, 100).with_scratch(256);
+        assert!(!task.has_arena());
+    }
+
     #[test]
     fn test_task_state_ready_after_new() {
         let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
@@ -252,6 +666,27 @@ mod tests {
         assert!(task.utilization() < f32::EPSILON);
     }
 
+    #[test]
+    fn test_utilization_ppm_rounds_up() {
+        let task = Task::new(b"edge", dummy_task, TaskPriority::NORMAL, 1000, 100);
+        assert_eq!(task.utilization_ppm(), 100_000);
+        // 1/3 = 333_333.3… → 切り上げ
+        let third = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 3, 1);
+        assert_eq!(third.utilization_ppm(), 333_334);
+        assert_eq!(Task::empty().utilization_ppm(), 0);
+    }
+
+    #[test]
+    fn test_display_row_under_header() {
+        let mut task = Task::new(b"edge", dummy_task, TaskPriority::HIGH, 1000, 125);
+        task.deadline_misses = 3;
+        let row = std::format!("{task}");
+        assert_eq!(row, "edge        1     1000  12.50%      3");
+        assert_eq!(row.len(), Task::TABLE_HEADER.len());
+        let debug = std::format!("{task:?}");
+        assert!(debug.starts_with("Task { name: ShortName(\"edge\"), priority: TaskPriority(1)"));
+    }
+
     #[test]
     fn test_utilization_full_cpu() {
         // wcet == period → 100% 使用率
@@ -323,6 +758,23 @@ mod tests {
         assert_ne!(TaskState::Suspended, TaskState::Inactive);
     }
 
+    #[test]
+    fn test_no_predecessors_inputs_complete() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
+        assert_eq!(task.predecessors, 0);
+        assert!(task.inputs_complete());
+    }
+
+    #[test]
+    fn test_inputs_complete_requires_all_predecessors() {
+        let mut task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
+        task.predecessors = 0b101;
+        task.inputs_ready = 0b001;
+        assert!(!task.inputs_complete());
+        task.inputs_ready = 0b111;
+        assert!(task.inputs_complete());
+    }
+
     #[test]
     fn test_is_active_states() {
         // Ready, Running, Sleeping, Suspended はすべて active
@@ -334,4 +786,40 @@ mod tests {
         task.state = TaskState::Suspended;
         assert!(task.is_active());
     }
+
+    #[test]
+    fn test_default_affinity_any_core() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
+        assert_eq!(task.affinity, ANY_CORE);
+        for core in 0..MAX_CORES {
+            assert!(task.runs_on(core));
+        }
+        assert!(!task.runs_on(MAX_CORES));
+    }
+
+    #[test]
+    fn test_with_affinity_pins_core() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10).with_affinity(0b10);
+        assert!(!task.runs_on(0));
+        assert!(task.runs_on(1));
+        // 存在しないコアのビットは落とす
+        let task = task.with_affinity(0xFF);
+        assert_eq!(task.affinity, ANY_CORE);
+        // どのコアも残らないマスクは全コア扱い (ディスパッチ不能にしない)
+        assert_eq!(task.with_affinity(0).affinity, ANY_CORE);
+        assert_eq!(task.with_affinity(1 << MAX_CORES).affinity, ANY_CORE);
+    }
+
+    #[test]
+    fn test_mode_membership() {
+        let task = Task::new(b"t", dummy_task, TaskPriority::NORMAL, 100, 10);
+        assert!(task.active_in(Mode(0)));
+        assert!(task.active_in(Mode(7)));
+        let task = task.in_modes(Mode(1).mask() | Mode(2).mask());
+        assert!(!task.active_in(Mode(0)));
+        assert!(task.active_in(Mode(2)));
+        // 範囲外のモードはどのタスクにも属さない
+        assert_eq!(Mode(8).mask(), 0);
+        assert!(!task.active_in(Mode(8)));
+    }
 }
@@ -0,0 +1,227 @@
+//! Compact binary telemetry format for [`KernelStats`]
+//!
+//! [`KernelStats::encode`] packs a statistics snapshot into a few dozen
+//! bytes for a UART / radio link without serde on the target: counters
+//! are LEB128 varints, task names are length-prefixed and the frame ends
+//! with a CRC-32. The float utilization is not transmitted; the decoder
+//! derives it from the ppm value. `KernelStats::decode` (host side,
+//! `std` feature) parses a frame back.
+//!
+//! # Frame (version 1)
+//!
+//! | Field | Encoding |
+//! |-------|----------|
+//! | version | `u8` |
+//! | total µs, ticks, jobs, context switches, utilization ppm, task faults | varint each |
+//! | flags | `u8` (bit 0 = schedulable) |
+//! | sleep: per state µs, entries | varint pairs (WFI, stop, standby) |
+//! | task count `n` | `u8` |
+//! | per task: name, jobs, misses, max exec µs, max response µs, utilization ppm | `u8` length + bytes, then varints |
+//! | CRC-32 of everything before | `u32` little-endian |
+//!
+//! Author: Moroya Sakamoto
+
+use crate::snapshot::crc32;
+use crate::task::MAX_TASKS;
+
+#[cfg(doc)]
+use crate::kernel::KernelStats;
+
+/// Current telemetry frame version
+pub const STATS_WIRE_VERSION: u8 = 1;
+
+/// Longest possible encoded frame (bytes)
+pub const MAX_STATS_LEN: usize =
+    1 + 6 * 10 + 1 + 3 * (10 + 5) + 1 + MAX_TASKS * (1 + 8 + 5 * 5) + 4;
+
+/// Why a telemetry frame could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The frame ends early
+    Truncated,
+    /// Written by an incompatible frame version
+    UnsupportedVersion(u8),
+    /// Checksum mismatch or invalid field
+    Corrupt,
+}
+
+/// Frame writer; silently stops at the end of the buffer
+pub(crate) struct WireWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    overflow: bool,
+}
+
+impl<'a> WireWriter<'a> {
+    /// Writer starting with the version byte
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        let mut w = Self {
+            buf,
+            pos: 0,
+            overflow: false,
+        };
+        w.u8(STATS_WIRE_VERSION);
+        w
+    }
+
+    pub(crate) fn u8(&mut self, v: u8) {
+        match self.buf.get_mut(self.pos) {
+            Some(slot) => {
+                *slot = v;
+                self.pos += 1;
+            }
+            None => self.overflow = true,
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, data: &[u8]) {
+        for &b in data {
+            self.u8(b);
+        }
+    }
+
+    /// Unsigned LEB128
+    pub(crate) fn varint(&mut self, mut v: u64) {
+        loop {
+            let byte = (v & 0x7F) as u8;
+            v >>= 7;
+            if v == 0 {
+                self.u8(byte);
+                return;
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+
+    /// Append the CRC; returns the frame length, or 0 if it did not fit
+    pub(crate) fn finish(mut self) -> usize {
+        let crc = crc32(&self.buf[..self.pos]);
+        self.bytes(&crc.to_le_bytes());
+        if self.overflow {
+            0
+        } else {
+            self.pos
+        }
+    }
+}
+
+/// Frame reader over a checksum-verified frame
+#[cfg(feature = "std")]
+pub(crate) struct WireReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl<'a> WireReader<'a> {
+    /// Verify version and CRC; the reader starts after the version byte
+    pub(crate) fn new(frame: &'a [u8]) -> Result<Self, DecodeError> {
+        let Some((&version, _)) = frame.split_first() else {
+            return Err(DecodeError::Truncated);
+        };
+        if version != STATS_WIRE_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        if frame.len() < 5 {
+            return Err(DecodeError::Truncated);
+        }
+        let (body, crc) = frame.split_at(frame.len() - 4);
+        if crc32(body).to_le_bytes() != crc {
+            return Err(DecodeError::Corrupt);
+        }
+        Ok(Self { buf: body, pos: 1 })
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.buf.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let data = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(DecodeError::Truncated)?;
+        self.pos += len;
+        Ok(data)
+    }
+
+    /// Unsigned LEB128 (at most 10 bytes)
+    pub(crate) fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut v = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = self.u8()?;
+            v |= ((byte & 0x7F) as u64)
+                .checked_shl(shift)
+                .ok_or(DecodeError::Corrupt)?;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(DecodeError::Corrupt)
+    }
+
+    /// Varint that must fit in a `u32`
+    pub(crate) fn varint_u32(&mut self) -> Result<u32, DecodeError> {
+        u32::try_from(self.varint()?).map_err(|_| DecodeError::Corrupt)
+    }
+
+    /// Were all bytes consumed?
+    pub(crate) const fn at_end(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_lengths() {
+        let mut buf = [0u8; 32];
+        let mut w = WireWriter::new(&mut buf);
+        w.varint(0);
+        w.varint(127);
+        w.varint(128);
+        w.varint(u64::MAX);
+        // 1 + 1 + 1 + 2 + 10 + CRC 4
+        assert_eq!(w.finish(), 19);
+        assert_eq!(&buf[1..5], &[0x00, 0x7F, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_overflow_returns_zero() {
+        let mut buf = [0u8; 4];
+        let mut w = WireWriter::new(&mut buf);
+        w.varint(1);
+        assert_eq!(w.finish(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reader_round_trip_and_errors() {
+        let mut buf = [0u8; 32];
+        let mut w = WireWriter::new(&mut buf);
+        w.varint(300);
+        w.bytes(b"ab");
+        let len = w.finish();
+        let mut r = WireReader::new(&buf[..len]).unwrap();
+        assert_eq!(r.varint(), Ok(300));
+        assert_eq!(r.bytes(2), Ok(&b"ab"[..]));
+        assert!(r.at_end());
+        assert_eq!(r.u8(), Err(DecodeError::Truncated));
+
+        assert_eq!(WireReader::new(&[]).err(), Some(DecodeError::Truncated));
+        assert_eq!(
+            WireReader::new(&[9, 0, 0, 0, 0]).err(),
+            Some(DecodeError::UnsupportedVersion(9))
+        );
+        let mut bad = buf;
+        bad[1] ^= 1;
+        assert_eq!(
+            WireReader::new(&bad[..len]).err(),
+            Some(DecodeError::Corrupt)
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Schedulability test harness (`std` feature)
+//!
+//! Helpers for property-based and exhaustive regression tests of task
+//! configurations:
+//!
+//! - [`TaskSetGen`] draws random task sets with a target utilization
+//!   (UUniFast over a period menu chosen to keep the hyperperiod short);
+//! - [`simulate`] runs an exact preemptive fixed-priority simulation of a
+//!   set over one hyperperiod — starting from the synchronous release,
+//!   the critical instant, so every response time the set can produce is
+//!   covered;
+//! - [`assert_rms_holds`] checks rate-monotonic theory on a set: when
+//!   [`rms_schedulable`] accepts it, the simulation must show no miss and
+//!   a kernel running it for a hyperperiod must report none either;
+//! - [`assert_kernel_meets_deadlines`] does the same for the tasks of an
+//!   already configured kernel, with their actual priorities.
+//!
+//! ```rust
+//! use alice_rtos::testkit::{assert_rms_holds, TaskSetGen};
+//!
+//! let mut gen = TaskSetGen::new(7);
+//! for _ in 0..20 {
+//!     // 65 % is under the Liu & Layland bound for any task count
+//!     assert_rms_holds(&gen.generate(5, 650_000));
+//! }
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use std::vec::Vec;
+
+use crate::kernel::Kernel;
+use crate::scheduler::{rms_schedulable, utilization_ppm};
+use crate::task::{TaskPriority, TaskState};
+
+/// Default period menu (µs): every hyperperiod divides 100 ms
+pub const DEFAULT_PERIODS_US: [u32; 9] = [
+    1_000, 2_000, 2_500, 5_000, 10_000, 20_000, 25_000, 50_000, 100_000,
+];
+
+/// Period, WCET and priority of one task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskSpec {
+    /// Period = relative deadline (µs)
+    pub period_us: u32,
+    /// Worst-case execution time (µs)
+    pub wcet_us: u32,
+    /// Fixed priority (lower = higher)
+    pub priority: TaskPriority,
+}
+
+impl TaskSpec {
+    /// Task with a placeholder priority (see [`rate_monotonic`])
+    #[must_use]
+    pub const fn new(period_us: u32, wcet_us: u32) -> Self {
+        Self {
+            period_us,
+            wcet_us,
+            priority: TaskPriority::NORMAL,
+        }
+    }
+}
+
+/// Assign rate-monotonic priorities: shorter period = higher priority
+///
+/// Equal periods share a priority (ties go to the lower index, as in the
+/// scheduler).
+pub fn rate_monotonic(tasks: &mut [TaskSpec]) {
+    let periods: Vec<u32> = tasks.iter().map(|t| t.period_us).collect();
+    for task in tasks.iter_mut() {
+        let mut shorter: Vec<u32> = periods
+            .iter()
+            .copied()
+            .filter(|&p| p < task.period_us)
+            .collect();
+        shorter.sort_unstable();
+        shorter.dedup();
+        task.priority = TaskPriority(shorter.len() as u8);
+    }
+}
+
+/// Total utilization of `tasks` (ppm)
+#[must_use]
+pub fn total_utilization_ppm(tasks: &[TaskSpec]) -> u32 {
+    tasks
+        .iter()
+        .map(|t| utilization_ppm(t.wcet_us, t.period_us))
+        .sum()
+}
+
+/// Least common multiple of the periods (µs; 0 for an empty set)
+#[must_use]
+pub fn hyperperiod_us(tasks: &[TaskSpec]) -> u64 {
+    tasks.iter().fold(0, |acc, t| {
+        let p = t.period_us as u64;
+        if acc == 0 {
+            p
+        } else {
+            acc / gcd(acc, p) * p
+        }
+    })
+}
+
+/// Greatest common divisor
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Random task-set generator (seeded, reproducible)
+pub struct TaskSetGen {
+    /// xorshift64 state (never 0)
+    rng: u64,
+    /// Periods drawn from
+    periods: Vec<u32>,
+}
+
+impl TaskSetGen {
+    /// Generator over [`DEFAULT_PERIODS_US`]
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+            periods: DEFAULT_PERIODS_US.to_vec(),
+        }
+    }
+
+    /// Draw periods from `periods_us` instead
+    ///
+    /// # Panics
+    ///
+    /// If `periods_us` is empty or contains 0.
+    #[must_use]
+    pub fn with_periods(mut self, periods_us: &[u32]) -> Self {
+        assert!(
+            !periods_us.is_empty() && !periods_us.contains(&0),
+            "periods must be non-zero"
+        );
+        self.periods = periods_us.to_vec();
+        self
+    }
+
+    /// Next pseudo-random value (xorshift64)
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        x
+    }
+
+    /// Uniform value in `[0, 1)`
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `n` tasks with rate-monotonic priorities and a total utilization
+    /// of at most `utilization_ppm`
+    ///
+    /// Utilizations are split with UUniFast; WCETs are rounded down (at
+    /// least 1 µs), so the result stays at or just under the target.
+    pub fn generate(&mut self, n: usize, utilization_ppm: u32) -> Vec<TaskSpec> {
+        let mut tasks = Vec::with_capacity(n);
+        let mut remaining = utilization_ppm as f64 / 1e6;
+        for i in 0..n {
+            let share = if i + 1 == n {
+                remaining
+            } else {
+                let next = remaining * self.unit().powf(1.0 / (n - i - 1) as f64);
+                let share = remaining - next;
+                remaining = next;
+                share
+            };
+            let pick = (self.next_u64() % self.periods.len() as u64) as usize;
+            let period = self.periods[pick];
+            let wcet = ((share * period as f64) as u32).max(1);
+            tasks.push(TaskSpec::new(period, wcet));
+        }
+        rate_monotonic(&mut tasks);
+        tasks
+    }
+}
+
+/// Outcome of [`simulate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SimReport {
+    /// Simulated hyperperiod (µs)
+    pub hyperperiod_us: u64,
+    /// Jobs that missed their deadline, per task
+    pub misses: Vec<u32>,
+    /// Largest release-to-completion time, per task (µs)
+    pub max_response_us: Vec<u32>,
+}
+
+impl SimReport {
+    /// Did any job miss its deadline?
+    #[must_use]
+    pub fn any_miss(&self) -> bool {
+        self.misses.iter().any(|&m| m > 0)
+    }
+}
+
+/// Exact preemptive fixed-priority simulation over one hyperperiod
+///
+/// All tasks are released at 0 and every `period_us` after; a job still
+/// unfinished at its next release counts as a miss (and keeps running).
+#[must_use]
+pub fn simulate(tasks: &[TaskSpec]) -> SimReport {
+    let n = tasks.len();
+    let period = |i: usize| tasks[i].period_us as u64;
+    let hyperperiod = hyperperiod_us(tasks);
+    let mut report = SimReport {
+        hyperperiod_us: hyperperiod,
+        misses: vec![0; n],
+        max_response_us: vec![0; n],
+    };
+    // 各タスクの未完了ジョブ: (リリース時刻, 残り実行時間)
+    let mut jobs: Vec<Vec<(u64, u64)>> = vec![Vec::new(); n];
+    let mut next_release = vec![0u64; n];
+    let mut now = 0u64;
+    while now < hyperperiod {
+        for i in 0..n {
+            if next_release[i] == now {
+                // 前のジョブの締め切り (= このリリース) までに終わっていない
+                report.misses[i] +=
+                    jobs[i].iter().filter(|j| j.0 + period(i) == now).count() as u32;
+                jobs[i].push((now, tasks[i].wcet_us as u64));
+                next_release[i] += tasks[i].period_us as u64;
+            }
+        }
+        let release = next_release.iter().copied().min().unwrap_or(hyperperiod);
+        let horizon = release.min(hyperperiod);
+        let running = (0..n)
+            .filter(|&i| !jobs[i].is_empty())
+            .min_by_key(|&i| (tasks[i].priority, i));
+        let Some(i) = running else {
+            now = horizon;
+            continue;
+        };
+        let job = &mut jobs[i][0];
+        let run = job.1.min(horizon - now);
+        job.1 -= run;
+        now += run;
+        if job.1 == 0 {
+            let response = (now - job.0).min(u32::MAX as u64) as u32;
+            report.max_response_us[i] = report.max_response_us[i].max(response);
+            jobs[i].remove(0);
+        }
+    }
+    // 超周期の終端 (= 全タスクの次のリリース) が締め切りのジョブ
+    for (i, pending) in jobs.iter().enumerate() {
+        report.misses[i] += pending.iter().filter(|j| j.0 + period(i) == now).count() as u32;
+    }
+    report
+}
+
+/// Tasks of `kernel` (periodic, not inactive) in slot order
+#[must_use]
+pub fn specs_of(kernel: &Kernel) -> Vec<TaskSpec> {
+    (0..kernel.scheduler.task_slots())
+        .filter_map(|idx| kernel.scheduler.get_task(idx))
+        .filter(|t| t.state != TaskState::Inactive && t.period_us > 0)
+        .map(|t| TaskSpec {
+            period_us: t.period_us,
+            wcet_us: t.wcet_us,
+            priority: t.priority,
+        })
+        .collect()
+}
+
+/// Register `tasks` in `kernel` with a no-op body; returns their slots
+///
+/// # Panics
+///
+/// If the task table is full.
+pub fn install(kernel: &mut Kernel, tasks: &[TaskSpec]) -> Vec<usize> {
+    fn noop(_: &mut [u8]) {}
+    tasks
+        .iter()
+        .map(|t| {
+            kernel
+                .add_task(b"testkit", noop, t.priority, t.period_us, t.wcet_us)
+                .expect("task table full")
+        })
+        .collect()
+}
+
+/// Run `kernel` for one hyperperiod of its tasks and assert no deadline miss
+///
+/// # Panics
+///
+/// If a task misses a deadline.
+pub fn assert_kernel_meets_deadlines(kernel: &mut Kernel) {
+    let hyperperiod = hyperperiod_us(&specs_of(kernel));
+    kernel.run_tickless(hyperperiod);
+    for idx in 0..kernel.scheduler.task_slots() {
+        if let Some(task) = kernel.scheduler.get_task(idx) {
+            assert_eq!(
+                task.deadline_misses, 0,
+                "task {idx} missed {} deadlines within the {hyperperiod} us hyperperiod",
+                task.deadline_misses
+            );
+        }
+    }
+}
+
+/// Assert rate-monotonic theory on `tasks`
+///
+/// If [`rms_schedulable`] accepts the set (Liu & Layland bound, or U ≤ 1
+/// for harmonic periods), the exact simulation must show no miss and a
+/// fresh kernel running the set for a hyperperiod must report none.
+/// Sets above the bound are only simulated (they may or may not miss).
+/// Returns the simulation report.
+///
+/// # Panics
+///
+/// If a set accepted by the bound misses a deadline.
+pub fn assert_rms_holds(tasks: &[TaskSpec]) -> SimReport {
+    let pairs: Vec<(u32, u32)> = tasks.iter().map(|t| (t.period_us, t.wcet_us)).collect();
+    let report = simulate(tasks);
+    if rms_schedulable(&pairs) {
+        assert!(
+            !report.any_miss(),
+            "RMS bound accepted {tasks:?} (U = {} ppm) but the simulation missed: {:?}",
+            total_utilization_ppm(tasks),
+            report.misses
+        );
+        let mut kernel = Kernel::testing();
+        install(&mut kernel, tasks);
+        assert_kernel_meets_deadlines(&mut kernel);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperperiod_and_rm_priorities() {
+        let mut tasks = [
+            TaskSpec::new(2_500, 100),
+            TaskSpec::new(1_000, 100),
+            TaskSpec::new(2_000, 100),
+            TaskSpec::new(1_000, 50),
+        ];
+        assert_eq!(hyperperiod_us(&tasks), 10_000);
+        assert_eq!(hyperperiod_us(&[]), 0);
+        rate_monotonic(&mut tasks);
+        let prio: Vec<u8> = tasks.iter().map(|t| t.priority.0).collect();
+        assert_eq!(prio, [2, 0, 1, 0]);
+        assert_eq!(total_utilization_ppm(&tasks), 240_000);
+    }
+
+    #[test]
+    fn test_generated_sets_hit_target() {
+        let mut gen = TaskSetGen::new(99);
+        for n in 1..=8 {
+            let tasks = gen.generate(n, 600_000);
+            assert_eq!(tasks.len(), n);
+            let u = total_utilization_ppm(&tasks);
+            // 切り捨て分だけ目標をわずかに下回る (1 µs 未満 × n)
+            assert!(u <= 600_000 + n as u32, "n={n} u={u}");
+            assert!(u >= 590_000 - n as u32 * 1_000, "n={n} u={u}");
+            assert!(100_000 % hyperperiod_us(&tasks) == 0);
+        }
+        // 同じシードなら同じタスクセット
+        assert_eq!(
+            TaskSetGen::new(5).generate(4, 500_000),
+            TaskSetGen::new(5).generate(4, 500_000)
+        );
+    }
+
+    #[test]
+    fn test_simulation_exact_response_times() {
+        // 古典例: C = (1, 2, 3), T = (4, 6, 12) → R = (1, 3, 10)
+        let mut tasks = [
+            TaskSpec::new(4, 1),
+            TaskSpec::new(6, 2),
+            TaskSpec::new(12, 3),
+        ];
+        rate_monotonic(&mut tasks);
+        let report = simulate(&tasks);
+        assert_eq!(report.hyperperiod_us, 12);
+        assert_eq!(report.max_response_us, [1, 3, 10]);
+        assert!(!report.any_miss());
+
+        // U > 1 は必ず落ちる
+        let mut over = [TaskSpec::new(4, 3), TaskSpec::new(6, 3)];
+        rate_monotonic(&mut over);
+        let report = simulate(&over);
+        assert!(report.misses[1] > 0);
+        assert_eq!(report.misses[0], 0);
+    }
+
+    #[test]
+    fn test_random_sets_under_bound_never_miss() {
+        let mut gen = TaskSetGen::new(2024);
+        for case in 0..50 {
+            let n = 2 + case % 7;
+            assert_rms_holds(&gen.generate(n, 690_000));
+        }
+        // 調和周期なら 100 % 近くまで
+        let mut harmonic = TaskSetGen::new(3).with_periods(&[1_000, 2_000, 4_000, 8_000]);
+        for _ in 0..20 {
+            let tasks = harmonic.generate(4, 980_000);
+            assert!(!assert_rms_holds(&tasks).any_miss());
+        }
+    }
+
+    #[test]
+    fn test_kernel_configuration_check() {
+        let mut kernel = Kernel::testing();
+        fn noop(_: &mut [u8]) {}
+        kernel.add_task(b"audio", noop, TaskPriority::CRITICAL, 1_000, 200);
+        kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 5_000, 1_000);
+        let specs = specs_of(&kernel);
+        assert_eq!(specs.len(), 2);
+        assert!(!simulate(&specs).any_miss());
+        assert_kernel_meets_deadlines(&mut kernel);
+    }
+}
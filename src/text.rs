@@ -0,0 +1,277 @@
+//! Heapless text helpers: task names and fixed-buffer formatting
+//!
+//! [`ShortName`] wraps the zero-padded `[u8; 8]` task name so it can be
+//! built from a `&str` and printed with trailing NULs trimmed. Its
+//! `Display` honours width / alignment (`{name:<8}`), which is what the
+//! tabular outputs of [`shell`](crate::shell) and the stats types need.
+//!
+//! [`FmtBuf`] is a `core::fmt::Write` target backed by an `N`-byte array.
+//! Text beyond the capacity is truncated at a character boundary and
+//! flagged ([`FmtBuf::is_truncated`]) instead of failing the `write!`.
+//! [`Percent`] prints a ppm value as `12.34%` without floats.
+//!
+//! ```rust
+//! use alice_rtos::text::{FmtBuf, ShortName};
+//! use core::fmt::Write;
+//!
+//! let name = ShortName::from("ctl");
+//! let mut buf: FmtBuf<16> = FmtBuf::new();
+//! write!(buf, "[{name:<5}] {}", 42).unwrap();
+//! assert_eq!(buf.as_str(), "[ctl  ] 42");
+//! assert_eq!(name.as_bytes(), b"ctl");
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::fmt::{self, Write};
+
+use crate::task::short_name;
+
+/// Task name: up to 8 bytes, zero-padded
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ShortName(pub [u8; 8]);
+
+impl ShortName {
+    /// Name from the first 8 bytes of `name` (const-evaluable)
+    #[must_use]
+    pub const fn new(name: &[u8]) -> Self {
+        Self(short_name(name))
+    }
+
+    /// Name bytes without the trailing NUL padding
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(8);
+        &self.0[..len]
+    }
+
+    /// Name as text; `"?"` if it is not valid UTF-8
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(self.as_bytes()).unwrap_or("?")
+    }
+
+    /// Is the name empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0[0] == 0
+    }
+}
+
+impl From<&str> for ShortName {
+    /// Truncates to 8 bytes (possibly inside a multi-byte character)
+    fn from(name: &str) -> Self {
+        Self::new(name.as_bytes())
+    }
+}
+
+impl From<&[u8]> for ShortName {
+    fn from(name: &[u8]) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<[u8; 8]> for ShortName {
+    fn from(name: [u8; 8]) -> Self {
+        Self(name)
+    }
+}
+
+impl From<ShortName> for [u8; 8] {
+    fn from(name: ShortName) -> Self {
+        name.0
+    }
+}
+
+impl fmt::Display for ShortName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Serialized as the trimmed name string
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShortName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// `serialize_with` helper writing a raw `[u8; 8]` name as a [`ShortName`]
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_name<S: serde::Serializer>(
+    name: &[u8; 8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&ShortName(*name), serializer)
+}
+
+impl fmt::Debug for ShortName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ShortName").field(&self.as_str()).finish()
+    }
+}
+
+/// Parts-per-million value displayed as a percentage (`12.34%`)
+///
+/// Integer only, truncated to two decimals; honours width / alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Percent(pub u32);
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf: FmtBuf<12> = FmtBuf::new();
+        write!(buf, "{}.{:02}%", self.0 / 10_000, self.0 / 100 % 100)?;
+        f.pad(buf.as_str())
+    }
+}
+
+/// `core::fmt::Write` target over an `N`-byte buffer, truncating on overflow
+#[derive(Clone)]
+pub struct FmtBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> Default for FmtBuf<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FmtBuf<N> {
+    /// Empty buffer
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Text written so far
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: write_str は文字境界でしか切らないので常に有効な UTF-8
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    /// Bytes written so far
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Bytes written so far
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Nothing written yet?
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Buffer size in bytes
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Was text dropped because the buffer was full?
+    #[must_use]
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Empty the buffer and reset the truncation flag
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+impl<const N: usize> fmt::Write for FmtBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = s.len().min(N - self.len);
+        if n < s.len() {
+            self.truncated = true;
+            // 文字の途中で切らない
+            while !s.is_char_boundary(n) {
+                n -= 1;
+            }
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Display for FmtBuf<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for FmtBuf<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_name_trims_padding() {
+        let name = ShortName::from("telemetry");
+        assert_eq!(name.as_str(), "telemetr");
+        let ctl = ShortName::from(*b"ctl\0\0\0\0\0");
+        assert_eq!(ctl, ShortName::new(b"ctl"));
+        assert_eq!(<[u8; 8]>::from(ctl), *b"ctl\0\0\0\0\0");
+        assert!(ShortName::default().is_empty());
+        // 非 UTF-8 は "?"
+        assert_eq!(ShortName::new(&[0xFF, b'a']).as_str(), "?");
+
+        let mut buf: FmtBuf<32> = FmtBuf::new();
+        write!(buf, "{ctl}|{ctl:>5}|{ctl:?}").unwrap();
+        assert_eq!(buf.as_str(), "ctl|  ctl|ShortName(\"ctl\")");
+    }
+
+    #[test]
+    fn test_percent_from_ppm() {
+        let mut buf: FmtBuf<32> = FmtBuf::new();
+        write!(
+            buf,
+            "{}|{}|{:>7}|",
+            Percent(0),
+            Percent(1_000_000),
+            Percent(123_456)
+        )
+        .unwrap();
+        assert_eq!(buf.as_str(), "0.00%|100.00%| 12.34%|");
+    }
+
+    #[test]
+    fn test_fmt_buf_truncates_at_char_boundary() {
+        let mut buf: FmtBuf<8> = FmtBuf::new();
+        write!(buf, "{}", 1234).unwrap();
+        assert_eq!(buf.as_str(), "1234");
+        assert!(!buf.is_truncated());
+        // "µs" は 3 バイト: 'µ' (2 バイト) の途中では切らない
+        write!(buf, "{}µs", 56).unwrap();
+        assert_eq!(buf.as_str(), "123456µ");
+        assert!(buf.is_truncated());
+        assert_eq!(buf.len(), 8);
+        buf.clear();
+        assert!(buf.is_empty() && !buf.is_truncated());
+        write!(buf, "{}µµµ", 12).unwrap();
+        assert_eq!(buf.as_str(), "12µµµ");
+        assert_eq!(buf.len(), 8);
+    }
+}
@@ -0,0 +1,470 @@
+//! Preemption-threshold scheduling
+//!
+//! Under plain fixed priorities any higher-priority release preempts the
+//! running job. With a preemption threshold θ ≥ its priority, a job can
+//! only be preempted by tasks whose priority is *above θ*: tasks between
+//! its priority and θ wait until it completes. Raising thresholds turns
+//! groups of tasks mutually non-preemptive, which cuts context switches
+//! (and, on a port with per-task stacks, lets those tasks share a stack)
+//! at the cost of blocking for the tasks skipped over.
+//!
+//! [`PreemptionThresholds`] keeps the optional threshold of each task
+//! outside the task table (the kernel's footprint is unchanged):
+//!
+//! - [`non_preempting`](PreemptionThresholds::non_preempting) is the
+//!   dispatcher rule — the tasks that may not preempt a running job. Only
+//!   the preemptive `timeline::TimelineSim` (`std`) honours it, via
+//!   `with_thresholds`;
+//! - [`response_times`](PreemptionThresholds::response_times) is the
+//!   response-time analysis of Wang & Saksena (1999): blocking by one
+//!   lower-priority job whose threshold shields it from the task, start
+//!   time delayed by every higher-priority task, and after the start only
+//!   interference from tasks above the task's own threshold, over all
+//!   jobs of the level-i busy period;
+//! - [`maximize`](PreemptionThresholds::maximize) raises every threshold
+//!   as far as the set stays schedulable.
+//!
+//! The kernel does not consult thresholds. Its tick runs every job to
+//! completion, so on the kernel a job is never preempted and the
+//! thresholds are for analysis only: they describe a preemptive port or
+//! the `TimelineSim` model. The kernel's own dispatch is the limit case
+//! of every threshold at [`TaskPriority::CRITICAL`], which
+//! [`response_times`](PreemptionThresholds::response_times) analyses
+//! as well.
+//!
+//! Equal priorities never preempt each other (ties go to the lower slot,
+//! as in the scheduler). Event-driven tasks (period 0) are not analysed
+//! and not charged, as in [`dmda`](crate::dmda). Scheduler overheads are
+//! not included.
+//!
+//! ```rust
+//! use alice_rtos::threshold::PreemptionThresholds;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"isr", noop, TaskPriority::CRITICAL, 1_000, 100).unwrap();
+//! let ctl = kernel.add_task(b"ctl", noop, TaskPriority::HIGH, 2_000, 300).unwrap();
+//! let log = kernel.add_task(b"log", noop, TaskPriority::LOW, 10_000, 400).unwrap();
+//!
+//! let mut thresholds = PreemptionThresholds::new();
+//! // `ctl` may no longer preempt `log`; `isr` still can
+//! assert!(thresholds.set(&kernel.scheduler, log, TaskPriority::HIGH));
+//! assert_eq!(thresholds.non_preempting(&kernel.scheduler, log), 0b110);
+//! // ctl: blocked up to 400 µs by log, plus one isr job
+//! let report = thresholds.response_times(&kernel.scheduler);
+//! assert_eq!(report.results[ctl].unwrap().worst_case_response, 800);
+//! assert!(report.all_schedulable);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::dmda::{DmdaReport, RtaResult};
+use crate::scheduler::Scheduler;
+use crate::task::{TaskMask, TaskPriority, MAX_TASKS};
+
+/// Iteration cap of each fixed-point search
+const MAX_ITERATIONS: u32 = 1_000;
+
+/// Optional preemption threshold per task slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreemptionThresholds {
+    /// Threshold per task (valid where `set` has the bit)
+    levels: [TaskPriority; MAX_TASKS],
+    /// Tasks with a threshold
+    set: TaskMask,
+}
+
+impl Default for PreemptionThresholds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreemptionThresholds {
+    /// No thresholds: plain fixed-priority preemption
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            levels: [TaskPriority::IDLE; MAX_TASKS],
+            set: 0,
+        }
+    }
+
+    /// Give task `idx` the preemption threshold `threshold`
+    ///
+    /// The threshold must be at or above the task's base priority
+    /// (numerically ≤); a threshold equal to it is the same as none.
+    /// Returns false for an inactive task or a threshold below its
+    /// priority.
+    pub fn set(&mut self, scheduler: &Scheduler, idx: usize, threshold: TaskPriority) -> bool {
+        let active = scheduler.get_task(idx).is_some_and(|t| t.is_active());
+        match scheduler.base_priority(idx) {
+            Some(priority) if active && threshold <= priority => {
+                self.levels[idx] = threshold;
+                self.set |= 1 << idx;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove the threshold of task `idx`
+    pub fn clear(&mut self, idx: usize) {
+        if idx < MAX_TASKS {
+            self.set &= !(1 << idx);
+        }
+    }
+
+    /// Threshold configured for task `idx`, if any
+    #[must_use]
+    pub fn threshold(&self, idx: usize) -> Option<TaskPriority> {
+        (idx < MAX_TASKS && self.set & (1 << idx) != 0).then(|| self.levels[idx])
+    }
+
+    /// Level a running job of `idx` must be preempted from above
+    ///
+    /// Its threshold, or its current priority if that is higher (a boost)
+    /// or no threshold is set. None for an out-of-range slot.
+    #[must_use]
+    pub fn level(&self, scheduler: &Scheduler, idx: usize) -> Option<TaskPriority> {
+        let priority = scheduler.get_task(idx)?.priority;
+        Some(self.threshold(idx).map_or(priority, |t| t.min(priority)))
+    }
+
+    /// Tasks that may not preempt a running job of `running`
+    ///
+    /// Every task whose priority is not strictly above the running job's
+    /// [`level`](Self::level); a preemptive dispatcher keeps them Ready
+    /// until the job completes. 0 for an invalid slot.
+    #[must_use]
+    pub fn non_preempting(&self, scheduler: &Scheduler, running: usize) -> TaskMask {
+        let Some(level) = self.level(scheduler, running) else {
+            return 0;
+        };
+        (0..scheduler.task_slots())
+            .filter(|&i| scheduler.get_task(i).is_some_and(|t| t.priority >= level))
+            .fold(0, |mask, i| mask | 1 << i)
+    }
+
+    /// Worst-case response time of every active periodic task
+    ///
+    /// Wang & Saksena's analysis with these thresholds; with none set it
+    /// is the exact analysis of fixed-priority scheduling in which equal
+    /// priorities do not preempt each other.
+    #[must_use]
+    pub fn response_times(&self, scheduler: &Scheduler) -> DmdaReport {
+        let set = Snapshot::new(self, scheduler);
+        let mut report = DmdaReport {
+            results: [None; MAX_TASKS],
+            task_count: scheduler.task_slots(),
+            all_schedulable: true,
+        };
+        for i in (0..MAX_TASKS).filter(|&i| set.periodic & (1 << i) != 0) {
+            let response = set.response(i);
+            let deadline = set.period[i];
+            let meets = response <= u64::from(deadline);
+            report.all_schedulable &= meets;
+            report.results[i] = Some(RtaResult {
+                task_index: i,
+                worst_case_response: response.min(u64::from(u32::MAX)) as u32,
+                deadline,
+                meets_deadline: meets,
+            });
+        }
+        report
+    }
+
+    /// Does every periodic task meet its deadline with these thresholds?
+    #[must_use]
+    pub fn is_schedulable(&self, scheduler: &Scheduler) -> bool {
+        self.response_times(scheduler).all_schedulable
+    }
+
+    /// Raise every threshold as far as the set stays schedulable
+    ///
+    /// Highest priority first, each task's threshold is moved up one
+    /// occupied priority level at a time until the next step would miss
+    /// a deadline. Returns false (and changes nothing) if the set is not
+    /// schedulable with the current thresholds.
+    pub fn maximize(&mut self, scheduler: &Scheduler) -> bool {
+        if !self.is_schedulable(scheduler) {
+            return false;
+        }
+        let mut order: [usize; MAX_TASKS] = [0; MAX_TASKS];
+        let mut n = 0;
+        for (i, _) in scheduler.tasks() {
+            order[n] = i;
+            n += 1;
+        }
+        let priority = |i: usize| scheduler.base_priority(i).unwrap_or(TaskPriority::IDLE);
+        order[..n].sort_unstable_by_key(|&i| (priority(i), i));
+
+        for &i in &order[..n] {
+            loop {
+                let current = self.threshold(i).unwrap_or(priority(i));
+                // 次に高い (数値が小さい) 使用中の優先度
+                let Some(next) = scheduler
+                    .tasks()
+                    .map(|(_, t)| t.priority)
+                    .filter(|&p| p < current)
+                    .max()
+                else {
+                    break;
+                };
+                let saved = *self;
+                self.set(scheduler, i, next);
+                if !self.is_schedulable(scheduler) {
+                    *self = saved;
+                    break;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Periodic tasks of a scheduler as seen by the analysis
+struct Snapshot {
+    /// Active tasks with a period
+    periodic: TaskMask,
+    /// Priority per slot
+    priority: [TaskPriority; MAX_TASKS],
+    /// Preemption threshold per slot
+    level: [TaskPriority; MAX_TASKS],
+    /// WCET per slot (µs)
+    wcet: [u64; MAX_TASKS],
+    /// Period per slot (µs)
+    period: [u32; MAX_TASKS],
+}
+
+impl Snapshot {
+    fn new(thresholds: &PreemptionThresholds, scheduler: &Scheduler) -> Self {
+        let mut set = Self {
+            periodic: 0,
+            priority: [TaskPriority::IDLE; MAX_TASKS],
+            level: [TaskPriority::IDLE; MAX_TASKS],
+            wcet: [0; MAX_TASKS],
+            period: [0; MAX_TASKS],
+        };
+        for (i, task) in scheduler.tasks().filter(|(_, t)| t.period_us > 0) {
+            set.periodic |= 1 << i;
+            set.priority[i] = task.priority;
+            set.level[i] = thresholds.level(scheduler, i).unwrap_or(task.priority);
+            set.wcet[i] = u64::from(task.wcet_us);
+            set.period[i] = task.period_us;
+        }
+        set
+    }
+
+    /// Slots of the periodic tasks in `mask`
+    fn slots(&self, mask: TaskMask) -> impl Iterator<Item = usize> + '_ {
+        (0..MAX_TASKS).filter(move |&j| self.periodic & mask & (1 << j) != 0)
+    }
+
+    /// Is `j` dispatched before `i` when both are ready?
+    fn before(&self, j: usize, i: usize) -> bool {
+        (self.priority[j], j) < (self.priority[i], i)
+    }
+
+    /// Worst-case response time of `i` (µs, `u64::MAX` if unbounded)
+    fn response(&self, i: usize) -> u64 {
+        let (c, t) = (self.wcet[i], u64::from(self.period[i]));
+        let all = TaskMask::MAX;
+        let higher = self
+            .slots(all)
+            .filter(|&j| self.before(j, i))
+            .fold(0, |m: TaskMask, j| m | 1 << j);
+        let preempting = self
+            .slots(higher)
+            .filter(|&j| self.priority[j] < self.level[i])
+            .fold(0, |m: TaskMask, j| m | 1 << j);
+        // 閾値で i から守られた低優先度ジョブ 1 つ分
+        let blocking = self
+            .slots(all)
+            .filter(|&j| j != i && !self.before(j, i) && self.priority[i] >= self.level[j])
+            .map(|j| self.wcet[j])
+            .max()
+            .unwrap_or(0);
+
+        // レベル i のビジー期間
+        let busy = self.fixed_point(blocking + c, |l| {
+            blocking
+                + l.div_ceil(t) * c
+                + self
+                    .slots(higher)
+                    .map(|j| l.div_ceil(u64::from(self.period[j])) * self.wcet[j])
+                    .sum::<u64>()
+        });
+        let Some(busy) = busy else {
+            return u64::MAX;
+        };
+
+        let mut worst = 0;
+        for q in 0..busy.div_ceil(t) {
+            // 開始: 先に到着した高優先度ジョブを全部待つ
+            let releases = |s: u64, j: usize| 1 + s / u64::from(self.period[j]);
+            let start = self.fixed_point(blocking + q * c, |s| {
+                blocking
+                    + q * c
+                    + self
+                        .slots(higher)
+                        .map(|j| releases(s, j) * self.wcet[j])
+                        .sum::<u64>()
+            });
+            let Some(start) = start else {
+                return u64::MAX;
+            };
+            // 開始後は閾値より上のタスクだけが横取りする
+            let finish = self.fixed_point(start + c, |f| {
+                start
+                    + c
+                    + self
+                        .slots(preempting)
+                        .map(|j| {
+                            let later = f
+                                .div_ceil(u64::from(self.period[j]))
+                                .saturating_sub(releases(start, j));
+                            later * self.wcet[j]
+                        })
+                        .sum::<u64>()
+            });
+            let Some(finish) = finish else {
+                return u64::MAX;
+            };
+            worst = worst.max(finish - q * t);
+        }
+        worst
+    }
+
+    /// Least fixed point of `f` from `x` (None past `u32::MAX` µs)
+    fn fixed_point(&self, mut x: u64, f: impl Fn(u64) -> u64) -> Option<u64> {
+        for _ in 0..MAX_ITERATIONS {
+            let next = f(x);
+            if next == x {
+                return Some(x);
+            }
+            if next > u64::from(u32::MAX) {
+                return None;
+            }
+            x = next;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::scheduler::Overhead;
+    use crate::timeline::TimelineSim;
+
+    fn noop(_: &mut [u8]) {}
+
+    /// U = 0.1 + 0.15 + 0.16 + 0.2 = 0.61
+    fn kernel() -> Kernel {
+        let mut k = Kernel::testing();
+        k.add_task(b"a", noop, TaskPriority::CRITICAL, 1_000, 100);
+        k.add_task(b"b", noop, TaskPriority::HIGH, 2_000, 300);
+        k.add_task(b"c", noop, TaskPriority::NORMAL, 5_000, 800);
+        k.add_task(b"d", noop, TaskPriority::LOW, 10_000, 2_000);
+        k
+    }
+
+    #[test]
+    fn test_set_rejects_threshold_below_priority() {
+        let k = kernel();
+        let mut th = PreemptionThresholds::new();
+        assert!(!th.set(&k.scheduler, 1, TaskPriority::NORMAL));
+        assert!(!th.set(&k.scheduler, 9, TaskPriority::CRITICAL));
+        assert!(th.set(&k.scheduler, 2, TaskPriority::HIGH));
+        assert_eq!(th.threshold(2), Some(TaskPriority::HIGH));
+        assert_eq!(th.level(&k.scheduler, 3), Some(TaskPriority::LOW));
+        th.clear(2);
+        assert_eq!(th.threshold(2), None);
+    }
+
+    #[test]
+    fn test_without_thresholds_matches_dmda() {
+        let k = kernel();
+        let ours = PreemptionThresholds::new().response_times(&k.scheduler);
+        let dmda = k.scheduler.response_times(&Overhead::NONE);
+        for i in 0..4 {
+            assert_eq!(
+                ours.results[i].unwrap().worst_case_response,
+                dmda.results[i].unwrap().worst_case_response
+            );
+        }
+        assert!(ours.all_schedulable);
+    }
+
+    #[test]
+    fn test_threshold_blocks_higher_and_shields_task() {
+        let k = kernel();
+        let mut th = PreemptionThresholds::new();
+        // d は b までを締め出す: b, c は d 1 ジョブ分ブロックされる
+        th.set(&k.scheduler, 3, TaskPriority::HIGH);
+        let r = th.response_times(&k.scheduler);
+        let wcrt = |i: usize| r.results[i].unwrap().worst_case_response;
+        assert_eq!(wcrt(0), 100);
+        // b: 2000 (d) + 300 + a の 3 ジョブ
+        assert_eq!(wcrt(1), 2_600);
+        assert!(!r.results[1].unwrap().meets_deadline);
+        assert!(!th.is_schedulable(&k.scheduler));
+    }
+
+    #[test]
+    fn test_all_critical_is_run_to_completion() {
+        // カーネルの実行モデル: どのジョブも誰にもプリエンプトされない
+        let k = kernel();
+        let mut th = PreemptionThresholds::new();
+        for i in 0..4 {
+            assert!(th.set(&k.scheduler, i, TaskPriority::CRITICAL));
+            assert_eq!(th.non_preempting(&k.scheduler, i), 0b1111);
+        }
+        // d は最長ジョブ: a は d 1 ジョブ分ブロックされる
+        let r = th.response_times(&k.scheduler);
+        assert_eq!(r.results[0].unwrap().worst_case_response, 2_100);
+    }
+
+    #[test]
+    fn test_maximize_keeps_schedulable() {
+        let k = kernel();
+        let mut th = PreemptionThresholds::new();
+        assert!(th.maximize(&k.scheduler));
+        assert!(th.is_schedulable(&k.scheduler));
+        // b はもう c, d を横取りされない: 閾値が上がっている
+        assert!(th.threshold(3).is_some_and(|t| t < TaskPriority::LOW));
+        let before = PreemptionThresholds::new().response_times(&k.scheduler);
+        let after = th.response_times(&k.scheduler);
+        // 最低優先度の応答時間は悪化しない
+        assert!(
+            after.results[3].unwrap().worst_case_response
+                <= before.results[3].unwrap().worst_case_response
+        );
+    }
+
+    #[test]
+    fn test_timeline_honours_thresholds() {
+        let mut plain = kernel();
+        let mut sim = TimelineSim::new();
+        sim.run_for(&mut plain, 100_000, 10);
+
+        let mut k = kernel();
+        let mut th = PreemptionThresholds::new();
+        assert!(th.maximize(&k.scheduler));
+        let report = th.response_times(&k.scheduler);
+        let mut shielded = TimelineSim::new().with_thresholds(th);
+        shielded.run_for(&mut k, 100_000, 10);
+
+        assert!(shielded.preemptions() < sim.preemptions());
+        for i in 0..4 {
+            assert_eq!(shielded.misses(i), 0);
+            // 解析値 + ティックの発行遅れ以内
+            let bound = report.results[i].unwrap().worst_case_response + 10;
+            assert!(shielded.worst_response_us(i) <= bound, "task {i}");
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Preemptive timeline simulation (`std` feature and tests)
+//!
+//! [`Kernel::run_for`] executes a job in zero simulated time, so a task
+//! set can never overload it and no job is ever preempted. [`TimelineSim`]
+//! steps a kernel like `run_for` but charges every dispatched job its
+//! execution time — the declared WCET, or whatever an [`ExecTimeFn`]
+//! returns (e.g. measured times) — against the simulated timeline:
+//!
+//! - the CPU is busy until the job's execution time has elapsed, and
+//!   tasks of the same or lower priority are not dispatched meanwhile;
+//! - a higher-priority release at a tick preempts the job, which resumes
+//!   once the preempting jobs have completed — or, with
+//!   [`with_thresholds`](TimelineSim::with_thresholds), only a release
+//!   above the job's preemption threshold does;
+//! - a job finishing after its absolute deadline is a miss
+//!   ([`TimelineSim::misses`]), and its release-to-completion time feeds
+//!   [`TimelineSim::worst_response_us`].
+//!
+//! The task body still runs (and publishes its outputs) at dispatch; only
+//! the time accounting follows preemptive fixed-priority scheduling, so
+//! results can be checked against RMS and response-time analysis.
+//!
+//! ```rust
+//! use alice_rtos::timeline::TimelineSim;
+//! use alice_rtos::{Kernel, TaskPriority};
+//!
+//! fn noop(_: &mut [u8]) {}
+//!
+//! let mut kernel = Kernel::testing();
+//! kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 300).unwrap();
+//! let lo = kernel.add_task(b"lo", noop, TaskPriority::LOW, 3_000, 1_200).unwrap();
+//! let mut sim = TimelineSim::new();
+//! sim.run_for(&mut kernel, 30_000, 10);
+//! // lo is preempted twice by hi: R = 1200 + 2 × 300, plus one tick of
+//! // dispatch latency
+//! assert_eq!(sim.worst_response_us(lo), 1_810);
+//! assert_eq!(sim.misses(lo), 0);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::kernel::{Kernel, KernelStats};
+use crate::task::{Task, TaskMask, MAX_TASKS};
+use crate::threshold::PreemptionThresholds;
+use crate::timer::{tick_to_u64, ticks_until};
+
+/// Execution time (µs) charged to a job of task `idx`
+pub type ExecTimeFn = fn(idx: usize, task: &Task) -> u32;
+
+/// A dispatched job that has not completed yet
+#[derive(Clone, Copy)]
+struct Job {
+    /// Task slot
+    idx: u8,
+    /// Execution time still to run (µs)
+    remaining_us: u32,
+    /// Release time (µs)
+    release_us: u64,
+    /// Absolute deadline (µs)
+    deadline_us: u64,
+}
+
+/// Drives a [`Kernel`] with jobs that take time and can be preempted
+pub struct TimelineSim {
+    /// Execution time source (None = declared WCET)
+    exec: Option<ExecTimeFn>,
+    /// In-flight jobs, preempted ones below the running one
+    stack: [Job; MAX_TASKS],
+    /// Jobs on `stack`
+    depth: usize,
+    /// Jobs completed per task slot
+    completed: [u32; MAX_TASKS],
+    /// Jobs completed after their deadline per task slot
+    misses: [u32; MAX_TASKS],
+    /// Largest release-to-completion time per task slot (µs)
+    worst_response: [u32; MAX_TASKS],
+    /// Dispatches that preempted an in-flight job
+    preemptions: u32,
+    /// Time the CPU spent executing jobs (µs)
+    busy_us: u64,
+    /// Preemption thresholds of the dispatcher (none = plain priorities)
+    thresholds: PreemptionThresholds,
+}
+
+impl Default for TimelineSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimelineSim {
+    /// Simulation charging each job its declared WCET
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            exec: None,
+            stack: [Job {
+                idx: 0,
+                remaining_us: 0,
+                release_us: 0,
+                deadline_us: 0,
+            }; MAX_TASKS],
+            depth: 0,
+            completed: [0; MAX_TASKS],
+            misses: [0; MAX_TASKS],
+            worst_response: [0; MAX_TASKS],
+            preemptions: 0,
+            busy_us: 0,
+            thresholds: PreemptionThresholds::new(),
+        }
+    }
+
+    /// Charge the time returned by `exec` instead of the WCET
+    #[must_use]
+    pub const fn with_exec_time(mut self, exec: ExecTimeFn) -> Self {
+        self.exec = Some(exec);
+        self
+    }
+
+    /// Dispatch with preemption thresholds: a running job is only
+    /// preempted by tasks above its threshold
+    #[must_use]
+    pub const fn with_thresholds(mut self, thresholds: PreemptionThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Run the CPU for `delta_us`, then tick the kernel at the end of it
+    ///
+    /// Returns the task dispatched by the tick, if any.
+    pub fn step(&mut self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        let start = kernel.timer.now_us();
+        let mut used = 0u64;
+        while used < delta_us && self.depth > 0 {
+            let job = &mut self.stack[self.depth - 1];
+            let run = u64::from(job.remaining_us).min(delta_us - used);
+            job.remaining_us -= run as u32;
+            used += run;
+            self.busy_us += run;
+            if job.remaining_us == 0 {
+                self.depth -= 1;
+                self.complete(self.stack[self.depth], start + used);
+            }
+        }
+
+        let executed = kernel.tick_excluding(delta_us, self.blocked(kernel));
+        if let Some(idx) = executed {
+            self.dispatch(kernel, idx);
+        }
+        executed
+    }
+
+    /// Tasks that may not be dispatched while the running job is in flight
+    fn blocked(&self, kernel: &Kernel) -> TaskMask {
+        match self.depth.checked_sub(1) {
+            // 実行中ジョブの閾値以下の優先度は横取りできない
+            Some(top) => self
+                .thresholds
+                .non_preempting(&kernel.scheduler, self.stack[top].idx as usize),
+            None => 0,
+        }
+    }
+
+    /// Push the job just dispatched by the kernel
+    fn dispatch(&mut self, kernel: &Kernel, idx: usize) {
+        let Some(task) = kernel.scheduler.get_task(idx) else {
+            return;
+        };
+        let now = kernel.timer.now_us();
+        // 実行後の next_activation がこのジョブの締め切り
+        let (release_us, deadline_us) = if task.period_us == 0 {
+            (now, u64::MAX)
+        } else {
+            let until = tick_to_u64(ticks_until(kernel.scheduler.now_us(), task.next_activation));
+            let deadline = now + until;
+            (deadline.saturating_sub(u64::from(task.period_us)), deadline)
+        };
+        let remaining_us = self.exec.map_or(task.wcet_us, |exec| exec(idx, task));
+        if self.depth > 0 {
+            self.preemptions += 1;
+        }
+        self.stack[self.depth] = Job {
+            idx: idx as u8,
+            remaining_us,
+            release_us,
+            deadline_us,
+        };
+        self.depth += 1;
+    }
+
+    /// Account a job completing at `at_us`
+    fn complete(&mut self, job: Job, at_us: u64) {
+        let idx = job.idx as usize;
+        self.completed[idx] += 1;
+        if at_us > job.deadline_us {
+            self.misses[idx] += 1;
+        }
+        let response = at_us
+            .saturating_sub(job.release_us)
+            .min(u64::from(u32::MAX)) as u32;
+        self.worst_response[idx] = self.worst_response[idx].max(response);
+    }
+
+    /// Run for `total_us` in fixed `tick_us` steps (see [`Kernel::run_for`])
+    pub fn run_for(&mut self, kernel: &mut Kernel, total_us: u64, tick_us: u64) -> KernelStats {
+        let start = kernel.timer.now_us();
+        let mut tasks_executed = 0u64;
+        while kernel.timer.now_us() - start < total_us {
+            if self.step(kernel, tick_us).is_some() {
+                tasks_executed += 1;
+            }
+        }
+        kernel.stats(kernel.timer.now_us() - start, tasks_executed)
+    }
+
+    /// Jobs of task `idx` completed so far
+    #[must_use]
+    pub fn completed(&self, idx: usize) -> u32 {
+        self.completed.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Jobs of task `idx` that completed after their deadline
+    #[must_use]
+    pub fn misses(&self, idx: usize) -> u32 {
+        self.misses.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Largest release-to-completion time of task `idx` (µs)
+    #[must_use]
+    pub fn worst_response_us(&self, idx: usize) -> u32 {
+        self.worst_response.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Dispatches that preempted an in-flight job
+    #[must_use]
+    pub const fn preemptions(&self) -> u32 {
+        self.preemptions
+    }
+
+    /// Time the CPU spent executing jobs (µs)
+    #[must_use]
+    pub const fn busy_us(&self) -> u64 {
+        self.busy_us
+    }
+
+    /// Jobs dispatched but not completed yet
+    #[must_use]
+    pub const fn in_flight(&self) -> usize {
+        self.depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    fn noop(_: &mut [u8]) {}
+
+    /// U = 0.3 + 0.4 = 0.7
+    fn kernel() -> Kernel {
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 300);
+        kernel.add_task(b"lo", noop, TaskPriority::LOW, 3_000, 1_200);
+        kernel
+    }
+
+    #[test]
+    fn test_zero_time_run_for_hides_preemption() {
+        // 従来の run_for では応答時間が見えない
+        let mut k = kernel();
+        k.run_for(30_000, 10);
+        assert_eq!(k.scheduler.get_task(1).unwrap().deadline_misses, 0);
+
+        let mut k = kernel();
+        let mut sim = TimelineSim::new();
+        sim.run_for(&mut k, 30_000, 10);
+        assert_eq!(sim.worst_response_us(0), 310);
+        // lo は hi に 2 回横取りされる (応答時間解析 + 1 tick)
+        assert_eq!(sim.worst_response_us(1), 1_810);
+        assert!(sim.preemptions() >= 10);
+        assert_eq!((sim.misses(0), sim.misses(1)), (0, 0));
+        // 30 ms で 0.7 の負荷
+        assert!(
+            (20_000..=21_300).contains(&sim.busy_us()),
+            "{}",
+            sim.busy_us()
+        );
+    }
+
+    #[test]
+    fn test_overload_misses_deadlines() {
+        let mut k = kernel();
+        k.add_task(b"extra", noop, TaskPriority::NORMAL, 2_000, 800);
+        let mut sim = TimelineSim::new();
+        sim.run_for(&mut k, 60_000, 10);
+        // U = 1.1: 最低優先度が締め切りを落とす
+        assert_eq!(sim.misses(0), 0);
+        assert!(sim.misses(1) > 0);
+        assert!(sim.completed(1) < 20);
+    }
+
+    #[test]
+    fn test_exec_time_fn_replaces_wcet() {
+        fn half(_: usize, task: &Task) -> u32 {
+            task.wcet_us / 2
+        }
+        let mut k = kernel();
+        let mut sim = TimelineSim::new().with_exec_time(half);
+        sim.run_for(&mut k, 30_000, 10);
+        assert_eq!(sim.worst_response_us(1), 600 + 150 + 10);
+        assert_eq!(sim.completed(0), 30);
+    }
+}
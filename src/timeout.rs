@@ -0,0 +1,306 @@
+//! Channel receives with a timeout, without polling in task code
+//!
+//! "Wait up to 2 ms for a sample, then carry on degraded": bind the
+//! consumer — an event-driven task ([`Task::released_by_isr`]) — to its
+//! channel and a timeout in a static [`RecvTimeouts`], and tick the kernel
+//! through [`RecvTimeouts::tick`]. The task stays blocked (Sleeping, no
+//! CPU) until the channel holds data or the timeout has passed since its
+//! previous job ended; then it is released like an interrupt release. In
+//! the task body [`RecvTimeouts::recv`] returns the oldest item, or
+//! [`KernelError::TimedOut`] for a job released by the timeout.
+//!
+//! Timeouts are counted per task ([`timeouts`](RecvTimeouts::timeouts))
+//! and may be up to 2³¹ µs (≈ 35 min). A timed-out job that finds data
+//! queued after all takes the data.
+//!
+//! Without a kernel in the loop, [`Receiver::recv_deadline`] does the same
+//! check against explicit instants.
+//!
+//! ```rust
+//! use alice_rtos::channel::Channel;
+//! use alice_rtos::timeout::RecvTimeouts;
+//! use alice_rtos::{Kernel, KernelError, Task, TaskPriority};
+//! use core::sync::atomic::{AtomicU32, Ordering};
+//!
+//! const CTL: usize = 0;
+//! static SAMPLES: Channel<u16, 4> = Channel::new();
+//! static WAITS: RecvTimeouts = RecvTimeouts::new().bind(CTL, &SAMPLES, 2_000);
+//! static DEGRADED: AtomicU32 = AtomicU32::new(0);
+//!
+//! fn ctl(_: &mut [u8]) {
+//!     match WAITS.recv(CTL, &SAMPLES) {
+//!         Ok(_sample) => {}
+//!         Err(KernelError::TimedOut) => {
+//!             DEGRADED.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!         Err(_) => {}
+//!     }
+//! }
+//!
+//! let mut kernel = Kernel::testing();
+//! let task = Task::new(b"ctl", ctl, TaskPriority::HIGH, 0, 50).released_by_isr();
+//! kernel.scheduler.register(task).unwrap();
+//!
+//! // Data at 1 ms: ctl runs at once
+//! let (mut tx, _) = SAMPLES.split().unwrap();
+//! assert_eq!(WAITS.tick(&mut kernel, 1_000), None);
+//! tx.send(7);
+//! assert_eq!(WAITS.tick(&mut kernel, 100), Some(CTL));
+//! // Then silence: blocked for 2 ms, released degraded
+//! for _ in 0..19 {
+//!     assert_eq!(WAITS.tick(&mut kernel, 100), None);
+//! }
+//! assert_eq!(WAITS.tick(&mut kernel, 100), Some(CTL));
+//! assert_eq!(DEGRADED.load(Ordering::Relaxed), 1);
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::atomic::{AtomicU16, AtomicU32, Ordering};
+use crate::channel::Channel;
+use crate::error::{KernelError, KernelResult};
+use crate::kernel::SizedKernel;
+use crate::policy::SchedulingPolicy;
+use crate::task::{TaskMask, TaskState, MAX_TASKS};
+
+#[cfg(doc)]
+use crate::channel::Receiver;
+#[cfg(doc)]
+use crate::task::Task;
+
+/// Something a task can block on until it holds data
+pub trait WaitSource: Sync {
+    /// Is there data to receive?
+    fn has_data(&self) -> bool;
+}
+
+impl<T: Copy + Send, const N: usize> WaitSource for Channel<T, N> {
+    fn has_data(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+/// A task blocked on a source
+#[derive(Clone, Copy)]
+struct Binding {
+    /// What the task waits for
+    source: &'static dyn WaitSource,
+    /// Longest wait (µs)
+    timeout_us: u32,
+}
+
+/// Receive timeouts of the tasks bound to a channel (declare as a `static`)
+pub struct RecvTimeouts {
+    /// Source and timeout of each bound task
+    bindings: [Option<Binding>; MAX_TASKS],
+    /// End of each task's current wait (low 32 bits of kernel µs)
+    deadlines: [AtomicU32; MAX_TASKS],
+    /// Tasks whose current wait has a deadline
+    armed: AtomicU16,
+    /// Tasks released by their deadline, not yet seen by `recv`
+    timed_out: AtomicU16,
+    /// Waits that ended by the timeout
+    timeouts: [AtomicU32; MAX_TASKS],
+}
+
+impl Default for RecvTimeouts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecvTimeouts {
+    /// No task bound
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            bindings: [None; MAX_TASKS],
+            deadlines: [const { AtomicU32::new(0) }; MAX_TASKS],
+            armed: AtomicU16::new(0),
+            timed_out: AtomicU16::new(0),
+            timeouts: [const { AtomicU32::new(0) }; MAX_TASKS],
+        }
+    }
+
+    /// Block task `idx` on `source` for at most `timeout_us` per wait
+    ///
+    /// Bind event-driven tasks only: a task released by its period runs
+    /// on its period regardless. `idx` must be below `MAX_TASKS`.
+    #[must_use]
+    pub const fn bind(
+        mut self,
+        idx: usize,
+        source: &'static dyn WaitSource,
+        timeout_us: u32,
+    ) -> Self {
+        self.bindings[idx] = Some(Binding { source, timeout_us });
+        self
+    }
+
+    /// Tick the kernel, releasing bound tasks on data or at their deadline
+    ///
+    /// A task's wait starts when its previous job ends (for the first
+    /// wait, at the first tick). Returns the task executed, if any.
+    pub fn tick<const SCRATCH: usize, P: SchedulingPolicy>(
+        &self,
+        kernel: &mut SizedKernel<SCRATCH, P>,
+        delta_us: u64,
+    ) -> Option<usize> {
+        let now = kernel.timer.now_us() as u32;
+        let at = now.wrapping_add(delta_us as u32);
+        for (idx, binding) in self.bindings.iter().enumerate() {
+            let Some(binding) = binding else {
+                continue;
+            };
+            if !kernel
+                .scheduler
+                .get_task(idx)
+                .is_some_and(|t| t.state == TaskState::Sleeping)
+            {
+                continue;
+            }
+            let bit: TaskMask = 1 << idx;
+            if self.armed.load(Ordering::Relaxed) & bit == 0 {
+                self.arm(idx, now, binding.timeout_us);
+            }
+            if binding.source.has_data() {
+                kernel.scheduler.release_from_isr(idx);
+            } else if reached(at, self.deadlines[idx].load(Ordering::Relaxed)) {
+                // 期限切れ: 縮退動作のためにリリース
+                self.timed_out.fetch_or(bit, Ordering::Release);
+                self.timeouts[idx].fetch_add(1, Ordering::Relaxed);
+                kernel.scheduler.release_from_isr(idx);
+            }
+        }
+
+        let executed = kernel.tick(delta_us);
+        if let Some(idx) = executed {
+            if let Some(binding) = self.bindings[idx] {
+                self.arm(idx, kernel.timer.now_us() as u32, binding.timeout_us);
+            }
+        }
+        executed
+    }
+
+    /// Oldest item of `channel` for bound task `idx` (call from its body)
+    ///
+    /// Returns [`KernelError::TimedOut`] when the job was released by the
+    /// timeout and nothing arrived since, [`KernelError::WouldBlock`] when
+    /// the channel is empty otherwise.
+    pub fn recv<T: Copy, const N: usize>(
+        &self,
+        idx: usize,
+        channel: &Channel<T, N>,
+    ) -> KernelResult<T> {
+        let bit: TaskMask = 1 << (idx % MAX_TASKS);
+        let timed_out = self.timed_out.fetch_and(!bit, Ordering::Acquire) & bit != 0;
+        match channel.pop() {
+            Some(item) => Ok(item),
+            None if timed_out => Err(KernelError::TimedOut),
+            None => Err(KernelError::WouldBlock),
+        }
+    }
+
+    /// Waits of task `idx` that ended by the timeout
+    #[must_use]
+    pub fn timeouts(&self, idx: usize) -> u32 {
+        self.timeouts
+            .get(idx)
+            .map_or(0, |n| n.load(Ordering::Relaxed))
+    }
+
+    /// Start a wait of `idx` at kernel time `now`
+    fn arm(&self, idx: usize, now: u32, timeout_us: u32) {
+        self.deadlines[idx].store(now.wrapping_add(timeout_us), Ordering::Relaxed);
+        self.armed.fetch_or(1 << idx, Ordering::Relaxed);
+    }
+}
+
+/// Has 32-bit time `now` reached `deadline`? (wrapping)
+const fn reached(now: u32, deadline: u32) -> bool {
+    now.wrapping_sub(deadline) as i32 >= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::task::{Task, TaskPriority};
+    use crate::timer::WrappingInstant;
+
+    static CH: Channel<u8, 4> = Channel::new();
+    static WAITS: RecvTimeouts = RecvTimeouts::new().bind(1, &CH, 500);
+    static GOT: AtomicU32 = AtomicU32::new(0);
+    static LATE: AtomicU32 = AtomicU32::new(0);
+
+    fn noop(_: &mut [u8]) {}
+
+    fn consumer(_: &mut [u8]) {
+        match WAITS.recv(1, &CH) {
+            Ok(v) => {
+                GOT.fetch_add(u32::from(v), Ordering::Relaxed);
+            }
+            Err(e) => {
+                assert_eq!(e, KernelError::TimedOut);
+                LATE.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocked_until_data_or_timeout() {
+        let mut k = Kernel::testing();
+        k.add_task(b"hi", noop, TaskPriority::HIGH, 1_000, 10);
+        let task = Task::new(b"rx", consumer, TaskPriority::LOW, 0, 10).released_by_isr();
+        assert_eq!(k.scheduler.register(task), Some(1));
+        let (mut tx, _) = CH.split().unwrap();
+
+        assert_eq!(WAITS.tick(&mut k, 0), Some(0));
+        // 400 µs 経過: まだ待つ
+        assert_eq!(WAITS.tick(&mut k, 400), None);
+        tx.send(5);
+        assert_eq!(WAITS.tick(&mut k, 50), Some(1));
+        assert_eq!(GOT.load(Ordering::Relaxed), 5);
+        // 待ちはジョブ終了 (t = 450) から: t = 950 で期限
+        assert_eq!(WAITS.tick(&mut k, 450), None);
+        assert_eq!(WAITS.tick(&mut k, 50), Some(1));
+        assert_eq!(LATE.load(Ordering::Relaxed), 1);
+        assert_eq!(WAITS.timeouts(1), 1);
+        // 優先度の高い hi が先 (t = 1 000)、rx はその次のティック
+        tx.send(2);
+        assert_eq!(WAITS.tick(&mut k, 50), Some(0));
+        assert_eq!(WAITS.tick(&mut k, 10), Some(1));
+        assert_eq!(GOT.load(Ordering::Relaxed), 7);
+        assert_eq!(WAITS.timeouts(1), 1);
+        assert_eq!(WAITS.timeouts(MAX_TASKS), 0);
+    }
+
+    #[test]
+    fn test_recv_deadline_on_receiver() {
+        static SAMPLES: Channel<u8, 4> = Channel::new();
+        let (mut tx, mut rx) = SAMPLES.split().unwrap();
+        let deadline = WrappingInstant::new(2_000);
+        assert_eq!(
+            rx.recv_deadline(WrappingInstant::new(1_000), deadline),
+            Err(KernelError::WouldBlock)
+        );
+        tx.send(3);
+        assert_eq!(
+            rx.recv_deadline(WrappingInstant::new(2_500), deadline),
+            Ok(3)
+        );
+        assert_eq!(
+            rx.recv_deadline(WrappingInstant::new(2_000), deadline),
+            Err(KernelError::TimedOut)
+        );
+    }
+
+    #[test]
+    fn test_unflagged_empty_recv_would_block() {
+        static EMPTY: Channel<u8, 2> = Channel::new();
+        let waits = RecvTimeouts::new().bind(3, &EMPTY, 100);
+        assert_eq!(waits.recv(3, &EMPTY), Err(KernelError::WouldBlock));
+        assert!(reached(5, u32::MAX - 5));
+        assert!(!reached(u32::MAX - 5, 5));
+    }
+}
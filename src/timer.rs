@@ -4,18 +4,184 @@
 //! On real hardware, this wraps `SysTick` (Cortex-M) or MTIME (RISC-V).
 //! For testing, uses a software counter.
 //!
+//! [`SysTimer::discipline`] steers the time base towards an external
+//! reference (GPS PPS, PTP, host clock): a measured rate error is
+//! corrected continuously and a measured offset is slewed out at no more
+//! than [`MAX_SLEW_PPM`], so kernel time never steps and never runs
+//! backwards and the scheduler's releases stay aligned with the
+//! reference.
+//!
 //! Author: Moroya Sakamoto
 
+/// Kernel time stamp (µs) on the per-tick path
+///
+/// `u64` by default. With the `tick-u32` feature it is a wrapping `u32`
+/// (≈ 71.6 min range), so 8/16-bit cores avoid 64-bit arithmetic in every
+/// tick. Compare stamps as [`WrappingInstant`]s (or with [`tick_reached`]
+/// / [`ticks_until`] / [`ticks_since`]), which stay correct across the
+/// wrap as long as the instants are less than half the range apart
+/// (2³¹ µs ≈ 35 min with `tick-u32`).
+#[cfg(not(feature = "tick-u32"))]
+pub type Tick = u64;
+
+/// Kernel time stamp (µs) on the per-tick path (wrapping, `tick-u32`)
+#[cfg(feature = "tick-u32")]
+pub type Tick = u32;
+
+/// Signed distance between two [`Tick`]s (µs)
+#[cfg(not(feature = "tick-u32"))]
+pub type TickDiff = i64;
+
+/// Signed distance between two [`Tick`]s (µs, `tick-u32`)
+#[cfg(feature = "tick-u32")]
+pub type TickDiff = i32;
+
+/// Point in kernel time, ordered by signed wrapping difference
+///
+/// `a >= b` on raw ticks breaks once the counter wraps: the instant just
+/// after the wrap compares as the earliest. A `WrappingInstant` compares
+/// by the sign of `a - b` taken modulo the tick range instead, so order,
+/// [`until`](Self::until) and [`since`](Self::since) hold across the wrap
+/// for instants less than half the range apart. The scheduler and
+/// [`Deadline`] compare time this way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct WrappingInstant(pub Tick);
+
+impl WrappingInstant {
+    /// Instant at raw tick `t`
+    #[inline(always)]
+    #[must_use]
+    pub const fn new(t: Tick) -> Self {
+        Self(t)
+    }
+
+    /// Instant from a 64-bit µs time stamp (low word with `tick-u32`)
+    #[inline(always)]
+    #[must_use]
+    #[allow(clippy::unnecessary_cast)] // u64 → u64 without `tick-u32`
+    pub const fn from_us(us: u64) -> Self {
+        Self(us as Tick)
+    }
+
+    /// Raw tick value
+    #[inline(always)]
+    #[must_use]
+    pub const fn ticks(self) -> Tick {
+        self.0
+    }
+
+    /// Signed time from `earlier` to `self` (negative if `self` is earlier)
+    #[inline(always)]
+    #[must_use]
+    pub const fn diff(self, earlier: Self) -> TickDiff {
+        self.0.wrapping_sub(earlier.0) as TickDiff
+    }
+
+    /// Has `self` reached instant `at`?
+    #[inline(always)]
+    #[must_use]
+    pub const fn has_reached(self, at: Self) -> bool {
+        self.diff(at) >= 0
+    }
+
+    /// Is `self` strictly before `other`?
+    #[inline(always)]
+    #[must_use]
+    pub const fn is_before(self, other: Self) -> bool {
+        self.diff(other) < 0
+    }
+
+    /// Time from `self` until `at` (0 once reached)
+    #[inline(always)]
+    #[must_use]
+    pub const fn until(self, at: Self) -> Tick {
+        if self.has_reached(at) {
+            0
+        } else {
+            at.0.wrapping_sub(self.0)
+        }
+    }
+
+    /// Time elapsed from `then` to `self` (0 if `then` is still ahead)
+    #[inline(always)]
+    #[must_use]
+    pub const fn since(self, then: Self) -> Tick {
+        if self.has_reached(then) {
+            self.0.wrapping_sub(then.0)
+        } else {
+            0
+        }
+    }
+
+    /// Instant `delta` µs later (wrapping)
+    #[inline(always)]
+    #[must_use]
+    pub const fn add(self, delta: Tick) -> Self {
+        Self(self.0.wrapping_add(delta))
+    }
+}
+
+impl PartialOrd for WrappingInstant {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.diff(*other).cmp(&0))
+    }
+}
+
+/// Has time `now` reached instant `at`?
+#[inline(always)]
+#[must_use]
+pub const fn tick_reached(now: Tick, at: Tick) -> bool {
+    WrappingInstant(now).has_reached(WrappingInstant(at))
+}
+
+/// Time from `now` until `at` (0 once reached)
+#[inline(always)]
+#[must_use]
+pub const fn ticks_until(now: Tick, at: Tick) -> Tick {
+    WrappingInstant(now).until(WrappingInstant(at))
+}
+
+/// Time elapsed from `then` to `now` (0 if `then` is still ahead)
+#[inline(always)]
+#[must_use]
+pub const fn ticks_since(now: Tick, then: Tick) -> Tick {
+    WrappingInstant(now).since(WrappingInstant(then))
+}
+
+/// Largest rate change used to slew out an offset (ppm, 1 ns per µs)
+pub const MAX_SLEW_PPM: u32 = 1_000;
+
+/// Largest rate error accepted by [`SysTimer::discipline`] (ppb)
+pub const MAX_DRIFT_PPB: i32 = 1_000_000;
+
+/// Unit of the sub-µs correction carry (fs, 1/32 ns)
+const FRAC_FS: i64 = 31_250;
+
+/// Widen a tick value to `u64`
+#[inline(always)]
+#[must_use]
+#[allow(clippy::unnecessary_cast)] // u64 → u64 without `tick-u32`
+pub const fn tick_to_u64(t: Tick) -> u64 {
+    t as u64
+}
+
 /// System timer
 ///
-/// Size: 16 bytes
+/// Size: 24 bytes (20 with `tick-u32`)
 pub struct SysTimer {
-    /// Current tick count (microseconds)
-    ticks_us: u64,
-    /// Timer frequency (ticks per microsecond)
-    ticks_per_us: u32,
+    /// Current tick count (microseconds; low word with `tick-u32`)
+    ticks_us: Tick,
     /// Overflow count
     overflows: u32,
+    /// Timer frequency (ticks per microsecond; ≤ 4294 for a `u32` Hz clock)
+    ticks_per_us: u16,
+    /// Sub-µs correction carried to the next advance (units of `FRAC_FS`)
+    frac: i16,
+    /// Rate correction (ppb, positive = local clock slow)
+    drift_ppb: i32,
+    /// Offset still to be slewed out (ns, positive = local clock behind)
+    slew_ns: i32,
 }
 
 impl SysTimer {
@@ -26,8 +192,11 @@ impl SysTimer {
     pub const fn new(clock_hz: u32) -> Self {
         Self {
             ticks_us: 0,
-            ticks_per_us: clock_hz / 1_000_000,
             overflows: 0,
+            ticks_per_us: (clock_hz / 1_000_000) as u16,
+            frac: 0,
+            drift_ppb: 0,
+            slew_ns: 0,
         }
     }
 
@@ -36,48 +205,175 @@ impl SysTimer {
     pub const fn software() -> Self {
         Self {
             ticks_us: 0,
-            ticks_per_us: 1,
             overflows: 0,
+            ticks_per_us: 1,
+            frac: 0,
+            drift_ppb: 0,
+            slew_ns: 0,
+        }
+    }
+
+    /// Advance time by `us` microseconds of the local clock
+    ///
+    /// Applies the [`discipline`](Self::discipline) correction and returns
+    /// the microseconds actually added to kernel time.
+    pub const fn advance(&mut self, us: u64) -> u64 {
+        let us = self.disciplined(us);
+        self.advance_raw(us);
+        us
+    }
+
+    /// `us` local microseconds corrected by the current discipline
+    const fn disciplined(&mut self, us: u64) -> u64 {
+        if self.drift_ppb == 0 && self.slew_ns == 0 && self.frac == 0 {
+            return us;
+        }
+        let elapsed = if us > i64::MAX as u64 / MAX_DRIFT_PPB as u64 {
+            i64::MAX / MAX_DRIFT_PPB as i64
+        } else {
+            us as i64
+        };
+        // 補正量は fs (µs × ppb) で積算
+        let mut fs = self.frac as i64 * FRAC_FS + elapsed * self.drift_ppb as i64;
+        if self.slew_ns != 0 {
+            // 1 µs あたり最大 1 ns (MAX_SLEW_PPM) だけ寄せる
+            let limit = elapsed * MAX_SLEW_PPM as i64 / 1_000;
+            let slew = self.slew_ns as i64;
+            let step = if slew > limit {
+                limit
+            } else if slew < -limit {
+                -limit
+            } else {
+                slew
+            };
+            self.slew_ns -= step as i32;
+            fs += step * 1_000_000;
+        }
+        let correction = fs / 1_000_000_000;
+        // 端数は最も近い 1/32 ns に丸めて持ち越す
+        let rem = fs % 1_000_000_000;
+        let half = if rem < 0 { -FRAC_FS / 2 } else { FRAC_FS / 2 };
+        self.frac = ((rem + half) / FRAC_FS) as i16;
+        // 補正は経過時間の 0.2 % 以内なので逆行しない
+        let corrected = us as i64 + correction;
+        if corrected < 0 {
+            0
+        } else {
+            corrected as u64
         }
     }
 
-    /// Advance time by microseconds
-    pub const fn advance(&mut self, us: u64) {
-        let new = self.ticks_us.wrapping_add(us);
+    /// Advance kernel time by exactly `us` microseconds
+    const fn advance_raw(&mut self, us: u64) {
+        let new = self.ticks_us.wrapping_add(us as Tick);
         if new < self.ticks_us {
-            self.overflows += 1;
+            self.overflows = self.overflows.wrapping_add(1);
+        }
+        #[cfg(feature = "tick-u32")]
+        {
+            // Whole 2³² µs spans that the low word cannot carry
+            self.overflows = self.overflows.wrapping_add((us >> 32) as u32);
         }
         self.ticks_us = new;
     }
 
     /// Current time in microseconds
+    ///
+    /// With `tick-u32` the overflow count extends the counter to 64 bits.
     #[must_use]
     pub const fn now_us(&self) -> u64 {
+        #[cfg(not(feature = "tick-u32"))]
+        {
+            self.ticks_us
+        }
+        #[cfg(feature = "tick-u32")]
+        {
+            ((self.overflows as u64) << 32) | self.ticks_us as u64
+        }
+    }
+
+    /// Current time as a [`Tick`] (the low word with `tick-u32`)
+    #[must_use]
+    pub const fn now_tick(&self) -> Tick {
         self.ticks_us
     }
 
     /// Current time in milliseconds
     #[must_use]
     pub const fn now_ms(&self) -> u64 {
-        self.ticks_us / 1000
+        self.now_us() / 1000
     }
 
     /// Current time in seconds (as f32)
     #[must_use]
     pub fn now_secs(&self) -> f32 {
-        self.ticks_us as f32 / 1_000_000.0
+        self.now_us() as f32 / 1_000_000.0
+    }
+
+    /// Steer the time base towards an external reference
+    ///
+    /// `offset_ns` is reference minus kernel time and `drift_ppb` the rate
+    /// error of the local clock (both positive when the local clock is
+    /// behind / slow), e.g. from the last two GPS PPS edges. The rate is
+    /// corrected from now on; the offset is slewed out by running up to
+    /// [`MAX_SLEW_PPM`] fast or slow, so time stays monotonic and no
+    /// release is skipped. Each call replaces the previous correction —
+    /// the new measurement already includes what was not slewed out yet.
+    /// `drift_ppb` is clamped to ±[`MAX_DRIFT_PPB`] and `offset_ns` to
+    /// ±2.1 s (larger offsets are slewed over further calls).
+    ///
+    /// The sub-µs part of the correction is carried between advances at
+    /// 1/32 ns resolution (at most 16 ppb of rate error at a 1 kHz tick).
+    /// Applies to time advanced with [`advance`](Self::advance) (and
+    /// `Kernel::tick`); `Kernel::tick_from` follows the hardware counter.
+    pub const fn discipline(&mut self, offset_ns: i64, drift_ppb: i32) {
+        self.slew_ns = if offset_ns > i32::MAX as i64 {
+            i32::MAX
+        } else if offset_ns < i32::MIN as i64 {
+            i32::MIN
+        } else {
+            offset_ns as i32
+        };
+        self.drift_ppb = if drift_ppb > MAX_DRIFT_PPB {
+            MAX_DRIFT_PPB
+        } else if drift_ppb < -MAX_DRIFT_PPB {
+            -MAX_DRIFT_PPB
+        } else {
+            drift_ppb
+        };
+    }
+
+    /// Rate correction currently applied (ppb)
+    #[must_use]
+    pub const fn drift_ppb(&self) -> i32 {
+        self.drift_ppb
     }
 
-    /// Reset timer
+    /// Offset not yet slewed out (ns)
+    #[must_use]
+    pub const fn slew_remaining_ns(&self) -> i32 {
+        self.slew_ns
+    }
+
+    /// Reset timer (time and discipline)
     pub const fn reset(&mut self) {
         self.ticks_us = 0;
         self.overflows = 0;
+        self.frac = 0;
+        self.drift_ppb = 0;
+        self.slew_ns = 0;
+    }
+
+    /// Set time and overflow count (snapshot restore)
+    pub(crate) const fn restore(&mut self, ticks_us: u64, overflows: u32) {
+        self.ticks_us = ticks_us as Tick;
+        self.overflows = overflows;
     }
 
     /// Timer frequency (ticks per microsecond)
     #[must_use]
     pub const fn ticks_per_us(&self) -> u32 {
-        self.ticks_per_us
+        self.ticks_per_us as u32
     }
 
     /// Number of overflows
@@ -89,49 +385,66 @@ impl SysTimer {
     /// Elapsed microseconds since a reference point
     #[must_use]
     pub const fn elapsed_since(&self, reference: u64) -> u64 {
-        self.ticks_us.wrapping_sub(reference)
+        self.now_us().wrapping_sub(reference)
     }
 
     /// Delay for given microseconds (busy-wait, for software timer)
     pub const fn delay_us(&mut self, us: u64) {
-        self.advance(us);
+        self.advance_raw(us);
     }
 }
 
+/// Hardware tick source with a one-shot compare interrupt
+///
+/// Implemented by the board support layer (`SysTick` reload, RISC-V
+/// `mtimecmp`, …). The tickless kernel loop programs the compare
+/// register for the next activation instead of taking a periodic tick.
+pub trait TickSource {
+    /// Current hardware time in microseconds
+    fn now_us(&self) -> u64;
+
+    /// Arm the compare interrupt to fire at absolute time `at_us`
+    fn set_compare(&mut self, at_us: u64);
+}
+
 /// Deadline tracker for a single task
+///
+/// Instants are taken as [`WrappingInstant`]s, so a deadline stays
+/// correct when the time stamps wrap between `start` and `current`.
 pub struct Deadline {
     /// Activation time
-    start: u64,
+    start: WrappingInstant,
     /// Deadline (absolute)
-    deadline: u64,
+    deadline: WrappingInstant,
 }
 
 impl Deadline {
     /// Create a new deadline
     #[must_use]
     pub const fn new(start: u64, period_us: u32) -> Self {
+        let start = WrappingInstant::from_us(start);
         Self {
             start,
-            deadline: start + period_us as u64,
+            deadline: start.add(period_us as Tick),
         }
     }
 
     /// Check if deadline is met
     #[must_use]
     pub const fn is_met(&self, current: u64) -> bool {
-        current <= self.deadline
+        self.deadline.has_reached(WrappingInstant::from_us(current))
     }
 
     /// Remaining time until deadline (0 if missed)
     #[must_use]
     pub const fn remaining(&self, current: u64) -> u64 {
-        self.deadline.saturating_sub(current)
+        tick_to_u64(WrappingInstant::from_us(current).until(self.deadline))
     }
 
-    /// Elapsed since start
+    /// Elapsed since start (0 before start)
     #[must_use]
     pub const fn elapsed(&self, current: u64) -> u64 {
-        current.wrapping_sub(self.start)
+        tick_to_u64(WrappingInstant::from_us(current).since(self.start))
     }
 }
 
@@ -245,7 +558,7 @@ mod tests {
     fn test_timer_overflows_increments() {
         let mut timer = SysTimer::software();
         // wrapping_add で桁あふれを起こす
-        timer.advance(u64::MAX);
+        timer.advance(tick_to_u64(Tick::MAX));
         timer.advance(1);
         assert!(timer.overflows() >= 1);
     }
@@ -268,9 +581,10 @@ mod tests {
     #[test]
     fn test_deadline_remaining_zero_when_past() {
         let dl = Deadline::new(0, 100);
-        // saturating_sub → 過ぎた後は 0
+        // 過ぎた後は 0 (u64::MAX は桁あふれ前の時刻 = 開始前として扱う)
         assert_eq!(dl.remaining(200), 0);
-        assert_eq!(dl.remaining(u64::MAX), 0);
+        assert_eq!(dl.remaining(1 << 30), 0);
+        assert_eq!(dl.remaining(u64::MAX), 101);
     }
 
     #[test]
@@ -288,4 +602,126 @@ mod tests {
         let ref_time = u64::MAX;
         assert_eq!(timer.elapsed_since(ref_time), 1);
     }
+
+    #[test]
+    fn test_tick_comparisons() {
+        assert!(tick_reached(5, 5));
+        assert!(!tick_reached(4, 5));
+        assert_eq!(ticks_until(4, 10), 6);
+        assert_eq!(ticks_until(10, 4), 0);
+        assert_eq!(ticks_since(10, 4), 6);
+        assert_eq!(ticks_since(4, 10), 0);
+    }
+
+    #[test]
+    fn test_tick_comparisons_across_wrap() {
+        // 桁あふれを跨いでも前後関係が保たれる
+        let before = Tick::MAX - 9;
+        let after = before.wrapping_add(20);
+        assert!(tick_reached(after, before));
+        assert!(!tick_reached(before, after));
+        assert_eq!(ticks_until(before, after), 20);
+        assert_eq!(ticks_since(after, before), 20);
+    }
+
+    #[test]
+    fn test_wrapping_instant_order_across_wrap() {
+        let before = WrappingInstant::new(Tick::MAX - 4);
+        let after = before.add(10);
+        assert_eq!(after.ticks(), 5);
+        // 生の値では after < before だが、差の符号で比較する
+        assert!(after > before);
+        assert!(before.is_before(after));
+        assert!(after.has_reached(before) && !before.has_reached(after));
+        assert_eq!((after.diff(before), before.diff(after)), (10, -10));
+        assert_eq!((before.until(after), after.until(before)), (10, 0));
+        assert_eq!((after.since(before), before.since(after)), (10, 0));
+        assert_eq!(after.partial_cmp(&after), Some(core::cmp::Ordering::Equal));
+    }
+
+    #[test]
+    fn test_deadline_across_wrap() {
+        let start = tick_to_u64(Tick::MAX) - 99;
+        let dl = Deadline::new(start, 1000);
+        // 締切は桁あふれの向こう側 (生の値 900)
+        let wrapped = start.wrapping_add(950) & tick_to_u64(Tick::MAX);
+        assert!(dl.is_met(wrapped));
+        assert_eq!(dl.remaining(wrapped), 50);
+        assert_eq!(dl.elapsed(wrapped), 950);
+        let late = start.wrapping_add(1001) & tick_to_u64(Tick::MAX);
+        assert!(!dl.is_met(late));
+        assert_eq!(dl.remaining(late), 0);
+    }
+
+    #[cfg(feature = "tick-u32")]
+    #[test]
+    fn test_u32_tick_extends_to_64_bits() {
+        let mut timer = SysTimer::software();
+        timer.advance(u32::MAX as u64);
+        timer.advance(2);
+        assert_eq!(timer.now_tick(), 1);
+        assert_eq!(timer.overflows(), 1);
+        assert_eq!(timer.now_us(), (1 << 32) + 1);
+        // 2³² µs を超える一括の前進も桁上げされる
+        timer.advance(3 << 32);
+        assert_eq!(timer.now_us(), (4 << 32) + 1);
+    }
+
+    #[test]
+    fn test_discipline_slews_without_stepping() {
+        let mut timer = SysTimer::software();
+        // 参照より 500 ns 遅れている
+        timer.discipline(500, 0);
+        let mut prev = timer.now_us();
+        let mut added = 0;
+        for _ in 0..1_000 {
+            added += timer.advance(1);
+            assert!(timer.now_us() >= prev);
+            prev = timer.now_us();
+        }
+        // 1 ns/µs で寄せるので 500 µs 後に完了
+        assert_eq!(timer.slew_remaining_ns(), 0);
+        assert_eq!(added, 1_000);
+        timer.discipline(2_000, 0);
+        assert_eq!(timer.advance(1_000), 1_001);
+        assert_eq!(timer.advance(1_000), 1_001);
+        assert_eq!(timer.slew_remaining_ns(), 0);
+        assert_eq!(timer.now_us(), 3_002);
+
+        // 進んでいる場合は遅く進めるだけで逆行しない
+        let mut ahead = SysTimer::software();
+        ahead.discipline(-3_000, 0);
+        assert_eq!(ahead.advance(1_000), 999);
+        assert_eq!(ahead.advance(10_000), 9_998);
+        assert_eq!(ahead.slew_remaining_ns(), 0);
+        assert_eq!(ahead.now_us(), 10_997);
+    }
+
+    #[test]
+    fn test_discipline_corrects_drift() {
+        let mut timer = SysTimer::software();
+        // ローカル水晶が 25 ppm 遅い
+        timer.discipline(0, 25_000);
+        for _ in 0..1_000 {
+            timer.advance(1_000);
+        }
+        assert_eq!(timer.now_us(), 1_000_025);
+        assert_eq!(timer.drift_ppb(), 25_000);
+
+        // 1 µs 未満の補正も持ち越して失わない
+        let mut fine = SysTimer::software();
+        fine.discipline(0, -334);
+        for _ in 0..3_000 {
+            fine.advance(1_000);
+        }
+        // 3 s で -1.002 µs (1/32 ns 単位の丸め込み)
+        assert_eq!(fine.now_us(), 2_999_999);
+
+        timer.discipline(i64::MAX, i32::MIN);
+        assert_eq!(timer.drift_ppb(), -MAX_DRIFT_PPB);
+        assert_eq!(timer.slew_remaining_ns(), i32::MAX);
+        timer.reset();
+        assert_eq!((timer.drift_ppb(), timer.slew_remaining_ns()), (0, 0));
+        assert_eq!(timer.advance(7), 7);
+    }
 }
@@ -0,0 +1,593 @@
+//! Compact binary execution trace for RTT / ITM style byte sinks
+//!
+//! Every trace entry is encoded in 4 or 8 bytes and queued in a static
+//! [`TraceBuffer`]; a low-priority task (or the idle loop) calls
+//! [`TraceBuffer::drain`], which hands the queued bytes to a user
+//! `fn(&[u8])` sink — a SEGGER RTT up-channel, an ITM stimulus port, a
+//! UART. Recording never blocks: when the buffer is full (or another
+//! context is recording at the same moment) the entry is dropped and
+//! counted, and the next entry that fits is preceded by a
+//! [`TraceEntry::Lost`] marker so the host sees the gap.
+//!
+//! Wire format (little endian), timestamps as µs since the previous entry
+//! (the first entry since time 0):
+//!
+//! | Form | Bytes | Layout |
+//! |------|-------|--------|
+//! | short | 4 | `kind`, `task`, `dt: u16` |
+//! | long | 8 | `kind \| 0x80`, `task`, `arg: u16`, `dt: u32` |
+//!
+//! The long form is used for entries with an argument (scratch bytes,
+//! target mode, lost count) or gaps longer than 65 ms. [`TraceDecoder`]
+//! turns the stream back into entries with absolute timestamps.
+//!
+//! ```rust
+//! use alice_rtos::trace::{TraceBuffer, TraceDecoder, TraceEntry};
+//!
+//! fn rtt_write(bytes: &[u8]) {
+//!     // rtt_channel.write(bytes);
+//!     let _ = bytes;
+//! }
+//!
+//! static TRACE: TraceBuffer<256> = TraceBuffer::new(rtt_write);
+//!
+//! assert!(TRACE.push(TraceEntry::Dispatch { idx: 1 }, 1_000));
+//! assert_eq!(TRACE.pending(), 4);
+//! assert_eq!(TRACE.drain(), 4);
+//!
+//! let mut decoder = TraceDecoder::new();
+//! let (entry, at_us, len) = decoder.next(&[0x00, 0x01, 0xE8, 0x03]).unwrap();
+//! assert_eq!((entry, at_us, len), (TraceEntry::Dispatch { idx: 1 }, 1_000, 4));
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use core::cell::UnsafeCell;
+
+use crate::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use crate::events::KernelEvent;
+use crate::kernel::Kernel;
+use crate::task::MAX_TASKS;
+
+/// Largest encoded entry (bytes)
+pub const MAX_ENTRY_BYTES: usize = 8;
+
+/// Long-form flag in the kind byte
+const LONG: u8 = 0x80;
+
+/// Entry kinds on the wire
+const KIND_DISPATCH: u8 = 0;
+const KIND_COMPLETE: u8 = 1;
+const KIND_REGISTERED: u8 = 2;
+const KIND_DEADLINE_MISS: u8 = 3;
+const KIND_SCRATCH_OVERRUN: u8 = 4;
+const KIND_FAULT: u8 = 5;
+const KIND_BUDGET: u8 = 6;
+const KIND_SHED: u8 = 7;
+const KIND_RESTORED: u8 = 8;
+const KIND_MODE: u8 = 9;
+const KIND_OVERCOMMIT: u8 = 10;
+const KIND_CORRUPTED: u8 = 11;
+const KIND_REPLICA: u8 = 12;
+const KIND_LOCK_OVERRUN: u8 = 13;
+const KIND_JOBS_LOST: u8 = 14;
+const KIND_LOST: u8 = 0x7F;
+
+/// One trace entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TraceEntry {
+    /// Task `idx` was dispatched
+    Dispatch {
+        /// Task slot
+        idx: u8,
+    },
+    /// Job of `idx` completed (e.g. from the `hooks` exit hook)
+    Complete {
+        /// Task slot
+        idx: u8,
+    },
+    /// Kernel event (timestamps of events come from the stream)
+    Event(KernelEvent),
+    /// `count` entries were dropped before this point
+    Lost {
+        /// Dropped entries (saturating)
+        count: u16,
+    },
+}
+
+impl From<KernelEvent> for TraceEntry {
+    fn from(event: KernelEvent) -> Self {
+        Self::Event(event)
+    }
+}
+
+impl TraceEntry {
+    /// Wire kind, task byte and argument (if any)
+    const fn fields(&self) -> (u8, u8, Option<u16>) {
+        match *self {
+            Self::Dispatch { idx } => (KIND_DISPATCH, idx, None),
+            Self::Complete { idx } => (KIND_COMPLETE, idx, None),
+            Self::Lost { count } => (KIND_LOST, 0, Some(count)),
+            Self::Event(event) => match event {
+                KernelEvent::TaskRegistered { idx } => (KIND_REGISTERED, idx, None),
+                KernelEvent::DeadlineMiss { idx, .. } => (KIND_DEADLINE_MISS, idx, None),
+                KernelEvent::ScratchOverrun { idx, used } => {
+                    (KIND_SCRATCH_OVERRUN, idx, Some(used))
+                }
+                KernelEvent::TaskFault { idx } => (KIND_FAULT, idx, None),
+                KernelEvent::BudgetExhausted { idx } => (KIND_BUDGET, idx, None),
+                KernelEvent::TaskShed { idx } => (KIND_SHED, idx, None),
+                KernelEvent::TaskRestored { idx } => (KIND_RESTORED, idx, None),
+                KernelEvent::Overcommitted { idx } => (KIND_OVERCOMMIT, idx, None),
+                KernelEvent::TaskCorrupted { idx, restored } => {
+                    (KIND_CORRUPTED, idx, Some(restored as u16))
+                }
+                KernelEvent::ReplicaFault { a, b, timeout } => {
+                    (KIND_REPLICA, a, Some(b as u16 | (timeout as u16) << 8))
+                }
+                KernelEvent::ModeChange { from, to } => (KIND_MODE, from, Some(to as u16)),
+                KernelEvent::JobsLost { idx, count } => (KIND_JOBS_LOST, idx, Some(count)),
+                KernelEvent::LockOverrun { held_us } => {
+                    // 65.5 ms で飽和
+                    let held = if held_us > u16::MAX as u32 {
+                        u16::MAX
+                    } else {
+                        held_us as u16
+                    };
+                    (KIND_LOCK_OVERRUN, 0, Some(held))
+                }
+            },
+        }
+    }
+
+    /// Encode into `out` with `dt_us` since the previous entry
+    ///
+    /// Returns the encoded length (4 or 8); gaps beyond `u32::MAX` µs
+    /// saturate.
+    pub fn encode(&self, dt_us: u64, out: &mut [u8; MAX_ENTRY_BYTES]) -> usize {
+        let (kind, task, arg) = self.fields();
+        match arg {
+            None if dt_us <= u16::MAX as u64 => {
+                out[0] = kind;
+                out[1] = task;
+                out[2..4].copy_from_slice(&(dt_us as u16).to_le_bytes());
+                4
+            }
+            _ => {
+                out[0] = kind | LONG;
+                out[1] = task;
+                out[2..4].copy_from_slice(&arg.unwrap_or(0).to_le_bytes());
+                out[4..8].copy_from_slice(&(dt_us.min(u32::MAX as u64) as u32).to_le_bytes());
+                8
+            }
+        }
+    }
+}
+
+/// Host-side decoder of a trace byte stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceDecoder {
+    /// Timestamp of the last decoded entry (µs)
+    now_us: u64,
+}
+
+impl TraceDecoder {
+    /// Decoder at time 0 (the start of a stream)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { now_us: 0 }
+    }
+
+    /// Decode the entry at the start of `bytes`
+    ///
+    /// Returns the entry, its absolute time (µs) and its encoded length.
+    /// `None` if `bytes` holds less than a whole entry or an unknown kind.
+    pub fn next(&mut self, bytes: &[u8]) -> Option<(TraceEntry, u64, usize)> {
+        let (&kind, _) = bytes.split_first()?;
+        let (len, arg, dt) = if kind & LONG == 0 {
+            let b = bytes.get(..4)?;
+            (4, 0, u16::from_le_bytes([b[2], b[3]]) as u64)
+        } else {
+            let b = bytes.get(..8)?;
+            let arg = u16::from_le_bytes([b[2], b[3]]);
+            (8, arg, u32::from_le_bytes([b[4], b[5], b[6], b[7]]) as u64)
+        };
+        let idx = bytes[1];
+        let at_us = self.now_us + dt;
+        let entry = match kind & !LONG {
+            KIND_DISPATCH => TraceEntry::Dispatch { idx },
+            KIND_COMPLETE => TraceEntry::Complete { idx },
+            KIND_LOST => TraceEntry::Lost { count: arg },
+            KIND_REGISTERED => KernelEvent::TaskRegistered { idx }.into(),
+            KIND_DEADLINE_MISS => KernelEvent::DeadlineMiss { idx, at_us }.into(),
+            KIND_SCRATCH_OVERRUN => KernelEvent::ScratchOverrun { idx, used: arg }.into(),
+            KIND_FAULT => KernelEvent::TaskFault { idx }.into(),
+            KIND_BUDGET => KernelEvent::BudgetExhausted { idx }.into(),
+            KIND_SHED => KernelEvent::TaskShed { idx }.into(),
+            KIND_RESTORED => KernelEvent::TaskRestored { idx }.into(),
+            KIND_OVERCOMMIT => KernelEvent::Overcommitted { idx }.into(),
+            KIND_REPLICA => KernelEvent::ReplicaFault {
+                a: idx,
+                b: arg as u8,
+                timeout: arg >> 8 != 0,
+            }
+            .into(),
+            KIND_CORRUPTED => KernelEvent::TaskCorrupted {
+                idx,
+                restored: arg != 0,
+            }
+            .into(),
+            KIND_MODE => KernelEvent::ModeChange {
+                from: idx,
+                to: arg as u8,
+            }
+            .into(),
+            KIND_JOBS_LOST => KernelEvent::JobsLost { idx, count: arg }.into(),
+            KIND_LOCK_OVERRUN => KernelEvent::LockOverrun {
+                held_us: u32::from(arg),
+            }
+            .into(),
+            _ => return None,
+        };
+        self.now_us = at_us;
+        Some((entry, at_us, len))
+    }
+}
+
+/// `N`-byte trace queue drained into a byte sink
+///
+/// One context records and one drains at a time; a second context
+/// arriving while the first is busy drops its entry (recording) or
+/// returns 0 (draining) instead of waiting.
+pub struct TraceBuffer<const N: usize> {
+    /// Queued bytes
+    buf: UnsafeCell<[u8; N]>,
+    /// Total bytes written (wrapping)
+    head: AtomicUsize,
+    /// Total bytes read (wrapping)
+    tail: AtomicUsize,
+    /// Timestamp of the last recorded entry (µs, owned by the recorder)
+    last_us: UnsafeCell<u64>,
+    /// A context is recording
+    writing: AtomicBool,
+    /// A context is draining
+    draining: AtomicBool,
+    /// Drops not yet reported by a `Lost` entry
+    unreported: AtomicU32,
+    /// Entries dropped in total
+    dropped: AtomicU32,
+    /// Byte output
+    sink: fn(&[u8]),
+    /// Bytes passed to the sink per `drain` (0 = all queued)
+    bytes_per_drain: usize,
+}
+
+// SAFETY: `buf[head..]` and `last_us` are only written by the context that
+// won `writing`, `buf[tail..head]` only read by the context that won
+// `draining`; head / tail publish the bytes with Release / Acquire.
+unsafe impl<const N: usize> Sync for TraceBuffer<N> {}
+
+impl<const N: usize> TraceBuffer<N> {
+    /// Empty queue draining into `sink` (usable in `static`s)
+    #[must_use]
+    pub const fn new(sink: fn(&[u8])) -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            last_us: UnsafeCell::new(0),
+            writing: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            unreported: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+            sink,
+            bytes_per_drain: 0,
+        }
+    }
+
+    /// Pass at most `bytes` to the sink per [`drain`](Self::drain)
+    ///
+    /// Bounds the drain task's WCET and the sink's bandwidth.
+    #[must_use]
+    pub const fn with_bytes_per_drain(mut self, bytes: usize) -> Self {
+        self.bytes_per_drain = bytes;
+        self
+    }
+
+    /// Record `entry` at kernel time `at_us`; false if it was dropped
+    pub fn push(&self, entry: TraceEntry, at_us: u64) -> bool {
+        if self
+            .writing
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.drop_entry();
+            return false;
+        }
+        let head = self.head.load(Ordering::Relaxed);
+        let free = N - head.wrapping_sub(self.tail.load(Ordering::Acquire));
+        // SAFETY: `writing` を取得したので last_us は排他
+        let last_us = unsafe { &mut *self.last_us.get() };
+        let dt = at_us.saturating_sub(*last_us);
+
+        let lost = self.unreported.load(Ordering::Relaxed);
+        let mut marker = [0u8; MAX_ENTRY_BYTES];
+        let mut encoded = [0u8; MAX_ENTRY_BYTES];
+        let (marker_len, n) = if lost > 0 {
+            // 欠落数を先に通知し、本体は同時刻 (dt = 0)
+            let count = lost.min(u16::MAX as u32) as u16;
+            let m = TraceEntry::Lost { count }.encode(dt, &mut marker);
+            (m, entry.encode(0, &mut encoded))
+        } else {
+            (0, entry.encode(dt, &mut encoded))
+        };
+
+        let stored = marker_len + n <= free;
+        if stored {
+            // SAFETY: head 以降の空き領域に書くのはこのコンテキストのみ
+            let buf = unsafe { &mut *self.buf.get() };
+            let bytes = marker[..marker_len].iter().chain(&encoded[..n]);
+            for (k, &b) in bytes.enumerate() {
+                buf[head.wrapping_add(k) % N] = b;
+            }
+            self.head
+                .store(head.wrapping_add(marker_len + n), Ordering::Release);
+            self.unreported.fetch_sub(lost, Ordering::Relaxed);
+            *last_us = at_us;
+        }
+        self.writing.store(false, Ordering::Release);
+        if !stored {
+            self.drop_entry();
+        }
+        stored
+    }
+
+    /// Count one dropped entry
+    fn drop_entry(&self) {
+        self.unreported.fetch_add(1, Ordering::Relaxed);
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Run `kernel.tick(delta_us)` and record what happened
+    pub fn tick(&self, kernel: &mut Kernel, delta_us: u64) -> Option<usize> {
+        self.record(kernel, |k| k.tick(delta_us))
+    }
+
+    /// Run `step` (one kernel tick) and record its dispatch and any
+    /// deadline misses it detected
+    pub fn record<F>(&self, kernel: &mut Kernel, step: F) -> Option<usize>
+    where
+        F: FnOnce(&mut Kernel) -> Option<usize>,
+    {
+        let mut misses = [0u32; MAX_TASKS];
+        for (idx, count) in misses.iter_mut().enumerate() {
+            *count = kernel
+                .scheduler
+                .get_task(idx)
+                .map_or(0, |t| t.deadline_misses);
+        }
+
+        let executed = step(kernel);
+        let now = kernel.timer.now_us();
+
+        for (idx, &before) in misses.iter().enumerate() {
+            let after = kernel
+                .scheduler
+                .get_task(idx)
+                .map_or(0, |t| t.deadline_misses);
+            for _ in before..after {
+                let idx = idx as u8;
+                self.push(KernelEvent::DeadlineMiss { idx, at_us: now }.into(), now);
+            }
+        }
+        if let Some(idx) = executed {
+            self.push(TraceEntry::Dispatch { idx: idx as u8 }, now);
+        }
+        executed
+    }
+
+    /// Pass queued bytes to the sink; returns the number passed
+    ///
+    /// Call from a low-priority task or the idle loop. The queue is handed
+    /// over in at most two contiguous slices (before and after the wrap).
+    pub fn drain(&self) -> usize {
+        if self
+            .draining
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return 0;
+        }
+        let tail = self.tail.load(Ordering::Relaxed);
+        let queued = self.head.load(Ordering::Acquire).wrapping_sub(tail);
+        let total = if self.bytes_per_drain == 0 {
+            queued
+        } else {
+            queued.min(self.bytes_per_drain)
+        };
+        let start = tail % N;
+        let first = total.min(N - start);
+        // SAFETY: tail..head は書き込み済みで、書き手は触らない
+        let buf = unsafe { &*self.buf.get() };
+        if first > 0 {
+            (self.sink)(&buf[start..start + first]);
+        }
+        if total > first {
+            (self.sink)(&buf[..total - first]);
+        }
+        self.tail.store(tail.wrapping_add(total), Ordering::Release);
+        self.draining.store(false, Ordering::Release);
+        total
+    }
+
+    /// Bytes waiting for the next drain
+    pub fn pending(&self) -> usize {
+        self.head
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.tail.load(Ordering::Acquire))
+    }
+
+    /// Entries dropped because the queue was full or busy
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::task::TaskPriority;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    /// Decode a whole stream
+    fn decode_all(mut bytes: &[u8]) -> Vec<(TraceEntry, u64)> {
+        let mut decoder = TraceDecoder::new();
+        let mut out = Vec::new();
+        while let Some((entry, at, len)) = decoder.next(bytes) {
+            out.push((entry, at));
+            bytes = &bytes[len..];
+        }
+        assert!(bytes.is_empty());
+        out
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let entries = [
+            (TraceEntry::Dispatch { idx: 3 }, 100, 4),
+            (TraceEntry::Complete { idx: 3 }, 123, 4),
+            (
+                KernelEvent::ScratchOverrun { idx: 2, used: 900 }.into(),
+                123,
+                8,
+            ),
+            (KernelEvent::ModeChange { from: 1, to: 2 }.into(), 500, 8),
+            (KernelEvent::LockOverrun { held_us: 700 }.into(), 500, 8),
+            (KernelEvent::JobsLost { idx: 6, count: 3 }.into(), 500, 8),
+            (KernelEvent::Overcommitted { idx: 4 }.into(), 500, 4),
+            (
+                KernelEvent::TaskCorrupted {
+                    idx: 5,
+                    restored: true,
+                }
+                .into(),
+                510,
+                8,
+            ),
+            (
+                KernelEvent::ReplicaFault {
+                    a: 1,
+                    b: 2,
+                    timeout: true,
+                }
+                .into(),
+                520,
+                8,
+            ),
+            // 65 ms を超える間隔は長形式
+            (TraceEntry::Dispatch { idx: 0 }, 100_500, 8),
+            (
+                KernelEvent::DeadlineMiss {
+                    idx: 0,
+                    at_us: 100_600,
+                }
+                .into(),
+                100_600,
+                4,
+            ),
+            (TraceEntry::Lost { count: 7 }, 100_600, 8),
+        ];
+        let mut stream = Vec::new();
+        let mut last = 0;
+        for (entry, at, len) in entries {
+            let mut out = [0u8; MAX_ENTRY_BYTES];
+            assert_eq!(entry.encode(at - last, &mut out), len);
+            stream.extend_from_slice(&out[..len]);
+            last = at;
+        }
+        let decoded = decode_all(&stream);
+        let expected: Vec<_> = entries.iter().map(|&(e, at, _)| (e, at)).collect();
+        assert_eq!(decoded, expected);
+
+        // 途中で切れたエントリや未知の種別はデコードしない
+        assert!(TraceDecoder::new().next(&stream[..3]).is_none());
+        assert!(TraceDecoder::new().next(&[0x42, 0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_full_buffer_drops_and_reports_loss() {
+        static OUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+        fn sink(bytes: &[u8]) {
+            OUT.lock().unwrap().extend_from_slice(bytes);
+        }
+        let trace: TraceBuffer<16> = TraceBuffer::new(sink);
+        for (k, at) in [10, 20, 30, 40].into_iter().enumerate() {
+            assert!(trace.push(TraceEntry::Dispatch { idx: k as u8 }, at));
+        }
+        // 満杯: ブロックせずに捨てて数える
+        assert!(!trace.push(TraceEntry::Dispatch { idx: 9 }, 50));
+        assert!(!trace.push(TraceEntry::Dispatch { idx: 9 }, 60));
+        assert_eq!(trace.dropped(), 2);
+        assert_eq!(trace.drain(), 16);
+        assert!(trace.push(TraceEntry::Complete { idx: 3 }, 70));
+        assert_eq!(trace.drain(), 12);
+
+        let decoded = decode_all(&OUT.lock().unwrap());
+        assert_eq!(decoded.len(), 6);
+        assert_eq!(decoded[3], (TraceEntry::Dispatch { idx: 3 }, 40));
+        assert_eq!(decoded[4], (TraceEntry::Lost { count: 2 }, 70));
+        assert_eq!(decoded[5], (TraceEntry::Complete { idx: 3 }, 70));
+    }
+
+    #[test]
+    fn test_drain_wraps_and_limits() {
+        static OUT: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+        fn sink(bytes: &[u8]) {
+            OUT.lock().unwrap().push(bytes.len());
+        }
+        let trace: TraceBuffer<12> = TraceBuffer::new(sink).with_bytes_per_drain(8);
+        for at in [1, 2, 3] {
+            trace.push(TraceEntry::Dispatch { idx: 0 }, at);
+        }
+        assert_eq!(trace.drain(), 8);
+        assert_eq!(trace.pending(), 4);
+        trace.push(TraceEntry::Dispatch { idx: 1 }, 4);
+        // 末尾をまたぐ 8 バイトは 2 スライスで渡す
+        assert_eq!(trace.drain(), 8);
+        assert_eq!(trace.drain(), 0);
+        assert_eq!(*OUT.lock().unwrap(), [8, 4, 4]);
+    }
+
+    #[test]
+    fn test_kernel_trace_stream() {
+        static STREAM: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+        fn sink(bytes: &[u8]) {
+            STREAM.lock().unwrap().extend_from_slice(bytes);
+        }
+        fn noop(_: &mut [u8]) {}
+        static TRACE: TraceBuffer<64> = TraceBuffer::new(sink);
+
+        let mut kernel = Kernel::testing();
+        kernel.add_task(b"fast", noop, TaskPriority::HIGH, 1_000, 100);
+        kernel.add_task(b"slow", noop, TaskPriority::LOW, 1_000, 100);
+        TRACE.tick(&mut kernel, 0);
+        TRACE.tick(&mut kernel, 10);
+        TRACE.tick(&mut kernel, 990);
+        TRACE.drain();
+
+        let decoded = decode_all(&STREAM.lock().unwrap());
+        assert_eq!(
+            decoded,
+            [
+                (TraceEntry::Dispatch { idx: 0 }, 0),
+                (TraceEntry::Dispatch { idx: 1 }, 10),
+                (TraceEntry::Dispatch { idx: 0 }, 1_000),
+            ]
+        );
+        assert_eq!(TRACE.dropped(), 0);
+    }
+}
@@ -0,0 +1,318 @@
+//! Hierarchical timing wheel
+//!
+//! A static, allocation-free wheel of up to [`MAX_WHEEL_ENTRIES`] deadlines
+//! (software timers, task releases) keyed by a small id. Each of the
+//! [`WHEEL_LEVELS`] levels has [`WHEEL_SLOTS`] buckets; a bucket is a bit
+//! mask of ids, like a [`TaskMask`]. An entry sits at the level of the
+//! highest time digit in which its expiry differs from the wheel's time,
+//! and moves down one or more levels when the wheel reaches its bucket.
+//!
+//! Insert and remove are O(1); [`advance`](TimerWheel::advance) visits at
+//! most one rotation of buckets per level, however far time jumps (the
+//! tickless case), and each entry cascades at most `WHEEL_LEVELS` times —
+//! O(1) amortized per expiry instead of a scan of every deadline per tick.
+//! [`next_expiry_us`](TimerWheel::next_expiry_us) only looks at the lowest
+//! occupied level.
+//!
+//! ```rust
+//! use alice_rtos::wheel::TimerWheel;
+//!
+//! let mut wheel = TimerWheel::new(1);
+//! wheel.insert(3, 1_500);
+//! wheel.insert(5, 70_000);
+//! assert_eq!(wheel.next_expiry_us(), Some(1_500));
+//! assert_eq!(wheel.advance(1_000), 0);
+//! assert_eq!(wheel.advance(2_000), 1 << 3);
+//! // A far expiry comes out of a single jump too
+//! assert_eq!(wheel.advance(1_000_000), 1 << 5);
+//! assert!(wheel.is_empty());
+//! ```
+//!
+//! Author: Moroya Sakamoto
+
+use crate::task::TaskMask;
+
+/// Bits of time per level
+const WHEEL_BITS: u32 = 4;
+
+/// Buckets per level
+pub const WHEEL_SLOTS: usize = 1 << WHEEL_BITS;
+
+/// Levels (range before an entry is parked: `WHEEL_SLOTS^WHEEL_LEVELS` units)
+pub const WHEEL_LEVELS: usize = 6;
+
+/// Entries per wheel (one bit each in a bucket)
+pub const MAX_WHEEL_ENTRIES: usize = TaskMask::BITS as usize;
+
+/// `TimerWheel::slot` marker: not in a bucket (absent or already due)
+const NO_SLOT: u8 = u8::MAX;
+
+/// Static hierarchical timing wheel
+#[derive(Debug, Clone)]
+pub struct TimerWheel {
+    /// Entries per bucket, per level
+    buckets: [[TaskMask; WHEEL_SLOTS]; WHEEL_LEVELS],
+    /// Bucket of each entry (`level * WHEEL_SLOTS + bucket`, `NO_SLOT` = none)
+    slot: [u8; MAX_WHEEL_ENTRIES],
+    /// Expiry of each entry (units)
+    expires: [u64; MAX_WHEEL_ENTRIES],
+    /// Entries in the wheel
+    pending: TaskMask,
+    /// Entries due, returned by the next `advance`
+    expired: TaskMask,
+    /// Wheel time (units)
+    now: u64,
+    /// Length of one unit (µs)
+    resolution_us: u32,
+}
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl TimerWheel {
+    /// Empty wheel at time 0 counting in `resolution_us` units (min 1)
+    ///
+    /// Expiries are rounded up to a unit, so an entry is never reported
+    /// early. Entries further than `WHEEL_SLOTS^WHEEL_LEVELS` units ahead
+    /// (16.7 s at 1 µs) wait in the top level and are placed again each
+    /// time it turns.
+    #[must_use]
+    pub const fn new(resolution_us: u32) -> Self {
+        Self {
+            buckets: [[0; WHEEL_SLOTS]; WHEEL_LEVELS],
+            slot: [NO_SLOT; MAX_WHEEL_ENTRIES],
+            expires: [0; MAX_WHEEL_ENTRIES],
+            pending: 0,
+            expired: 0,
+            now: 0,
+            resolution_us: if resolution_us == 0 { 1 } else { resolution_us },
+        }
+    }
+
+    /// Schedule `id` at `at_us`, replacing its previous expiry
+    ///
+    /// An expiry at or before the wheel's time is reported by the next
+    /// [`advance`](Self::advance). Returns false for an id out of range.
+    pub fn insert(&mut self, id: usize, at_us: u64) -> bool {
+        if id >= MAX_WHEEL_ENTRIES {
+            return false;
+        }
+        self.remove(id);
+        self.expires[id] = at_us.div_ceil(u64::from(self.resolution_us));
+        self.pending |= 1 << id;
+        self.place(id);
+        true
+    }
+
+    /// Take `id` out of the wheel (false if it was not in it)
+    pub fn remove(&mut self, id: usize) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        let bit: TaskMask = 1 << id;
+        let slot = self.slot[id];
+        if slot != NO_SLOT {
+            let (level, bucket) = (slot as usize / WHEEL_SLOTS, slot as usize % WHEEL_SLOTS);
+            self.buckets[level][bucket] &= !bit;
+            self.slot[id] = NO_SLOT;
+        }
+        self.pending &= !bit;
+        self.expired &= !bit;
+        true
+    }
+
+    /// Is `id` scheduled (or due and not yet reported)?
+    #[must_use]
+    pub fn contains(&self, id: usize) -> bool {
+        id < MAX_WHEEL_ENTRIES && self.pending & (1 << id) != 0
+    }
+
+    /// No entries scheduled
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.pending == 0
+    }
+
+    /// Expiry of `id` (µs, rounded up to the resolution)
+    #[must_use]
+    pub fn expiry_us(&self, id: usize) -> Option<u64> {
+        self.contains(id)
+            .then(|| self.expires[id] * u64::from(self.resolution_us))
+    }
+
+    /// Move the wheel to `now_us` and take the entries due by then
+    ///
+    /// Returns them as a mask (bit i = id i); they leave the wheel. Time
+    /// never goes back: an earlier `now_us` only reports entries already
+    /// due.
+    pub fn advance(&mut self, now_us: u64) -> TaskMask {
+        let to = now_us / u64::from(self.resolution_us);
+        if to > self.now {
+            let mut pulled: TaskMask = 0;
+            for (level, buckets) in self.buckets.iter_mut().enumerate() {
+                let shift = level as u32 * WHEEL_BITS;
+                let (from, until) = (self.now >> shift, to >> shift);
+                if from == until {
+                    // Higher digits did not move either
+                    break;
+                }
+                let steps = (until - from).min(WHEEL_SLOTS as u64);
+                for step in 1..=steps {
+                    let bucket = &mut buckets[((from + step) % WHEEL_SLOTS as u64) as usize];
+                    pulled |= *bucket;
+                    *bucket = 0;
+                }
+            }
+            self.now = to;
+            // Due ones are reported, the rest cascade to a lower level
+            let mut bits = pulled;
+            while bits != 0 {
+                let id = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                self.slot[id] = NO_SLOT;
+                self.place(id);
+            }
+        }
+        let due = self.expired;
+        self.expired = 0;
+        self.pending &= !due;
+        due
+    }
+
+    /// Earliest expiry in the wheel (µs)
+    ///
+    /// Entries of a lower level all expire before those of a higher one,
+    /// so only the lowest occupied level is searched.
+    #[must_use]
+    pub fn next_expiry_us(&self) -> Option<u64> {
+        let mut ids = self.expired;
+        if ids == 0 {
+            ids = self
+                .buckets
+                .iter()
+                .map(|level| level.iter().fold(0, |mask, bucket| mask | bucket))
+                .find(|&mask| mask != 0)?;
+        }
+        let mut earliest = u64::MAX;
+        while ids != 0 {
+            let id = ids.trailing_zeros() as usize;
+            ids &= ids - 1;
+            earliest = earliest.min(self.expires[id]);
+        }
+        Some(earliest * u64::from(self.resolution_us))
+    }
+
+    /// Put `id` in the bucket its expiry falls in, or mark it due
+    fn place(&mut self, id: usize) {
+        let at = self.expires[id];
+        if at <= self.now {
+            self.expired |= 1 << id;
+            return;
+        }
+        // Level of the highest time digit that differs from now
+        let level = ((u64::BITS - 1 - (at ^ self.now).leading_zeros()) / WHEEL_BITS) as usize;
+        let (level, digit) = if level < WHEEL_LEVELS {
+            (level, at >> (level as u32 * WHEEL_BITS))
+        } else {
+            // Beyond the top level: park in the top bucket of its expiry if
+            // this turn reaches it, in the last bucket of the turn otherwise
+            let shift = (WHEEL_LEVELS as u32 - 1) * WHEEL_BITS;
+            let last = (self.now >> shift) + WHEEL_SLOTS as u64 - 1;
+            (WHEEL_LEVELS - 1, (at >> shift).min(last))
+        };
+        let bucket = (digit % WHEEL_SLOTS as u64) as usize;
+        self.buckets[level][bucket] |= 1 << id;
+        self.slot[id] = (level * WHEEL_SLOTS + bucket) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_at_expiry_not_before() {
+        let mut w = TimerWheel::new(1);
+        assert!(w.insert(0, 17));
+        assert!(w.insert(1, 300));
+        assert!(!w.insert(MAX_WHEEL_ENTRIES, 10));
+        assert_eq!(w.advance(16), 0);
+        assert_eq!(w.advance(17), 1 << 0);
+        assert_eq!(w.next_expiry_us(), Some(300));
+        assert_eq!(w.advance(299), 0);
+        assert_eq!(w.advance(300), 1 << 1);
+        assert_eq!(w.advance(10_000), 0);
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_remove_reinsert_and_past_expiry() {
+        let mut w = TimerWheel::new(1);
+        w.insert(2, 5_000);
+        assert!(w.remove(2));
+        assert!(!w.remove(2));
+        assert_eq!(w.advance(6_000), 0);
+        // 過去の期限は次の advance で即時
+        w.insert(2, 100);
+        assert_eq!(w.expiry_us(2), Some(100));
+        assert_eq!(w.next_expiry_us(), Some(100));
+        assert_eq!(w.advance(6_000), 1 << 2);
+        // 再挿入は前の期限を置き換える
+        w.insert(4, 7_000);
+        w.insert(4, 9_000);
+        assert_eq!(w.advance(8_000), 0);
+        assert_eq!(w.advance(9_000), 1 << 4);
+        assert_eq!(w.expiry_us(4), None);
+    }
+
+    #[test]
+    fn test_resolution_rounds_up() {
+        let mut w = TimerWheel::new(100);
+        w.insert(0, 250);
+        assert_eq!(w.expiry_us(0), Some(300));
+        assert_eq!(w.advance(299), 0);
+        assert_eq!(w.advance(300), 1);
+        assert_eq!(TimerWheel::new(0).resolution_us, 1);
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        // 素朴な線形探索と同じ結果になること (期限の遠い項目と大きな跳躍を含む)
+        let mut w = TimerWheel::new(1);
+        let mut expect = [None::<u64>; MAX_WHEEL_ENTRIES];
+        let mut seed = 0x2545_f491_u64;
+        let mut now = 0u64;
+        for _ in 0..20_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let id = (seed % MAX_WHEEL_ENTRIES as u64) as usize;
+            match seed >> 60 {
+                0 => {
+                    w.remove(id);
+                    expect[id] = None;
+                }
+                1..=5 => {
+                    let at = now + (seed >> 20) % (1 << ((seed >> 8) % 30));
+                    w.insert(id, at);
+                    expect[id] = Some(at);
+                }
+                _ => {
+                    now += (seed >> 24) % (1 << ((seed >> 4) % 22));
+                    let due = w.advance(now);
+                    for (i, e) in expect.iter_mut().enumerate() {
+                        let is_due = e.is_some_and(|at| at <= now);
+                        assert_eq!(due & (1 << i) != 0, is_due, "id {i} at {now}");
+                        if is_due {
+                            *e = None;
+                        }
+                    }
+                }
+            }
+            assert_eq!(w.next_expiry_us(), expect.iter().flatten().min().copied());
+        }
+    }
+}